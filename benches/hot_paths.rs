@@ -0,0 +1,300 @@
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use core_rust_qti::{
+    core::{
+        security::{decode_token, encode_token, get_user_from_token, jwt_keys, Claims},
+        session::add_session_with_ttl,
+        session_store::create_redis_pool,
+        sqlx_utils::Sort,
+        test_utils::generate_test_user,
+    },
+    factory::{
+        permission::PermissionFactory, permission_attribute::PermissionAttributeFactory,
+        role::RoleFactory, user::UserFactory,
+    },
+    init_openapi_route,
+    repository::{role::paginate_role, user_permission::user_has_permission},
+    settings::{get_config, Config},
+    AppState,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use poem::{
+    http::{StatusCode, Uri},
+    Endpoint, Request,
+};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use tokio::{runtime::Runtime, task::JoinSet};
+
+/// Roles seeded for paginate, enough for a few hundred pages
+const ROLE_ROWS: usize = 2000;
+/// Concurrent requests per iteration of the load profile
+const LOAD_CONCURRENCY: usize = 64;
+
+struct Services {
+    config: Config,
+    db: PgPool,
+    redis_conn: deadpool_redis::Pool,
+}
+
+/// Postgres and redis from config, None when either is unreachable so the pure
+/// benchmarks still run without services
+fn connect(rt: &Runtime) -> Option<Services> {
+    let config = get_config();
+    let db = rt.block_on(async {
+        PgPoolOptions::new()
+            .max_connections(LOAD_CONCURRENCY as u32)
+            .acquire_timeout(Duration::from_secs(3))
+            .connect(&config.database_url)
+            .await
+    });
+    // the pool connects lazily, take a connection to know redis is reachable
+    let redis_conn = rt.block_on(async {
+        let mut redis_config = config.clone();
+        redis_config.redis_pool_timeout = Some(3);
+        let pool = create_redis_pool(&redis_config)?;
+        pool.get().await?;
+        anyhow::Ok(pool)
+    });
+    match (db, redis_conn) {
+        (Ok(db), Ok(redis_conn)) => Some(Services {
+            config,
+            db,
+            redis_conn,
+        }),
+        (db, redis_conn) => {
+            eprintln!(
+                "skip database benchmarks, postgres: {:?}, redis: {:?}",
+                db.err(),
+                redis_conn.err()
+            );
+            None
+        }
+    }
+}
+
+fn bench_token(c: &mut Criterion, rt: &Runtime, services: Option<&Services>) {
+    let config = get_config();
+    let claims = Claims::new(&uuid::Uuid::now_v7().to_string(), "bench", config.clone());
+    let keys = jwt_keys();
+    let token = encode_token(&claims, keys).unwrap();
+    let mut group = c.benchmark_group("token");
+    group.bench_function("encode", |b| {
+        b.iter(|| encode_token(black_box(&claims), keys).unwrap())
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| decode_token(black_box(&token), keys).unwrap())
+    });
+
+    // session lookup in redis and user fetch, what every authorized handler does first
+    if let Some(services) = services {
+        let token = &token;
+        group.bench_function("get_user_from_token", |b| {
+            b.to_async(rt).iter_custom(|iters| async move {
+                let mut tx = services.db.begin().await.unwrap();
+                let mut redis_conn = services.redis_conn.get().await.unwrap();
+                let user = UserFactory::new().create(&mut tx).await.unwrap();
+                add_session_with_ttl(
+                    &mut redis_conn,
+                    &user,
+                    token.clone(),
+                    token.clone(),
+                    60,
+                    None,
+                )
+                .await
+                .unwrap();
+                let start = Instant::now();
+                for _ in 0..iters {
+                    let res = get_user_from_token(&mut tx, &mut redis_conn, Some(token.clone()))
+                        .await
+                        .unwrap();
+                    black_box(res);
+                }
+                let elapsed = start.elapsed();
+                tx.rollback().await.unwrap();
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_permission(c: &mut Criterion, rt: &Runtime, services: &Services) {
+    let mut group = c.benchmark_group("user_has_permission");
+    // direct grant, through a role, and missing which has to check every path
+    for (name, permission_name) in [
+        ("user", "bench_user"),
+        ("role", "bench_role"),
+        ("none", "bench_none"),
+    ] {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.to_async(rt).iter_custom(|iters| async move {
+                let mut tx = services.db.begin().await.unwrap();
+                let attribute = PermissionAttributeFactory::new()
+                    .name("read")
+                    .create(&mut tx)
+                    .await
+                    .unwrap();
+                let user_permission = PermissionFactory::new()
+                    .permission_name("bench_user")
+                    .with_attribute(&attribute)
+                    .create(&mut tx)
+                    .await
+                    .unwrap();
+                let role_permission = PermissionFactory::new()
+                    .permission_name("bench_role")
+                    .with_attribute(&attribute)
+                    .create(&mut tx)
+                    .await
+                    .unwrap();
+                let role = RoleFactory::new()
+                    .with_permission(&role_permission, &attribute)
+                    .create(&mut tx)
+                    .await
+                    .unwrap();
+                let user = UserFactory::new()
+                    .with_role(&role)
+                    .with_permission(&user_permission, &attribute)
+                    .create(&mut tx)
+                    .await
+                    .unwrap();
+                let start = Instant::now();
+                for _ in 0..iters {
+                    let res = user_has_permission(&mut tx, &user.id, permission_name, "read")
+                        .await
+                        .unwrap();
+                    black_box(res);
+                }
+                let elapsed = start.elapsed();
+                tx.rollback().await.unwrap();
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_paginate(c: &mut Criterion, rt: &Runtime, services: &Services) {
+    let mut group = c.benchmark_group("paginate_role");
+    // first page, a deep offset, and a search
+    for (name, page, search) in [
+        ("first_page", 1, None),
+        ("last_page", (ROLE_ROWS / 10) as u32, None),
+        ("search", 1, Some("bench_role")),
+    ] {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.to_async(rt).iter_custom(|iters| async move {
+                let mut tx = services.db.begin().await.unwrap();
+                RoleFactory::new()
+                    .role_name("bench_role")
+                    .create(&mut tx)
+                    .await
+                    .unwrap();
+                for _ in 1..ROLE_ROWS {
+                    RoleFactory::new().create(&mut tx).await.unwrap();
+                }
+                let start = Instant::now();
+                for _ in 0..iters {
+                    let res = paginate_role(
+                        &mut tx,
+                        page,
+                        10,
+                        search.map(str::to_string),
+                        Sort::default(),
+                    )
+                    .await
+                    .unwrap();
+                    black_box(res);
+                }
+                let elapsed = start.elapsed();
+                tx.rollback().await.unwrap();
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Load profile, LOAD_CONCURRENCY authorized paginate requests at once through the whole
+/// route including middlewares. Creates a bench user, run it against a disposable database
+fn bench_load(c: &mut Criterion, rt: &Runtime, services: &Services) {
+    let mut config = services.config.clone();
+    config.prefix = Some("/api".to_string());
+    config.rate_limit_per_minute = Some(0);
+    let app_state = Arc::new(AppState {
+        db: services.db.clone(),
+        redis_conn: services.redis_conn.clone().into(),
+    });
+    let test_user = rt.block_on(async {
+        let mut db = app_state.db.acquire().await.unwrap();
+        let mut redis_conn = app_state.redis_conn.get().await.unwrap();
+        generate_test_user(
+            &mut db,
+            &mut redis_conn,
+            config.clone(),
+            &format!("bench_{}", uuid::Uuid::now_v7()),
+            "password",
+        )
+        .await
+        .unwrap()
+    });
+    let app = Arc::new(init_openapi_route(app_state.clone(), &config));
+    let authorization = format!("Bearer {}", test_user.token);
+
+    let mut group = c.benchmark_group("load");
+    group.throughput(criterion::Throughput::Elements(LOAD_CONCURRENCY as u64));
+    group.sample_size(20);
+    group.bench_function("paginate_role_api", |b| {
+        b.to_async(rt).iter(|| async {
+            let mut requests = JoinSet::new();
+            for _ in 0..LOAD_CONCURRENCY {
+                let app = app.clone();
+                let req = Request::builder()
+                    .uri(Uri::from_static("/api/role/?page=1&page_size=10"))
+                    .header("authorization", authorization.clone())
+                    .finish();
+                requests.spawn(async move { app.call(req).await });
+            }
+            while let Some(res) = requests.join_next().await {
+                let resp = res.unwrap().unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+            }
+        })
+    });
+    group.finish();
+
+    rt.block_on(async {
+        let user_id = test_user.user.id;
+        sqlx::query("DELETE FROM public.user_profile WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&app_state.db)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM public.user WHERE id = $1")
+            .bind(user_id)
+            .execute(&app_state.db)
+            .await
+            .unwrap();
+    });
+}
+
+fn hot_paths(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let services = connect(&rt);
+    bench_token(c, &rt, services.as_ref());
+    let Some(services) = services else {
+        return;
+    };
+    bench_permission(c, &rt, &services);
+    bench_paginate(c, &rt, &services);
+    if env::var("BENCH_LOAD").is_ok_and(|x| x == "1") {
+        bench_load(c, &rt, &services);
+    }
+}
+
+criterion_group!(benches, hot_paths);
+criterion_main!(benches);
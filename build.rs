@@ -0,0 +1,43 @@
+use std::{env, process::Command, time::SystemTime};
+
+// Embed build metadata read by core::build_info
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+
+    // GIT_COMMIT wins for builds without a .git directory, e.g. docker
+    let git_commit = match env::var("GIT_COMMIT") {
+        Ok(val) if !val.is_empty() => val,
+        _ => Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|x| x.status.success())
+            .and_then(|x| String::from_utf8(x.stdout).ok())
+            .map(|x| x.trim().to_string())
+            .unwrap_or("unknown".to_string()),
+    };
+    let build_timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|x| x.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp);
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features.join(","));
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/core.proto");
+        tonic_build::compile_protos("proto/core.proto")
+            .expect("failed to compile proto/core.proto");
+    }
+}
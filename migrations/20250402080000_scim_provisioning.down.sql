@@ -0,0 +1,3 @@
+DROP TABLE public.scim_provisioning_event;
+DROP TABLE public.scim_target_user;
+DROP TABLE public.scim_target;
@@ -0,0 +1,46 @@
+CREATE TABLE public.scim_target (
+	id uuid NOT NULL,
+	"name" varchar NOT NULL,
+	base_url varchar NOT NULL,
+	bearer_token varchar NULL,
+	is_active bool NULL,
+	max_attempts int4 NOT NULL DEFAULT 5,
+	created_by uuid NULL,
+	updated_by uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	deleted_date timestamptz NULL,
+	CONSTRAINT scim_target_pkey PRIMARY KEY (id),
+	CONSTRAINT scim_target_created_by_fkey FOREIGN KEY (created_by) REFERENCES public."user"(id),
+	CONSTRAINT scim_target_updated_by_fkey FOREIGN KEY (updated_by) REFERENCES public."user"(id)
+);
+CREATE INDEX ix_scim_target_id ON public.scim_target USING btree (id);
+CREATE UNIQUE INDEX ix_scim_target_name ON public.scim_target USING btree (name);
+
+CREATE TABLE public.scim_target_user (
+	target_id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	external_id varchar NOT NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT scim_target_user_pkey PRIMARY KEY (target_id, user_id),
+	CONSTRAINT scim_target_user_target_id_fkey FOREIGN KEY (target_id) REFERENCES public.scim_target(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT scim_target_user_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE public.scim_provisioning_event (
+	id uuid NOT NULL,
+	target_id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	event_type varchar NOT NULL,
+	status varchar NOT NULL,
+	attempts int4 NOT NULL DEFAULT 0,
+	last_error varchar NULL,
+	next_attempt_date timestamptz NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT scim_provisioning_event_pkey PRIMARY KEY (id),
+	CONSTRAINT scim_provisioning_event_target_id_fkey FOREIGN KEY (target_id) REFERENCES public.scim_target(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT scim_provisioning_event_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX ix_scim_provisioning_event_status ON public.scim_provisioning_event USING btree (status, next_attempt_date);
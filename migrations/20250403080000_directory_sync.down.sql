@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS public.user_identity;
+DROP TABLE IF EXISTS public.directory_sync_run;
+DROP TABLE IF EXISTS public.directory_source;
@@ -0,0 +1,61 @@
+CREATE TABLE public.directory_source (
+	id uuid NOT NULL,
+	"name" varchar NOT NULL,
+	provider varchar NOT NULL,
+	is_active bool NULL,
+	sync_interval int4 NOT NULL DEFAULT 60,
+	ldap_url varchar NULL,
+	ldap_bind_dn varchar NULL,
+	ldap_bind_password varchar NULL,
+	ldap_base_dn varchar NULL,
+	ldap_user_filter varchar NULL,
+	ldap_group_filter varchar NULL,
+	azure_tenant_id varchar NULL,
+	azure_client_id varchar NULL,
+	azure_client_secret varchar NULL,
+	azure_authority_url varchar NULL,
+	azure_graph_url varchar NULL,
+	last_sync_date timestamptz NULL,
+	created_by uuid NULL,
+	updated_by uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	deleted_date timestamptz NULL,
+	CONSTRAINT directory_source_pkey PRIMARY KEY (id),
+	CONSTRAINT directory_source_created_by_fkey FOREIGN KEY (created_by) REFERENCES public."user"(id),
+	CONSTRAINT directory_source_updated_by_fkey FOREIGN KEY (updated_by) REFERENCES public."user"(id)
+);
+CREATE UNIQUE INDEX ix_directory_source_name ON public.directory_source USING btree ("name");
+
+CREATE TABLE public.directory_sync_run (
+	id uuid NOT NULL,
+	source_id uuid NOT NULL,
+	status varchar NOT NULL,
+	users_created int4 NOT NULL DEFAULT 0,
+	users_updated int4 NOT NULL DEFAULT 0,
+	users_deactivated int4 NOT NULL DEFAULT 0,
+	groups_created int4 NOT NULL DEFAULT 0,
+	memberships_added int4 NOT NULL DEFAULT 0,
+	memberships_removed int4 NOT NULL DEFAULT 0,
+	error varchar NULL,
+	started_date timestamptz NULL,
+	finished_date timestamptz NULL,
+	CONSTRAINT directory_sync_run_pkey PRIMARY KEY (id),
+	CONSTRAINT directory_sync_run_source_id_fkey FOREIGN KEY (source_id) REFERENCES public.directory_source(id) ON DELETE CASCADE
+);
+CREATE INDEX ix_directory_sync_run_source_id ON public.directory_sync_run USING btree (source_id, started_date);
+
+CREATE TABLE public.user_identity (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	provider varchar NOT NULL,
+	subject varchar NOT NULL,
+	directory_source_id uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT user_identity_pkey PRIMARY KEY (id),
+	CONSTRAINT user_identity_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE,
+	CONSTRAINT user_identity_directory_source_id_fkey FOREIGN KEY (directory_source_id) REFERENCES public.directory_source(id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX ix_user_identity_provider_subject ON public.user_identity USING btree (provider, subject);
+CREATE INDEX ix_user_identity_user_id ON public.user_identity USING btree (user_id);
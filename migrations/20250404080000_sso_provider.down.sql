@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS public.sso_jit_rule;
+DROP TABLE IF EXISTS public.sso_provider;
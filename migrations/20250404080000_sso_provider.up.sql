@@ -0,0 +1,38 @@
+CREATE TABLE public.sso_provider (
+	id uuid NOT NULL,
+	"name" varchar NOT NULL,
+	protocol varchar NOT NULL DEFAULT 'oidc',
+	issuer varchar NOT NULL,
+	client_id varchar NOT NULL,
+	jwks_url varchar NOT NULL,
+	username_claim varchar NOT NULL DEFAULT 'preferred_username',
+	email_claim varchar NOT NULL DEFAULT 'email',
+	is_active bool NULL,
+	jit_enabled bool NOT NULL DEFAULT false,
+	created_by uuid NULL,
+	updated_by uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	deleted_date timestamptz NULL,
+	CONSTRAINT sso_provider_pkey PRIMARY KEY (id),
+	CONSTRAINT sso_provider_created_by_fkey FOREIGN KEY (created_by) REFERENCES public."user"(id),
+	CONSTRAINT sso_provider_updated_by_fkey FOREIGN KEY (updated_by) REFERENCES public."user"(id)
+);
+CREATE UNIQUE INDEX ix_sso_provider_name ON public.sso_provider USING btree ("name");
+
+CREATE TABLE public.sso_jit_rule (
+	id uuid NOT NULL,
+	provider_id uuid NOT NULL,
+	claim varchar NULL,
+	value varchar NULL,
+	group_id uuid NULL,
+	role_id uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT sso_jit_rule_pkey PRIMARY KEY (id),
+	CONSTRAINT sso_jit_rule_assignment_check CHECK (group_id IS NOT NULL OR role_id IS NOT NULL),
+	CONSTRAINT sso_jit_rule_provider_id_fkey FOREIGN KEY (provider_id) REFERENCES public.sso_provider(id) ON DELETE CASCADE,
+	CONSTRAINT sso_jit_rule_group_id_fkey FOREIGN KEY (group_id) REFERENCES public."group"(id) ON DELETE CASCADE,
+	CONSTRAINT sso_jit_rule_role_id_fkey FOREIGN KEY (role_id) REFERENCES public."role"(id) ON DELETE CASCADE
+);
+CREATE INDEX ix_sso_jit_rule_provider_id ON public.sso_jit_rule USING btree (provider_id);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS public.sso_role_mapping;
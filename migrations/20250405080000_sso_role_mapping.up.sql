@@ -0,0 +1,16 @@
+CREATE TABLE public.sso_role_mapping (
+	id uuid NOT NULL,
+	provider_id uuid NOT NULL,
+	claim varchar NOT NULL,
+	value varchar NOT NULL,
+	group_id uuid NULL,
+	role_id uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT sso_role_mapping_pkey PRIMARY KEY (id),
+	CONSTRAINT sso_role_mapping_assignment_check CHECK (group_id IS NOT NULL OR role_id IS NOT NULL),
+	CONSTRAINT sso_role_mapping_provider_id_fkey FOREIGN KEY (provider_id) REFERENCES public.sso_provider(id) ON DELETE CASCADE,
+	CONSTRAINT sso_role_mapping_group_id_fkey FOREIGN KEY (group_id) REFERENCES public."group"(id) ON DELETE CASCADE,
+	CONSTRAINT sso_role_mapping_role_id_fkey FOREIGN KEY (role_id) REFERENCES public."role"(id) ON DELETE CASCADE
+);
+CREATE INDEX ix_sso_role_mapping_provider_id ON public.sso_role_mapping USING btree (provider_id);
@@ -0,0 +1,15 @@
+CREATE TABLE public.user_data_export (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	status varchar NOT NULL,
+	payload text NULL,
+	last_error varchar NULL,
+	requested_by uuid NULL,
+	created_date timestamptz NULL,
+	completed_date timestamptz NULL,
+	CONSTRAINT user_data_export_pkey PRIMARY KEY (id),
+	CONSTRAINT user_data_export_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT user_data_export_requested_by_fkey FOREIGN KEY (requested_by) REFERENCES public."user"(id) ON DELETE SET NULL
+);
+CREATE INDEX ix_user_data_export_status ON public.user_data_export USING btree (status, created_date);
+CREATE INDEX ix_user_data_export_user_id ON public.user_data_export USING btree (user_id);
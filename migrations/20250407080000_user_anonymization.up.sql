@@ -0,0 +1,10 @@
+CREATE TABLE public.user_anonymization (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	anonymized_by uuid NULL,
+	created_date timestamptz NULL,
+	CONSTRAINT user_anonymization_pkey PRIMARY KEY (id),
+	CONSTRAINT user_anonymization_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT user_anonymization_anonymized_by_fkey FOREIGN KEY (anonymized_by) REFERENCES public."user"(id) ON DELETE SET NULL
+);
+CREATE UNIQUE INDEX ix_user_anonymization_user_id ON public.user_anonymization USING btree (user_id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS public.user_consent;
+DROP TABLE IF EXISTS public.consent_type;
@@ -0,0 +1,28 @@
+CREATE TABLE public.consent_type (
+	id uuid NOT NULL,
+	"name" varchar NOT NULL,
+	description text NULL,
+	"version" int4 NOT NULL DEFAULT 1,
+	is_active bool NOT NULL DEFAULT true,
+	created_by uuid NULL,
+	updated_by uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT consent_type_pkey PRIMARY KEY (id),
+	CONSTRAINT consent_type_created_by_fkey FOREIGN KEY (created_by) REFERENCES public."user"(id) ON DELETE SET NULL,
+	CONSTRAINT consent_type_updated_by_fkey FOREIGN KEY (updated_by) REFERENCES public."user"(id) ON DELETE SET NULL
+);
+CREATE UNIQUE INDEX ix_consent_type_name ON public.consent_type USING btree ("name");
+
+CREATE TABLE public.user_consent (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	consent_type_id uuid NOT NULL,
+	"version" int4 NOT NULL,
+	accepted bool NOT NULL,
+	created_date timestamptz NULL,
+	CONSTRAINT user_consent_pkey PRIMARY KEY (id),
+	CONSTRAINT user_consent_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT user_consent_consent_type_id_fkey FOREIGN KEY (consent_type_id) REFERENCES public.consent_type(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX ix_user_consent_user_id ON public.user_consent USING btree (user_id, consent_type_id, created_date);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS public.user_terms_acceptance;
+DROP TABLE IF EXISTS public.terms_version;
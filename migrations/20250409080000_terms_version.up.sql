@@ -0,0 +1,24 @@
+CREATE TABLE public.terms_version (
+	id uuid NOT NULL,
+	kind varchar NOT NULL,
+	"version" varchar NOT NULL,
+	url varchar NULL,
+	"content" text NULL,
+	published_by uuid NULL,
+	published_date timestamptz NULL,
+	CONSTRAINT terms_version_pkey PRIMARY KEY (id),
+	CONSTRAINT terms_version_published_by_fkey FOREIGN KEY (published_by) REFERENCES public."user"(id) ON DELETE SET NULL
+);
+CREATE UNIQUE INDEX ix_terms_version_kind_version ON public.terms_version USING btree (kind, "version");
+CREATE INDEX ix_terms_version_published_date ON public.terms_version USING btree (kind, published_date);
+
+CREATE TABLE public.user_terms_acceptance (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	terms_version_id uuid NOT NULL,
+	accepted_date timestamptz NULL,
+	CONSTRAINT user_terms_acceptance_pkey PRIMARY KEY (id),
+	CONSTRAINT user_terms_acceptance_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT user_terms_acceptance_terms_version_id_fkey FOREIGN KEY (terms_version_id) REFERENCES public.terms_version(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE UNIQUE INDEX ix_user_terms_acceptance_user_id ON public.user_terms_acceptance USING btree (user_id, terms_version_id);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS public.user_status_history;
+ALTER TABLE public."user" DROP CONSTRAINT IF EXISTS user_status_check;
+ALTER TABLE public."user" DROP COLUMN IF EXISTS status;
@@ -0,0 +1,20 @@
+ALTER TABLE public."user" ADD COLUMN status varchar NOT NULL DEFAULT 'active';
+UPDATE public."user" SET status = 'suspended' WHERE is_active IS NOT TRUE;
+UPDATE public."user" SET status = 'deprovisioned'
+WHERE id IN (SELECT user_id FROM public.user_anonymization);
+ALTER TABLE public."user" ADD CONSTRAINT user_status_check
+CHECK (status IN ('pending', 'active', 'suspended', 'deprovisioned'));
+
+CREATE TABLE public.user_status_history (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	from_status varchar NOT NULL,
+	to_status varchar NOT NULL,
+	reason text NULL,
+	changed_by uuid NULL,
+	created_date timestamptz NULL,
+	CONSTRAINT user_status_history_pkey PRIMARY KEY (id),
+	CONSTRAINT user_status_history_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT user_status_history_changed_by_fkey FOREIGN KEY (changed_by) REFERENCES public."user"(id) ON DELETE SET NULL
+);
+CREATE INDEX ix_user_status_history_user_id ON public.user_status_history USING btree (user_id, created_date);
@@ -0,0 +1,11 @@
+CREATE TABLE public.user_activity (
+	user_id uuid NOT NULL,
+	last_login_date timestamptz NULL,
+	dormant_warning_date timestamptz NULL,
+	dormant_flagged_date timestamptz NULL,
+	dormant_exempt bool NOT NULL DEFAULT false,
+	dormant_exempt_reason text NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT user_activity_pkey PRIMARY KEY (user_id),
+	CONSTRAINT user_activity_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
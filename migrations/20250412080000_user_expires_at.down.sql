@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS public.ix_user_expires_at;
+ALTER TABLE public."user" DROP COLUMN IF EXISTS expires_at;
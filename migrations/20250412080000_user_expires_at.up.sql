@@ -0,0 +1,2 @@
+ALTER TABLE public."user" ADD COLUMN expires_at timestamptz NULL;
+CREATE INDEX ix_user_expires_at ON public."user" USING btree (expires_at) WHERE expires_at IS NOT NULL;
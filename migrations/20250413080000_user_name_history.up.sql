@@ -0,0 +1,12 @@
+CREATE TABLE public.user_name_history (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	user_name varchar NOT NULL,
+	changed_by uuid NULL,
+	created_date timestamptz NULL,
+	CONSTRAINT user_name_history_pkey PRIMARY KEY (id),
+	CONSTRAINT user_name_history_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE,
+	CONSTRAINT user_name_history_changed_by_fkey FOREIGN KEY (changed_by) REFERENCES public."user"(id) ON DELETE SET NULL
+);
+CREATE INDEX ix_user_name_history_user_id ON public.user_name_history USING btree (user_id, created_date);
+CREATE INDEX ix_user_name_history_user_name ON public.user_name_history USING btree (user_name, created_date);
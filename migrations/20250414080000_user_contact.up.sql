@@ -0,0 +1,15 @@
+CREATE TABLE public.user_contact (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	kind varchar NOT NULL,
+	value varchar NOT NULL,
+	is_primary bool NOT NULL DEFAULT false,
+	verified_date timestamptz NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT user_contact_pkey PRIMARY KEY (id),
+	CONSTRAINT user_contact_kind_check CHECK (kind IN ('email', 'phone')),
+	CONSTRAINT user_contact_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX ix_user_contact_user_id ON public.user_contact USING btree (user_id);
+CREATE UNIQUE INDEX ix_user_contact_primary ON public.user_contact USING btree (user_id, kind) WHERE is_primary;
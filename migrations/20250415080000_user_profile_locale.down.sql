@@ -0,0 +1,2 @@
+ALTER TABLE public.user_profile DROP COLUMN IF EXISTS timezone;
+ALTER TABLE public.user_profile DROP COLUMN IF EXISTS locale;
@@ -0,0 +1,2 @@
+ALTER TABLE public.user_profile ADD COLUMN locale varchar(35) NULL;
+ALTER TABLE public.user_profile ADD COLUMN timezone varchar(64) NULL;
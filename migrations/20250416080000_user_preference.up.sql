@@ -0,0 +1,11 @@
+CREATE TABLE public.user_preference (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	"key" varchar(100) NOT NULL,
+	value jsonb NOT NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT user_preference_pkey PRIMARY KEY (id),
+	CONSTRAINT user_preference_user_id_key_key UNIQUE (user_id, "key"),
+	CONSTRAINT user_preference_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
@@ -0,0 +1,15 @@
+CREATE TABLE public.notification_template (
+	id uuid NOT NULL,
+	"event" varchar NOT NULL,
+	channel varchar NOT NULL,
+	locale varchar(35) NOT NULL,
+	subject varchar NULL,
+	body text NOT NULL,
+	created_by uuid NULL,
+	updated_by uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT notification_template_pkey PRIMARY KEY (id),
+	CONSTRAINT notification_template_channel_check CHECK (channel IN ('email', 'sms')),
+	CONSTRAINT notification_template_event_channel_locale_key UNIQUE ("event", channel, locale)
+);
@@ -0,0 +1,5 @@
+DELETE FROM public.notification_template WHERE channel = 'push';
+ALTER TABLE public.notification_template DROP CONSTRAINT notification_template_channel_check;
+ALTER TABLE public.notification_template ADD CONSTRAINT notification_template_channel_check CHECK (channel IN ('email', 'sms'));
+
+DROP TABLE IF EXISTS public.user_device;
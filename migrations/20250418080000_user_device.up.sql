@@ -0,0 +1,17 @@
+CREATE TABLE public.user_device (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	platform varchar NOT NULL,
+	"token" varchar(4096) NOT NULL,
+	"name" varchar(255) NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	CONSTRAINT user_device_pkey PRIMARY KEY (id),
+	CONSTRAINT user_device_platform_check CHECK (platform IN ('fcm', 'apns')),
+	CONSTRAINT user_device_platform_token_key UNIQUE (platform, "token"),
+	CONSTRAINT user_device_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX user_device_user_id_idx ON public.user_device USING btree (user_id);
+
+ALTER TABLE public.notification_template DROP CONSTRAINT notification_template_channel_check;
+ALTER TABLE public.notification_template ADD CONSTRAINT notification_template_channel_check CHECK (channel IN ('email', 'sms', 'push'));
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS public.ix_permission_attribute_category;
+ALTER TABLE public.permission_attribute DROP COLUMN IF EXISTS category;
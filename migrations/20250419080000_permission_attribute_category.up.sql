@@ -0,0 +1,2 @@
+ALTER TABLE public.permission_attribute ADD COLUMN category varchar(255) NULL;
+CREATE INDEX ix_permission_attribute_category ON public.permission_attribute USING btree (category);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS public.ix_permission_attribute_deleted_date;
+ALTER TABLE public.permission_attribute DROP COLUMN IF EXISTS deleted_date;
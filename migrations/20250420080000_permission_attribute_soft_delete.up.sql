@@ -0,0 +1,2 @@
+ALTER TABLE public.permission_attribute ADD COLUMN deleted_date timestamptz NULL;
+CREATE INDEX ix_permission_attribute_deleted_date ON public.permission_attribute USING btree (deleted_date);
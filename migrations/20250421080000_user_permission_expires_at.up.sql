@@ -0,0 +1,2 @@
+ALTER TABLE public.user_permission ADD COLUMN expires_at timestamptz NULL;
+CREATE INDEX ix_user_permission_expires_at ON public.user_permission USING btree (expires_at);
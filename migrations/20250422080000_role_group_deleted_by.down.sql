@@ -0,0 +1,4 @@
+ALTER TABLE public."group" DROP CONSTRAINT IF EXISTS group_deleted_by_fkey;
+ALTER TABLE public."group" DROP COLUMN IF EXISTS deleted_by;
+ALTER TABLE public.role DROP CONSTRAINT IF EXISTS role_deleted_by_fkey;
+ALTER TABLE public.role DROP COLUMN IF EXISTS deleted_by;
@@ -0,0 +1,4 @@
+ALTER TABLE public.role ADD COLUMN deleted_by uuid NULL;
+ALTER TABLE public.role ADD CONSTRAINT role_deleted_by_fkey FOREIGN KEY (deleted_by) REFERENCES public."user"(id);
+ALTER TABLE public."group" ADD COLUMN deleted_by uuid NULL;
+ALTER TABLE public."group" ADD CONSTRAINT group_deleted_by_fkey FOREIGN KEY (deleted_by) REFERENCES public."user"(id);
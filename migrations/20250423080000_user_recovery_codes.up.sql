@@ -0,0 +1,10 @@
+CREATE TABLE public.user_recovery_codes (
+	id uuid NOT NULL,
+	user_id uuid NOT NULL,
+	code_hash varchar NOT NULL,
+	used_date timestamptz NULL,
+	created_date timestamptz NULL,
+	CONSTRAINT user_recovery_codes_pkey PRIMARY KEY (id),
+	CONSTRAINT user_recovery_codes_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX ix_user_recovery_codes_user_id ON public.user_recovery_codes USING btree (user_id);
@@ -0,0 +1 @@
+ALTER TABLE public.user_profile DROP COLUMN IF EXISTS email_verified_at;
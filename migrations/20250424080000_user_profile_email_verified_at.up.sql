@@ -0,0 +1 @@
+ALTER TABLE public.user_profile ADD COLUMN email_verified_at timestamptz NULL;
@@ -0,0 +1,20 @@
+CREATE TABLE public.service_account (
+	id uuid NOT NULL,
+	"name" varchar NOT NULL,
+	description varchar NULL,
+	user_id uuid NOT NULL,
+	key_prefix varchar NOT NULL,
+	key_hash varchar NOT NULL,
+	expires_at timestamptz NULL,
+	last_used_at timestamptz NULL,
+	created_by uuid NULL,
+	updated_by uuid NULL,
+	created_date timestamptz NULL,
+	updated_date timestamptz NULL,
+	deleted_date timestamptz NULL,
+	CONSTRAINT service_account_pkey PRIMARY KEY (id),
+	CONSTRAINT service_account_name_key UNIQUE ("name"),
+	CONSTRAINT service_account_key_hash_key UNIQUE (key_hash),
+	CONSTRAINT service_account_user_id_fkey FOREIGN KEY (user_id) REFERENCES public."user"(id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX ix_service_account_user_id ON public.service_account USING btree (user_id);
@@ -0,0 +1,7 @@
+ALTER TABLE public.sso_provider DROP COLUMN IF EXISTS scopes;
+ALTER TABLE public.sso_provider DROP COLUMN IF EXISTS userinfo_url;
+ALTER TABLE public.sso_provider DROP COLUMN IF EXISTS token_url;
+ALTER TABLE public.sso_provider DROP COLUMN IF EXISTS authorization_url;
+ALTER TABLE public.sso_provider DROP COLUMN IF EXISTS client_secret;
+UPDATE public.sso_provider SET jwks_url = '' WHERE jwks_url IS NULL;
+ALTER TABLE public.sso_provider ALTER COLUMN jwks_url SET NOT NULL;
@@ -0,0 +1,6 @@
+ALTER TABLE public.sso_provider ALTER COLUMN jwks_url DROP NOT NULL;
+ALTER TABLE public.sso_provider ADD COLUMN client_secret varchar NULL;
+ALTER TABLE public.sso_provider ADD COLUMN authorization_url varchar NULL;
+ALTER TABLE public.sso_provider ADD COLUMN token_url varchar NULL;
+ALTER TABLE public.sso_provider ADD COLUMN userinfo_url varchar NULL;
+ALTER TABLE public.sso_provider ADD COLUMN scopes varchar NULL;
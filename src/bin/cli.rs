@@ -1,15 +1,34 @@
-use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use chrono::Utc;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use core_rust_qti::{
     cli::{
         auth,
+        cache::{flush_cache, DEFAULT_FLUSH_PATTERN},
+        check_config::validate_config,
         db::{db_generate, db_list, db_migrate, db_revert},
+        healthcheck::healthcheck,
+        openapi, permission_sync, rbac, seed,
+    },
+    core::{
+        api_version::latest_api_version,
+        db::{init_pool, plan_migrations, run_migrations, MigrationStep},
+        dormant_account::{run_dormant_account_check, DormantPolicy},
+        pii::{reencrypt_user_contacts, reencrypt_user_profiles, PiiKeys},
+        retention::{parse_retention_period, purge_soft_deleted, PurgeEntity},
+        secrets::{encrypt_secret, generate_master_key, parse_master_key},
+        security::{jwt_algorithm, jwt_key_grace, JwtKey, JwtKeyring},
+        session::get_redis_connection,
+        user_import::{import_users, parse_user_csv, UserImportStatus},
     },
-    core::db::init_pool,
-    settings::get_config,
+    settings::{get_config, try_get_config},
 };
 
+/// Core service management commands
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(name = "cli", version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
     #[command(subcommand)]
@@ -20,8 +39,118 @@ struct Cli {
 enum Commands {
     /// Database related command
     Db(DbArgs),
+    /// Terminal admin console to manage users (build with --features tui)
+    #[cfg(feature = "tui")]
+    Admin,
     /// Authentication related command
     Auth(AuthArgs),
+    /// Backup and restore roles, groups, permissions and attributes between environments
+    Rbac(RbacArgs),
+    /// Load and validate settings without connecting to any service
+    CheckConfig,
+    /// Encrypt secrets stored in the config file
+    Secret(SecretArgs),
+    /// Delete service keys from Redis, login sessions are kept by default
+    FlushCache {
+        #[arg(short, long, default_value = DEFAULT_FLUSH_PATTERN)]
+        pattern: String,
+        /// Also delete login sessions, every user is logged out
+        #[arg(long)]
+        include_sessions: bool,
+    },
+    /// Print shell completion script
+    ///
+    /// e.g. `cli completions bash > /etc/bash_completion.d/cli`
+    Completions {
+        /// bash, zsh, fish, elvish or powershell
+        shell: Shell,
+    },
+    /// Check Postgres and Redis connectivity, exit non-zero on failure
+    Healthcheck,
+    /// Run embedded migrations up or down to a version, latest when omitted
+    Migrate {
+        /// Target migration version, 0 reverts everything
+        #[arg(long)]
+        to: Option<i64>,
+        /// Only print the migrations that would run
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write the OpenAPI spec to a file without starting the server
+    ExportOpenapi {
+        #[arg(short, long, default_value = "openapi.json")]
+        out: String,
+        /// Server url prefix, PREFIX env or "/" when omitted
+        #[arg(short, long)]
+        prefix: Option<String>,
+        /// Api version, latest when omitted
+        #[arg(long)]
+        api_version: Option<String>,
+    },
+    /// Create users from a csv file
+    ///
+    /// Header: user_name,password,email,first_name,last_name,address,is_active,group_roles.
+    /// group_roles is a | separated list of group:role names.
+    ImportUsers {
+        /// Csv file path
+        file: String,
+        /// Validate every row without writing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Sign a token for an existing user without going through the API
+    IssueToken {
+        /// User id or user name
+        #[arg(short, long)]
+        user: String,
+        /// Token lifetime in minutes, JWT_EXP when omitted
+        #[arg(short, long)]
+        ttl: Option<u32>,
+        /// Comma separated scopes written as name.attribute e.g. user.read, the token is
+        /// limited to them
+        #[arg(short, long, value_delimiter = ',')]
+        scopes: Vec<String>,
+    },
+    /// Reconcile permissions, attributes and role grants with a yaml manifest
+    SyncPermissions {
+        /// Yaml manifest path
+        manifest: String,
+        /// Delete rows missing from the manifest
+        #[arg(long)]
+        prune: bool,
+        /// Only print the changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Permanently remove soft deleted rows, same purge as the retention worker
+    Purge {
+        /// Retention period such as 90d, 12h or 2w
+        #[arg(long)]
+        older_than: String,
+        /// user, role or group, every entity when omitted
+        #[arg(long)]
+        entity: Option<String>,
+    },
+    /// Run the dormant account check once, same as the hourly worker
+    DormantAccounts,
+    /// Re-encrypt profile personal data with PII_ENCRYPTION_KEY
+    ///
+    /// Rows written with PII_ENCRYPTION_PREVIOUS_KEYS or before encryption was enabled
+    /// are rewritten, previous keys can be dropped once it succeeds.
+    RotatePiiKey,
+    /// Sign new tokens with a fresh key of JWT_ALGORITHM in JWT_KEYS_FILE
+    ///
+    /// The current key, JWT_SECRET or JWT_PRIVATE_KEY on the first rotation, keeps
+    /// validating tokens and stays on /.well-known/jwks.json for JWT_KEY_GRACE seconds so
+    /// nobody is logged out. Restart the servers to sign with the new key.
+    #[command(alias = "rotate-jwt-key")]
+    RotateKeys,
+    /// Load baseline roles, groups, permissions and permission attributes, safe to re-run
+    Seed {
+        /// Json seed file, built-in defaults are used when omitted
+        #[arg(short, long)]
+        file: Option<String>,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -39,6 +168,57 @@ enum AuthCommands {
         #[arg(short, long)]
         password: String,
     },
+    /// Create active user with the admin role from `cli seed`, password is prompted unless generated
+    CreateSuperuser {
+        #[arg(short, long)]
+        username: String,
+        #[arg(short, long)]
+        email: String,
+        /// Generate a random password and print it instead of prompting
+        #[arg(long)]
+        generate_password: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct RbacArgs {
+    #[command(subcommand)]
+    command: RbacCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum RbacCommands {
+    /// Write every role, group, permission and attribute with their links, users excluded
+    Export {
+        #[arg(short, long, default_value = "rbac.json")]
+        out: String,
+    },
+    /// Restore an export by name, rows missing from the file are left untouched
+    Import {
+        /// Json file written by `cli rbac export`
+        file: String,
+        /// Only print what would be written
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct SecretArgs {
+    #[command(subcommand)]
+    command: SecretCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum SecretCommands {
+    /// Print a new random master key for CONFIG_MASTER_KEY
+    GenerateKey,
+    /// Print the `ENC[...]` form of a value, to paste in the config file
+    Encrypt {
+        /// Plaintext, prompted when omitted so it stays out of shell history
+        #[arg(long)]
+        value: Option<String>,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -50,15 +230,37 @@ struct DbArgs {
 #[derive(Debug, Subcommand)]
 enum DbCommands {
     /// Generate new migration file
-    Generate { migration_name: String },
+    Generate {
+        /// Migration name, used in the file name
+        migration_name: String,
+    },
     /// List all migration
     List,
-    /// Run all pending migration
+    /// Run all pending migration embedded in the binary
     Migrate,
-    /// Revert latest migration
+    /// Revert latest migration embedded in the binary
     Revert,
 }
 
+/// Exit with the error or print the applied and reverted migrations
+fn print_migration_steps(res: anyhow::Result<Vec<MigrationStep>>, dry_run: bool) {
+    let steps = match res {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("migration failed: {err}");
+            std::process::exit(1);
+        }
+    };
+    if steps.is_empty() {
+        println!("database is up to date");
+    }
+    for step in steps {
+        let action = if step.revert { "revert" } else { "apply" };
+        let prefix = if dry_run { "would " } else { "" };
+        println!("{prefix}{action} {} {}", step.version, step.description);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -79,17 +281,27 @@ async fn main() {
                 println!("run all pending migration");
                 let _ = dotenvy::dotenv();
                 let config = get_config();
-                println!("run migration on {}", config.database_url);
-                db_migrate(&config).await;
+                let pool = init_pool(&config).await;
+                print_migration_steps(db_migrate(&pool).await, false);
             }
             DbCommands::Revert => {
                 println!("revert latest migration");
                 let _ = dotenvy::dotenv();
                 let config = get_config();
-                println!("{}", config.database_url);
-                db_revert(&config).await;
+                let pool = init_pool(&config).await;
+                print_migration_steps(db_revert(&pool).await, false);
             }
         },
+        #[cfg(feature = "tui")]
+        Commands::Admin => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            if let Err(err) = core_rust_qti::cli::admin_tui::run_admin_tui(pool).await {
+                eprintln!("admin console failed: {err}");
+                std::process::exit(1);
+            }
+        }
         Commands::Auth(auth_args) => match &auth_args.command {
             AuthCommands::CreateUser { username, password } => {
                 println!("create user: {username:?}");
@@ -98,6 +310,489 @@ async fn main() {
                 let pool = init_pool(&config).await;
                 auth::create_user(&pool, username, password).await.unwrap();
             }
+            AuthCommands::CreateSuperuser {
+                username,
+                email,
+                generate_password,
+            } => {
+                println!("create superuser: {username:?}");
+                let password = if *generate_password {
+                    auth::generate_password(20)
+                } else {
+                    let password = rpassword::prompt_password("Password: ").unwrap();
+                    let confirm = rpassword::prompt_password("Confirm password: ").unwrap();
+                    if password.is_empty() || password != confirm {
+                        eprintln!("password is empty or does not match");
+                        std::process::exit(1);
+                    }
+                    password
+                };
+                let _ = dotenvy::dotenv();
+                let config = get_config();
+                let pool = init_pool(&config).await;
+                match auth::create_superuser(&pool, username, email, &password).await {
+                    Ok(user) => {
+                        println!("superuser {} created with id {}", user.user_name, user.id);
+                        if *generate_password {
+                            println!("generated password: {password}");
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("failed to create superuser: {err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
         },
+        Commands::CheckConfig => {
+            let _ = dotenvy::dotenv();
+            let config = match try_get_config() {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("failed to load config: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let issues = validate_config(&config);
+            if issues.is_empty() {
+                println!("config is valid");
+            }
+            for issue in issues.iter() {
+                eprintln!("{issue}");
+            }
+            if !issues.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "cli", &mut std::io::stdout());
+        }
+        Commands::FlushCache {
+            pattern,
+            include_sessions,
+        } => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let mut redis_conn = get_redis_connection(&config.redis_url).await.unwrap();
+            match flush_cache(&mut redis_conn, pattern, *include_sessions).await {
+                Ok(summary) => println!(
+                    "deleted {} keys matching {pattern}, kept {} sessions",
+                    summary.deleted, summary.sessions_kept
+                ),
+                Err(err) => {
+                    eprintln!("flush cache failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Healthcheck => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let mut healthy = true;
+            for (service, res) in healthcheck(&config).await {
+                match res {
+                    Ok(_) => println!("{service}: ok"),
+                    Err(err) => {
+                        healthy = false;
+                        eprintln!("{service}: {err}");
+                    }
+                }
+            }
+            if !healthy {
+                std::process::exit(1);
+            }
+        }
+        Commands::Migrate { to, dry_run } => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            let res = if *dry_run {
+                plan_migrations(&pool, *to).await
+            } else {
+                run_migrations(&pool, *to).await
+            };
+            print_migration_steps(res, *dry_run);
+        }
+        Commands::ExportOpenapi {
+            out,
+            prefix,
+            api_version,
+        } => {
+            let _ = dotenvy::dotenv();
+            let prefix = match prefix {
+                Some(val) => val.clone(),
+                None => std::env::var("PREFIX").unwrap_or("/".to_string()),
+            };
+            let api_version = api_version
+                .clone()
+                .unwrap_or(latest_api_version().to_string());
+            match openapi::export_openapi_to_file(&prefix, &api_version, out) {
+                Ok(_) => println!("openapi spec written to {out}"),
+                Err(err) => {
+                    eprintln!("failed to export openapi spec: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::ImportUsers { file, dry_run } => {
+            let content = match std::fs::read_to_string(file) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("failed to read {file}: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let rows = match parse_user_csv(&content) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("invalid csv file: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            let reports = match import_users(&pool, rows, *dry_run).await {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("import failed: {err}");
+                    std::process::exit(1);
+                }
+            };
+            for report in reports.iter() {
+                println!("{report}");
+            }
+            let invalid = reports
+                .iter()
+                .filter(|x| matches!(x.status, UserImportStatus::Invalid(_)))
+                .count();
+            println!("{} rows, {} invalid", reports.len(), invalid);
+            if invalid > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::IssueToken { user, ttl, scopes } => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            let mut redis_conn = get_redis_connection(&config.redis_url).await.unwrap();
+            match auth::issue_token(&pool, &mut redis_conn, &config, user, *ttl, scopes.clone())
+                .await
+            {
+                Ok(issued) => {
+                    eprintln!(
+                        "token for {} ({}) expires at {}",
+                        issued.user.user_name, issued.user.id, issued.exp
+                    );
+                    println!("{}", issued.token);
+                }
+                Err(err) => {
+                    eprintln!("failed to issue token: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::SyncPermissions {
+            manifest,
+            prune,
+            dry_run,
+        } => {
+            let content = match std::fs::read_to_string(manifest) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("failed to read {manifest}: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let data = match permission_sync::parse_permission_manifest(&content) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("invalid manifest: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            match permission_sync::sync_permissions(&pool, &data, *prune, *dry_run).await {
+                Ok(changes) => {
+                    if changes.is_empty() {
+                        println!("permissions are in sync");
+                    }
+                    for change in changes {
+                        println!("{change}");
+                    }
+                }
+                Err(err) => {
+                    eprintln!("sync failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Purge { older_than, entity } => {
+            let older_than = match parse_retention_period(older_than) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let entities = match entity {
+                Some(val) => match val.parse::<PurgeEntity>() {
+                    Ok(val) => vec![val],
+                    Err(err) => {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                },
+                None => PurgeEntity::all(),
+            };
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            match purge_soft_deleted(&pool, &entities, older_than, None).await {
+                Ok(summary) => println!("purged {summary}"),
+                Err(err) => {
+                    eprintln!("purge failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::DormantAccounts => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let policy = match DormantPolicy::from_config(&config) {
+                Ok(Some(val)) => val,
+                Ok(None) => {
+                    eprintln!("set DORMANT_ACCOUNT_DAYS");
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let pool = init_pool(&config).await;
+            let mut redis_conn = get_redis_connection(&config.redis_url).await.unwrap();
+            match run_dormant_account_check(&pool, &mut redis_conn, &policy, None).await {
+                Ok(summary) => println!("dormant accounts {summary}"),
+                Err(err) => {
+                    eprintln!("dormant account check failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::RotatePiiKey => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let keys = match PiiKeys::from_config(&config) {
+                Ok(Some(val)) => val,
+                Ok(None) => {
+                    eprintln!("set PII_ENCRYPTION_KEY");
+                    std::process::exit(1);
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let pool = init_pool(&config).await;
+            match reencrypt_user_profiles(&pool, &keys).await {
+                Ok(count) => println!("re-encrypted {count} user profiles"),
+                Err(err) => {
+                    eprintln!("re-encryption failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+            match reencrypt_user_contacts(&pool, &keys).await {
+                Ok(count) => println!("re-encrypted {count} user contacts"),
+                Err(err) => {
+                    eprintln!("re-encryption failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::RotateKeys => {
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let path = match config.jwt_keys_file.as_deref().filter(|x| !x.is_empty()) {
+                Some(val) => PathBuf::from(val),
+                None => {
+                    eprintln!("set JWT_KEYS_FILE");
+                    std::process::exit(1);
+                }
+            };
+            let algorithm = match jwt_algorithm(&config) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let mut keyring = match JwtKeyring::load(&path) {
+                Ok(val) => val.unwrap_or_default(),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let now = Utc::now();
+            // tokens signed before the keyring existed keep validating
+            if keyring.keys.is_empty() {
+                match JwtKey::from_config(&config, now) {
+                    Ok(val) => keyring.keys.push(val),
+                    Err(err) => {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let key = match JwtKey::generate(algorithm, now) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let kid = keyring.rotate(key, jwt_key_grace(&config), now).kid.clone();
+            if let Err(err) = keyring.save(&path) {
+                eprintln!("failed to write {}: {err}", path.display());
+                std::process::exit(1);
+            }
+            println!(
+                "signing with {:?} key {kid}, {} retired keys still validate",
+                algorithm,
+                keyring.keys.len() - 1
+            );
+        }
+        Commands::Secret(secret_args) => match &secret_args.command {
+            SecretCommands::GenerateKey => {
+                println!("{}", generate_master_key());
+            }
+            SecretCommands::Encrypt { value } => {
+                let _ = dotenvy::dotenv();
+                let master_key = match (
+                    std::env::var("CONFIG_MASTER_KEY"),
+                    std::env::var("CONFIG_MASTER_KEY_FILE"),
+                ) {
+                    (Ok(val), _) => val,
+                    (Err(_), Ok(path)) => match std::fs::read_to_string(&path) {
+                        Ok(val) => val,
+                        Err(err) => {
+                            eprintln!("failed to read {path}: {err}");
+                            std::process::exit(1);
+                        }
+                    },
+                    _ => {
+                        eprintln!("set CONFIG_MASTER_KEY or CONFIG_MASTER_KEY_FILE");
+                        std::process::exit(1);
+                    }
+                };
+                let key = match parse_master_key(&master_key) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("invalid master key: {err}");
+                        std::process::exit(1);
+                    }
+                };
+                let value = match value {
+                    Some(val) => val.clone(),
+                    None => rpassword::prompt_password("Value: ").unwrap(),
+                };
+                match encrypt_secret(&key, &value) {
+                    Ok(val) => println!("{val}"),
+                    Err(err) => {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        },
+        Commands::Rbac(rbac_args) => match &rbac_args.command {
+            RbacCommands::Export { out } => {
+                let _ = dotenvy::dotenv();
+                let config = get_config();
+                let pool = init_pool(&config).await;
+                let backup = match rbac::export_rbac(&pool).await {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("export failed: {err}");
+                        std::process::exit(1);
+                    }
+                };
+                let content = serde_json::to_string_pretty(&backup).unwrap();
+                if let Err(err) = std::fs::write(out, content) {
+                    eprintln!("failed to write {out}: {err}");
+                    std::process::exit(1);
+                }
+                println!(
+                    "exported {} roles, {} groups, {} permissions, {} permission attributes to {out}",
+                    backup.roles.len(),
+                    backup.groups.len(),
+                    backup.permissions.len(),
+                    backup.permission_attributes.len()
+                );
+            }
+            RbacCommands::Import { file, dry_run } => {
+                let content = match std::fs::read_to_string(file) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("failed to read {file}: {err}");
+                        std::process::exit(1);
+                    }
+                };
+                let backup = match rbac::parse_rbac_backup(&content) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        eprintln!("invalid backup: {err}");
+                        std::process::exit(1);
+                    }
+                };
+                let _ = dotenvy::dotenv();
+                let config = get_config();
+                let pool = init_pool(&config).await;
+                match rbac::import_rbac(&pool, &backup, *dry_run).await {
+                    Ok(summary) => {
+                        if *dry_run {
+                            println!("dry run, nothing written");
+                        }
+                        println!("{summary}");
+                    }
+                    Err(err) => {
+                        eprintln!("import failed: {err}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        },
+        Commands::Seed { file } => {
+            let content = match file {
+                Some(path) => {
+                    println!("seed from {path}");
+                    std::fs::read_to_string(path).unwrap()
+                }
+                None => {
+                    println!("seed built-in defaults");
+                    seed::DEFAULT_SEED.to_string()
+                }
+            };
+            let data = match seed::parse_seed_data(&content) {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("invalid seed file: {err}");
+                    std::process::exit(1);
+                }
+            };
+            let _ = dotenvy::dotenv();
+            let config = get_config();
+            let pool = init_pool(&config).await;
+            match seed::seed(&pool, &data).await {
+                Ok(summary) => println!("{summary:?}"),
+                Err(err) => {
+                    eprintln!("seed failed: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
     }
 }
@@ -1,46 +1,277 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use core_rust_qti::{core::db::init_pool, init_openapi_route, settings::get_config, AppState};
+use core_rust_qti::{
+    core::{
+        account_expiry::spawn_account_expiry_worker,
+        api_version::parse_deprecated_versions,
+        data_export::spawn_data_export_worker,
+        db::{init_pool, pending_migrations, run_migrations, Backend},
+        deprecation::parse_route_sunsets,
+        directory_sync::spawn_directory_sync_worker,
+        dormant_account::{spawn_dormant_account_worker, DormantPolicy},
+        permission_expiry::spawn_permission_expiry_worker,
+        pii::{init_pii_keys, PiiKeys},
+        retention::spawn_retention_worker,
+        sanitize::{mask_sensitive, MaskingMakeWriter},
+        scim::spawn_scim_worker,
+        security::{init_jwt_keys, JwtKeys},
+        session_store::{
+            create_redis_pool, spawn_memory_store_sweeper, MemoryStore, SessionPool,
+            MEMORY_STORE_SWEEP_INTERVAL, SESSION_STORE_MEMORY,
+        },
+        tls::{init_https_redirect_route, server_listener},
+    },
+    init_openapi_route,
+    settings::{init_config, try_get_config, LogFormat},
+    AppState,
+};
 use poem::listener::TcpListener;
-use tracing::Level;
 
 #[tokio::main]
 async fn main() {
-    let log_level = Level::DEBUG;
+    // Settings are resolved once here, handlers only read the loaded copy
+    let config = match try_get_config() {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("failed to load config: {err}");
+            std::process::exit(1);
+        }
+    };
+    init_config(config.clone());
+    let profile = match config.profile() {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("invalid APP_ENV profile: {err}");
+            std::process::exit(1);
+        }
+    };
     // Logging to File
     let file_appender = tracing_appender::rolling::daily("./logs", "app.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-    tracing_subscriber::fmt()
-        .with_writer(non_blocking)
-        .with_max_level(log_level)
-        .init();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(MaskingMakeWriter::new(non_blocking))
+        .with_max_level(profile.log_level);
+    match profile.log_format {
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
 
     // Logging to Console
-    // tracing_subscriber::fmt().with_max_level(log_level).init();
+    // tracing_subscriber::fmt().with_max_level(profile.log_level).init();
 
-    let config = get_config();
-    tracing::info!("run with config: {:?}", config);
+    tracing::info!("run with config: {:?}", config.redacted());
+    tracing::info!("run with {} profile: {:?}", profile.app_env, profile);
+
+    // Keys encrypting personal data at rest
+    match PiiKeys::from_config(&config) {
+        Ok(keys) => {
+            tracing::info!("pii encryption enabled: {}", keys.is_some());
+            init_pii_keys(keys);
+        }
+        Err(err) => {
+            tracing::error!("invalid pii encryption keys: {}", err);
+            eprintln!("invalid pii encryption keys: {err}");
+            std::process::exit(1);
+        }
+    }
+
+    // Keys signing tokens, a keyring once JWT_KEYS_FILE is rotated
+    match JwtKeys::from_config(&config) {
+        Ok(keys) => {
+            tracing::info!("signing tokens with key {}", keys.kid());
+            init_jwt_keys(keys);
+        }
+        Err(err) => {
+            tracing::error!("invalid jwt signing keys: {}", err);
+            eprintln!("invalid jwt signing keys: {err}");
+            std::process::exit(1);
+        }
+    }
 
     // Init Database Connection
-    tracing::info!("Init Postgres connection on {}", config.database_url);
+    let backend = match Backend::from_url(&config.database_url) {
+        Ok(val) => val,
+        Err(err) => {
+            tracing::error!("invalid DATABASE_URL: {}", err);
+            eprintln!("invalid DATABASE_URL: {err}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = backend.ensure_supported() {
+        tracing::error!("unsupported database: {}", err);
+        eprintln!("unsupported database: {err}");
+        std::process::exit(1);
+    }
+    tracing::info!(
+        "Init Postgres connection on {}",
+        mask_sensitive(&config.database_url)
+    );
     let pool = init_pool(&config).await;
+    // Refuse to serve on outdated schema unless auto migrate enabled
+    let pending = pending_migrations(&pool)
+        .await
+        .expect("Failed to check database migration");
+    if !pending.is_empty() {
+        if config.auto_migrate.unwrap_or(false) {
+            tracing::info!("apply {} pending migration", pending.len());
+            run_migrations(&pool, None)
+                .await
+                .expect("Failed to run database migration");
+        } else {
+            tracing::error!(
+                "database schema is behind, {} pending migration, run `cli migrate` or set AUTO_MIGRATE=true",
+                pending.len()
+            );
+            eprintln!("database schema is behind, run `cli migrate` or set AUTO_MIGRATE=true");
+            std::process::exit(1);
+        }
+    }
     // Init Redis Connection
-    tracing::info!("Init Redis connection on {}", config.redis_url.clone());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool: SessionPool = match config.session_store.as_deref() {
+        Some(SESSION_STORE_MEMORY) => {
+            tracing::warn!(
+                "sessions and caches kept in process, do not run more than one instance"
+            );
+            let store = MemoryStore::new();
+            spawn_memory_store_sweeper(&store, MEMORY_STORE_SWEEP_INTERVAL);
+            store.into()
+        }
+        _ => {
+            tracing::info!(
+                "Init Redis connection on {}",
+                mask_sensitive(&config.redis_url)
+            );
+            create_redis_pool(&config).unwrap().into()
+        }
+    };
+    // Start outbound scim provisioning worker
+    let scim_worker_interval = config.scim_worker_interval.unwrap_or(30);
+    tracing::info!("run scim worker every {} seconds", scim_worker_interval);
+    spawn_scim_worker(pool.clone(), Duration::from_secs(scim_worker_interval));
+    // Start scheduled directory sync worker
+    let directory_sync_worker_interval = config.directory_sync_worker_interval.unwrap_or(60);
+    tracing::info!(
+        "check due directory sync every {} seconds",
+        directory_sync_worker_interval
+    );
+    spawn_directory_sync_worker(
+        pool.clone(),
+        redis_pool.clone(),
+        Duration::from_secs(directory_sync_worker_interval),
+    );
+    // Start user data export worker
+    let data_export_worker_interval = config.data_export_worker_interval.unwrap_or(10);
+    tracing::info!(
+        "generate requested user data export every {} seconds",
+        data_export_worker_interval
+    );
+    spawn_data_export_worker(
+        pool.clone(),
+        Duration::from_secs(data_export_worker_interval),
+    );
+    // Start retention purge of soft deleted data when enabled
+    if let Some(retention_days) = config.retention_days {
+        tracing::info!(
+            "purge soft deleted data older than {} days every hour",
+            retention_days
+        );
+        spawn_retention_worker(
+            pool.clone(),
+            Duration::from_secs(3600),
+            chrono::Duration::days(retention_days as i64),
+        );
+    }
+    // Start suspension of expired accounts
+    tracing::info!("suspend expired accounts every hour");
+    spawn_account_expiry_worker(pool.clone(), redis_pool.clone(), Duration::from_secs(3600));
+    // Start removal of expired temporary user permissions
+    tracing::info!("remove expired user permissions every hour");
+    spawn_permission_expiry_worker(pool.clone(), Duration::from_secs(3600));
+    // Start dormant account check when enabled
+    match DormantPolicy::from_config(&config) {
+        Ok(Some(policy)) => {
+            tracing::info!(
+                "{} accounts without login for {} days every hour",
+                policy.action,
+                policy.days
+            );
+            spawn_dormant_account_worker(
+                pool.clone(),
+                redis_pool.clone(),
+                Duration::from_secs(3600),
+                policy,
+            );
+        }
+        Ok(None) => {}
+        Err(err) => {
+            tracing::error!("invalid dormant account settings: {}", err);
+            eprintln!("invalid dormant account settings: {err}");
+            std::process::exit(1);
+        }
+    }
     // Init App State
-    let app_state = Arc::new(AppState {
-        db: pool,
-        redis_conn: redis_pool,
-    });
+    let app_state = Arc::new(AppState::new(pool, redis_pool));
 
+    if let Err(err) = parse_deprecated_versions(config.api_deprecated_versions.as_deref()) {
+        tracing::error!("invalid API_DEPRECATED_VERSIONS: {}", err);
+        eprintln!("invalid API_DEPRECATED_VERSIONS: {err}");
+        std::process::exit(1);
+    }
+    if let Err(err) = parse_route_sunsets(config.deprecated_route_sunset.as_deref()) {
+        tracing::error!("invalid DEPRECATED_ROUTE_SUNSET: {}", err);
+        eprintln!("invalid DEPRECATED_ROUTE_SUNSET: {err}");
+        std::process::exit(1);
+    }
     let app = init_openapi_route(app_state.clone(), &config);
+    let listener = match server_listener(&config) {
+        Ok(val) => val,
+        Err(err) => {
+            tracing::error!("invalid tls settings: {}", err);
+            eprintln!("invalid tls settings: {err}");
+            std::process::exit(1);
+        }
+    };
+    // Plain http listener sending clients to the tls port
+    if let Some(redirect_port) = config.http_redirect_port {
+        tracing::info!(
+            "redirect http on {}:{} to https",
+            config.host,
+            redirect_port
+        );
+        let redirect = poem::Server::new(TcpListener::bind(format!(
+            "{}:{}",
+            config.host, redirect_port
+        )))
+        .run(init_https_redirect_route(config.port));
+        tokio::spawn(async move {
+            if let Err(err) = redirect.await {
+                tracing::error!("error: on https redirect listener error: {}", err);
+            }
+        });
+    }
+    // Internal grpc api on its own port
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_port) = config.grpc_port {
+        let addr = match format!("{}:{}", config.host, grpc_port).parse() {
+            Ok(val) => val,
+            Err(err) => {
+                tracing::error!("invalid GRPC_PORT address: {}", err);
+                eprintln!("invalid GRPC_PORT address: {err}");
+                std::process::exit(1);
+            }
+        };
+        tracing::info!("run grpc server on {}", addr);
+        let grpc = core_rust_qti::grpc::serve(app_state.clone(), addr);
+        tokio::spawn(async move {
+            if let Err(err) = grpc.await {
+                tracing::error!("error: on grpc server error: {}", err);
+            }
+        });
+    }
+    #[cfg(not(feature = "grpc"))]
+    if config.grpc_port.is_some() {
+        tracing::warn!("GRPC_PORT ignored, built without the grpc feature");
+    }
     tracing::info!("run server on {}:{}", config.host, config.port);
-    poem::Server::new(TcpListener::bind(format!(
-        "{}:{}",
-        config.host, config.port
-    )))
-    .run(app)
-    .await
-    .unwrap()
+    poem::Server::new(listener).run(app).await.unwrap()
 }
@@ -0,0 +1,206 @@
+use chrono::Local;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    cli::{auth::generate_password, cache::grants_changed},
+    core::{
+        lifecycle::{change_user_status, revoke_deactivated_sessions},
+        pii::decrypt_pii,
+        security::hash_password,
+        session::get_redis_connection,
+    },
+    model::{
+        role::Role,
+        scim_provisioning_event::{EVENT_DEACTIVATE, EVENT_UPDATE},
+        user::UserStatus,
+        user_group_roles::UserGroupRoles,
+    },
+    repository,
+    settings::get_config,
+};
+
+/// User line shown by the admin console
+#[derive(Debug, Clone, FromRow)]
+pub struct AdminUser {
+    pub id: Uuid,
+    pub user_name: String,
+    pub is_active: Option<bool>,
+    pub email: Option<String>,
+    /// Role names, group scoped roles included
+    pub roles: Vec<String>,
+}
+
+pub async fn list_admin_users(
+    pool: &PgPool,
+    search: Option<&str>,
+    limit: u32,
+) -> anyhow::Result<Vec<AdminUser>> {
+    let search = format!("%{}%", search.unwrap_or(""));
+    let users: Vec<AdminUser> = sqlx::query_as(
+        r#"SELECT u.id, u.user_name, u.is_active, p.email,
+        COALESCE(array_agg(DISTINCT r.role_name) FILTER (WHERE r.role_name IS NOT NULL), '{}') AS roles
+        FROM public.user u
+        LEFT JOIN public.user_profile p ON p.user_id = u.id
+        LEFT JOIN public.user_group_roles ugr ON ugr.user_id = u.id
+        LEFT JOIN public.role r ON r.id = ugr.role_id AND r.deleted_date IS NULL
+        WHERE u.deleted_date IS NULL AND u.user_name ILIKE $1
+        GROUP BY u.id, u.user_name, u.is_active, p.email
+        ORDER BY u.user_name
+        LIMIT $2"#,
+    )
+    .bind(search)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await?;
+    let mut res = vec![];
+    for mut user in users {
+        user.email = decrypt_pii(user.email)?;
+        res.push(user);
+    }
+    Ok(res)
+}
+
+pub async fn list_admin_roles(pool: &PgPool) -> anyhow::Result<Vec<Role>> {
+    let mut tx = pool.begin().await?;
+    let mut roles = repository::role::get_all_role(&mut tx).await?;
+    tx.commit().await?;
+    roles.sort_by(|a, b| a.role_name.cmp(&b.role_name));
+    Ok(roles)
+}
+
+/// Activate or suspend a user and queue the scim change, return the new status. Suspending
+/// signs the user out once committed
+pub async fn set_user_active(pool: &PgPool, user_id: &Uuid, active: bool) -> anyhow::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let now = Local::now().fixed_offset();
+    let mut user = match repository::user::get_user_by_id(&mut tx, user_id, None).await? {
+        (Some(user), _) => user,
+        (None, _) => anyhow::bail!("user {} not found", user_id),
+    };
+    let status = UserStatus::from_active(active);
+    let history = match user.status != status {
+        true => Some(change_user_status(&mut tx, &mut user, status, None, None, &now).await?),
+        false => None,
+    };
+    let event = match active {
+        true => EVENT_UPDATE,
+        false => EVENT_DEACTIVATE,
+    };
+    repository::scim_provisioning_event::enqueue_scim_event(&mut tx, user_id, event, Some(now))
+        .await?;
+    tx.commit().await?;
+    if history.is_some_and(|x| x.left_active()) {
+        let mut redis_conn = get_redis_connection(&get_config().redis_url).await?;
+        revoke_deactivated_sessions(&mut redis_conn, &[user.id]).await?;
+    }
+    Ok(active)
+}
+
+/// Replace the user password with a generated one and return it
+pub async fn reset_user_password(pool: &PgPool, user_id: &Uuid) -> anyhow::Result<String> {
+    let password = generate_password(16);
+    let hashed_password = match hash_password(&password) {
+        Ok(val) => val,
+        Err(err) => anyhow::bail!(err.to_string()),
+    };
+    let res = sqlx::query(
+        r#"UPDATE public.user SET password = $1, updated_by = NULL, updated_date = $2
+        WHERE id = $3 AND deleted_date IS NULL"#,
+    )
+    .bind(hashed_password)
+    .bind(Local::now().fixed_offset())
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        anyhow::bail!("user {} not found", user_id);
+    }
+    Ok(password)
+}
+
+/// Grant a role outside any group, return false when the user already has it
+pub async fn assign_user_role(
+    pool: &PgPool,
+    user_id: &Uuid,
+    role_id: &Uuid,
+) -> anyhow::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let existing: Option<(Uuid,)> = sqlx::query_as(
+        r#"SELECT id FROM public.user_group_roles
+        WHERE user_id = $1 AND role_id = $2 AND group_id IS NULL"#,
+    )
+    .bind(user_id)
+    .bind(role_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if existing.is_some() {
+        return Ok(false);
+    }
+    repository::user_group_roles::add_user_group_roles(
+        &mut tx,
+        &UserGroupRoles {
+            id: Uuid::now_v7(),
+            user_id: Some(*user_id),
+            group_id: None,
+            role_id: Some(*role_id),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    grants_changed(Some(&[*user_id])).await;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        cli::{
+            admin::{
+                assign_user_role, list_admin_roles, list_admin_users, reset_user_password,
+                set_user_active,
+            },
+            auth::create_user,
+        },
+        core::security::verify_hash_password,
+        factory::role::RoleFactory,
+        repository,
+    };
+
+    #[sqlx::test]
+    async fn test_admin_actions(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        create_user(&pool, "operator", "secret").await?;
+        let role = RoleFactory::<()>::new().generate_one(&pool, ()).await?;
+        let user = list_admin_users(&pool, Some("oper"), 10).await?;
+        assert_eq!(user.len(), 1);
+        let user = user[0].clone();
+        assert!(user.roles.is_empty());
+
+        // When
+        set_user_active(&pool, &user.id, false).await?;
+        let password = reset_user_password(&pool, &user.id).await?;
+        let first = assign_user_role(&pool, &user.id, &role.id).await?;
+        let second = assign_user_role(&pool, &user.id, &role.id).await?;
+
+        // Expect
+        assert!(first);
+        assert!(!second);
+        let listed = list_admin_users(&pool, None, 10).await?;
+        assert_eq!(listed[0].is_active, Some(false));
+        assert_eq!(listed[0].roles, vec![role.role_name.clone()]);
+        let mut tx = pool.begin().await?;
+        let (stored, _) = repository::user::get_user_by_id(&mut tx, &user.id, None).await?;
+        assert!(verify_hash_password(&password, &stored.unwrap().password).unwrap());
+        assert!(list_admin_roles(&pool)
+            .await?
+            .iter()
+            .any(|x| x.id == role.id));
+        assert!(set_user_active(&pool, &uuid::Uuid::now_v7(), true)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
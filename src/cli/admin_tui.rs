@@ -0,0 +1,262 @@
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+use sqlx::PgPool;
+
+use crate::{
+    cli::admin::{
+        assign_user_role, list_admin_roles, list_admin_users, reset_user_password, set_user_active,
+        AdminUser,
+    },
+    model::role::Role,
+};
+
+const USER_LIMIT: u32 = 200;
+const HELP: &str =
+    "j/k move  / search  a toggle active  p reset password  r assign role  g reload  q quit";
+
+enum Mode {
+    Browse,
+    Search(String),
+    ConfirmReset,
+    AssignRole(ListState),
+}
+
+struct App {
+    users: Vec<AdminUser>,
+    roles: Vec<Role>,
+    table_state: TableState,
+    search: Option<String>,
+    mode: Mode,
+    message: String,
+    quit: bool,
+}
+
+impl App {
+    fn selected_user(&self) -> Option<&AdminUser> {
+        self.table_state.selected().and_then(|x| self.users.get(x))
+    }
+
+    fn move_selection(&mut self, step: i64) {
+        if self.users.is_empty() {
+            self.table_state.select(None);
+            return;
+        }
+        let current = self.table_state.selected().unwrap_or(0) as i64;
+        let next = (current + step).clamp(0, self.users.len() as i64 - 1);
+        self.table_state.select(Some(next as usize));
+    }
+}
+
+async fn reload(app: &mut App, pool: &PgPool) -> anyhow::Result<()> {
+    app.users = list_admin_users(pool, app.search.as_deref(), USER_LIMIT).await?;
+    app.roles = list_admin_roles(pool).await?;
+    let selected = app.table_state.selected().unwrap_or(0);
+    app.table_state = TableState::default();
+    if !app.users.is_empty() {
+        app.table_state
+            .select(Some(selected.min(app.users.len() - 1)));
+    }
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [table_area, status_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+
+    let rows: Vec<Row> = app
+        .users
+        .iter()
+        .map(|x| {
+            Row::new(vec![
+                x.user_name.clone(),
+                x.email.clone().unwrap_or_default(),
+                match x.is_active {
+                    Some(true) => "active".to_string(),
+                    _ => "inactive".to_string(),
+                },
+                x.roles.join(", "),
+            ])
+        })
+        .collect();
+    let title = match &app.search {
+        Some(search) => format!(" Users matching {} ", search),
+        None => " Users ".to_string(),
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(25),
+            Constraint::Percentage(30),
+            Constraint::Length(10),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(
+        Row::new(vec!["User name", "Email", "Status", "Roles"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::default().borders(Borders::ALL).title(title))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, table_area, &mut app.table_state);
+
+    let status = match &app.mode {
+        Mode::Browse => app.message.clone(),
+        Mode::Search(input) => format!("search: {}_", input),
+        Mode::ConfirmReset => "reset password of selected user? y/n".to_string(),
+        Mode::AssignRole(_) => "enter assign  esc cancel".to_string(),
+    };
+    frame.render_widget(
+        Paragraph::new(status).block(Block::default().borders(Borders::ALL).title(HELP)),
+        status_area,
+    );
+
+    if let Mode::AssignRole(state) = &mut app.mode {
+        let items: Vec<ListItem> = app
+            .roles
+            .iter()
+            .map(|x| ListItem::new(x.role_name.clone()))
+            .collect();
+        let [_, popup, _] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(40),
+            Constraint::Percentage(30),
+        ])
+        .areas(table_area);
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Assign role "),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_widget(ratatui::widgets::Clear, popup);
+        frame.render_stateful_widget(list, popup, state);
+    }
+}
+
+async fn handle_key(app: &mut App, pool: &PgPool, key: KeyCode) -> anyhow::Result<()> {
+    let mode = std::mem::replace(&mut app.mode, Mode::Browse);
+    match mode {
+        Mode::Search(mut input) => match key {
+            KeyCode::Enter => {
+                app.search = if input.is_empty() { None } else { Some(input) };
+                reload(app, pool).await?;
+            }
+            KeyCode::Esc => {}
+            KeyCode::Backspace => {
+                input.pop();
+                app.mode = Mode::Search(input);
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+                app.mode = Mode::Search(input);
+            }
+            _ => app.mode = Mode::Search(input),
+        },
+        Mode::ConfirmReset => {
+            if key == KeyCode::Char('y') {
+                if let Some(user) = app.selected_user().cloned() {
+                    let password = reset_user_password(pool, &user.id).await?;
+                    app.message = format!("new password of {}: {}", user.user_name, password);
+                }
+            }
+        }
+        Mode::AssignRole(mut state) => match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                state.select_previous();
+                app.mode = Mode::AssignRole(state);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.select_next();
+                app.mode = Mode::AssignRole(state);
+            }
+            KeyCode::Enter => {
+                let role = state.selected().and_then(|x| app.roles.get(x)).cloned();
+                if let (Some(user), Some(role)) = (app.selected_user().cloned(), role) {
+                    app.message = match assign_user_role(pool, &user.id, &role.id).await? {
+                        true => format!("{} assigned to {}", role.role_name, user.user_name),
+                        false => format!("{} already has {}", user.user_name, role.role_name),
+                    };
+                    reload(app, pool).await?;
+                }
+            }
+            KeyCode::Esc => {}
+            _ => app.mode = Mode::AssignRole(state),
+        },
+        Mode::Browse => match key {
+            KeyCode::Char('q') | KeyCode::Esc => app.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => app.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => app.move_selection(1),
+            KeyCode::Char('/') => {
+                app.mode = Mode::Search(app.search.clone().unwrap_or_default());
+            }
+            KeyCode::Char('g') => {
+                reload(app, pool).await?;
+                app.message = "reloaded".to_string();
+            }
+            KeyCode::Char('a') => {
+                if let Some(user) = app.selected_user().cloned() {
+                    let active =
+                        set_user_active(pool, &user.id, user.is_active != Some(true)).await?;
+                    app.message = format!(
+                        "{} is now {}",
+                        user.user_name,
+                        if active { "active" } else { "inactive" }
+                    );
+                    reload(app, pool).await?;
+                }
+            }
+            KeyCode::Char('p') if app.selected_user().is_some() => {
+                app.mode = Mode::ConfirmReset;
+            }
+            KeyCode::Char('r') if app.selected_user().is_some() => {
+                let mut state = ListState::default();
+                if !app.roles.is_empty() {
+                    state.select(Some(0));
+                }
+                app.mode = Mode::AssignRole(state);
+            }
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
+async fn run(terminal: &mut DefaultTerminal, pool: &PgPool) -> anyhow::Result<()> {
+    let mut app = App {
+        users: vec![],
+        roles: vec![],
+        table_state: TableState::default(),
+        search: None,
+        mode: Mode::Browse,
+        message: "".to_string(),
+        quit: false,
+    };
+    reload(&mut app, pool).await?;
+    while !app.quit {
+        terminal.draw(|frame| draw(frame, &mut app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Err(err) = handle_key(&mut app, pool, key.code).await {
+                app.mode = Mode::Browse;
+                app.message = format!("error: {}", err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the admin console until the user quits, terminal is restored on exit
+pub async fn run_admin_tui(pool: PgPool) -> anyhow::Result<()> {
+    let mut terminal = ratatui::init();
+    let res = run(&mut terminal, &pool).await;
+    ratatui::restore();
+    res
+}
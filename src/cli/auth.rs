@@ -1,13 +1,29 @@
-use chrono::Local;
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use rand::{rngs::OsRng, seq::SliceRandom};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    core::security::hash_password,
-    model::{user::User, user_profile::UserProfile},
+    core::{
+        security::{encode_token, hash_password, Claims, JwtKeys},
+        service_account::parse_scope,
+        session::add_session_with_ttl,
+        session_store::SessionStore,
+    },
+    model::{
+        user::{User, UserStatus},
+        user_group_roles::UserGroupRoles,
+        user_profile::UserProfile,
+    },
     repository,
+    settings::Config,
 };
 
+pub const SUPERUSER_ROLE_NAME: &str = "admin";
+
+const GENERATED_PASSWORD_CHARS: &[u8] =
+    b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789!@#$%^&*-_";
+
 pub async fn create_user(pool: &PgPool, username: &str, password: &str) -> anyhow::Result<()> {
     let mut tx = pool.begin().await?;
 
@@ -17,13 +33,15 @@ pub async fn create_user(pool: &PgPool, username: &str, password: &str) -> anyho
         id: Uuid::now_v7(),
         user_name: username.to_string(),
         password: hashed_password,
-        is_active: Some(true),
+        status: UserStatus::Active,
+        expires_at: None,
         is_2faenabled: Some(false),
         created_by: None,
         updated_by: None,
         created_date: Some(now),
         updated_date: Some(now),
         deleted_date: None,
+        scopes: None,
     };
     let user_profile = UserProfile {
         id: user.id,
@@ -32,6 +50,9 @@ pub async fn create_user(pool: &PgPool, username: &str, password: &str) -> anyho
         last_name: None,
         email: None,
         address: None,
+        locale: None,
+        timezone: None,
+        email_verified_at: None,
     };
     repository::user::create_user(&mut tx, &user, &user_profile)
         .await
@@ -40,11 +61,184 @@ pub async fn create_user(pool: &PgPool, username: &str, password: &str) -> anyho
     Ok(())
 }
 
+/// Random password for users created without an interactive prompt
+pub fn generate_password(length: usize) -> String {
+    // choose draws uniformly, a byte modulo the charset length would favour its first chars
+    (0..length)
+        .filter_map(|_| GENERATED_PASSWORD_CHARS.choose(&mut OsRng))
+        .map(|x| *x as char)
+        .collect()
+}
+
+/// Create an active user holding the admin role, the role and its permissions come
+/// from `cli seed` so an unseeded database is refused
+pub async fn create_superuser(
+    pool: &PgPool,
+    username: &str,
+    email: &str,
+    password: &str,
+) -> anyhow::Result<User> {
+    let mut tx = pool.begin().await?;
+
+    // a role without grants would leave the superuser unable to do anything
+    let role = match repository::role::get_role_by_name(&mut tx, SUPERUSER_ROLE_NAME).await? {
+        Some(val) => val,
+        None => anyhow::bail!("role {} not found, run cli seed first", SUPERUSER_ROLE_NAME),
+    };
+    let (_, permission_count, _) = repository::role_permission::get_all_role_permission(
+        &mut tx,
+        None,
+        Some(1),
+        &role.id,
+        None,
+    )
+    .await?;
+    if permission_count == 0 {
+        anyhow::bail!(
+            "role {} has no permissions, run cli seed first",
+            SUPERUSER_ROLE_NAME
+        );
+    }
+
+    let (existing, _) = repository::user::get_user_by_username(&mut tx, username).await?;
+    if existing.is_some() {
+        anyhow::bail!("user {} already exists", username);
+    }
+    let hashed_password = match hash_password(password) {
+        Ok(val) => val,
+        Err(err) => anyhow::bail!(err.to_string()),
+    };
+    let now = Local::now().fixed_offset();
+    let user = User {
+        id: Uuid::now_v7(),
+        user_name: username.to_string(),
+        password: hashed_password,
+        status: UserStatus::Active,
+        expires_at: None,
+        is_2faenabled: Some(false),
+        created_by: None,
+        updated_by: None,
+        created_date: Some(now),
+        updated_date: Some(now),
+        deleted_date: None,
+        scopes: None,
+    };
+    let user_profile = UserProfile {
+        id: user.id,
+        user_id: user.id,
+        first_name: None,
+        last_name: None,
+        email: Some(email.to_string()),
+        address: None,
+        locale: None,
+        timezone: None,
+        email_verified_at: None,
+    };
+    repository::user::create_user(&mut tx, &user, &user_profile).await?;
+
+    repository::user_group_roles::add_user_group_roles(
+        &mut tx,
+        &UserGroupRoles {
+            id: Uuid::now_v7(),
+            user_id: Some(user.id),
+            group_id: None,
+            role_id: Some(role.id),
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(user)
+}
+
+pub struct IssuedToken {
+    pub user: User,
+    pub token: String,
+    pub exp: DateTime<FixedOffset>,
+}
+
+/// Sign a token for an active user found by id or user name and register its session,
+/// ttl in minutes defaults to JWT_EXP. Requests with the token are limited to the scopes
+/// when any are given. No refresh token is issued.
+pub async fn issue_token<C: SessionStore>(
+    pool: &PgPool,
+    redis_conn: &mut C,
+    config: &Config,
+    user: &str,
+    ttl: Option<u32>,
+    scopes: Vec<String>,
+) -> anyhow::Result<IssuedToken> {
+    let mut tx = pool.begin().await?;
+    let found = match Uuid::parse_str(user) {
+        Ok(id) => {
+            repository::user::get_user_by_id(&mut tx, &id, None)
+                .await?
+                .0
+        }
+        Err(_) => {
+            repository::user::get_user_by_username(&mut tx, user)
+                .await?
+                .0
+        }
+    };
+    tx.commit().await?;
+    let found = match found {
+        Some(val) if val.deleted_date.is_none() => val,
+        _ => anyhow::bail!("user {} not found", user),
+    };
+    if !found.is_active() {
+        anyhow::bail!("user {} is not active", found.user_name);
+    }
+
+    let ttl = ttl.unwrap_or(config.jwt_exp as u32);
+    if ttl == 0 {
+        anyhow::bail!("ttl must be greater than 0");
+    }
+    if let Some(scope) = scopes.iter().find(|x| parse_scope(x).is_none()) {
+        anyhow::bail!("scope {} is not written as name.attribute", scope);
+    }
+    let exp = Local::now().fixed_offset() + Duration::minutes(ttl as i64);
+    let claims = Claims {
+        id: found.id.to_string(),
+        user_name: found.user_name.clone(),
+        exp: exp.timestamp(),
+        scopes: if scopes.is_empty() {
+            None
+        } else {
+            Some(scopes)
+        },
+    };
+    let token = encode_token(&claims, &JwtKeys::from_config(config)?)?;
+    add_session_with_ttl(
+        redis_conn,
+        &found,
+        token.clone(),
+        "".to_string(),
+        ttl as u64 * 60,
+        claims.scopes,
+    )
+    .await?;
+    Ok(IssuedToken {
+        user: found,
+        token,
+        exp,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use sqlx::PgPool;
 
-    use crate::cli::auth::create_user;
+    use crate::{
+        cli::{
+            auth::{create_superuser, create_user, generate_password, issue_token},
+            seed::{parse_seed_data, seed, DEFAULT_SEED},
+        },
+        core::{
+            security::{decode_token, get_user_from_token, JwtKeys},
+            session::get_redis_connection,
+        },
+        settings::get_config,
+    };
 
     #[sqlx::test]
     async fn test_create_user(pool: PgPool) -> sqlx::Result<()> {
@@ -69,4 +263,124 @@ mod tests {
         assert_eq!(db_res.unwrap().0, username);
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn test_create_superuser(pool: PgPool) -> sqlx::Result<()> {
+        // Given unseeded database, admin role missing
+        let password = generate_password(20);
+        assert!(
+            create_superuser(&pool, "root", "root@example.com", &password)
+                .await
+                .is_err()
+        );
+        seed(&pool, &parse_seed_data(DEFAULT_SEED).unwrap())
+            .await
+            .unwrap();
+
+        // When
+        let user = create_superuser(&pool, "root", "root@example.com", &password)
+            .await
+            .unwrap();
+
+        // Expect
+        assert_eq!(password.len(), 20);
+        assert!(user.is_active());
+        let db_res: Option<(String, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT r.role_name, p.email
+            FROM public.user_group_roles ugr
+            JOIN public.role r ON r.id = ugr.role_id
+            JOIN public.user_profile p ON p.user_id = ugr.user_id
+            WHERE ugr.user_id = $1 AND ugr.group_id IS NULL
+            "#,
+        )
+        .bind(user.id)
+        .fetch_optional(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            db_res,
+            Some(("admin".to_string(), Some("root@example.com".to_string())))
+        );
+
+        // When second superuser reuse the admin role, duplicated username rejected
+        create_superuser(&pool, "root2", "root2@example.com", &password)
+            .await
+            .unwrap();
+        assert!(
+            create_superuser(&pool, "root", "root@example.com", &password)
+                .await
+                .is_err()
+        );
+        let count: (i64,) =
+            sqlx::query_as("SELECT count(id) FROM public.role WHERE role_name = 'admin'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(count.0, 1);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_issue_token(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let mut redis_conn = get_redis_connection(&config.redis_url).await?;
+        create_user(&pool, "robot", "secret").await?;
+
+        // When
+        let issued = issue_token(
+            &pool,
+            &mut redis_conn,
+            &config,
+            "robot",
+            Some(5),
+            vec!["user.read".to_string()],
+        )
+        .await?;
+
+        // Expect
+        let claims = decode_token(&issued.token, &JwtKeys::from_config(&config)?)?;
+        assert_eq!(claims.user_name, "robot");
+        assert_eq!(claims.scopes, Some(vec!["user.read".to_string()]));
+        assert_eq!(claims.exp, issued.exp.timestamp());
+        let mut tx = pool.begin().await?;
+        let user = get_user_from_token(&mut tx, &mut redis_conn, Some(issued.token))
+            .await?
+            .unwrap();
+        assert_eq!(user.id, issued.user.id);
+        assert!(user.has_scope("user", "read"));
+        assert!(!user.has_scope("user", "create"));
+
+        // When lookup by id, unknown user
+        let by_id = issue_token(
+            &pool,
+            &mut redis_conn,
+            &config,
+            &issued.user.id.to_string(),
+            None,
+            vec![],
+        )
+        .await?;
+        let unknown = issue_token(&pool, &mut redis_conn, &config, "nobody", None, vec![]).await;
+        let bad_scope = issue_token(
+            &pool,
+            &mut redis_conn,
+            &config,
+            "robot",
+            None,
+            vec!["user:read".to_string()],
+        )
+        .await;
+
+        // Expect
+        assert_eq!(by_id.user.id, issued.user.id);
+        let user = get_user_from_token(&mut tx, &mut redis_conn, Some(by_id.token))
+            .await?
+            .unwrap();
+        assert!(user.scopes.is_none());
+        assert!(unknown.is_err());
+        assert!(bad_scope.is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,118 @@
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        permission_cache::{all_permissions_changed, permissions_changed},
+        session::{get_redis_connection, scan_keys, SessionData},
+        session_store::SessionStore,
+    },
+    settings::get_config,
+};
+
+pub const DEFAULT_FLUSH_PATTERN: &str = "core:*";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FlushSummary {
+    pub deleted: u64,
+    pub sessions_kept: u64,
+}
+
+async fn is_session_key<C: SessionStore>(redis_conn: &mut C, key: &str) -> bool {
+    // non string values fail with WRONGTYPE and are never sessions
+    let value = match redis_conn.get(key).await {
+        Ok(val) => val,
+        Err(_) => return false,
+    };
+    match value {
+        Some(val) => serde_json::from_str::<SessionData>(&val).is_ok(),
+        None => false,
+    }
+}
+
+/// Delete keys matching pattern, login sessions are kept unless include_sessions
+pub async fn flush_cache<C: SessionStore>(
+    redis_conn: &mut C,
+    pattern: &str,
+    include_sessions: bool,
+) -> anyhow::Result<FlushSummary> {
+    let keys = scan_keys(redis_conn, pattern).await?;
+    let mut summary = FlushSummary::default();
+    for key in keys.iter() {
+        if !include_sessions && is_session_key(redis_conn, key).await {
+            summary.sessions_kept += 1;
+            continue;
+        }
+        summary.deleted += redis_conn.del(key).await?;
+    }
+    Ok(summary)
+}
+
+/// Drop the cached permission sets after a command committed grant changes, every set when
+/// user_ids is None. The change is already committed, an unreachable redis is only logged
+pub async fn grants_changed(user_ids: Option<&[Uuid]>) {
+    let mut redis_conn = match get_redis_connection(&get_config().redis_url).await {
+        Ok(val) => val,
+        Err(err) => {
+            tracing::warn!("error: on cli::cache::grants_changed error: {}", err);
+            return;
+        }
+    };
+    match user_ids {
+        Some(user_ids) => permissions_changed(&mut redis_conn, user_ids).await,
+        None => all_permissions_changed(&mut redis_conn).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::AsyncCommands;
+    use uuid::Uuid;
+
+    use crate::{
+        cli::cache::{flush_cache, FlushSummary},
+        core::session::{get_redis_connection, SessionData},
+        settings::get_config,
+    };
+
+    #[tokio::test]
+    async fn test_flush_cache() -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let mut redis_conn = get_redis_connection(&config.redis_url).await?;
+        let prefix = format!("core:test:{}", Uuid::now_v7());
+        let session = serde_json::to_string(&SessionData {
+            user_id: Uuid::now_v7().to_string(),
+            refresh_token: "".to_string(),
+            created_at: 0,
+            scopes: None,
+        })?;
+        let _: () = redis_conn.set(format!("{prefix}:a"), "1").await?;
+        let _: () = redis_conn.set(format!("{prefix}:b"), "2").await?;
+        let _: () = redis_conn
+            .set(format!("{prefix}:session"), &session)
+            .await?;
+        let _: () = redis_conn.set(format!("{prefix}-other"), "3").await?;
+
+        // When
+        let summary = flush_cache(&mut redis_conn, &format!("{prefix}:*"), false).await?;
+
+        // Expect
+        assert_eq!(
+            summary,
+            FlushSummary {
+                deleted: 2,
+                sessions_kept: 1
+            }
+        );
+        let other: Option<String> = redis_conn.get(format!("{prefix}-other")).await?;
+        assert!(other.is_some());
+
+        // When
+        let summary = flush_cache(&mut redis_conn, &format!("{prefix}*"), true).await?;
+
+        // Expect
+        assert_eq!(summary.deleted, 2);
+        assert_eq!(summary.sessions_kept, 0);
+        Ok(())
+    }
+}
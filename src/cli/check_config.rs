@@ -0,0 +1,483 @@
+use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+
+use redis::IntoConnectionInfo;
+use sqlx::postgres::PgConnectOptions;
+
+use crate::{
+    core::{
+        api_version::{latest_api_version, parse_deprecated_versions},
+        db::Backend,
+        deprecation::{deprecated_operations, parse_route_sunsets},
+        dormant_account::DormantPolicy,
+        ldap_auth::LdapAuth,
+        locale::{normalize_locale, parse_timezone, SUPPORTED_LANGUAGES},
+        mailer::ConfiguredMailer,
+        pii::PiiKeys,
+        push::ConfiguredPushPublisher,
+        security::{jwt_algorithm, JwtKeys},
+        session_store::{SESSION_STORE_MEMORY, SESSION_STORE_REDIS},
+        sms::ConfiguredSmsSender,
+        tls::{tls_mode, TlsMode},
+    },
+    init_openapi_service,
+    settings::{AppEnv, Config},
+};
+
+pub const MIN_JWT_SECRET_LENGTH: usize = 32;
+pub const MIN_JWT_SECRET_ENTROPY_BITS: f64 = 128.0;
+
+#[derive(Debug, PartialEq)]
+pub struct ConfigIssue {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Shannon entropy of the secret in bits, character frequency based
+pub fn secret_entropy_bits(secret: &str) -> f64 {
+    let len = secret.chars().count();
+    if len == 0 {
+        return 0.0;
+    }
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in secret.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    let per_char: f64 = counts
+        .values()
+        .map(|x| {
+            let p = *x as f64 / len as f64;
+            -p * p.log2()
+        })
+        .sum();
+    per_char * len as f64
+}
+
+/// Check settings that only fail at first use, empty result means valid
+pub fn validate_config(config: &Config) -> Vec<ConfigIssue> {
+    let mut issues: Vec<ConfigIssue> = vec![];
+    if config.env != "file" && config.env != "server" {
+        issues.push(ConfigIssue {
+            field: "ENV",
+            message: format!("must be file or server, got {}", config.env),
+        });
+    }
+    match config.profile() {
+        Ok(profile) => {
+            if profile.app_env == AppEnv::Prod
+                && profile.cors_allow_origins.contains(&"*".to_string())
+            {
+                issues.push(ConfigIssue {
+                    field: "CORS_ALLOW_ORIGINS",
+                    message: "* is not allowed with APP_ENV=prod, list the origins".to_string(),
+                });
+            }
+            if profile.app_env == AppEnv::Prod && profile.debug_errors {
+                issues.push(ConfigIssue {
+                    field: "DEBUG_ERRORS",
+                    message: "returns internal errors to clients, disable it with APP_ENV=prod"
+                        .to_string(),
+                });
+            }
+        }
+        Err(err) => issues.push(ConfigIssue {
+            field: "PROFILE",
+            message: err.to_string(),
+        }),
+    }
+    if config.host.is_empty() {
+        issues.push(ConfigIssue {
+            field: "HOST",
+            message: "is empty".to_string(),
+        });
+    }
+    if let Some(prefix) = &config.prefix {
+        if !prefix.starts_with('/') {
+            issues.push(ConfigIssue {
+                field: "PREFIX",
+                message: "must start with /".to_string(),
+            });
+        }
+    }
+    if let Err(err) = Backend::from_url(&config.database_url).and_then(|x| x.ensure_supported()) {
+        issues.push(ConfigIssue {
+            field: "DATABASE_URL",
+            message: err.to_string(),
+        });
+    } else if let Err(err) = PgConnectOptions::from_str(&config.database_url) {
+        issues.push(ConfigIssue {
+            field: "DATABASE_URL",
+            message: err.to_string(),
+        });
+    }
+    if let Err(err) = config.redis_url.as_str().into_connection_info() {
+        issues.push(ConfigIssue {
+            field: "REDIS_URL",
+            message: err.to_string(),
+        });
+    }
+    if let Some(session_store) = &config.session_store {
+        if session_store != SESSION_STORE_REDIS && session_store != SESSION_STORE_MEMORY {
+            issues.push(ConfigIssue {
+                field: "SESSION_STORE",
+                message: format!("must be redis or memory, got {}", session_store),
+            });
+        }
+    }
+    if config.redis_pool_max_size == Some(0) {
+        issues.push(ConfigIssue {
+            field: "REDIS_POOL_MAX_SIZE",
+            message: "must be greater than 0".to_string(),
+        });
+    }
+    if config.jwt_secret.chars().count() < MIN_JWT_SECRET_LENGTH {
+        issues.push(ConfigIssue {
+            field: "JWT_SECRET",
+            message: format!("must be at least {} characters", MIN_JWT_SECRET_LENGTH),
+        });
+    } else if secret_entropy_bits(&config.jwt_secret) < MIN_JWT_SECRET_ENTROPY_BITS {
+        issues.push(ConfigIssue {
+            field: "JWT_SECRET",
+            message: format!(
+                "entropy is below {} bits, use a random value",
+                MIN_JWT_SECRET_ENTROPY_BITS
+            ),
+        });
+    }
+    if config.jwt_exp == 0 {
+        issues.push(ConfigIssue {
+            field: "JWT_EXP",
+            message: "must be greater than 0".to_string(),
+        });
+    }
+    if config.jwt_refresh_exp <= config.jwt_exp {
+        issues.push(ConfigIssue {
+            field: "JWT_REFRESH_EXP",
+            message: "must be greater than JWT_EXP".to_string(),
+        });
+    }
+    for (field, interval) in [
+        ("SCIM_WORKER_INTERVAL", config.scim_worker_interval),
+        (
+            "DIRECTORY_SYNC_WORKER_INTERVAL",
+            config.directory_sync_worker_interval,
+        ),
+        (
+            "DATA_EXPORT_WORKER_INTERVAL",
+            config.data_export_worker_interval,
+        ),
+        ("LOGIN_LOCKOUT_WINDOW", config.login_lockout_window),
+        ("LOGIN_LOCKOUT_DURATION", config.login_lockout_duration),
+        ("EMAIL_VERIFICATION_TTL", config.email_verification_ttl),
+    ] {
+        if interval == Some(0) {
+            issues.push(ConfigIssue {
+                field,
+                message: "must be greater than 0".to_string(),
+            });
+        }
+    }
+    if let Err(err) = parse_deprecated_versions(config.api_deprecated_versions.as_deref()) {
+        issues.push(ConfigIssue {
+            field: "API_DEPRECATED_VERSIONS",
+            message: err.to_string(),
+        });
+    }
+    match parse_route_sunsets(config.deprecated_route_sunset.as_deref()) {
+        Ok(sunsets) => {
+            let spec = init_openapi_service("/", latest_api_version()).spec();
+            let operations = deprecated_operations(&spec, &HashMap::new()).unwrap_or_default();
+            let mut routes: Vec<&String> = sunsets
+                .keys()
+                .filter(|x| !operations.iter().any(|op| &&op.key() == x))
+                .collect();
+            routes.sort();
+            for route in routes {
+                issues.push(ConfigIssue {
+                    field: "DEPRECATED_ROUTE_SUNSET",
+                    message: format!("{} is not a deprecated route", route),
+                });
+            }
+        }
+        Err(err) => issues.push(ConfigIssue {
+            field: "DEPRECATED_ROUTE_SUNSET",
+            message: err.to_string(),
+        }),
+    }
+    if let Err(err) = DormantPolicy::from_config(config) {
+        issues.push(ConfigIssue {
+            field: "DORMANT_ACCOUNT",
+            message: err.to_string(),
+        });
+    }
+    if let Err(err) = ConfiguredMailer::from_config(config) {
+        issues.push(ConfigIssue {
+            field: "EMAIL_MAILER",
+            message: err.to_string(),
+        });
+    }
+    if let Err(err) = ConfiguredSmsSender::from_config(config) {
+        issues.push(ConfigIssue {
+            field: "SMS_PROVIDER",
+            message: err.to_string(),
+        });
+    }
+    if let Err(err) = ConfiguredPushPublisher::from_config(config) {
+        issues.push(ConfigIssue {
+            field: "PUSH",
+            message: err.to_string(),
+        });
+    }
+    if let Err(err) = LdapAuth::from_config(config) {
+        issues.push(ConfigIssue {
+            field: "LDAP_AUTH",
+            message: err.to_string(),
+        });
+    }
+    if let Some(locale) = config
+        .default_locale
+        .as_deref()
+        .filter(|x| normalize_locale(x).is_none())
+    {
+        issues.push(ConfigIssue {
+            field: "DEFAULT_LOCALE",
+            message: format!(
+                "{} is not supported, language must be one of {}",
+                locale,
+                SUPPORTED_LANGUAGES.join(", ")
+            ),
+        });
+    }
+    if let Some(timezone) = config
+        .default_timezone
+        .as_deref()
+        .filter(|x| parse_timezone(x).is_none())
+    {
+        issues.push(ConfigIssue {
+            field: "DEFAULT_TIMEZONE",
+            message: format!("{} is not an IANA timezone", timezone),
+        });
+    }
+    match jwt_algorithm(config) {
+        Ok(_) => {
+            if let Err(err) = JwtKeys::from_config(config) {
+                issues.push(ConfigIssue {
+                    // JWT_SECRET alone never fails
+                    field: match config
+                        .jwt_keys_file
+                        .as_deref()
+                        .filter(|x| Path::new(x).exists())
+                    {
+                        Some(_) => "JWT_KEYS_FILE",
+                        None => "JWT_PRIVATE_KEY",
+                    },
+                    message: err.to_string(),
+                });
+            }
+        }
+        Err(err) => issues.push(ConfigIssue {
+            field: "JWT_ALGORITHM",
+            message: err.to_string(),
+        }),
+    }
+    if let Err(err) = PiiKeys::from_config(config) {
+        issues.push(ConfigIssue {
+            field: "PII_ENCRYPTION_KEY",
+            message: err.to_string(),
+        });
+    }
+    match tls_mode(config) {
+        Ok(TlsMode::Files {
+            cert_path,
+            key_path,
+        }) => {
+            for (field, path) in [("TLS_CERT_PATH", cert_path), ("TLS_KEY_PATH", key_path)] {
+                if let Err(err) = std::fs::metadata(&path) {
+                    issues.push(ConfigIssue {
+                        field,
+                        message: format!("{}: {}", path, err),
+                    });
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(err) => issues.push(ConfigIssue {
+            field: "TLS",
+            message: err.to_string(),
+        }),
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::check_config::{secret_entropy_bits, validate_config},
+        settings::Config,
+    };
+
+    fn valid_config() -> Config {
+        Config {
+            env: "file".to_string(),
+            host: "0.0.0.0".to_string(),
+            port: 3504,
+            prefix: Some("/".to_string()),
+            database_url: "postgres://postgres@localhost:5432/core".to_string(),
+            jwt_secret: "kV9#qT2!mX7pL4$wZ8rB1nC6yH3dF5gJ".to_string(),
+            jwt_exp: 240,
+            jwt_refresh_exp: 600,
+            jwt_algorithm: None,
+            jwt_private_key: None,
+            jwt_keys_file: None,
+            jwt_key_grace: None,
+            redis_url: "redis://127.0.0.1:6379/0".to_string(),
+            session_store: None,
+            redis_pool_max_size: None,
+            redis_pool_timeout: None,
+            scim_worker_interval: Some(30),
+            directory_sync_worker_interval: None,
+            data_export_worker_interval: None,
+            auto_migrate: None,
+            retention_days: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            tls_acme_domains: None,
+            tls_acme_contact: None,
+            tls_acme_cache_dir: None,
+            tls_acme_staging: None,
+            http_redirect_port: None,
+            grpc_port: None,
+            api_deprecated_versions: None,
+            openapi_redoc: None,
+            deprecation_headers: None,
+            deprecated_route_sunset: None,
+            app_env: None,
+            log_format: None,
+            log_level: None,
+            cors_allow_origins: None,
+            rate_limit_per_minute: None,
+            debug_errors: None,
+            pii_encryption_key: None,
+            pii_encryption_previous_keys: None,
+            terms_acceptance_required: None,
+            dormant_account_days: None,
+            dormant_account_warning_days: None,
+            dormant_account_action: None,
+            dormant_account_webhook_url: None,
+            email_mailer: None,
+            email_webhook_url: None,
+            email_change_confirm_url: None,
+            email_change_ttl: None,
+            email_verification_enabled: None,
+            email_verification_url: None,
+            email_verification_ttl: None,
+            ses_access_key_id: None,
+            ses_secret_access_key: None,
+            ses_session_token: None,
+            ses_region: None,
+            ses_from: None,
+            ses_endpoint: None,
+            smtp_host: None,
+            smtp_port: None,
+            smtp_username: None,
+            smtp_password: None,
+            smtp_from: None,
+            smtp_tls: None,
+            sms_provider: None,
+            sms_from: None,
+            sms_api_key: None,
+            sms_api_secret: None,
+            sms_api_url: None,
+            push_fcm_project_id: None,
+            push_fcm_client_email: None,
+            push_fcm_private_key: None,
+            push_apns_key_id: None,
+            push_apns_team_id: None,
+            push_apns_private_key: None,
+            push_apns_topic: None,
+            push_apns_sandbox: None,
+            user_name_reserve_days: None,
+            permission_cache_ttl: None,
+            permission_change_revokes_sessions: None,
+            login_lockout_attempts: None,
+            login_lockout_window: None,
+            login_lockout_duration: None,
+            default_locale: None,
+            default_timezone: None,
+            ldap_auth_url: None,
+            ldap_auth_bind_dn: None,
+            ldap_auth_bind_password: None,
+            ldap_auth_base_dn: None,
+            ldap_auth_user_filter: None,
+            ldap_auth_attributes: None,
+            ldap_auth_group_mapping: None,
+        }
+    }
+
+    #[test]
+    fn test_secret_entropy_bits() {
+        assert_eq!(secret_entropy_bits(""), 0.0);
+        assert_eq!(secret_entropy_bits("aaaaaaaa"), 0.0);
+        assert_eq!(secret_entropy_bits("abcd"), 8.0);
+    }
+
+    #[test]
+    fn test_validate_config() {
+        // Expect
+        assert!(validate_config(&valid_config()).is_empty());
+
+        // When
+        let mut config = valid_config();
+        config.app_env = Some("prod".to_string());
+        config.cors_allow_origins = Some("*".to_string());
+        config.debug_errors = Some(true);
+        config.prefix = Some("api".to_string());
+        config.database_url = "mysql://localhost/core".to_string();
+        config.redis_url = "not a url".to_string();
+        config.session_store = Some("memcached".to_string());
+        config.redis_pool_max_size = Some(0);
+        config.jwt_secret = "a".repeat(40);
+        config.jwt_refresh_exp = 100;
+        config.api_deprecated_versions = Some("v9".to_string());
+        config.deprecated_route_sunset = Some("GET /auth/login@2027-01-31".to_string());
+        config.email_mailer = Some("smtp".to_string());
+        config.sms_provider = Some("vonage".to_string());
+        config.ldap_auth_url = Some("ldap://localhost:389".to_string());
+        config.default_timezone = Some("Mars/Olympus".to_string());
+        config.pii_encryption_key = Some("c2hvcnQ=".to_string());
+        config.tls_cert_path = Some("/nonexistent/cert.pem".to_string());
+        config.tls_key_path = Some("/nonexistent/key.pem".to_string());
+        let issues = validate_config(&config);
+
+        // Expect
+        let fields: Vec<&str> = issues.iter().map(|x| x.field).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "CORS_ALLOW_ORIGINS",
+                "DEBUG_ERRORS",
+                "PREFIX",
+                "DATABASE_URL",
+                "REDIS_URL",
+                "SESSION_STORE",
+                "REDIS_POOL_MAX_SIZE",
+                "JWT_SECRET",
+                "JWT_REFRESH_EXP",
+                "API_DEPRECATED_VERSIONS",
+                "DEPRECATED_ROUTE_SUNSET",
+                "EMAIL_MAILER",
+                "SMS_PROVIDER",
+                "LDAP_AUTH",
+                "DEFAULT_TIMEZONE",
+                "PII_ENCRYPTION_KEY",
+                "TLS_CERT_PATH",
+                "TLS_KEY_PATH"
+            ]
+        );
+    }
+}
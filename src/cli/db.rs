@@ -1,6 +1,10 @@
+use sqlx::PgPool;
 use tokio::process::Command;
 
-use crate::settings::Config;
+use crate::{
+    core::db::{plan_migrations, run_migrations, MigrationStep},
+    settings::Config,
+};
 
 pub async fn db_generate(migration_name: &String) {
     let _ = Command::new("sqlx")
@@ -24,24 +28,48 @@ pub async fn db_list(config: &Config) {
         .unwrap();
 }
 
-pub async fn db_migrate(config: &Config) {
-    let _ = Command::new("sqlx")
-        .arg("migrate")
-        .arg("run")
-        .arg("-D")
-        .arg(&config.database_url)
-        .status()
-        .await
-        .unwrap();
+/// Apply every pending migration embedded in this build
+pub async fn db_migrate(pool: &PgPool) -> anyhow::Result<Vec<MigrationStep>> {
+    run_migrations(pool, None).await
 }
 
-pub async fn db_revert(config: &Config) {
-    let _ = Command::new("sqlx")
-        .arg("migrate")
-        .arg("revert")
-        .arg("-D")
-        .arg(&config.database_url)
-        .status()
-        .await
-        .unwrap();
+/// Revert the latest applied migration embedded in this build, nothing when none is applied
+pub async fn db_revert(pool: &PgPool) -> anyhow::Result<Vec<MigrationStep>> {
+    // reverting everything lists the applied versions newest first
+    let applied = plan_migrations(pool, Some(0)).await?;
+    if applied.is_empty() {
+        return Ok(vec![]);
+    }
+    let target = applied.get(1).map(|x| x.version).unwrap_or(0);
+    run_migrations(pool, Some(target)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        cli::db::{db_migrate, db_revert},
+        core::db::{latest_migration_version, pending_migrations},
+    };
+
+    #[sqlx::test(migrations = false)]
+    async fn test_db_migrate_and_revert(pool: PgPool) -> anyhow::Result<()> {
+        // When
+        let applied = db_migrate(&pool).await?;
+
+        // Expect every embedded migration applied
+        assert!(!applied.is_empty());
+        assert!(pending_migrations(&pool).await?.is_empty());
+
+        // When
+        let reverted = db_revert(&pool).await?;
+
+        // Expect only the latest reverted
+        assert_eq!(reverted.len(), 1);
+        assert!(reverted[0].revert);
+        assert_eq!(Some(reverted[0].version), latest_migration_version());
+        assert_eq!(pending_migrations(&pool).await?.len(), 1);
+        Ok(())
+    }
 }
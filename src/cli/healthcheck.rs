@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use sqlx::postgres::PgPoolOptions;
+
+use crate::settings::Config;
+
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub async fn check_postgres(database_url: &str) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .acquire_timeout(HEALTHCHECK_TIMEOUT)
+        .connect(database_url)
+        .await?;
+    sqlx::query("SELECT 1").execute(&pool).await?;
+    pool.close().await;
+    Ok(())
+}
+
+pub async fn check_redis(redis_url: &str) -> anyhow::Result<()> {
+    let client = redis::Client::open(redis_url)?;
+    tokio::time::timeout(HEALTHCHECK_TIMEOUT, async {
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        redis::cmd("PING").exec_async(&mut conn).await
+    })
+    .await??;
+    Ok(())
+}
+
+/// Check every backing service, return (service name, result) pairs
+pub async fn healthcheck(config: &Config) -> Vec<(&'static str, anyhow::Result<()>)> {
+    vec![
+        ("postgres", check_postgres(&config.database_url).await),
+        ("redis", check_redis(&config.redis_url).await),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::healthcheck::{check_postgres, check_redis, healthcheck},
+        settings::get_config,
+    };
+
+    #[tokio::test]
+    async fn test_healthcheck() {
+        // When
+        let config = get_config();
+        let res = healthcheck(&config).await;
+
+        // Expect
+        assert!(res.iter().all(|(_, x)| x.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck_unreachable() {
+        // When
+        let postgres = check_postgres("postgres://postgres@127.0.0.1:1/core").await;
+        let redis = check_redis("redis://127.0.0.1:1/0").await;
+
+        // Expect
+        assert!(postgres.is_err());
+        assert!(redis.is_err());
+    }
+}
@@ -1,2 +1,12 @@
+pub mod admin;
+#[cfg(feature = "tui")]
+pub mod admin_tui;
 pub mod auth;
+pub mod cache;
+pub mod check_config;
 pub mod db;
+pub mod healthcheck;
+pub mod openapi;
+pub mod permission_sync;
+pub mod rbac;
+pub mod seed;
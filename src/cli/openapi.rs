@@ -0,0 +1,64 @@
+use crate::{
+    core::api_version::{versioned_prefix, API_VERSIONS},
+    init_openapi_service,
+};
+
+/// Render the OpenAPI json spec of one api version without starting the server
+pub fn export_openapi(prefix: &str, version: &str) -> anyhow::Result<String> {
+    if !API_VERSIONS.contains(&version) {
+        anyhow::bail!(
+            "unknown api version {}, expected one of {}",
+            version,
+            API_VERSIONS.join(", ")
+        );
+    }
+    Ok(init_openapi_service(&versioned_prefix(prefix, version), version).spec())
+}
+
+/// Write the OpenAPI json spec to the given path
+pub fn export_openapi_to_file(prefix: &str, version: &str, out: &str) -> anyhow::Result<()> {
+    std::fs::write(out, export_openapi(prefix, version)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::cli::openapi::{export_openapi, export_openapi_to_file};
+
+    #[test]
+    fn test_export_openapi() {
+        // When
+        let spec: Value = serde_json::from_str(&export_openapi("/api", "v2").unwrap()).unwrap();
+
+        // Expect
+        assert_eq!(spec["info"]["title"], "Core");
+        assert_eq!(spec["info"]["version"], "2.0");
+        assert_eq!(spec["servers"][0]["url"], "/api/v2");
+        assert!(spec["paths"]["/auth/login"].is_object());
+        assert!(spec["paths"]["/sso-provider"].is_object());
+        let bearer = &spec["components"]["securitySchemes"]["BearerAuthorization"];
+        assert_eq!(bearer["scheme"], "bearer");
+        assert_eq!(bearer["bearerFormat"], "JWT");
+        let login = &spec["components"]["schemas"]["LoginRequest"];
+        assert_eq!(login["example"]["user_name"], "admin");
+        assert!(spec["paths"]["/auth/login"]["post"]["summary"].is_string());
+        assert!(export_openapi("/api", "v9").is_err());
+    }
+
+    #[test]
+    fn test_export_openapi_to_file() {
+        // Given
+        let out = std::env::temp_dir().join(format!("openapi-{}.json", uuid::Uuid::now_v7()));
+        let out = out.to_str().unwrap();
+
+        // When
+        export_openapi_to_file("/", "v1", out).unwrap();
+
+        // Expect
+        let content = std::fs::read_to_string(out).unwrap();
+        std::fs::remove_file(out).unwrap();
+        assert_eq!(content, export_openapi("/", "v1").unwrap());
+    }
+}
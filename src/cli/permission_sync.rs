@@ -0,0 +1,487 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chrono::Local;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    cli::{
+        cache::grants_changed,
+        seed::{
+            get_or_create_rbac_entity, SeedPermission, SeedPermissionAttribute, SeedRbacEntity,
+        },
+    },
+    core::sqlx_utils::Sort,
+    model::{
+        permission::Permission, permission_attribute::PermissionAttribute,
+        permission_attribute_list::PermissionAttributeList, role::TABLE_NAME as ROLE_TABLE_NAME,
+        role_permission::RolePermission,
+    },
+    repository,
+};
+
+/// Declarative permissions, attributes and role grants, same item shape as the seed file
+#[derive(Debug, Deserialize)]
+pub struct PermissionManifest {
+    #[serde(default)]
+    pub permission_attributes: Vec<SeedPermissionAttribute>,
+    #[serde(default)]
+    pub permissions: Vec<SeedPermission>,
+    #[serde(default)]
+    pub roles: Vec<SeedRbacEntity>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SyncChange {
+    CreateAttribute(String),
+    UpdateAttribute(String),
+    DeleteAttribute(String),
+    CreatePermission(String),
+    UpdatePermission(String),
+    DeletePermission(String),
+    /// (permission, attribute)
+    AddPermissionAttribute(String, String),
+    RemovePermissionAttribute(String, String),
+    CreateRole(String),
+    /// (role, permission, attribute)
+    Grant(String, String, String),
+    Revoke(String, String, String),
+}
+
+impl fmt::Display for SyncChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncChange::CreateAttribute(name) => write!(f, "+ attribute {}", name),
+            SyncChange::UpdateAttribute(name) => write!(f, "~ attribute {}", name),
+            SyncChange::DeleteAttribute(name) => write!(f, "- attribute {}", name),
+            SyncChange::CreatePermission(name) => write!(f, "+ permission {}", name),
+            SyncChange::UpdatePermission(name) => write!(f, "~ permission {}", name),
+            SyncChange::DeletePermission(name) => write!(f, "- permission {}", name),
+            SyncChange::AddPermissionAttribute(permission, attribute) => {
+                write!(f, "+ permission {} attribute {}", permission, attribute)
+            }
+            SyncChange::RemovePermissionAttribute(permission, attribute) => {
+                write!(f, "- permission {} attribute {}", permission, attribute)
+            }
+            SyncChange::CreateRole(name) => write!(f, "+ role {}", name),
+            SyncChange::Grant(role, permission, attribute) => {
+                write!(f, "+ role {} grant {}:{}", role, permission, attribute)
+            }
+            SyncChange::Revoke(role, permission, attribute) => {
+                write!(f, "- role {} grant {}:{}", role, permission, attribute)
+            }
+        }
+    }
+}
+
+pub fn parse_permission_manifest(content: &str) -> anyhow::Result<PermissionManifest> {
+    Ok(serde_yaml::from_str(content)?)
+}
+
+fn lookup(names: &HashMap<Uuid, String>, id: &Uuid) -> String {
+    names.get(id).cloned().unwrap_or(id.to_string())
+}
+
+/// Reconcile database against the manifest and return the changes made.
+/// Without prune only missing or different rows are written, with prune
+/// permissions, attributes, attribute links and grants of listed roles absent
+/// from the manifest are deleted. On dry run the changes are rolled back.
+pub async fn sync_permissions(
+    pool: &PgPool,
+    manifest: &PermissionManifest,
+    prune: bool,
+    dry_run: bool,
+) -> anyhow::Result<Vec<SyncChange>> {
+    let mut tx = pool.begin().await?;
+    let now = Local::now().fixed_offset();
+    let mut changes: Vec<SyncChange> = vec![];
+
+    let (existing_attributes, _, _) =
+        repository::permission_attribute::get_all_permission_attribute(
+            &mut tx,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(true),
+        )
+        .await?;
+    let (existing_permissions, _, _) = repository::permission::get_all_permission(
+        &mut tx,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(true),
+        Sort::default(),
+    )
+    .await?;
+    let existing_permissions: Vec<_> = existing_permissions.into_iter().map(|x| x.data).collect();
+    let mut attribute_names: HashMap<Uuid, String> = existing_attributes
+        .iter()
+        .map(|x| (x.id, x.name.clone()))
+        .collect();
+    let mut permission_names: HashMap<Uuid, String> = existing_permissions
+        .iter()
+        .map(|x| (x.id, x.permission_name.clone()))
+        .collect();
+
+    // Permission attributes
+    let mut attributes: HashMap<String, Uuid> = HashMap::new();
+    for item in manifest.permission_attributes.iter() {
+        match existing_attributes.iter().find(|x| x.name == item.name) {
+            Some(attribute) => {
+                if attribute.description != item.description || attribute.category != item.category
+                {
+                    let mut attribute = attribute.clone();
+                    attribute.description = item.description.clone();
+                    attribute.category = item.category.clone();
+                    attribute.updated_date = Some(now);
+                    repository::permission_attribute::update_permission_attribute(
+                        &mut tx, &attribute,
+                    )
+                    .await?;
+                    changes.push(SyncChange::UpdateAttribute(item.name.clone()));
+                }
+                attributes.insert(item.name.clone(), attribute.id);
+            }
+            None => {
+                let attribute = PermissionAttribute {
+                    id: Uuid::now_v7(),
+                    name: item.name.clone(),
+                    description: item.description.clone(),
+                    category: item.category.clone(),
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                    deleted_date: None,
+                };
+                repository::permission_attribute::create_permission_attribute(&mut tx, &attribute)
+                    .await?;
+                changes.push(SyncChange::CreateAttribute(item.name.clone()));
+                attribute_names.insert(attribute.id, attribute.name.clone());
+                attributes.insert(item.name.clone(), attribute.id);
+            }
+        }
+    }
+
+    // Permissions and their available attributes
+    let mut permissions: HashMap<String, Uuid> = HashMap::new();
+    for item in manifest.permissions.iter() {
+        let permission_id = match existing_permissions
+            .iter()
+            .find(|x| x.permission_name == item.name)
+        {
+            Some(permission) => {
+                if permission.description != item.description
+                    || permission.is_user != item.is_user
+                    || permission.is_role != item.is_role
+                    || permission.is_group != item.is_group
+                {
+                    let mut permission = permission.clone();
+                    permission.description = item.description.clone();
+                    permission.is_user = item.is_user;
+                    permission.is_role = item.is_role;
+                    permission.is_group = item.is_group;
+                    permission.updated_date = Some(now);
+                    repository::permission::update_permission(&mut tx, &permission).await?;
+                    changes.push(SyncChange::UpdatePermission(item.name.clone()));
+                }
+                permission.id
+            }
+            None => {
+                let permission = Permission {
+                    id: Uuid::now_v7(),
+                    permission_name: item.name.clone(),
+                    is_user: item.is_user,
+                    is_role: item.is_role,
+                    is_group: item.is_group,
+                    description: item.description.clone(),
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                };
+                repository::permission::create_permission(&mut tx, &permission).await?;
+                changes.push(SyncChange::CreatePermission(item.name.clone()));
+                permission_names.insert(permission.id, permission.permission_name.clone());
+                permission.id
+            }
+        };
+        permissions.insert(item.name.clone(), permission_id);
+
+        let current = repository::permission_attribute_list::get_all_permission_attribute_list(
+            &mut tx,
+            Some(&permission_id),
+            None,
+        )
+        .await?;
+        let mut wanted: HashSet<Uuid> = HashSet::new();
+        for attribute in item.attributes.iter() {
+            let attribute_id = match attributes.get(attribute) {
+                Some(val) => *val,
+                None => anyhow::bail!(
+                    "permission {}: attribute {} is not in manifest",
+                    item.name,
+                    attribute
+                ),
+            };
+            wanted.insert(attribute_id);
+            if current.iter().any(|x| x.attribute_id == attribute_id) {
+                continue;
+            }
+            repository::permission_attribute_list::create_permission_attribute_list(
+                &mut tx,
+                &PermissionAttributeList {
+                    permission_id,
+                    attribute_id,
+                },
+            )
+            .await?;
+            changes.push(SyncChange::AddPermissionAttribute(
+                item.name.clone(),
+                attribute.clone(),
+            ));
+        }
+        if prune {
+            for link in current.iter().filter(|x| !wanted.contains(&x.attribute_id)) {
+                repository::permission_attribute_list::delete_permission_attribute_list(
+                    &mut tx, link,
+                )
+                .await?;
+                changes.push(SyncChange::RemovePermissionAttribute(
+                    item.name.clone(),
+                    lookup(&attribute_names, &link.attribute_id),
+                ));
+            }
+        }
+    }
+
+    // Role grants
+    for item in manifest.roles.iter() {
+        let mut wanted: HashSet<(Uuid, Uuid)> = HashSet::new();
+        for grant in item.permissions.iter() {
+            let permission_id = match permissions.get(&grant.permission) {
+                Some(val) => *val,
+                None => anyhow::bail!(
+                    "role {}: permission {} is not in manifest",
+                    item.name,
+                    grant.permission
+                ),
+            };
+            for attribute in grant.attributes.iter() {
+                match attributes.get(attribute) {
+                    Some(attribute_id) => {
+                        wanted.insert((permission_id, *attribute_id));
+                    }
+                    None => anyhow::bail!(
+                        "role {}: attribute {} is not in manifest",
+                        item.name,
+                        attribute
+                    ),
+                }
+            }
+        }
+        let (role_id, created) =
+            get_or_create_rbac_entity(&mut tx, ROLE_TABLE_NAME, "role_name", item, &now).await?;
+        if created {
+            changes.push(SyncChange::CreateRole(item.name.clone()));
+        }
+        let (current, _, _) = repository::role_permission::get_all_role_permission(
+            &mut tx,
+            None,
+            None,
+            &role_id,
+            Some(true),
+        )
+        .await?;
+        let mut grants: Vec<&(Uuid, Uuid)> = wanted.iter().collect();
+        grants.sort();
+        for (permission_id, attribute_id) in grants {
+            if current
+                .iter()
+                .any(|x| x.permission_id == *permission_id && x.attribute_id == *attribute_id)
+            {
+                continue;
+            }
+            repository::role_permission::create_role_permission(
+                &mut tx,
+                &RolePermission {
+                    role_id,
+                    permission_id: *permission_id,
+                    attribute_id: *attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                },
+            )
+            .await?;
+            changes.push(SyncChange::Grant(
+                item.name.clone(),
+                lookup(&permission_names, permission_id),
+                lookup(&attribute_names, attribute_id),
+            ));
+        }
+        if prune {
+            for role_permission in current
+                .iter()
+                .filter(|x| !wanted.contains(&(x.permission_id, x.attribute_id)))
+            {
+                repository::role_permission::delete_role_permission(&mut tx, role_permission)
+                    .await?;
+                changes.push(SyncChange::Revoke(
+                    item.name.clone(),
+                    lookup(&permission_names, &role_permission.permission_id),
+                    lookup(&attribute_names, &role_permission.attribute_id),
+                ));
+            }
+        }
+    }
+
+    // Anything left out of the manifest, grants and links cascade
+    if prune {
+        for permission in existing_permissions
+            .iter()
+            .filter(|x| !permissions.contains_key(&x.permission_name))
+        {
+            repository::permission::delete_permission(&mut tx, permission).await?;
+            changes.push(SyncChange::DeletePermission(
+                permission.permission_name.clone(),
+            ));
+        }
+        for attribute in existing_attributes
+            .iter()
+            .filter(|x| !attributes.contains_key(&x.name))
+        {
+            repository::permission_attribute::delete_permission_attribute(&mut tx, attribute)
+                .await?;
+            changes.push(SyncChange::DeleteAttribute(attribute.name.clone()));
+        }
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+        grants_changed(None).await;
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::cli::permission_sync::{parse_permission_manifest, sync_permissions, SyncChange};
+
+    const MANIFEST: &str = r#"
+permission_attributes:
+  - name: read
+  - name: update
+permissions:
+  - name: user
+    is_user: true
+    attributes: [read, update]
+roles:
+  - name: operator
+    permissions:
+      - permission: user
+        attributes: [read]
+"#;
+
+    #[sqlx::test]
+    async fn test_sync_permissions(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let manifest = parse_permission_manifest(MANIFEST)?;
+
+        // When dry run
+        let planned = sync_permissions(&pool, &manifest, false, true).await?;
+
+        // Expect nothing written
+        assert_eq!(planned.len(), 7);
+        let count: (i64,) = sqlx::query_as("SELECT count(*) FROM public.permission")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count.0, 0);
+
+        // When
+        let applied = sync_permissions(&pool, &manifest, false, false).await?;
+        let again = sync_permissions(&pool, &manifest, true, false).await?;
+
+        // Expect
+        assert_eq!(applied, planned);
+        assert!(again.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_sync_permissions_prune(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        sync_permissions(&pool, &parse_permission_manifest(MANIFEST)?, false, false).await?;
+        let manifest = parse_permission_manifest(
+            r#"
+permission_attributes:
+  - name: read
+permissions:
+  - name: user
+    is_user: true
+    attributes: [read]
+roles:
+  - name: operator
+"#,
+        )?;
+
+        // When without prune
+        let changes = sync_permissions(&pool, &manifest, false, false).await?;
+
+        // Expect
+        assert!(changes.is_empty());
+
+        // When
+        let changes = sync_permissions(&pool, &manifest, true, false).await?;
+
+        // Expect
+        assert_eq!(
+            changes,
+            vec![
+                SyncChange::RemovePermissionAttribute("user".to_string(), "update".to_string()),
+                SyncChange::Revoke(
+                    "operator".to_string(),
+                    "user".to_string(),
+                    "read".to_string()
+                ),
+                SyncChange::DeleteAttribute("update".to_string()),
+            ]
+        );
+        let count: (i64,) = sqlx::query_as("SELECT count(*) FROM public.role_permissions")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count.0, 0);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_sync_permissions_unknown_reference(pool: PgPool) -> anyhow::Result<()> {
+        // When
+        let manifest = parse_permission_manifest(
+            "roles:\n  - name: ops\n    permissions:\n      - permission: missing\n        attributes: [read]\n",
+        )?;
+        let res = sync_permissions(&pool, &manifest, false, false).await;
+
+        // Expect
+        assert!(res.is_err());
+        let count: (i64,) = sqlx::query_as("SELECT count(*) FROM public.role")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count.0, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,736 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    cli::cache::grants_changed,
+    core::sqlx_utils::Sort,
+    model::{
+        group::TABLE_NAME as GROUP_TABLE_NAME, group_permission::GroupPermission,
+        permission::Permission, permission_attribute::PermissionAttribute,
+        permission_attribute_list::PermissionAttributeList, role::TABLE_NAME as ROLE_TABLE_NAME,
+        role_permission::RolePermission,
+    },
+    repository,
+};
+
+/// Bumped when the backup layout changes, import refuses other versions
+pub const RBAC_BACKUP_VERSION: u32 = 1;
+
+/// Roles, groups, permissions and attributes keyed by name so the
+/// backup can be restored into another environment with different ids
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RbacBackup {
+    pub version: u32,
+    pub exported_at: DateTime<FixedOffset>,
+    pub permission_attributes: Vec<RbacAttribute>,
+    pub permissions: Vec<RbacPermission>,
+    pub roles: Vec<RbacEntity>,
+    pub groups: Vec<RbacEntity>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RbacAttribute {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RbacPermission {
+    pub name: String,
+    pub description: Option<String>,
+    pub is_user: Option<bool>,
+    pub is_role: Option<bool>,
+    pub is_group: Option<bool>,
+    /// Attribute names available on the permission
+    pub attributes: Vec<String>,
+}
+
+/// Role or group with its grants, parent is only set on groups
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RbacEntity {
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub permissions: Vec<RbacGrant>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RbacGrant {
+    pub permission: String,
+    pub attributes: Vec<String>,
+}
+
+/// Rows written by an import, unchanged rows are not counted
+#[derive(Debug, Default, PartialEq)]
+pub struct RbacImportSummary {
+    pub permission_attributes: u32,
+    pub permissions: u32,
+    pub roles: u32,
+    pub groups: u32,
+    pub links_added: u32,
+    pub links_removed: u32,
+}
+
+impl fmt::Display for RbacImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "permission attributes: {}, permissions: {}, roles: {}, groups: {}, links added: {}, links removed: {}",
+            self.permission_attributes,
+            self.permissions,
+            self.roles,
+            self.groups,
+            self.links_added,
+            self.links_removed
+        )
+    }
+}
+
+pub fn parse_rbac_backup(content: &str) -> anyhow::Result<RbacBackup> {
+    let backup: RbacBackup = serde_json::from_str(content)?;
+    if backup.version != RBAC_BACKUP_VERSION {
+        anyhow::bail!(
+            "unsupported backup version {}, expected {}",
+            backup.version,
+            RBAC_BACKUP_VERSION
+        );
+    }
+    Ok(backup)
+}
+
+/// Group (permission id, attribute id) pairs into grants sorted by name
+fn grants_by_name(
+    links: impl Iterator<Item = (Uuid, Uuid)>,
+    permission_names: &HashMap<Uuid, String>,
+    attribute_names: &HashMap<Uuid, String>,
+) -> Vec<RbacGrant> {
+    let mut grouped: HashMap<String, Vec<String>> = HashMap::new();
+    for (permission_id, attribute_id) in links {
+        if let (Some(permission), Some(attribute)) = (
+            permission_names.get(&permission_id),
+            attribute_names.get(&attribute_id),
+        ) {
+            grouped
+                .entry(permission.clone())
+                .or_default()
+                .push(attribute.clone());
+        }
+    }
+    let mut grants: Vec<RbacGrant> = grouped
+        .into_iter()
+        .map(|(permission, mut attributes)| {
+            attributes.sort();
+            RbacGrant {
+                permission,
+                attributes,
+            }
+        })
+        .collect();
+    grants.sort_by(|a, b| a.permission.cmp(&b.permission));
+    grants
+}
+
+/// Dump every permission attribute, permission, role and group (soft
+/// deleted excluded) with their link tables, users are left out
+pub async fn export_rbac(pool: &PgPool) -> anyhow::Result<RbacBackup> {
+    let mut tx = pool.begin().await?;
+    let (attributes, _, _) = repository::permission_attribute::get_all_permission_attribute(
+        &mut tx,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(true),
+    )
+    .await?;
+    let (permissions, _, _) = repository::permission::get_all_permission(
+        &mut tx,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(true),
+        Sort::default(),
+    )
+    .await?;
+    let permissions: Vec<_> = permissions.into_iter().map(|x| x.data).collect();
+    let attribute_names: HashMap<Uuid, String> =
+        attributes.iter().map(|x| (x.id, x.name.clone())).collect();
+    let permission_names: HashMap<Uuid, String> = permissions
+        .iter()
+        .map(|x| (x.id, x.permission_name.clone()))
+        .collect();
+
+    let links = repository::permission_attribute_list::get_all_permission_attribute_list(
+        &mut tx, None, None,
+    )
+    .await?;
+    let mut rbac_permissions: Vec<RbacPermission> = permissions
+        .iter()
+        .map(|x| {
+            let mut attributes: Vec<String> = links
+                .iter()
+                .filter(|link| link.permission_id == x.id)
+                .filter_map(|link| attribute_names.get(&link.attribute_id).cloned())
+                .collect();
+            attributes.sort();
+            RbacPermission {
+                name: x.permission_name.clone(),
+                description: x.description.clone(),
+                is_user: x.is_user,
+                is_role: x.is_role,
+                is_group: x.is_group,
+                attributes,
+            }
+        })
+        .collect();
+    rbac_permissions.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut rbac_attributes: Vec<RbacAttribute> = attributes
+        .into_iter()
+        .map(|x| RbacAttribute {
+            name: x.name,
+            description: x.description,
+            category: x.category,
+        })
+        .collect();
+    rbac_attributes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut roles: Vec<RbacEntity> = vec![];
+    for role in repository::role::get_all_role(&mut tx).await? {
+        let (grants, _, _) = repository::role_permission::get_all_role_permission(
+            &mut tx,
+            None,
+            None,
+            &role.id,
+            Some(true),
+        )
+        .await?;
+        roles.push(RbacEntity {
+            name: role.role_name,
+            description: role.description,
+            is_active: role.is_active,
+            parent: None,
+            permissions: grants_by_name(
+                grants.iter().map(|x| (x.permission_id, x.attribute_id)),
+                &permission_names,
+                &attribute_names,
+            ),
+        });
+    }
+    roles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let groups = repository::group::get_all_group(&mut tx).await?;
+    let group_names: HashMap<Uuid, String> = groups
+        .iter()
+        .map(|x| (x.id, x.group_name.clone()))
+        .collect();
+    let parents: HashMap<Uuid, Uuid> = sqlx::query_as::<_, (Uuid, Uuid)>(
+        format!(
+            "SELECT id, parent_id FROM {} WHERE parent_id IS NOT NULL",
+            GROUP_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .collect();
+    let mut rbac_groups: Vec<RbacEntity> = vec![];
+    for group in groups.iter() {
+        let (grants, _, _) = repository::group_permission::get_all_group_permission(
+            &mut tx,
+            None,
+            None,
+            &group.id,
+            Some(true),
+        )
+        .await?;
+        rbac_groups.push(RbacEntity {
+            name: group.group_name.clone(),
+            description: group.description.clone(),
+            is_active: group.is_active,
+            parent: parents
+                .get(&group.id)
+                .and_then(|x| group_names.get(x))
+                .cloned(),
+            permissions: grants_by_name(
+                grants.iter().map(|x| (x.permission_id, x.attribute_id)),
+                &permission_names,
+                &attribute_names,
+            ),
+        });
+    }
+    rbac_groups.sort_by(|a, b| a.name.cmp(&b.name));
+    tx.commit().await?;
+
+    Ok(RbacBackup {
+        version: RBAC_BACKUP_VERSION,
+        exported_at: Local::now().fixed_offset(),
+        permission_attributes: rbac_attributes,
+        permissions: rbac_permissions,
+        roles,
+        groups: rbac_groups,
+    })
+}
+
+/// (id, description, is_active, deleted_date)
+type ExistingRbacEntity = (
+    Uuid,
+    Option<String>,
+    Option<bool>,
+    Option<DateTime<FixedOffset>>,
+);
+
+/// Insert or update a role or group by name, a soft deleted row with the
+/// same name is restored since names are unique. Return (id, written).
+async fn upsert_rbac_entity(
+    tx: &mut Transaction<'_, Postgres>,
+    table_name: &str,
+    name_column: &str,
+    entity: &RbacEntity,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<(Uuid, bool)> {
+    let existing: Option<ExistingRbacEntity> = sqlx::query_as(
+        format!(
+            "SELECT id, description, is_active, deleted_date FROM {} WHERE {} = $1",
+            table_name, name_column
+        )
+        .as_str(),
+    )
+    .bind(&entity.name)
+    .fetch_optional(&mut **tx)
+    .await?;
+    match existing {
+        Some((id, description, is_active, deleted_date)) => {
+            if description == entity.description
+                && is_active == entity.is_active
+                && deleted_date.is_none()
+            {
+                return Ok((id, false));
+            }
+            sqlx::query(
+                format!(
+                    r#"UPDATE {} SET description = $1, is_active = $2, updated_by = NULL,
+                    updated_date = $3, deleted_date = NULL, deleted_by = NULL WHERE id = $4"#,
+                    table_name
+                )
+                .as_str(),
+            )
+            .bind(&entity.description)
+            .bind(entity.is_active)
+            .bind(now)
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+            Ok((id, true))
+        }
+        None => {
+            let id = Uuid::now_v7();
+            sqlx::query(
+                format!(
+                    r#"INSERT INTO {} (id, {}, description, is_active, created_by, updated_by,
+                    created_date, updated_date, deleted_date)
+                    VALUES ($1, $2, $3, $4, NULL, NULL, $5, $5, NULL)"#,
+                    table_name, name_column
+                )
+                .as_str(),
+            )
+            .bind(id)
+            .bind(&entity.name)
+            .bind(&entity.description)
+            .bind(entity.is_active)
+            .bind(now)
+            .execute(&mut **tx)
+            .await?;
+            Ok((id, true))
+        }
+    }
+}
+
+/// Resolve grants to (permission id, attribute id) pairs, failing on names missing from the backup
+fn resolve_grants(
+    entity: &RbacEntity,
+    permissions: &HashMap<String, Uuid>,
+    attributes: &HashMap<String, Uuid>,
+) -> anyhow::Result<HashSet<(Uuid, Uuid)>> {
+    let mut res = HashSet::new();
+    for grant in entity.permissions.iter() {
+        let permission_id = match permissions.get(&grant.permission) {
+            Some(val) => *val,
+            None => anyhow::bail!(
+                "{}: permission {} is not in backup",
+                entity.name,
+                grant.permission
+            ),
+        };
+        for attribute in grant.attributes.iter() {
+            match attributes.get(attribute) {
+                Some(attribute_id) => {
+                    res.insert((permission_id, *attribute_id));
+                }
+                None => anyhow::bail!("{}: attribute {} is not in backup", entity.name, attribute),
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Restore a backup matching rows by name. Every entity in the backup ends
+/// up with exactly the attributes, grants and parent it had on export;
+/// rows absent from the backup are left untouched. On dry run the changes
+/// are rolled back.
+pub async fn import_rbac(
+    pool: &PgPool,
+    backup: &RbacBackup,
+    dry_run: bool,
+) -> anyhow::Result<RbacImportSummary> {
+    let mut tx = pool.begin().await?;
+    let now = Local::now().fixed_offset();
+    let mut summary = RbacImportSummary::default();
+
+    // Permission attributes
+    let (existing_attributes, _, _) =
+        repository::permission_attribute::get_all_permission_attribute(
+            &mut tx,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(true),
+        )
+        .await?;
+    let mut attributes: HashMap<String, Uuid> = HashMap::new();
+    for item in backup.permission_attributes.iter() {
+        match existing_attributes.iter().find(|x| x.name == item.name) {
+            Some(attribute) => {
+                if attribute.description != item.description || attribute.category != item.category
+                {
+                    let mut attribute = attribute.clone();
+                    attribute.description = item.description.clone();
+                    attribute.category = item.category.clone();
+                    attribute.updated_date = Some(now);
+                    repository::permission_attribute::update_permission_attribute(
+                        &mut tx, &attribute,
+                    )
+                    .await?;
+                    summary.permission_attributes += 1;
+                }
+                attributes.insert(item.name.clone(), attribute.id);
+            }
+            None => {
+                let attribute = PermissionAttribute {
+                    id: Uuid::now_v7(),
+                    name: item.name.clone(),
+                    description: item.description.clone(),
+                    category: item.category.clone(),
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                    deleted_date: None,
+                };
+                repository::permission_attribute::create_permission_attribute(&mut tx, &attribute)
+                    .await?;
+                summary.permission_attributes += 1;
+                attributes.insert(item.name.clone(), attribute.id);
+            }
+        }
+    }
+
+    // Permissions and their available attributes
+    let (existing_permissions, _, _) = repository::permission::get_all_permission(
+        &mut tx,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(true),
+        Sort::default(),
+    )
+    .await?;
+    let existing_permissions: Vec<_> = existing_permissions.into_iter().map(|x| x.data).collect();
+    let mut permissions: HashMap<String, Uuid> = HashMap::new();
+    for item in backup.permissions.iter() {
+        let permission_id = match existing_permissions
+            .iter()
+            .find(|x| x.permission_name == item.name)
+        {
+            Some(permission) => {
+                if permission.description != item.description
+                    || permission.is_user != item.is_user
+                    || permission.is_role != item.is_role
+                    || permission.is_group != item.is_group
+                {
+                    let mut permission = permission.clone();
+                    permission.description = item.description.clone();
+                    permission.is_user = item.is_user;
+                    permission.is_role = item.is_role;
+                    permission.is_group = item.is_group;
+                    permission.updated_date = Some(now);
+                    repository::permission::update_permission(&mut tx, &permission).await?;
+                    summary.permissions += 1;
+                }
+                permission.id
+            }
+            None => {
+                let permission = Permission {
+                    id: Uuid::now_v7(),
+                    permission_name: item.name.clone(),
+                    is_user: item.is_user,
+                    is_role: item.is_role,
+                    is_group: item.is_group,
+                    description: item.description.clone(),
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                };
+                repository::permission::create_permission(&mut tx, &permission).await?;
+                summary.permissions += 1;
+                permission.id
+            }
+        };
+        permissions.insert(item.name.clone(), permission_id);
+
+        let mut wanted: HashSet<Uuid> = HashSet::new();
+        for attribute in item.attributes.iter() {
+            match attributes.get(attribute) {
+                Some(attribute_id) => {
+                    wanted.insert(*attribute_id);
+                }
+                None => anyhow::bail!(
+                    "permission {}: attribute {} is not in backup",
+                    item.name,
+                    attribute
+                ),
+            }
+        }
+        let current = repository::permission_attribute_list::get_all_permission_attribute_list(
+            &mut tx,
+            Some(&permission_id),
+            None,
+        )
+        .await?;
+        for link in current.iter().filter(|x| !wanted.contains(&x.attribute_id)) {
+            repository::permission_attribute_list::delete_permission_attribute_list(&mut tx, link)
+                .await?;
+            summary.links_removed += 1;
+        }
+        for attribute_id in wanted.iter() {
+            if current.iter().any(|x| x.attribute_id == *attribute_id) {
+                continue;
+            }
+            repository::permission_attribute_list::create_permission_attribute_list(
+                &mut tx,
+                &PermissionAttributeList {
+                    permission_id,
+                    attribute_id: *attribute_id,
+                },
+            )
+            .await?;
+            summary.links_added += 1;
+        }
+    }
+
+    // Role grants
+    for item in backup.roles.iter() {
+        let wanted = resolve_grants(item, &permissions, &attributes)?;
+        let (role_id, written) =
+            upsert_rbac_entity(&mut tx, ROLE_TABLE_NAME, "role_name", item, &now).await?;
+        if written {
+            summary.roles += 1;
+        }
+        let (current, _, _) = repository::role_permission::get_all_role_permission(
+            &mut tx,
+            None,
+            None,
+            &role_id,
+            Some(true),
+        )
+        .await?;
+        for role_permission in current
+            .iter()
+            .filter(|x| !wanted.contains(&(x.permission_id, x.attribute_id)))
+        {
+            repository::role_permission::delete_role_permission(&mut tx, role_permission).await?;
+            summary.links_removed += 1;
+        }
+        for (permission_id, attribute_id) in wanted.iter() {
+            if current
+                .iter()
+                .any(|x| x.permission_id == *permission_id && x.attribute_id == *attribute_id)
+            {
+                continue;
+            }
+            repository::role_permission::create_role_permission(
+                &mut tx,
+                &RolePermission {
+                    role_id,
+                    permission_id: *permission_id,
+                    attribute_id: *attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                },
+            )
+            .await?;
+            summary.links_added += 1;
+        }
+    }
+
+    // Group grants, parents are linked once every group exists
+    let mut groups: HashMap<String, Uuid> = HashMap::new();
+    for item in backup.groups.iter() {
+        let wanted = resolve_grants(item, &permissions, &attributes)?;
+        let (group_id, written) =
+            upsert_rbac_entity(&mut tx, GROUP_TABLE_NAME, "group_name", item, &now).await?;
+        if written {
+            summary.groups += 1;
+        }
+        groups.insert(item.name.clone(), group_id);
+        let (current, _, _) = repository::group_permission::get_all_group_permission(
+            &mut tx,
+            None,
+            None,
+            &group_id,
+            Some(true),
+        )
+        .await?;
+        for group_permission in current
+            .iter()
+            .filter(|x| !wanted.contains(&(x.permission_id, x.attribute_id)))
+        {
+            repository::group_permission::delete_group_permission(&mut tx, group_permission)
+                .await?;
+            summary.links_removed += 1;
+        }
+        for (permission_id, attribute_id) in wanted.iter() {
+            if current
+                .iter()
+                .any(|x| x.permission_id == *permission_id && x.attribute_id == *attribute_id)
+            {
+                continue;
+            }
+            repository::group_permission::create_group_permission(
+                &mut tx,
+                &GroupPermission {
+                    group_id,
+                    permission_id: *permission_id,
+                    attribute_id: *attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                },
+            )
+            .await?;
+            summary.links_added += 1;
+        }
+    }
+    for item in backup.groups.iter() {
+        let parent_id = match &item.parent {
+            Some(parent) => match groups.get(parent) {
+                Some(val) => Some(*val),
+                None => anyhow::bail!("group {}: parent {} is not in backup", item.name, parent),
+            },
+            None => None,
+        };
+        let res = sqlx::query(
+            format!(
+                "UPDATE {} SET parent_id = $1 WHERE id = $2 AND parent_id IS DISTINCT FROM $1",
+                GROUP_TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(parent_id)
+        .bind(groups[&item.name])
+        .execute(&mut *tx)
+        .await?;
+        summary.links_added += res.rows_affected() as u32;
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+        grants_changed(None).await;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::cli::{
+        rbac::{export_rbac, import_rbac, parse_rbac_backup, RbacImportSummary},
+        seed::{parse_seed_data, seed, DEFAULT_SEED},
+    };
+
+    #[sqlx::test]
+    async fn test_export_import_rbac(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        seed(&pool, &parse_seed_data(DEFAULT_SEED)?).await?;
+        sqlx::query(
+            r#"INSERT INTO public."group" (id, group_name, is_active, parent_id)
+            SELECT $1, 'child', true, id FROM public."group" LIMIT 1"#,
+        )
+        .bind(uuid::Uuid::now_v7())
+        .execute(&pool)
+        .await?;
+        let backup = export_rbac(&pool).await?;
+        let content = serde_json::to_string_pretty(&backup)?;
+        assert!(!backup.roles.is_empty());
+        assert!(backup.groups.iter().any(|x| x.parent.is_some()));
+
+        // When restored into itself
+        let unchanged = import_rbac(&pool, &parse_rbac_backup(&content)?, false).await?;
+
+        // Expect
+        assert_eq!(unchanged, RbacImportSummary::default());
+
+        // When restored into an empty database
+        sqlx::query(
+            r#"TRUNCATE public.permission_attribute, public.permission, public.role,
+            public."group" CASCADE"#,
+        )
+        .execute(&pool)
+        .await?;
+        let planned = import_rbac(&pool, &backup, true).await?;
+        let count: (i64,) = sqlx::query_as("SELECT count(*) FROM public.role")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count.0, 0);
+        let restored = import_rbac(&pool, &backup, false).await?;
+
+        // Expect same content
+        assert_eq!(planned, restored);
+        assert_eq!(restored.roles as usize, backup.roles.len());
+        assert_eq!(restored.groups as usize, backup.groups.len());
+        let again = export_rbac(&pool).await?;
+        assert_eq!(again.permission_attributes, backup.permission_attributes);
+        assert_eq!(again.permissions, backup.permissions);
+        assert_eq!(again.roles, backup.roles);
+        assert_eq!(again.groups, backup.groups);
+        assert!(parse_rbac_backup(&content.replace("\"version\": 1", "\"version\": 9")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    cli::cache::grants_changed,
+    model::{
+        group::TABLE_NAME as GROUP_TABLE_NAME, group_permission::GroupPermission,
+        permission::Permission, permission_attribute::PermissionAttribute,
+        permission_attribute_list::PermissionAttributeList, role::TABLE_NAME as ROLE_TABLE_NAME,
+        role_permission::RolePermission,
+    },
+    repository,
+};
+
+/// Baseline data loaded by `cli seed` when no file is given
+pub const DEFAULT_SEED: &str = include_str!("seed.json");
+
+#[derive(Debug, Deserialize)]
+pub struct SeedData {
+    #[serde(default)]
+    pub permission_attributes: Vec<SeedPermissionAttribute>,
+    #[serde(default)]
+    pub permissions: Vec<SeedPermission>,
+    #[serde(default)]
+    pub roles: Vec<SeedRbacEntity>,
+    #[serde(default)]
+    pub groups: Vec<SeedRbacEntity>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedPermissionAttribute {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedPermission {
+    pub name: String,
+    pub description: Option<String>,
+    pub is_user: Option<bool>,
+    pub is_role: Option<bool>,
+    pub is_group: Option<bool>,
+    /// Attribute names available on the permission
+    #[serde(default)]
+    pub attributes: Vec<String>,
+}
+
+/// Role or group with the permissions granted to it
+#[derive(Debug, Deserialize)]
+pub struct SeedRbacEntity {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub permissions: Vec<SeedGrant>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedGrant {
+    pub permission: String,
+    pub attributes: Vec<String>,
+}
+
+/// Number of rows inserted, existing rows are never counted nor modified
+#[derive(Debug, Default, PartialEq)]
+pub struct SeedSummary {
+    pub permission_attributes: u32,
+    pub permissions: u32,
+    pub roles: u32,
+    pub groups: u32,
+    pub grants: u32,
+}
+
+pub fn parse_seed_data(content: &str) -> anyhow::Result<SeedData> {
+    Ok(serde_json::from_str(content)?)
+}
+
+/// Resolve grants to (permission id, attribute id) pairs, failing on unknown names
+fn resolve_grants(
+    grants: &[SeedGrant],
+    owner: &str,
+    permissions: &HashMap<String, Uuid>,
+    attributes: &HashMap<String, Uuid>,
+) -> anyhow::Result<Vec<(Uuid, Uuid)>> {
+    let mut res = vec![];
+    for grant in grants {
+        let permission_id = match permissions.get(&grant.permission) {
+            Some(val) => *val,
+            None => anyhow::bail!("{}: unknown permission {}", owner, grant.permission),
+        };
+        for attribute in grant.attributes.iter() {
+            match attributes.get(attribute) {
+                Some(attribute_id) => res.push((permission_id, *attribute_id)),
+                None => anyhow::bail!("{}: unknown permission attribute {}", owner, attribute),
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Id of the active role or group with the given name, inserted when missing
+pub(crate) async fn get_or_create_rbac_entity(
+    tx: &mut Transaction<'_, Postgres>,
+    table_name: &str,
+    name_column: &str,
+    entity: &SeedRbacEntity,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<(Uuid, bool)> {
+    let existing: Option<(Uuid,)> = sqlx::query_as(
+        format!(
+            "SELECT id FROM {} WHERE {} = $1 AND deleted_date IS NULL",
+            table_name, name_column
+        )
+        .as_str(),
+    )
+    .bind(&entity.name)
+    .fetch_optional(&mut **tx)
+    .await?;
+    if let Some((id,)) = existing {
+        return Ok((id, false));
+    }
+    let id = Uuid::now_v7();
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, {}, description, is_active, created_by, updated_by,
+            created_date, updated_date, deleted_date)
+            VALUES ($1, $2, $3, true, NULL, NULL, $4, $4, NULL)"#,
+            table_name, name_column
+        )
+        .as_str(),
+    )
+    .bind(id)
+    .bind(&entity.name)
+    .bind(&entity.description)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok((id, true))
+}
+
+/// Insert missing seed rows, safe to run any number of times
+pub async fn seed(pool: &PgPool, data: &SeedData) -> anyhow::Result<SeedSummary> {
+    let mut tx = pool.begin().await?;
+    let now = Local::now().fixed_offset();
+    let mut summary = SeedSummary::default();
+
+    let mut attributes: HashMap<String, Uuid> = HashMap::new();
+    for item in data.permission_attributes.iter() {
+        let attribute =
+            repository::permission_attribute::get_permission_attribute_by_name(&mut tx, &item.name)
+                .await?;
+        let id = match attribute {
+            Some(val) => val.id,
+            None => {
+                let new_attribute = PermissionAttribute {
+                    id: Uuid::now_v7(),
+                    name: item.name.clone(),
+                    description: item.description.clone(),
+                    category: item.category.clone(),
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                    deleted_date: None,
+                };
+                repository::permission_attribute::create_permission_attribute(
+                    &mut tx,
+                    &new_attribute,
+                )
+                .await?;
+                summary.permission_attributes += 1;
+                new_attribute.id
+            }
+        };
+        attributes.insert(item.name.clone(), id);
+    }
+
+    let mut permissions: HashMap<String, Uuid> = HashMap::new();
+    for item in data.permissions.iter() {
+        let permission =
+            repository::permission::get_permission_by_name(&mut tx, &item.name).await?;
+        let id = match permission {
+            Some(val) => val.id,
+            None => {
+                let new_permission = Permission {
+                    id: Uuid::now_v7(),
+                    permission_name: item.name.clone(),
+                    is_user: item.is_user,
+                    is_role: item.is_role,
+                    is_group: item.is_group,
+                    description: item.description.clone(),
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                };
+                repository::permission::create_permission(&mut tx, &new_permission).await?;
+                summary.permissions += 1;
+                new_permission.id
+            }
+        };
+        let current = repository::permission_attribute_list::get_all_permission_attribute_list(
+            &mut tx,
+            Some(&id),
+            None,
+        )
+        .await?;
+        for attribute in item.attributes.iter() {
+            let attribute_id = match attributes.get(attribute) {
+                Some(val) => *val,
+                None => anyhow::bail!(
+                    "permission {}: unknown permission attribute {}",
+                    item.name,
+                    attribute
+                ),
+            };
+            if current.iter().any(|x| x.attribute_id == attribute_id) {
+                continue;
+            }
+            repository::permission_attribute_list::create_permission_attribute_list(
+                &mut tx,
+                &PermissionAttributeList {
+                    permission_id: id,
+                    attribute_id,
+                },
+            )
+            .await?;
+        }
+        permissions.insert(item.name.clone(), id);
+    }
+
+    for item in data.roles.iter() {
+        let grants = resolve_grants(
+            &item.permissions,
+            &format!("role {}", item.name),
+            &permissions,
+            &attributes,
+        )?;
+        let (role_id, created) =
+            get_or_create_rbac_entity(&mut tx, ROLE_TABLE_NAME, "role_name", item, &now).await?;
+        if created {
+            summary.roles += 1;
+        }
+        for (permission_id, attribute_id) in grants {
+            let existing = repository::role_permission::get_detail_role_permission(
+                &mut tx,
+                &role_id,
+                &permission_id,
+                &attribute_id,
+            )
+            .await?;
+            if existing.is_some() {
+                continue;
+            }
+            repository::role_permission::create_role_permission(
+                &mut tx,
+                &RolePermission {
+                    role_id,
+                    permission_id,
+                    attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                },
+            )
+            .await?;
+            summary.grants += 1;
+        }
+    }
+
+    for item in data.groups.iter() {
+        let grants = resolve_grants(
+            &item.permissions,
+            &format!("group {}", item.name),
+            &permissions,
+            &attributes,
+        )?;
+        let (group_id, created) =
+            get_or_create_rbac_entity(&mut tx, GROUP_TABLE_NAME, "group_name", item, &now).await?;
+        if created {
+            summary.groups += 1;
+        }
+        for (permission_id, attribute_id) in grants {
+            let existing = repository::group_permission::get_detail_group_permission(
+                &mut tx,
+                &group_id,
+                &permission_id,
+                &attribute_id,
+            )
+            .await?;
+            if existing.is_some() {
+                continue;
+            }
+            repository::group_permission::create_group_permission(
+                &mut tx,
+                &GroupPermission {
+                    group_id,
+                    permission_id,
+                    attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                },
+            )
+            .await?;
+            summary.grants += 1;
+        }
+    }
+
+    tx.commit().await?;
+    grants_changed(None).await;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::cli::seed::{parse_seed_data, seed, SeedSummary, DEFAULT_SEED};
+
+    #[sqlx::test]
+    async fn test_seed_default_is_idempotent(pool: PgPool) -> sqlx::Result<()> {
+        // When
+        let data = parse_seed_data(DEFAULT_SEED).unwrap();
+        let first = seed(&pool, &data).await.unwrap();
+        let second = seed(&pool, &data).await.unwrap();
+
+        // Expect
+        assert_eq!(first.permission_attributes, 4);
+        assert_eq!(first.permissions, 11);
+        assert_eq!(first.roles, 2);
+        assert_eq!(first.groups, 1);
+        assert_eq!(first.grants, 38);
+        assert_eq!(second, SeedSummary::default());
+        let count: (i64,) = sqlx::query_as(
+            r#"SELECT count(*) FROM public.role_permissions rp
+            JOIN public.role r ON r.id = rp.role_id
+            WHERE r.role_name = 'admin'"#,
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(count.0, 33);
+        let count: (i64,) = sqlx::query_as("SELECT count(*) FROM public.permission_attribute_list")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count.0, 33);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_seed_unknown_reference(pool: PgPool) -> sqlx::Result<()> {
+        // When
+        let data = parse_seed_data(
+            r#"{"roles": [{"name": "ops", "permissions": [{"permission": "missing", "attributes": ["read"]}]}]}"#,
+        )
+        .unwrap();
+        let res = seed(&pool, &data).await;
+
+        // Expect nothing written
+        assert!(res.is_err());
+        let count: (i64,) = sqlx::query_as("SELECT count(*) FROM public.role")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count.0, 0);
+        Ok(())
+    }
+}
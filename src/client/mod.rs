@@ -0,0 +1,331 @@
+use std::fmt;
+
+use poem_openapi::types::{ParseFromJSON, ToJSON};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde_json::Value;
+
+use crate::schema::{
+    auth::{
+        IntrospectRequest, IntrospectResponse, LoginRequest, LoginResponse, RefreshTokenRequest,
+        RefreshTokenResponse,
+    },
+    common::PaginateResponse,
+    user::{
+        DetailUser, UserCreateRequest, UserCreateResponse, UserDetailResponse, UserUpdateRequest,
+        UserUpdateResponse,
+    },
+    user_permission::DetailUserPermissionResponse,
+};
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// Non 2xx response, `code` is the ErrorCode of the body when present
+    Api {
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+    },
+    Transport(reqwest::Error),
+    /// Body does not match the schema struct
+    Decode(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Api {
+                status,
+                code,
+                message,
+            } => write!(
+                f,
+                "{} {}: {}",
+                status,
+                code.as_deref().unwrap_or("UNKNOWN"),
+                message
+            ),
+            ClientError::Transport(err) => write!(f, "transport: {}", err),
+            ClientError::Decode(err) => write!(f, "decode: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(val: reqwest::Error) -> Self {
+        ClientError::Transport(val)
+    }
+}
+
+impl ClientError {
+    /// Status of an api error, None for transport and decode errors
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+/// Typed client of the core api, request and response bodies are the schema structs the
+/// server is built from so both sides change together.
+///
+/// `base_url` includes the prefix, e.g. https://core.example.com/api
+pub struct CoreClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl CoreClient {
+    pub fn new(base_url: &str) -> Self {
+        Self::with_http(reqwest::Client::new(), base_url)
+    }
+
+    /// Reuse a configured reqwest client, e.g. with timeouts or a proxy
+    pub fn with_http(http: reqwest::Client, base_url: &str) -> Self {
+        Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    /// Bearer token sent with every request, set by login and refresh_token
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    fn body<T: ToJSON>(val: &T) -> Value {
+        val.to_json().unwrap_or(Value::Null)
+    }
+
+    async fn send_raw(req: RequestBuilder) -> Result<Option<Value>, ClientError> {
+        let resp = req.send().await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        let body: Option<Value> = if text.is_empty() {
+            None
+        } else {
+            Some(serde_json::from_str(&text).map_err(|x| ClientError::Decode(x.to_string()))?)
+        };
+        if status.is_success() {
+            return Ok(body);
+        }
+        let body = body.unwrap_or(Value::Null);
+        Err(ClientError::Api {
+            status,
+            code: body["code"].as_str().map(|x| x.to_string()),
+            message: body["message"]
+                .as_str()
+                .or(body["detail"].as_str())
+                .map(|x| x.to_string())
+                .unwrap_or(text),
+        })
+    }
+
+    async fn send<T: ParseFromJSON>(req: RequestBuilder) -> Result<T, ClientError> {
+        let body = Self::send_raw(req).await?;
+        T::parse_from_json(body).map_err(|x| ClientError::Decode(x.into_message()))
+    }
+
+    /// Keeps the returned token for the following requests
+    pub async fn login(
+        &mut self,
+        user_name: &str,
+        password: &str,
+    ) -> Result<LoginResponse, ClientError> {
+        let res: LoginResponse = Self::send(self.request(Method::POST, "/auth/login").json(
+            &Self::body(&LoginRequest {
+                user_name: user_name.to_string(),
+                password: password.to_string(),
+                accept_terms: None,
+                recovery_code: None,
+            }),
+        ))
+        .await?;
+        self.token = Some(res.token.clone());
+        Ok(res)
+    }
+
+    /// Keeps the returned token for the following requests. The refresh token is single use,
+    /// keep the one in the response for the next refresh
+    pub async fn refresh_token(
+        &mut self,
+        refresh_token: &str,
+    ) -> Result<RefreshTokenResponse, ClientError> {
+        let res: RefreshTokenResponse = Self::send(
+            self.request(Method::POST, "/auth/refresh")
+                .json(&Self::body(&RefreshTokenRequest {
+                    refresh_token: refresh_token.to_string(),
+                })),
+        )
+        .await?;
+        self.token = Some(res.token.clone());
+        Ok(res)
+    }
+
+    pub async fn logout(&mut self) -> Result<(), ClientError> {
+        Self::send_raw(self.request(Method::POST, "/auth/logout")).await?;
+        self.token = None;
+        Ok(())
+    }
+
+    /// Whether any token, not only the client's, is active
+    pub async fn introspect(&self, token: &str) -> Result<IntrospectResponse, ClientError> {
+        Self::send(
+            self.request(Method::POST, "/auth/introspect")
+                .json(&Self::body(&IntrospectRequest {
+                    token: token.to_string(),
+                })),
+        )
+        .await
+    }
+
+    pub async fn list_users(
+        &self,
+        page: Option<u32>,
+        page_size: Option<u32>,
+        search: Option<&str>,
+    ) -> Result<PaginateResponse<DetailUser>, ClientError> {
+        Self::send(self.request(Method::GET, "/user").query(&[
+            ("page", page.map(|x| x.to_string())),
+            ("page_size", page_size.map(|x| x.to_string())),
+            ("search", search.map(|x| x.to_string())),
+        ]))
+        .await
+    }
+
+    pub async fn get_user(&self, id: &str) -> Result<UserDetailResponse, ClientError> {
+        Self::send(
+            self.request(Method::GET, "/user/detail")
+                .query(&[("id", id)]),
+        )
+        .await
+    }
+
+    pub async fn create_user(
+        &self,
+        data: &UserCreateRequest,
+    ) -> Result<UserCreateResponse, ClientError> {
+        Self::send(self.request(Method::POST, "/user").json(&Self::body(data))).await
+    }
+
+    pub async fn update_user(
+        &self,
+        id: &str,
+        data: &UserUpdateRequest,
+    ) -> Result<UserUpdateResponse, ClientError> {
+        Self::send(
+            self.request(Method::PUT, "/user")
+                .query(&[("id", id)])
+                .json(&Self::body(data)),
+        )
+        .await
+    }
+
+    pub async fn delete_user(&self, id: &str) -> Result<(), ClientError> {
+        Self::send_raw(self.request(Method::DELETE, "/user").query(&[("id", id)])).await?;
+        Ok(())
+    }
+
+    /// Permission attributes granted to the user directly
+    pub async fn user_permissions(
+        &self,
+        user_id: &str,
+    ) -> Result<PaginateResponse<DetailUserPermissionResponse>, ClientError> {
+        Self::send(
+            self.request(Method::GET, "/user-permissions")
+                .query(&[("user_id", user_id), ("all", "true")]),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use poem::{
+        listener::{Acceptor, Listener, TcpListener},
+        Server,
+    };
+    use reqwest::StatusCode;
+    use sqlx::PgPool;
+
+    use crate::{
+        client::CoreClient,
+        core::{session_store::create_redis_pool, test_utils::generate_test_user},
+        init_openapi_route,
+        settings::get_config,
+        AppState,
+    };
+
+    #[sqlx::test]
+    async fn test_core_client(pool: PgPool) -> anyhow::Result<()> {
+        // Given a server on a random port
+        let mut config = get_config();
+        config.prefix = Some("/api".to_string());
+        let redis_pool = create_redis_pool(&config).unwrap();
+        let app_state = Arc::new(AppState {
+            db: pool,
+            redis_conn: redis_pool.into(),
+        });
+        let mut db = app_state.db.acquire().await?;
+        let mut redis_conn = app_state.redis_conn.get().await?;
+        let test_user = generate_test_user(
+            &mut db,
+            &mut redis_conn,
+            config.clone(),
+            "test_user",
+            "password",
+        )
+        .await?;
+        let acceptor = TcpListener::bind("127.0.0.1:0").into_acceptor().await?;
+        let addr = acceptor.local_addr()[0].as_socket_addr().cloned().unwrap();
+        let app = init_openapi_route(app_state.clone(), &config);
+        tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+        let mut cli = CoreClient::new(&format!("http://{}/api", addr));
+
+        // When
+        let login = cli.login("test_user", "password").await?;
+        let user = cli.get_user(&test_user.user.id.to_string()).await;
+        let introspected = cli.introspect(&login.token).await?;
+
+        // Expect
+        assert_eq!(cli.token(), Some(login.token.as_str()));
+        assert!(introspected.active);
+        match user {
+            Ok(val) => assert_eq!(val.user_name, "test_user"),
+            Err(err) => assert_eq!(err.status(), Some(StatusCode::FORBIDDEN)),
+        }
+
+        // When
+        cli.logout().await?;
+        let err = match cli.list_users(None, None, None).await {
+            Ok(_) => panic!("list_users succeeded after logout"),
+            Err(err) => err,
+        };
+
+        // Expect
+        assert!(cli.token().is_none());
+        assert_eq!(err.status(), Some(StatusCode::UNAUTHORIZED));
+        assert!(!cli.introspect(&login.token).await?.active);
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, FixedOffset, Local};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use crate::{
+    core::{
+        lifecycle::{change_user_status, revoke_deactivated_sessions},
+        session_store::{SessionPool, SessionStore},
+    },
+    model::{scim_provisioning_event::EVENT_DEACTIVATE, user::UserStatus},
+    repository::{scim_provisioning_event::enqueue_scim_event, user::get_expired_users},
+};
+
+/// Suspend active users whose expires_at has passed, returns the number suspended
+pub async fn suspend_expired_users<C: SessionStore>(
+    pool: &PgPool,
+    redis_conn: &mut C,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<u64> {
+    let now = now.unwrap_or(Local::now().fixed_offset());
+    let mut tx = pool.begin().await?;
+    let mut suspended_user_ids = vec![];
+    for mut user in get_expired_users(&mut tx, &now).await? {
+        change_user_status(
+            &mut tx,
+            &mut user,
+            UserStatus::Suspended,
+            Some("account expired".to_string()),
+            None,
+            &now,
+        )
+        .await?;
+        enqueue_scim_event(&mut tx, &user.id, EVENT_DEACTIVATE, Some(now)).await?;
+        suspended_user_ids.push(user.id);
+    }
+    tx.commit().await?;
+    revoke_deactivated_sessions(redis_conn, &suspended_user_ids).await?;
+    Ok(suspended_user_ids.len() as u64)
+}
+
+pub fn spawn_account_expiry_worker(
+    pool: PgPool,
+    redis_pool: SessionPool,
+    interval: StdDuration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let res = match redis_pool.get().await {
+                Ok(mut redis_conn) => suspend_expired_users(&pool, &mut redis_conn, None).await,
+                Err(err) => Err(err.into()),
+            };
+            match res {
+                Ok(0) => {}
+                Ok(suspended) => tracing::info!("suspended {} expired users", suspended),
+                Err(err) => tracing::error!(
+                    "error: on core::account_expiry::spawn_account_expiry_worker error: {}",
+                    err
+                ),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, FixedOffset, Local};
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{account_expiry::suspend_expired_users, session_store::MemoryStore},
+        factory::user::UserFactory,
+        model::user::{User, UserStatus},
+        repository::{user::get_user_by_id, user_status_history::get_user_status_history_by_user},
+    };
+
+    #[sqlx::test]
+    async fn test_suspend_expired_users(pool: PgPool) -> anyhow::Result<()> {
+        // Given one expired user, one expiring tomorrow and one without expiry
+        let now = Local::now().fixed_offset();
+        let mut factory = UserFactory::<(DateTime<FixedOffset>, DateTime<FixedOffset>)>::new();
+        factory.modified_many(|x, i, (expired, expiring)| {
+            let mut user = User {
+                deleted_date: None,
+                expires_at: match i {
+                    0 => Some(expired),
+                    1 => Some(expiring),
+                    _ => None,
+                },
+                ..x.clone()
+            };
+            user.status = UserStatus::Active;
+            user
+        });
+        let users = factory
+            .generate_many(
+                &pool,
+                3,
+                (now - Duration::hours(1), now + Duration::days(1)),
+            )
+            .await?;
+
+        // When
+        let mut redis_conn = MemoryStore::new();
+        let first = suspend_expired_users(&pool, &mut redis_conn, Some(now)).await?;
+        let second = suspend_expired_users(&pool, &mut redis_conn, Some(now)).await?;
+
+        // Expect only the expired user suspended, once
+        assert_eq!(first, 1);
+        assert_eq!(second, 0);
+        let mut tx = pool.begin().await?;
+        let (user, _) = get_user_by_id(&mut tx, &users[0].id, None).await?;
+        assert_eq!(user.unwrap().status, UserStatus::Suspended);
+        let history = get_user_status_history_by_user(&mut tx, &users[0].id).await?;
+        assert_eq!(history[0].reason, Some("account expired".to_string()));
+        for user in users[1..].iter() {
+            let (user, _) = get_user_by_id(&mut tx, &user.id, None).await?;
+            assert_eq!(user.unwrap().status, UserStatus::Active);
+        }
+        Ok(())
+    }
+}
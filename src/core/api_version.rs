@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use poem::{
+    http::{header, HeaderValue},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+use crate::core::utils::date_to_http_date;
+
+/// Versions mounted under {prefix}/{version}, oldest first, the last one is the latest
+pub const API_VERSIONS: [&str; 2] = ["v1", "v2"];
+
+/// Version served on the unversioned prefix for clients predating versioning
+pub const LEGACY_API_VERSION: &str = "v1";
+
+pub fn latest_api_version() -> &'static str {
+    API_VERSIONS[API_VERSIONS.len() - 1]
+}
+
+/// Join server prefix and version without doubling the slash
+pub fn versioned_prefix(prefix: &str, version: &str) -> String {
+    format!("{}/{}", prefix.trim_end_matches('/'), version)
+}
+
+/// Parse API_DEPRECATED_VERSIONS, comma separated `version` or
+/// `version@YYYY-MM-DD` where the date is the sunset (removal) date
+pub fn parse_deprecated_versions(
+    value: Option<&str>,
+) -> anyhow::Result<HashMap<String, Option<NaiveDate>>> {
+    let mut res = HashMap::new();
+    for item in value.unwrap_or("").split(',').map(|x| x.trim()) {
+        if item.is_empty() {
+            continue;
+        }
+        let (version, sunset) = match item.split_once('@') {
+            Some((version, date)) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                Ok(val) => (version, Some(val)),
+                Err(_) => anyhow::bail!("invalid sunset date {}, expected YYYY-MM-DD", date),
+            },
+            None => (item, None),
+        };
+        if !API_VERSIONS.contains(&version) {
+            anyhow::bail!(
+                "unknown api version {}, expected one of {}",
+                version,
+                API_VERSIONS.join(", ")
+            );
+        }
+        if version == latest_api_version() {
+            anyhow::bail!("latest api version {} can not be deprecated", version);
+        }
+        res.insert(version.to_string(), sunset);
+    }
+    Ok(res)
+}
+
+/// Add Deprecation, Sunset and successor Link headers to every response of a
+/// deprecated version, responses of supported versions are left untouched
+pub struct ApiVersionHeaders {
+    deprecated: bool,
+    sunset: Option<HeaderValue>,
+    successor: Option<HeaderValue>,
+}
+
+impl ApiVersionHeaders {
+    pub fn new(
+        deprecated_versions: &HashMap<String, Option<NaiveDate>>,
+        version: &str,
+        successor_prefix: &str,
+    ) -> Self {
+        match deprecated_versions.get(version) {
+            Some(sunset) => ApiVersionHeaders {
+                deprecated: true,
+                sunset: sunset.and_then(|x| HeaderValue::from_str(&date_to_http_date(x)).ok()),
+                successor: HeaderValue::from_str(&format!(
+                    "<{}>; rel=\"successor-version\"",
+                    successor_prefix
+                ))
+                .ok(),
+            },
+            None => ApiVersionHeaders {
+                deprecated: false,
+                sunset: None,
+                successor: None,
+            },
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ApiVersionHeaders {
+    type Output = ApiVersionHeadersEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ApiVersionHeadersEndpoint {
+            inner: ep,
+            deprecated: self.deprecated,
+            sunset: self.sunset.clone(),
+            successor: self.successor.clone(),
+        }
+    }
+}
+
+pub struct ApiVersionHeadersEndpoint<E> {
+    inner: E,
+    deprecated: bool,
+    sunset: Option<HeaderValue>,
+    successor: Option<HeaderValue>,
+}
+
+impl<E: Endpoint> Endpoint for ApiVersionHeadersEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let mut resp = match self.inner.call(req).await {
+            Ok(val) => val.into_response(),
+            Err(err) => err.into_response(),
+        };
+        if self.deprecated {
+            let headers = resp.headers_mut();
+            headers.insert("Deprecation", HeaderValue::from_static("true"));
+            if let Some(sunset) = &self.sunset {
+                headers.insert("Sunset", sunset.clone());
+            }
+            if let Some(successor) = &self.successor {
+                headers.append(header::LINK, successor.clone());
+            }
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::NaiveDate;
+    use poem::{http::StatusCode, test::TestClient};
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            api_version::{parse_deprecated_versions, versioned_prefix},
+            session_store::create_redis_pool,
+        },
+        init_openapi_route,
+        settings::get_config,
+        AppState,
+    };
+
+    #[test]
+    fn test_versioned_prefix() {
+        assert_eq!(versioned_prefix("/", "v1"), "/v1");
+        assert_eq!(versioned_prefix("/api", "v2"), "/api/v2");
+        assert_eq!(versioned_prefix("/api/", "v2"), "/api/v2");
+    }
+
+    #[test]
+    fn test_parse_deprecated_versions() {
+        // Expect
+        assert!(parse_deprecated_versions(None).unwrap().is_empty());
+        let versions = parse_deprecated_versions(Some("v1@2027-01-31")).unwrap();
+        assert_eq!(
+            versions.get("v1"),
+            Some(&Some(NaiveDate::from_ymd_opt(2027, 1, 31).unwrap()))
+        );
+        assert_eq!(
+            parse_deprecated_versions(Some(" v1 ")).unwrap().get("v1"),
+            Some(&None)
+        );
+        assert!(parse_deprecated_versions(Some("v9")).is_err());
+        assert!(parse_deprecated_versions(Some("v2")).is_err());
+        assert!(parse_deprecated_versions(Some("v1@31-01-2027")).is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_versioned_routes(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let mut config = get_config();
+        config.prefix = Some("/api".to_string());
+        config.api_deprecated_versions = Some("v1@2027-01-31".to_string());
+        config.openapi_redoc = Some(true);
+        let redis_pool = create_redis_pool(&config).unwrap();
+        let app_state = Arc::new(AppState {
+            db: pool,
+            redis_conn: redis_pool.into(),
+        });
+        let cli = TestClient::new(init_openapi_route(app_state, &config));
+
+        // When
+        let v1 = cli.get("/api/v1/user").send().await;
+        let legacy = cli.get("/api/user").send().await;
+        let v2 = cli.get("/api/v2/user").send().await;
+        let spec = cli.get("/v2/openapi.json").send().await;
+        let redoc = cli.get("/redoc/v2").send().await;
+
+        // Expect
+        for resp in [&v1, &legacy] {
+            resp.assert_status(StatusCode::UNAUTHORIZED);
+            resp.assert_header("Deprecation", "true");
+            resp.assert_header("Sunset", "Sun, 31 Jan 2027 00:00:00 GMT");
+            resp.assert_header("Link", "</api/v2>; rel=\"successor-version\"");
+        }
+        v2.assert_status(StatusCode::UNAUTHORIZED);
+        v2.assert_header_is_not_exist("Deprecation");
+        spec.assert_status_is_ok();
+        redoc.assert_status_is_ok();
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, SecondsFormat};
+
+/// Values embedded by build.rs at compile time
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_COMMIT: &str = env!("BUILD_GIT_COMMIT");
+const BUILD_TIMESTAMP: &str = env!("BUILD_TIMESTAMP");
+const BUILD_FEATURES: &str = env!("BUILD_FEATURES");
+
+/// Build time in RFC 3339 UTC
+pub fn build_timestamp() -> String {
+    let seconds = BUILD_TIMESTAMP.parse::<i64>().unwrap_or(0);
+    match DateTime::from_timestamp(seconds, 0) {
+        Some(val) => val.to_rfc3339_opts(SecondsFormat::Secs, true),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Cargo features the binary was compiled with
+pub fn enabled_features() -> Vec<String> {
+    BUILD_FEATURES
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
@@ -0,0 +1,360 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    core::pii::pii_keys,
+    model::{
+        directory_source::TABLE_NAME as DIRECTORY_SOURCE_TABLE_NAME,
+        scim_target::TABLE_NAME as SCIM_TARGET_TABLE_NAME,
+        service_account::TABLE_NAME as SERVICE_ACCOUNT_TABLE_NAME,
+        sso_provider::TABLE_NAME as SSO_PROVIDER_TABLE_NAME, user::TABLE_NAME as USER_TABLE_NAME,
+        user_contact::TABLE_NAME as USER_CONTACT_TABLE_NAME,
+        user_data_export::TABLE_NAME as USER_DATA_EXPORT_TABLE_NAME,
+        user_device::TABLE_NAME as USER_DEVICE_TABLE_NAME,
+        user_identity::TABLE_NAME as USER_IDENTITY_TABLE_NAME,
+        user_name_history::TABLE_NAME as USER_NAME_HISTORY_TABLE_NAME,
+        user_preference::TABLE_NAME as USER_PREFERENCE_TABLE_NAME,
+        user_profile::TABLE_NAME as USER_PROFILE_TABLE_NAME,
+        user_recovery_code::TABLE_NAME as USER_RECOVERY_CODE_TABLE_NAME,
+        user_status_history::TABLE_NAME as USER_STATUS_HISTORY_TABLE_NAME,
+    },
+};
+
+pub const STORE_POSTGRES: &str = "postgres";
+pub const STORE_REDIS: &str = "redis";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataClass {
+    /// Personal data identifying a user
+    Pii,
+    /// Credentials and secrets, never returned or logged in clear
+    Sensitive,
+    Public,
+}
+
+impl DataClass {
+    pub const ALL: [DataClass; 3] = [DataClass::Pii, DataClass::Sensitive, DataClass::Public];
+}
+
+impl FromStr for DataClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pii" => Ok(DataClass::Pii),
+            "sensitive" => Ok(DataClass::Sensitive),
+            "public" => Ok(DataClass::Public),
+            _ => anyhow::bail!("must be pii, sensitive or public, got {}", s),
+        }
+    }
+}
+
+impl fmt::Display for DataClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = match self {
+            DataClass::Pii => "pii",
+            DataClass::Sensitive => "sensitive",
+            DataClass::Public => "public",
+        };
+        write!(f, "{}", val)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protection {
+    Plaintext,
+    /// One way hash, argon2 or sha256 for random keys
+    Hashed,
+    /// Encrypted with PII_ENCRYPTION_KEY when configured, see core::pii
+    PiiKey,
+    /// Expires with the session
+    Ttl,
+}
+
+/// Where a stored attribute lives and how it is protected
+#[derive(Clone, Copy, Debug)]
+pub struct ClassifiedAttribute {
+    pub store: &'static str,
+    /// Table, or key pattern for redis
+    pub location: &'static str,
+    pub attribute: &'static str,
+    pub class: DataClass,
+    pub protection: Protection,
+}
+
+const fn attribute(
+    store: &'static str,
+    location: &'static str,
+    attribute: &'static str,
+    class: DataClass,
+    protection: Protection,
+) -> ClassifiedAttribute {
+    ClassifiedAttribute {
+        store,
+        location,
+        attribute,
+        class,
+        protection,
+    }
+}
+
+/// Stored attributes holding personal or sensitive data, plus public identifiers
+/// they are joined with. Update it together with migrations adding such columns.
+pub const DATA_CLASSIFICATION: &[ClassifiedAttribute] = &[
+    attribute(
+        STORE_POSTGRES,
+        USER_TABLE_NAME,
+        "user_name",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_TABLE_NAME,
+        "password",
+        DataClass::Sensitive,
+        Protection::Hashed,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_TABLE_NAME,
+        "id",
+        DataClass::Public,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_PROFILE_TABLE_NAME,
+        "first_name",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_PROFILE_TABLE_NAME,
+        "last_name",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_PROFILE_TABLE_NAME,
+        "email",
+        DataClass::Pii,
+        Protection::PiiKey,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_PROFILE_TABLE_NAME,
+        "address",
+        DataClass::Pii,
+        Protection::PiiKey,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_CONTACT_TABLE_NAME,
+        "value",
+        DataClass::Pii,
+        Protection::PiiKey,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_IDENTITY_TABLE_NAME,
+        "subject",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_IDENTITY_TABLE_NAME,
+        "provider",
+        DataClass::Public,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_DATA_EXPORT_TABLE_NAME,
+        "payload",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_NAME_HISTORY_TABLE_NAME,
+        "user_name",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_PREFERENCE_TABLE_NAME,
+        "value",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_DEVICE_TABLE_NAME,
+        "name",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_DEVICE_TABLE_NAME,
+        "token",
+        DataClass::Sensitive,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_RECOVERY_CODE_TABLE_NAME,
+        "code_hash",
+        DataClass::Sensitive,
+        Protection::Hashed,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        SERVICE_ACCOUNT_TABLE_NAME,
+        "key_hash",
+        DataClass::Sensitive,
+        Protection::Hashed,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        USER_STATUS_HISTORY_TABLE_NAME,
+        "reason",
+        DataClass::Pii,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        SCIM_TARGET_TABLE_NAME,
+        "bearer_token",
+        DataClass::Sensitive,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        DIRECTORY_SOURCE_TABLE_NAME,
+        "ldap_bind_password",
+        DataClass::Sensitive,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        DIRECTORY_SOURCE_TABLE_NAME,
+        "azure_client_secret",
+        DataClass::Sensitive,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        SSO_PROVIDER_TABLE_NAME,
+        "client_id",
+        DataClass::Public,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_POSTGRES,
+        SSO_PROVIDER_TABLE_NAME,
+        "client_secret",
+        DataClass::Sensitive,
+        Protection::Plaintext,
+    ),
+    attribute(
+        STORE_REDIS,
+        "<access token>",
+        "session",
+        DataClass::Sensitive,
+        Protection::Ttl,
+    ),
+    attribute(
+        STORE_REDIS,
+        "core:refresh_tokens:<refresh token>",
+        "user_id",
+        DataClass::Sensitive,
+        Protection::Ttl,
+    ),
+    attribute(
+        STORE_REDIS,
+        "core:user_refresh_tokens:<user id>",
+        "refresh tokens",
+        DataClass::Sensitive,
+        Protection::Ttl,
+    ),
+    attribute(
+        STORE_REDIS,
+        "email_change:<token>",
+        "new_email",
+        DataClass::Pii,
+        Protection::Ttl,
+    ),
+    attribute(
+        STORE_REDIS,
+        "email_verification:<token>",
+        "email",
+        DataClass::Pii,
+        Protection::Ttl,
+    ),
+    attribute(
+        STORE_REDIS,
+        "sso_state:<state>",
+        "code_verifier",
+        DataClass::Sensitive,
+        Protection::Ttl,
+    ),
+];
+
+pub fn classification_of(location: &str, attribute: &str) -> Option<&'static ClassifiedAttribute> {
+    DATA_CLASSIFICATION
+        .iter()
+        .find(|x| x.location == location && x.attribute == attribute)
+}
+
+pub fn attributes_of_class(class: DataClass) -> Vec<&'static ClassifiedAttribute> {
+    DATA_CLASSIFICATION
+        .iter()
+        .filter(|x| x.class == class)
+        .collect()
+}
+
+/// Effective protection, PiiKey columns are plaintext until PII_ENCRYPTION_KEY is set
+pub fn effective_protection(attribute: &ClassifiedAttribute) -> &'static str {
+    match attribute.protection {
+        Protection::Plaintext => "plaintext",
+        Protection::Hashed => "hashed",
+        Protection::PiiKey if pii_keys().is_some() => "encrypted",
+        Protection::PiiKey => "plaintext",
+        Protection::Ttl => "expiring",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::core::classification::{
+        attributes_of_class, classification_of, DataClass, Protection, DATA_CLASSIFICATION,
+    };
+
+    #[test]
+    fn test_data_classification() {
+        // Expect every attribute listed once
+        let keys: HashSet<(&str, &str)> = DATA_CLASSIFICATION
+            .iter()
+            .map(|x| (x.location, x.attribute))
+            .collect();
+        assert_eq!(keys.len(), DATA_CLASSIFICATION.len());
+
+        // Expect columns encrypted by core::pii classified as pii
+        let email = classification_of("public.user_profile", "email").unwrap();
+        assert_eq!(email.class, DataClass::Pii);
+        assert_eq!(email.protection, Protection::PiiKey);
+        assert!(attributes_of_class(DataClass::Sensitive)
+            .iter()
+            .all(|x| x.class == DataClass::Sensitive));
+        assert!(classification_of("public.user", "unknown").is_none());
+        assert_eq!("PII".parse::<DataClass>().unwrap(), DataClass::Pii);
+        assert!("secret".parse::<DataClass>().is_err());
+    }
+}
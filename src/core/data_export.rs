@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, time::Duration as StdDuration};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::task::JoinHandle;
+
+use crate::{
+    core::utils::{datetime_to_string, datetime_to_string_opt},
+    model::{
+        user::UserStatus,
+        user_data_export::{UserDataExport, STATUS_COMPLETED, STATUS_FAILED},
+    },
+    repository::{
+        user::get_user_by_id,
+        user_contact::get_user_contacts_by_user,
+        user_data_export::{
+            get_group_role_names_by_user, get_pending_user_data_export,
+            get_permission_names_by_user, get_scim_links_by_user, get_user_data_export_by_user,
+            get_user_identity_by_user, update_user_data_export,
+        },
+        user_name_history::get_user_name_history_by_user,
+        user_preference::get_user_preferences_by_user,
+    },
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedUser {
+    pub id: String,
+    pub user_name: String,
+    pub is_active: Option<bool>,
+    /// Lifecycle status, missing in exports generated before it existed
+    pub status: Option<UserStatus>,
+    pub expires_at: Option<String>,
+    pub is_2faenabled: Option<bool>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+    pub deleted_date: Option<String>,
+    /// Newest first, missing in exports generated before it existed
+    #[serde(default)]
+    pub previous_user_names: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedProfile {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub address: Option<String>,
+    pub email: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedContact {
+    pub kind: String,
+    pub value: String,
+    pub is_primary: bool,
+    pub verified_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedAssignment {
+    pub group: Option<String>,
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedPermission {
+    pub permission: String,
+    pub attribute: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub created_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedScimLink {
+    pub target: String,
+    pub external_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedDataExport {
+    pub id: String,
+    pub status: String,
+    pub created_date: Option<String>,
+    pub completed_date: Option<String>,
+}
+
+/// Everything the service stores about a user. The password hash is left out,
+/// login sessions only live in Redis until they expire and are not included.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserDataExportDocument {
+    pub export_id: String,
+    pub generated_date: String,
+    pub user: ExportedUser,
+    pub profile: Option<ExportedProfile>,
+    /// Missing in exports generated before contacts existed
+    #[serde(default)]
+    pub contacts: Vec<ExportedContact>,
+    /// Missing in exports generated before preferences existed
+    #[serde(default)]
+    pub preferences: BTreeMap<String, serde_json::Value>,
+    pub assignments: Vec<ExportedAssignment>,
+    pub permissions: Vec<ExportedPermission>,
+    pub identities: Vec<ExportedIdentity>,
+    pub provisioned_to: Vec<ExportedScimLink>,
+    pub data_exports: Vec<ExportedDataExport>,
+}
+
+pub async fn build_user_data_export(
+    tx: &mut Transaction<'_, Postgres>,
+    export: &UserDataExport,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserDataExportDocument> {
+    let (user, user_profile) = get_user_by_id(tx, &export.user_id, Some(false)).await?;
+    let user = match user {
+        Some(val) => val,
+        None => anyhow::bail!("user with id = {} not found", export.user_id),
+    };
+    let assignments = get_group_role_names_by_user(tx, &user.id).await?;
+    let permissions = get_permission_names_by_user(tx, &user.id).await?;
+    let identities = get_user_identity_by_user(tx, &user.id).await?;
+    let scim_links = get_scim_links_by_user(tx, &user.id).await?;
+    let data_exports = get_user_data_export_by_user(tx, &user.id).await?;
+    let user_name_history = get_user_name_history_by_user(tx, &user.id).await?;
+    let contacts = get_user_contacts_by_user(tx, &user.id).await?;
+    let preferences = get_user_preferences_by_user(tx, &user.id).await?;
+    Ok(UserDataExportDocument {
+        export_id: export.id.to_string(),
+        generated_date: datetime_to_string(*now),
+        user: ExportedUser {
+            id: user.id.to_string(),
+            is_active: Some(user.is_active()),
+            user_name: user.user_name,
+            status: Some(user.status),
+            expires_at: datetime_to_string_opt(user.expires_at),
+            is_2faenabled: user.is_2faenabled,
+            created_date: datetime_to_string_opt(user.created_date),
+            updated_date: datetime_to_string_opt(user.updated_date),
+            deleted_date: datetime_to_string_opt(user.deleted_date),
+            previous_user_names: user_name_history.into_iter().map(|x| x.user_name).collect(),
+        },
+        profile: user_profile.map(|x| ExportedProfile {
+            first_name: x.first_name,
+            last_name: x.last_name,
+            address: x.address,
+            email: x.email,
+            locale: x.locale,
+            timezone: x.timezone,
+        }),
+        contacts: contacts
+            .into_iter()
+            .map(|x| ExportedContact {
+                kind: x.kind,
+                value: x.value,
+                is_primary: x.is_primary,
+                verified_date: datetime_to_string_opt(x.verified_date),
+            })
+            .collect(),
+        preferences: preferences.into_iter().map(|x| (x.key, x.value)).collect(),
+        assignments: assignments
+            .into_iter()
+            .map(|(group, role)| ExportedAssignment { group, role })
+            .collect(),
+        permissions: permissions
+            .into_iter()
+            .map(|(permission, attribute)| ExportedPermission {
+                permission,
+                attribute,
+            })
+            .collect(),
+        identities: identities
+            .into_iter()
+            .map(|x| ExportedIdentity {
+                provider: x.provider,
+                subject: x.subject,
+                created_date: datetime_to_string_opt(x.created_date),
+            })
+            .collect(),
+        provisioned_to: scim_links
+            .into_iter()
+            .map(|(target, external_id)| ExportedScimLink {
+                target,
+                external_id,
+            })
+            .collect(),
+        data_exports: data_exports
+            .into_iter()
+            .map(|x| ExportedDataExport {
+                id: x.id.to_string(),
+                status: x.status,
+                created_date: datetime_to_string_opt(x.created_date),
+                completed_date: datetime_to_string_opt(x.completed_date),
+            })
+            .collect(),
+    })
+}
+
+/// Build the document of one export and record the outcome on the export row
+pub async fn process_user_data_export(
+    tx: &mut Transaction<'_, Postgres>,
+    export: &mut UserDataExport,
+) -> anyhow::Result<()> {
+    let now = Local::now().fixed_offset();
+    let payload = match build_user_data_export(tx, export, &now).await {
+        Ok(val) => serde_json::to_string_pretty(&val).map_err(anyhow::Error::from),
+        Err(err) => Err(err),
+    };
+    match payload {
+        Ok(val) => {
+            export.status = STATUS_COMPLETED.to_string();
+            export.payload = Some(val);
+            export.last_error = None;
+        }
+        Err(err) => {
+            tracing::warn!("user data export {} failed: {}", export.id, err);
+            export.status = STATUS_FAILED.to_string();
+            export.payload = None;
+            export.last_error = Some(err.to_string());
+        }
+    }
+    export.completed_date = Some(now);
+    update_user_data_export(tx, export).await?;
+    Ok(())
+}
+
+/// Process a batch of pending exports, return number of processed exports
+pub async fn process_pending_user_data_exports(
+    pool: &PgPool,
+    batch_size: u32,
+) -> anyhow::Result<u32> {
+    let mut tx = pool.begin().await?;
+    let mut exports = get_pending_user_data_export(&mut tx, batch_size).await?;
+    for export in exports.iter_mut() {
+        process_user_data_export(&mut tx, export).await?;
+    }
+    tx.commit().await?;
+    Ok(exports.len() as u32)
+}
+
+/// Generate requested user data exports in background every `interval`
+pub fn spawn_data_export_worker(pool: PgPool, interval: StdDuration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = process_pending_user_data_exports(&pool, 10).await {
+                tracing::error!(
+                    "error: on core::data_export::spawn_data_export_worker error: {}",
+                    err
+                );
+            }
+        }
+    })
+}
@@ -1,9 +1,59 @@
-use std::time::Duration;
+use std::{collections::HashMap, fmt, time::Duration};
 
-use sqlx::{pool::PoolOptions, Pool, Postgres};
+use sqlx::{
+    migrate::{Migrate, Migration, Migrator},
+    pool::PoolOptions,
+    Pool, Postgres,
+};
 
 use crate::settings::Config;
 
+/// Migrations embedded into the binary at build time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Database behind DATABASE_URL. Repositories are postgres only, mysql and sqlite urls are
+/// recognized so startup and check-config can say so instead of failing to connect
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Postgres,
+    MySql,
+    Sqlite,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Postgres => write!(f, "postgres"),
+            Self::MySql => write!(f, "mysql"),
+            Self::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> anyhow::Result<Self> {
+        match url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("postgres") | Some("postgresql") => Ok(Self::Postgres),
+            Some("mysql") | Some("mariadb") => Ok(Self::MySql),
+            Some("sqlite") => Ok(Self::Sqlite),
+            _ => {
+                anyhow::bail!("unsupported DATABASE_URL scheme, expected postgres")
+            }
+        }
+    }
+
+    /// Whether the api can run on it, fails with what is missing otherwise
+    pub fn ensure_supported(&self) -> anyhow::Result<()> {
+        if *self != Self::Postgres {
+            anyhow::bail!(
+                "DATABASE_URL is {}, the repositories only support postgres",
+                self
+            );
+        }
+        Ok(())
+    }
+}
+
 pub async fn init_pool(config: &Config) -> Pool<Postgres> {
     PoolOptions::new()
         .min_connections(5)
@@ -13,3 +63,192 @@ pub async fn init_pool(config: &Config) -> Pool<Postgres> {
         .await
         .expect("Failed to connect to database")
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStep {
+    pub version: i64,
+    pub description: String,
+    pub revert: bool,
+}
+
+fn migration_step(migration: &Migration, revert: bool) -> MigrationStep {
+    MigrationStep {
+        version: migration.version,
+        description: migration.description.to_string(),
+        revert,
+    }
+}
+
+pub fn latest_migration_version() -> Option<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| m.version)
+        .max()
+}
+
+async fn plan_on<C: Migrate>(
+    conn: &mut C,
+    target: Option<i64>,
+) -> anyhow::Result<Vec<MigrationStep>> {
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        anyhow::bail!(
+            "migration {} is partially applied, fix it manually",
+            version
+        );
+    }
+    let applied: HashMap<i64, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum))
+        .collect();
+    for (version, checksum) in applied.iter() {
+        let known = MIGRATOR
+            .iter()
+            .find(|m| m.version == *version && !m.migration_type.is_down_migration());
+        match known {
+            Some(m) if m.checksum == *checksum => {}
+            Some(_) => anyhow::bail!("applied migration {} was modified", version),
+            None => anyhow::bail!("applied migration {} is unknown to this build", version),
+        }
+    }
+
+    let target = match target {
+        Some(val) if val != 0 && !MIGRATOR.version_exists(val) => {
+            anyhow::bail!("unknown migration version {}", val)
+        }
+        Some(val) => val,
+        None => latest_migration_version().unwrap_or(0),
+    };
+    let mut steps: Vec<MigrationStep> = MIGRATOR
+        .iter()
+        .rev()
+        .filter(|m| m.migration_type.is_down_migration())
+        .filter(|m| m.version > target && applied.contains_key(&m.version))
+        .map(|m| migration_step(m, true))
+        .collect();
+    steps.extend(
+        MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .filter(|m| m.version <= target && !applied.contains_key(&m.version))
+            .map(|m| migration_step(m, false)),
+    );
+    Ok(steps)
+}
+
+/// Steps needed to bring the schema to target version, latest when target is None.
+/// Target 0 reverts every migration.
+pub async fn plan_migrations(
+    pool: &Pool<Postgres>,
+    target: Option<i64>,
+) -> anyhow::Result<Vec<MigrationStep>> {
+    let mut conn = pool.acquire().await?;
+    plan_on(&mut *conn, target).await
+}
+
+/// Apply or revert migrations up to target version, return the steps taken
+pub async fn run_migrations(
+    pool: &Pool<Postgres>,
+    target: Option<i64>,
+) -> anyhow::Result<Vec<MigrationStep>> {
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    let res = async {
+        let steps = plan_on(&mut *conn, target).await?;
+        for step in steps.iter() {
+            let migration = MIGRATOR.iter().find(|m| {
+                m.version == step.version && m.migration_type.is_down_migration() == step.revert
+            });
+            match migration {
+                Some(m) if step.revert => conn.revert(m).await?,
+                Some(m) => conn.apply(m).await?,
+                None => anyhow::bail!("migration {} has no down script", step.version),
+            };
+        }
+        Ok(steps)
+    }
+    .await;
+    conn.unlock().await?;
+    res
+}
+
+/// Migrations of this build not yet applied on the database
+pub async fn pending_migrations(pool: &Pool<Postgres>) -> anyhow::Result<Vec<MigrationStep>> {
+    Ok(plan_migrations(pool, None)
+        .await?
+        .into_iter()
+        .filter(|step| !step.revert)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(
+            Backend::from_url("postgres://localhost/core").unwrap(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::from_url("mysql://localhost/core").unwrap(),
+            Backend::MySql
+        );
+        assert_eq!(
+            Backend::from_url("sqlite::memory:").unwrap(),
+            Backend::Sqlite
+        );
+        assert!(Backend::from_url("localhost/core").is_err());
+        assert!(Backend::Postgres.ensure_supported().is_ok());
+        assert!(Backend::Sqlite.ensure_supported().is_err());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_run_migrations_to_version(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let versions: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(|m| m.version)
+            .collect();
+        assert!(versions.len() >= 2);
+
+        // When dry run
+        let plan = plan_migrations(&pool, None).await?;
+
+        // Expect everything pending
+        assert_eq!(
+            plan.iter().map(|x| x.version).collect::<Vec<i64>>(),
+            versions
+        );
+
+        // When migrate to first version
+        let steps = run_migrations(&pool, Some(versions[0])).await?;
+
+        // Expect
+        assert_eq!(steps.len(), 1);
+        assert_eq!(pending_migrations(&pool).await?.len(), versions.len() - 1);
+
+        // When migrate to latest
+        run_migrations(&pool, None).await?;
+
+        // Expect up to date
+        assert!(pending_migrations(&pool).await?.is_empty());
+
+        // When revert back to first version
+        let steps = run_migrations(&pool, Some(versions[0])).await?;
+
+        // Expect
+        assert_eq!(steps.len(), versions.len() - 1);
+        assert!(steps.iter().all(|x| x.revert));
+        assert!(steps[0].version > steps[1].version);
+        assert!(run_migrations(&pool, Some(1)).await.is_err());
+        Ok(())
+    }
+}
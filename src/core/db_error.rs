@@ -0,0 +1,282 @@
+use std::{future::Future, sync::LazyLock, time::Duration};
+
+use poem_openapi::payload::Json;
+use regex::Regex;
+use sqlx::postgres::PgDatabaseError;
+
+use crate::schema::common::{BadRequestResponse, ConflictResponse};
+
+pub const UNIQUE_VIOLATION: &str = "23505";
+pub const FOREIGN_KEY_VIOLATION: &str = "23503";
+pub const NOT_NULL_VIOLATION: &str = "23502";
+pub const CHECK_VIOLATION: &str = "23514";
+pub const SERIALIZATION_FAILURE: &str = "40001";
+pub const DEADLOCK_DETECTED: &str = "40P01";
+
+/// Attempts of a transaction before its last error is returned
+pub const TX_MAX_ATTEMPTS: u32 = 3;
+/// Wait before the first retry, doubled on every next one
+const TX_RETRY_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Columns of the key in a postgres detail, e.g. Key (kind, version)=(tos, 1) already exists.
+static KEY_DETAIL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^Key \((.+?)\)=").unwrap());
+
+/// Constraint a write was rejected by, named after the request field
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstraintViolation {
+    /// Duplicate of an existing row
+    Unique {
+        field: String,
+    },
+    /// Reference to a row that does not exist
+    ForeignKey {
+        field: String,
+    },
+    NotNull {
+        field: String,
+    },
+    Check {
+        constraint: String,
+    },
+}
+
+impl ConstraintViolation {
+    pub fn field(&self) -> &str {
+        match self {
+            Self::Unique { field } | Self::ForeignKey { field } | Self::NotNull { field } => field,
+            Self::Check { constraint } => constraint,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::Unique { field } => format!("{} already exists", field),
+            Self::ForeignKey { field } => {
+                format!("{} does not reference an existing record", field)
+            }
+            Self::NotNull { field } => format!("{} is required", field),
+            Self::Check { constraint } => format!("value rejected by {}", constraint),
+        }
+    }
+
+    /// 409 for duplicates, 400 otherwise, `conflict` and `bad_request` are the response
+    /// variants of the handler e.g. RoleCreateResponses::Conflict
+    pub fn response<T>(
+        self,
+        conflict: impl FnOnce(Json<ConflictResponse>) -> T,
+        bad_request: impl FnOnce(Json<BadRequestResponse>) -> T,
+    ) -> T {
+        let field = self.field().to_string();
+        match self {
+            Self::Unique { .. } => conflict(Json(
+                ConflictResponse::new(self.message()).with_field(field),
+            )),
+            _ => bad_request(Json(
+                BadRequestResponse::new(self.message()).with_field(field),
+            )),
+        }
+    }
+}
+
+fn key_fields(detail: Option<&str>) -> Option<String> {
+    KEY_DETAIL
+        .captures(detail?)
+        .and_then(|x| x.get(1))
+        .map(|x| x.as_str().replace('"', ""))
+}
+
+fn pg_error(err: &anyhow::Error) -> Option<&PgDatabaseError> {
+    err.chain()
+        .find_map(|x| match x.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::Database(db_err)) => db_err.try_downcast_ref::<PgDatabaseError>(),
+            _ => None,
+        })
+}
+
+/// Unique, foreign key, not null or check violation behind a repository error
+pub fn constraint_violation(err: &anyhow::Error) -> Option<ConstraintViolation> {
+    let pg_err = pg_error(err)?;
+    let constraint = pg_err.constraint().unwrap_or_default().to_string();
+    let field = key_fields(pg_err.detail()).unwrap_or(constraint.clone());
+    match pg_err.code() {
+        UNIQUE_VIOLATION => Some(ConstraintViolation::Unique { field }),
+        FOREIGN_KEY_VIOLATION => Some(ConstraintViolation::ForeignKey { field }),
+        NOT_NULL_VIOLATION => Some(ConstraintViolation::NotNull {
+            field: pg_err.column().unwrap_or_default().to_string(),
+        }),
+        CHECK_VIOLATION => Some(ConstraintViolation::Check { constraint }),
+        _ => None,
+    }
+}
+
+/// SQLSTATE of a database error
+pub fn sqlx_sqlstate(err: &sqlx::Error) -> Option<String> {
+    err.as_database_error()?.code().map(|x| x.into_owned())
+}
+
+/// SQLSTATE of the database error behind a repository error
+pub fn sqlstate(err: &anyhow::Error) -> Option<String> {
+    err.chain()
+        .find_map(|x| x.downcast_ref::<sqlx::Error>())
+        .and_then(sqlx_sqlstate)
+}
+
+/// Serialization failure or deadlock, the transaction can succeed when run again
+pub fn is_retryable_sqlstate(code: &str) -> bool {
+    [SERIALIZATION_FAILURE, DEADLOCK_DETECTED].contains(&code)
+}
+
+/// Same as [`is_retryable_sqlstate`] for the database error behind a repository error
+pub fn is_retryable_error(err: &anyhow::Error) -> bool {
+    sqlstate(err).is_some_and(|x| is_retryable_sqlstate(&x))
+}
+
+/// Outcome of a transaction telling whether running it again may succeed
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+}
+
+impl<T> Retryable for anyhow::Result<T> {
+    fn is_retryable(&self) -> bool {
+        self.as_ref().is_err_and(is_retryable_error)
+    }
+}
+
+/// Run a transaction, from begin to commit, again while it fails on a serialization
+/// failure or deadlock, at most TX_MAX_ATTEMPTS times with exponential backoff
+pub async fn retry_transaction<T, F, Fut>(mut f: F) -> T
+where
+    T: Retryable,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = T>,
+{
+    let mut attempt = 1;
+    loop {
+        let res = f().await;
+        if attempt >= TX_MAX_ATTEMPTS || !res.is_retryable() {
+            return res;
+        }
+        tracing::warn!(
+            "transaction attempt {} of {} hit a serialization failure, retrying",
+            attempt,
+            TX_MAX_ATTEMPTS
+        );
+        tokio::time::sleep(TX_RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use sqlx::PgPool;
+
+    use crate::core::db_error::{
+        constraint_violation, is_retryable_error, is_retryable_sqlstate, key_fields,
+        retry_transaction, sqlstate, ConstraintViolation, Retryable, DEADLOCK_DETECTED,
+        SERIALIZATION_FAILURE, TX_MAX_ATTEMPTS,
+    };
+
+    #[test]
+    fn test_key_fields() {
+        assert_eq!(
+            key_fields(Some("Key (role_name)=(admin) already exists.")),
+            Some("role_name".to_string())
+        );
+        assert_eq!(
+            key_fields(Some("Key (kind, \"version\")=(tos, 1) already exists.")),
+            Some("kind, version".to_string())
+        );
+        assert!(key_fields(Some("Failing row contains (1).")).is_none());
+        assert!(key_fields(None).is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_constraint_violation(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let insert =
+            "INSERT INTO public.role (id, role_name) VALUES (gen_random_uuid(), 'auditor')";
+        sqlx::query(insert).execute(&pool).await?;
+
+        // When
+        let err: anyhow::Error = sqlx::query(insert).execute(&pool).await.unwrap_err().into();
+
+        // Expect
+        assert_eq!(
+            constraint_violation(&err),
+            Some(ConstraintViolation::Unique {
+                field: "role_name".to_string()
+            })
+        );
+        assert!(constraint_violation(&anyhow::anyhow!("connection reset")).is_none());
+        Ok(())
+    }
+
+    /// Err holds the SQLSTATE of the failure
+    struct Outcome(Result<u32, &'static str>);
+
+    impl Retryable for Outcome {
+        fn is_retryable(&self) -> bool {
+            self.0.as_ref().is_err_and(|x| is_retryable_sqlstate(x))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_transaction() {
+        // When deadlocked once
+        let calls = AtomicU32::new(0);
+        let res = retry_transaction(|| async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Outcome(Err(DEADLOCK_DETECTED)),
+                n => Outcome(Ok(n)),
+            }
+        })
+        .await;
+
+        // Expect second attempt returned
+        assert_eq!(res.0, Ok(1));
+
+        // When always failing on serialization
+        let calls = AtomicU32::new(0);
+        let res = retry_transaction(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Outcome(Err(SERIALIZATION_FAILURE))
+        })
+        .await;
+
+        // Expect bounded attempts
+        assert!(res.0.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), TX_MAX_ATTEMPTS);
+
+        // When failing on anything else
+        let calls = AtomicU32::new(0);
+        let res = retry_transaction(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Outcome(Err("23505"))
+        })
+        .await;
+
+        // Expect no retry
+        assert!(res.0.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test]
+    async fn test_is_retryable_error(pool: PgPool) -> anyhow::Result<()> {
+        // When
+        let err: anyhow::Error =
+            sqlx::query("DO $$ BEGIN RAISE EXCEPTION USING ERRCODE = '40P01'; END $$")
+                .execute(&pool)
+                .await
+                .unwrap_err()
+                .into();
+
+        // Expect
+        assert!(is_retryable_error(&err));
+        assert_eq!(sqlstate(&err), Some(DEADLOCK_DETECTED.to_string()));
+        assert!(Err::<(), _>(err).is_retryable());
+        assert!(!is_retryable_error(&anyhow::anyhow!("deadlock detected")));
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::NaiveDate;
+use poem::{
+    http::{HeaderValue, Method},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+use crate::core::utils::date_to_http_date;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeprecatedOperation {
+    pub method: Method,
+    /// Path template relative to the mount, e.g. /user/{id}
+    pub path: String,
+    pub sunset: Option<NaiveDate>,
+}
+
+impl DeprecatedOperation {
+    /// Key used by DEPRECATED_ROUTE_SUNSET, e.g. `GET /user/all`
+    pub fn key(&self) -> String {
+        format!("{} {}", self.method, self.path)
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method != method {
+            return false;
+        }
+        let template: Vec<&str> = self.path.split('/').collect();
+        let segments: Vec<&str> = path.split('/').collect();
+        template.len() == segments.len()
+            && template
+                .iter()
+                .zip(segments.iter())
+                .all(|(t, s)| t == s || (t.starts_with('{') && t.ends_with('}')))
+    }
+}
+
+/// Parse DEPRECATED_ROUTE_SUNSET, comma separated `METHOD /path@YYYY-MM-DD`
+pub fn parse_route_sunsets(value: Option<&str>) -> anyhow::Result<HashMap<String, NaiveDate>> {
+    let mut res = HashMap::new();
+    for item in value.unwrap_or("").split(',').map(|x| x.trim()) {
+        if item.is_empty() {
+            continue;
+        }
+        let (route, date) = match item.rsplit_once('@') {
+            Some(val) => val,
+            None => anyhow::bail!("invalid entry {}, expected METHOD /path@YYYY-MM-DD", item),
+        };
+        let (method, path) = match route.trim().split_once(' ') {
+            Some((method, path)) if path.trim().starts_with('/') => (method, path.trim()),
+            _ => anyhow::bail!("invalid route {}, expected METHOD /path", route),
+        };
+        let method = match Method::from_bytes(method.to_uppercase().as_bytes()) {
+            Ok(val) => val,
+            Err(_) => anyhow::bail!("invalid method {}", method),
+        };
+        let date = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(val) => val,
+            Err(_) => anyhow::bail!("invalid sunset date {}, expected YYYY-MM-DD", date),
+        };
+        // Operation paths in the spec carry no trailing slash
+        let path = match path.trim_end_matches('/') {
+            "" => "/",
+            val => val,
+        };
+        res.insert(format!("{} {}", method, path), date);
+    }
+    Ok(res)
+}
+
+/// Operations flagged deprecated in an OpenAPI json spec
+pub fn deprecated_operations(
+    spec: &str,
+    sunsets: &HashMap<String, NaiveDate>,
+) -> anyhow::Result<Vec<DeprecatedOperation>> {
+    let spec: serde_json::Value = serde_json::from_str(spec)?;
+    let mut res = vec![];
+    let paths = match spec["paths"].as_object() {
+        Some(val) => val,
+        None => return Ok(res),
+    };
+    for (path, operations) in paths.iter() {
+        let operations = match operations.as_object() {
+            Some(val) => val,
+            None => continue,
+        };
+        for (method, operation) in operations.iter() {
+            if operation["deprecated"] != serde_json::Value::Bool(true) {
+                continue;
+            }
+            let method = Method::from_bytes(method.to_uppercase().as_bytes())?;
+            let sunset = sunsets.get(&format!("{} {}", method, path)).copied();
+            res.push(DeprecatedOperation {
+                method,
+                path: path.clone(),
+                sunset,
+            });
+        }
+    }
+    Ok(res)
+}
+
+/// Add Deprecation and Sunset headers to responses of deprecated operations.
+/// Mark a handler with `#[oai(deprecated)]`: the operation is flagged in the
+/// OpenAPI spec and, unless DEPRECATION_HEADERS is false, its responses carry
+/// `Deprecation: true` plus `Sunset` when DEPRECATED_ROUTE_SUNSET has a date for it.
+pub struct DeprecatedRouteHeaders {
+    operations: Arc<Vec<DeprecatedOperation>>,
+}
+
+impl DeprecatedRouteHeaders {
+    /// Operations are read from the spec of the service the middleware wraps,
+    /// no operation is tracked when headers are disabled
+    pub fn new(
+        spec: &str,
+        sunsets: &HashMap<String, NaiveDate>,
+        enabled: bool,
+    ) -> anyhow::Result<Self> {
+        let operations = match enabled {
+            true => deprecated_operations(spec, sunsets)?,
+            false => vec![],
+        };
+        Ok(DeprecatedRouteHeaders {
+            operations: Arc::new(operations),
+        })
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for DeprecatedRouteHeaders {
+    type Output = DeprecatedRouteHeadersEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        DeprecatedRouteHeadersEndpoint {
+            inner: ep,
+            operations: self.operations.clone(),
+        }
+    }
+}
+
+pub struct DeprecatedRouteHeadersEndpoint<E> {
+    inner: E,
+    operations: Arc<Vec<DeprecatedOperation>>,
+}
+
+impl<E: Endpoint> Endpoint for DeprecatedRouteHeadersEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let operation = self
+            .operations
+            .iter()
+            .find(|x| x.matches(req.method(), req.uri().path()))
+            .cloned();
+        let mut resp = match self.inner.call(req).await {
+            Ok(val) => val.into_response(),
+            Err(err) => err.into_response(),
+        };
+        if let Some(operation) = operation {
+            let headers = resp.headers_mut();
+            headers.insert("Deprecation", HeaderValue::from_static("true"));
+            if let Some(sunset) = operation.sunset {
+                if let Ok(val) = HeaderValue::from_str(&date_to_http_date(sunset)) {
+                    headers.insert("Sunset", val);
+                }
+            }
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use poem::{http::Method, test::TestClient, EndpointExt};
+    use poem_openapi::{param::Path, payload::PlainText, OpenApi, OpenApiService};
+
+    use crate::core::deprecation::{
+        deprecated_operations, parse_route_sunsets, DeprecatedOperation, DeprecatedRouteHeaders,
+    };
+
+    struct Api;
+
+    #[OpenApi]
+    impl Api {
+        #[oai(path = "/item/:id", method = "get", deprecated)]
+        async fn old_item(&self, id: Path<String>) -> PlainText<String> {
+            PlainText(id.0)
+        }
+
+        #[oai(path = "/item/:id", method = "put")]
+        async fn item(&self, id: Path<String>) -> PlainText<String> {
+            PlainText(id.0)
+        }
+    }
+
+    #[test]
+    fn test_parse_route_sunsets() {
+        // Expect
+        let sunsets = parse_route_sunsets(Some("get /user/all/@2027-01-31")).unwrap();
+        assert_eq!(
+            sunsets.get("GET /user/all"),
+            Some(&NaiveDate::from_ymd_opt(2027, 1, 31).unwrap())
+        );
+        assert!(parse_route_sunsets(None).unwrap().is_empty());
+        assert!(parse_route_sunsets(Some("/user/all/@2027-01-31")).is_err());
+        assert!(parse_route_sunsets(Some("GET /user/all/")).is_err());
+        assert!(parse_route_sunsets(Some("GET /user/all/@tomorrow")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deprecated_route_headers() {
+        // Given
+        let service = OpenApiService::new(Api, "Test", "1.0");
+        let spec = service.spec();
+        let sunsets = parse_route_sunsets(Some("GET /item/{id}@2027-01-31")).unwrap();
+
+        // Expect flagged in spec
+        assert_eq!(
+            deprecated_operations(&spec, &sunsets).unwrap(),
+            vec![DeprecatedOperation {
+                method: Method::GET,
+                path: "/item/{id}".to_string(),
+                sunset: NaiveDate::from_ymd_opt(2027, 1, 31),
+            }]
+        );
+
+        // When
+        let cli = TestClient::new(
+            service.with(DeprecatedRouteHeaders::new(&spec, &sunsets, true).unwrap()),
+        );
+        let deprecated = cli.get("/item/1").send().await;
+        let current = cli.put("/item/1").send().await;
+
+        // Expect
+        deprecated.assert_status_is_ok();
+        deprecated.assert_header("Deprecation", "true");
+        deprecated.assert_header("Sunset", "Sun, 31 Jan 2027 00:00:00 GMT");
+        current.assert_status_is_ok();
+        current.assert_header_is_not_exist("Deprecation");
+
+        // Expect nothing emitted when disabled
+        let service = OpenApiService::new(Api, "Test", "1.0");
+        let cli = TestClient::new(
+            service.with(DeprecatedRouteHeaders::new(&spec, &sunsets, false).unwrap()),
+        );
+        cli.get("/item/1")
+            .send()
+            .await
+            .assert_header_is_not_exist("Deprecation");
+    }
+}
@@ -0,0 +1,856 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration as StdDuration,
+};
+
+use chrono::{DateTime, FixedOffset, Local};
+use ldap3::{LdapConnAsync, Scope, SearchEntry};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        lifecycle::{change_user_status, check_transition, revoke_deactivated_sessions},
+        permission_cache::permissions_changed,
+        security::hash_password,
+        session_store::{SessionPool, SessionStore},
+    },
+    model::{
+        directory_source::{DirectorySource, PROVIDER_AZURE_AD, PROVIDER_LDAP},
+        directory_sync_run::{DirectorySyncRun, STATUS_FAILED, STATUS_SUCCESS},
+        group::Group,
+        scim_provisioning_event::{EVENT_CREATE, EVENT_DEACTIVATE, EVENT_UPDATE},
+        user::{User, UserStatus},
+        user_group_roles::UserGroupRoles,
+        user_identity::UserIdentity,
+        user_profile::UserProfile,
+        user_status_history::UserStatusHistory,
+    },
+    repository::{
+        directory_source::{get_due_directory_source, update_directory_source_last_sync},
+        directory_sync_run::create_directory_sync_run,
+        group::{create_group, get_group_by_name},
+        scim_provisioning_event::enqueue_scim_event,
+        user::{create_user, get_user_by_id, get_user_by_username, update_user},
+        user_group_roles::{
+            add_user_group_roles, delete_user_group_roles_by_id, get_user_group_memberships,
+        },
+        user_identity::{
+            create_user_identity, get_user_identity, get_user_identity_by_directory_source,
+        },
+        user_status_history::create_user_status_history,
+    },
+};
+
+pub const DEFAULT_LDAP_USER_FILTER: &str = "(objectClass=person)";
+pub const DEFAULT_AZURE_AUTHORITY_URL: &str = "https://login.microsoftonline.com";
+pub const DEFAULT_AZURE_GRAPH_URL: &str = "https://graph.microsoft.com";
+
+const LDAP_USER_ATTRS: [&str; 10] = [
+    "uid",
+    "sAMAccountName",
+    "givenName",
+    "sn",
+    "mail",
+    "entryUUID",
+    "objectGUID",
+    "memberOf",
+    "userAccountControl",
+    "nsAccountLock",
+];
+// userAccountControl ACCOUNTDISABLE flag on active directory
+const LDAP_ACCOUNT_DISABLE: i64 = 0x2;
+
+/// User as seen by the upstream directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryUser {
+    pub external_id: String,
+    pub user_name: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub is_active: bool,
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DirectorySyncSummary {
+    pub users_created: i32,
+    pub users_updated: i32,
+    pub users_deactivated: i32,
+    pub groups_created: i32,
+    pub memberships_added: i32,
+    pub memberships_removed: i32,
+    /// Users whose memberships or status changed, their cached permissions are dropped
+    /// once the run is committed
+    pub changed_user_ids: Vec<Uuid>,
+    /// Users who left active, signed out once the run is committed
+    pub deactivated_user_ids: Vec<Uuid>,
+}
+
+/// Common name of the first RDN, e.g. `CN=Admins,OU=Groups,DC=corp` -> `Admins`
+pub fn dn_common_name(dn: &str) -> Option<String> {
+    let rdn = dn.split(',').next()?;
+    let (key, value) = rdn.split_once('=')?;
+    if key.trim().eq_ignore_ascii_case("cn") {
+        Some(value.trim().to_string())
+    } else {
+        None
+    }
+}
+
+pub fn first_attr(entry: &SearchEntry, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| entry.attrs.get(*name).and_then(|vals| vals.first()))
+        .cloned()
+}
+
+/// Stable id of an ldap entry, entryUUID or objectGUID, the dn when neither is returned
+pub fn ldap_external_id(entry: &SearchEntry) -> String {
+    match first_attr(entry, &["entryUUID"]) {
+        Some(val) => val,
+        None => match entry.bin_attrs.get("objectGUID").and_then(|x| x.first()) {
+            Some(guid) => guid.iter().map(|b| format!("{:02x}", b)).collect(),
+            None => entry.dn.to_lowercase(),
+        },
+    }
+}
+
+/// Map ldap search entry into directory user, entries without user name are ignored
+pub fn ldap_entry_to_directory_user(entry: &SearchEntry) -> Option<DirectoryUser> {
+    let user_name = first_attr(entry, &["sAMAccountName", "uid"])?;
+    let external_id = ldap_external_id(entry);
+    let disabled_by_uac = first_attr(entry, &["userAccountControl"])
+        .and_then(|val| val.parse::<i64>().ok())
+        .map(|val| val & LDAP_ACCOUNT_DISABLE != 0)
+        .unwrap_or(false);
+    let disabled_by_lock = first_attr(entry, &["nsAccountLock"])
+        .map(|val| val.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    let groups = entry
+        .attrs
+        .get("memberOf")
+        .map(|dns| dns.iter().filter_map(|dn| dn_common_name(dn)).collect())
+        .unwrap_or_default();
+    Some(DirectoryUser {
+        external_id,
+        user_name,
+        first_name: first_attr(entry, &["givenName"]),
+        last_name: first_attr(entry, &["sn"]),
+        email: first_attr(entry, &["mail"]),
+        is_active: !disabled_by_uac && !disabled_by_lock,
+        groups,
+    })
+}
+
+pub async fn fetch_ldap_users(source: &DirectorySource) -> anyhow::Result<Vec<DirectoryUser>> {
+    let (url, base_dn) = match (&source.ldap_url, &source.ldap_base_dn) {
+        (Some(url), Some(base_dn)) => (url, base_dn),
+        _ => anyhow::bail!("ldap source require ldap_url and ldap_base_dn"),
+    };
+    let (conn, mut ldap) = LdapConnAsync::new(url).await?;
+    ldap3::drive!(conn);
+    if let Some(bind_dn) = &source.ldap_bind_dn {
+        ldap.simple_bind(
+            bind_dn,
+            source.ldap_bind_password.as_deref().unwrap_or_default(),
+        )
+        .await?
+        .success()?;
+    }
+
+    let user_filter = source
+        .ldap_user_filter
+        .as_deref()
+        .unwrap_or(DEFAULT_LDAP_USER_FILTER);
+    let (entries, _) = ldap
+        .search(
+            base_dn,
+            Scope::Subtree,
+            user_filter,
+            LDAP_USER_ATTRS.to_vec(),
+        )
+        .await?
+        .success()?;
+    let mut users: Vec<(String, DirectoryUser)> = entries
+        .into_iter()
+        .map(SearchEntry::construct)
+        .filter_map(|entry| {
+            ldap_entry_to_directory_user(&entry).map(|user| (entry.dn.to_lowercase(), user))
+        })
+        .collect();
+
+    // directories without memberOf overlay expose membership on the group entry instead
+    if let Some(group_filter) = &source.ldap_group_filter {
+        let (entries, _) = ldap
+            .search(
+                base_dn,
+                Scope::Subtree,
+                group_filter,
+                vec!["cn", "member", "uniqueMember"],
+            )
+            .await?
+            .success()?;
+        let mut member_groups: HashMap<String, Vec<String>> = HashMap::new();
+        for entry in entries.into_iter().map(SearchEntry::construct) {
+            let group_name = match first_attr(&entry, &["cn"]) {
+                Some(val) => val,
+                None => continue,
+            };
+            for attr in ["member", "uniqueMember"] {
+                for member in entry.attrs.get(attr).into_iter().flatten() {
+                    member_groups
+                        .entry(member.to_lowercase())
+                        .or_default()
+                        .push(group_name.clone());
+                }
+            }
+        }
+        for (dn, user) in users.iter_mut() {
+            if let Some(groups) = member_groups.remove(dn.as_str()) {
+                user.groups.extend(groups);
+            }
+        }
+    }
+    let _ = ldap.unbind().await;
+    Ok(users.into_iter().map(|(_, user)| user).collect())
+}
+
+#[derive(Deserialize)]
+struct GraphTokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct GraphPage<T> {
+    value: Vec<T>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphUser {
+    id: String,
+    user_principal_name: String,
+    given_name: Option<String>,
+    surname: Option<String>,
+    mail: Option<String>,
+    account_enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphGroup {
+    id: String,
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct GraphDirectoryObject {
+    id: String,
+}
+
+async fn graph_get_all<T: for<'de> Deserialize<'de>>(
+    http: &reqwest::Client,
+    token: &str,
+    url: String,
+) -> anyhow::Result<Vec<T>> {
+    let mut results: Vec<T> = vec![];
+    let mut next_url = Some(url);
+    while let Some(url) = next_url {
+        let page: GraphPage<T> = http
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        results.extend(page.value);
+        next_url = page.next_link;
+    }
+    Ok(results)
+}
+
+pub async fn fetch_azure_ad_users(source: &DirectorySource) -> anyhow::Result<Vec<DirectoryUser>> {
+    let (tenant_id, client_id, client_secret) = match (
+        &source.azure_tenant_id,
+        &source.azure_client_id,
+        &source.azure_client_secret,
+    ) {
+        (Some(tenant_id), Some(client_id), Some(client_secret)) => {
+            (tenant_id, client_id, client_secret)
+        }
+        _ => anyhow::bail!(
+            "azure_ad source require azure_tenant_id, azure_client_id and azure_client_secret"
+        ),
+    };
+    let authority_url = source
+        .azure_authority_url
+        .as_deref()
+        .unwrap_or(DEFAULT_AZURE_AUTHORITY_URL)
+        .trim_end_matches('/');
+    let graph_url = source
+        .azure_graph_url
+        .as_deref()
+        .unwrap_or(DEFAULT_AZURE_GRAPH_URL)
+        .trim_end_matches('/');
+    let http = reqwest::Client::builder()
+        .timeout(StdDuration::from_secs(30))
+        .build()?;
+
+    let token: GraphTokenResponse = http
+        .post(format!("{}/{}/oauth2/v2.0/token", authority_url, tenant_id))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("scope", format!("{}/.default", graph_url).as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let graph_users: Vec<GraphUser> = graph_get_all(
+        &http,
+        &token.access_token,
+        format!(
+            "{}/v1.0/users?$select=id,userPrincipalName,givenName,surname,mail,accountEnabled",
+            graph_url
+        ),
+    )
+    .await?;
+    let graph_groups: Vec<GraphGroup> = graph_get_all(
+        &http,
+        &token.access_token,
+        format!("{}/v1.0/groups?$select=id,displayName", graph_url),
+    )
+    .await?;
+    let mut member_groups: HashMap<String, Vec<String>> = HashMap::new();
+    for group in graph_groups {
+        let members: Vec<GraphDirectoryObject> = graph_get_all(
+            &http,
+            &token.access_token,
+            format!("{}/v1.0/groups/{}/members?$select=id", graph_url, group.id),
+        )
+        .await?;
+        for member in members {
+            member_groups
+                .entry(member.id)
+                .or_default()
+                .push(group.display_name.clone());
+        }
+    }
+
+    Ok(graph_users
+        .into_iter()
+        .map(|item| DirectoryUser {
+            groups: member_groups.remove(&item.id).unwrap_or_default(),
+            external_id: item.id,
+            user_name: item.user_principal_name,
+            first_name: item.given_name,
+            last_name: item.surname,
+            email: item.mail,
+            is_active: item.account_enabled.unwrap_or(true),
+        })
+        .collect())
+}
+
+pub async fn fetch_directory_users(source: &DirectorySource) -> anyhow::Result<Vec<DirectoryUser>> {
+    match source.provider.as_str() {
+        PROVIDER_LDAP => fetch_ldap_users(source).await,
+        PROVIDER_AZURE_AD => fetch_azure_ad_users(source).await,
+        provider => anyhow::bail!("unknown directory provider {}", provider),
+    }
+}
+
+pub fn empty_user_profile(user_id: Uuid) -> UserProfile {
+    UserProfile {
+        id: user_id,
+        user_id,
+        first_name: None,
+        last_name: None,
+        address: None,
+        email: None,
+        locale: None,
+        timezone: None,
+        email_verified_at: None,
+    }
+}
+
+/// Copy directory attributes into local user, return true when anything changed
+fn apply_directory_user(
+    user: &mut User,
+    user_profile: &mut UserProfile,
+    directory_user: &DirectoryUser,
+) -> bool {
+    // suspend or reactivate on the directory flag, deprovisioned users stay deprovisioned
+    let status = UserStatus::from_active(directory_user.is_active);
+    let status_changed = user.status != status && check_transition(user.status, status).is_none();
+    let changed = user.user_name != directory_user.user_name
+        || status_changed
+        || user_profile.first_name != directory_user.first_name
+        || user_profile.last_name != directory_user.last_name
+        || user_profile.email != directory_user.email;
+    user.user_name = directory_user.user_name.clone();
+    if status_changed {
+        user.status = status;
+    }
+    user_profile.first_name = directory_user.first_name.clone();
+    user_profile.last_name = directory_user.last_name.clone();
+    user_profile.email = directory_user.email.clone();
+    changed
+}
+
+/// Directory is the source of truth for role-less group memberships of synced users,
+/// memberships carrying a role are managed locally and left untouched
+#[allow(clippy::too_many_arguments)]
+async fn sync_group_memberships(
+    tx: &mut Transaction<'_, Postgres>,
+    source: &DirectorySource,
+    actor: &User,
+    user_id: &Uuid,
+    group_names: &[String],
+    group_cache: &mut HashMap<String, Group>,
+    summary: &mut DirectorySyncSummary,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    let mut desired_group_ids: HashSet<Uuid> = HashSet::new();
+    for group_name in group_names {
+        if !group_cache.contains_key(group_name) {
+            let group = match get_group_by_name(tx, group_name).await? {
+                Some(val) => val,
+                None => {
+                    summary.groups_created += 1;
+                    create_group(
+                        tx,
+                        None,
+                        group_name.clone(),
+                        Some(format!("imported from directory {}", source.name)),
+                        Some(true),
+                        actor.clone(),
+                        Some(*now),
+                    )
+                    .await?
+                }
+            };
+            group_cache.insert(group_name.clone(), group);
+        }
+        desired_group_ids.insert(group_cache[group_name].id);
+    }
+
+    let memberships = get_user_group_memberships(tx, user_id).await?;
+    let current_group_ids: HashSet<Uuid> = memberships
+        .iter()
+        .filter_map(|item| item.group_id)
+        .collect();
+    let changed_before = summary.memberships_added + summary.memberships_removed;
+    for item in memberships.iter() {
+        if !item
+            .group_id
+            .is_some_and(|group_id| desired_group_ids.contains(&group_id))
+        {
+            delete_user_group_roles_by_id(tx, &item.id).await?;
+            summary.memberships_removed += 1;
+        }
+    }
+    for group_id in desired_group_ids.difference(&current_group_ids) {
+        add_user_group_roles(
+            tx,
+            &UserGroupRoles {
+                id: Uuid::now_v7(),
+                user_id: Some(*user_id),
+                group_id: Some(*group_id),
+                role_id: None,
+            },
+        )
+        .await?;
+        summary.memberships_added += 1;
+    }
+    if summary.memberships_added + summary.memberships_removed != changed_before {
+        summary.changed_user_ids.push(*user_id);
+    }
+    Ok(())
+}
+
+/// Reconcile local users of a directory source against the users fetched from it
+pub async fn reconcile_directory_users(
+    tx: &mut Transaction<'_, Postgres>,
+    source: &DirectorySource,
+    actor: &User,
+    directory_users: &[DirectoryUser],
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<DirectorySyncSummary> {
+    let mut summary = DirectorySyncSummary::default();
+    let identities = get_user_identity_by_directory_source(tx, &source.id).await?;
+    // an empty result is far more likely a misconfigured filter than an empty directory
+    if directory_users.is_empty() && !identities.is_empty() {
+        anyhow::bail!(
+            "directory returned no users, refusing to deactivate {} synced users",
+            identities.len()
+        );
+    }
+
+    let mut group_cache: HashMap<String, Group> = HashMap::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for directory_user in directory_users {
+        if !seen.insert(directory_user.external_id.clone()) {
+            continue;
+        }
+        let identity = get_user_identity(tx, &source.provider, &directory_user.external_id).await?;
+        let linked = identity.is_some();
+        let existing = match identity {
+            Some(identity) => get_user_by_id(tx, &identity.user_id, Some(true)).await?,
+            None => {
+                let (user, user_profile) =
+                    get_user_by_username(tx, &directory_user.user_name).await?;
+                match user {
+                    // adopt local account with the same user name
+                    Some(user) if user.deleted_date.is_none() => {
+                        create_user_identity(
+                            tx,
+                            &UserIdentity {
+                                id: Uuid::now_v7(),
+                                user_id: user.id,
+                                provider: source.provider.clone(),
+                                subject: directory_user.external_id.clone(),
+                                directory_source_id: Some(source.id),
+                                created_date: Some(*now),
+                                updated_date: Some(*now),
+                            },
+                        )
+                        .await?;
+                        (Some(user), user_profile)
+                    }
+                    Some(_) => {
+                        tracing::warn!(
+                            "directory sync {}: user name {} belongs to a deleted user, skipped",
+                            source.name,
+                            directory_user.user_name
+                        );
+                        continue;
+                    }
+                    None => (None, None),
+                }
+            }
+        };
+
+        let user_id = match existing {
+            (Some(mut user), user_profile) => {
+                let from_status = user.status;
+                let mut user_profile = user_profile.unwrap_or(empty_user_profile(user.id));
+                if apply_directory_user(&mut user, &mut user_profile, directory_user) {
+                    update_user(tx, &mut user, &user_profile, actor, now).await?;
+                    let history = UserStatusHistory {
+                        id: Uuid::now_v7(),
+                        user_id: user.id,
+                        from_status,
+                        to_status: user.status,
+                        reason: Some(format!("directory sync {}", source.name)),
+                        changed_by: Some(actor.id),
+                        created_date: Some(*now),
+                    };
+                    if history.from_status != history.to_status {
+                        create_user_status_history(tx, &history).await?;
+                    }
+                    if history.left_active() {
+                        summary.users_deactivated += 1;
+                        summary.changed_user_ids.push(user.id);
+                        summary.deactivated_user_ids.push(user.id);
+                        enqueue_scim_event(tx, &user.id, EVENT_DEACTIVATE, Some(*now)).await?;
+                    } else {
+                        summary.users_updated += 1;
+                        enqueue_scim_event(tx, &user.id, EVENT_UPDATE, Some(*now)).await?;
+                    }
+                }
+                user.id
+            }
+            // linked user was deleted locally, deletion wins over the directory
+            (None, _) if linked => continue,
+            (None, _) => {
+                let id = Uuid::now_v7();
+                // directory users authenticate upstream, local password is never handed out
+                let password = match hash_password(&Uuid::now_v7().to_string()) {
+                    Ok(val) => val,
+                    Err(err) => anyhow::bail!(err.to_string()),
+                };
+                let mut new_user = User {
+                    id,
+                    user_name: String::new(),
+                    password,
+                    status: UserStatus::from_active(directory_user.is_active),
+                    expires_at: None,
+                    is_2faenabled: Some(false),
+                    created_by: Some(actor.id),
+                    updated_by: Some(actor.id),
+                    created_date: Some(*now),
+                    updated_date: Some(*now),
+                    deleted_date: None,
+                    scopes: None,
+                };
+                let mut new_user_profile = empty_user_profile(id);
+                apply_directory_user(&mut new_user, &mut new_user_profile, directory_user);
+                create_user(tx, &new_user, &new_user_profile).await?;
+                create_user_identity(
+                    tx,
+                    &UserIdentity {
+                        id: Uuid::now_v7(),
+                        user_id: id,
+                        provider: source.provider.clone(),
+                        subject: directory_user.external_id.clone(),
+                        directory_source_id: Some(source.id),
+                        created_date: Some(*now),
+                        updated_date: Some(*now),
+                    },
+                )
+                .await?;
+                summary.users_created += 1;
+                enqueue_scim_event(tx, &id, EVENT_CREATE, Some(*now)).await?;
+                id
+            }
+        };
+
+        sync_group_memberships(
+            tx,
+            source,
+            actor,
+            &user_id,
+            &directory_user.groups,
+            &mut group_cache,
+            &mut summary,
+            now,
+        )
+        .await?;
+    }
+
+    // users removed from the directory are deactivated, never deleted
+    for identity in identities.iter() {
+        if seen.contains(&identity.subject) {
+            continue;
+        }
+        let (user, _) = get_user_by_id(tx, &identity.user_id, Some(true)).await?;
+        let mut user = match user {
+            Some(user) if user.status == UserStatus::Active => user,
+            _ => continue,
+        };
+        change_user_status(
+            tx,
+            &mut user,
+            UserStatus::Suspended,
+            Some(format!("removed from directory {}", source.name)),
+            Some(actor.id),
+            now,
+        )
+        .await?;
+        summary.users_deactivated += 1;
+        summary.changed_user_ids.push(user.id);
+        summary.deactivated_user_ids.push(user.id);
+        enqueue_scim_event(tx, &user.id, EVENT_DEACTIVATE, Some(*now)).await?;
+    }
+    Ok(summary)
+}
+
+async fn sync_directory_source<C: SessionStore>(
+    pool: &PgPool,
+    redis_conn: &mut C,
+    source: &DirectorySource,
+    started_date: &DateTime<FixedOffset>,
+) -> anyhow::Result<DirectorySyncRun> {
+    let directory_users = fetch_directory_users(source).await?;
+
+    let mut tx = pool.begin().await?;
+    // serialize runs of the same source between scheduled worker and manual trigger
+    let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_xact_lock(hashtext($1::text))")
+        .bind(source.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !locked {
+        anyhow::bail!("directory sync for source {} already running", source.name);
+    }
+    // changes made by sync are attributed to the user who configured the source
+    let actor = match source.created_by {
+        Some(created_by) => get_user_by_id(&mut tx, &created_by, Some(false)).await?.0,
+        None => None,
+    };
+    let actor = match actor {
+        Some(val) => val,
+        None => anyhow::bail!("directory source {} has no owner", source.name),
+    };
+    let now = Local::now().fixed_offset();
+    let summary =
+        reconcile_directory_users(&mut tx, source, &actor, &directory_users, &now).await?;
+    let run = DirectorySyncRun {
+        id: Uuid::now_v7(),
+        source_id: source.id,
+        status: STATUS_SUCCESS.to_string(),
+        users_created: summary.users_created,
+        users_updated: summary.users_updated,
+        users_deactivated: summary.users_deactivated,
+        groups_created: summary.groups_created,
+        memberships_added: summary.memberships_added,
+        memberships_removed: summary.memberships_removed,
+        error: None,
+        started_date: Some(*started_date),
+        finished_date: Some(Local::now().fixed_offset()),
+    };
+    create_directory_sync_run(&mut tx, &run).await?;
+    update_directory_source_last_sync(&mut tx, &source.id, started_date).await?;
+    tx.commit().await?;
+    revoke_deactivated_sessions(redis_conn, &summary.deactivated_user_ids).await?;
+    permissions_changed(redis_conn, &summary.changed_user_ids).await;
+    Ok(run)
+}
+
+/// Sync one source and record the run, failures are recorded as failed runs
+pub async fn run_directory_sync<C: SessionStore>(
+    pool: &PgPool,
+    redis_conn: &mut C,
+    source: &DirectorySource,
+) -> anyhow::Result<DirectorySyncRun> {
+    let started_date = Local::now().fixed_offset();
+    match sync_directory_source(pool, redis_conn, source, &started_date).await {
+        Ok(run) => Ok(run),
+        Err(err) => {
+            tracing::warn!("directory sync {} failed: {}", source.name, err);
+            let run = DirectorySyncRun {
+                id: Uuid::now_v7(),
+                source_id: source.id,
+                status: STATUS_FAILED.to_string(),
+                users_created: 0,
+                users_updated: 0,
+                users_deactivated: 0,
+                groups_created: 0,
+                memberships_added: 0,
+                memberships_removed: 0,
+                error: Some(err.to_string()),
+                started_date: Some(started_date),
+                finished_date: Some(Local::now().fixed_offset()),
+            };
+            let mut tx = pool.begin().await?;
+            create_directory_sync_run(&mut tx, &run).await?;
+            update_directory_source_last_sync(&mut tx, &source.id, &started_date).await?;
+            tx.commit().await?;
+            Ok(run)
+        }
+    }
+}
+
+/// Sync every source whose interval elapsed, return number of synced sources
+pub async fn sync_due_directory_sources<C: SessionStore>(
+    pool: &PgPool,
+    redis_conn: &mut C,
+) -> anyhow::Result<u32> {
+    let mut tx = pool.begin().await?;
+    let now = Local::now().fixed_offset();
+    let sources = get_due_directory_source(&mut tx, &now).await?;
+    tx.commit().await?;
+    for source in sources.iter() {
+        run_directory_sync(pool, redis_conn, source).await?;
+    }
+    Ok(sources.len() as u32)
+}
+
+/// Check for due directory sources in background every `interval`
+pub fn spawn_directory_sync_worker(
+    pool: PgPool,
+    redis_pool: SessionPool,
+    interval: StdDuration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let res = match redis_pool.get().await {
+                Ok(mut redis_conn) => sync_due_directory_sources(&pool, &mut redis_conn).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = res {
+                tracing::error!(
+                    "error: on core::directory_sync::spawn_directory_sync_worker error: {}",
+                    err
+                );
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn entry(dn: &str, attrs: &[(&str, &[&str])]) -> SearchEntry {
+        SearchEntry {
+            dn: dn.to_string(),
+            attrs: attrs
+                .iter()
+                .map(|(key, vals)| {
+                    (
+                        key.to_string(),
+                        vals.iter().map(|x| x.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            bin_attrs: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_dn_common_name() {
+        assert_eq!(
+            dn_common_name("CN=Admins,OU=Groups,DC=corp,DC=local"),
+            Some("Admins".to_string())
+        );
+        assert_eq!(dn_common_name("cn=dev, ou=groups"), Some("dev".to_string()));
+        assert_eq!(dn_common_name("OU=Groups,DC=corp"), None);
+    }
+
+    #[test]
+    fn test_ldap_entry_to_directory_user() {
+        let ad_user = entry(
+            "CN=John Doe,OU=Users,DC=corp,DC=local",
+            &[
+                ("sAMAccountName", &["john"]),
+                ("givenName", &["John"]),
+                ("sn", &["Doe"]),
+                ("mail", &["john@corp.local"]),
+                ("userAccountControl", &["514"]),
+                (
+                    "memberOf",
+                    &["CN=Admins,OU=Groups,DC=corp,DC=local", "CN=Dev,OU=Groups"],
+                ),
+            ],
+        );
+        assert_eq!(
+            ldap_entry_to_directory_user(&ad_user),
+            Some(DirectoryUser {
+                external_id: "cn=john doe,ou=users,dc=corp,dc=local".to_string(),
+                user_name: "john".to_string(),
+                first_name: Some("John".to_string()),
+                last_name: Some("Doe".to_string()),
+                email: Some("john@corp.local".to_string()),
+                is_active: false,
+                groups: vec!["Admins".to_string(), "Dev".to_string()],
+            })
+        );
+
+        let openldap_user = entry(
+            "uid=jane,ou=people,dc=example",
+            &[("uid", &["jane"]), ("entryUUID", &["1234-abcd"])],
+        );
+        let user = ldap_entry_to_directory_user(&openldap_user).unwrap();
+        assert_eq!(user.external_id, "1234-abcd".to_string());
+        assert!(user.is_active);
+        assert!(user.groups.is_empty());
+
+        let no_user_name = entry("cn=printer,dc=example", &[("cn", &["printer"])]);
+        assert_eq!(ldap_entry_to_directory_user(&no_user_name), None);
+    }
+}
@@ -0,0 +1,370 @@
+use std::{fmt, str::FromStr, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::task::JoinHandle;
+
+use crate::{
+    core::{
+        lifecycle::{change_user_status, revoke_deactivated_sessions},
+        session_store::{SessionPool, SessionStore},
+        utils::datetime_to_string_opt,
+    },
+    model::{
+        scim_provisioning_event::EVENT_DEACTIVATE, user::UserStatus,
+        user_activity::DormantCandidate,
+    },
+    repository::{
+        scim_provisioning_event::enqueue_scim_event,
+        user::get_user_by_id,
+        user_activity::{
+            get_dormant_candidates, set_user_dormant_flagged, set_user_dormant_warning,
+        },
+    },
+    settings::Config,
+};
+
+pub const DEFAULT_WARNING_DAYS: u64 = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DormantAction {
+    /// Only mark the account, listed on GET /dormant-account/
+    Flag,
+    Suspend,
+}
+
+impl FromStr for DormantAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "flag" => Ok(DormantAction::Flag),
+            "suspend" => Ok(DormantAction::Suspend),
+            _ => anyhow::bail!("must be flag or suspend, got {}", s),
+        }
+    }
+}
+
+impl fmt::Display for DormantAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = match self {
+            DormantAction::Flag => "flag",
+            DormantAction::Suspend => "suspend",
+        };
+        write!(f, "{}", val)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DormantPolicy {
+    /// Days without login before the action is taken
+    pub days: u64,
+    /// Days between the warning and the action
+    pub warning_days: u64,
+    pub action: DormantAction,
+    /// Warnings are POSTed here as json, only logged when empty
+    pub webhook_url: Option<String>,
+}
+
+impl DormantPolicy {
+    /// None when DORMANT_ACCOUNT_DAYS is not set
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let days = match config.dormant_account_days {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        let warning_days = config
+            .dormant_account_warning_days
+            .unwrap_or(DEFAULT_WARNING_DAYS);
+        if warning_days >= days {
+            anyhow::bail!(
+                "DORMANT_ACCOUNT_WARNING_DAYS ({}) must be lower than DORMANT_ACCOUNT_DAYS ({})",
+                warning_days,
+                days
+            );
+        }
+        let action = match &config.dormant_account_action {
+            Some(val) => val.parse()?,
+            None => DormantAction::Suspend,
+        };
+        Ok(Some(Self {
+            days,
+            warning_days,
+            action,
+            webhook_url: config
+                .dormant_account_webhook_url
+                .clone()
+                .filter(|x| !x.is_empty()),
+        }))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DormantSummary {
+    pub warned: u64,
+    pub flagged: u64,
+    pub suspended: u64,
+}
+
+impl fmt::Display for DormantSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "warned: {}, flagged: {}, suspended: {}",
+            self.warned, self.flagged, self.suspended
+        )
+    }
+}
+
+/// Body of the warning sent before the action is taken
+#[derive(Debug, Serialize)]
+pub struct DormantWarning {
+    pub user_id: String,
+    pub user_name: String,
+    pub email: Option<String>,
+    /// Profile locale and timezone the receiver renders the warning in, empty for defaults
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub last_activity_date: Option<String>,
+    pub action: String,
+    /// Earliest date the action is taken unless the user logs in
+    pub action_date: Option<String>,
+}
+
+async fn send_dormant_warning(
+    policy: &DormantPolicy,
+    warning: &DormantWarning,
+) -> anyhow::Result<()> {
+    let webhook_url = match &policy.webhook_url {
+        Some(val) => val,
+        None => {
+            tracing::warn!(
+                "dormant account {} ({}) will be {} on {}",
+                warning.user_name,
+                warning.user_id,
+                warning.action,
+                warning.action_date.clone().unwrap_or_default()
+            );
+            return Ok(());
+        }
+    };
+    let http = reqwest::Client::builder()
+        .timeout(StdDuration::from_secs(10))
+        .build()?;
+    let resp = http.post(webhook_url).json(warning).send().await?;
+    if !resp.status().is_success() {
+        anyhow::bail!("dormant warning webhook answered {}", resp.status());
+    }
+    Ok(())
+}
+
+async fn warn_dormant_user(
+    tx: &mut Transaction<'_, Postgres>,
+    policy: &DormantPolicy,
+    candidate: &DormantCandidate,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    let (_, user_profile) = get_user_by_id(tx, &candidate.user_id, None).await?;
+    let (email, locale, timezone) = match user_profile {
+        Some(val) => (val.email, val.locale, val.timezone),
+        None => (None, None, None),
+    };
+    let warning = DormantWarning {
+        user_id: candidate.user_id.to_string(),
+        user_name: candidate.user_name.clone(),
+        email,
+        locale,
+        timezone,
+        last_activity_date: datetime_to_string_opt(candidate.last_activity_date),
+        action: policy.action.to_string(),
+        action_date: datetime_to_string_opt(Some(
+            *now + Duration::days(policy.warning_days as i64),
+        )),
+    };
+    send_dormant_warning(policy, &warning).await?;
+    set_user_dormant_warning(tx, &candidate.user_id, now).await
+}
+
+/// Warn users nearing DORMANT_ACCOUNT_DAYS without login, then flag or suspend them once
+/// the warning is at least DORMANT_ACCOUNT_WARNING_DAYS old. Exempt users are skipped.
+pub async fn run_dormant_account_check<C: SessionStore>(
+    pool: &PgPool,
+    redis_conn: &mut C,
+    policy: &DormantPolicy,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<DormantSummary> {
+    let now = now.unwrap_or(Local::now().fixed_offset());
+    let dormant_since = now - Duration::days(policy.days as i64);
+    let warn_since = now - Duration::days((policy.days - policy.warning_days) as i64);
+    let warned_before = now - Duration::days(policy.warning_days as i64);
+    let mut summary = DormantSummary::default();
+    let mut suspended_user_ids = vec![];
+    let mut tx = pool.begin().await?;
+    for candidate in get_dormant_candidates(&mut tx, &warn_since).await? {
+        let warning_date = match candidate.dormant_warning_date {
+            Some(val) => val,
+            None => {
+                // failed warnings are retried on the next run, the action waits for them
+                match warn_dormant_user(&mut tx, policy, &candidate, &now).await {
+                    Ok(_) => summary.warned += 1,
+                    Err(err) => tracing::error!(
+                        "error: on core::dormant_account warning user {} error: {}",
+                        candidate.user_id,
+                        err
+                    ),
+                }
+                continue;
+            }
+        };
+        let is_dormant = candidate
+            .last_activity_date
+            .is_none_or(|x| x < dormant_since);
+        if !is_dormant || warning_date > warned_before {
+            continue;
+        }
+        match policy.action {
+            DormantAction::Flag => {
+                if candidate.dormant_flagged_date.is_none() {
+                    set_user_dormant_flagged(&mut tx, &candidate.user_id, &now).await?;
+                    summary.flagged += 1;
+                }
+            }
+            DormantAction::Suspend => {
+                let (user, _) = get_user_by_id(&mut tx, &candidate.user_id, None).await?;
+                let mut user = match user {
+                    Some(val) => val,
+                    None => continue,
+                };
+                change_user_status(
+                    &mut tx,
+                    &mut user,
+                    UserStatus::Suspended,
+                    Some(format!("no login for {} days", policy.days)),
+                    None,
+                    &now,
+                )
+                .await?;
+                enqueue_scim_event(&mut tx, &user.id, EVENT_DEACTIVATE, Some(now)).await?;
+                summary.suspended += 1;
+                suspended_user_ids.push(user.id);
+            }
+        }
+    }
+    tx.commit().await?;
+    revoke_deactivated_sessions(redis_conn, &suspended_user_ids).await?;
+    Ok(summary)
+}
+
+pub fn spawn_dormant_account_worker(
+    pool: PgPool,
+    redis_pool: SessionPool,
+    interval: StdDuration,
+    policy: DormantPolicy,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let res = match redis_pool.get().await {
+                Ok(mut redis_conn) => {
+                    run_dormant_account_check(&pool, &mut redis_conn, &policy, None).await
+                }
+                Err(err) => Err(err.into()),
+            };
+            match res {
+                Ok(summary) => tracing::info!("dormant account check {}", summary),
+                Err(err) => tracing::error!(
+                    "error: on core::dormant_account::spawn_dormant_account_worker error: {}",
+                    err
+                ),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            dormant_account::{run_dormant_account_check, DormantAction, DormantPolicy},
+            session_store::MemoryStore,
+        },
+        factory::user::UserFactory,
+        model::user::{User, UserStatus},
+        repository::{
+            user::get_user_by_id,
+            user_activity::{
+                get_user_activity_by_user, record_user_login, set_user_dormant_exemption,
+            },
+        },
+    };
+
+    #[sqlx::test]
+    async fn test_run_dormant_account_check(pool: PgPool) -> anyhow::Result<()> {
+        // Given users created 100 days ago, one logged in recently and one exempt
+        let now = Local::now().fixed_offset();
+        let mut factory = UserFactory::<chrono::DateTime<chrono::FixedOffset>>::new();
+        factory.modified_many(|x, _, created_date| {
+            let mut user = User {
+                created_date: Some(created_date),
+                deleted_date: None,
+                ..x.clone()
+            };
+            user.status = UserStatus::Active;
+            user
+        });
+        let users = factory
+            .generate_many(&pool, 3, now - Duration::days(100))
+            .await?;
+        let mut tx = pool.begin().await?;
+        record_user_login(&mut tx, &users[1].id, &(now - Duration::days(1))).await?;
+        set_user_dormant_exemption(&mut tx, &users[2].id, true, None, &now).await?;
+        tx.commit().await?;
+        let policy = DormantPolicy {
+            days: 90,
+            warning_days: 7,
+            action: DormantAction::Suspend,
+            webhook_url: None,
+        };
+
+        // When
+        let mut redis_conn = MemoryStore::new();
+        let first = run_dormant_account_check(&pool, &mut redis_conn, &policy, Some(now)).await?;
+        let second = run_dormant_account_check(&pool, &mut redis_conn, &policy, Some(now)).await?;
+        let third = run_dormant_account_check(
+            &pool,
+            &mut redis_conn,
+            &policy,
+            Some(now + Duration::days(8)),
+        )
+        .await?;
+
+        // Expect warning first, action only after the warning period
+        assert_eq!(first.warned, 1);
+        assert_eq!(second.warned + second.suspended, 0);
+        assert_eq!(third.suspended, 1);
+        let mut tx = pool.begin().await?;
+        let (user, _) = get_user_by_id(&mut tx, &users[0].id, None).await?;
+        assert_eq!(user.unwrap().status, UserStatus::Suspended);
+        for user in users[1..].iter() {
+            let (user, _) = get_user_by_id(&mut tx, &user.id, None).await?;
+            assert_eq!(user.unwrap().status, UserStatus::Active);
+        }
+        let activity = get_user_activity_by_user(&mut tx, &users[0].id).await?;
+        assert!(activity.unwrap().dormant_warning_date.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dormant_action() {
+        assert_eq!(
+            "Flag".parse::<DormantAction>().unwrap(),
+            DormantAction::Flag
+        );
+        assert!("delete".parse::<DormantAction>().is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use serde::Serialize;
+
+use crate::{
+    core::mailer::{ConfiguredMailer, Mailer},
+    settings::Config,
+};
+
+/// Email handed to a mailer, also the body POSTed to EMAIL_WEBHOOK_URL
+#[derive(Clone, Debug, Serialize)]
+pub struct OutgoingEmail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Send with the mailer selected by EMAIL_MAILER
+pub async fn send_email(config: &Config, email: &OutgoingEmail) -> anyhow::Result<()> {
+    ConfiguredMailer::from_config(config)?.send(email).await
+}
+
+/// Loose check, the confirmation link proves the address
+pub fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::email::is_valid_email;
+
+    #[test]
+    fn test_is_valid_email() {
+        assert!(is_valid_email("jane.doe@example.com"));
+        assert!(!is_valid_email("jane.doe"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("jane@localhost"));
+        assert!(!is_valid_email("jane doe@example.com"));
+    }
+}
@@ -0,0 +1,218 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        locale::UserLocale, notifications::send_notification, session_store::SessionStore,
+        user_contact::alert_phone, utils::utc_now,
+    },
+    model::{
+        notification_template::{
+            CHANNEL_EMAIL, CHANNEL_SMS, EVENT_EMAIL_CHANGE_CONFIRM, EVENT_EMAIL_CHANGE_NOTICE,
+        },
+        user::User,
+    },
+    repository::user_contact::get_user_contacts_by_user,
+    settings::Config,
+};
+
+pub const DEFAULT_EMAIL_CHANGE_TTL: u64 = 86400;
+pub const TOKEN_KEY_PREFIX: &str = "email_change:";
+pub const USER_KEY_PREFIX: &str = "email_change_user:";
+
+/// Stored in redis under the confirmation token until confirmed or expired
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PendingEmailChange {
+    pub user_id: String,
+    pub new_email: String,
+}
+
+/// Random 256 bit url safe token
+pub fn generate_email_change_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Store a pending change, a previous pending change of the user is discarded
+pub async fn add_pending_email_change<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+    new_email: &str,
+    ttl: u64,
+) -> anyhow::Result<String> {
+    let user_key = format!("{}{}", USER_KEY_PREFIX, user_id);
+    if let Some(previous) = redis_conn.get(&user_key).await? {
+        redis_conn
+            .del(&format!("{}{}", TOKEN_KEY_PREFIX, previous))
+            .await?;
+    }
+    let token = generate_email_change_token();
+    let pending = PendingEmailChange {
+        user_id: user_id.to_string(),
+        new_email: new_email.to_string(),
+    };
+    redis_conn
+        .set_ex(
+            &format!("{}{}", TOKEN_KEY_PREFIX, token),
+            &serde_json::to_string(&pending)?,
+            ttl,
+        )
+        .await?;
+    redis_conn.set_ex(&user_key, &token, ttl).await?;
+    Ok(token)
+}
+
+/// Consume the token, None when unknown or expired
+pub async fn take_pending_email_change<C: SessionStore>(
+    redis_conn: &mut C,
+    token: &str,
+) -> anyhow::Result<Option<PendingEmailChange>> {
+    let token_key = format!("{}{}", TOKEN_KEY_PREFIX, token);
+    let res = redis_conn.get(&token_key).await?;
+    let pending: PendingEmailChange = match res {
+        Some(val) => serde_json::from_str(&val)?,
+        None => return Ok(None),
+    };
+    redis_conn.del(&token_key).await?;
+    redis_conn
+        .del(&format!("{}{}", USER_KEY_PREFIX, pending.user_id))
+        .await?;
+    Ok(Some(pending))
+}
+
+/// url with the token appended, the bare token when not configured
+pub fn token_link(url: Option<&str>, token: &str) -> String {
+    match url.filter(|x| !x.is_empty()) {
+        Some(url) if url.contains('?') => format!("{}&token={}", url, token),
+        Some(url) => format!("{}?token={}", url, token),
+        None => token.to_string(),
+    }
+}
+
+/// EMAIL_CHANGE_CONFIRM_URL with the token appended, the bare token when not configured
+pub fn email_change_confirm_link(config: &Config, token: &str) -> String {
+    token_link(config.email_change_confirm_url.as_deref(), token)
+}
+
+/// Start an email change: the new address gets a confirmation link, the old one and the
+/// verified phone a notice. The profile keeps the old address until
+/// POST /auth/email-change/confirm/.
+pub async fn request_email_change<C: SessionStore>(
+    tx: &mut Transaction<'_, Postgres>,
+    redis_conn: &mut C,
+    config: &Config,
+    user: &User,
+    locale: &UserLocale,
+    old_email: Option<&str>,
+    new_email: &str,
+) -> anyhow::Result<String> {
+    let ttl = config.email_change_ttl.unwrap_or(DEFAULT_EMAIL_CHANGE_TTL);
+    let token = add_pending_email_change(redis_conn, &user.id, new_email, ttl).await?;
+    let expires_date = utc_now() + Duration::seconds(ttl as i64);
+    send_notification(
+        tx,
+        config,
+        EVENT_EMAIL_CHANGE_CONFIRM,
+        CHANNEL_EMAIL,
+        new_email,
+        locale,
+        &json!({
+            "user_name": user.user_name,
+            "link": email_change_confirm_link(config, &token),
+            "expires_date": locale.format_datetime(expires_date),
+        }),
+    )
+    .await?;
+    if let Some(old_email) = old_email.filter(|x| !x.is_empty()) {
+        send_notification(
+            tx,
+            config,
+            EVENT_EMAIL_CHANGE_NOTICE,
+            CHANNEL_EMAIL,
+            old_email,
+            locale,
+            &json!({"user_name": user.user_name, "new_email": new_email}),
+        )
+        .await?;
+    }
+    let contacts = get_user_contacts_by_user(tx, &user.id).await?;
+    if let Some(phone) = alert_phone(&contacts) {
+        send_notification(
+            tx,
+            config,
+            EVENT_EMAIL_CHANGE_NOTICE,
+            CHANNEL_SMS,
+            &phone.value,
+            locale,
+            &json!({"user_name": user.user_name, "new_email": new_email}),
+        )
+        .await?;
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        core::{
+            email_change::{
+                add_pending_email_change, email_change_confirm_link, take_pending_email_change,
+            },
+            session_store::create_redis_pool,
+        },
+        settings::get_config,
+    };
+
+    #[tokio::test]
+    async fn test_pending_email_change() -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let mut redis_conn = create_redis_pool(&config)?.get().await?;
+        let user_id = Uuid::now_v7();
+
+        // When requested twice
+        let first =
+            add_pending_email_change(&mut redis_conn, &user_id, "a@example.com", 60).await?;
+        let second =
+            add_pending_email_change(&mut redis_conn, &user_id, "b@example.com", 60).await?;
+
+        // Expect only the latest token confirms, once
+        assert!(take_pending_email_change(&mut redis_conn, &first)
+            .await?
+            .is_none());
+        let pending = take_pending_email_change(&mut redis_conn, &second)
+            .await?
+            .unwrap();
+        assert_eq!(pending.user_id, user_id.to_string());
+        assert_eq!(pending.new_email, "b@example.com");
+        assert!(take_pending_email_change(&mut redis_conn, &second)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_email_change_confirm_link() {
+        let mut config = get_config();
+        config.email_change_confirm_url = None;
+        assert_eq!(email_change_confirm_link(&config, "abc"), "abc");
+        config.email_change_confirm_url = Some("https://app.example.com/confirm".to_string());
+        assert_eq!(
+            email_change_confirm_link(&config, "abc"),
+            "https://app.example.com/confirm?token=abc"
+        );
+        config.email_change_confirm_url = Some("https://app.example.com/?page=email".to_string());
+        assert_eq!(
+            email_change_confirm_link(&config, "abc"),
+            "https://app.example.com/?page=email&token=abc"
+        );
+    }
+}
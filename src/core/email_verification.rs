@@ -0,0 +1,153 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        email_change::{generate_email_change_token, token_link},
+        locale::UserLocale,
+        notifications::send_notification,
+        session_store::SessionStore,
+        utils::utc_now,
+    },
+    model::{
+        notification_template::{CHANNEL_EMAIL, EVENT_EMAIL_VERIFICATION},
+        user::User,
+    },
+    settings::Config,
+};
+
+pub const DEFAULT_EMAIL_VERIFICATION_TTL: u64 = 86400;
+pub const TOKEN_KEY_PREFIX: &str = "email_verification:";
+pub const USER_KEY_PREFIX: &str = "email_verification_user:";
+
+/// Stored in redis under the verification token until used or expired
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PendingEmailVerification {
+    pub user_id: String,
+    /// Address the link was sent to, the token is void once the profile email differs
+    pub email: String,
+}
+
+/// Store a pending verification, a previous link of the user stops working
+pub async fn add_pending_email_verification<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+    email: &str,
+    ttl: u64,
+) -> anyhow::Result<String> {
+    let user_key = format!("{}{}", USER_KEY_PREFIX, user_id);
+    if let Some(previous) = redis_conn.get(&user_key).await? {
+        redis_conn
+            .del(&format!("{}{}", TOKEN_KEY_PREFIX, previous))
+            .await?;
+    }
+    let token = generate_email_change_token();
+    let pending = PendingEmailVerification {
+        user_id: user_id.to_string(),
+        email: email.to_string(),
+    };
+    redis_conn
+        .set_ex(
+            &format!("{}{}", TOKEN_KEY_PREFIX, token),
+            &serde_json::to_string(&pending)?,
+            ttl,
+        )
+        .await?;
+    redis_conn.set_ex(&user_key, &token, ttl).await?;
+    Ok(token)
+}
+
+/// Consume the token, None when unknown or expired
+pub async fn take_pending_email_verification<C: SessionStore>(
+    redis_conn: &mut C,
+    token: &str,
+) -> anyhow::Result<Option<PendingEmailVerification>> {
+    let token_key = format!("{}{}", TOKEN_KEY_PREFIX, token);
+    let res = redis_conn.get(&token_key).await?;
+    let pending: PendingEmailVerification = match res {
+        Some(val) => serde_json::from_str(&val)?,
+        None => return Ok(None),
+    };
+    redis_conn.del(&token_key).await?;
+    redis_conn
+        .del(&format!("{}{}", USER_KEY_PREFIX, pending.user_id))
+        .await?;
+    Ok(Some(pending))
+}
+
+/// EMAIL_VERIFICATION_URL with the token appended, the bare token when not configured
+pub fn email_verification_link(config: &Config, token: &str) -> String {
+    token_link(config.email_verification_url.as_deref(), token)
+}
+
+/// Send a verification link to the email of the user, the profile keeps email_verified_at
+/// empty until POST /auth/verify-email/
+pub async fn request_email_verification<C: SessionStore>(
+    tx: &mut Transaction<'_, Postgres>,
+    redis_conn: &mut C,
+    config: &Config,
+    user: &User,
+    locale: &UserLocale,
+    email: &str,
+) -> anyhow::Result<String> {
+    let ttl = config
+        .email_verification_ttl
+        .unwrap_or(DEFAULT_EMAIL_VERIFICATION_TTL);
+    let token = add_pending_email_verification(redis_conn, &user.id, email, ttl).await?;
+    let expires_date = utc_now() + Duration::seconds(ttl as i64);
+    send_notification(
+        tx,
+        config,
+        EVENT_EMAIL_VERIFICATION,
+        CHANNEL_EMAIL,
+        email,
+        locale,
+        &json!({
+            "user_name": user.user_name,
+            "link": email_verification_link(config, &token),
+            "expires_date": locale.format_datetime(expires_date),
+        }),
+    )
+    .await?;
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::core::{
+        email_verification::{add_pending_email_verification, take_pending_email_verification},
+        session_store::MemoryStore,
+    };
+
+    #[tokio::test]
+    async fn test_pending_email_verification() -> anyhow::Result<()> {
+        // Given
+        let mut redis_conn = MemoryStore::new();
+        let user_id = Uuid::now_v7();
+
+        // When sent twice
+        let first =
+            add_pending_email_verification(&mut redis_conn, &user_id, "a@example.com", 60).await?;
+        let second =
+            add_pending_email_verification(&mut redis_conn, &user_id, "a@example.com", 60).await?;
+
+        // Expect only the latest token verifies, once
+        assert!(take_pending_email_verification(&mut redis_conn, &first)
+            .await?
+            .is_none());
+        let pending = take_pending_email_verification(&mut redis_conn, &second)
+            .await?
+            .unwrap();
+        assert_eq!(pending.user_id, user_id.to_string());
+        assert_eq!(pending.email, "a@example.com");
+        assert!(take_pending_email_verification(&mut redis_conn, &second)
+            .await?
+            .is_none());
+        Ok(())
+    }
+}
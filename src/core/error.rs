@@ -0,0 +1,234 @@
+use std::future::Future;
+
+use poem::{error::ResponseError, http::StatusCode, IntoResponse, Response};
+use poem_openapi::{payload::Json, types::ToJSON};
+
+use crate::{
+    core::{
+        db_error::{constraint_violation, is_retryable_error, ConstraintViolation, Retryable},
+        permission_grant::GrantTargetError,
+        sqlx_utils::InvalidSort,
+    },
+    schema::common::{
+        BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+        NotFoundResponse, UnauthorizedResponse,
+    },
+};
+
+/// Error of a handler step, converted into the response variant of the same name so
+/// handlers can use `?` instead of matching every call, see impl_from_app_error.
+/// Repository errors are anyhow errors, a constraint violation behind one becomes a
+/// Conflict or BadRequest naming the field, an InvalidSort a BadRequest and anything
+/// else an InternalServerError
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{}", .0.message)]
+    BadRequest(BadRequestResponse),
+    #[error("{}", .0.message)]
+    Unauthorized(UnauthorizedResponse),
+    #[error("{}", .0.message)]
+    Forbidden(ForbiddenResponse),
+    #[error("{}", .0.message)]
+    NotFound(NotFoundResponse),
+    #[error("{}", .0.message)]
+    Conflict(ConflictResponse),
+    #[error(transparent)]
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::BadRequest(BadRequestResponse::new(message.into()))
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::Unauthorized(UnauthorizedResponse::default())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::Forbidden(ForbiddenResponse::new(message.into()))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(NotFoundResponse::new(message.into()))
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict(ConflictResponse::new(message.into()))
+    }
+
+    /// Body of the 500 returned for the error, also logs it. Client errors reaching a
+    /// handler without the matching variant end up here too
+    pub fn internal_server_error(&self) -> InternalServerErrorResponse {
+        match self {
+            Self::Internal(err) => InternalServerErrorResponse::from_error(err),
+            err => InternalServerErrorResponse::from_error(&anyhow::anyhow!("{}", err)),
+        }
+    }
+}
+
+impl From<ConstraintViolation> for AppError {
+    fn from(violation: ConstraintViolation) -> Self {
+        violation.response(|Json(x)| Self::Conflict(x), |Json(x)| Self::BadRequest(x))
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(invalid_sort) = err.downcast_ref::<InvalidSort>() {
+            return Self::BadRequest(
+                BadRequestResponse::new(invalid_sort.to_string())
+                    .with_field(invalid_sort.field.clone()),
+            );
+        }
+        match constraint_violation(&err) {
+            Some(violation) => violation.into(),
+            None => Self::Internal(err),
+        }
+    }
+}
+
+/// Unknown permission or attribute of a grant, a bad request naming the id
+impl From<GrantTargetError> for AppError {
+    fn from(err: GrantTargetError) -> Self {
+        match err {
+            GrantTargetError::Internal(err) => err.into(),
+            err => Self::bad_request(err.to_string()),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        anyhow::Error::from(err).into()
+    }
+}
+
+impl From<redis::RedisError> for AppError {
+    fn from(err: redis::RedisError) -> Self {
+        Self::Internal(err.into())
+    }
+}
+
+impl From<deadpool_redis::PoolError> for AppError {
+    fn from(err: deadpool_redis::PoolError) -> Self {
+        Self::Internal(err.into())
+    }
+}
+
+/// A handler step retried by retry_transaction, see Retryable
+impl<T> Retryable for Result<T, AppError> {
+    fn is_retryable(&self) -> bool {
+        matches!(self, Err(AppError::Internal(err)) if is_retryable_error(err))
+    }
+}
+
+/// For poem extractors and plain handlers, e.g. AuthContext
+impl ResponseError for AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn as_response(&self) -> Response {
+        let body = match self {
+            Self::BadRequest(x) => x.to_json(),
+            Self::Unauthorized(x) => x.to_json(),
+            Self::Forbidden(x) => x.to_json(),
+            Self::NotFound(x) => x.to_json(),
+            Self::Conflict(x) => x.to_json(),
+            Self::Internal(_) => self.internal_server_error().to_json(),
+        };
+        poem::web::Json(body)
+            .with_status(self.status())
+            .into_response()
+    }
+}
+
+/// Implement `From<AppError>` for an ApiResponse enum. The listed variants take the
+/// error of the same name, every other error becomes the InternalServerError variant
+/// ```ignore
+/// impl_from_app_error!(RoleDetailResponses { Unauthorized, Forbidden, NotFound });
+/// ```
+#[macro_export]
+macro_rules! impl_from_app_error {
+    ($responses:ident { $($variant:ident),* $(,)? }) => {
+        impl From<$crate::core::error::AppError> for $responses {
+            fn from(err: $crate::core::error::AppError) -> Self {
+                match err {
+                    $($crate::core::error::AppError::$variant(body) => {
+                        Self::$variant(::poem_openapi::payload::Json(body))
+                    })*
+                    err => Self::InternalServerError(::poem_openapi::payload::Json(
+                        err.internal_server_error(),
+                    )),
+                }
+            }
+        }
+    };
+}
+
+/// Run a handler body written with `?`, its error becomes the matching response variant
+pub async fn respond<R, F>(f: F) -> R
+where
+    R: From<AppError>,
+    F: Future<Output = Result<R, AppError>>,
+{
+    f.await.unwrap_or_else(R::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{error::ResponseError, http::StatusCode};
+
+    use crate::{
+        core::{db_error::ConstraintViolation, error::AppError},
+        schema::role::RoleCreateResponses,
+    };
+
+    #[test]
+    fn test_app_error_into_response() {
+        // When constraint violation
+        let err: AppError = ConstraintViolation::Unique {
+            field: "role_name".to_string(),
+        }
+        .into();
+
+        // Expect conflict naming the field
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        match RoleCreateResponses::from(err) {
+            RoleCreateResponses::Conflict(body) => {
+                assert_eq!(body.0.message, "role_name already exists");
+                assert_eq!(body.0.field, Some("role_name".to_string()));
+            }
+            _ => panic!("expected conflict"),
+        }
+
+        // When not found reaches a handler without a not found response
+        let err = AppError::not_found("role with id = 1 not found");
+
+        // Expect internal server error
+        match RoleCreateResponses::from(err) {
+            RoleCreateResponses::InternalServerError(body) => {
+                assert!(body.0.error.contains("role with id = 1 not found"));
+            }
+            _ => panic!("expected internal server error"),
+        }
+
+        // When any other error
+        let err: AppError = anyhow::anyhow!("connection reset").into();
+
+        // Expect internal server error
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(matches!(
+            RoleCreateResponses::from(err),
+            RoleCreateResponses::InternalServerError(_)
+        ));
+    }
+}
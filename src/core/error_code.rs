@@ -0,0 +1,208 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{core::i18n::message_pattern, schema::common::ErrorCode};
+
+/// Code of handler messages, `{}` is any text as in core::i18n. Messages not listed
+/// get the generic code of their status.
+pub const ERROR_CODES: &[(&str, ErrorCode)] = &[
+    ("Invalid credentials", ErrorCode::InvalidCredentials),
+    ("invalid or expired token", ErrorCode::TokenExpired),
+    ("user is inactive", ErrorCode::AccountInactive),
+    ("user is not provisioned", ErrorCode::AccountNotProvisioned),
+    ("user is {}", ErrorCode::AccountInactive),
+    ("user account expired", ErrorCode::AccountExpired),
+    (
+        "too many failed logins, try again in {} seconds",
+        ErrorCode::AccountLocked,
+    ),
+    ("{} {} permission required", ErrorCode::PermissionRequired),
+    ("latest terms not accepted: {}", ErrorCode::TermsNotAccepted),
+    (
+        "new_password and confirm_new_password must be same",
+        ErrorCode::PasswordMismatch,
+    ),
+    ("email is unchanged", ErrorCode::EmailUnchanged),
+    ("{} is not a valid email address", ErrorCode::InvalidEmail),
+    (
+        "locale {} is not supported, language must be one of {}",
+        ErrorCode::UnsupportedLocale,
+    ),
+    ("user not found", ErrorCode::UserNotFound),
+    ("user with id = {} not found", ErrorCode::UserNotFound),
+    ("user with id {} not found", ErrorCode::UserNotFound),
+    ("user with user_id = {} not found", ErrorCode::UserNotFound),
+    ("invalid user_id {}", ErrorCode::UserNotFound),
+    (
+        "user with id = {} already anonymized",
+        ErrorCode::UserAlreadyAnonymized,
+    ),
+    ("user name {} is taken", ErrorCode::UserNameConflict),
+    (
+        "user name {} is already used by another user",
+        ErrorCode::UserNameConflict,
+    ),
+    (
+        "user name {} was used by another user in the last {} days",
+        ErrorCode::UserNameConflict,
+    ),
+    ("role with id = {} not found", ErrorCode::RoleNotFound),
+    ("role with id {} not found", ErrorCode::RoleNotFound),
+    ("group with id = {} not found", ErrorCode::GroupNotFound),
+    ("group with id {} not found", ErrorCode::GroupNotFound),
+    (
+        "permission with id = {} not found",
+        ErrorCode::PermissionNotFound,
+    ),
+    (
+        "permission with id {} not found",
+        ErrorCode::PermissionNotFound,
+    ),
+    (
+        "attribute with id {} not found",
+        ErrorCode::AttributeNotFound,
+    ),
+    (
+        "permission attribute id = {} not found",
+        ErrorCode::AttributeNotFound,
+    ),
+    (
+        "permission_attribute_id with id = {} not found",
+        ErrorCode::AttributeNotFound,
+    ),
+    (
+        "permission attribute {} is in use",
+        ErrorCode::AttributeInUse,
+    ),
+    (
+        "user_group_roles with user_id = {}, role_id = {}, group id = {} not found",
+        ErrorCode::UserGroupRoleNotFound,
+    ),
+    (
+        "user_group_roles with user_id = {}, role_id = {}, group id = {} already exist",
+        ErrorCode::UserGroupRoleConflict,
+    ),
+    (
+        "role_permission with role_id = {}, permission_id = {}, attribute_id = {} already exists",
+        ErrorCode::RolePermissionConflict,
+    ),
+    (
+        "group_permission with group_id = {}, permission_id = {}, attribute_id = {} already exists",
+        ErrorCode::GroupPermissionConflict,
+    ),
+    (
+        "user_permission with user_id = {}, permission_id = {}, attribute_id = {} already exists",
+        ErrorCode::UserPermissionConflict,
+    ),
+    (
+        "consent type with id = {} not found",
+        ErrorCode::ConsentTypeNotFound,
+    ),
+    (
+        "consent type with name = {} already exists",
+        ErrorCode::ConsentTypeConflict,
+    ),
+    (
+        "terms version with id = {} not found",
+        ErrorCode::TermsVersionNotFound,
+    ),
+    (
+        "{} version {} already published",
+        ErrorCode::TermsVersionConflict,
+    ),
+    (
+        "data export with id = {} not found",
+        ErrorCode::DataExportNotFound,
+    ),
+    (
+        "directory source with id = {} not found",
+        ErrorCode::DirectorySourceNotFound,
+    ),
+    (
+        "sso provider with id = {} not found",
+        ErrorCode::SsoProviderNotFound,
+    ),
+    ("sso provider {} not found", ErrorCode::SsoProviderNotFound),
+    (
+        "jit rule with id = {} not found",
+        ErrorCode::JitRuleNotFound,
+    ),
+    (
+        "role mapping with id = {} not found",
+        ErrorCode::RoleMappingNotFound,
+    ),
+    (
+        "scim target with id = {} not found",
+        ErrorCode::ScimTargetNotFound,
+    ),
+    (
+        "scim provisioning event with id = {} not found",
+        ErrorCode::ScimEventNotFound,
+    ),
+    (
+        "notification template with id = {} not found",
+        ErrorCode::NotificationTemplateNotFound,
+    ),
+    (
+        "{} {} template for {} already exists",
+        ErrorCode::NotificationTemplateConflict,
+    ),
+    (
+        "user contact with id = {} not found",
+        ErrorCode::UserContactNotFound,
+    ),
+    ("{} {} already added", ErrorCode::UserContactConflict),
+    (
+        "user device with id = {} not found",
+        ErrorCode::UserDeviceNotFound,
+    ),
+    ("preference {} not found", ErrorCode::PreferenceNotFound),
+    // constraint violations of core::db_error, after the specific messages above
+    ("{} already exists", ErrorCode::DuplicateValue),
+    (
+        "{} does not reference an existing record",
+        ErrorCode::ReferenceNotFound,
+    ),
+];
+
+static PATTERNS: LazyLock<Vec<(Regex, ErrorCode)>> = LazyLock::new(|| {
+    ERROR_CODES
+        .iter()
+        .map(|(source, code)| (message_pattern(source), *code))
+        .collect()
+});
+
+/// Code of the first catalogue message matching, `fallback` otherwise
+pub fn error_code_of(message: &str, fallback: ErrorCode) -> ErrorCode {
+    PATTERNS
+        .iter()
+        .find(|(pattern, _)| pattern.is_match(message))
+        .map(|(_, code)| *code)
+        .unwrap_or(fallback)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::error_code::error_code_of, schema::common::ErrorCode};
+
+    #[test]
+    fn test_error_code_of() {
+        assert_eq!(
+            error_code_of("user with id = 42 not found", ErrorCode::NotFound),
+            ErrorCode::UserNotFound
+        );
+        assert_eq!(
+            error_code_of("role read permission required", ErrorCode::Forbidden),
+            ErrorCode::PermissionRequired
+        );
+        assert_eq!(
+            error_code_of("user is suspended", ErrorCode::Forbidden),
+            ErrorCode::AccountInactive
+        );
+        assert_eq!(
+            error_code_of("something else", ErrorCode::BadRequest),
+            ErrorCode::BadRequest
+        );
+    }
+}
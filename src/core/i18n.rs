@@ -0,0 +1,440 @@
+use std::sync::{Arc, LazyLock};
+
+use poem::{
+    http::{header, HeaderValue},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{
+    core::{
+        locale::{normalize_locale, DEFAULT_LOCALE, SUPPORTED_LANGUAGES},
+        session::get_session,
+    },
+    repository::user::get_user_by_id,
+    AppState,
+};
+
+/// English messages returned by handlers with their translations, `{}` is a value
+/// carried over as is. Handlers keep writing English, add the message here to localize it.
+pub const MESSAGES: &[(&str, &str)] = &[
+    ("unauthorized", "tidak terautentikasi"),
+    ("forbidden", "akses ditolak"),
+    ("internal server error", "terjadi kesalahan pada server"),
+    ("Invalid credentials", "Kredensial tidak valid"),
+    (
+        "invalid or expired token",
+        "token tidak valid atau kedaluwarsa",
+    ),
+    ("user account expired", "akun pengguna sudah kedaluwarsa"),
+    (
+        "too many failed logins, try again in {} seconds",
+        "terlalu banyak login gagal, coba lagi dalam {} detik",
+    ),
+    ("user is inactive", "pengguna tidak aktif"),
+    ("user is not provisioned", "pengguna belum terdaftar"),
+    ("user is {}", "pengguna berstatus {}"),
+    ("user not found", "pengguna tidak ditemukan"),
+    ("{} {} permission required", "izin {} {} diperlukan"),
+    (
+        "user with id = {} not found",
+        "pengguna dengan id = {} tidak ditemukan",
+    ),
+    (
+        "user with id {} not found",
+        "pengguna dengan id {} tidak ditemukan",
+    ),
+    (
+        "user with user_id = {} not found",
+        "pengguna dengan user_id = {} tidak ditemukan",
+    ),
+    (
+        "user with id = {} already anonymized",
+        "pengguna dengan id = {} sudah dianonimkan",
+    ),
+    (
+        "user name {} is already used by another user",
+        "nama pengguna {} sudah dipakai pengguna lain",
+    ),
+    (
+        "user password updated successfully",
+        "kata sandi pengguna berhasil diperbarui",
+    ),
+    (
+        "new_password and confirm_new_password must be same",
+        "new_password dan confirm_new_password harus sama",
+    ),
+    (
+        "role with id = {} not found",
+        "peran dengan id = {} tidak ditemukan",
+    ),
+    (
+        "role with id {} not found",
+        "peran dengan id {} tidak ditemukan",
+    ),
+    (
+        "group with id = {} not found",
+        "grup dengan id = {} tidak ditemukan",
+    ),
+    (
+        "group with id {} not found",
+        "grup dengan id {} tidak ditemukan",
+    ),
+    (
+        "permission with id = {} not found",
+        "izin dengan id = {} tidak ditemukan",
+    ),
+    (
+        "permission with id {} not found",
+        "izin dengan id {} tidak ditemukan",
+    ),
+    (
+        "attribute with id {} not found",
+        "atribut dengan id {} tidak ditemukan",
+    ),
+    (
+        "permission attribute id = {} not found",
+        "atribut izin dengan id = {} tidak ditemukan",
+    ),
+    (
+        "permission attribute {} is in use",
+        "atribut izin {} sedang digunakan",
+    ),
+    (
+        "group_id or role_id is required",
+        "group_id atau role_id wajib diisi",
+    ),
+    ("name is required", "nama wajib diisi"),
+    ("version is required", "versi wajib diisi"),
+    ("email is unchanged", "email tidak berubah"),
+    (
+        "{} is not a valid email address",
+        "{} bukan alamat email yang valid",
+    ),
+    (
+        "{} is not a valid phone number",
+        "{} bukan nomor telepon yang valid",
+    ),
+    ("{} {} already added", "{} {} sudah ditambahkan"),
+    ("{} is required", "{} wajib diisi"),
+    ("{} already exists", "{} sudah ada"),
+    (
+        "{} does not reference an existing record",
+        "{} tidak merujuk ke data yang ada",
+    ),
+    (
+        "{} must be at most {} characters",
+        "{} maksimal {} karakter",
+    ),
+    ("{} is not a valid uuid", "{} bukan uuid yang valid"),
+    (
+        "{} may only contain letters, digits and . _ - + @",
+        "{} hanya boleh berisi huruf, angka dan . _ - + @",
+    ),
+    (
+        "user contact with id = {} not found",
+        "kontak pengguna dengan id = {} tidak ditemukan",
+    ),
+    (
+        "user device with id = {} not found",
+        "perangkat pengguna dengan id = {} tidak ditemukan",
+    ),
+    ("preference {} not found", "preferensi {} tidak ditemukan"),
+    (
+        "locale {} is not supported, language must be one of {}",
+        "locale {} tidak didukung, bahasa harus salah satu dari {}",
+    ),
+    (
+        "timezone {} is not an IANA timezone",
+        "zona waktu {} bukan zona waktu IANA",
+    ),
+    (
+        "data export with id = {} not found",
+        "ekspor data dengan id = {} tidak ditemukan",
+    ),
+    (
+        "data export is {}, not completed",
+        "ekspor data berstatus {}, belum selesai",
+    ),
+    (
+        "consent type with id = {} not found",
+        "jenis persetujuan dengan id = {} tidak ditemukan",
+    ),
+    (
+        "consent type {} is not active",
+        "jenis persetujuan {} tidak aktif",
+    ),
+    (
+        "terms version with id = {} not found",
+        "versi ketentuan dengan id = {} tidak ditemukan",
+    ),
+    (
+        "only the latest version can be accepted",
+        "hanya versi terbaru yang dapat disetujui",
+    ),
+    (
+        "notification template with id = {} not found",
+        "templat notifikasi dengan id = {} tidak ditemukan",
+    ),
+    (
+        "sso provider with id = {} not found",
+        "penyedia sso dengan id = {} tidak ditemukan",
+    ),
+    (
+        "sso provider {} not found",
+        "penyedia sso {} tidak ditemukan",
+    ),
+    (
+        "directory source with id = {} not found",
+        "sumber direktori dengan id = {} tidak ditemukan",
+    ),
+    (
+        "scim target with id = {} not found",
+        "target scim dengan id = {} tidak ditemukan",
+    ),
+];
+
+/// Anchored pattern of a catalogue message, `{}` matches any text
+pub fn message_pattern(source: &str) -> Regex {
+    let pattern = source
+        .split("{}")
+        .map(regex::escape)
+        .collect::<Vec<String>>()
+        .join("(.+?)");
+    Regex::new(&format!("^{}$", pattern)).unwrap()
+}
+
+static PATTERNS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    MESSAGES
+        .iter()
+        .map(|(source, translation)| (message_pattern(source), *translation))
+        .collect()
+});
+
+/// Message in the language, unchanged for English and messages not in the catalogue
+pub fn translate(message: &str, language: &str) -> String {
+    if language == DEFAULT_LOCALE {
+        return message.to_string();
+    }
+    for (pattern, translation) in PATTERNS.iter() {
+        if let Some(captures) = pattern.captures(message) {
+            let mut res = String::new();
+            for (i, part) in translation.split("{}").enumerate() {
+                if i > 0 {
+                    res.push_str(captures.get(i).map(|x| x.as_str()).unwrap_or_default());
+                }
+                res.push_str(part);
+            }
+            return res;
+        }
+    }
+    message.to_string()
+}
+
+/// Supported language of a locale, e.g. id for id-ID
+pub fn locale_language(locale: &str) -> Option<&'static str> {
+    let locale = normalize_locale(locale)?;
+    let language = locale.split('-').next()?;
+    SUPPORTED_LANGUAGES
+        .iter()
+        .find(|x| **x == language)
+        .copied()
+}
+
+/// Most preferred supported language of an Accept-Language header
+pub fn negotiate_language(accept_language: &str) -> Option<&'static str> {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|x| x.trim().strip_prefix("q="))
+                .map(|x| x.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            Some((tag, quality))
+        })
+        .filter(|(tag, quality)| !tag.is_empty() && *tag != "*" && *quality > 0.0)
+        .collect();
+    // stable, equal weights keep the header order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges.iter().find_map(|(tag, _)| locale_language(tag))
+}
+
+/// Language of the profile locale of the bearer token user
+async fn profile_language(state: &AppState, token: String) -> anyhow::Result<Option<&'static str>> {
+    let mut redis_conn = state.redis_conn.get().await?;
+    let session = match get_session(&mut redis_conn, token).await? {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let mut tx = state.db.begin().await?;
+    let (_, user_profile) = get_user_by_id(&mut tx, &session.user_id.parse()?, None).await?;
+    Ok(user_profile
+        .and_then(|x| x.locale)
+        .and_then(|x| locale_language(&x)))
+}
+
+/// Translate `message` of error responses, and `msg` of field errors, to the language of
+/// Accept-Language, then of the profile locale of the bearer token user, then DEFAULT_LOCALE
+pub struct LocalizedMessages {
+    default_language: &'static str,
+}
+
+impl LocalizedMessages {
+    pub fn new(default_locale: Option<&str>) -> Self {
+        Self {
+            default_language: default_locale
+                .and_then(locale_language)
+                .unwrap_or(DEFAULT_LOCALE),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for LocalizedMessages {
+    type Output = LocalizedMessagesEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        LocalizedMessagesEndpoint {
+            inner: ep,
+            default_language: self.default_language,
+        }
+    }
+}
+
+pub struct LocalizedMessagesEndpoint<E> {
+    inner: E,
+    default_language: &'static str,
+}
+
+fn translate_body(body: &mut Value, language: &str) {
+    if let Some(Value::String(message)) = body.get_mut("message") {
+        *message = translate(message, language);
+    }
+    if let Some(Value::Array(detail)) = body.get_mut("detail") {
+        for item in detail.iter_mut() {
+            if let Some(Value::String(msg)) = item.get_mut("msg") {
+                *msg = translate(msg, language);
+            }
+        }
+    }
+}
+
+impl<E: Endpoint> Endpoint for LocalizedMessagesEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let accept_language = req
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|x| x.to_str().ok())
+            .and_then(negotiate_language);
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .map(|x| x.trim().to_string());
+        let state = req.data::<Arc<AppState>>().cloned();
+        let mut resp = match self.inner.call(req).await {
+            Ok(val) => val.into_response(),
+            Err(err) => err.into_response(),
+        };
+        let status = resp.status();
+        let is_json = resp
+            .content_type()
+            .is_some_and(|x| x.starts_with("application/json"));
+        if !(status.is_client_error() || status.is_server_error()) || !is_json {
+            return Ok(resp);
+        }
+        let language = match (accept_language, state, token) {
+            (Some(val), _, _) => val,
+            (None, Some(state), Some(token)) => match profile_language(&state, token).await {
+                Ok(val) => val.unwrap_or(self.default_language),
+                Err(err) => {
+                    tracing::warn!("error: on core::i18n profile_language error: {}", err);
+                    self.default_language
+                }
+            },
+            _ => self.default_language,
+        };
+        resp.headers_mut()
+            .insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(language));
+        if language == DEFAULT_LOCALE {
+            return Ok(resp);
+        }
+        let body = resp.take_body().into_bytes().await?;
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(mut val) => {
+                translate_body(&mut val, language);
+                resp.set_body(serde_json::to_vec(&val).unwrap_or(body.to_vec()));
+            }
+            Err(_) => resp.set_body(body),
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{endpoint::make_sync, http::StatusCode, test::TestClient, EndpointExt, Response};
+
+    use crate::core::i18n::{negotiate_language, translate, LocalizedMessages};
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(
+            translate("user with id = 42 not found", "id"),
+            "pengguna dengan id = 42 tidak ditemukan"
+        );
+        assert_eq!(
+            translate("user read permission required", "id"),
+            "izin user read diperlukan"
+        );
+        assert_eq!(translate("unauthorized", "en"), "unauthorized");
+        assert_eq!(translate("not in catalogue", "id"), "not in catalogue");
+    }
+
+    #[test]
+    fn test_negotiate_language() {
+        assert_eq!(negotiate_language("id-ID,id;q=0.9,en;q=0.8"), Some("id"));
+        assert_eq!(negotiate_language("fr-FR, en;q=0.5, id;q=0.7"), Some("id"));
+        assert_eq!(negotiate_language("en, id"), Some("en"));
+        assert_eq!(negotiate_language("fr, *"), None);
+    }
+
+    #[tokio::test]
+    async fn test_localized_messages() {
+        // Given
+        let ep = make_sync(|_| {
+            Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .content_type("application/json; charset=utf-8")
+                .body(r#"{"message":"user with id = 42 not found"}"#)
+        })
+        .with(LocalizedMessages::new(None));
+        let cli = TestClient::new(ep);
+
+        // When
+        let resp = cli
+            .get("/")
+            .header("accept-language", "id-ID,id;q=0.9")
+            .send()
+            .await;
+
+        // Expect
+        resp.assert_status(StatusCode::NOT_FOUND);
+        resp.assert_header("content-language", "id");
+        resp.assert_json(
+            &serde_json::json!({"message": "pengguna dengan id = 42 tidak ditemukan"}),
+        )
+        .await;
+
+        // Expect English by default
+        let resp = cli.get("/").send().await;
+        resp.assert_header("content-language", "en");
+        resp.assert_json(&serde_json::json!({"message": "user with id = 42 not found"}))
+            .await;
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, FixedOffset};
+use ldap3::{ldap_escape, LdapConnAsync, Scope, SearchEntry};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        directory_sync::{dn_common_name, empty_user_profile, first_attr, ldap_external_id},
+        security::hash_password,
+    },
+    model::{
+        directory_source::PROVIDER_LDAP,
+        scim_provisioning_event::{EVENT_CREATE, EVENT_UPDATE},
+        user::{User, UserStatus},
+        user_group_roles::UserGroupRoles,
+        user_identity::UserIdentity,
+        user_profile::UserProfile,
+    },
+    repository::{
+        group::get_group_by_name,
+        scim_provisioning_event::enqueue_scim_event,
+        user::{create_user, get_user_by_id, get_user_by_username, update_user},
+        user_group_roles::{
+            add_user_group_roles, delete_user_group_roles_by_id, get_user_group_memberships,
+        },
+        user_identity::{create_user_identity, get_user_identity, get_user_identity_by_user},
+    },
+    settings::Config,
+};
+
+pub const USER_NAME_PLACEHOLDER: &str = "{user_name}";
+pub const DEFAULT_LDAP_AUTH_USER_FILTER: &str = "(|(uid={user_name})(sAMAccountName={user_name}))";
+// resultCode of a bind with a wrong password, unknown dn or disabled account
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+
+/// Directory attribute read into each profile field
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdapAttributes {
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+}
+
+impl Default for LdapAttributes {
+    fn default() -> Self {
+        Self {
+            first_name: "givenName".to_string(),
+            last_name: "sn".to_string(),
+            email: "mail".to_string(),
+        }
+    }
+}
+
+/// Login checked by a bind on the directory, see LDAP_AUTH_* settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdapAuth {
+    pub url: String,
+    /// Service account searching the user entry, anonymous search when empty
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    pub base_dn: String,
+    /// Search filter with `{user_name}` replaced by the escaped login user name
+    pub user_filter: String,
+    pub attributes: LdapAttributes,
+    /// Directory group common name to local group name, matched case insensitive
+    pub group_mapping: Vec<(String, String)>,
+}
+
+/// `key=value` pairs separated by comma
+fn parse_pairs(val: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut res = vec![];
+    for item in val.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        match item.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+                res.push((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => anyhow::bail!("{} is not written as key=value", item),
+        }
+    }
+    Ok(res)
+}
+
+impl LdapAuth {
+    /// None when LDAP_AUTH_URL is not set
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let url = match config.ldap_auth_url.as_deref().filter(|x| !x.is_empty()) {
+            Some(val) => val.to_string(),
+            None => return Ok(None),
+        };
+        if !url.starts_with("ldap://") && !url.starts_with("ldaps://") {
+            anyhow::bail!("LDAP_AUTH_URL must start with ldap:// or ldaps://");
+        }
+        let base_dn = match config
+            .ldap_auth_base_dn
+            .as_deref()
+            .filter(|x| !x.is_empty())
+        {
+            Some(val) => val.to_string(),
+            None => anyhow::bail!("LDAP_AUTH_BASE_DN is required with LDAP_AUTH_URL"),
+        };
+        let user_filter = config
+            .ldap_auth_user_filter
+            .clone()
+            .filter(|x| !x.is_empty())
+            .unwrap_or(DEFAULT_LDAP_AUTH_USER_FILTER.to_string());
+        if !user_filter.contains(USER_NAME_PLACEHOLDER) {
+            anyhow::bail!(
+                "LDAP_AUTH_USER_FILTER must contain {}",
+                USER_NAME_PLACEHOLDER
+            );
+        }
+        let mut attributes = LdapAttributes::default();
+        let pairs = match parse_pairs(config.ldap_auth_attributes.as_deref().unwrap_or_default()) {
+            Ok(val) => val,
+            Err(err) => anyhow::bail!("LDAP_AUTH_ATTRIBUTES: {}", err),
+        };
+        for (field, attribute) in pairs {
+            match field.as_str() {
+                "first_name" => attributes.first_name = attribute,
+                "last_name" => attributes.last_name = attribute,
+                "email" => attributes.email = attribute,
+                _ => anyhow::bail!(
+                    "LDAP_AUTH_ATTRIBUTES: {} must be first_name, last_name or email",
+                    field
+                ),
+            }
+        }
+        let group_mapping = match parse_pairs(
+            config
+                .ldap_auth_group_mapping
+                .as_deref()
+                .unwrap_or_default(),
+        ) {
+            Ok(val) => val,
+            Err(err) => anyhow::bail!("LDAP_AUTH_GROUP_MAPPING: {}", err),
+        };
+        Ok(Some(Self {
+            url,
+            bind_dn: config.ldap_auth_bind_dn.clone().filter(|x| !x.is_empty()),
+            bind_password: config.ldap_auth_bind_password.clone(),
+            base_dn,
+            user_filter,
+            attributes,
+            group_mapping,
+        }))
+    }
+
+    /// Local groups of the directory groups, each listed once
+    pub fn mapped_local_groups(&self, ldap_groups: &[String]) -> Vec<String> {
+        let mut res: Vec<String> = vec![];
+        for (ldap_group, local_group) in self.group_mapping.iter() {
+            if ldap_groups
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(ldap_group))
+                && !res.contains(local_group)
+            {
+                res.push(local_group.clone());
+            }
+        }
+        res
+    }
+}
+
+/// Directory entry of a user whose password was accepted
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdapAuthUser {
+    /// entryUUID / objectGUID, same subject as directory sync uses
+    pub external_id: String,
+    pub user_name: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    /// Common names of the memberOf groups
+    pub groups: Vec<String>,
+}
+
+/// Find the user entry and bind as it with the password. None when the user is not found,
+/// found more than once or the password is refused
+pub async fn ldap_authenticate(
+    auth: &LdapAuth,
+    user_name: &str,
+    password: &str,
+) -> anyhow::Result<Option<LdapAuthUser>> {
+    // a simple bind with an empty password is an anonymous bind and always succeeds
+    if user_name.trim().is_empty() || password.is_empty() {
+        return Ok(None);
+    }
+    let (conn, mut ldap) = LdapConnAsync::new(&auth.url).await?;
+    ldap3::drive!(conn);
+    if let Some(bind_dn) = &auth.bind_dn {
+        ldap.simple_bind(bind_dn, auth.bind_password.as_deref().unwrap_or_default())
+            .await?
+            .success()?;
+    }
+
+    let filter = auth
+        .user_filter
+        .replace(USER_NAME_PLACEHOLDER, &ldap_escape(user_name));
+    let attrs = vec![
+        "uid",
+        "sAMAccountName",
+        "entryUUID",
+        "objectGUID",
+        "memberOf",
+        auth.attributes.first_name.as_str(),
+        auth.attributes.last_name.as_str(),
+        auth.attributes.email.as_str(),
+    ];
+    let (entries, _) = ldap
+        .search(&auth.base_dn, Scope::Subtree, &filter, attrs)
+        .await?
+        .success()?;
+    if entries.len() != 1 {
+        if entries.len() > 1 {
+            tracing::warn!(
+                "ldap login: {} entries match user {}, refused",
+                entries.len(),
+                user_name
+            );
+        }
+        let _ = ldap.unbind().await;
+        return Ok(None);
+    }
+    let entry = SearchEntry::construct(entries.into_iter().next().unwrap());
+
+    let res = ldap.simple_bind(&entry.dn, password).await?;
+    let _ = ldap.unbind().await;
+    if res.rc == LDAP_INVALID_CREDENTIALS {
+        return Ok(None);
+    }
+    res.success()?;
+    Ok(Some(LdapAuthUser {
+        external_id: ldap_external_id(&entry),
+        user_name: first_attr(&entry, &["sAMAccountName", "uid"]),
+        first_name: first_attr(&entry, &[auth.attributes.first_name.as_str()]),
+        last_name: first_attr(&entry, &[auth.attributes.last_name.as_str()]),
+        email: first_attr(&entry, &[auth.attributes.email.as_str()]),
+        groups: entry
+            .attrs
+            .get("memberOf")
+            .map(|dns| dns.iter().filter_map(|dn| dn_common_name(dn)).collect())
+            .unwrap_or_default(),
+    }))
+}
+
+/// Copy directory attributes into the profile, return true when anything changed
+fn apply_ldap_user(user_profile: &mut UserProfile, ldap_user: &LdapAuthUser) -> bool {
+    let changed = user_profile.first_name != ldap_user.first_name
+        || user_profile.last_name != ldap_user.last_name
+        || user_profile.email != ldap_user.email;
+    user_profile.first_name = ldap_user.first_name.clone();
+    user_profile.last_name = ldap_user.last_name.clone();
+    user_profile.email = ldap_user.email.clone();
+    changed
+}
+
+/// Role-less memberships of the mapped local groups follow the directory, other memberships
+/// are managed locally. Local groups missing from the database are skipped. Whether a
+/// membership was added or removed
+async fn sync_ldap_groups(
+    tx: &mut Transaction<'_, Postgres>,
+    auth: &LdapAuth,
+    user_id: &Uuid,
+    ldap_groups: &[String],
+) -> anyhow::Result<bool> {
+    if auth.group_mapping.is_empty() {
+        return Ok(false);
+    }
+    let desired = auth.mapped_local_groups(ldap_groups);
+    let mut managed: HashSet<Uuid> = HashSet::new();
+    let mut desired_ids: HashSet<Uuid> = HashSet::new();
+    for (_, group_name) in auth.group_mapping.iter() {
+        match get_group_by_name(tx, group_name).await? {
+            Some(group) => {
+                managed.insert(group.id);
+                if desired.contains(group_name) {
+                    desired_ids.insert(group.id);
+                }
+            }
+            None => tracing::warn!("ldap login: mapped group {} does not exist", group_name),
+        }
+    }
+
+    let memberships = get_user_group_memberships(tx, user_id).await?;
+    let mut current: HashSet<Uuid> = HashSet::new();
+    let mut changed = false;
+    for item in memberships.iter() {
+        let group_id = match item.group_id {
+            Some(val) if managed.contains(&val) => val,
+            _ => continue,
+        };
+        if !desired_ids.contains(&group_id) || !current.insert(group_id) {
+            delete_user_group_roles_by_id(tx, &item.id).await?;
+            changed = true;
+        }
+    }
+    for group_id in desired_ids.difference(&current) {
+        changed = true;
+        add_user_group_roles(
+            tx,
+            &UserGroupRoles {
+                id: Uuid::now_v7(),
+                user_id: Some(*user_id),
+                group_id: Some(*group_id),
+                role_id: None,
+            },
+        )
+        .await?;
+    }
+    Ok(changed)
+}
+
+/// Create the local user of a directory user logging in for the first time, None when its
+/// user name belongs to a local user
+async fn provision_ldap_user(
+    tx: &mut Transaction<'_, Postgres>,
+    ldap_user: &LdapAuthUser,
+    user_name: &str,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<Option<User>> {
+    // never take over a local account on the strength of a matching name
+    let (existing, _) = get_user_by_username(tx, user_name).await?;
+    if existing.is_some() {
+        tracing::warn!(
+            "ldap login: user name {} belongs to a local user, not provisioned",
+            user_name
+        );
+        return Ok(None);
+    }
+    let id = Uuid::now_v7();
+    // ldap users authenticate upstream, local password is never handed out
+    let password = match hash_password(&Uuid::now_v7().to_string()) {
+        Ok(val) => val,
+        Err(err) => anyhow::bail!(err.to_string()),
+    };
+    let user = User {
+        id,
+        user_name: user_name.to_string(),
+        password,
+        status: UserStatus::Active,
+        expires_at: None,
+        is_2faenabled: Some(false),
+        created_by: None,
+        updated_by: None,
+        created_date: Some(*now),
+        updated_date: Some(*now),
+        deleted_date: None,
+        scopes: None,
+    };
+    let mut user_profile = empty_user_profile(id);
+    apply_ldap_user(&mut user_profile, ldap_user);
+    create_user(tx, &user, &user_profile).await?;
+    create_user_identity(
+        tx,
+        &UserIdentity {
+            id: Uuid::now_v7(),
+            user_id: id,
+            provider: PROVIDER_LDAP.to_string(),
+            subject: ldap_user.external_id.clone(),
+            directory_source_id: None,
+            created_date: Some(*now),
+            updated_date: Some(*now),
+        },
+    )
+    .await?;
+    enqueue_scim_event(tx, &id, EVENT_CREATE, Some(*now)).await?;
+    Ok(Some(user))
+}
+
+/// Outcome of ldap_login
+pub enum LdapLogin {
+    /// Local user not linked to the directory, its own password applies
+    Local,
+    Rejected,
+    /// `groups_changed` when mapped group memberships were added or removed, see
+    /// permissions_changed
+    Accepted {
+        user: Box<User>,
+        groups_changed: bool,
+    },
+}
+
+/// Login of users linked to ldap and of user names unknown locally, checked by a bind on the
+/// directory. Accepted users get their profile and mapped groups refreshed, unknown ones are
+/// provisioned. Status and expiry are left to the caller
+pub async fn ldap_login(
+    tx: &mut Transaction<'_, Postgres>,
+    auth: &LdapAuth,
+    existing: Option<(&User, &UserProfile)>,
+    user_name: &str,
+    password: &str,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<LdapLogin> {
+    if let Some((user, _)) = existing {
+        if get_user_identity_by_user(tx, &user.id, PROVIDER_LDAP)
+            .await?
+            .is_none()
+        {
+            return Ok(LdapLogin::Local);
+        }
+    }
+    let ldap_user = match ldap_authenticate(auth, user_name, password).await? {
+        Some(val) => val,
+        None => return Ok(LdapLogin::Rejected),
+    };
+
+    let (mut user, mut user_profile) = match existing {
+        Some((user, user_profile)) => (user.clone(), user_profile.clone()),
+        // renamed locally, the identity still points at the user
+        None => match get_user_identity(tx, PROVIDER_LDAP, &ldap_user.external_id).await? {
+            Some(identity) => match get_user_by_id(tx, &identity.user_id, Some(true)).await? {
+                (Some(user), user_profile) => {
+                    let user_profile = user_profile.unwrap_or(empty_user_profile(user.id));
+                    (user, user_profile)
+                }
+                (None, _) => return Ok(LdapLogin::Rejected),
+            },
+            None => {
+                let user_name = ldap_user.user_name.as_deref().unwrap_or(user_name);
+                return match provision_ldap_user(tx, &ldap_user, user_name, now).await? {
+                    Some(user) => {
+                        let groups_changed =
+                            sync_ldap_groups(tx, auth, &user.id, &ldap_user.groups).await?;
+                        Ok(LdapLogin::Accepted {
+                            user: Box::new(user),
+                            groups_changed,
+                        })
+                    }
+                    None => Ok(LdapLogin::Rejected),
+                };
+            }
+        },
+    };
+    if apply_ldap_user(&mut user_profile, &ldap_user) {
+        let actor = user.clone();
+        update_user(tx, &mut user, &user_profile, &actor, now).await?;
+        enqueue_scim_event(tx, &user.id, EVENT_UPDATE, Some(*now)).await?;
+    }
+    let groups_changed = sync_ldap_groups(tx, auth, &user.id, &ldap_user.groups).await?;
+    Ok(LdapLogin::Accepted {
+        user: Box::new(user),
+        groups_changed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::ldap_auth::{LdapAttributes, LdapAuth, DEFAULT_LDAP_AUTH_USER_FILTER},
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_ldap_auth_from_config() {
+        // Given
+        let mut config = get_config();
+        config.ldap_auth_url = None;
+
+        // Expect disabled without url
+        assert_eq!(LdapAuth::from_config(&config).unwrap(), None);
+
+        // When only url and base dn set
+        config.ldap_auth_url = Some("ldaps://ldap.example.com".to_string());
+        config.ldap_auth_base_dn = Some("ou=people,dc=example,dc=com".to_string());
+        config.ldap_auth_bind_dn = None;
+        config.ldap_auth_user_filter = None;
+        config.ldap_auth_attributes = None;
+        config.ldap_auth_group_mapping = None;
+        let auth = LdapAuth::from_config(&config).unwrap().unwrap();
+
+        // Expect defaults
+        assert_eq!(auth.user_filter, DEFAULT_LDAP_AUTH_USER_FILTER);
+        assert_eq!(auth.attributes, LdapAttributes::default());
+        assert!(auth.group_mapping.is_empty());
+
+        // When mappings set
+        config.ldap_auth_attributes = Some("email=userPrincipalName, first_name=cn".to_string());
+        config.ldap_auth_group_mapping =
+            Some("Engineering=engineering,Ops=engineering,Admins=admin".to_string());
+        let auth = LdapAuth::from_config(&config).unwrap().unwrap();
+
+        // Expect
+        assert_eq!(auth.attributes.email, "userPrincipalName");
+        assert_eq!(auth.attributes.first_name, "cn");
+        assert_eq!(auth.attributes.last_name, "sn");
+        assert_eq!(
+            auth.mapped_local_groups(&["ops".to_string(), "Engineering".to_string()]),
+            vec!["engineering".to_string()]
+        );
+        assert_eq!(
+            auth.mapped_local_groups(&["Admins".to_string(), "Sales".to_string()]),
+            vec!["admin".to_string()]
+        );
+
+        // Expect invalid settings rejected
+        for (field, value) in [
+            ("url", "http://ldap.example.com"),
+            ("base_dn", ""),
+            ("user_filter", "(uid=admin)"),
+            ("attributes", "phone=telephoneNumber"),
+            ("attributes", "email"),
+            ("group_mapping", "Engineering="),
+        ] {
+            let mut config = config.clone();
+            match field {
+                "url" => config.ldap_auth_url = Some(value.to_string()),
+                "base_dn" => config.ldap_auth_base_dn = Some(value.to_string()),
+                "user_filter" => config.ldap_auth_user_filter = Some(value.to_string()),
+                "attributes" => config.ldap_auth_attributes = Some(value.to_string()),
+                _ => config.ldap_auth_group_mapping = Some(value.to_string()),
+            }
+            assert!(LdapAuth::from_config(&config).is_err(), "{}", field);
+        }
+    }
+}
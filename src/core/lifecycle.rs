@@ -0,0 +1,181 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{session::revoke_user_sessions, session_store::SessionStore},
+    model::{
+        user::{User, UserStatus},
+        user_status_history::UserStatusHistory,
+    },
+    repository::{user::update_user_status, user_status_history::create_user_status_history},
+    settings::get_config,
+};
+
+/// Statuses reachable from `status`, deprovisioned is terminal
+pub fn allowed_transitions(status: UserStatus) -> &'static [UserStatus] {
+    match status {
+        UserStatus::Pending => &[UserStatus::Active, UserStatus::Deprovisioned],
+        UserStatus::Active => &[UserStatus::Suspended, UserStatus::Deprovisioned],
+        UserStatus::Suspended => &[UserStatus::Active, UserStatus::Deprovisioned],
+        UserStatus::Deprovisioned => &[],
+    }
+}
+
+/// Return the reason a transition is rejected, None when allowed
+pub fn check_transition(from: UserStatus, to: UserStatus) -> Option<String> {
+    if from == to {
+        return Some(format!("user is already {}", to));
+    }
+    let allowed = allowed_transitions(from);
+    if !allowed.contains(&to) {
+        if allowed.is_empty() {
+            return Some(format!("user is {}, status can not be changed", from));
+        }
+        return Some(format!(
+            "user is {}, status can only change to {}",
+            from,
+            allowed
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    None
+}
+
+/// Move a user to `status` and record the change. `changed_by` is empty for
+/// automatic changes such as directory sync or scheduled jobs. Sessions are left alone, once
+/// committed pass the users whose history [`UserStatusHistory::left_active`] to
+/// [`revoke_deactivated_sessions`].
+pub async fn change_user_status(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &mut User,
+    status: UserStatus,
+    reason: Option<String>,
+    changed_by: Option<Uuid>,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserStatusHistory> {
+    if let Some(err) = check_transition(user.status, status) {
+        anyhow::bail!(err);
+    }
+    let history = UserStatusHistory {
+        id: Uuid::now_v7(),
+        user_id: user.id,
+        from_status: user.status,
+        to_status: status,
+        reason,
+        changed_by,
+        created_date: Some(*now),
+    };
+    user.status = status;
+    user.updated_by = changed_by.or(user.updated_by);
+    user.updated_date = Some(*now);
+    update_user_status(tx, user).await?;
+    create_user_status_history(tx, &history).await?;
+    Ok(history)
+}
+
+/// Sign users who left active out of every session. Runs after the status change is
+/// committed, a rolled back change keeps the sessions
+pub async fn revoke_deactivated_sessions<C: SessionStore>(
+    redis_conn: &mut C,
+    user_ids: &[Uuid],
+) -> anyhow::Result<()> {
+    // jwt_exp is in minutes, longer than any session issued before
+    let ttl = get_config().jwt_exp as u64 * 60;
+    for user_id in user_ids {
+        revoke_user_sessions(redis_conn, user_id, ttl).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            lifecycle::{change_user_status, check_transition, revoke_deactivated_sessions},
+            session::get_session,
+            session_store::create_redis_pool,
+            test_utils::generate_test_user,
+        },
+        model::user::UserStatus,
+        repository::{user::get_user_by_id, user_status_history::get_user_status_history_by_user},
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_check_transition() {
+        assert!(check_transition(UserStatus::Pending, UserStatus::Active).is_none());
+        assert!(check_transition(UserStatus::Active, UserStatus::Suspended).is_none());
+        assert!(check_transition(UserStatus::Suspended, UserStatus::Active).is_none());
+        assert!(check_transition(UserStatus::Suspended, UserStatus::Deprovisioned).is_none());
+        assert!(check_transition(UserStatus::Active, UserStatus::Pending).is_some());
+        assert!(check_transition(UserStatus::Active, UserStatus::Active).is_some());
+        assert!(check_transition(UserStatus::Deprovisioned, UserStatus::Active).is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_change_user_status(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let mut redis_conn = create_redis_pool(&config)?.get().await?;
+        let mut db = pool.acquire().await?;
+        let test_user =
+            generate_test_user(&mut db, &mut redis_conn, config, "test_user", "password").await?;
+        let now = Local::now().fixed_offset();
+        let mut tx = pool.begin().await?;
+        let mut user = test_user.user.clone();
+
+        // When
+        change_user_status(
+            &mut tx,
+            &mut user,
+            UserStatus::Suspended,
+            Some("policy violation".to_string()),
+            None,
+            &now,
+        )
+        .await?;
+
+        // Expect status and is_active stored with history
+        let (stored, _) = get_user_by_id(&mut tx, &user.id, None).await?;
+        let stored = stored.unwrap();
+        assert_eq!(stored.status, UserStatus::Suspended);
+        assert!(!stored.is_active());
+        let (is_active,): (Option<bool>,) =
+            sqlx::query_as("SELECT is_active FROM public.user WHERE id = $1")
+                .bind(user.id)
+                .fetch_one(&mut *tx)
+                .await?;
+        assert_eq!(is_active, Some(false));
+        let history = get_user_status_history_by_user(&mut tx, &user.id).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].from_status, UserStatus::Active);
+        assert_eq!(history[0].reason, Some("policy violation".to_string()));
+        // and still signed in until revoked after commit
+        assert!(history[0].left_active());
+        assert!(get_session(&mut redis_conn, test_user.token.clone())
+            .await?
+            .is_some());
+        tx.commit().await?;
+        revoke_deactivated_sessions(&mut redis_conn, &[user.id]).await?;
+        assert!(get_session(&mut redis_conn, test_user.token.clone())
+            .await?
+            .is_none());
+
+        // When transition not allowed
+        let mut tx = pool.begin().await?;
+        let res =
+            change_user_status(&mut tx, &mut user, UserStatus::Pending, None, None, &now).await;
+
+        // Expect
+        assert!(res.is_err());
+        assert_eq!(user.status, UserStatus::Suspended);
+        Ok(())
+    }
+}
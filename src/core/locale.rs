@@ -0,0 +1,174 @@
+use chrono::{DateTime, FixedOffset};
+use chrono_tz::Tz;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    core::request_id::set_request_timezone, model::user_profile::UserProfile,
+    repository::user::get_user_by_id, settings::Config,
+};
+
+pub const DEFAULT_LOCALE: &str = "en";
+pub const DEFAULT_TIMEZONE: &str = "Asia/Jakarta";
+/// Languages notification emails are written in
+pub const SUPPORTED_LANGUAGES: &[&str] = &["en", "id"];
+
+/// Locale as stored, e.g. id_id -> id-ID. None when the language is not supported
+pub fn normalize_locale(val: &str) -> Option<String> {
+    let mut parts = val.trim().split(['-', '_']);
+    let language = parts.next()?.to_lowercase();
+    if !SUPPORTED_LANGUAGES.contains(&language.as_str()) {
+        return None;
+    }
+    match (parts.next(), parts.next()) {
+        (None, _) => Some(language),
+        (Some(region), None)
+            if region.len() == 2 && region.chars().all(|x| x.is_ascii_alphabetic()) =>
+        {
+            Some(format!("{}-{}", language, region.to_uppercase()))
+        }
+        _ => None,
+    }
+}
+
+/// IANA timezone name such as Asia/Jakarta
+pub fn parse_timezone(val: &str) -> Option<Tz> {
+    val.trim().parse().ok()
+}
+
+/// Profile locale and timezone as stored, empty values are cleared. Err is the reason
+/// a value is rejected.
+pub fn normalize_profile_locale(
+    locale: Option<String>,
+    timezone: Option<String>,
+) -> Result<(Option<String>, Option<String>), String> {
+    let locale = match locale.filter(|x| !x.trim().is_empty()) {
+        Some(val) => Some(normalize_locale(&val).ok_or(format!(
+            "locale {} is not supported, language must be one of {}",
+            val,
+            SUPPORTED_LANGUAGES.join(", ")
+        ))?),
+        None => None,
+    };
+    let timezone = match timezone.filter(|x| !x.trim().is_empty()) {
+        Some(val) => match parse_timezone(&val) {
+            Some(tz) => Some(tz.name().to_string()),
+            None => return Err(format!("timezone {} is not an IANA timezone", val)),
+        },
+        None => None,
+    };
+    Ok((locale, timezone))
+}
+
+/// Locale and timezone messages to a user are rendered in
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserLocale {
+    pub locale: String,
+    pub timezone: Tz,
+}
+
+impl UserLocale {
+    /// Profile values, falling back to DEFAULT_LOCALE and DEFAULT_TIMEZONE
+    pub fn resolve(config: &Config, user_profile: Option<&UserProfile>) -> Self {
+        let locale = [
+            user_profile.and_then(|x| x.locale.as_deref()),
+            config.default_locale.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(normalize_locale)
+        .unwrap_or(DEFAULT_LOCALE.to_string());
+        let timezone = [
+            user_profile.and_then(|x| x.timezone.as_deref()),
+            config.default_timezone.as_deref(),
+            Some(DEFAULT_TIMEZONE),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(parse_timezone)
+        .unwrap_or(Tz::UTC);
+        Self { locale, timezone }
+    }
+
+    /// Language part of the locale, e.g. id for id-ID
+    pub fn language(&self) -> &str {
+        self.locale.split('-').next().unwrap_or(DEFAULT_LOCALE)
+    }
+
+    /// Date and time in the user timezone, e.g. 2025-04-15 09:30 WIB
+    pub fn format_datetime(&self, datetime: DateTime<FixedOffset>) -> String {
+        datetime
+            .with_timezone(&self.timezone)
+            .format("%Y-%m-%d %H:%M %Z")
+            .to_string()
+    }
+}
+
+/// Format the response of the request in the timezone of the user, for handlers that
+/// authenticate the user themselves such as login, see get_user_from_token
+pub async fn use_user_timezone(
+    conn: &mut PgConnection,
+    config: &Config,
+    user_id: &Uuid,
+) -> anyhow::Result<()> {
+    let (_, user_profile) = get_user_by_id(conn, user_id, None).await?;
+    set_request_timezone(UserLocale::resolve(config, user_profile.as_ref()).timezone);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::{
+        core::locale::{normalize_locale, normalize_profile_locale, parse_timezone, UserLocale},
+        model::user_profile::UserProfile,
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_normalize_locale() {
+        assert_eq!(normalize_locale("id_id"), Some("id-ID".to_string()));
+        assert_eq!(normalize_locale(" EN "), Some("en".to_string()));
+        assert!(normalize_locale("fr-FR").is_none());
+        assert!(normalize_locale("en-USA").is_none());
+        assert!(parse_timezone("Asia/Makassar").is_some());
+        assert!(parse_timezone("+0700").is_none());
+        assert_eq!(
+            normalize_profile_locale(Some("id".to_string()), Some(" ".to_string())),
+            Ok((Some("id".to_string()), None))
+        );
+        assert!(normalize_profile_locale(None, Some("Asia/Nowhere".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_user_locale() {
+        // Given
+        let mut config = get_config();
+        config.default_locale = None;
+        config.default_timezone = None;
+        let id = uuid::Uuid::now_v7();
+        let user_profile = UserProfile {
+            id,
+            user_id: id,
+            first_name: None,
+            last_name: None,
+            address: None,
+            email: None,
+            locale: Some("id-ID".to_string()),
+            timezone: Some("Asia/Makassar".to_string()),
+            email_verified_at: None,
+        };
+        let datetime = DateTime::parse_from_rfc3339("2025-04-15T01:30:00Z").unwrap();
+
+        // Expect profile values honored
+        let locale = UserLocale::resolve(&config, Some(&user_profile));
+        assert_eq!(locale.language(), "id");
+        assert_eq!(locale.format_datetime(datetime), "2025-04-15 09:30 WITA");
+
+        // Expect defaults without a profile
+        let locale = UserLocale::resolve(&config, None);
+        assert_eq!(locale.locale, "en");
+        assert_eq!(locale.format_datetime(datetime), "2025-04-15 08:30 WIB");
+    }
+}
@@ -0,0 +1,208 @@
+use uuid::Uuid;
+
+use crate::settings::Config;
+
+use super::session_store::SessionStore;
+
+pub const DEFAULT_LOGIN_LOCKOUT_ATTEMPTS: u32 = 5;
+pub const DEFAULT_LOGIN_LOCKOUT_WINDOW: u64 = 900;
+pub const DEFAULT_LOGIN_LOCKOUT_DURATION: u64 = 900;
+pub const FAILURES_KEY_PREFIX: &str = "login_failures:";
+pub const LOCK_KEY_PREFIX: &str = "login_lock:";
+
+/// Failed logins allowed within the window before the user is locked for the duration,
+/// both in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginLockout {
+    pub attempts: u32,
+    pub window: u64,
+    pub duration: u64,
+}
+
+impl LoginLockout {
+    /// Thresholds from settings, None when LOGIN_LOCKOUT_ATTEMPTS is 0
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let attempts = config
+            .login_lockout_attempts
+            .unwrap_or(DEFAULT_LOGIN_LOCKOUT_ATTEMPTS);
+        if attempts == 0 {
+            return None;
+        }
+        Some(Self {
+            attempts,
+            window: config
+                .login_lockout_window
+                .unwrap_or(DEFAULT_LOGIN_LOCKOUT_WINDOW),
+            duration: config
+                .login_lockout_duration
+                .unwrap_or(DEFAULT_LOGIN_LOCKOUT_DURATION),
+        })
+    }
+}
+
+/// Message of the 403 returned while locked
+pub fn login_locked_message(retry_after: u64) -> String {
+    format!(
+        "too many failed logins, try again in {} seconds",
+        retry_after
+    )
+}
+
+/// Seconds until the lock of the user ends, None when not locked
+pub async fn get_login_lock<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+) -> anyhow::Result<Option<u64>> {
+    let ttl = redis_conn
+        .ttl(&format!("{}{}", LOCK_KEY_PREFIX, user_id))
+        .await?;
+    // -2 is a missing key, locks are always set with an expiry
+    Ok(match ttl {
+        -2 => None,
+        val => Some(val.max(1) as u64),
+    })
+}
+
+/// Count a failed login of the user, the count starts over after the window.
+/// Lock the user once the attempts are reached, return whether it did
+pub async fn record_login_failure<C: SessionStore>(
+    redis_conn: &mut C,
+    lockout: &LoginLockout,
+    user_id: &Uuid,
+) -> anyhow::Result<bool> {
+    let failures_key = format!("{}{}", FAILURES_KEY_PREFIX, user_id);
+    let count = redis_conn.incr(&failures_key).await?;
+    if count == 1 {
+        redis_conn.expire(&failures_key, lockout.window).await?;
+    }
+    if count < lockout.attempts as i64 {
+        return Ok(false);
+    }
+    tracing::warn!(
+        "user {} locked after {} failed logins for {} seconds",
+        user_id,
+        count,
+        lockout.duration
+    );
+    redis_conn
+        .set_ex(
+            &format!("{}{}", LOCK_KEY_PREFIX, user_id),
+            "1",
+            lockout.duration,
+        )
+        .await?;
+    redis_conn.del(&failures_key).await?;
+    Ok(true)
+}
+
+/// Forget the failed logins of the user, after a successful login
+pub async fn clear_login_failures<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+) -> anyhow::Result<()> {
+    redis_conn
+        .del(&format!("{}{}", FAILURES_KEY_PREFIX, user_id))
+        .await?;
+    Ok(())
+}
+
+/// Lift the lock and forget the failed logins, return whether the user was locked
+pub async fn unlock_user<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+) -> anyhow::Result<bool> {
+    let deleted = redis_conn
+        .del(&format!("{}{}", LOCK_KEY_PREFIX, user_id))
+        .await?;
+    clear_login_failures(redis_conn, user_id).await?;
+    Ok(deleted > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        core::{
+            login_lockout::{
+                clear_login_failures, get_login_lock, record_login_failure, unlock_user,
+                LoginLockout,
+            },
+            session_store::{create_redis_pool, MemoryStore},
+        },
+        settings::get_config,
+    };
+
+    #[tokio::test]
+    async fn test_login_lockout() -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let mut redis_conn = create_redis_pool(&config)?.get().await?;
+        let lockout = LoginLockout {
+            attempts: 3,
+            window: 60,
+            duration: 120,
+        };
+        let user_id = Uuid::now_v7();
+
+        // When failures are cleared by a successful login
+        record_login_failure(&mut redis_conn, &lockout, &user_id).await?;
+        record_login_failure(&mut redis_conn, &lockout, &user_id).await?;
+        clear_login_failures(&mut redis_conn, &user_id).await?;
+
+        // Expect the count to start over
+        assert!(!record_login_failure(&mut redis_conn, &lockout, &user_id).await?);
+        assert!(!record_login_failure(&mut redis_conn, &lockout, &user_id).await?);
+        assert_eq!(get_login_lock(&mut redis_conn, &user_id).await?, None);
+
+        // Expect locked on the last attempt
+        assert!(record_login_failure(&mut redis_conn, &lockout, &user_id).await?);
+        let retry_after = get_login_lock(&mut redis_conn, &user_id).await?.unwrap();
+        assert!(retry_after > 60 && retry_after <= 120);
+
+        // Expect unlocked once
+        assert!(unlock_user(&mut redis_conn, &user_id).await?);
+        assert!(!unlock_user(&mut redis_conn, &user_id).await?);
+        assert_eq!(get_login_lock(&mut redis_conn, &user_id).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_lockout_memory_store() -> anyhow::Result<()> {
+        // Given
+        let mut store = MemoryStore::new();
+        let lockout = LoginLockout {
+            attempts: 2,
+            window: 60,
+            duration: 120,
+        };
+        let user_id = Uuid::now_v7();
+
+        // Expect
+        assert!(!record_login_failure(&mut store, &lockout, &user_id).await?);
+        assert!(record_login_failure(&mut store, &lockout, &user_id).await?);
+        assert!(get_login_lock(&mut store, &user_id).await?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_login_lockout_from_config() {
+        // Given
+        let mut config = get_config();
+        config.login_lockout_attempts = None;
+        config.login_lockout_window = Some(60);
+        config.login_lockout_duration = None;
+
+        // Expect
+        assert_eq!(
+            LoginLockout::from_config(&config),
+            Some(LoginLockout {
+                attempts: 5,
+                window: 60,
+                duration: 900
+            })
+        );
+        config.login_lockout_attempts = Some(0);
+        assert_eq!(LoginLockout::from_config(&config), None);
+    }
+}
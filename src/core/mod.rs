@@ -1,6 +1,51 @@
+pub mod account_expiry;
+pub mod api_version;
+pub mod build_info;
+pub mod classification;
+pub mod data_export;
 pub mod db;
+pub mod db_error;
+pub mod deprecation;
+pub mod directory_sync;
+pub mod dormant_account;
+pub mod email;
+pub mod email_change;
+pub mod email_verification;
+pub mod error;
+pub mod error_code;
+pub mod i18n;
+pub mod jwk;
+pub mod ldap_auth;
+pub mod lifecycle;
+pub mod locale;
+pub mod login_lockout;
+pub mod mailer;
+pub mod notifications;
+pub mod permission_cache;
+pub mod permission_expiry;
+pub mod permission_grant;
+pub mod pii;
+pub mod preference;
+pub mod push;
+pub mod rate_limit;
+pub mod recovery_code;
+pub mod request_id;
+pub mod retention;
+pub mod sanitize;
+pub mod scim;
+pub mod secrets;
 pub mod security;
+pub mod service_account;
 pub mod session;
+pub mod session_store;
+pub mod sms;
 pub mod sqlx_utils;
+pub mod sso;
+pub mod terms;
 pub mod test_utils;
+pub mod tls;
+pub mod user_contact;
+pub mod user_import;
+pub mod user_name;
 pub mod utils;
+pub mod validation;
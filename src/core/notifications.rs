@@ -0,0 +1,375 @@
+use handlebars::{no_escape, Handlebars, Template};
+use serde_json::Value;
+use sqlx::{Postgres, Transaction};
+
+use crate::{
+    core::{
+        email::{send_email, OutgoingEmail},
+        locale::{UserLocale, DEFAULT_LOCALE},
+        sms::{send_sms, OutgoingSms},
+    },
+    model::notification_template::{
+        CHANNEL_EMAIL, CHANNEL_PUSH, CHANNEL_SMS, EVENT_EMAIL_CHANGE_CONFIRM,
+        EVENT_EMAIL_CHANGE_NOTICE, EVENT_EMAIL_VERIFICATION, EVENT_INVITE, EVENT_NEW_LOGIN,
+        EVENT_PASSWORD_CHANGED, EVENT_PASSWORD_RESET,
+    },
+    repository::notification_template::get_notification_template,
+    settings::Config,
+};
+
+/// Template shipped with the service, used when no template is stored for the event
+#[derive(Clone, Copy, Debug)]
+pub struct BuiltinTemplate {
+    pub event: &'static str,
+    pub channel: &'static str,
+    pub language: &'static str,
+    pub subject: Option<&'static str>,
+    pub body: &'static str,
+}
+
+const fn builtin(
+    event: &'static str,
+    channel: &'static str,
+    language: &'static str,
+    subject: Option<&'static str>,
+    body: &'static str,
+) -> BuiltinTemplate {
+    BuiltinTemplate {
+        event,
+        channel,
+        language,
+        subject,
+        body,
+    }
+}
+
+/// Variables: user_name on every event, link and expires_date on invite, password_reset,
+/// email_change_confirm and email_verification, new_email on email_change_notice, date on
+/// new_login and password_changed
+pub const BUILTIN_TEMPLATES: &[BuiltinTemplate] = &[
+    builtin(
+        EVENT_INVITE,
+        CHANNEL_EMAIL,
+        "en",
+        Some("Your account is ready"),
+        "Hi {{user_name}},\n\nAn account was created for you. Set your password before {{expires_date}}:\n{{link}}",
+    ),
+    builtin(
+        EVENT_INVITE,
+        CHANNEL_EMAIL,
+        "id",
+        Some("Akun Anda sudah siap"),
+        "Halo {{user_name}},\n\nAkun telah dibuat untuk Anda. Atur kata sandi Anda sebelum {{expires_date}}:\n{{link}}",
+    ),
+    builtin(
+        EVENT_PASSWORD_RESET,
+        CHANNEL_EMAIL,
+        "en",
+        Some("Reset your password"),
+        "Hi {{user_name}},\n\nReset your password before {{expires_date}}:\n{{link}}\n\nIgnore this email if you did not ask for a reset.",
+    ),
+    builtin(
+        EVENT_PASSWORD_RESET,
+        CHANNEL_EMAIL,
+        "id",
+        Some("Atur ulang kata sandi Anda"),
+        "Halo {{user_name}},\n\nAtur ulang kata sandi Anda sebelum {{expires_date}}:\n{{link}}\n\nAbaikan email ini jika Anda tidak memintanya.",
+    ),
+    builtin(
+        EVENT_PASSWORD_RESET,
+        CHANNEL_SMS,
+        "en",
+        None,
+        "Reset your password before {{expires_date}}: {{link}}",
+    ),
+    builtin(
+        EVENT_PASSWORD_RESET,
+        CHANNEL_SMS,
+        "id",
+        None,
+        "Atur ulang kata sandi Anda sebelum {{expires_date}}: {{link}}",
+    ),
+    builtin(
+        EVENT_EMAIL_CHANGE_CONFIRM,
+        CHANNEL_EMAIL,
+        "en",
+        Some("Confirm your new email address"),
+        "Hi {{user_name}},\n\nConfirm this address for your account before {{expires_date}}:\n{{link}}\n\nIgnore this email if you did not ask for the change.",
+    ),
+    builtin(
+        EVENT_EMAIL_CHANGE_CONFIRM,
+        CHANNEL_EMAIL,
+        "id",
+        Some("Konfirmasi alamat email baru Anda"),
+        "Halo {{user_name}},\n\nKonfirmasi alamat ini untuk akun Anda sebelum {{expires_date}}:\n{{link}}\n\nAbaikan email ini jika Anda tidak meminta perubahan.",
+    ),
+    builtin(
+        EVENT_EMAIL_VERIFICATION,
+        CHANNEL_EMAIL,
+        "en",
+        Some("Verify your email address"),
+        "Hi {{user_name}},\n\nVerify this address for your account before {{expires_date}}:\n{{link}}",
+    ),
+    builtin(
+        EVENT_EMAIL_VERIFICATION,
+        CHANNEL_EMAIL,
+        "id",
+        Some("Verifikasi alamat email Anda"),
+        "Halo {{user_name}},\n\nVerifikasi alamat ini untuk akun Anda sebelum {{expires_date}}:\n{{link}}",
+    ),
+    builtin(
+        EVENT_EMAIL_CHANGE_NOTICE,
+        CHANNEL_EMAIL,
+        "en",
+        Some("Email address change requested"),
+        "Hi {{user_name}},\n\nA change of your account email to {{new_email}} was requested. It only takes effect once confirmed from the new address. Contact your administrator if this was not you.",
+    ),
+    builtin(
+        EVENT_EMAIL_CHANGE_NOTICE,
+        CHANNEL_EMAIL,
+        "id",
+        Some("Permintaan perubahan alamat email"),
+        "Halo {{user_name}},\n\nPerubahan email akun Anda menjadi {{new_email}} telah diminta. Perubahan hanya berlaku setelah dikonfirmasi dari alamat baru. Hubungi administrator jika ini bukan Anda.",
+    ),
+    builtin(
+        EVENT_EMAIL_CHANGE_NOTICE,
+        CHANNEL_SMS,
+        "en",
+        None,
+        "A change of your account email to {{new_email}} was requested. Contact your administrator if this was not you.",
+    ),
+    builtin(
+        EVENT_EMAIL_CHANGE_NOTICE,
+        CHANNEL_SMS,
+        "id",
+        None,
+        "Perubahan email akun Anda menjadi {{new_email}} telah diminta. Hubungi administrator jika ini bukan Anda.",
+    ),
+    builtin(
+        EVENT_NEW_LOGIN,
+        CHANNEL_PUSH,
+        "en",
+        Some("New login"),
+        "{{user_name}} logged in on {{date}}. Change your password if this was not you.",
+    ),
+    builtin(
+        EVENT_NEW_LOGIN,
+        CHANNEL_PUSH,
+        "id",
+        Some("Login baru"),
+        "{{user_name}} login pada {{date}}. Ganti kata sandi Anda jika ini bukan Anda.",
+    ),
+    builtin(
+        EVENT_PASSWORD_CHANGED,
+        CHANNEL_PUSH,
+        "en",
+        Some("Password changed"),
+        "The password of {{user_name}} was changed on {{date}}. Contact your administrator if this was not you.",
+    ),
+    builtin(
+        EVENT_PASSWORD_CHANGED,
+        CHANNEL_PUSH,
+        "id",
+        Some("Kata sandi diubah"),
+        "Kata sandi {{user_name}} diubah pada {{date}}. Hubungi administrator jika ini bukan Anda.",
+    ),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedTemplate {
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedNotification {
+    pub subject: String,
+    pub body: String,
+}
+
+/// Return the reason the template does not compile, None when valid
+pub fn check_template(template: &str) -> Option<String> {
+    Template::compile(template).err().map(|err| err.to_string())
+}
+
+/// Render without html escaping, missing variables are an error
+pub fn render_template(template: &str, data: &Value) -> anyhow::Result<String> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    handlebars.register_escape_fn(no_escape);
+    Ok(handlebars.render_template(template, data)?)
+}
+
+pub fn builtin_template(event: &str, channel: &str, language: &str) -> Option<ResolvedTemplate> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|x| x.event == event && x.channel == channel && x.language == language)
+        .map(|x| ResolvedTemplate {
+            subject: x.subject.map(|x| x.to_string()),
+            body: x.body.to_string(),
+        })
+}
+
+/// Stored template of the user locale, its language or DEFAULT_LOCALE, then the
+/// built-in one. None when the event is not sent over the channel.
+pub async fn resolve_template(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &str,
+    channel: &str,
+    locale: &UserLocale,
+) -> anyhow::Result<Option<ResolvedTemplate>> {
+    let mut candidates = vec![locale.locale.as_str()];
+    for val in [locale.language(), DEFAULT_LOCALE] {
+        if !candidates.contains(&val) {
+            candidates.push(val);
+        }
+    }
+    for candidate in candidates.iter() {
+        if let Some(template) = get_notification_template(tx, event, channel, candidate).await? {
+            return Ok(Some(ResolvedTemplate {
+                subject: template.subject,
+                body: template.body,
+            }));
+        }
+    }
+    Ok(candidates
+        .iter()
+        .find_map(|x| builtin_template(event, channel, x)))
+}
+
+pub async fn render_notification(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &str,
+    channel: &str,
+    locale: &UserLocale,
+    data: &Value,
+) -> anyhow::Result<RenderedNotification> {
+    let template = match resolve_template(tx, event, channel, locale).await? {
+        Some(val) => val,
+        None => anyhow::bail!("no {} template for {}", channel, event),
+    };
+    Ok(RenderedNotification {
+        subject: match template.subject {
+            Some(val) => render_template(&val, data)?,
+            None => String::new(),
+        },
+        body: render_template(&template.body, data)?,
+    })
+}
+
+/// Render the event template in the user locale and deliver it to `to` over the channel
+pub async fn send_notification(
+    tx: &mut Transaction<'_, Postgres>,
+    config: &Config,
+    event: &str,
+    channel: &str,
+    to: &str,
+    locale: &UserLocale,
+    data: &Value,
+) -> anyhow::Result<()> {
+    let rendered = render_notification(tx, event, channel, locale, data).await?;
+    match channel {
+        CHANNEL_EMAIL => {
+            send_email(
+                config,
+                &OutgoingEmail {
+                    to: to.to_string(),
+                    subject: rendered.subject,
+                    body: rendered.body,
+                },
+            )
+            .await
+        }
+        CHANNEL_SMS => {
+            send_sms(
+                config,
+                &OutgoingSms {
+                    to: to.to_string(),
+                    body: rendered.body,
+                },
+            )
+            .await
+        }
+        CHANNEL_PUSH => anyhow::bail!("push is sent to devices, see core::push"),
+        _ => anyhow::bail!("unknown notification channel {}", channel),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            locale::UserLocale,
+            notifications::{check_template, render_notification, render_template},
+        },
+        model::notification_template::{
+            NotificationTemplate, CHANNEL_EMAIL, EVENT_EMAIL_CHANGE_CONFIRM, EVENT_INVITE,
+        },
+        repository::notification_template::create_notification_template,
+    };
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template(
+                "Hi {{user_name}} <{{link}}>",
+                &json!({"user_name": "budi", "link": "a&b"})
+            )
+            .unwrap(),
+            "Hi budi <a&b>"
+        );
+        assert!(render_template("Hi {{user_name}}", &json!({})).is_err());
+        assert!(check_template("Hi {{#if x}}").is_some());
+        assert!(check_template("Hi {{user_name}}").is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_render_notification(pool: PgPool) -> anyhow::Result<()> {
+        // Given a stored id invite and no stored email change template
+        let mut tx = pool.begin().await?;
+        let id = uuid::Uuid::now_v7();
+        create_notification_template(
+            &mut tx,
+            &NotificationTemplate {
+                id,
+                event: EVENT_INVITE.to_string(),
+                channel: CHANNEL_EMAIL.to_string(),
+                locale: "id".to_string(),
+                subject: Some("Selamat datang {{user_name}}".to_string()),
+                body: "{{link}}".to_string(),
+                created_by: None,
+                updated_by: None,
+                created_date: None,
+                updated_date: None,
+            },
+        )
+        .await?;
+        let locale = UserLocale {
+            locale: "id-ID".to_string(),
+            timezone: "Asia/Jakarta".parse().unwrap(),
+        };
+        let data = json!({"user_name": "budi", "link": "https://example.com", "expires_date": "2025-04-15 08:00 WIB"});
+
+        // When
+        let invite =
+            render_notification(&mut tx, EVENT_INVITE, CHANNEL_EMAIL, &locale, &data).await?;
+        let confirm = render_notification(
+            &mut tx,
+            EVENT_EMAIL_CHANGE_CONFIRM,
+            CHANNEL_EMAIL,
+            &locale,
+            &data,
+        )
+        .await?;
+
+        // Expect stored template for the language, built-in one otherwise
+        assert_eq!(invite.subject, "Selamat datang budi");
+        assert_eq!(invite.body, "https://example.com");
+        assert_eq!(confirm.subject, "Konfirmasi alamat email baru Anda");
+        assert!(confirm.body.contains("2025-04-15 08:00 WIB"));
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        session::{revoke_user_sessions, scan_keys},
+        session_store::{SessionPool, SessionStore},
+        utils::utc_now,
+    },
+    repository::{
+        user::get_active_user_ids,
+        user_permission::{get_user_permission_names, user_has_permission},
+    },
+    settings::{get_config, Config},
+};
+
+pub const DEFAULT_PERMISSION_CACHE_TTL: u64 = 300;
+const PERMISSION_CACHE_PREFIX: &str = "core:permissions:";
+
+/// Required to rebuild the cache
+pub const PERMISSION_NAME: &str = "permission_cache";
+pub const PERMISSION_ATTRIBUTE: &str = "update";
+
+pub const REBUILD_STATUS_RUNNING: &str = "running";
+pub const REBUILD_STATUS_DONE: &str = "done";
+pub const REBUILD_STATUS_FAILED: &str = "failed";
+const REBUILD_PREFIX: &str = "core:permission_cache_rebuild:";
+/// Progress of a rebuild stays readable for a day
+const REBUILD_TTL: u64 = 86400;
+/// Progress is saved every this many users
+const REBUILD_SAVE_EVERY: u64 = 100;
+
+fn permission_cache_key(user_id: &Uuid) -> String {
+    format!("{}{}", PERMISSION_CACHE_PREFIX, user_id)
+}
+
+fn permission_cache_ttl(config: &Config) -> u64 {
+    config
+        .permission_cache_ttl
+        .unwrap_or(DEFAULT_PERMISSION_CACHE_TTL)
+}
+
+/// Cache (permission_name, attribute_name) pairs as the permission set of the user
+pub async fn cache_user_permissions<C: SessionStore>(
+    redis_conn: &mut C,
+    ttl: u64,
+    user_id: &Uuid,
+    names: &[(String, String)],
+) -> anyhow::Result<()> {
+    redis_conn
+        .set_ex(
+            &permission_cache_key(user_id),
+            &serde_json::to_string(names)?,
+            ttl,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Load the permission set of the user and cache it for ttl seconds
+async fn warm_user_permissions<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    ttl: u64,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let res = get_user_permission_names(conn, user_id).await?;
+    cache_user_permissions(redis_conn, ttl, user_id, &res).await?;
+    Ok(res)
+}
+
+/// Whether the user holds the permission attribute, see user_has_permission. The permission
+/// set of the user is cached for PERMISSION_CACHE_TTL seconds, invalidate_user_permissions
+/// drops it when roles, groups or grants change
+pub async fn has_permission<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    config: &Config,
+    user_id: &Uuid,
+    permission_name: &str,
+    attribute_name: &str,
+) -> anyhow::Result<bool> {
+    if permission_cache_ttl(config) == 0 {
+        return user_has_permission(conn, user_id, permission_name, attribute_name).await;
+    }
+    let names = get_user_permissions(conn, redis_conn, config, user_id).await?;
+    Ok(names.iter().any(|(permission, attribute)| {
+        permission == permission_name && attribute == attribute_name
+    }))
+}
+
+/// (permission_name, attribute_name) the user holds, from the cache when it is enabled
+pub async fn get_user_permissions<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    config: &Config,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let ttl = permission_cache_ttl(config);
+    if ttl == 0 {
+        return get_user_permission_names(conn, user_id).await;
+    }
+    let cached = redis_conn.get(&permission_cache_key(user_id)).await?;
+    match cached.and_then(|x| serde_json::from_str(&x).ok()) {
+        Some(val) => Ok(val),
+        None => warm_user_permissions(conn, redis_conn, ttl, user_id).await,
+    }
+}
+
+/// Drop the cached permission set of users whose roles, groups or grants changed. With
+/// PERMISSION_CHANGE_REVOKES_SESSIONS their current tokens are rejected until refreshed.
+/// Call after the transaction is committed so the next lookup sees the change
+pub async fn invalidate_user_permissions<C: SessionStore>(
+    redis_conn: &mut C,
+    config: &Config,
+    user_ids: &[Uuid],
+) -> anyhow::Result<()> {
+    for user_id in user_ids {
+        redis_conn.del(&permission_cache_key(user_id)).await?;
+        if config.permission_change_revokes_sessions.unwrap_or(false) {
+            // jwt_exp is in minutes, longer than any session issued before
+            revoke_user_sessions(redis_conn, user_id, config.jwt_exp as u64 * 60).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Drop every cached permission set, for changes to a permission or attribute itself
+pub async fn invalidate_all_permissions<C: SessionStore>(
+    redis_conn: &mut C,
+) -> anyhow::Result<u64> {
+    let mut deleted = 0;
+    for key in scan_keys(redis_conn, &format!("{}*", PERMISSION_CACHE_PREFIX)).await? {
+        deleted += redis_conn.del(&key).await?;
+    }
+    Ok(deleted)
+}
+
+/// Hook for handlers after committing a change to the roles, groups or grants of users.
+/// Failures are only logged, the cached sets expire after PERMISSION_CACHE_TTL anyway
+pub async fn permissions_changed<C: SessionStore>(redis_conn: &mut C, user_ids: &[Uuid]) {
+    if let Err(err) = invalidate_user_permissions(redis_conn, &get_config(), user_ids).await {
+        tracing::warn!(
+            "error: on core::permission_cache::permissions_changed error: {}",
+            err
+        );
+    }
+}
+
+/// Hook for logins that changed the roles or groups of the user logging in, e.g. sso role
+/// mappings. Only drops the cached set, the session about to be issued has to stay valid
+pub async fn login_permissions_changed<C: SessionStore>(redis_conn: &mut C, user_id: &Uuid) {
+    if let Err(err) = redis_conn.del(&permission_cache_key(user_id)).await {
+        tracing::warn!(
+            "error: on core::permission_cache::login_permissions_changed error: {}",
+            err
+        );
+    }
+}
+
+/// Hook for handlers after committing a change to a permission or attribute, e.g. a rename
+pub async fn all_permissions_changed<C: SessionStore>(redis_conn: &mut C) {
+    if let Err(err) = invalidate_all_permissions(redis_conn).await {
+        tracing::warn!(
+            "error: on core::permission_cache::all_permissions_changed error: {}",
+            err
+        );
+    }
+}
+
+/// Rebuild of the cached permission sets, kept in the session store under its id
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PermissionCacheRebuild {
+    pub id: Uuid,
+    pub status: String,
+    pub total: u64,
+    pub done: u64,
+    pub failed: u64,
+    pub error: Option<String>,
+    pub created_date: DateTime<FixedOffset>,
+    pub finished_date: Option<DateTime<FixedOffset>>,
+}
+
+impl PermissionCacheRebuild {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            status: REBUILD_STATUS_RUNNING.to_string(),
+            total: 0,
+            done: 0,
+            failed: 0,
+            error: None,
+            created_date: utc_now(),
+            finished_date: None,
+        }
+    }
+}
+
+impl Default for PermissionCacheRebuild {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn save_permission_cache_rebuild<C: SessionStore>(
+    redis_conn: &mut C,
+    rebuild: &PermissionCacheRebuild,
+) -> anyhow::Result<()> {
+    redis_conn
+        .set_ex(
+            &format!("{}{}", REBUILD_PREFIX, rebuild.id),
+            &serde_json::to_string(rebuild)?,
+            REBUILD_TTL,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_permission_cache_rebuild<C: SessionStore>(
+    redis_conn: &mut C,
+    id: &Uuid,
+) -> anyhow::Result<Option<PermissionCacheRebuild>> {
+    let res = redis_conn.get(&format!("{}{}", REBUILD_PREFIX, id)).await?;
+    match res {
+        Some(val) => Ok(Some(serde_json::from_str(&val)?)),
+        None => Ok(None),
+    }
+}
+
+/// Recompute and cache the permission sets of the users, every active user when None.
+/// A user that fails is counted and skipped, progress is saved along the way
+pub async fn rebuild_permission_cache(
+    pool: &PgPool,
+    session_pool: &SessionPool,
+    config: &Config,
+    user_ids: Option<Vec<Uuid>>,
+    rebuild: &mut PermissionCacheRebuild,
+) -> anyhow::Result<()> {
+    let ttl = permission_cache_ttl(config);
+    let mut redis_conn = session_pool.get().await?;
+    let user_ids = match user_ids {
+        Some(val) => val,
+        None => get_active_user_ids(&mut *pool.acquire().await?).await?,
+    };
+    rebuild.total = user_ids.len() as u64;
+    save_permission_cache_rebuild(&mut redis_conn, rebuild).await?;
+    for user_id in user_ids {
+        // only reads, a failing user leaves nothing to roll back for the rest
+        let mut conn = pool.acquire().await?;
+        if let Err(err) = warm_user_permissions(&mut conn, &mut redis_conn, ttl, &user_id).await {
+            tracing::warn!(
+                "error: on core::permission_cache::rebuild_permission_cache user {} error: {}",
+                user_id,
+                err
+            );
+            rebuild.failed += 1;
+        }
+        rebuild.done += 1;
+        if rebuild.done.is_multiple_of(REBUILD_SAVE_EVERY) {
+            save_permission_cache_rebuild(&mut redis_conn, rebuild).await?;
+        }
+    }
+    rebuild.status = REBUILD_STATUS_DONE.to_string();
+    rebuild.finished_date = Some(utc_now());
+    save_permission_cache_rebuild(&mut redis_conn, rebuild).await?;
+    Ok(())
+}
+
+/// Rebuild in the background, the caller polls the progress by rebuild.id
+pub fn spawn_permission_cache_rebuild(
+    pool: PgPool,
+    session_pool: SessionPool,
+    config: Config,
+    user_ids: Option<Vec<Uuid>>,
+    mut rebuild: PermissionCacheRebuild,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let res =
+            rebuild_permission_cache(&pool, &session_pool, &config, user_ids, &mut rebuild).await;
+        if let Err(err) = res {
+            tracing::error!(
+                "error: on core::permission_cache::spawn_permission_cache_rebuild error: {}",
+                err
+            );
+            rebuild.status = REBUILD_STATUS_FAILED.to_string();
+            rebuild.error = Some(err.to_string());
+            rebuild.finished_date = Some(utc_now());
+            if let Ok(mut redis_conn) = session_pool.get().await {
+                let _ = save_permission_cache_rebuild(&mut redis_conn, &rebuild).await;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            permission_cache::{
+                has_permission, invalidate_all_permissions, invalidate_user_permissions,
+            },
+            session::{add_session, get_session},
+            session_store::MemoryStore,
+        },
+        factory::{
+            permission::PermissionFactory, permission_attribute::PermissionAttributeFactory,
+            user::UserFactory,
+        },
+        model::user_permission::UserPermission,
+        repository::user_permission::create_user_permission,
+        settings::get_config,
+    };
+
+    #[sqlx::test]
+    async fn test_invalidate_user_permissions(pool: PgPool) -> anyhow::Result<()> {
+        // Given a user without invoice read whose permission set is cached
+        let mut config = get_config();
+        config.permission_cache_ttl = Some(60);
+        config.permission_change_revokes_sessions = Some(true);
+        let mut redis_conn = MemoryStore::new();
+        let mut tx = pool.begin().await?;
+        let read = PermissionAttributeFactory::new()
+            .name("read")
+            .create(&mut tx)
+            .await?;
+        let permission = PermissionFactory::new()
+            .permission_name("invoice")
+            .with_attribute(&read)
+            .create(&mut tx)
+            .await?;
+        let user = UserFactory::new()
+            .user_name("alice")
+            .create(&mut tx)
+            .await?;
+        tx.commit().await?;
+        add_session(
+            &mut redis_conn,
+            &user,
+            &config,
+            "token".to_string(),
+            "refresh".to_string(),
+        )
+        .await?;
+        let mut tx = pool.begin().await?;
+        assert!(
+            !has_permission(
+                &mut tx,
+                &mut redis_conn,
+                &config,
+                &user.id,
+                "invoice",
+                "read"
+            )
+            .await?
+        );
+
+        // When granted without invalidating
+        create_user_permission(
+            &mut tx,
+            &UserPermission {
+                user_id: user.id,
+                permission_id: permission.id,
+                attribute_id: read.id,
+                created_by: None,
+                updated_by: None,
+                created_date: None,
+                updated_date: None,
+                expires_at: None,
+            },
+        )
+        .await?;
+
+        // Expect the cached set is still used
+        assert!(
+            !has_permission(
+                &mut tx,
+                &mut redis_conn,
+                &config,
+                &user.id,
+                "invoice",
+                "read"
+            )
+            .await?
+        );
+
+        // When invalidated
+        invalidate_user_permissions(&mut redis_conn, &config, &[user.id]).await?;
+
+        // Expect the grant is seen and the session has to be refreshed
+        assert!(
+            has_permission(
+                &mut tx,
+                &mut redis_conn,
+                &config,
+                &user.id,
+                "invoice",
+                "read"
+            )
+            .await?
+        );
+        assert!(get_session(&mut redis_conn, "token".to_string())
+            .await?
+            .is_none());
+
+        // Expect the cached set is dropped with every other one
+        assert_eq!(invalidate_all_permissions(&mut redis_conn).await?, 1);
+        Ok(())
+    }
+}
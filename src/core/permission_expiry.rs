@@ -0,0 +1,120 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, FixedOffset, Local};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use crate::repository::user_permission::delete_expired_user_permissions;
+
+/// Remove temporary direct grants whose expires_at has passed, returns the number removed.
+/// They already grant nothing, this keeps listings and exports clean.
+pub async fn remove_expired_user_permissions(
+    pool: &PgPool,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<u64> {
+    let now = now.unwrap_or(Local::now().fixed_offset());
+    let mut tx = pool.begin().await?;
+    let removed = delete_expired_user_permissions(&mut tx, &now).await?;
+    tx.commit().await?;
+    Ok(removed)
+}
+
+pub fn spawn_permission_expiry_worker(pool: PgPool, interval: StdDuration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match remove_expired_user_permissions(&pool, None).await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("removed {} expired user permissions", removed),
+                Err(err) => tracing::error!(
+                    "error: on core::permission_expiry::spawn_permission_expiry_worker error: {}",
+                    err
+                ),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+    use sqlx::PgPool;
+
+    use crate::{
+        core::permission_expiry::remove_expired_user_permissions,
+        factory::{
+            permission::PermissionFactory, permission_attribute::PermissionAttributeFactory,
+            user::UserFactory,
+        },
+        model::user_permission::TABLE_NAME,
+        repository::user_permission::user_has_permission,
+    };
+
+    #[sqlx::test]
+    async fn test_remove_expired_user_permissions(pool: PgPool) -> anyhow::Result<()> {
+        // Given a user granted read until an hour ago and write until tomorrow
+        let now = Local::now().fixed_offset();
+        let mut tx = pool.begin().await?;
+        let read = PermissionAttributeFactory::new()
+            .name("read")
+            .create(&mut tx)
+            .await?;
+        let write = PermissionAttributeFactory::new()
+            .name("write")
+            .create(&mut tx)
+            .await?;
+        let permission = PermissionFactory::new()
+            .permission_name("invoice")
+            .with_attribute(&read)
+            .with_attribute(&write)
+            .create(&mut tx)
+            .await?;
+        let user = UserFactory::new()
+            .user_name("temp_user")
+            .with_permission(&permission, &read)
+            .with_permission(&permission, &write)
+            .create(&mut tx)
+            .await?;
+        for (attribute, expires_at) in [
+            (&read, now - Duration::hours(1)),
+            (&write, now + Duration::days(1)),
+        ] {
+            sqlx::query(
+                format!(
+                    "UPDATE {} SET expires_at = $1 WHERE user_id = $2 AND attribute_id = $3",
+                    TABLE_NAME
+                )
+                .as_str(),
+            )
+            .bind(expires_at)
+            .bind(user.id)
+            .bind(attribute.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        // Expect the expired grant ignored before cleanup
+        let mut tx = pool.begin().await?;
+        assert!(!user_has_permission(&mut tx, &user.id, "invoice", "read").await?);
+        assert!(user_has_permission(&mut tx, &user.id, "invoice", "write").await?);
+        tx.commit().await?;
+
+        // When
+        let first = remove_expired_user_permissions(&pool, Some(now)).await?;
+        let second = remove_expired_user_permissions(&pool, Some(now)).await?;
+
+        // Expect only the expired grant removed, once
+        assert_eq!(first, 1);
+        assert_eq!(second, 0);
+        let remaining: (i64,) = sqlx::query_as(
+            format!("SELECT count(*) FROM {} WHERE user_id = $1", TABLE_NAME).as_str(),
+        )
+        .bind(user.id)
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(remaining.0, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use uuid::Uuid;
+
+use crate::{
+    model::{permission::Permission, permission_attribute::PermissionAttribute},
+    repository::rbac::RbacRepository,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GrantTargetError {
+    #[error("permission with id {0} not found")]
+    PermissionNotFound(String),
+    #[error("attribute with id {0} not found")]
+    AttributeNotFound(String),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+/// Permission and attribute a user, role or group grant refers to, ids as sent by the
+/// client. Invalid ids are reported as not found
+pub async fn resolve_grant_target<R: RbacRepository>(
+    repo: &mut R,
+    permission_id: &str,
+    attribute_id: &str,
+) -> Result<(Permission, PermissionAttribute), GrantTargetError> {
+    let permission = match Uuid::parse_str(permission_id) {
+        Ok(id) => repo.get_permission_by_id(&id).await?,
+        Err(_) => None,
+    }
+    .ok_or(GrantTargetError::PermissionNotFound(
+        permission_id.to_string(),
+    ))?;
+    let attribute = match Uuid::parse_str(attribute_id) {
+        Ok(id) => repo.get_permission_attribute_by_id(&id).await?,
+        Err(_) => None,
+    }
+    .ok_or(GrantTargetError::AttributeNotFound(
+        attribute_id.to_string(),
+    ))?;
+    Ok((permission, attribute))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        core::permission_grant::{resolve_grant_target, GrantTargetError},
+        model::{permission::Permission, permission_attribute::PermissionAttribute},
+        repository::memory::MemoryRepository,
+    };
+
+    #[tokio::test]
+    async fn test_resolve_grant_target() -> anyhow::Result<()> {
+        // Given
+        let mut repo = MemoryRepository::new();
+        let permission = Permission {
+            id: Uuid::now_v7(),
+            permission_name: "report".to_string(),
+            is_user: Some(true),
+            is_role: Some(true),
+            is_group: Some(true),
+            description: None,
+            created_by: None,
+            updated_by: None,
+            created_date: None,
+            updated_date: None,
+        };
+        let attribute = PermissionAttribute {
+            id: Uuid::now_v7(),
+            name: "read".to_string(),
+            description: None,
+            category: None,
+            created_date: None,
+            updated_date: None,
+            deleted_date: None,
+        };
+        repo.permissions.insert(permission.id, permission.clone());
+        repo.permission_attributes
+            .insert(attribute.id, attribute.clone());
+        let permission_id = permission.id.to_string();
+        let attribute_id = attribute.id.to_string();
+
+        // Expect both found
+        let (found_permission, found_attribute) =
+            resolve_grant_target(&mut repo, &permission_id, &attribute_id).await?;
+        assert_eq!(found_permission.id, permission.id);
+        assert_eq!(found_attribute.id, attribute.id);
+
+        // Expect unknown and invalid ids reported as not found
+        let unknown_id = Uuid::now_v7().to_string();
+        let err = resolve_grant_target(&mut repo, &unknown_id, &attribute_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GrantTargetError::PermissionNotFound(_)));
+        assert_eq!(
+            err.to_string(),
+            format!("permission with id {} not found", unknown_id)
+        );
+        let err = resolve_grant_target(&mut repo, &permission_id, "read")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "attribute with id read not found");
+        Ok(())
+    }
+}
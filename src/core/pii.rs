@@ -0,0 +1,271 @@
+use std::sync::OnceLock;
+
+use aes_gcm::{Aes256Gcm, Key};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    core::secrets::{decrypt_secret, encrypt_secret, is_encrypted, parse_master_key},
+    model::{
+        user_contact::TABLE_NAME as USER_CONTACT_TABLE_NAME,
+        user_profile::{UserProfile, TABLE_NAME as USER_PROFILE_TABLE_NAME},
+    },
+    settings::{try_get_config, Config},
+};
+
+/// Keys encrypting personal data columns at rest. Values are written with the
+/// current key, previous keys only decrypt rows not yet re-encrypted.
+#[derive(Clone)]
+pub struct PiiKeys {
+    current: Key<Aes256Gcm>,
+    previous: Vec<Key<Aes256Gcm>>,
+}
+
+impl PiiKeys {
+    pub fn new(current: Key<Aes256Gcm>, previous: Vec<Key<Aes256Gcm>>) -> Self {
+        Self { current, previous }
+    }
+
+    /// PII_ENCRYPTION_KEY and comma separated PII_ENCRYPTION_PREVIOUS_KEYS,
+    /// none when encryption is disabled
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let current = match &config.pii_encryption_key {
+            Some(val) if !val.trim().is_empty() => match parse_master_key(val) {
+                Ok(val) => val,
+                Err(err) => anyhow::bail!("PII_ENCRYPTION_KEY {}", err),
+            },
+            _ => return Ok(None),
+        };
+        let mut previous = vec![];
+        for val in config
+            .pii_encryption_previous_keys
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+        {
+            match parse_master_key(val) {
+                Ok(val) => previous.push(val),
+                Err(err) => anyhow::bail!("PII_ENCRYPTION_PREVIOUS_KEYS {}", err),
+            }
+        }
+        Ok(Some(Self::new(current, previous)))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> anyhow::Result<String> {
+        encrypt_secret(&self.current, plaintext)
+    }
+
+    /// Plaintext values written before encryption was enabled are returned as is
+    pub fn decrypt(&self, value: &str) -> anyhow::Result<String> {
+        if !is_encrypted(value) {
+            return Ok(value.to_string());
+        }
+        for key in std::iter::once(&self.current).chain(self.previous.iter()) {
+            if let Ok(val) = decrypt_secret(key, value) {
+                return Ok(val);
+            }
+        }
+        anyhow::bail!("failed to decrypt personal data, unknown PII_ENCRYPTION_KEY")
+    }
+}
+
+static PII_KEYS: OnceLock<Option<PiiKeys>> = OnceLock::new();
+
+/// Set the process wide keys, false when they were already loaded
+pub fn init_pii_keys(keys: Option<PiiKeys>) -> bool {
+    PII_KEYS.set(keys).is_ok()
+}
+
+/// Process wide keys, loaded from settings on first use unless initialized
+pub fn pii_keys() -> Option<&'static PiiKeys> {
+    PII_KEYS
+        .get_or_init(|| match try_get_config() {
+            Ok(config) => PiiKeys::from_config(&config).expect("invalid PII encryption keys"),
+            Err(_) => None,
+        })
+        .as_ref()
+}
+
+pub fn encrypt_pii(value: &Option<String>) -> anyhow::Result<Option<String>> {
+    match (value, pii_keys()) {
+        (Some(val), Some(keys)) => Ok(Some(keys.encrypt(val)?)),
+        _ => Ok(value.clone()),
+    }
+}
+
+pub fn decrypt_pii(value: Option<String>) -> anyhow::Result<Option<String>> {
+    match (value, pii_keys()) {
+        (Some(val), Some(keys)) => Ok(Some(keys.decrypt(&val)?)),
+        (value, _) => Ok(value),
+    }
+}
+
+/// Profile with its encrypted columns (email, address) as stored on database
+pub fn encrypt_user_profile(user_profile: &UserProfile) -> anyhow::Result<UserProfile> {
+    let mut res = user_profile.clone();
+    res.email = encrypt_pii(&user_profile.email)?;
+    res.address = encrypt_pii(&user_profile.address)?;
+    Ok(res)
+}
+
+pub fn decrypt_user_profile(mut user_profile: UserProfile) -> anyhow::Result<UserProfile> {
+    user_profile.email = decrypt_pii(user_profile.email)?;
+    user_profile.address = decrypt_pii(user_profile.address)?;
+    Ok(user_profile)
+}
+
+/// Rewrite every profile with the current key, plaintext rows included,
+/// return number of rewritten profiles
+pub async fn reencrypt_user_profiles(pool: &PgPool, keys: &PiiKeys) -> anyhow::Result<u64> {
+    let mut tx = pool.begin().await?;
+    let rows: Vec<(Uuid, Option<String>, Option<String>)> = sqlx::query_as(
+        format!(
+            r#"SELECT id, email, address FROM {}
+            WHERE email IS NOT NULL OR address IS NOT NULL
+            FOR UPDATE"#,
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut count = 0;
+    for (id, email, address) in rows {
+        let mut values = vec![];
+        for value in [email, address] {
+            values.push(match value {
+                Some(val) => Some(keys.encrypt(&keys.decrypt(&val)?)?),
+                None => None,
+            });
+        }
+        sqlx::query(
+            format!(
+                "UPDATE {} SET email = $1, address = $2 WHERE id = $3",
+                USER_PROFILE_TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(&values[0])
+        .bind(&values[1])
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        count += 1;
+    }
+    tx.commit().await?;
+    Ok(count)
+}
+
+/// Re-encrypt every user contact value with the current key, return number of rows
+pub async fn reencrypt_user_contacts(pool: &PgPool, keys: &PiiKeys) -> anyhow::Result<u64> {
+    let mut tx = pool.begin().await?;
+    let rows: Vec<(Uuid, String)> = sqlx::query_as(
+        format!(
+            "SELECT id, value FROM {} FOR UPDATE",
+            USER_CONTACT_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut count = 0;
+    for (id, value) in rows {
+        sqlx::query(
+            format!(
+                "UPDATE {} SET value = $1 WHERE id = $2",
+                USER_CONTACT_TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(keys.encrypt(&keys.decrypt(&value)?)?)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        count += 1;
+    }
+    tx.commit().await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::core::{
+        pii::{reencrypt_user_profiles, PiiKeys},
+        secrets::{generate_master_key, is_encrypted, parse_master_key},
+    };
+
+    #[test]
+    fn test_pii_keys_decrypt_with_previous_key() {
+        // Given
+        let old_key = parse_master_key(&generate_master_key()).unwrap();
+        let new_key = parse_master_key(&generate_master_key()).unwrap();
+        let old = PiiKeys::new(old_key, vec![]);
+        let rotated = PiiKeys::new(new_key, vec![old_key]);
+
+        // When
+        let encrypted = old.encrypt("jane@example.com").unwrap();
+
+        // Expect
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(rotated.decrypt(&encrypted).unwrap(), "jane@example.com");
+        assert_eq!(rotated.decrypt("plain").unwrap(), "plain");
+        assert!(PiiKeys::new(new_key, vec![]).decrypt(&encrypted).is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_reencrypt_user_profiles(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let old_key = parse_master_key(&generate_master_key()).unwrap();
+        let new_key = parse_master_key(&generate_master_key()).unwrap();
+        let old = PiiKeys::new(old_key, vec![]);
+        for (email, address) in [
+            (Some(old.encrypt("jane@example.com")?), None),
+            (
+                Some("john@example.com".to_string()),
+                Some("Main st".to_string()),
+            ),
+        ] {
+            let id = Uuid::now_v7();
+            sqlx::query(
+                r#"INSERT INTO public.user (id, user_name, password) VALUES ($1, $2, 'x')"#,
+            )
+            .bind(id)
+            .bind(id.to_string())
+            .execute(&pool)
+            .await?;
+            sqlx::query(
+                r#"INSERT INTO public.user_profile (id, user_id, email, address)
+                VALUES ($1, $1, $2, $3)"#,
+            )
+            .bind(id)
+            .bind(email)
+            .bind(address)
+            .execute(&pool)
+            .await?;
+        }
+
+        // When
+        let keys = PiiKeys::new(new_key, vec![old_key]);
+        let count = reencrypt_user_profiles(&pool, &keys).await?;
+
+        // Expect every value readable with the new key alone
+        assert_eq!(count, 2);
+        let new = PiiKeys::new(new_key, vec![]);
+        let rows: Vec<(Option<String>,)> =
+            sqlx::query_as("SELECT email FROM public.user_profile ORDER BY email")
+                .fetch_all(&pool)
+                .await?;
+        let mut emails: Vec<String> = rows
+            .into_iter()
+            .map(|(email,)| new.decrypt(&email.unwrap()).unwrap())
+            .collect();
+        emails.sort();
+        assert_eq!(emails, vec!["jane@example.com", "john@example.com"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use serde_json::Value;
+
+pub const MAX_KEY_LENGTH: usize = 100;
+/// Limit of one serialized value
+pub const MAX_VALUE_BYTES: usize = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreferenceType {
+    Bool,
+    Integer { min: i64, max: i64 },
+    OneOf(&'static [&'static str]),
+}
+
+/// Preferences shared by every application. Other keys must be namespaced, e.g.
+/// myapp.sidebar, and take any json.
+pub const KNOWN_PREFERENCES: &[(&str, PreferenceType)] = &[
+    ("theme", PreferenceType::OneOf(&["light", "dark", "system"])),
+    (
+        "date_format",
+        PreferenceType::OneOf(&["YYYY-MM-DD", "DD/MM/YYYY", "MM/DD/YYYY"]),
+    ),
+    ("page_size", PreferenceType::Integer { min: 1, max: 100 }),
+    ("email_notifications", PreferenceType::Bool),
+];
+
+pub fn known_preference_type(key: &str) -> Option<PreferenceType> {
+    KNOWN_PREFERENCES
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, kind)| *kind)
+}
+
+/// Return the reason the preference is rejected, None when valid
+pub fn check_preference(key: &str, value: &Value) -> Option<String> {
+    if key.is_empty()
+        || key.len() > MAX_KEY_LENGTH
+        || !key
+            .chars()
+            .all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || matches!(x, '_' | '-' | '.'))
+    {
+        return Some(format!(
+            "{}: key must be 1 to {} lowercase letters, digits, _, - or .",
+            key, MAX_KEY_LENGTH
+        ));
+    }
+    let kind = match known_preference_type(key) {
+        Some(val) => val,
+        None if key.contains('.') => {
+            if value.to_string().len() > MAX_VALUE_BYTES {
+                return Some(format!("{}: value exceeds {} bytes", key, MAX_VALUE_BYTES));
+            }
+            return None;
+        }
+        None => {
+            return Some(format!(
+                "{}: unknown preference, app specific keys must be namespaced e.g. myapp.{}",
+                key, key
+            ))
+        }
+    };
+    match kind {
+        PreferenceType::Bool if value.is_boolean() => None,
+        PreferenceType::Bool => Some(format!("{}: must be true or false", key)),
+        PreferenceType::Integer { min, max } => match value.as_i64() {
+            Some(val) if (min..=max).contains(&val) => None,
+            _ => Some(format!(
+                "{}: must be an integer from {} to {}",
+                key, min, max
+            )),
+        },
+        PreferenceType::OneOf(values) => match value.as_str() {
+            Some(val) if values.contains(&val) => None,
+            _ => Some(format!("{}: must be one of {}", key, values.join(", "))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::core::preference::check_preference;
+
+    #[test]
+    fn test_check_preference() {
+        assert!(check_preference("theme", &json!("dark")).is_none());
+        assert!(check_preference("theme", &json!("blue")).is_some());
+        assert!(check_preference("page_size", &json!(25)).is_none());
+        assert!(check_preference("page_size", &json!(500)).is_some());
+        assert!(check_preference("email_notifications", &json!("yes")).is_some());
+        assert!(check_preference("myapp.sidebar", &json!({"collapsed": true})).is_none());
+        assert!(check_preference("sidebar", &json!(true)).is_some());
+        assert!(check_preference("MyApp.Sidebar", &json!(true)).is_some());
+        assert!(check_preference("myapp.notes", &json!("x".repeat(20_000))).is_some());
+    }
+}
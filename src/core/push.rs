@@ -0,0 +1,435 @@
+use std::{future::Future, time::Duration as StdDuration};
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::{
+    core::{locale::UserLocale, notifications::render_notification, utils::utc_now},
+    model::{
+        notification_template::CHANNEL_PUSH,
+        user_device::{UserDevice, PLATFORM_APNS, PLATFORM_FCM},
+    },
+    repository::{
+        user::get_user_by_id,
+        user_device::{delete_user_device, get_user_devices_by_user},
+    },
+    settings::Config,
+};
+
+const SEND_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+pub const FCM_API_URL: &str = "https://fcm.googleapis.com";
+pub const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+pub const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+pub const APNS_API_URL: &str = "https://api.push.apple.com";
+pub const APNS_SANDBOX_API_URL: &str = "https://api.sandbox.push.apple.com";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OutgoingPush {
+    pub title: String,
+    pub body: String,
+    /// Event name passed to the app as data
+    pub event: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PushOutcome {
+    Delivered,
+    /// The app was uninstalled or the token rotated, the device should be removed
+    InvalidToken,
+}
+
+/// Delivers a push to one device token, implemented per platform so tests can record
+pub trait PushPublisher {
+    fn send(
+        &self,
+        token: &str,
+        push: &OutgoingPush,
+    ) -> impl Future<Output = anyhow::Result<PushOutcome>> + Send;
+}
+
+/// PEM keys in environment variables usually have escaped newlines
+fn pem_key(val: &str) -> String {
+    val.replace("\\n", "\n")
+}
+
+#[derive(Serialize)]
+struct ServiceAccountClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+}
+
+/// FCM HTTP v1 with a service account, an access token is requested per send
+pub struct FcmPublisher {
+    pub project_id: String,
+    pub client_email: String,
+    pub private_key: String,
+    pub api_url: String,
+    pub token_url: String,
+}
+
+impl FcmPublisher {
+    async fn access_token(&self, http: &reqwest::Client) -> anyhow::Result<String> {
+        let iat = utc_now().timestamp();
+        let assertion = encode(
+            &Header::new(Algorithm::RS256),
+            &ServiceAccountClaims {
+                iss: &self.client_email,
+                scope: FCM_SCOPE,
+                aud: &self.token_url,
+                iat,
+                exp: iat + 3600,
+            },
+            &EncodingKey::from_rsa_pem(self.private_key.as_bytes())?,
+        )?;
+        let resp = http
+            .post(&self.token_url)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("google token endpoint answered {}", resp.status());
+        }
+        let token: AccessTokenResponse = resp.json().await?;
+        Ok(token.access_token)
+    }
+}
+
+impl PushPublisher for FcmPublisher {
+    async fn send(&self, token: &str, push: &OutgoingPush) -> anyhow::Result<PushOutcome> {
+        let http = reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?;
+        let access_token = self.access_token(&http).await?;
+        let resp = http
+            .post(format!(
+                "{}/v1/projects/{}/messages:send",
+                self.api_url.trim_end_matches('/'),
+                self.project_id
+            ))
+            .bearer_auth(access_token)
+            .json(&json!({
+                "message": {
+                    "token": token,
+                    "notification": {"title": push.title, "body": push.body},
+                    "data": {"event": push.event},
+                }
+            }))
+            .send()
+            .await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(PushOutcome::Delivered);
+        }
+        let body = resp.text().await.unwrap_or_default();
+        if status == StatusCode::NOT_FOUND || body.contains("UNREGISTERED") {
+            return Ok(PushOutcome::InvalidToken);
+        }
+        anyhow::bail!("fcm answered {}: {}", status, body)
+    }
+}
+
+#[derive(Serialize)]
+struct ApnsClaims<'a> {
+    iss: &'a str,
+    iat: i64,
+}
+
+/// APNs with a .p8 signing key
+pub struct ApnsPublisher {
+    pub key_id: String,
+    pub team_id: String,
+    pub private_key: String,
+    /// App bundle id
+    pub topic: String,
+    pub api_url: String,
+}
+
+impl PushPublisher for ApnsPublisher {
+    async fn send(&self, token: &str, push: &OutgoingPush) -> anyhow::Result<PushOutcome> {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(self.key_id.clone());
+        let jwt = encode(
+            &header,
+            &ApnsClaims {
+                iss: &self.team_id,
+                iat: utc_now().timestamp(),
+            },
+            &EncodingKey::from_ec_pem(self.private_key.as_bytes())?,
+        )?;
+        let http = reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?;
+        let resp = http
+            .post(format!(
+                "{}/3/device/{}",
+                self.api_url.trim_end_matches('/'),
+                token
+            ))
+            .bearer_auth(jwt)
+            .header("apns-topic", &self.topic)
+            .header("apns-push-type", "alert")
+            .json(&json!({
+                "aps": {"alert": {"title": push.title, "body": push.body}},
+                "event": push.event,
+            }))
+            .send()
+            .await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(PushOutcome::Delivered);
+        }
+        let body = resp.text().await.unwrap_or_default();
+        if status == StatusCode::GONE || body.contains("BadDeviceToken") {
+            return Ok(PushOutcome::InvalidToken);
+        }
+        anyhow::bail!("apns answered {}: {}", status, body)
+    }
+}
+
+pub struct LogPublisher;
+
+impl PushPublisher for LogPublisher {
+    async fn send(&self, token: &str, push: &OutgoingPush) -> anyhow::Result<PushOutcome> {
+        let suffix: String = token.chars().rev().take(6).collect();
+        tracing::info!(
+            "push to ...{}: {}\n{}",
+            suffix.chars().rev().collect::<String>(),
+            push.title,
+            push.body
+        );
+        Ok(PushOutcome::Delivered)
+    }
+}
+
+/// Publishers per platform, platforms without settings are only logged
+pub struct ConfiguredPushPublisher {
+    pub fcm: Option<FcmPublisher>,
+    pub apns: Option<ApnsPublisher>,
+}
+
+impl ConfiguredPushPublisher {
+    /// FCM is enabled by PUSH_FCM_PROJECT_ID, APNs by PUSH_APNS_KEY_ID
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let required =
+            |name: &str, val: &Option<String>| match val.as_deref().filter(|x| !x.is_empty()) {
+                Some(val) => Ok(val.to_string()),
+                None => anyhow::bail!("{} is required", name),
+            };
+        let fcm = match config
+            .push_fcm_project_id
+            .as_deref()
+            .filter(|x| !x.is_empty())
+        {
+            Some(project_id) => Some(FcmPublisher {
+                project_id: project_id.to_string(),
+                client_email: required("PUSH_FCM_CLIENT_EMAIL", &config.push_fcm_client_email)?,
+                private_key: pem_key(&required(
+                    "PUSH_FCM_PRIVATE_KEY",
+                    &config.push_fcm_private_key,
+                )?),
+                api_url: FCM_API_URL.to_string(),
+                token_url: GOOGLE_TOKEN_URL.to_string(),
+            }),
+            None => None,
+        };
+        let apns = match config.push_apns_key_id.as_deref().filter(|x| !x.is_empty()) {
+            Some(key_id) => Some(ApnsPublisher {
+                key_id: key_id.to_string(),
+                team_id: required("PUSH_APNS_TEAM_ID", &config.push_apns_team_id)?,
+                private_key: pem_key(&required(
+                    "PUSH_APNS_PRIVATE_KEY",
+                    &config.push_apns_private_key,
+                )?),
+                topic: required("PUSH_APNS_TOPIC", &config.push_apns_topic)?,
+                api_url: match config.push_apns_sandbox.unwrap_or(false) {
+                    true => APNS_SANDBOX_API_URL.to_string(),
+                    false => APNS_API_URL.to_string(),
+                },
+            }),
+            None => None,
+        };
+        if let Some(fcm) = &fcm {
+            if let Err(err) = EncodingKey::from_rsa_pem(fcm.private_key.as_bytes()) {
+                anyhow::bail!("PUSH_FCM_PRIVATE_KEY {}", err);
+            }
+        }
+        if let Some(apns) = &apns {
+            if let Err(err) = EncodingKey::from_ec_pem(apns.private_key.as_bytes()) {
+                anyhow::bail!("PUSH_APNS_PRIVATE_KEY {}", err);
+            }
+        }
+        Ok(Self { fcm, apns })
+    }
+
+    pub async fn send(
+        &self,
+        device: &UserDevice,
+        push: &OutgoingPush,
+    ) -> anyhow::Result<PushOutcome> {
+        match (device.platform.as_str(), &self.fcm, &self.apns) {
+            (PLATFORM_FCM, Some(fcm), _) => fcm.send(&device.token, push).await,
+            (PLATFORM_APNS, _, Some(apns)) => apns.send(&device.token, push).await,
+            _ => LogPublisher.send(&device.token, push).await,
+        }
+    }
+}
+
+/// Render the push template of the event in the user locale and send it to every device
+/// of the user. Devices with an invalid token are removed. Returns the delivered count.
+pub async fn notify_user_devices(
+    pool: &PgPool,
+    config: &Config,
+    user_id: &Uuid,
+    event: &str,
+) -> anyhow::Result<usize> {
+    let mut tx = pool.begin().await?;
+    let devices = get_user_devices_by_user(&mut tx, user_id).await?;
+    if devices.is_empty() {
+        return Ok(0);
+    }
+    let (user, user_profile) = get_user_by_id(&mut tx, user_id, None).await?;
+    let user = match user {
+        Some(val) => val,
+        None => return Ok(0),
+    };
+    let locale = UserLocale::resolve(config, user_profile.as_ref());
+    let data: Value = json!({
+        "user_name": user.user_name,
+        "date": locale.format_datetime(utc_now()),
+    });
+    let rendered = render_notification(&mut tx, event, CHANNEL_PUSH, &locale, &data).await?;
+    let push = OutgoingPush {
+        title: rendered.subject,
+        body: rendered.body,
+        event: event.to_string(),
+    };
+    let publisher = ConfiguredPushPublisher::from_config(config)?;
+    let mut delivered = 0;
+    for device in devices.iter() {
+        match publisher.send(device, &push).await {
+            Ok(PushOutcome::Delivered) => delivered += 1,
+            Ok(PushOutcome::InvalidToken) => delete_user_device(&mut tx, &device.id).await?,
+            Err(err) => tracing::error!(
+                "error: on core::push sending {} to device {} error: {}",
+                event,
+                device.id,
+                err
+            ),
+        }
+    }
+    tx.commit().await?;
+    Ok(delivered)
+}
+
+/// Notify in the background so the request is not held up by the providers
+pub fn spawn_user_devices_notification(
+    pool: PgPool,
+    config: Config,
+    user_id: Uuid,
+    event: &'static str,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(err) = notify_user_devices(&pool, &config, &user_id, event).await {
+            tracing::error!(
+                "error: on core::push::spawn_user_devices_notification error: {}",
+                err
+            );
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::{
+        core::{
+            push::{notify_user_devices, ConfiguredPushPublisher},
+            session_store::create_redis_pool,
+            test_utils::generate_test_user,
+            utils::utc_now,
+        },
+        model::{
+            notification_template::EVENT_NEW_LOGIN,
+            user_device::{UserDevice, PLATFORM_FCM},
+        },
+        repository::user_device::upsert_user_device,
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_configured_push_publisher() {
+        // Given
+        let mut config = get_config();
+        config.push_fcm_project_id = None;
+        config.push_apns_key_id = None;
+
+        // Expect platforms without settings only logged
+        let publisher = ConfiguredPushPublisher::from_config(&config).unwrap();
+        assert!(publisher.fcm.is_none() && publisher.apns.is_none());
+
+        // Expect enabled platforms to need credentials
+        config.push_apns_key_id = Some("ABC123DEFG".to_string());
+        config.push_apns_team_id = None;
+        assert!(ConfiguredPushPublisher::from_config(&config).is_err());
+        config.push_apns_team_id = Some("DEF123GHIJ".to_string());
+        config.push_apns_topic = Some("com.example.core".to_string());
+        config.push_apns_private_key = Some("not a key".to_string());
+        assert!(ConfiguredPushPublisher::from_config(&config).is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_notify_user_devices(pool: PgPool) -> anyhow::Result<()> {
+        // Given a user with one device and push only logged
+        let mut config = get_config();
+        config.push_fcm_project_id = None;
+        config.push_apns_key_id = None;
+        let mut redis_conn = create_redis_pool(&config)?.get().await?;
+        let mut db = pool.acquire().await?;
+        let test_user = generate_test_user(
+            &mut db,
+            &mut redis_conn,
+            config.clone(),
+            "test_user",
+            "password",
+        )
+        .await?;
+        let now = utc_now();
+        let mut tx = pool.begin().await?;
+        upsert_user_device(
+            &mut tx,
+            &UserDevice {
+                id: Uuid::now_v7(),
+                user_id: test_user.user.id,
+                platform: PLATFORM_FCM.to_string(),
+                token: "fcm-token".to_string(),
+                name: None,
+                created_date: Some(now),
+                updated_date: Some(now),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        // When
+        let delivered =
+            notify_user_devices(&pool, &config, &test_user.user.id, EVENT_NEW_LOGIN).await?;
+
+        // Expect
+        assert_eq!(delivered, 1);
+        Ok(())
+    }
+}
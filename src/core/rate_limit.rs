@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use poem::{
+    http::{header, StatusCode},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Fixed window limit of requests per minute per client ip, kept in process
+/// memory so every instance counts on its own. `None` lets every request through
+pub struct RateLimit {
+    per_minute: Option<u32>,
+}
+
+impl RateLimit {
+    pub fn new(per_minute: Option<u32>) -> Self {
+        RateLimit { per_minute }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RateLimit {
+    type Output = RateLimitEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RateLimitEndpoint {
+            inner: ep,
+            per_minute: self.per_minute,
+            windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+pub struct RateLimitEndpoint<E> {
+    inner: E,
+    per_minute: Option<u32>,
+    windows: Arc<Mutex<HashMap<String, (Instant, u32)>>>,
+}
+
+impl<E> RateLimitEndpoint<E> {
+    /// Count the request, return seconds until the window resets when over the limit
+    fn hit(&self, client: String, per_minute: u32) -> Option<u64> {
+        let now = Instant::now();
+        let mut windows = match self.windows.lock() {
+            Ok(val) => val,
+            Err(err) => err.into_inner(),
+        };
+        // Drop expired windows so idle clients don't pile up
+        if windows.len() > 10_000 {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        }
+        let (start, count) = windows.entry(client).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= per_minute {
+            let remaining = WINDOW.saturating_sub(now.duration_since(*start));
+            return Some((remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)).max(1));
+        }
+        *count += 1;
+        None
+    }
+}
+
+impl<E: Endpoint> Endpoint for RateLimitEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        if let Some(per_minute) = self.per_minute {
+            let client = match req.remote_addr().as_socket_addr() {
+                Some(val) => val.ip().to_string(),
+                None => req.remote_addr().to_string(),
+            };
+            if let Some(retry_after) = self.hit(client, per_minute) {
+                return Ok(Response::builder()
+                    .status(StatusCode::TOO_MANY_REQUESTS)
+                    .header(header::RETRY_AFTER, retry_after.to_string())
+                    .body("too many requests"));
+            }
+        }
+        match self.inner.call(req).await {
+            Ok(val) => Ok(val.into_response()),
+            Err(err) => Ok(err.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{handler, http::StatusCode, test::TestClient, EndpointExt, Route};
+
+    use crate::core::rate_limit::RateLimit;
+
+    #[handler]
+    fn index() -> &'static str {
+        "ok"
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        // Given
+        let cli = TestClient::new(Route::new().at("/", index).with(RateLimit::new(Some(2))));
+
+        // When
+        let first = cli.get("/").send().await;
+        let second = cli.get("/").send().await;
+        let third = cli.get("/").send().await;
+
+        // Expect
+        first.assert_status_is_ok();
+        second.assert_status_is_ok();
+        third.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        third.assert_header("Retry-After", "60");
+
+        // Expect no limit when disabled
+        let cli = TestClient::new(Route::new().at("/", index).with(RateLimit::new(None)));
+        for _ in 0..5 {
+            cli.get("/").send().await.assert_status_is_ok();
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::{DateTime, FixedOffset};
+use rand::{rngs::OsRng, seq::SliceRandom};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::security::{hash_password, verify_hash_password},
+    model::user_recovery_code::RECOVERY_CODE_COUNT,
+    repository::user_recovery_code::{
+        get_unused_user_recovery_codes, replace_user_recovery_codes, use_user_recovery_code,
+    },
+};
+
+/// Lowercase letters and digits without the look alike 0, 1, i, l and o
+const RECOVERY_CODE_CHARS: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Random code formatted as xxxxx-xxxxx
+pub fn generate_recovery_code() -> String {
+    let code: String = (0..RECOVERY_CODE_LENGTH)
+        .filter_map(|_| RECOVERY_CODE_CHARS.choose(&mut OsRng))
+        .map(|x| *x as char)
+        .collect();
+    format!(
+        "{}-{}",
+        &code[..RECOVERY_CODE_LENGTH / 2],
+        &code[RECOVERY_CODE_LENGTH / 2..]
+    )
+}
+
+/// Codes are accepted regardless of case, dashes and spaces
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|x| !x.is_whitespace() && *x != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Issue a new set of codes for the user, the previous set stops working.
+/// Return the plain codes, they cannot be shown again
+pub async fn generate_user_recovery_codes(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<Vec<String>> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let mut code_hashes = vec![];
+    for code in codes.iter() {
+        code_hashes.push(
+            hash_password(&normalize_recovery_code(code)).map_err(|err| anyhow::anyhow!(err))?,
+        );
+    }
+    replace_user_recovery_codes(tx, user_id, &code_hashes, now).await?;
+    Ok(codes)
+}
+
+/// Unused code of the user matching the code, left unused so a login rejected later on
+/// does not burn it
+pub async fn find_recovery_code(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    code: &str,
+) -> anyhow::Result<Option<Uuid>> {
+    let code = normalize_recovery_code(code);
+    if code.chars().count() != RECOVERY_CODE_LENGTH {
+        return Ok(None);
+    }
+    for item in get_unused_user_recovery_codes(conn, user_id).await? {
+        if verify_hash_password(&code, &item.code_hash).map_err(|err| anyhow::anyhow!(err))? {
+            return Ok(Some(item.id));
+        }
+    }
+    Ok(None)
+}
+
+/// Check the code against the unused codes of the user and mark the match used
+pub async fn use_recovery_code(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    code: &str,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<bool> {
+    match find_recovery_code(tx, user_id, code).await? {
+        Some(id) => use_user_recovery_code(tx, &id, now).await,
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            recovery_code::{
+                generate_recovery_code, generate_user_recovery_codes, normalize_recovery_code,
+                use_recovery_code,
+            },
+            utils::utc_now,
+        },
+        factory::user::UserFactory,
+        model::user_recovery_code::RECOVERY_CODE_COUNT,
+        repository::user_recovery_code::count_unused_user_recovery_codes,
+    };
+
+    #[test]
+    fn test_generate_recovery_code() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(&code[5..6], "-");
+        assert_eq!(normalize_recovery_code(&code).len(), 10);
+        assert_eq!(normalize_recovery_code(" ABCDE-fghjk "), "abcdefghjk");
+    }
+
+    #[sqlx::test]
+    async fn test_use_recovery_code(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let mut tx = pool.begin().await?;
+        let user = UserFactory::new()
+            .user_name("alice")
+            .create(&mut tx)
+            .await?;
+        let now = utc_now();
+        let old_codes = generate_user_recovery_codes(&mut tx, &user.id, &now).await?;
+        let codes = generate_user_recovery_codes(&mut tx, &user.id, &now).await?;
+
+        // Expect only the latest set to work, each code once
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(!use_recovery_code(&mut tx, &user.id, &old_codes[0], &now).await?);
+        assert!(use_recovery_code(&mut tx, &user.id, &codes[0].to_uppercase(), &now).await?);
+        assert!(!use_recovery_code(&mut tx, &user.id, &codes[0], &now).await?);
+        assert!(!use_recovery_code(&mut tx, &user.id, "abcde-fghjk", &now).await?);
+        assert_eq!(
+            count_unused_user_recovery_codes(&mut tx, &user.id).await?,
+            RECOVERY_CODE_COUNT as i64 - 1
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use std::cell::Cell;
+
+use chrono_tz::Tz;
+use poem::{http::HeaderValue, Endpoint, IntoResponse, Middleware, Request, Response, Result};
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longer incoming ids are replaced, the id ends up in every log line of the request
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+struct RequestContext {
+    id: String,
+    debug_errors: bool,
+    /// Set once the user of the request is known
+    timezone: Cell<Option<Tz>>,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+/// Id of the request being handled, None outside of a request e.g. in the cli
+pub fn current_request_id() -> Option<String> {
+    REQUEST.try_with(|x| x.id.clone()).ok()
+}
+
+/// Whether 500 responses of the request being handled carry the error detail,
+/// false outside of a request
+pub fn debug_errors() -> bool {
+    REQUEST.try_with(|x| x.debug_errors).unwrap_or(false)
+}
+
+/// Timezone of the user of the request being handled, None before the user is known
+/// and outside of a request
+pub fn request_timezone() -> Option<Tz> {
+    REQUEST.try_with(|x| x.timezone.get()).ok().flatten()
+}
+
+/// Format the timestamps of the response of the request being handled in the timezone
+/// of its user, see core::utils::datetime_to_string. Ignored outside of a request
+pub fn set_request_timezone(timezone: Tz) {
+    let _ = REQUEST.try_with(|x| x.timezone.set(Some(timezone)));
+}
+
+/// The incoming id when it is a printable ascii value, a new uuid otherwise
+fn request_id(req: &Request) -> String {
+    req.header(REQUEST_ID_HEADER)
+        .map(str::trim)
+        .filter(|x| {
+            !x.is_empty()
+                && x.len() <= MAX_REQUEST_ID_LENGTH
+                && x.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::now_v7().to_string())
+}
+
+/// Take the X-Request-Id of the request or generate one, handle the request in a tracing
+/// span carrying it and return it as X-Request-Id. 500 bodies quote it so a reported error
+/// can be found in the logs, the error itself is only returned with `debug_errors`, see
+/// InternalServerErrorResponse
+pub struct RequestId {
+    debug_errors: bool,
+}
+
+impl RequestId {
+    pub fn new(debug_errors: bool) -> Self {
+        RequestId { debug_errors }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestId {
+    type Output = RequestIdEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestIdEndpoint {
+            inner: ep,
+            debug_errors: self.debug_errors,
+        }
+    }
+}
+
+pub struct RequestIdEndpoint<E> {
+    inner: E,
+    debug_errors: bool,
+}
+
+impl<E: Endpoint> Endpoint for RequestIdEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        let id = request_id(&req);
+        let span = tracing::info_span!("request", request_id = %id);
+        // Errors become responses inside the scope, their 500 body reads the id too
+        let context = RequestContext {
+            id: id.clone(),
+            debug_errors: self.debug_errors,
+            timezone: Cell::new(None),
+        };
+        let mut resp = REQUEST
+            .scope(
+                context,
+                async {
+                    match self.inner.call(req).await {
+                        Ok(val) => val.into_response(),
+                        Err(err) => err.into_response(),
+                    }
+                }
+                .instrument(span),
+            )
+            .await;
+        if let Ok(val) = HeaderValue::from_str(&id) {
+            resp.headers_mut().insert(REQUEST_ID_HEADER, val);
+        }
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{handler, http::StatusCode, test::TestClient, EndpointExt, Route};
+
+    use crate::core::{
+        error::AppError,
+        request_id::{current_request_id, RequestId},
+    };
+
+    #[handler]
+    fn index() -> String {
+        current_request_id().unwrap_or_default()
+    }
+
+    #[handler]
+    async fn failing() -> poem::Result<String> {
+        Err(AppError::from(anyhow::anyhow!("connection refused")).into())
+    }
+
+    #[tokio::test]
+    async fn test_request_id() {
+        // Given
+        let cli = TestClient::new(Route::new().at("/", index).with(RequestId::new(false)));
+
+        // When
+        let resp = cli.get("/").header("X-Request-Id", "abc-123").send().await;
+
+        // Expect the incoming id kept
+        resp.assert_status_is_ok();
+        resp.assert_header("X-Request-Id", "abc-123");
+        resp.assert_text("abc-123").await;
+
+        // When no or an invalid id
+        let resp = cli.get("/").send().await;
+        let invalid = cli.get("/").header("X-Request-Id", "a b").send().await;
+
+        // Expect a generated one, the same the handler sees
+        let id = resp.0.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(id.len(), 36);
+        resp.assert_text(id).await;
+        let id = invalid.0.headers()["x-request-id"].to_str().unwrap();
+        assert_ne!(id, "a b");
+        assert_eq!(current_request_id(), None);
+    }
+
+    #[tokio::test]
+    async fn test_debug_errors() {
+        // Given
+        let cli = TestClient::new(Route::new().at("/", failing).with(RequestId::new(false)));
+
+        // When
+        let resp = cli.get("/").header("X-Request-Id", "abc-123").send().await;
+
+        // Expect the error kept in the logs
+        resp.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        let json = resp.json().await;
+        let body = json.value().object();
+        body.get("message").assert_string("internal server error");
+        body.get("request_id").assert_string("abc-123");
+        assert!(body.get_opt("detail").is_none());
+
+        // When debug_errors
+        let cli = TestClient::new(Route::new().at("/", failing).with(RequestId::new(true)));
+        let resp = cli.get("/").send().await;
+
+        // Expect the error returned
+        let json = resp.json().await;
+        assert!(json
+            .value()
+            .object()
+            .get("detail")
+            .string()
+            .contains("connection refused"));
+    }
+}
@@ -0,0 +1,329 @@
+use std::{fmt, str::FromStr, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::model::{
+    group::TABLE_NAME as GROUP_TABLE_NAME, role::TABLE_NAME as ROLE_TABLE_NAME,
+    user::TABLE_NAME as USER_TABLE_NAME,
+};
+
+/// Audit columns are cleared instead of deleting the referencing row
+const AUDIT_COLUMNS: [&str; 3] = ["created_by", "updated_by", "deleted_by"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PurgeEntity {
+    User,
+    Role,
+    Group,
+}
+
+impl PurgeEntity {
+    pub fn all() -> Vec<PurgeEntity> {
+        vec![PurgeEntity::User, PurgeEntity::Role, PurgeEntity::Group]
+    }
+
+    fn table_name(&self) -> &'static str {
+        match self {
+            PurgeEntity::User => USER_TABLE_NAME,
+            PurgeEntity::Role => ROLE_TABLE_NAME,
+            PurgeEntity::Group => GROUP_TABLE_NAME,
+        }
+    }
+}
+
+impl FromStr for PurgeEntity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(PurgeEntity::User),
+            "role" => Ok(PurgeEntity::Role),
+            "group" => Ok(PurgeEntity::Group),
+            _ => anyhow::bail!("unknown entity {}, expected user, role or group", s),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PurgeSummary {
+    pub users: u64,
+    pub roles: u64,
+    pub groups: u64,
+    /// Rows of other tables removed together with the purged rows
+    pub dependents: u64,
+    /// created_by / updated_by / deleted_by references cleared
+    pub references_cleared: u64,
+}
+
+impl fmt::Display for PurgeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "users: {}, roles: {}, groups: {}, dependent rows: {}, references cleared: {}",
+            self.users, self.roles, self.groups, self.dependents, self.references_cleared
+        )
+    }
+}
+
+/// Parse retention period such as 90d, 12h or 2w
+pub fn parse_retention_period(value: &str) -> anyhow::Result<Duration> {
+    let value = value.trim();
+    if value.len() < 2 {
+        anyhow::bail!("invalid period {}, expected e.g. 90d", value);
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: i64 = match amount.parse() {
+        Ok(val) if val > 0 => val,
+        _ => anyhow::bail!("invalid period {}, expected e.g. 90d", value),
+    };
+    match unit {
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => anyhow::bail!("invalid period unit {}, expected h, d or w", unit),
+    }
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn quote_table(table_name: &str) -> String {
+    match table_name.split_once('.') {
+        Some((schema, table)) => format!("{}.{}", quote_ident(schema), quote_ident(table)),
+        None => quote_ident(table_name),
+    }
+}
+
+/// Hard delete soft deleted rows of one table, return (purged, dependents, references cleared).
+/// Foreign keys without ON DELETE action are resolved first: audit columns are set
+/// to NULL and other referencing rows (profiles, memberships) are deleted.
+async fn purge_table(
+    tx: &mut Transaction<'_, Postgres>,
+    table_name: &str,
+    cutoff: &DateTime<FixedOffset>,
+) -> anyhow::Result<(u64, u64, u64)> {
+    let table = quote_table(table_name);
+    let ids: Vec<(Uuid,)> = sqlx::query_as(
+        format!(
+            "SELECT id FROM {} WHERE deleted_date IS NOT NULL AND deleted_date < $1",
+            table
+        )
+        .as_str(),
+    )
+    .bind(cutoff)
+    .fetch_all(&mut **tx)
+    .await?;
+    if ids.is_empty() {
+        return Ok((0, 0, 0));
+    }
+    let ids: Vec<Uuid> = ids.into_iter().map(|x| x.0).collect();
+
+    // (schema, table, column, on delete action)
+    let references: Vec<(String, String, String, String)> = sqlx::query_as(
+        r#"SELECT ns.nspname::text, cl.relname::text, a.attname::text, c.confdeltype::text
+        FROM pg_constraint c
+        JOIN pg_class cl ON cl.oid = c.conrelid
+        JOIN pg_namespace ns ON ns.oid = cl.relnamespace
+        JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = c.conkey[1]
+        WHERE c.contype = 'f' AND c.confrelid = $1::regclass"#,
+    )
+    .bind(&table)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut dependents: u64 = 0;
+    let mut cleared: u64 = 0;
+    for (schema, name, column, action) in references.iter() {
+        let ref_table = format!("{}.{}", quote_ident(schema), quote_ident(name));
+        let column_ident = quote_ident(column);
+        let self_reference = ref_table == table;
+        let no_action = action == "a" || action == "r";
+        if self_reference && (AUDIT_COLUMNS.contains(&column.as_str()) || !no_action) {
+            // Keep surviving rows, e.g. child groups of a purged parent
+            cleared += sqlx::query(
+                format!(
+                    "UPDATE {} SET {} = NULL WHERE {} = ANY($1) AND NOT (id = ANY($1))",
+                    ref_table, column_ident, column_ident
+                )
+                .as_str(),
+            )
+            .bind(&ids)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        } else if !no_action {
+            continue;
+        } else if AUDIT_COLUMNS.contains(&column.as_str()) {
+            cleared += sqlx::query(
+                format!(
+                    "UPDATE {} SET {} = NULL WHERE {} = ANY($1)",
+                    ref_table, column_ident, column_ident
+                )
+                .as_str(),
+            )
+            .bind(&ids)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        } else {
+            dependents += sqlx::query(
+                format!("DELETE FROM {} WHERE {} = ANY($1)", ref_table, column_ident).as_str(),
+            )
+            .bind(&ids)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        }
+    }
+
+    let purged = sqlx::query(format!("DELETE FROM {} WHERE id = ANY($1)", table).as_str())
+        .bind(&ids)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+    Ok((purged, dependents, cleared))
+}
+
+/// Permanently remove rows soft deleted before now - older_than
+pub async fn purge_soft_deleted(
+    pool: &PgPool,
+    entities: &[PurgeEntity],
+    older_than: Duration,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<PurgeSummary> {
+    let now = now.unwrap_or(Local::now().fixed_offset());
+    let cutoff = now - older_than;
+    let mut summary = PurgeSummary::default();
+    let mut tx = pool.begin().await?;
+    for entity in entities.iter() {
+        let (purged, dependents, cleared) =
+            purge_table(&mut tx, entity.table_name(), &cutoff).await?;
+        match entity {
+            PurgeEntity::User => summary.users += purged,
+            PurgeEntity::Role => summary.roles += purged,
+            PurgeEntity::Group => summary.groups += purged,
+        }
+        summary.dependents += dependents;
+        summary.references_cleared += cleared;
+    }
+    tx.commit().await?;
+    Ok(summary)
+}
+
+pub fn spawn_retention_worker(
+    pool: PgPool,
+    interval: StdDuration,
+    older_than: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match purge_soft_deleted(&pool, &PurgeEntity::all(), older_than, None).await {
+                Ok(summary) => tracing::info!("retention purge {}", summary),
+                Err(err) => tracing::error!(
+                    "error: on core::retention::spawn_retention_worker error: {}",
+                    err
+                ),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::factory::{group::GroupFactory, role::RoleFactory, user::UserFactory};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_retention_period() {
+        assert_eq!(parse_retention_period("90d").unwrap(), Duration::days(90));
+        assert_eq!(parse_retention_period("12h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_retention_period("2w").unwrap(), Duration::weeks(2));
+        assert!(parse_retention_period("0d").is_err());
+        assert!(parse_retention_period("90").is_err());
+        assert!(parse_retention_period("d").is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_purge_soft_deleted(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let now = Local::now().fixed_offset();
+        let old_user = UserFactory::<()>::new().generate_one(&pool, ()).await?;
+        let recent_user = UserFactory::<()>::new().generate_one(&pool, ()).await?;
+        let live_user = UserFactory::<()>::new().generate_one(&pool, ()).await?;
+        let old_role = RoleFactory::<()>::new().generate_one(&pool, ()).await?;
+        let parent_group = GroupFactory::<()>::new().generate_one(&pool, ()).await?;
+        let child_group = GroupFactory::<()>::new().generate_one(&pool, ()).await?;
+        sqlx::query(r#"UPDATE public."group" SET parent_id = $1 WHERE id = $2"#)
+            .bind(parent_group.id)
+            .bind(child_group.id)
+            .execute(&pool)
+            .await?;
+        sqlx::query("UPDATE public.user SET created_by = $1 WHERE id = $2")
+            .bind(old_user.id)
+            .bind(live_user.id)
+            .execute(&pool)
+            .await?;
+        sqlx::query("INSERT INTO public.user_profile (id, user_id) VALUES ($1, $2)")
+            .bind(Uuid::now_v7())
+            .bind(old_user.id)
+            .execute(&pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO public.user_group_roles (id, user_id, group_id, role_id) VALUES ($1, $2, NULL, $3)",
+        )
+        .bind(Uuid::now_v7())
+        .bind(live_user.id)
+        .bind(old_role.id)
+        .execute(&pool)
+        .await?;
+        let soft_delete = |table: &'static str, id: Uuid, days: i64| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query(
+                    format!("UPDATE {} SET deleted_date = $1 WHERE id = $2", table).as_str(),
+                )
+                .bind(now - Duration::days(days))
+                .bind(id)
+                .execute(&pool)
+                .await
+            }
+        };
+        soft_delete(r#"public.user"#, old_user.id, 100).await?;
+        soft_delete(r#"public.user"#, recent_user.id, 10).await?;
+        soft_delete(r#"public.role"#, old_role.id, 100).await?;
+        soft_delete(r#"public."group""#, parent_group.id, 100).await?;
+
+        // When
+        let summary =
+            purge_soft_deleted(&pool, &PurgeEntity::all(), Duration::days(90), Some(now)).await?;
+
+        // Expect
+        assert_eq!(summary.users, 1);
+        assert_eq!(summary.roles, 1);
+        assert_eq!(summary.groups, 1);
+        assert_eq!(summary.dependents, 2);
+        assert_eq!(summary.references_cleared, 2);
+        let remaining: Vec<(Uuid, Option<Uuid>)> =
+            sqlx::query_as("SELECT id, created_by FROM public.user ORDER BY id")
+                .fetch_all(&pool)
+                .await?;
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.iter().all(|x| x.1.is_none()));
+        let child: (Option<Uuid>,) =
+            sqlx::query_as(r#"SELECT parent_id FROM public."group" WHERE id = $1"#)
+                .bind(child_group.id)
+                .fetch_one(&pool)
+                .await?;
+        assert_eq!(child.0, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::task::JoinHandle;
+
+use crate::{
+    model::{
+        scim_provisioning_event::{
+            ScimProvisioningEvent, EVENT_CREATE, EVENT_DEACTIVATE, EVENT_UPDATE, STATUS_FAILED,
+            STATUS_PENDING, STATUS_SUCCESS,
+        },
+        scim_target::ScimTarget,
+        scim_target_user::ScimTargetUser,
+        user::User,
+        user_profile::UserProfile,
+    },
+    repository::{
+        scim_provisioning_event::{
+            claim_due_scim_provisioning_event, get_scim_target_user,
+            update_scim_provisioning_event, upsert_scim_target_user,
+        },
+        scim_target::get_scim_target_by_id,
+        user::get_user_by_id,
+    },
+};
+
+pub const SCIM_USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const SCIM_PATCH_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+/// Timeout in seconds of each request to a scim target
+const SCIM_REQUEST_TIMEOUT: i64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScimEmail {
+    pub value: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    pub schemas: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub external_id: String,
+    pub user_name: String,
+    pub name: ScimName,
+    pub emails: Vec<ScimEmail>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    pub active: bool,
+}
+
+/// Map local user into SCIM core user resource
+pub fn build_scim_user(user: &User, user_profile: Option<&UserProfile>) -> ScimUser {
+    let (given_name, family_name, email, locale, timezone) = match user_profile {
+        Some(val) => (
+            val.first_name.clone(),
+            val.last_name.clone(),
+            val.email.clone(),
+            val.locale.clone(),
+            val.timezone.clone(),
+        ),
+        None => (None, None, None, None, None),
+    };
+    ScimUser {
+        schemas: vec![SCIM_USER_SCHEMA.to_string()],
+        id: None,
+        external_id: user.id.to_string(),
+        user_name: user.user_name.clone(),
+        name: ScimName {
+            given_name,
+            family_name,
+        },
+        emails: email
+            .map(|value| {
+                vec![ScimEmail {
+                    value,
+                    primary: true,
+                }]
+            })
+            .unwrap_or_default(),
+        locale,
+        timezone,
+        active: user.is_active() && user.deleted_date.is_none(),
+    }
+}
+
+/// Delay before the next delivery attempt, doubled on every failure and capped at 1 hour
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exp = attempts.clamp(1, 8) - 1;
+    let seconds = 30 * 2_i64.pow(exp as u32);
+    Duration::seconds(seconds.min(60 * 60))
+}
+
+pub struct ScimClient {
+    http: reqwest::Client,
+    base_url: String,
+    bearer_token: Option<String>,
+}
+
+impl ScimClient {
+    pub fn new(target: &ScimTarget) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(StdDuration::from_secs(SCIM_REQUEST_TIMEOUT as u64))
+            .build()?;
+        Ok(Self {
+            http,
+            base_url: target.base_url.trim_end_matches('/').to_string(),
+            bearer_token: target.bearer_token.clone(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let mut req = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path))
+            .header(reqwest::header::CONTENT_TYPE, SCIM_CONTENT_TYPE)
+            .header(reqwest::header::ACCEPT, SCIM_CONTENT_TYPE);
+        if let Some(token) = &self.bearer_token {
+            req = req.bearer_auth(token);
+        }
+        req
+    }
+
+    /// Create user on downstream app, return the id assigned by downstream app
+    pub async fn create_user(&self, scim_user: &ScimUser) -> anyhow::Result<String> {
+        let resp = self
+            .request(reqwest::Method::POST, "/Users")
+            .json(scim_user)
+            .send()
+            .await?
+            .error_for_status()?;
+        let created: ScimUser = resp.json().await?;
+        match created.id {
+            Some(id) => Ok(id),
+            None => anyhow::bail!("scim target did not return user id"),
+        }
+    }
+
+    pub async fn replace_user(&self, id: &str, scim_user: &ScimUser) -> anyhow::Result<()> {
+        let mut scim_user = scim_user.clone();
+        scim_user.id = Some(id.to_string());
+        self.request(reqwest::Method::PUT, &format!("/Users/{}", id))
+            .json(&scim_user)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn deactivate_user(&self, id: &str) -> anyhow::Result<()> {
+        self.request(reqwest::Method::PATCH, &format!("/Users/{}", id))
+            .json(&json!({
+                "schemas": [SCIM_PATCH_SCHEMA],
+                "Operations": [{"op": "replace", "value": {"active": false}}]
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+async fn deliver_scim_event(
+    tx: &mut Transaction<'_, Postgres>,
+    target: &ScimTarget,
+    event: &ScimProvisioningEvent,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    let (user, user_profile) = get_user_by_id(tx, &event.user_id, Some(false)).await?;
+    let user = match user {
+        Some(val) => val,
+        None => anyhow::bail!("user with id = {} not found", event.user_id),
+    };
+    let client = ScimClient::new(target)?;
+    let scim_user = build_scim_user(&user, user_profile.as_ref());
+    let link = get_scim_target_user(tx, &target.id, &user.id).await?;
+
+    match (event.event_type.as_str(), link) {
+        (EVENT_CREATE | EVENT_UPDATE, Some(link)) => {
+            client.replace_user(&link.external_id, &scim_user).await?;
+        }
+        (EVENT_CREATE | EVENT_UPDATE, None) => {
+            let external_id = client.create_user(&scim_user).await?;
+            upsert_scim_target_user(
+                tx,
+                &ScimTargetUser {
+                    target_id: target.id,
+                    user_id: user.id,
+                    external_id,
+                    created_date: Some(*now),
+                    updated_date: Some(*now),
+                },
+            )
+            .await?;
+        }
+        (EVENT_DEACTIVATE, Some(link)) => {
+            client.deactivate_user(&link.external_id).await?;
+        }
+        // user never reached downstream app, nothing to deactivate
+        (EVENT_DEACTIVATE, None) => {}
+        (event_type, _) => anyhow::bail!("unknown scim event type {}", event_type),
+    }
+    Ok(())
+}
+
+/// Push one event to its target and record the outcome on the event row
+pub async fn process_scim_event(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &mut ScimProvisioningEvent,
+) -> anyhow::Result<()> {
+    let now = Local::now().fixed_offset();
+    let target = get_scim_target_by_id(tx, &event.target_id).await?;
+    event.attempts += 1;
+    event.updated_date = Some(now);
+    let result = match target {
+        Some(target) if target.is_active.unwrap_or(false) => {
+            match deliver_scim_event(tx, &target, event, &now).await {
+                Ok(_) => Ok(()),
+                Err(err) => Err((err.to_string(), event.attempts >= target.max_attempts)),
+            }
+        }
+        _ => Err(("scim target is inactive or deleted".to_string(), true)),
+    };
+    match result {
+        Ok(_) => {
+            event.status = STATUS_SUCCESS.to_string();
+            event.last_error = None;
+            event.next_attempt_date = None;
+        }
+        Err((err, give_up)) => {
+            tracing::warn!(
+                "scim event {} to target {} failed on attempt {}: {}",
+                event.id,
+                event.target_id,
+                event.attempts,
+                err
+            );
+            event.last_error = Some(err);
+            if give_up {
+                event.status = STATUS_FAILED.to_string();
+                event.next_attempt_date = None;
+            } else {
+                event.status = STATUS_PENDING.to_string();
+                event.next_attempt_date = Some(now + retry_delay(event.attempts));
+            }
+        }
+    }
+    update_scim_provisioning_event(tx, event).await?;
+    Ok(())
+}
+
+/// Process a batch of due events, return number of processed events.
+/// The batch is claimed in a short transaction, then every event is delivered and its
+/// outcome recorded in a transaction of its own, so a failing event does not undo the
+/// deliveries before it and no row lock is held across the outbound requests
+pub async fn process_pending_scim_events(pool: &PgPool, batch_size: u32) -> anyhow::Result<u32> {
+    let now = Local::now().fixed_offset();
+    // long enough for every event of the batch to time out twice before it is due again
+    let claim_until = now + Duration::seconds(2 * SCIM_REQUEST_TIMEOUT * batch_size.max(1) as i64);
+    let mut tx = pool.begin().await?;
+    let mut events =
+        claim_due_scim_provisioning_event(&mut tx, &now, &claim_until, batch_size).await?;
+    tx.commit().await?;
+
+    let mut processed = 0;
+    for event in events.iter_mut() {
+        let res = async {
+            let mut tx = pool.begin().await?;
+            process_scim_event(&mut tx, event).await?;
+            tx.commit().await?;
+            anyhow::Ok(())
+        }
+        .await;
+        match res {
+            Ok(_) => processed += 1,
+            // left pending, the event is due again once its claim runs out
+            Err(err) => tracing::error!(
+                "error: on core::scim::process_pending_scim_events event {} error: {}",
+                event.id,
+                err
+            ),
+        }
+    }
+    Ok(processed)
+}
+
+/// Run scim outbound provisioning in background every `interval`
+pub fn spawn_scim_worker(pool: PgPool, interval: StdDuration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = process_pending_scim_events(&pool, 50).await {
+                tracing::error!("error: on core::scim::spawn_scim_worker error: {}", err);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::model::user::UserStatus;
+
+    #[test]
+    fn test_build_scim_user() {
+        let now = Local::now().fixed_offset();
+        let id = Uuid::now_v7();
+        let user = User {
+            id,
+            user_name: "john".to_string(),
+            password: "".to_string(),
+            status: UserStatus::Active,
+            expires_at: None,
+            is_2faenabled: Some(false),
+            created_by: None,
+            updated_by: None,
+            created_date: Some(now),
+            updated_date: Some(now),
+            deleted_date: None,
+            scopes: None,
+        };
+        let user_profile = UserProfile {
+            id,
+            user_id: id,
+            first_name: Some("John".to_string()),
+            last_name: Some("Doe".to_string()),
+            address: None,
+            email: Some("john@example.com".to_string()),
+            locale: None,
+            timezone: None,
+            email_verified_at: None,
+        };
+        let scim_user = build_scim_user(&user, Some(&user_profile));
+        assert_eq!(
+            serde_json::to_value(&scim_user).unwrap(),
+            json!({
+                "schemas": [SCIM_USER_SCHEMA],
+                "externalId": id.to_string(),
+                "userName": "john",
+                "name": {"givenName": "John", "familyName": "Doe"},
+                "emails": [{"value": "john@example.com", "primary": true}],
+                "active": true
+            })
+        );
+
+        let mut deleted_user = user.clone();
+        deleted_user.deleted_date = Some(now);
+        assert!(!build_scim_user(&deleted_user, None).active);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::seconds(60));
+        assert_eq!(retry_delay(3), Duration::seconds(120));
+        assert_eq!(retry_delay(20), Duration::seconds(60 * 60));
+    }
+}
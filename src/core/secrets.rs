@@ -0,0 +1,85 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Encrypted config values look like `ENC[aes256gcm:<base64 nonce + ciphertext>]`
+pub const ENCRYPTED_PREFIX: &str = "ENC[aes256gcm:";
+const NONCE_LENGTH: usize = 12;
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX) && value.ends_with(']')
+}
+
+/// Random 256 bit master key, base64 encoded
+pub fn generate_master_key() -> String {
+    STANDARD.encode(Aes256Gcm::generate_key(OsRng))
+}
+
+/// Decode a base64 master key, must be 32 bytes
+pub fn parse_master_key(value: &str) -> anyhow::Result<Key<Aes256Gcm>> {
+    let bytes = match STANDARD.decode(value.trim()) {
+        Ok(val) => val,
+        Err(_) => anyhow::bail!("master key is not valid base64"),
+    };
+    if bytes.len() != 32 {
+        anyhow::bail!("master key must be 32 bytes, got {}", bytes.len());
+    }
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
+}
+
+pub fn encrypt_secret(key: &Key<Aes256Gcm>, plaintext: &str) -> anyhow::Result<String> {
+    let cipher = Aes256Gcm::new(key);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = match cipher.encrypt(&nonce, plaintext.as_bytes()) {
+        Ok(val) => val,
+        Err(_) => anyhow::bail!("failed to encrypt secret"),
+    };
+    let mut payload = nonce.to_vec();
+    payload.extend(ciphertext);
+    Ok(format!("{}{}]", ENCRYPTED_PREFIX, STANDARD.encode(payload)))
+}
+
+pub fn decrypt_secret(key: &Key<Aes256Gcm>, value: &str) -> anyhow::Result<String> {
+    if !is_encrypted(value) {
+        anyhow::bail!("value is not encrypted");
+    }
+    let encoded = &value[ENCRYPTED_PREFIX.len()..value.len() - 1];
+    let payload = match STANDARD.decode(encoded) {
+        Ok(val) if val.len() > NONCE_LENGTH => val,
+        _ => anyhow::bail!("encrypted value is malformed"),
+    };
+    let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+    let cipher = Aes256Gcm::new(key);
+    let plaintext = match cipher.decrypt(Nonce::from_slice(nonce), ciphertext) {
+        Ok(val) => val,
+        Err(_) => anyhow::bail!("failed to decrypt, wrong master key or tampered value"),
+    };
+    Ok(String::from_utf8(plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::secrets::{
+        decrypt_secret, encrypt_secret, generate_master_key, is_encrypted, parse_master_key,
+    };
+
+    #[test]
+    fn test_encrypt_decrypt_secret() {
+        // Given
+        let key = parse_master_key(&generate_master_key()).unwrap();
+        let other_key = parse_master_key(&generate_master_key()).unwrap();
+
+        // When
+        let encrypted = encrypt_secret(&key, "jwt secret").unwrap();
+
+        // Expect
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("jwt secret"));
+        assert_eq!(decrypt_secret(&key, &encrypted).unwrap(), "jwt secret");
+        assert!(decrypt_secret(&other_key, &encrypted).is_err());
+        assert!(decrypt_secret(&key, "ENC[aes256gcm:AAAA]").is_err());
+        assert!(parse_master_key("c2hvcnQ=").is_err());
+    }
+}
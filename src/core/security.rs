@@ -2,18 +2,43 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
     Argon2, PasswordHash, PasswordVerifier,
 };
-use chrono::{Duration, Local};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use poem::Request;
+use chrono::{DateTime, Duration, Local, Utc};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, Context};
+use poem::{handler, http::header, web::Json, FromRequest, Request, RequestBody};
 use poem_openapi::{auth::Bearer, SecurityScheme};
-use redis::ConnectionLike;
-use serde::{Deserialize, Serialize};
-use sqlx::{Postgres, Transaction};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{pool::PoolConnection, PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::{model::user::User, repository::user::get_user_by_id, settings::Config};
+use crate::{
+    model::user::{User, UserStatus},
+    repository::user::get_user_by_id,
+    settings::{get_config, Config},
+    AppState,
+};
 
-use super::session::get_session;
+use super::{
+    error::AppError,
+    jwk::{generate_private_key, public_jwk},
+    locale::UserLocale,
+    permission_cache::has_permission,
+    request_id::set_request_timezone,
+    secrets::generate_master_key,
+    service_account::{get_user_from_api_key, is_api_key},
+    session::get_session,
+    session_store::{SessionConn, SessionStore},
+    utils::utc_now,
+};
 
 /// password hashing
 pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
@@ -49,18 +74,19 @@ mod test_hash_password {
     #[test]
     fn test_hashing_password() {
         let password = "secretpassword";
-        let hash = hash_password(&password);
+        let hash = hash_password(password);
         assert!(hash.is_ok());
         let hash = hash.unwrap();
-        let verify = verify_hash_password(&password, &hash);
+        let verify = verify_hash_password(password, &hash);
         assert!(verify.is_ok());
         assert!(verify.unwrap());
         let verify_false = verify_hash_password("wrongpassword", &hash);
         assert!(verify_false.is_ok());
-        assert_eq!(verify_false.unwrap(), false);
+        assert!(!verify_false.unwrap());
     }
 }
 
+#[derive(Clone)]
 pub struct Keys {
     pub encoding: EncodingKey,
     pub decoding: DecodingKey,
@@ -80,6 +106,9 @@ pub struct Claims {
     pub id: String,
     pub user_name: String,
     pub exp: i64,
+    /// Only set on tokens minted by `cli issue-token` and the client credentials grant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
 }
 
 impl Claims {
@@ -90,22 +119,391 @@ impl Claims {
             id: user_id.to_string(),
             user_name: user_name.to_string(),
             exp,
+            scopes: None,
+        }
+    }
+}
+
+/// Kid of JWT_SECRET, also the kid it keeps once imported into a keyring
+pub const JWT_SECRET_KID: &str = "jwt_secret";
+
+/// Signing key of a keyring, see JwtKeyring
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JwtKey {
+    pub kid: String,
+    /// HS256 in keyrings written before RS256 and ES256 keys
+    #[serde(default = "default_key_algorithm")]
+    pub algorithm: Algorithm,
+    /// HMAC secret with HS256, PEM private key with RS256 and ES256
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    /// Set once another key signs, tokens keep validating for the grace window after
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+fn default_key_algorithm() -> Algorithm {
+    Algorithm::HS256
+}
+
+impl JwtKey {
+    pub fn from_secret(jwt_secret: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            kid: JWT_SECRET_KID.to_string(),
+            algorithm: Algorithm::HS256,
+            secret: jwt_secret.to_string(),
+            created_at: now,
+            retired_at: None,
+        }
+    }
+
+    /// Key signing until JWT_KEYS_FILE exists, JWT_SECRET or JWT_PRIVATE_KEY
+    pub fn from_config(config: &Config, now: DateTime<Utc>) -> anyhow::Result<Self> {
+        let algorithm = jwt_algorithm(config)?;
+        if algorithm == Algorithm::HS256 {
+            return Ok(Self::from_secret(&config.jwt_secret, now));
+        }
+        let private_key = match config.jwt_private_key.as_deref().filter(|x| !x.is_empty()) {
+            Some(val) => val,
+            None => anyhow::bail!(
+                "JWT_PRIVATE_KEY is required with JWT_ALGORITHM={:?}",
+                algorithm
+            ),
+        };
+        let kid = match public_jwk(algorithm, private_key) {
+            Ok(val) => val.common.key_id.unwrap_or_default(),
+            Err(err) => anyhow::bail!("JWT_PRIVATE_KEY {}", err),
+        };
+        Ok(Self {
+            kid,
+            algorithm,
+            secret: private_key.to_string(),
+            created_at: now,
+            retired_at: None,
+        })
+    }
+
+    /// New random secret with HS256, new private key with RS256 and ES256
+    pub fn generate(algorithm: Algorithm, now: DateTime<Utc>) -> anyhow::Result<Self> {
+        let (kid, secret) = match algorithm {
+            Algorithm::HS256 => (Uuid::now_v7().simple().to_string(), generate_master_key()),
+            _ => {
+                let private_key = generate_private_key(algorithm)?;
+                let jwk = public_jwk(algorithm, &private_key)?;
+                (jwk.common.key_id.unwrap_or_default(), private_key)
+            }
+        };
+        Ok(Self {
+            kid,
+            algorithm,
+            secret,
+            created_at: now,
+            retired_at: None,
+        })
+    }
+}
+
+/// Keys of JWT_KEYS_FILE, written by `cli rotate-keys`. The key that is not retired
+/// signs new tokens
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JwtKeyring {
+    pub keys: Vec<JwtKey>,
+}
+
+impl JwtKeyring {
+    /// None when the file does not exist yet
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(val) => val,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => anyhow::bail!("read {}: {}", path.display(), err),
+        };
+        match serde_json::from_str(&content) {
+            Ok(val) => Ok(Some(val)),
+            Err(err) => anyhow::bail!("parse {}: {}", path.display(), err),
+        }
+    }
+
+    /// Replace the file at once, readable by its owner only
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn current(&self) -> Option<&JwtKey> {
+        self.keys.iter().rev().find(|x| x.retired_at.is_none())
+    }
+
+    /// Retire the current key and add `key`, signing from now on. Keys retired longer
+    /// than the grace are dropped
+    pub fn rotate(&mut self, key: JwtKey, grace: Duration, now: DateTime<Utc>) -> &JwtKey {
+        for key in self.keys.iter_mut().filter(|x| x.retired_at.is_none()) {
+            key.retired_at = Some(now);
+        }
+        self.keys.retain(|x| {
+            x.retired_at
+                .is_some_and(|retired_at| retired_at + grace > now)
+        });
+        self.keys.push(key);
+        &self.keys[self.keys.len() - 1]
+    }
+}
+
+/// Keys signing and validating tokens, the current one signs and writes its kid in the
+/// header. Previous keys of a keyring validate until their grace window ends
+#[derive(Clone)]
+pub struct JwtKeys {
+    kid: String,
+    algorithm: Algorithm,
+    current: Keys,
+    /// Public key published on /.well-known/jwks.json, none for HMAC secrets
+    jwk: Option<Jwk>,
+    previous: Vec<RetiredJwtKey>,
+}
+
+/// Key retired by a rotation, it validates and its public key stays published until
+/// `valid_until` so tokens signed before the rotation keep working
+#[derive(Clone)]
+struct RetiredJwtKey {
+    kid: String,
+    algorithm: Algorithm,
+    decoding: DecodingKey,
+    jwk: Option<Jwk>,
+    valid_until: DateTime<Utc>,
+}
+
+impl JwtKeys {
+    pub fn from_secret(jwt_secret: &str) -> Self {
+        Self {
+            kid: JWT_SECRET_KID.to_string(),
+            algorithm: Algorithm::HS256,
+            current: Keys::new(jwt_secret.as_bytes()),
+            jwk: None,
+            previous: vec![],
+        }
+    }
+
+    /// RS256 or ES256 key, the kid is the thumbprint of its public key
+    pub fn from_private_key(algorithm: Algorithm, private_key_pem: &str) -> anyhow::Result<Self> {
+        let jwk = public_jwk(algorithm, private_key_pem)?;
+        let encoding = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_key_pem.as_bytes())?,
+            _ => EncodingKey::from_ec_pem(private_key_pem.as_bytes())?,
+        };
+        Ok(Self {
+            kid: jwk.common.key_id.clone().unwrap_or_default(),
+            algorithm,
+            current: Keys {
+                encoding,
+                decoding: DecodingKey::from_jwk(&jwk)?,
+            },
+            jwk: Some(jwk),
+            previous: vec![],
+        })
+    }
+
+    /// Signing key of a keyring, RS256 and ES256 keys take the thumbprint as kid
+    pub fn from_key(key: &JwtKey) -> anyhow::Result<Self> {
+        match key.algorithm {
+            Algorithm::HS256 => Ok(Self {
+                kid: key.kid.clone(),
+                ..Self::from_secret(&key.secret)
+            }),
+            algorithm => Self::from_private_key(algorithm, &key.secret),
+        }
+    }
+
+    /// Also validate tokens of the retired `key` until `valid_until`, its public key is
+    /// published as long
+    pub fn with_retired(
+        mut self,
+        key: &JwtKey,
+        valid_until: DateTime<Utc>,
+    ) -> anyhow::Result<Self> {
+        let retired = match key.algorithm {
+            Algorithm::HS256 => RetiredJwtKey {
+                kid: key.kid.clone(),
+                algorithm: Algorithm::HS256,
+                decoding: DecodingKey::from_secret(key.secret.as_bytes()),
+                jwk: None,
+                valid_until,
+            },
+            algorithm => {
+                let jwk = public_jwk(algorithm, &key.secret)?;
+                RetiredJwtKey {
+                    kid: jwk.common.key_id.clone().unwrap_or_default(),
+                    algorithm,
+                    decoding: DecodingKey::from_jwk(&jwk)?,
+                    jwk: Some(jwk),
+                    valid_until,
+                }
+            }
+        };
+        self.previous.push(retired);
+        Ok(self)
+    }
+
+    pub fn from_keyring(keyring: &JwtKeyring, grace: Duration) -> anyhow::Result<Self> {
+        let current = match keyring.current() {
+            Some(val) => val,
+            None => anyhow::bail!("keyring has no current key, run cli rotate-keys"),
+        };
+        let mut keys = match Self::from_key(current) {
+            Ok(val) => val,
+            Err(err) => anyhow::bail!("key {}: {}", current.kid, err),
+        };
+        for key in keyring.keys.iter() {
+            if let Some(retired_at) = key.retired_at {
+                keys = match keys.with_retired(key, retired_at + grace) {
+                    Ok(val) => val,
+                    Err(err) => anyhow::bail!("key {}: {}", key.kid, err),
+                };
+            }
+        }
+        Ok(keys)
+    }
+
+    /// The keyring of JWT_KEYS_FILE once it exists, retired keys validating for
+    /// JWT_KEY_GRACE seconds. Until then JWT_PRIVATE_KEY with RS256 and ES256, JWT_SECRET
+    /// with HS256
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let algorithm = jwt_algorithm(config)?;
+        if let Some(path) = config.jwt_keys_file.as_deref().filter(|x| !x.is_empty()) {
+            if let Some(keyring) = JwtKeyring::load(Path::new(path))? {
+                let keys = Self::from_keyring(&keyring, jwt_key_grace(config))?;
+                if keys.algorithm != algorithm {
+                    anyhow::bail!(
+                        "{} signs with {:?} but JWT_ALGORITHM is {:?}, run cli rotate-keys to switch",
+                        path,
+                        keys.algorithm,
+                        algorithm
+                    );
+                }
+                return Ok(keys);
+            }
+        }
+        Self::from_key(&JwtKey::from_config(config, Utc::now())?)
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    /// Key set of the public key and of retired public keys still validating, empty for
+    /// HMAC secrets which are never published
+    pub fn jwks(&self) -> JwkSet {
+        let now = Utc::now();
+        JwkSet {
+            keys: self
+                .jwk
+                .iter()
+                .chain(
+                    self.previous
+                        .iter()
+                        .filter(|x| x.valid_until > now)
+                        .filter_map(|x| x.jwk.as_ref()),
+                )
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> anyhow::Result<String> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.kid.clone());
+        Ok(encode(&header, claims, &self.current.encoding)?)
+    }
+
+    /// Validate with the key named by the kid, every key for tokens signed before kids
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> anyhow::Result<T> {
+        let now = Utc::now();
+        let mut keys = std::iter::once((self.kid.as_str(), &self.current.decoding, self.algorithm))
+            .chain(
+                self.previous
+                    .iter()
+                    .filter(|x| x.valid_until > now)
+                    .map(|x| (x.kid.as_str(), &x.decoding, x.algorithm)),
+            );
+        match decode_header(token)?.kid {
+            Some(kid) => match keys.find(|(x, _, _)| *x == kid) {
+                Some((_, decoding, algorithm)) => {
+                    Ok(decode::<T>(token, decoding, &Validation::new(algorithm))?.claims)
+                }
+                None => anyhow::bail!("unknown or expired signing key {}", kid),
+            },
+            None => {
+                let mut last_err = None;
+                for (_, decoding, algorithm) in keys {
+                    match decode::<T>(token, decoding, &Validation::new(algorithm)) {
+                        Ok(val) => return Ok(val.claims),
+                        Err(err) => last_err = Some(err),
+                    }
+                }
+                Err(last_err
+                    .map(Into::into)
+                    .unwrap_or_else(|| anyhow!("no signing key")))
+            }
         }
     }
 }
 
+/// JWT_ALGORITHM, HS256 when empty
+pub fn jwt_algorithm(config: &Config) -> anyhow::Result<Algorithm> {
+    match config
+        .jwt_algorithm
+        .as_deref()
+        .map(|x| x.trim().to_uppercase())
+        .as_deref()
+    {
+        None | Some("") | Some("HS256") => Ok(Algorithm::HS256),
+        Some("RS256") => Ok(Algorithm::RS256),
+        Some("ES256") => Ok(Algorithm::ES256),
+        Some(val) => anyhow::bail!("JWT_ALGORITHM must be HS256, RS256 or ES256, got {}", val),
+    }
+}
+
+/// Seconds a retired key keeps validating, defaults to the refresh token lifetime so
+/// a rotation logs nobody out
+pub fn jwt_key_grace(config: &Config) -> Duration {
+    match config.jwt_key_grace {
+        Some(val) => Duration::seconds(val as i64),
+        None => Duration::minutes(config.jwt_refresh_exp as i64),
+    }
+}
+
+static JWT_KEYS: OnceLock<JwtKeys> = OnceLock::new();
+
+/// Set the process wide keys, false when they were already loaded
+pub fn init_jwt_keys(keys: JwtKeys) -> bool {
+    JWT_KEYS.set(keys).is_ok()
+}
+
+/// Process wide keys, loaded from settings on first use unless initialized
+pub fn jwt_keys() -> &'static JwtKeys {
+    JWT_KEYS.get_or_init(|| JwtKeys::from_config(&get_config()).expect("invalid JWT signing keys"))
+}
+
+/// Public keys validating tokens of this service, for services verifying them locally
+#[handler]
+pub fn jwks_json() -> Json<JwkSet> {
+    Json(jwt_keys().jwks())
+}
+
 /// Generate token
-pub fn encode_token(claims: &Claims, jwt_secret: String) -> anyhow::Result<String> {
-    let keys = Keys::new(jwt_secret.as_bytes());
-    let token = encode(&Header::default(), &claims, &keys.encoding)?;
-    Ok(token)
+pub fn encode_token(claims: &Claims, keys: &JwtKeys) -> anyhow::Result<String> {
+    keys.encode(claims)
 }
 
 /// Extract payload and Validate token
-pub fn decode_token(token: &str, jwt_secret: String) -> anyhow::Result<Claims> {
-    let keys = Keys::new(jwt_secret.as_bytes());
-    let token_data = decode::<Claims>(token, &keys.decoding, &Validation::default())?;
-    Ok(token_data.claims)
+pub fn decode_token(token: &str, keys: &JwtKeys) -> anyhow::Result<Claims> {
+    keys.decode(token)
 }
 
 pub async fn generate_token_from_user(user: User, config: Config) -> anyhow::Result<String> {
@@ -114,27 +512,375 @@ pub async fn generate_token_from_user(user: User, config: Config) -> anyhow::Res
         user.user_name.as_str(),
         config.clone(),
     );
-    let token = encode_token(&claims, config.jwt_secret)?;
+    let token = encode_token(&claims, jwt_keys())?;
     Ok(token)
 }
 
-pub async fn get_user_from_token<C: ConnectionLike>(
-    tx: &mut Transaction<'_, Postgres>,
+/// User of a session access token or of a service account api key, see core::service_account.
+/// None for users deleted, expired or no longer active since the token was issued. The
+/// user carries the scopes of the session. The response of the request is formatted in the
+/// timezone of the user, see set_request_timezone
+pub async fn get_user_from_token<C: SessionStore>(
+    conn: &mut PgConnection,
     redis_conn: &mut C,
     jwt_token: Option<String>,
 ) -> anyhow::Result<Option<User>> {
     if jwt_token.is_none() {
         return Ok(None);
     }
-    let session = get_session(redis_conn, jwt_token.unwrap())?;
-    if session.is_none() {
-        return Ok(None);
+    let jwt_token = jwt_token.unwrap();
+    let (user, user_profile) = if is_api_key(&jwt_token) {
+        (get_user_from_api_key(conn, &jwt_token).await?, None)
+    } else {
+        let session = match get_session(redis_conn, jwt_token).await? {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        let user_id = Uuid::parse_str(&session.user_id)?;
+        let (user, user_profile) = get_user_by_id(conn, &user_id, None).await?;
+        let user = user.map(|x| User {
+            scopes: session.scopes,
+            ..x
+        });
+        (user, user_profile)
+    };
+    let now = utc_now();
+    let user = user.filter(|x| x.status == UserStatus::Active && !x.is_expired(&now));
+    if user.is_some() {
+        set_request_timezone(UserLocale::resolve(&get_config(), user_profile.as_ref()).timezone);
     }
-    let user_id = Uuid::parse_str(&session.unwrap().user_id)?;
-    let (user, _) = get_user_by_id(tx, &user_id, None).await?;
     Ok(user)
 }
 
+/// Transaction, redis connection and user of an authenticated request, taken as a handler
+/// argument instead of beginning the transaction, getting the redis connection and
+/// validating the token by hand. Fails with the same 500 and 401 bodies the handlers return,
+/// see AppError.
+/// Keep the BearerAuthorization argument too, it documents the security scheme
+pub struct AuthContext {
+    pub tx: Transaction<'static, Postgres>,
+    pub redis_conn: SessionConn,
+    pub user: User,
+}
+
+/// Same as AuthContext with a pool connection instead of a transaction, for handlers that
+/// only read
+pub struct ReadAuthContext {
+    pub db: PoolConnection<Postgres>,
+    pub redis_conn: SessionConn,
+    pub user: User,
+}
+
+fn request_state(req: &Request) -> Result<&Arc<AppState>, AppError> {
+    req.data::<Arc<AppState>>()
+        .ok_or_else(|| AppError::Internal(anyhow!("app state is not set")))
+}
+
+/// Token of the Authorization header, the bearer scheme is case insensitive
+pub fn bearer_token(req: &Request) -> Option<String> {
+    req.header(header::AUTHORIZATION)
+        .and_then(|x| x.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim().to_string())
+}
+
+/// Redis connection and user of the request bearer token, 401 without a valid one
+async fn authenticate(
+    req: &Request,
+    state: &AppState,
+    conn: &mut PgConnection,
+) -> Result<(SessionConn, User), AppError> {
+    let mut redis_conn = state
+        .redis_conn
+        .get()
+        .await
+        .context("get redis pool connection")?;
+    let user = get_user_from_token(conn, &mut redis_conn, bearer_token(req))
+        .await
+        .context("get user from token")?
+        .ok_or_else(AppError::unauthorized)?;
+    Ok((redis_conn, user))
+}
+
+impl<'a> FromRequest<'a> for AuthContext {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> poem::Result<Self> {
+        let state = request_state(req)?;
+        let mut tx = state
+            .db
+            .begin()
+            .await
+            .context("begin transaction")
+            .map_err(AppError::from)?;
+        let (redis_conn, user) = authenticate(req, state, &mut tx).await?;
+        Ok(Self {
+            tx,
+            redis_conn,
+            user,
+        })
+    }
+}
+
+impl<'a> FromRequest<'a> for ReadAuthContext {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> poem::Result<Self> {
+        let state = request_state(req)?;
+        let mut db = state
+            .db
+            .acquire()
+            .await
+            .context("acquire connection")
+            .map_err(AppError::from)?;
+        let (redis_conn, user) = authenticate(req, state, &mut db).await?;
+        Ok(Self {
+            db,
+            redis_conn,
+            user,
+        })
+    }
+}
+
+/// Whether the user holds `permission`, written as `name.attribute` e.g. `user.create`.
+/// Grants made directly, through roles and through groups all count, see has_permission.
+/// Scoped tokens only get the granted permissions within their scopes, see User::has_scope
+pub async fn require_permission<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    user: &User,
+    permission: &str,
+) -> anyhow::Result<bool> {
+    let (permission_name, attribute_name) = match permission.rsplit_once('.') {
+        Some(val) => val,
+        None => anyhow::bail!("permission {} is not written as name.attribute", permission),
+    };
+    if !user.has_scope(permission_name, attribute_name) {
+        return Ok(false);
+    }
+    has_permission(
+        conn,
+        redis_conn,
+        &get_config(),
+        &user.id,
+        permission_name,
+        attribute_name,
+    )
+    .await
+}
+
+/// Message of the 403 returned when require_permission fails
+pub fn permission_required(permission: &str) -> String {
+    format!("{} permission required", permission.replace('.', " "))
+}
+
+/// Same as require_permission, failing with the 403 when the permission is missing
+pub async fn ensure_permission<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    user: &User,
+    permission: &str,
+) -> Result<(), AppError> {
+    if require_permission(conn, redis_conn, user, permission)
+        .await
+        .context("require_permission")?
+    {
+        Ok(())
+    } else {
+        Err(AppError::forbidden(permission_required(permission)))
+    }
+}
+
+#[cfg(test)]
+mod test_jwt_keys {
+    use chrono::{Duration, Utc};
+    use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+
+    use crate::{
+        core::{
+            security::{Claims, JwtKey, JwtKeyring, JwtKeys, JWT_SECRET_KID},
+            test_utils::{TEST_EC_PRIVATE_KEY, TEST_RSA_PRIVATE_KEY},
+        },
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_jwt_key_rotation() -> anyhow::Result<()> {
+        // Given a token signed with JWT_SECRET
+        let config = get_config();
+        let claims = Claims::new("0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b", "test_user", config);
+        let secret_keys = JwtKeys::from_secret("jwt secret");
+        let old_token = secret_keys.encode(&claims)?;
+        let now = Utc::now();
+
+        // When rotated
+        let mut keyring = JwtKeyring {
+            keys: vec![JwtKey::from_secret("jwt secret", now)],
+        };
+        let kid = keyring
+            .rotate(
+                JwtKey::generate(Algorithm::HS256, now)?,
+                Duration::hours(1),
+                now,
+            )
+            .kid
+            .clone();
+        let keys = JwtKeys::from_keyring(&keyring, Duration::hours(1))?;
+        let new_token = keys.encode(&claims)?;
+
+        // Expect new tokens carry the new kid, old tokens still valid
+        assert_eq!(keys.kid(), kid);
+        assert_eq!(keyring.keys[0].kid, JWT_SECRET_KID);
+        assert_eq!(
+            jsonwebtoken::decode_header(&new_token)?.kid,
+            Some(kid.clone())
+        );
+        assert_eq!(keys.decode::<Claims>(&old_token)?.user_name, "test_user");
+        assert_eq!(keys.decode::<Claims>(&new_token)?.user_name, "test_user");
+        assert!(secret_keys.decode::<Claims>(&new_token).is_err());
+
+        // When the grace window is over
+        let keys = JwtKeys::from_keyring(&keyring, Duration::zero())?;
+
+        // Expect only the new key validates
+        assert!(keys.decode::<Claims>(&old_token).is_err());
+        assert!(keys.decode::<Claims>(&new_token).is_ok());
+
+        // Expect keys retired longer than the grace dropped on the next rotation
+        keyring.rotate(
+            JwtKey::generate(Algorithm::HS256, now)?,
+            Duration::hours(1),
+            now + Duration::hours(2),
+        );
+        assert_eq!(keyring.keys.len(), 2);
+        assert_eq!(keyring.keys[0].kid, kid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_asymmetric_jwt_keys() -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let claims = Claims::new(
+            "0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b",
+            "test_user",
+            config.clone(),
+        );
+
+        for (algorithm, private_key) in [
+            (Algorithm::RS256, TEST_RSA_PRIVATE_KEY),
+            (Algorithm::ES256, TEST_EC_PRIVATE_KEY),
+        ] {
+            // When
+            let keys = JwtKeys::from_private_key(algorithm, private_key)?;
+            let token = keys.encode(&claims)?;
+
+            // Expect verifiable with the published key alone
+            let header = jsonwebtoken::decode_header(&token)?;
+            assert_eq!(header.alg, algorithm);
+            let jwks = keys.jwks();
+            let jwk = jwks.find(header.kid.as_deref().unwrap()).unwrap();
+            let decoded = jsonwebtoken::decode::<Claims>(
+                &token,
+                &DecodingKey::from_jwk(jwk)?,
+                &Validation::new(algorithm),
+            )?;
+            assert_eq!(decoded.claims.user_name, "test_user");
+            assert_eq!(keys.decode::<Claims>(&token)?.user_name, "test_user");
+            assert!(JwtKeys::from_secret(&config.jwt_secret)
+                .decode::<Claims>(&token)
+                .is_err());
+        }
+
+        // Expect secrets never published
+        assert!(JwtKeys::from_secret("jwt secret").jwks().keys.is_empty());
+
+        // Expect a private key required with RS256
+        let mut config = config;
+        config.jwt_algorithm = Some("RS256".to_string());
+        config.jwt_private_key = None;
+        assert!(JwtKeys::from_config(&config).is_err());
+        config.jwt_private_key = Some(TEST_RSA_PRIVATE_KEY.to_string());
+        assert_eq!(JwtKeys::from_config(&config)?.jwks().keys.len(), 1);
+        config.jwt_algorithm = Some("none".to_string());
+        assert!(JwtKeys::from_config(&config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retired_asymmetric_jwt_keys() -> anyhow::Result<()> {
+        // Given a token signed with the RSA key
+        let config = get_config();
+        let claims = Claims::new("0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b", "test_user", config);
+        let rsa_keys = JwtKeys::from_private_key(Algorithm::RS256, TEST_RSA_PRIVATE_KEY)?;
+        let old_token = rsa_keys.encode(&claims)?;
+        let now = Utc::now();
+
+        // When the keyring switches to the EC key
+        let keyring = JwtKeyring {
+            keys: vec![
+                JwtKey {
+                    kid: rsa_keys.kid().to_string(),
+                    algorithm: Algorithm::RS256,
+                    secret: TEST_RSA_PRIVATE_KEY.to_string(),
+                    created_at: now,
+                    retired_at: Some(now),
+                },
+                JwtKey {
+                    kid: "ec".to_string(),
+                    algorithm: Algorithm::ES256,
+                    secret: TEST_EC_PRIVATE_KEY.to_string(),
+                    created_at: now,
+                    retired_at: None,
+                },
+            ],
+        };
+        let keys = JwtKeys::from_keyring(&keyring, Duration::hours(1))?;
+        let new_token = keys.encode(&claims)?;
+
+        // Expect both published, old tokens still valid
+        assert_eq!(
+            jsonwebtoken::decode_header(&new_token)?.alg,
+            Algorithm::ES256
+        );
+        assert_eq!(keys.jwks().keys.len(), 2);
+        assert!(keys.jwks().find(rsa_keys.kid()).is_some());
+        assert_eq!(keys.decode::<Claims>(&old_token)?.user_name, "test_user");
+        assert_eq!(keys.decode::<Claims>(&new_token)?.user_name, "test_user");
+
+        // When the grace window is over
+        let keys = JwtKeys::from_keyring(&keyring, Duration::zero())?;
+
+        // Expect the retired key neither validates nor published
+        assert_eq!(keys.jwks().keys.len(), 1);
+        assert!(keys.decode::<Claims>(&old_token).is_err());
+
+        // Expect keyrings written before key algorithms hold secrets
+        let keyring: JwtKeyring = serde_json::from_str(
+            r#"{"keys":[{"kid":"a","secret":"jwt secret","created_at":"2025-01-01T00:00:00Z"}]}"#,
+        )?;
+        assert_eq!(keyring.keys[0].algorithm, Algorithm::HS256);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_keyring_file() -> anyhow::Result<()> {
+        // Given
+        let path = std::env::temp_dir().join(format!("jwt_keys_{}.json", uuid::Uuid::now_v7()));
+        assert_eq!(JwtKeyring::load(&path)?, None);
+        let mut keyring = JwtKeyring::default();
+        keyring.rotate(
+            JwtKey::generate(Algorithm::ES256, Utc::now())?,
+            Duration::hours(1),
+            Utc::now(),
+        );
+
+        // When
+        keyring.save(&path)?;
+
+        // Expect
+        assert_eq!(JwtKeyring::load(&path)?, Some(keyring));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test_generate_token {
     use chrono::Local;
@@ -145,8 +891,12 @@ mod test_generate_token {
         core::{
             security::{generate_token_from_user, get_user_from_token, hash_password},
             session::add_session,
+            session_store::create_redis_pool,
+        },
+        model::{
+            user::{User, UserStatus},
+            user_profile::UserProfile,
         },
-        model::{user::User, user_profile::UserProfile},
         settings::get_config,
     };
 
@@ -154,9 +904,8 @@ mod test_generate_token {
     async fn test_generate_token(pool: PgPool) -> anyhow::Result<()> {
         // Given
         let config = get_config();
-        let client = redis::Client::open(config.redis_url.clone()).unwrap();
-        let redis_pool = r2d2::Pool::builder().build(client).unwrap();
-        let mut redis_conn = redis_pool.get()?;
+        let redis_pool = create_redis_pool(&config).unwrap();
+        let mut redis_conn = redis_pool.get().await?;
         let mut tx = pool.begin().await?;
         // Prepare user
         let username = "hello".to_string();
@@ -168,13 +917,15 @@ mod test_generate_token {
             id,
             user_name: username.to_string(),
             password: hashed_password,
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             created_by: None,
             updated_by: None,
             created_date: Some(now),
             updated_date: Some(now),
             deleted_date: None,
             is_2faenabled: Some(false),
+            scopes: None,
         };
         let user_profile = UserProfile {
             id,
@@ -183,6 +934,9 @@ mod test_generate_token {
             last_name: None,
             address: None,
             email: None,
+            locale: None,
+            timezone: None,
+            email_verified_at: None,
         };
         // create user on db
         sqlx::query(
@@ -194,7 +948,7 @@ mod test_generate_token {
         .bind(user.id)
         .bind(&user.user_name)
         .bind(&user.password)
-        .bind(user.is_active)
+        .bind(user.is_active())
         .bind(user.created_date)
         .bind(user.updated_date)
         .execute(&mut *tx)
@@ -222,9 +976,21 @@ mod test_generate_token {
             &config,
             token.clone(),
             "".to_string(),
-        )?;
-        let token_user = get_user_from_token(&mut tx, &mut redis_conn, Some(token)).await?;
+        )
+        .await?;
+        let token_user = get_user_from_token(&mut tx, &mut redis_conn, Some(token.clone())).await?;
         assert!(token_user.is_some());
+
+        // When suspended after the token was issued
+        sqlx::query("UPDATE public.user SET status = $1 WHERE id = $2")
+            .bind(UserStatus::Suspended)
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+        let token_user = get_user_from_token(&mut tx, &mut redis_conn, Some(token)).await?;
+
+        // Expect
+        assert!(token_user.is_none());
         Ok(())
     }
 }
@@ -235,6 +1001,9 @@ pub struct ClaimsRefresh {
     pub user_name: String,
     pub exp: i64,
     pub type_key: String,
+    /// Unique per token so a rotated refresh token never equals the one it replaces
+    #[serde(default)]
+    pub jti: String,
 }
 
 impl ClaimsRefresh {
@@ -246,22 +1015,19 @@ impl ClaimsRefresh {
             user_name: user_name.to_string(),
             exp,
             type_key: "refresh".to_string(),
+            jti: Uuid::now_v7().to_string(),
         }
     }
 }
 
 /// Generate refresh token
-pub fn encode_refresh_token(claims: &ClaimsRefresh, jwt_secret: String) -> anyhow::Result<String> {
-    let keys = Keys::new(jwt_secret.as_bytes());
-    let token = encode(&Header::default(), &claims, &keys.encoding)?;
-    Ok(token)
+pub fn encode_refresh_token(claims: &ClaimsRefresh, keys: &JwtKeys) -> anyhow::Result<String> {
+    keys.encode(claims)
 }
 
 /// Extract payload and Validate referesh token
-pub fn decode_refresh_token(token: &str, jwt_secret: String) -> anyhow::Result<ClaimsRefresh> {
-    let keys = Keys::new(jwt_secret.as_bytes());
-    let token_data = decode::<ClaimsRefresh>(token, &keys.decoding, &Validation::default())?;
-    Ok(token_data.claims)
+pub fn decode_refresh_token(token: &str, keys: &JwtKeys) -> anyhow::Result<ClaimsRefresh> {
+    keys.decode(token)
 }
 
 pub async fn generate_refresh_token_from_user(
@@ -273,19 +1039,18 @@ pub async fn generate_refresh_token_from_user(
         user.user_name.as_str(),
         config.clone(),
     );
-    let token = encode_refresh_token(&claims, config.jwt_secret)?;
+    let token = encode_refresh_token(&claims, jwt_keys())?;
     Ok(token)
 }
 
 pub async fn get_user_from_refresh_token(
     tx: &mut Transaction<'_, Postgres>,
     refresh_token: Option<String>,
-    config: Config,
 ) -> anyhow::Result<Option<User>> {
     if refresh_token.is_none() {
         return Ok(None);
     }
-    let claims = decode_refresh_token(refresh_token.unwrap().as_str(), config.jwt_secret)?;
+    let claims = decode_refresh_token(refresh_token.unwrap().as_str(), jwt_keys())?;
     let user_id = Uuid::parse_str(&claims.id)?;
     let (user, _) = get_user_by_id(tx, &user_id, None).await?;
     Ok(user)
@@ -301,7 +1066,10 @@ mod test_generate_refresh_token {
         core::security::{
             generate_refresh_token_from_user, get_user_from_refresh_token, hash_password,
         },
-        model::{user::User, user_profile::UserProfile},
+        model::{
+            user::{User, UserStatus},
+            user_profile::UserProfile,
+        },
         settings::get_config,
     };
 
@@ -320,13 +1088,15 @@ mod test_generate_refresh_token {
             id,
             user_name: username.to_string(),
             password: hashed_password,
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             created_by: None,
             updated_by: None,
             created_date: Some(now),
             updated_date: Some(now),
             deleted_date: None,
             is_2faenabled: Some(false),
+            scopes: None,
         };
         let user_profile = UserProfile {
             id,
@@ -335,6 +1105,9 @@ mod test_generate_refresh_token {
             last_name: None,
             address: None,
             email: None,
+            locale: None,
+            timezone: None,
+            email_verified_at: None,
         };
         // create user on db
         sqlx::query(
@@ -346,7 +1119,7 @@ mod test_generate_refresh_token {
         .bind(user.id)
         .bind(&user.user_name)
         .bind(&user.password)
-        .bind(user.is_active)
+        .bind(user.is_active())
         .bind(user.created_date)
         .bind(user.updated_date)
         .execute(&mut *tx)
@@ -368,7 +1141,7 @@ mod test_generate_refresh_token {
 
         // When
         let token = generate_refresh_token_from_user(user.clone(), config.clone()).await?;
-        let token_user = get_user_from_refresh_token(&mut tx, Some(token), config).await?;
+        let token_user = get_user_from_refresh_token(&mut tx, Some(token)).await?;
         assert!(token_user.is_some());
         Ok(())
     }
@@ -379,9 +1152,10 @@ pub struct UserApiKey {
     pub token: Option<String>,
 }
 
-/// ApiKey authorization
+/// Access token returned by /auth/login or a service account api key, sent as
+/// `Authorization: Bearer <token>`
 #[derive(SecurityScheme)]
-#[oai(ty = "bearer", checker = "bearer_checker")]
+#[oai(ty = "bearer", bearer_format = "JWT", checker = "bearer_checker")]
 pub struct BearerAuthorization(pub UserApiKey);
 
 pub async fn bearer_checker(_req: &Request, api_key: Bearer) -> Option<UserApiKey> {
@@ -0,0 +1,211 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Duration;
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        security::{encode_token, jwt_keys, Claims},
+        session::add_session_with_ttl,
+        session_store::SessionStore,
+        utils::utc_now,
+    },
+    model::user::{User, UserStatus},
+    repository::{
+        service_account::{
+            get_service_account_by_id, get_service_account_by_key_hash, touch_service_account,
+        },
+        user::get_user_by_id,
+        user_permission::get_user_permission_names,
+    },
+    settings::Config,
+};
+
+/// Api keys start with it, bearer tokens without it are looked up as sessions
+pub const API_KEY_PREFIX: &str = "sa_";
+/// Characters of the key kept in plain text to recognize it, the prefix and 8 more
+pub const API_KEY_DISPLAY_LENGTH: usize = 11;
+/// last_used_at is written at most once per interval, in seconds
+pub const LAST_USED_INTERVAL: i64 = 60;
+
+/// Random 256 bit url safe key with the api key prefix
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", API_KEY_PREFIX, URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Hex sha256 of the key, keys are random so a slow hash like argon2 adds nothing and
+/// would cost every request
+pub fn hash_api_key(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+pub fn api_key_display_prefix(api_key: &str) -> String {
+    api_key.chars().take(API_KEY_DISPLAY_LENGTH).collect()
+}
+
+/// Split a scope written as `name.attribute` e.g. `user.read`
+pub fn parse_scope(scope: &str) -> Option<(&str, &str)> {
+    scope
+        .trim()
+        .rsplit_once('.')
+        .filter(|(name, attribute)| !name.is_empty() && !attribute.is_empty())
+}
+
+/// Backing user of the service account of the key. None for unknown, deleted and expired
+/// keys and when the backing user is no longer active. Records the key as used
+pub async fn get_user_from_api_key(
+    conn: &mut PgConnection,
+    api_key: &str,
+) -> anyhow::Result<Option<User>> {
+    let account = match get_service_account_by_key_hash(conn, &hash_api_key(api_key)).await? {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let now = utc_now();
+    if account.is_expired(&now) {
+        return Ok(None);
+    }
+    let (user, _) = get_user_by_id(conn, &account.user_id, None).await?;
+    let user = user.filter(|x| x.status == UserStatus::Active);
+    if user.is_some() {
+        touch_service_account(conn, &account.id, &now, LAST_USED_INTERVAL).await?;
+    }
+    Ok(user)
+}
+
+/// Why the client credentials grant was refused, the variants map to the RFC 6749 error
+/// codes, see route::auth
+#[derive(Debug, thiserror::Error)]
+pub enum ClientCredentialsError {
+    #[error("client authentication failed")]
+    InvalidClient,
+    #[error("{0}")]
+    InvalidScope(String),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+/// Access token of a service account, registered as a session of its backing user
+pub struct ClientCredentialsToken {
+    pub access_token: String,
+    /// Seconds
+    pub expires_in: i64,
+    pub scopes: Vec<String>,
+}
+
+/// Client credentials grant: `client_id` is the service account id and `client_secret`
+/// its api key. The token carries the requested scopes, space separated, or every scope
+/// of the account when none are requested. It lives JWT_EXP minutes at most and never
+/// past the expiry of the account. No refresh token is issued, clients ask again
+pub async fn issue_client_credentials_token<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    config: &Config,
+    client_id: &str,
+    client_secret: &str,
+    scope: Option<&str>,
+) -> Result<ClientCredentialsToken, ClientCredentialsError> {
+    let account_id =
+        Uuid::parse_str(client_id.trim()).map_err(|_| ClientCredentialsError::InvalidClient)?;
+    let now = utc_now();
+    let account = get_service_account_by_id(conn, &account_id)
+        .await?
+        .filter(|x| x.key_hash == hash_api_key(client_secret) && !x.is_expired(&now))
+        .ok_or(ClientCredentialsError::InvalidClient)?;
+    let (user, _) = get_user_by_id(conn, &account.user_id, None).await?;
+    let user = user
+        .filter(|x| x.status == UserStatus::Active)
+        .ok_or(ClientCredentialsError::InvalidClient)?;
+
+    let granted: Vec<String> = get_user_permission_names(conn, &user.id)
+        .await?
+        .into_iter()
+        .map(|(permission, attribute)| format!("{}.{}", permission, attribute))
+        .collect();
+    let mut scopes: Vec<String> = vec![];
+    for item in scope.unwrap_or_default().split_whitespace() {
+        if !granted.iter().any(|x| x == item) {
+            return Err(ClientCredentialsError::InvalidScope(format!(
+                "scope {} is not granted to the client",
+                item
+            )));
+        }
+        if !scopes.iter().any(|x| x == item) {
+            scopes.push(item.to_string());
+        }
+    }
+    if scopes.is_empty() {
+        scopes = granted;
+    }
+
+    let mut expires_in = config.jwt_exp as i64 * 60;
+    if let Some(expires_at) = account.expires_at {
+        expires_in = expires_in.min((expires_at - now).num_seconds().max(1));
+    }
+    let claims = Claims {
+        id: user.id.to_string(),
+        user_name: user.user_name.clone(),
+        exp: (now + Duration::seconds(expires_in)).timestamp(),
+        scopes: Some(scopes.clone()),
+    };
+    let access_token = encode_token(&claims, jwt_keys())?;
+    add_session_with_ttl(
+        redis_conn,
+        &user,
+        access_token.clone(),
+        "".to_string(),
+        expires_in as u64,
+        Some(scopes.clone()),
+    )
+    .await?;
+    touch_service_account(conn, &account.id, &now, LAST_USED_INTERVAL).await?;
+    Ok(ClientCredentialsToken {
+        access_token,
+        expires_in,
+        scopes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::service_account::{
+        api_key_display_prefix, generate_api_key, hash_api_key, is_api_key, parse_scope,
+    };
+
+    #[test]
+    fn test_api_key() {
+        // When
+        let api_key = generate_api_key();
+
+        // Expect
+        assert!(is_api_key(&api_key));
+        assert_eq!(api_key.len(), 46);
+        assert_ne!(api_key, generate_api_key());
+        assert_eq!(
+            hash_api_key("sa_test"),
+            "9e4e0a50e86f0dda4f0a6c3893f073880c7305fe1d724b255581365de2a4dc43"
+        );
+        assert_eq!(api_key_display_prefix(&api_key), api_key[..11]);
+        assert!(!is_api_key("eyJhbGciOiJIUzI1NiJ9"));
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!(parse_scope("user.read"), Some(("user", "read")));
+        assert_eq!(
+            parse_scope(" user_permission.create "),
+            Some(("user_permission", "create"))
+        );
+        assert_eq!(parse_scope("user"), None);
+        assert_eq!(parse_scope(".read"), None);
+        assert_eq!(parse_scope("user."), None);
+    }
+}
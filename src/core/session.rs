@@ -1,23 +1,55 @@
-use redis::{Connection, ConnectionLike};
+use chrono::Utc;
+use redis::aio::MultiplexedConnection;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::{model::user::User, settings::Config};
 
+use super::session_store::SessionStore;
+
 // use super::security::Claims;
 
-pub fn get_redis_connection(redis_url: &str) -> anyhow::Result<Connection> {
+pub async fn get_redis_connection(redis_url: &str) -> anyhow::Result<MultiplexedConnection> {
     let client = redis::Client::open(redis_url)?;
-    let con = client.get_connection()?;
+    let con = client.get_multiplexed_async_connection().await?;
     Ok(con)
 }
 
+/// Keys matching pattern, walked with SCAN so large keyspaces do not block the server
+/// the way KEYS does
+pub async fn scan_keys<C: SessionStore>(
+    redis_conn: &mut C,
+    pattern: &str,
+) -> anyhow::Result<Vec<String>> {
+    Ok(redis_conn.scan_match(pattern).await?)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SessionData {
     pub user_id: String,
     pub refresh_token: String,
+    /// unix timestamp in milliseconds, compared with revoke_user_sessions
+    #[serde(default)]
+    pub created_at: i64,
+    /// Scopes of the token, see Claims
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+
+fn sessions_revoked_key(user_id: &str) -> String {
+    format!("core:sessions_revoked:{}", user_id)
 }
 
-pub fn add_session<C: ConnectionLike>(
+fn refresh_token_key(refresh_token: &str) -> String {
+    format!("core:refresh_tokens:{}", refresh_token)
+}
+
+/// Set of the refresh tokens issued to the user, see revoke_other_user_sessions
+fn user_refresh_tokens_key(user_id: &str) -> String {
+    format!("core:user_refresh_tokens:{}", user_id)
+}
+
+pub async fn add_session<C: SessionStore>(
     redis_conn: &mut C,
     user: &User,
     config: &Config,
@@ -25,41 +57,176 @@ pub fn add_session<C: ConnectionLike>(
     refresh_token: String,
 ) -> anyhow::Result<()> {
     // let token_exp_date = *now + Duration::minutes(config.jwt_exp as i64);
+    add_refresh_token(
+        redis_conn,
+        user,
+        &refresh_token,
+        config.jwt_refresh_exp as u64 * 60,
+    )
+    .await?;
+    add_session_with_ttl(
+        redis_conn,
+        user,
+        token,
+        refresh_token,
+        config.jwt_exp as u64 * 60,
+        None,
+    )
+    .await
+}
+
+/// Register a refresh token, it can be exchanged once with take_refresh_token. ttl in seconds
+pub async fn add_refresh_token<C: SessionStore>(
+    redis_conn: &mut C,
+    user: &User,
+    refresh_token: &str,
+    ttl: u64,
+) -> anyhow::Result<()> {
+    let user_id = user.id.to_string();
+    redis_conn
+        .set_ex(&refresh_token_key(refresh_token), &user_id, ttl)
+        .await?;
+    // the set lives as long as the longest lived of its tokens
+    let tokens_key = user_refresh_tokens_key(&user_id);
+    redis_conn.sadd(&tokens_key, refresh_token).await?;
+    if redis_conn.ttl(&tokens_key).await? < ttl as i64 {
+        redis_conn.expire(&tokens_key, ttl).await?;
+    }
+    Ok(())
+}
+
+/// Consume a refresh token, false when it is unknown, expired or was already used
+pub async fn take_refresh_token<C: SessionStore>(
+    redis_conn: &mut C,
+    refresh_token: &str,
+) -> anyhow::Result<bool> {
+    let key = refresh_token_key(refresh_token);
+    let owner = redis_conn.get(&key).await?;
+    // DEL is atomic, of two concurrent refreshes with the same token only one wins
+    let deleted = redis_conn.del(&key).await?;
+    if let (true, Some(owner)) = (deleted > 0, owner) {
+        redis_conn
+            .srem(&user_refresh_tokens_key(&owner), refresh_token)
+            .await?;
+    }
+    Ok(deleted > 0)
+}
+
+/// Register session expiring after ttl seconds, requests with the token are limited to
+/// the scopes when set
+pub async fn add_session_with_ttl<C: SessionStore>(
+    redis_conn: &mut C,
+    user: &User,
+    token: String,
+    refresh_token: String,
+    ttl: u64,
+    scopes: Option<Vec<String>>,
+) -> anyhow::Result<()> {
     let session_data = SessionData {
         user_id: user.id.to_string(),
         refresh_token,
+        created_at: Utc::now().timestamp_millis(),
+        scopes,
     };
     let session_json = serde_json::to_string(&session_data)?;
-    redis::Cmd::set_ex(token, session_json, config.jwt_exp as u64).exec(redis_conn)?;
+    redis_conn.set_ex(&token, &session_json, ttl).await?;
     Ok(())
 }
 
-pub fn get_session<C: ConnectionLike>(
+pub async fn get_session<C: SessionStore>(
     redis_conn: &mut C,
     token: String,
 ) -> anyhow::Result<Option<SessionData>> {
-    let res: Option<String> = redis::cmd("get").arg(token).query(redis_conn)?;
+    let res = redis_conn.get(&token).await?;
     if res.is_none() {
         return Ok(None);
     }
     let res = res.unwrap();
     let session_data: SessionData = serde_json::from_str(res.as_str())?;
+    let revoked_at: Option<i64> = redis_conn
+        .get(&sessions_revoked_key(&session_data.user_id))
+        .await?
+        .and_then(|x| x.parse().ok());
+    if revoked_at.is_some_and(|x| session_data.created_at <= x) {
+        return Ok(None);
+    }
     Ok(Some(session_data))
 }
 
-pub fn remove_session<C: ConnectionLike>(
+/// Reject every session of the user created before now, the client has to refresh its
+/// token. The marker outlives the sessions it applies to, ttl in seconds
+pub async fn revoke_user_sessions<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+    ttl: u64,
+) -> anyhow::Result<()> {
+    redis_conn
+        .set_ex(
+            &sessions_revoked_key(&user_id.to_string()),
+            &Utc::now().timestamp_millis().to_string(),
+            ttl,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Sign the user out of every session but the one of `token`, which keeps its refresh
+/// token. ttl of the revocation marker in seconds, see revoke_user_sessions
+pub async fn revoke_other_user_sessions<C: SessionStore>(
+    redis_conn: &mut C,
+    user_id: &Uuid,
+    token: &str,
+    ttl: u64,
+) -> anyhow::Result<()> {
+    revoke_user_sessions(redis_conn, user_id, ttl).await?;
+    // the kept session is registered again as created after the marker
+    let mut kept_refresh_token = None;
+    let res = redis_conn.get(token).await?;
+    if let Some(res) = res {
+        let mut session_data: SessionData = serde_json::from_str(res.as_str())?;
+        let token_ttl = redis_conn.ttl(token).await?;
+        if token_ttl > 0 {
+            session_data.created_at = Utc::now().timestamp_millis() + 1;
+            redis_conn
+                .set_ex(
+                    token,
+                    &serde_json::to_string(&session_data)?,
+                    token_ttl as u64,
+                )
+                .await?;
+            kept_refresh_token = Some(session_data.refresh_token);
+        }
+    }
+    let tokens_key = user_refresh_tokens_key(&user_id.to_string());
+    for refresh_token in redis_conn.smembers(&tokens_key).await? {
+        if kept_refresh_token.as_ref() == Some(&refresh_token) {
+            continue;
+        }
+        redis_conn.del(&refresh_token_key(&refresh_token)).await?;
+        redis_conn.srem(&tokens_key, &refresh_token).await?;
+    }
+    Ok(())
+}
+
+pub async fn remove_session<C: SessionStore>(
     redis_conn: &mut C,
     token: String,
 ) -> anyhow::Result<bool> {
-    let res: Option<String> = redis::cmd("get").arg(&token).query(redis_conn)?;
+    let res = redis_conn.get(&token).await?;
     if res.is_none() {
         return Ok(false);
     }
     let res = res.unwrap();
     let session_data: SessionData = serde_json::from_str(res.as_str())?;
-    redis::cmd("del")
-        .arg(session_data.refresh_token)
-        .exec(redis_conn)?;
-    redis::cmd("del").arg(token).exec(redis_conn)?;
+    redis_conn
+        .del(&refresh_token_key(&session_data.refresh_token))
+        .await?;
+    redis_conn
+        .srem(
+            &user_refresh_tokens_key(&session_data.user_id),
+            &session_data.refresh_token,
+        )
+        .await?;
+    redis_conn.del(&token).await?;
     Ok(true)
 }
@@ -0,0 +1,611 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use deadpool_redis::{Connection, Pool, PoolConfig, PoolError, Runtime};
+use redis::{aio::ConnectionLike, ErrorKind, RedisError, RedisResult};
+use tokio::task::JoinHandle;
+
+use crate::settings::Config;
+
+pub const SESSION_STORE_REDIS: &str = "redis";
+pub const SESSION_STORE_MEMORY: &str = "memory";
+pub const DEFAULT_REDIS_POOL_MAX_SIZE: usize = 16;
+pub const DEFAULT_REDIS_POOL_TIMEOUT: u64 = 5;
+/// How often spawn_memory_store_sweeper drops expired MemoryStore entries
+pub const MEMORY_STORE_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The string and set commands sessions and caches use, answered by redis or by
+/// MemoryStore. Ttls are in seconds
+pub trait SessionStore: Send {
+    fn get(&mut self, key: &str) -> impl Future<Output = RedisResult<Option<String>>> + Send;
+
+    fn set_ex(
+        &mut self,
+        key: &str,
+        value: &str,
+        ttl: u64,
+    ) -> impl Future<Output = RedisResult<()>> + Send;
+
+    /// Number of keys removed, 0 when it did not exist
+    fn del(&mut self, key: &str) -> impl Future<Output = RedisResult<u64>> + Send;
+
+    /// Seconds left, -1 for a key without expiry and -2 for a missing key
+    fn ttl(&mut self, key: &str) -> impl Future<Output = RedisResult<i64>> + Send;
+
+    /// Add 1 to an integer value and return it, a missing key starts from 0 without expiry
+    fn incr(&mut self, key: &str) -> impl Future<Output = RedisResult<i64>> + Send;
+
+    /// False when the key does not exist
+    fn expire(&mut self, key: &str, ttl: u64) -> impl Future<Output = RedisResult<bool>> + Send;
+
+    /// Every key matching a glob pattern of `*` and `?`, walked with SCAN on redis
+    fn scan_match(
+        &mut self,
+        pattern: &str,
+    ) -> impl Future<Output = RedisResult<Vec<String>>> + Send;
+
+    /// Add a member to the set, a missing key starts empty without expiry
+    fn sadd(&mut self, key: &str, member: &str) -> impl Future<Output = RedisResult<()>> + Send;
+
+    /// Remove a member from the set, the key is deleted once the set is empty
+    fn srem(&mut self, key: &str, member: &str) -> impl Future<Output = RedisResult<()>> + Send;
+
+    /// Members of the set, empty for a missing key
+    fn smembers(&mut self, key: &str) -> impl Future<Output = RedisResult<Vec<String>>> + Send;
+}
+
+impl<C: ConnectionLike + Send> SessionStore for C {
+    async fn get(&mut self, key: &str) -> RedisResult<Option<String>> {
+        redis::cmd("GET").arg(key).query_async(self).await
+    }
+
+    async fn set_ex(&mut self, key: &str, value: &str, ttl: u64) -> RedisResult<()> {
+        redis::Cmd::set_ex(key, value, ttl).exec_async(self).await
+    }
+
+    async fn del(&mut self, key: &str) -> RedisResult<u64> {
+        redis::cmd("DEL").arg(key).query_async(self).await
+    }
+
+    async fn ttl(&mut self, key: &str) -> RedisResult<i64> {
+        redis::cmd("TTL").arg(key).query_async(self).await
+    }
+
+    async fn incr(&mut self, key: &str) -> RedisResult<i64> {
+        redis::cmd("INCR").arg(key).query_async(self).await
+    }
+
+    async fn expire(&mut self, key: &str, ttl: u64) -> RedisResult<bool> {
+        redis::cmd("EXPIRE")
+            .arg(key)
+            .arg(ttl)
+            .query_async(self)
+            .await
+    }
+
+    async fn scan_match(&mut self, pattern: &str) -> RedisResult<Vec<String>> {
+        // SCAN walks the keyspace in pages instead of blocking redis like KEYS
+        let mut keys = vec![];
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, page): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query_async(self)
+                .await?;
+            keys.extend(page);
+            if next == 0 {
+                return Ok(keys);
+            }
+            cursor = next;
+        }
+    }
+
+    async fn sadd(&mut self, key: &str, member: &str) -> RedisResult<()> {
+        redis::cmd("SADD")
+            .arg(key)
+            .arg(member)
+            .exec_async(self)
+            .await
+    }
+
+    async fn srem(&mut self, key: &str, member: &str) -> RedisResult<()> {
+        redis::cmd("SREM")
+            .arg(key)
+            .arg(member)
+            .exec_async(self)
+            .await
+    }
+
+    async fn smembers(&mut self, key: &str) -> RedisResult<Vec<String>> {
+        redis::cmd("SMEMBERS").arg(key).query_async(self).await
+    }
+}
+
+/// `*` and `?` glob of scan_match patterns
+fn glob_match(pattern: &[u8], val: &[u8]) -> bool {
+    match (pattern.first(), val.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], val) || (!val.is_empty() && glob_match(pattern, &val[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &val[1..]),
+        (Some(x), Some(y)) if x == y => glob_match(&pattern[1..], &val[1..]),
+        _ => false,
+    }
+}
+
+enum Value {
+    String(String),
+    Set(HashSet<String>),
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Option<Instant>,
+}
+
+/// Error redis answers with for a string command on a set and the other way around
+fn wrong_type() -> RedisError {
+    RedisError::from((ErrorKind::TypeError, "key holds the wrong kind of value"))
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|x| x <= now)
+    }
+}
+
+/// In-process replacement for redis on single instance deployments and tests. Expired
+/// entries are dropped when they are read or scanned, and by spawn_memory_store_sweeper
+/// for the keys nobody reads again. Clones share the same data.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `f` on the live entry of the key, an expired one is removed first
+    fn with_entry<T>(&self, key: &str, f: impl FnOnce(Option<&mut Entry>, Instant) -> T) -> T {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_some_and(|x| x.is_expired(now)) {
+            entries.remove(key);
+        }
+        f(entries.get_mut(key), now)
+    }
+
+    fn insert(&self, key: &str, value: String, expires_at: Option<Instant>) {
+        self.entries.lock().unwrap().insert(
+            key.to_string(),
+            Entry {
+                value: Value::String(value),
+                expires_at,
+            },
+        );
+    }
+
+    /// Drop every expired entry, returns how many
+    pub fn remove_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.retain(|_, x| !x.is_expired(now));
+        count - entries.len()
+    }
+}
+
+/// Drop the expired entries of the store every `interval`, stops once every clone of the
+/// store is dropped
+pub fn spawn_memory_store_sweeper(store: &MemoryStore, interval: Duration) -> JoinHandle<()> {
+    let entries = Arc::downgrade(&store.entries);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let store = match entries.upgrade() {
+                Some(entries) => MemoryStore { entries },
+                None => break,
+            };
+            let removed = store.remove_expired();
+            if removed > 0 {
+                tracing::debug!("removed {} expired memory store entries", removed);
+            }
+        }
+    })
+}
+
+/// Answered in place, nothing is awaited
+impl SessionStore for MemoryStore {
+    async fn get(&mut self, key: &str) -> RedisResult<Option<String>> {
+        self.with_entry(key, |entry, _| match entry.map(|x| &x.value) {
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(Value::Set(_)) => Err(wrong_type()),
+            None => Ok(None),
+        })
+    }
+
+    async fn set_ex(&mut self, key: &str, value: &str, ttl: u64) -> RedisResult<()> {
+        let expires_at = Instant::now() + Duration::from_secs(ttl);
+        self.insert(key, value.to_string(), Some(expires_at));
+        Ok(())
+    }
+
+    async fn del(&mut self, key: &str) -> RedisResult<u64> {
+        let now = Instant::now();
+        let removed = self.entries.lock().unwrap().remove(key);
+        Ok(removed.is_some_and(|x| !x.is_expired(now)) as u64)
+    }
+
+    async fn ttl(&mut self, key: &str) -> RedisResult<i64> {
+        Ok(self.with_entry(key, |entry, now| match entry {
+            Some(entry) => entry
+                .expires_at
+                .map(|x| x.duration_since(now).as_secs() as i64)
+                .unwrap_or(-1),
+            None => -2,
+        }))
+    }
+
+    async fn incr(&mut self, key: &str) -> RedisResult<i64> {
+        self.with_entry(key, |entry, _| match entry.map(|x| &mut x.value) {
+            Some(Value::String(value)) => {
+                let count = value.parse::<i64>().map_err(|_| {
+                    RedisError::from((ErrorKind::TypeError, "value is not an integer"))
+                })? + 1;
+                *value = count.to_string();
+                Ok(Some(count))
+            }
+            Some(Value::Set(_)) => Err(wrong_type()),
+            None => Ok(None),
+        })
+        .map(|count| match count {
+            Some(count) => count,
+            None => {
+                self.insert(key, "1".to_string(), None);
+                1
+            }
+        })
+    }
+
+    async fn expire(&mut self, key: &str, ttl: u64) -> RedisResult<bool> {
+        Ok(self.with_entry(key, |entry, now| match entry {
+            Some(entry) => {
+                entry.expires_at = Some(now + Duration::from_secs(ttl));
+                true
+            }
+            None => false,
+        }))
+    }
+
+    async fn scan_match(&mut self, pattern: &str) -> RedisResult<Vec<String>> {
+        // a scan visits every entry anyway, the expired ones are dropped on the way
+        self.remove_expired();
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .keys()
+            .filter(|key| glob_match(pattern.as_bytes(), key.as_bytes()))
+            .cloned()
+            .collect())
+    }
+
+    async fn sadd(&mut self, key: &str, member: &str) -> RedisResult<()> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_some_and(|x| x.is_expired(now)) {
+            entries.remove(key);
+        }
+        let entry = entries.entry(key.to_string()).or_insert_with(|| Entry {
+            value: Value::Set(HashSet::new()),
+            expires_at: None,
+        });
+        match &mut entry.value {
+            Value::Set(members) => {
+                members.insert(member.to_string());
+                Ok(())
+            }
+            Value::String(_) => Err(wrong_type()),
+        }
+    }
+
+    async fn srem(&mut self, key: &str, member: &str) -> RedisResult<()> {
+        let is_empty = self.with_entry(key, |entry, _| match entry.map(|x| &mut x.value) {
+            Some(Value::Set(members)) => {
+                members.remove(member);
+                Ok(members.is_empty())
+            }
+            Some(Value::String(_)) => Err(wrong_type()),
+            None => Ok(false),
+        })?;
+        if is_empty {
+            self.entries.lock().unwrap().remove(key);
+        }
+        Ok(())
+    }
+
+    async fn smembers(&mut self, key: &str) -> RedisResult<Vec<String>> {
+        self.with_entry(key, |entry, _| match entry.map(|x| &x.value) {
+            Some(Value::Set(members)) => Ok(members.iter().cloned().collect()),
+            Some(Value::String(_)) => Err(wrong_type()),
+            None => Ok(vec![]),
+        })
+    }
+}
+
+/// Async redis pool of REDIS_URL with REDIS_POOL_MAX_SIZE connections, getting one fails
+/// after waiting REDIS_POOL_TIMEOUT seconds
+pub fn create_redis_pool(config: &Config) -> anyhow::Result<Pool> {
+    let mut pool_config = PoolConfig::new(
+        config
+            .redis_pool_max_size
+            .unwrap_or(DEFAULT_REDIS_POOL_MAX_SIZE),
+    );
+    pool_config.timeouts.wait = Some(Duration::from_secs(
+        config
+            .redis_pool_timeout
+            .unwrap_or(DEFAULT_REDIS_POOL_TIMEOUT),
+    ));
+    let mut redis_config = deadpool_redis::Config::from_url(config.redis_url.clone());
+    redis_config.pool = Some(pool_config);
+    Ok(redis_config.create_pool(Some(Runtime::Tokio1))?)
+}
+
+/// Where sessions and caches are kept, SESSION_STORE=redis or memory. Handlers take a
+/// connection with `get().await` and pass it to functions generic over SessionStore
+#[derive(Clone)]
+pub enum SessionPool {
+    Redis(Pool),
+    Memory(MemoryStore),
+}
+
+impl From<Pool> for SessionPool {
+    fn from(val: Pool) -> Self {
+        Self::Redis(val)
+    }
+}
+
+impl From<MemoryStore> for SessionPool {
+    fn from(val: MemoryStore) -> Self {
+        Self::Memory(val)
+    }
+}
+
+impl SessionPool {
+    pub async fn get(&self) -> Result<SessionConn, PoolError> {
+        match self {
+            Self::Redis(pool) => Ok(SessionConn::Redis(pool.get().await?)),
+            Self::Memory(store) => Ok(SessionConn::Memory(store.clone())),
+        }
+    }
+}
+
+pub enum SessionConn {
+    Redis(Connection),
+    Memory(MemoryStore),
+}
+
+impl SessionStore for SessionConn {
+    async fn get(&mut self, key: &str) -> RedisResult<Option<String>> {
+        match self {
+            Self::Redis(conn) => conn.get(key).await,
+            Self::Memory(store) => store.get(key).await,
+        }
+    }
+
+    async fn set_ex(&mut self, key: &str, value: &str, ttl: u64) -> RedisResult<()> {
+        match self {
+            Self::Redis(conn) => conn.set_ex(key, value, ttl).await,
+            Self::Memory(store) => store.set_ex(key, value, ttl).await,
+        }
+    }
+
+    async fn del(&mut self, key: &str) -> RedisResult<u64> {
+        match self {
+            Self::Redis(conn) => conn.del(key).await,
+            Self::Memory(store) => store.del(key).await,
+        }
+    }
+
+    async fn ttl(&mut self, key: &str) -> RedisResult<i64> {
+        match self {
+            Self::Redis(conn) => conn.ttl(key).await,
+            Self::Memory(store) => store.ttl(key).await,
+        }
+    }
+
+    async fn incr(&mut self, key: &str) -> RedisResult<i64> {
+        match self {
+            Self::Redis(conn) => conn.incr(key).await,
+            Self::Memory(store) => store.incr(key).await,
+        }
+    }
+
+    async fn expire(&mut self, key: &str, ttl: u64) -> RedisResult<bool> {
+        match self {
+            Self::Redis(conn) => conn.expire(key, ttl).await,
+            Self::Memory(store) => store.expire(key, ttl).await,
+        }
+    }
+
+    async fn scan_match(&mut self, pattern: &str) -> RedisResult<Vec<String>> {
+        match self {
+            Self::Redis(conn) => conn.scan_match(pattern).await,
+            Self::Memory(store) => store.scan_match(pattern).await,
+        }
+    }
+
+    async fn sadd(&mut self, key: &str, member: &str) -> RedisResult<()> {
+        match self {
+            Self::Redis(conn) => conn.sadd(key, member).await,
+            Self::Memory(store) => store.sadd(key, member).await,
+        }
+    }
+
+    async fn srem(&mut self, key: &str, member: &str) -> RedisResult<()> {
+        match self {
+            Self::Redis(conn) => conn.srem(key, member).await,
+            Self::Memory(store) => store.srem(key, member).await,
+        }
+    }
+
+    async fn smembers(&mut self, key: &str) -> RedisResult<Vec<String>> {
+        match self {
+            Self::Redis(conn) => conn.smembers(key).await,
+            Self::Memory(store) => store.smembers(key).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Local;
+    use tokio::time::sleep;
+    use uuid::Uuid;
+
+    use crate::{
+        core::{
+            session::{add_session_with_ttl, get_session, remove_session, scan_keys},
+            session_store::{
+                create_redis_pool, glob_match, spawn_memory_store_sweeper, MemoryStore,
+                SessionPool, SessionStore, DEFAULT_REDIS_POOL_MAX_SIZE,
+            },
+        },
+        model::user::{User, UserStatus},
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_create_redis_pool() -> anyhow::Result<()> {
+        // When
+        let mut config = get_config();
+        config.redis_pool_max_size = None;
+        let pool = create_redis_pool(&config)?;
+
+        // Expect connections are only opened on demand
+        assert_eq!(pool.status().max_size, DEFAULT_REDIS_POOL_MAX_SIZE);
+        assert_eq!(pool.status().size, 0);
+
+        // When
+        config.redis_pool_max_size = Some(4);
+        config.redis_pool_timeout = Some(1);
+
+        // Expect
+        assert_eq!(create_redis_pool(&config)?.status().max_size, 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() -> anyhow::Result<()> {
+        // Given
+        let store = MemoryStore::new();
+        let mut conn = store.clone();
+
+        // When
+        conn.set_ex("core:a", "1", 1).await?;
+        conn.set_ex("core:b", "b", 60).await?;
+        conn.incr("core:c").await?;
+
+        // Expect shared between clones
+        assert_eq!(store.clone().get("core:a").await?, Some("1".to_string()));
+        let mut keys = scan_keys(&mut conn, "core:*").await?;
+        keys.sort();
+        assert_eq!(keys, vec!["core:a", "core:b", "core:c"]);
+        assert_eq!(conn.incr("core:c").await?, 2);
+        assert_eq!(conn.ttl("core:c").await?, -1);
+        assert!(conn.expire("core:c", 60).await?);
+        assert!(conn.ttl("core:c").await? > 58);
+        assert!(conn.incr("core:b").await.is_err());
+        assert_eq!(conn.del("core:b").await?, 1);
+        assert_eq!(conn.del("core:b").await?, 0);
+        assert_eq!(conn.ttl("core:b").await?, -2);
+        assert!(!conn.expire("core:b", 60).await?);
+
+        // When ttl passed
+        sleep(Duration::from_millis(1100)).await;
+        conn.set_ex("core:d", "4", 60).await?;
+
+        // Expect writes leave the expired entry until it is read
+        assert!(store.entries.lock().unwrap().contains_key("core:a"));
+        assert!(conn.get("core:a").await?.is_none());
+        assert!(!store.entries.lock().unwrap().contains_key("core:a"));
+
+        // When an entry nobody reads expires
+        conn.expire("core:d", 0).await?;
+        spawn_memory_store_sweeper(&store, Duration::from_millis(10));
+        sleep(Duration::from_millis(50)).await;
+
+        // Expect the sweeper dropped it
+        assert!(!store.entries.lock().unwrap().contains_key("core:d"));
+        assert!(store.entries.lock().unwrap().contains_key("core:c"));
+        assert!(glob_match(b"core:?:*", b"core:1:session"));
+
+        // When
+        conn.sadd("core:set", "a").await?;
+        conn.sadd("core:set", "b").await?;
+        conn.srem("core:set", "a").await?;
+
+        // Expect
+        assert_eq!(conn.smembers("core:set").await?, vec!["b"]);
+        assert!(conn.get("core:set").await.is_err());
+        assert!(conn.sadd("core:c", "a").await.is_err());
+        conn.srem("core:set", "b").await?;
+        assert_eq!(conn.ttl("core:set").await?, -2);
+        assert!(!glob_match(b"core:*", b"other"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_session() -> anyhow::Result<()> {
+        // Given
+        let pool = SessionPool::from(MemoryStore::new());
+        let mut conn = pool.get().await?;
+        let now = Local::now().fixed_offset();
+        let user = User {
+            id: Uuid::now_v7(),
+            user_name: "test_user".to_string(),
+            password: "".to_string(),
+            is_2faenabled: Some(false),
+            created_by: None,
+            updated_by: None,
+            created_date: Some(now),
+            updated_date: Some(now),
+            deleted_date: None,
+            status: UserStatus::Active,
+            expires_at: None,
+            scopes: None,
+        };
+
+        // When
+        add_session_with_ttl(
+            &mut conn,
+            &user,
+            "token".to_string(),
+            "refresh".to_string(),
+            60,
+            None,
+        )
+        .await?;
+
+        // Expect visible to another connection of the pool
+        let session = get_session(&mut pool.get().await?, "token".to_string())
+            .await?
+            .unwrap();
+        assert_eq!(session.user_id, user.id.to_string());
+        assert!(remove_session(&mut conn, "token".to_string()).await?);
+        assert!(get_session(&mut conn, "token".to_string()).await?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use std::{fmt, future::Future, str::FromStr, time::Duration as StdDuration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Config;
+
+const SEND_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+pub const TWILIO_API_URL: &str = "https://api.twilio.com";
+pub const VONAGE_API_URL: &str = "https://rest.nexmo.com";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OutgoingSms {
+    /// E.164, e.g. +6281234567890
+    pub to: String,
+    pub body: String,
+}
+
+/// Delivers SMS, implemented per provider so tests can record instead of sending
+pub trait SmsSender {
+    fn send(&self, sms: &OutgoingSms) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmsProvider {
+    Twilio,
+    Vonage,
+    /// Only log the message, for development
+    Log,
+}
+
+impl FromStr for SmsProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "twilio" => Ok(SmsProvider::Twilio),
+            "vonage" => Ok(SmsProvider::Vonage),
+            "log" => Ok(SmsProvider::Log),
+            _ => anyhow::bail!("must be twilio, vonage or log, got {}", s),
+        }
+    }
+}
+
+impl fmt::Display for SmsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = match self {
+            SmsProvider::Twilio => "twilio",
+            SmsProvider::Vonage => "vonage",
+            SmsProvider::Log => "log",
+        };
+        write!(f, "{}", val)
+    }
+}
+
+/// Credentials shared by the providers, SMS_API_KEY is the Twilio account SID
+struct SmsCredentials {
+    api_key: String,
+    api_secret: String,
+    from: String,
+}
+
+fn sms_credentials(config: &Config, provider: SmsProvider) -> anyhow::Result<SmsCredentials> {
+    let required = |name: &str, val: &Option<String>| match val.as_deref().filter(|x| !x.is_empty())
+    {
+        Some(val) => Ok(val.to_string()),
+        None => anyhow::bail!("{} is required with SMS_PROVIDER={}", name, provider),
+    };
+    Ok(SmsCredentials {
+        api_key: required("SMS_API_KEY", &config.sms_api_key)?,
+        api_secret: required("SMS_API_SECRET", &config.sms_api_secret)?,
+        from: required("SMS_FROM", &config.sms_from)?,
+    })
+}
+
+pub struct TwilioSender {
+    pub account_sid: String,
+    pub auth_token: String,
+    pub from: String,
+    pub api_url: String,
+}
+
+impl SmsSender for TwilioSender {
+    async fn send(&self, sms: &OutgoingSms) -> anyhow::Result<()> {
+        let http = reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?;
+        let resp = http
+            .post(format!(
+                "{}/2010-04-01/Accounts/{}/Messages.json",
+                self.api_url.trim_end_matches('/'),
+                self.account_sid
+            ))
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&[
+                ("From", self.from.as_str()),
+                ("To", sms.to.as_str()),
+                ("Body", sms.body.as_str()),
+            ])
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("twilio answered {}", resp.status());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VonageMessage {
+    status: String,
+    #[serde(rename = "error-text")]
+    error_text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VonageResponse {
+    messages: Vec<VonageMessage>,
+}
+
+/// Vonage answers 200 with a per message status, 0 means accepted
+fn vonage_error(resp: &VonageResponse) -> Option<String> {
+    match resp.messages.iter().find(|x| x.status != "0") {
+        Some(message) => Some(format!(
+            "vonage status {}: {}",
+            message.status,
+            message.error_text.clone().unwrap_or_default()
+        )),
+        None if resp.messages.is_empty() => Some("vonage accepted no message".to_string()),
+        None => None,
+    }
+}
+
+pub struct VonageSender {
+    pub api_key: String,
+    pub api_secret: String,
+    pub from: String,
+    pub api_url: String,
+}
+
+impl SmsSender for VonageSender {
+    async fn send(&self, sms: &OutgoingSms) -> anyhow::Result<()> {
+        let http = reqwest::Client::builder().timeout(SEND_TIMEOUT).build()?;
+        let resp = http
+            .post(format!("{}/sms/json", self.api_url.trim_end_matches('/')))
+            .json(&serde_json::json!({
+                "api_key": self.api_key,
+                "api_secret": self.api_secret,
+                "from": self.from,
+                "to": sms.to.trim_start_matches('+'),
+                "text": sms.body,
+            }))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("vonage answered {}", resp.status());
+        }
+        if let Some(err) = vonage_error(&resp.json().await?) {
+            anyhow::bail!(err);
+        }
+        Ok(())
+    }
+}
+
+pub struct LogSender;
+
+impl SmsSender for LogSender {
+    async fn send(&self, sms: &OutgoingSms) -> anyhow::Result<()> {
+        tracing::info!("sms to {}: {}", sms.to, sms.body);
+        Ok(())
+    }
+}
+
+/// Sender selected by SMS_PROVIDER
+pub enum ConfiguredSmsSender {
+    Twilio(TwilioSender),
+    Vonage(VonageSender),
+    Log(LogSender),
+}
+
+impl ConfiguredSmsSender {
+    /// SMS_PROVIDER defaults to log
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let provider = match &config.sms_provider {
+            Some(val) => val.parse()?,
+            None => SmsProvider::Log,
+        };
+        let api_url = config.sms_api_url.clone().filter(|x| !x.is_empty());
+        match provider {
+            SmsProvider::Twilio => {
+                let credentials = sms_credentials(config, provider)?;
+                Ok(Self::Twilio(TwilioSender {
+                    account_sid: credentials.api_key,
+                    auth_token: credentials.api_secret,
+                    from: credentials.from,
+                    api_url: api_url.unwrap_or(TWILIO_API_URL.to_string()),
+                }))
+            }
+            SmsProvider::Vonage => {
+                let credentials = sms_credentials(config, provider)?;
+                Ok(Self::Vonage(VonageSender {
+                    api_key: credentials.api_key,
+                    api_secret: credentials.api_secret,
+                    from: credentials.from,
+                    api_url: api_url.unwrap_or(VONAGE_API_URL.to_string()),
+                }))
+            }
+            SmsProvider::Log => Ok(Self::Log(LogSender)),
+        }
+    }
+
+    pub fn provider(&self) -> SmsProvider {
+        match self {
+            Self::Twilio(_) => SmsProvider::Twilio,
+            Self::Vonage(_) => SmsProvider::Vonage,
+            Self::Log(_) => SmsProvider::Log,
+        }
+    }
+}
+
+impl SmsSender for ConfiguredSmsSender {
+    async fn send(&self, sms: &OutgoingSms) -> anyhow::Result<()> {
+        match self {
+            Self::Twilio(sender) => sender.send(sms).await,
+            Self::Vonage(sender) => sender.send(sms).await,
+            Self::Log(sender) => sender.send(sms).await,
+        }
+    }
+}
+
+/// Send with the provider selected by SMS_PROVIDER
+pub async fn send_sms(config: &Config, sms: &OutgoingSms) -> anyhow::Result<()> {
+    ConfiguredSmsSender::from_config(config)?.send(sms).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::sms::{vonage_error, ConfiguredSmsSender, SmsProvider, VonageResponse},
+        settings::get_config,
+    };
+
+    #[test]
+    fn test_configured_sms_sender() {
+        // Given
+        let mut config = get_config();
+        config.sms_provider = None;
+
+        // Expect log only by default
+        assert_eq!(
+            ConfiguredSmsSender::from_config(&config)
+                .unwrap()
+                .provider(),
+            SmsProvider::Log
+        );
+
+        // Expect providers to need credentials
+        config.sms_provider = Some("twilio".to_string());
+        config.sms_api_key = None;
+        assert!(ConfiguredSmsSender::from_config(&config).is_err());
+        config.sms_api_key = Some("AC0123".to_string());
+        config.sms_api_secret = Some("secret".to_string());
+        config.sms_from = Some("+15005550006".to_string());
+        assert_eq!(
+            ConfiguredSmsSender::from_config(&config)
+                .unwrap()
+                .provider(),
+            SmsProvider::Twilio
+        );
+        assert!("sns".parse::<SmsProvider>().is_err());
+    }
+
+    #[test]
+    fn test_vonage_error() {
+        let accepted: VonageResponse =
+            serde_json::from_str(r#"{"messages": [{"status": "0"}]}"#).unwrap();
+        assert!(vonage_error(&accepted).is_none());
+        let rejected: VonageResponse = serde_json::from_str(
+            r#"{"messages": [{"status": "4", "error-text": "Bad Credentials"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vonage_error(&rejected).unwrap(),
+            "vonage status 4: Bad Credentials"
+        );
+    }
+}
@@ -6,6 +6,8 @@ use sqlx::{
 };
 use uuid::Uuid;
 
+use crate::model::user::TABLE_NAME as USER_TABLE_NAME;
+
 #[derive(Clone)]
 pub enum SqlxBinds {
     String(String),
@@ -49,6 +51,104 @@ pub fn binds_query_as<'a, T: for<'r> sqlx::FromRow<'r, PgRow>>(
     q
 }
 
+/// Requested order of a paginate query, see Sort::order_by
+#[derive(Clone, Debug, Default)]
+pub struct Sort {
+    pub sort_by: Option<String>,
+    /// asc or desc, asc by default when sort_by is given
+    pub sort_dir: Option<String>,
+}
+
+/// sort_by or sort_dir outside of what the paginate query allows
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("{field} must be one of {allowed}")]
+pub struct InvalidSort {
+    pub field: String,
+    pub allowed: String,
+}
+
+impl Sort {
+    pub fn new(sort_by: Option<String>, sort_dir: Option<String>) -> Self {
+        Self { sort_by, sort_dir }
+    }
+
+    /// ORDER BY items for query_builder. sort_by must be one of `columns`, the caller's
+    /// whitelist, since it is written into the statement. Without sort_by the rows are
+    /// ordered by `default`, written as column and direction e.g. `updated_date DESC`.
+    /// id breaks ties so pages do not overlap
+    pub fn order_by(&self, columns: &[&str], default: &str) -> Result<Vec<String>, InvalidSort> {
+        let direction = match self.sort_dir.as_deref().map(|x| x.trim().to_lowercase()) {
+            None => None,
+            Some(val) if val == "asc" => Some("ASC"),
+            Some(val) if val == "desc" => Some("DESC"),
+            Some(_) => {
+                return Err(InvalidSort {
+                    field: "sort_dir".to_string(),
+                    allowed: "asc, desc".to_string(),
+                })
+            }
+        };
+        let (column, direction) = match self.sort_by.as_deref().map(str::trim) {
+            None => {
+                let (column, default_direction) =
+                    default.split_once(' ').unwrap_or((default, "ASC"));
+                (column, direction.unwrap_or(default_direction))
+            }
+            Some(val) => match columns.iter().find(|x| **x == val) {
+                Some(column) => (*column, direction.unwrap_or("ASC")),
+                None => {
+                    return Err(InvalidSort {
+                        field: "sort_by".to_string(),
+                        allowed: columns.join(", "),
+                    })
+                }
+            },
+        };
+        let mut res = vec![format!("{} {}", column, direction)];
+        if column != "id" {
+            res.push(format!("id {}", direction));
+        }
+        Ok(res)
+    }
+
+    /// Same as order_by for a query naming the table `alias`
+    pub fn order_by_aliased(
+        &self,
+        alias: &str,
+        columns: &[&str],
+        default: &str,
+    ) -> Result<Vec<String>, InvalidSort> {
+        Ok(self
+            .order_by(columns, default)?
+            .into_iter()
+            .map(|x| format!("{}.{}", alias, x))
+            .collect())
+    }
+}
+
+/// Select list of a list query on `alias` for model::audited::Audited rows, see audited_from
+pub fn audited_select(alias: &str) -> String {
+    format!(
+        "{}.*, created_by_user.user_name AS created_by_name, updated_by_user.user_name AS updated_by_name",
+        alias
+    )
+}
+
+/// `table_name` as `alias` joined with the users in its created_by and updated_by, soft
+/// deleted users are left out. Filters and orders must name the columns with `alias`
+pub fn audited_from(table_name: &str, alias: &str) -> String {
+    format!(
+        "{table} AS {alias} \
+        LEFT JOIN {user} AS created_by_user \
+        ON created_by_user.id = {alias}.created_by AND created_by_user.deleted_date IS NULL \
+        LEFT JOIN {user} AS updated_by_user \
+        ON updated_by_user.id = {alias}.updated_by AND updated_by_user.deleted_date IS NULL",
+        table = table_name,
+        alias = alias,
+        user = USER_TABLE_NAME,
+    )
+}
+
 pub fn query_builder(
     select: Option<String>,
     table_name: &str,
@@ -91,13 +191,13 @@ pub fn query_builder(
     }
 
     // Limit
-    if limit.is_some() {
-        stmt.push_str(format!(" LIMIT {}", limit.unwrap()).as_str());
+    if let Some(limit) = limit {
+        stmt.push_str(format!(" LIMIT {}", limit).as_str());
     }
 
     // Offset
-    if offset.is_some() {
-        stmt.push_str(format!(" OFFSET {}", offset.unwrap()).as_str());
+    if let Some(offset) = offset {
+        stmt.push_str(format!(" OFFSET {}", offset).as_str());
     }
     stmt
 }
@@ -121,3 +221,49 @@ pub fn in_helper(
         filters.push(query);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::core::sqlx_utils::{InvalidSort, Sort};
+
+    const COLUMNS: &[&str] = &["role_name", "updated_date"];
+
+    #[test]
+    fn test_sort_order_by() {
+        assert_eq!(
+            Sort::default().order_by(COLUMNS, "updated_date DESC"),
+            Ok(vec!["updated_date DESC".to_string(), "id DESC".to_string()])
+        );
+        assert_eq!(
+            Sort::new(Some("role_name".to_string()), None).order_by(COLUMNS, "updated_date DESC"),
+            Ok(vec!["role_name ASC".to_string(), "id ASC".to_string()])
+        );
+        assert_eq!(
+            Sort::new(Some("role_name".to_string()), Some("DESC".to_string()))
+                .order_by(COLUMNS, "updated_date DESC"),
+            Ok(vec!["role_name DESC".to_string(), "id DESC".to_string()])
+        );
+        assert_eq!(
+            Sort::new(Some("password; --".to_string()), None)
+                .order_by(COLUMNS, "updated_date DESC"),
+            Err(InvalidSort {
+                field: "sort_by".to_string(),
+                allowed: "role_name, updated_date".to_string(),
+            })
+        );
+        assert_eq!(
+            Sort::new(None, Some("up".to_string()))
+                .order_by(COLUMNS, "updated_date DESC")
+                .unwrap_err()
+                .field,
+            "sort_dir"
+        );
+        assert_eq!(
+            Sort::default().order_by_aliased("r", COLUMNS, "updated_date DESC"),
+            Ok(vec![
+                "r.updated_date DESC".to_string(),
+                "r.id DESC".to_string()
+            ])
+        );
+    }
+}
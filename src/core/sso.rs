@@ -0,0 +1,779 @@
+use std::collections::{HashMap, HashSet};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, FixedOffset};
+use jsonwebtoken::{
+    decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, TokenData, Validation,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{security::hash_password, session_store::SessionStore},
+    model::{
+        scim_provisioning_event::EVENT_CREATE,
+        sso_jit_rule::SsoJitRule,
+        sso_provider::{SsoProvider, PROTOCOL_GITHUB, PROTOCOL_GOOGLE},
+        sso_role_mapping::SsoRoleMapping,
+        user::{User, UserStatus},
+        user_group_roles::UserGroupRoles,
+        user_identity::UserIdentity,
+        user_profile::UserProfile,
+    },
+    repository::{
+        scim_provisioning_event::enqueue_scim_event,
+        sso_jit_rule::get_sso_jit_rule_by_provider,
+        sso_role_mapping::get_sso_role_mapping_by_provider,
+        user::{create_user, get_user_by_id, get_user_by_username, get_user_group_roles_by_user},
+        user_group_roles::{add_user_group_roles, delete_user_group_roles_by_id},
+        user_identity::{create_user_identity, get_user_identity},
+    },
+};
+
+pub type SsoClaims = HashMap<String, Value>;
+
+/// user_identity provider of sso linked users starts with it, followed by the provider name
+pub const SSO_IDENTITY_PREFIX: &str = "sso:";
+pub const AUTHORIZATION_STATE_KEY_PREFIX: &str = "sso_state:";
+/// Seconds the user has to come back from the provider with the code
+pub const AUTHORIZATION_STATE_TTL: u64 = 600;
+const DEFAULT_USERNAME_CLAIM: &str = "preferred_username";
+const DEFAULT_EMAIL_CLAIM: &str = "email";
+const DEFAULT_OIDC_SCOPES: &str = "openid profile email";
+
+type GroupRolePair = (Option<Uuid>, Option<Uuid>);
+
+/// Outcome of resolving a verified sso login to a local user
+#[derive(Debug)]
+/// `grants_changed` when the role mappings added or removed assignments of the user, see
+/// login_permissions_changed
+pub enum SsoLoginOutcome {
+    LoggedIn {
+        user: User,
+        grants_changed: bool,
+    },
+    Provisioned {
+        user: User,
+        grants_changed: bool,
+    },
+    /// Unknown user and the provider does not allow jit provisioning
+    NotProvisioned,
+    /// Linked user is inactive or deleted
+    Inactive,
+    /// Local user name derived from the claims is already taken by another account
+    UserNameTaken(String),
+    /// Claims do not contain anything usable as local user name
+    MissingUserName,
+}
+
+/// Provider name stored on user_identity for users linked through an sso provider
+pub fn identity_provider_name(provider: &SsoProvider) -> String {
+    format!("{}{}", SSO_IDENTITY_PREFIX, provider.name)
+}
+
+pub async fn fetch_jwks(jwks_url: &str) -> anyhow::Result<JwkSet> {
+    let res = reqwest::Client::new()
+        .get(jwks_url)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(res.json::<JwkSet>().await?)
+}
+
+/// Verify id token signature, issuer, audience and expiry against the provider jwks
+pub async fn verify_id_token(provider: &SsoProvider, id_token: &str) -> anyhow::Result<SsoClaims> {
+    let header = decode_header(id_token)?;
+    // id tokens signed with the client secret can not be verified from a public jwks
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        anyhow::bail!("unsupported id token algorithm {:?}", header.alg);
+    }
+    let jwks_url = match &provider.jwks_url {
+        Some(val) => val,
+        None => anyhow::bail!("sso provider {} has no jwks_url", provider.name),
+    };
+    let jwks = fetch_jwks(jwks_url).await?;
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    };
+    let jwk = match jwk {
+        Some(val) => val,
+        None => anyhow::bail!("no matching key found on jwks of {}", provider.name),
+    };
+    let key = DecodingKey::from_jwk(jwk)?;
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[provider.client_id.as_str()]);
+    validation.set_issuer(&[provider.issuer.as_str()]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+    let token_data: TokenData<SsoClaims> = decode(id_token, &key, &validation)?;
+    Ok(token_data.claims)
+}
+
+/// Values of a claim flattened to strings, array claims (e.g. groups) yield one value per item
+pub fn claim_values(claims: &SsoClaims, claim: &str) -> Vec<String> {
+    fn scalar(value: &Value) -> Option<String> {
+        match value {
+            Value::String(val) => Some(val.clone()),
+            Value::Bool(val) => Some(val.to_string()),
+            Value::Number(val) => Some(val.to_string()),
+            _ => None,
+        }
+    }
+    match claims.get(claim) {
+        Some(Value::Array(items)) => items.iter().filter_map(scalar).collect(),
+        Some(value) => scalar(value).into_iter().collect(),
+        None => vec![],
+    }
+}
+
+/// Claim carries the value, or is merely present when no value is expected
+pub fn claim_matches(claims: &SsoClaims, claim: &str, value: Option<&str>) -> bool {
+    let values = claim_values(claims, claim);
+    match value {
+        Some(expected) => values.iter().any(|item| item == expected),
+        None => !values.is_empty(),
+    }
+}
+
+pub fn jit_rule_matches(rule: &SsoJitRule, claims: &SsoClaims) -> bool {
+    match &rule.claim {
+        Some(claim) => claim_matches(claims, claim, rule.value.as_deref()),
+        None => true,
+    }
+}
+
+/// Group/role pairs granted by the mappings matching the claims,
+/// and every pair the provider mappings manage
+pub fn desired_role_mapping_grants(
+    mappings: &[SsoRoleMapping],
+    claims: &SsoClaims,
+) -> (HashSet<GroupRolePair>, HashSet<GroupRolePair>) {
+    let mut desired: HashSet<GroupRolePair> = HashSet::new();
+    let mut managed: HashSet<GroupRolePair> = HashSet::new();
+    for mapping in mappings {
+        let pair = (mapping.group_id, mapping.role_id);
+        managed.insert(pair);
+        if claim_matches(claims, &mapping.claim, Some(&mapping.value)) {
+            desired.insert(pair);
+        }
+    }
+    (desired, managed)
+}
+
+/// Keep the group/role assignments managed by the provider role mappings in sync with the
+/// claims, assignments not covered by any mapping are left untouched
+pub async fn apply_sso_role_mappings(
+    tx: &mut Transaction<'_, Postgres>,
+    provider: &SsoProvider,
+    user: &User,
+    claims: &SsoClaims,
+) -> anyhow::Result<(u32, u32)> {
+    let mappings = get_sso_role_mapping_by_provider(tx, &provider.id).await?;
+    if mappings.is_empty() {
+        return Ok((0, 0));
+    }
+    let (desired, managed) = desired_role_mapping_grants(&mappings, claims);
+    let mut current: HashSet<GroupRolePair> = HashSet::new();
+    let mut removed = 0;
+    for item in get_user_group_roles_by_user(tx, user).await? {
+        let pair = (item.group_id, item.role_id);
+        // duplicated rows of a granted pair are collapsed as well
+        if managed.contains(&pair) && (!desired.contains(&pair) || !current.insert(pair)) {
+            delete_user_group_roles_by_id(tx, &item.id).await?;
+            removed += 1;
+        }
+    }
+    let mut added = 0;
+    for (group_id, role_id) in desired.difference(&current) {
+        add_user_group_roles(
+            tx,
+            &UserGroupRoles {
+                id: Uuid::now_v7(),
+                user_id: Some(user.id),
+                group_id: *group_id,
+                role_id: *role_id,
+            },
+        )
+        .await?;
+        added += 1;
+    }
+    Ok((added, removed))
+}
+
+/// Local user name for a sso user, username claim first then email claim
+pub fn sso_user_name(provider: &SsoProvider, claims: &SsoClaims) -> Option<String> {
+    [
+        provider.username_claim.as_str(),
+        provider.email_claim.as_str(),
+    ]
+    .iter()
+    .filter_map(|claim| claim_values(claims, claim).into_iter().next())
+    .find(|val| !val.trim().is_empty())
+}
+
+/// Create local user linked to the sso subject and grant groups/roles of matching jit rules
+pub async fn provision_sso_user(
+    tx: &mut Transaction<'_, Postgres>,
+    provider: &SsoProvider,
+    subject: &str,
+    user_name: &str,
+    claims: &SsoClaims,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<User> {
+    let id = Uuid::now_v7();
+    // sso users authenticate upstream, local password is never handed out
+    let password = match hash_password(&Uuid::now_v7().to_string()) {
+        Ok(val) => val,
+        Err(err) => anyhow::bail!(err.to_string()),
+    };
+    let user = User {
+        id,
+        user_name: user_name.to_string(),
+        password,
+        status: UserStatus::Active,
+        expires_at: None,
+        is_2faenabled: Some(false),
+        created_by: provider.created_by,
+        updated_by: provider.created_by,
+        created_date: Some(*now),
+        updated_date: Some(*now),
+        deleted_date: None,
+        scopes: None,
+    };
+    let user_profile = UserProfile {
+        id,
+        user_id: id,
+        first_name: claim_values(claims, "given_name").into_iter().next(),
+        last_name: claim_values(claims, "family_name").into_iter().next(),
+        address: None,
+        email: claim_values(claims, &provider.email_claim)
+            .into_iter()
+            .next(),
+        locale: None,
+        timezone: None,
+        email_verified_at: None,
+    };
+    create_user(tx, &user, &user_profile).await?;
+    create_user_identity(
+        tx,
+        &UserIdentity {
+            id: Uuid::now_v7(),
+            user_id: id,
+            provider: identity_provider_name(provider),
+            subject: subject.to_string(),
+            directory_source_id: None,
+            created_date: Some(*now),
+            updated_date: Some(*now),
+        },
+    )
+    .await?;
+
+    let rules = get_sso_jit_rule_by_provider(tx, &provider.id).await?;
+    let mut granted: HashSet<(Option<Uuid>, Option<Uuid>)> = HashSet::new();
+    for rule in rules.iter().filter(|rule| jit_rule_matches(rule, claims)) {
+        if !granted.insert((rule.group_id, rule.role_id)) {
+            continue;
+        }
+        add_user_group_roles(
+            tx,
+            &UserGroupRoles {
+                id: Uuid::now_v7(),
+                user_id: Some(id),
+                group_id: rule.group_id,
+                role_id: rule.role_id,
+            },
+        )
+        .await?;
+    }
+    enqueue_scim_event(tx, &id, EVENT_CREATE, Some(*now)).await?;
+    Ok(user)
+}
+
+/// Resolve verified claims to a local user, provisioning it when the provider allows,
+/// role mappings are applied on every successful login
+pub async fn resolve_sso_user(
+    tx: &mut Transaction<'_, Postgres>,
+    provider: &SsoProvider,
+    claims: &SsoClaims,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<SsoLoginOutcome> {
+    let subject = match claim_values(claims, "sub").into_iter().next() {
+        Some(val) => val,
+        None => anyhow::bail!("id token has no sub claim"),
+    };
+    let identity_provider = identity_provider_name(provider);
+    if let Some(identity) = get_user_identity(tx, &identity_provider, &subject).await? {
+        let (user, _) = get_user_by_id(tx, &identity.user_id, Some(true)).await?;
+        let user = match user {
+            Some(user)
+                if user.deleted_date.is_none()
+                    && user.status == UserStatus::Active
+                    && !user.is_expired(now) =>
+            {
+                user
+            }
+            _ => return Ok(SsoLoginOutcome::Inactive),
+        };
+        let (added, removed) = apply_sso_role_mappings(tx, provider, &user, claims).await?;
+        return Ok(SsoLoginOutcome::LoggedIn {
+            user,
+            grants_changed: added + removed > 0,
+        });
+    }
+
+    if !provider.jit_enabled {
+        return Ok(SsoLoginOutcome::NotProvisioned);
+    }
+    let user_name = match sso_user_name(provider, claims) {
+        Some(val) => val,
+        None => return Ok(SsoLoginOutcome::MissingUserName),
+    };
+    // never link an existing local account on the strength of a claim alone
+    let (existing, _) = get_user_by_username(tx, &user_name).await?;
+    if existing.is_some() {
+        return Ok(SsoLoginOutcome::UserNameTaken(user_name));
+    }
+    let user = provision_sso_user(tx, provider, &subject, &user_name, claims, now).await?;
+    let (added, removed) = apply_sso_role_mappings(tx, provider, &user, claims).await?;
+    Ok(SsoLoginOutcome::Provisioned {
+        user,
+        grants_changed: added + removed > 0,
+    })
+}
+
+/// Fill what the provider leaves empty with the defaults of its protocol, Google and
+/// GitHub only need the client id and secret
+pub fn apply_protocol_defaults(provider: &mut SsoProvider) {
+    fn fill(value: &mut Option<String>, default: &str) {
+        if value.as_deref().is_none_or(|x| x.trim().is_empty()) {
+            *value = Some(default.to_string());
+        }
+    }
+    fn fill_str(value: &mut String, default: &str) {
+        if value.trim().is_empty() {
+            *value = default.to_string();
+        }
+    }
+    match provider.protocol.as_str() {
+        PROTOCOL_GOOGLE => {
+            fill_str(&mut provider.issuer, "https://accounts.google.com");
+            fill(
+                &mut provider.jwks_url,
+                "https://www.googleapis.com/oauth2/v3/certs",
+            );
+            fill(
+                &mut provider.authorization_url,
+                "https://accounts.google.com/o/oauth2/v2/auth",
+            );
+            fill(
+                &mut provider.token_url,
+                "https://oauth2.googleapis.com/token",
+            );
+        }
+        PROTOCOL_GITHUB => {
+            fill_str(&mut provider.issuer, "https://github.com");
+            fill_str(&mut provider.username_claim, "login");
+            fill(
+                &mut provider.authorization_url,
+                "https://github.com/login/oauth/authorize",
+            );
+            fill(
+                &mut provider.token_url,
+                "https://github.com/login/oauth/access_token",
+            );
+            fill(&mut provider.userinfo_url, "https://api.github.com/user");
+            fill(&mut provider.scopes, "read:user user:email");
+        }
+        _ => {}
+    }
+    fill_str(&mut provider.username_claim, DEFAULT_USERNAME_CLAIM);
+    fill_str(&mut provider.email_claim, DEFAULT_EMAIL_CLAIM);
+    fill(&mut provider.scopes, DEFAULT_OIDC_SCOPES);
+}
+
+/// Provider has the endpoints of the authorization code flow
+pub fn supports_authorization_code(provider: &SsoProvider) -> bool {
+    provider.authorization_url.is_some() && provider.token_url.is_some()
+}
+
+/// Stored in redis under the state of an authorization request until the user comes back
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PendingAuthorization {
+    pub provider_id: String,
+    pub redirect_uri: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    /// Set when a logged in user links the identity instead of logging in with it
+    pub link_user_id: Option<String>,
+}
+
+/// Random 256 bit url safe value, used as state, nonce and pkce code verifier
+fn generate_authorization_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// S256 pkce challenge of the verifier
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Url the user is sent to, the provider redirects back to redirect_uri with code and state
+pub fn build_authorization_url(
+    provider: &SsoProvider,
+    state: &str,
+    pending: &PendingAuthorization,
+) -> anyhow::Result<String> {
+    let base = match &provider.authorization_url {
+        Some(val) => val,
+        None => anyhow::bail!("sso provider {} has no authorization_url", provider.name),
+    };
+    let mut url = reqwest::Url::parse(base)?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", &pending.redirect_uri)
+        .append_pair("scope", provider.scopes.as_deref().unwrap_or_default())
+        .append_pair("state", state)
+        .append_pair("code_challenge", &code_challenge(&pending.code_verifier))
+        .append_pair("code_challenge_method", "S256");
+    if provider.is_oidc() {
+        url.query_pairs_mut().append_pair("nonce", &pending.nonce);
+    }
+    Ok(url.to_string())
+}
+
+/// Start an authorization request, return the url to send the user to and its state
+pub async fn start_authorization<C: SessionStore>(
+    redis_conn: &mut C,
+    provider: &SsoProvider,
+    redirect_uri: &str,
+    link_user_id: Option<&Uuid>,
+) -> anyhow::Result<(String, String)> {
+    let state = generate_authorization_token();
+    let pending = PendingAuthorization {
+        provider_id: provider.id.to_string(),
+        redirect_uri: redirect_uri.to_string(),
+        code_verifier: generate_authorization_token(),
+        nonce: generate_authorization_token(),
+        link_user_id: link_user_id.map(|x| x.to_string()),
+    };
+    let url = build_authorization_url(provider, &state, &pending)?;
+    redis_conn
+        .set_ex(
+            &format!("{}{}", AUTHORIZATION_STATE_KEY_PREFIX, state),
+            &serde_json::to_string(&pending)?,
+            AUTHORIZATION_STATE_TTL,
+        )
+        .await?;
+    Ok((url, state))
+}
+
+/// Consume the state, None when unknown or expired
+pub async fn take_authorization<C: SessionStore>(
+    redis_conn: &mut C,
+    state: &str,
+) -> anyhow::Result<Option<PendingAuthorization>> {
+    let key = format!("{}{}", AUTHORIZATION_STATE_KEY_PREFIX, state);
+    let res = redis_conn.get(&key).await?;
+    let pending: PendingAuthorization = match res {
+        Some(val) => serde_json::from_str(&val)?,
+        None => return Ok(None),
+    };
+    redis_conn.del(&key).await?;
+    Ok(Some(pending))
+}
+
+/// Token endpoint response, GitHub answers errors with 200 and an error field
+#[derive(Deserialize)]
+struct TokenEndpointResponse {
+    access_token: Option<String>,
+    id_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+async fn exchange_authorization_code(
+    provider: &SsoProvider,
+    pending: &PendingAuthorization,
+    code: &str,
+) -> anyhow::Result<TokenEndpointResponse> {
+    let token_url = match &provider.token_url {
+        Some(val) => val,
+        None => anyhow::bail!("sso provider {} has no token_url", provider.name),
+    };
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", pending.redirect_uri.as_str()),
+        ("client_id", provider.client_id.as_str()),
+        ("code_verifier", pending.code_verifier.as_str()),
+    ];
+    if let Some(client_secret) = &provider.client_secret {
+        form.push(("client_secret", client_secret.as_str()));
+    }
+    let res = reqwest::Client::new()
+        .post(token_url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&form)
+        .send()
+        .await?;
+    let status = res.status();
+    let body: TokenEndpointResponse = res.json().await?;
+    if let Some(error) = body.error {
+        anyhow::bail!(
+            "token endpoint returned {}: {}",
+            error,
+            body.error_description.unwrap_or_default()
+        );
+    }
+    if !status.is_success() {
+        anyhow::bail!("token endpoint returned {}", status);
+    }
+    Ok(body)
+}
+
+/// Claims from the userinfo endpoint, a numeric `id` (GitHub) stands in for a missing `sub`
+async fn fetch_userinfo(provider: &SsoProvider, access_token: &str) -> anyhow::Result<SsoClaims> {
+    let userinfo_url = match &provider.userinfo_url {
+        Some(val) => val,
+        None => anyhow::bail!("sso provider {} has no userinfo_url", provider.name),
+    };
+    let res = reqwest::Client::new()
+        .get(userinfo_url)
+        .bearer_auth(access_token)
+        .header(reqwest::header::ACCEPT, "application/json")
+        // required by the GitHub api
+        .header(reqwest::header::USER_AGENT, env!("CARGO_PKG_NAME"))
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut claims: SsoClaims = res.json().await?;
+    if !claims.contains_key("sub") {
+        if let Some(id) = claim_values(&claims, "id").into_iter().next() {
+            claims.insert("sub".to_string(), Value::String(id));
+        }
+    }
+    Ok(claims)
+}
+
+/// Exchange the code of an authorization request for the claims of the user who granted it,
+/// from the verified id token on oidc providers and from the userinfo endpoint otherwise
+pub async fn authorization_code_claims(
+    provider: &SsoProvider,
+    pending: &PendingAuthorization,
+    code: &str,
+) -> anyhow::Result<SsoClaims> {
+    let tokens = exchange_authorization_code(provider, pending, code).await?;
+    if provider.is_oidc() {
+        let id_token = match tokens.id_token {
+            Some(val) => val,
+            None => anyhow::bail!("token endpoint returned no id_token"),
+        };
+        let claims = verify_id_token(provider, &id_token).await?;
+        // id token replayed from another authorization request
+        if claim_values(&claims, "nonce").first() != Some(&pending.nonce) {
+            anyhow::bail!("id token nonce does not match");
+        }
+        return Ok(claims);
+    }
+    let access_token = match tokens.access_token {
+        Some(val) => val,
+        None => anyhow::bail!("token endpoint returned no access_token"),
+    };
+    fetch_userinfo(provider, &access_token).await
+}
+
+/// Outcome of linking a verified sso login to a logged in user
+#[derive(Debug)]
+pub enum SsoLinkOutcome {
+    Linked(UserIdentity),
+    /// Subject is already linked to the user
+    AlreadyLinked(UserIdentity),
+    /// Subject is linked to another user
+    LinkedToOther,
+}
+
+/// Link the subject of the claims to the user, so later sso logins resolve to it
+pub async fn link_sso_identity(
+    tx: &mut Transaction<'_, Postgres>,
+    provider: &SsoProvider,
+    user: &User,
+    claims: &SsoClaims,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<SsoLinkOutcome> {
+    let subject = match claim_values(claims, "sub").into_iter().next() {
+        Some(val) => val,
+        None => anyhow::bail!("claims have no sub"),
+    };
+    let identity_provider = identity_provider_name(provider);
+    if let Some(identity) = get_user_identity(tx, &identity_provider, &subject).await? {
+        if identity.user_id == user.id {
+            return Ok(SsoLinkOutcome::AlreadyLinked(identity));
+        }
+        return Ok(SsoLinkOutcome::LinkedToOther);
+    }
+    let identity = UserIdentity {
+        id: Uuid::now_v7(),
+        user_id: user.id,
+        provider: identity_provider,
+        subject,
+        directory_source_id: None,
+        created_date: Some(*now),
+        updated_date: Some(*now),
+    };
+    create_user_identity(tx, &identity).await?;
+    Ok(SsoLinkOutcome::Linked(identity))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn claims(value: Value) -> SsoClaims {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn rule(claim: Option<&str>, value: Option<&str>) -> SsoJitRule {
+        SsoJitRule {
+            id: Uuid::now_v7(),
+            provider_id: Uuid::now_v7(),
+            claim: claim.map(|val| val.to_string()),
+            value: value.map(|val| val.to_string()),
+            group_id: Some(Uuid::now_v7()),
+            role_id: None,
+            created_date: None,
+            updated_date: None,
+        }
+    }
+
+    fn provider(protocol: &str) -> SsoProvider {
+        SsoProvider {
+            id: Uuid::now_v7(),
+            name: "corp".to_string(),
+            protocol: protocol.to_string(),
+            issuer: "".to_string(),
+            client_id: "core".to_string(),
+            jwks_url: None,
+            username_claim: "".to_string(),
+            email_claim: "".to_string(),
+            is_active: Some(true),
+            jit_enabled: false,
+            created_by: None,
+            updated_by: None,
+            created_date: None,
+            updated_date: None,
+            deleted_date: None,
+            client_secret: None,
+            authorization_url: None,
+            token_url: None,
+            userinfo_url: None,
+            scopes: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_protocol_defaults() {
+        // When
+        let mut google = provider(PROTOCOL_GOOGLE);
+        apply_protocol_defaults(&mut google);
+        let mut github = provider(PROTOCOL_GITHUB);
+        github.scopes = Some("read:user".to_string());
+        apply_protocol_defaults(&mut github);
+        let mut oidc = provider("oidc");
+        apply_protocol_defaults(&mut oidc);
+
+        // Expect
+        assert_eq!(google.issuer, "https://accounts.google.com");
+        assert!(google.is_oidc() && supports_authorization_code(&google));
+        assert_eq!(google.username_claim, "preferred_username");
+        assert_eq!(google.scopes.as_deref(), Some("openid profile email"));
+        assert!(!github.is_oidc() && supports_authorization_code(&github));
+        assert_eq!(github.username_claim, "login");
+        assert_eq!(github.scopes.as_deref(), Some("read:user"));
+        assert_eq!(
+            github.userinfo_url.as_deref(),
+            Some("https://api.github.com/user")
+        );
+        assert!(oidc.jwks_url.is_none() && !supports_authorization_code(&oidc));
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // RFC 7636 appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_claim_values() {
+        let claims = claims(json!({
+            "sub": "abc",
+            "groups": ["admins", "devs", 7],
+            "email_verified": true,
+            "nested": {"a": 1}
+        }));
+        assert_eq!(claim_values(&claims, "sub"), vec!["abc"]);
+        assert_eq!(claim_values(&claims, "groups"), vec!["admins", "devs", "7"]);
+        assert_eq!(claim_values(&claims, "email_verified"), vec!["true"]);
+        assert!(claim_values(&claims, "nested").is_empty());
+        assert!(claim_values(&claims, "missing").is_empty());
+    }
+
+    #[test]
+    fn test_jit_rule_matches() {
+        let claims = claims(json!({"groups": ["admins"], "department": "it"}));
+        assert!(jit_rule_matches(&rule(None, None), &claims));
+        assert!(jit_rule_matches(
+            &rule(Some("groups"), Some("admins")),
+            &claims
+        ));
+        assert!(!jit_rule_matches(
+            &rule(Some("groups"), Some("devs")),
+            &claims
+        ));
+        assert!(jit_rule_matches(&rule(Some("department"), None), &claims));
+        assert!(!jit_rule_matches(&rule(Some("office"), None), &claims));
+    }
+
+    #[test]
+    fn test_desired_role_mapping_grants() {
+        let group_id = Some(Uuid::now_v7());
+        let role_id = Some(Uuid::now_v7());
+        let mapping = |value: &str, group_id: Option<Uuid>, role_id: Option<Uuid>| SsoRoleMapping {
+            id: Uuid::now_v7(),
+            provider_id: Uuid::now_v7(),
+            claim: "groups".to_string(),
+            value: value.to_string(),
+            group_id,
+            role_id,
+            created_date: None,
+            updated_date: None,
+        };
+        let mappings = vec![
+            mapping("admins", group_id, role_id),
+            mapping("devs", group_id, None),
+            mapping("ops", group_id, role_id),
+        ];
+        let (desired, managed) =
+            desired_role_mapping_grants(&mappings, &claims(json!({"groups": ["admins"]})));
+        assert_eq!(desired, HashSet::from([(group_id, role_id)]));
+        assert_eq!(
+            managed,
+            HashSet::from([(group_id, role_id), (group_id, None)])
+        );
+    }
+}
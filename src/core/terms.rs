@@ -0,0 +1,116 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    model::terms_version::TermsVersion,
+    repository::user_terms_acceptance::{accept_terms_version, get_pending_terms_version_by_user},
+};
+
+/// Accept the pending versions listed in `accept_terms` (terms version ids) and return
+/// the ones still pending. With TERMS_ACCEPTANCE_REQUIRED no token is issued until empty.
+pub async fn accept_and_get_pending_terms(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    accept_terms: &[String],
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<Vec<TermsVersion>> {
+    let mut res = vec![];
+    for terms_version in get_pending_terms_version_by_user(tx, user_id).await? {
+        if accept_terms
+            .iter()
+            .any(|x| Uuid::parse_str(x.trim()).is_ok_and(|x| x == terms_version.id))
+        {
+            accept_terms_version(tx, user_id, &terms_version.id, now).await?;
+        } else {
+            res.push(terms_version);
+        }
+    }
+    Ok(res)
+}
+
+pub fn pending_terms_message(pending: &[TermsVersion]) -> String {
+    format!(
+        "latest terms not accepted: {}",
+        pending
+            .iter()
+            .map(|x| format!("{} {} ({})", x.kind, x.version, x.id))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::{
+        core::{
+            session_store::create_redis_pool, terms::accept_and_get_pending_terms,
+            test_utils::generate_test_user,
+        },
+        model::terms_version::{TermsVersion, KIND_PRIVACY_POLICY, KIND_TERMS_OF_SERVICE},
+        repository::terms_version::create_terms_version,
+        settings::get_config,
+    };
+
+    #[sqlx::test]
+    async fn test_accept_and_get_pending_terms(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let mut redis_conn = create_redis_pool(&config)?.get().await?;
+        let mut db = pool.acquire().await?;
+        let test_user =
+            generate_test_user(&mut db, &mut redis_conn, config, "test_user", "password").await?;
+        let now = Local::now().fixed_offset();
+        let mut tx = pool.begin().await?;
+        let mut versions = vec![];
+        for (kind, version, published_date) in [
+            (KIND_TERMS_OF_SERVICE, "1", now - Duration::days(1)),
+            (KIND_TERMS_OF_SERVICE, "2", now),
+            (KIND_PRIVACY_POLICY, "1", now),
+        ] {
+            let terms_version = TermsVersion {
+                id: Uuid::now_v7(),
+                kind: kind.to_string(),
+                version: version.to_string(),
+                url: None,
+                content: None,
+                published_by: None,
+                published_date: Some(published_date),
+            };
+            create_terms_version(&mut tx, &terms_version).await?;
+            versions.push(terms_version);
+        }
+
+        // When accepting an outdated version
+        let pending = accept_and_get_pending_terms(
+            &mut tx,
+            &test_user.user.id,
+            &[versions[0].id.to_string()],
+            &now,
+        )
+        .await?;
+
+        // Expect latest of each kind pending
+        assert_eq!(
+            pending.iter().map(|x| x.id).collect::<Vec<Uuid>>(),
+            vec![versions[2].id, versions[1].id]
+        );
+
+        // When accepting the latest versions
+        let pending = accept_and_get_pending_terms(
+            &mut tx,
+            &test_user.user.id,
+            &[versions[1].id.to_string(), versions[2].id.to_string()],
+            &now,
+        )
+        .await?;
+
+        // Expect
+        assert!(pending.is_empty());
+        Ok(())
+    }
+}
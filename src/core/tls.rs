@@ -0,0 +1,233 @@
+use poem::{
+    handler,
+    http::{header, StatusCode},
+    listener::{
+        acme::{AutoCert, LETS_ENCRYPT_PRODUCTION, LETS_ENCRYPT_STAGING},
+        BoxListener, Listener, RustlsCertificate, RustlsConfig, TcpListener,
+    },
+    web::Data,
+    EndpointExt, IntoEndpoint, Request, Response, Route,
+};
+
+use crate::settings::Config;
+
+/// How the main listener terminates connections, resolved from settings
+#[derive(Debug, PartialEq)]
+pub enum TlsMode {
+    Plain,
+    /// PEM certificate chain and private key paths
+    Files {
+        cert_path: String,
+        key_path: String,
+    },
+    /// Certificates issued and renewed through ACME TLS-ALPN-01 on the listener port
+    Acme {
+        domains: Vec<String>,
+        contact: Option<String>,
+        cache_dir: Option<String>,
+        staging: bool,
+    },
+}
+
+/// Resolve the TLS mode, fail on incomplete or conflicting settings
+pub fn tls_mode(config: &Config) -> anyhow::Result<TlsMode> {
+    let domains: Vec<String> = config
+        .tls_acme_domains
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect();
+    let mode = match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(_), Some(_)) if !domains.is_empty() => {
+            anyhow::bail!("TLS_CERT_PATH and TLS_ACME_DOMAINS can not be used together")
+        }
+        (Some(cert_path), Some(key_path)) => TlsMode::Files {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+        },
+        (Some(_), None) | (None, Some(_)) => {
+            anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together")
+        }
+        (None, None) if !domains.is_empty() => TlsMode::Acme {
+            domains,
+            contact: config.tls_acme_contact.clone(),
+            cache_dir: config.tls_acme_cache_dir.clone(),
+            staging: config.tls_acme_staging.unwrap_or(false),
+        },
+        (None, None) => TlsMode::Plain,
+    };
+    if let Some(redirect_port) = config.http_redirect_port {
+        if mode == TlsMode::Plain {
+            anyhow::bail!("HTTP_REDIRECT_PORT requires TLS to be enabled");
+        }
+        if redirect_port == config.port {
+            anyhow::bail!("HTTP_REDIRECT_PORT must differ from PORT");
+        }
+    }
+    Ok(mode)
+}
+
+/// Listener on HOST:PORT, wrapped in TLS according to settings
+pub fn server_listener(config: &Config) -> anyhow::Result<BoxListener> {
+    let listener = TcpListener::bind(format!("{}:{}", config.host, config.port));
+    match tls_mode(config)? {
+        TlsMode::Plain => Ok(listener.boxed()),
+        TlsMode::Files {
+            cert_path,
+            key_path,
+        } => {
+            let cert = match std::fs::read(&cert_path) {
+                Ok(val) => val,
+                Err(err) => anyhow::bail!("failed to read {}: {}", cert_path, err),
+            };
+            let key = match std::fs::read(&key_path) {
+                Ok(val) => val,
+                Err(err) => anyhow::bail!("failed to read {}: {}", key_path, err),
+            };
+            let tls_config =
+                RustlsConfig::new().fallback(RustlsCertificate::new().cert(cert).key(key));
+            Ok(listener.rustls(tls_config).boxed())
+        }
+        TlsMode::Acme {
+            domains,
+            contact,
+            cache_dir,
+            staging,
+        } => {
+            let mut builder = AutoCert::builder().directory_url(match staging {
+                true => LETS_ENCRYPT_STAGING,
+                false => LETS_ENCRYPT_PRODUCTION,
+            });
+            for domain in domains {
+                builder = builder.domain(domain);
+            }
+            if let Some(contact) = contact {
+                builder = builder.contact(contact);
+            }
+            if let Some(cache_dir) = cache_dir {
+                builder = builder.cache_path(cache_dir);
+            }
+            Ok(listener.acme(builder.build()?).boxed())
+        }
+    }
+}
+
+#[handler]
+fn redirect_to_https(req: &Request, https_port: Data<&u16>) -> Response {
+    let host = match req.header(header::HOST) {
+        Some(val) => val,
+        None => return Response::builder().status(StatusCode::BAD_REQUEST).finish(),
+    };
+    // Drop the plain http port, keep bracketed ipv6 hosts intact
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let authority = match *https_port.0 {
+        443 => host.to_string(),
+        port => format!("{}:{}", host, port),
+    };
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|x| x.as_str())
+        .unwrap_or("/");
+    Response::builder()
+        .status(StatusCode::PERMANENT_REDIRECT)
+        .header(header::LOCATION, format!("https://{}{}", authority, path))
+        .finish()
+}
+
+/// Plain http endpoint answering every request with a redirect to the https port
+pub fn init_https_redirect_route(https_port: u16) -> impl IntoEndpoint {
+    Route::new()
+        .at("/*path", redirect_to_https)
+        .at("/", redirect_to_https)
+        .data(https_port)
+}
+
+#[cfg(test)]
+mod tests {
+    use poem::{http::StatusCode, test::TestClient};
+
+    use crate::{
+        core::tls::{init_https_redirect_route, tls_mode, TlsMode},
+        settings::{get_config, Config},
+    };
+
+    fn plain_config() -> Config {
+        let mut config = get_config();
+        config.tls_cert_path = None;
+        config.tls_key_path = None;
+        config.tls_acme_domains = None;
+        config.http_redirect_port = None;
+        config
+    }
+
+    #[test]
+    fn test_tls_mode() {
+        // Expect plain by default
+        assert_eq!(tls_mode(&plain_config()).unwrap(), TlsMode::Plain);
+
+        // Expect certificate files
+        let mut config = plain_config();
+        config.tls_cert_path = Some("cert.pem".to_string());
+        config.tls_key_path = Some("key.pem".to_string());
+        config.http_redirect_port = Some(8080);
+        assert_eq!(
+            tls_mode(&config).unwrap(),
+            TlsMode::Files {
+                cert_path: "cert.pem".to_string(),
+                key_path: "key.pem".to_string()
+            }
+        );
+
+        // Expect acme
+        let mut config = plain_config();
+        config.tls_acme_domains = Some("example.com, www.example.com".to_string());
+        assert_eq!(
+            tls_mode(&config).unwrap(),
+            TlsMode::Acme {
+                domains: vec!["example.com".to_string(), "www.example.com".to_string()],
+                contact: None,
+                cache_dir: None,
+                staging: false,
+            }
+        );
+
+        // Expect invalid combinations rejected
+        let mut config = plain_config();
+        config.tls_cert_path = Some("cert.pem".to_string());
+        assert!(tls_mode(&config).is_err());
+        config.tls_key_path = Some("key.pem".to_string());
+        config.tls_acme_domains = Some("example.com".to_string());
+        assert!(tls_mode(&config).is_err());
+        let mut config = plain_config();
+        config.http_redirect_port = Some(8080);
+        assert!(tls_mode(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_https_redirect() {
+        // Given
+        let cli = TestClient::new(init_https_redirect_route(8443));
+
+        // When
+        let resp = cli
+            .get("/api/user?page=2")
+            .header("Host", "example.com:8080")
+            .send()
+            .await;
+
+        // Expect
+        resp.assert_status(StatusCode::PERMANENT_REDIRECT);
+        resp.assert_header("Location", "https://example.com:8443/api/user?page=2");
+
+        // Expect default https port omitted
+        let cli = TestClient::new(init_https_redirect_route(443));
+        let resp = cli.get("/").header("Host", "example.com").send().await;
+        resp.assert_header("Location", "https://example.com/");
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{
+    core::email::is_valid_email,
+    model::user_contact::{UserContact, KINDS, KIND_EMAIL, KIND_PHONE},
+};
+
+/// Digits with an optional leading +, spaces, dashes, dots and parentheses dropped
+pub fn normalize_phone(value: &str) -> Option<String> {
+    let value: String = value
+        .chars()
+        .filter(|x| !matches!(x, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let digits = value.strip_prefix('+').unwrap_or(&value);
+    if !(7..=15).contains(&digits.len()) || !digits.chars().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    Some(value)
+}
+
+/// Contact value as stored, or the reason it is rejected
+pub fn normalize_contact(kind: &str, value: &str) -> Result<String, String> {
+    let value = value.trim();
+    match kind {
+        KIND_EMAIL if is_valid_email(value) => Ok(value.to_string()),
+        KIND_EMAIL => Err(format!("{} is not a valid email address", value)),
+        KIND_PHONE => {
+            normalize_phone(value).ok_or(format!("{} is not a valid phone number", value))
+        }
+        _ => Err(format!("kind must be one of {}", KINDS.join(", "))),
+    }
+}
+
+/// Verified phone security alerts are sent to, the primary one when verified
+pub fn alert_phone(contacts: &[UserContact]) -> Option<&UserContact> {
+    contacts
+        .iter()
+        .filter(|x| x.kind == KIND_PHONE && x.verified_date.is_some())
+        .max_by_key(|x| x.is_primary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::user_contact::{normalize_contact, normalize_phone};
+
+    #[test]
+    fn test_normalize_contact() {
+        assert_eq!(
+            normalize_phone("+62 (812) 3456-7890"),
+            Some("+6281234567890".to_string())
+        );
+        assert!(normalize_phone("12345").is_none());
+        assert!(normalize_phone("+62 812 abc").is_none());
+        assert_eq!(
+            normalize_contact("email", " jane@example.com "),
+            Ok("jane@example.com".to_string())
+        );
+        assert!(normalize_contact("email", "jane").is_err());
+        assert!(normalize_contact("fax", "123").is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::{collections::HashSet, fmt};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::security::hash_password,
+    model::{
+        scim_provisioning_event::EVENT_CREATE,
+        user::{User, UserStatus},
+        user_group_roles::UserGroupRoles,
+        user_profile::UserProfile,
+    },
+    repository,
+};
+
+/// One csv row, group_roles is a `|` separated list of `group:role` names
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserImportRow {
+    pub user_name: String,
+    pub password: String,
+    pub email: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub address: Option<String>,
+    pub is_active: Option<bool>,
+    pub group_roles: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UserImportStatus {
+    Created,
+    /// Row passed validation on dry run
+    Valid,
+    Invalid(String),
+}
+
+#[derive(Debug)]
+pub struct UserImportReport {
+    /// Line number in the csv file, header is line 1
+    pub line: usize,
+    pub user_name: Option<String>,
+    pub status: UserImportStatus,
+}
+
+impl fmt::Display for UserImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let user_name = self.user_name.clone().unwrap_or("-".to_string());
+        match &self.status {
+            UserImportStatus::Created => write!(f, "line {}: {} created", self.line, user_name),
+            UserImportStatus::Valid => write!(f, "line {}: {} valid", self.line, user_name),
+            UserImportStatus::Invalid(reason) => {
+                write!(f, "line {}: {} invalid, {}", self.line, user_name, reason)
+            }
+        }
+    }
+}
+
+/// Parse csv content, rows that can not be read are kept as errors so they show up in the report
+pub fn parse_user_csv(content: &str) -> anyhow::Result<Vec<Result<UserImportRow, String>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    reader.headers()?;
+    Ok(reader
+        .deserialize::<UserImportRow>()
+        .map(|x| x.map_err(|err| err.to_string()))
+        .collect())
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.clone().filter(|x| !x.is_empty())
+}
+
+/// Validate a row and resolve its group role names to ids
+pub async fn validate_user_import_row(
+    tx: &mut Transaction<'_, Postgres>,
+    row: &UserImportRow,
+) -> anyhow::Result<Result<Vec<(Uuid, Uuid)>, String>> {
+    if row.user_name.is_empty() || row.user_name.contains(char::is_whitespace) {
+        return Ok(Err("user_name is empty or contains whitespace".to_string()));
+    }
+    if row.password.is_empty() {
+        return Ok(Err("password is empty".to_string()));
+    }
+    if let Some(email) = non_empty(&row.email) {
+        if !email.contains('@') {
+            return Ok(Err(format!("email {} is not valid", email)));
+        }
+    }
+    let (existing, _) = repository::user::get_user_by_username(tx, &row.user_name).await?;
+    if existing.is_some() {
+        return Ok(Err(format!("user {} already exists", row.user_name)));
+    }
+
+    let mut group_roles: Vec<(Uuid, Uuid)> = vec![];
+    let items = non_empty(&row.group_roles).unwrap_or_default();
+    for item in items.split('|').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        let (group_name, role_name) = match item.split_once(':') {
+            Some(val) => val,
+            None => return Ok(Err(format!("group role {} is not group:role", item))),
+        };
+        let group = repository::group::get_group_by_name(tx, group_name.trim()).await?;
+        let group = match group {
+            Some(val) => val,
+            None => return Ok(Err(format!("group {} not found", group_name))),
+        };
+        let role = repository::role::get_role_by_name(tx, role_name.trim()).await?;
+        let role = match role {
+            Some(val) => val,
+            None => return Ok(Err(format!("role {} not found", role_name))),
+        };
+        if !group_roles.contains(&(group.id, role.id)) {
+            group_roles.push((group.id, role.id));
+        }
+    }
+    Ok(Ok(group_roles))
+}
+
+async fn import_user_row(
+    tx: &mut Transaction<'_, Postgres>,
+    row: &UserImportRow,
+    group_roles: &[(Uuid, Uuid)],
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    let hashed_password = match hash_password(&row.password) {
+        Ok(val) => val,
+        Err(err) => anyhow::bail!(err.to_string()),
+    };
+    let user = User {
+        id: Uuid::now_v7(),
+        user_name: row.user_name.clone(),
+        password: hashed_password,
+        status: UserStatus::from_active(row.is_active.unwrap_or(true)),
+        expires_at: None,
+        is_2faenabled: Some(false),
+        created_by: None,
+        updated_by: None,
+        created_date: Some(*now),
+        updated_date: Some(*now),
+        deleted_date: None,
+        scopes: None,
+    };
+    let user_profile = UserProfile {
+        id: Uuid::now_v7(),
+        user_id: user.id,
+        first_name: non_empty(&row.first_name),
+        last_name: non_empty(&row.last_name),
+        address: non_empty(&row.address),
+        email: non_empty(&row.email),
+        locale: None,
+        timezone: None,
+        email_verified_at: None,
+    };
+    repository::user::create_user(tx, &user, &user_profile).await?;
+    let user_group_roles: Vec<UserGroupRoles> = group_roles
+        .iter()
+        .map(|(group_id, role_id)| UserGroupRoles {
+            id: Uuid::now_v7(),
+            user_id: Some(user.id),
+            group_id: Some(*group_id),
+            role_id: Some(*role_id),
+        })
+        .collect();
+    if !user_group_roles.is_empty() {
+        repository::user::upsert_user_group_roles(tx, &user, &user_group_roles).await?;
+    }
+    repository::scim_provisioning_event::enqueue_scim_event(tx, &user.id, EVENT_CREATE, Some(*now))
+        .await?;
+    Ok(())
+}
+
+/// Import rows one transaction each so a bad row does not block the rest,
+/// on dry run every row is validated and rolled back
+pub async fn import_users(
+    pool: &PgPool,
+    rows: Vec<Result<UserImportRow, String>>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<UserImportReport>> {
+    let now = Local::now().fixed_offset();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut reports: Vec<UserImportReport> = vec![];
+    for (idx, row) in rows.into_iter().enumerate() {
+        let line = idx + 2;
+        let row = match row {
+            Ok(val) => val,
+            Err(err) => {
+                reports.push(UserImportReport {
+                    line,
+                    user_name: None,
+                    status: UserImportStatus::Invalid(err),
+                });
+                continue;
+            }
+        };
+        if !seen.insert(row.user_name.clone()) {
+            reports.push(UserImportReport {
+                line,
+                user_name: Some(row.user_name.clone()),
+                status: UserImportStatus::Invalid(format!(
+                    "user {} is duplicated in file",
+                    row.user_name
+                )),
+            });
+            continue;
+        }
+
+        let mut tx = pool.begin().await?;
+        let group_roles = match validate_user_import_row(&mut tx, &row).await? {
+            Ok(val) => val,
+            Err(reason) => {
+                reports.push(UserImportReport {
+                    line,
+                    user_name: Some(row.user_name.clone()),
+                    status: UserImportStatus::Invalid(reason),
+                });
+                continue;
+            }
+        };
+        let status = if dry_run {
+            UserImportStatus::Valid
+        } else {
+            match import_user_row(&mut tx, &row, &group_roles, &now).await {
+                Ok(_) => {
+                    tx.commit().await?;
+                    UserImportStatus::Created
+                }
+                Err(err) => UserImportStatus::Invalid(err.to_string()),
+            }
+        };
+        reports.push(UserImportReport {
+            line,
+            user_name: Some(row.user_name.clone()),
+            status,
+        });
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::factory::{group::GroupFactory, role::RoleFactory, user::UserFactory};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_user_csv() {
+        // When
+        let rows = parse_user_csv(
+            "user_name,password,email,first_name,last_name,address,is_active,group_roles\n\
+            john, secret ,john@example.com,John,,,true,\n\
+            jane,secret,,,,,maybe,\n",
+        )
+        .unwrap();
+
+        // Expect
+        assert_eq!(rows.len(), 2);
+        let john = rows[0].as_ref().unwrap();
+        assert_eq!(john.password, "secret");
+        assert_eq!(non_empty(&john.last_name), None);
+        assert!(rows[1].is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_import_users(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let existing = UserFactory::<()>::new().generate_one(&pool, ()).await?;
+        let group = GroupFactory::<()>::new().generate_one(&pool, ()).await?;
+        let role = RoleFactory::<()>::new().generate_one(&pool, ()).await?;
+        let content = format!(
+            "user_name,password,email,first_name,last_name,address,is_active,group_roles\n\
+            alice,secret,alice@example.com,Alice,,,true,{}:{}\n\
+            {},secret,,,,,,\n\
+            bob,secret,not-an-email,,,,,\n\
+            carol,secret,,,,,,{}:missing\n\
+            alice,secret,,,,,,\n",
+            group.group_name, role.role_name, existing.user_name, group.group_name
+        );
+
+        // When dry run
+        let reports = import_users(&pool, parse_user_csv(&content)?, true).await?;
+
+        // Expect nothing written
+        let statuses: Vec<&UserImportStatus> = reports.iter().map(|x| &x.status).collect();
+        assert_eq!(statuses[0], &UserImportStatus::Valid);
+        assert!(statuses[1..]
+            .iter()
+            .all(|x| matches!(x, UserImportStatus::Invalid(_))));
+        assert_eq!(reports[4].line, 6);
+        let mut tx = pool.begin().await?;
+        let (alice, _) = repository::user::get_user_by_username(&mut tx, "alice").await?;
+        assert!(alice.is_none());
+        tx.rollback().await?;
+
+        // When
+        let reports = import_users(&pool, parse_user_csv(&content)?, false).await?;
+
+        // Expect
+        assert_eq!(reports[0].status, UserImportStatus::Created);
+        let mut tx = pool.begin().await?;
+        let (alice, profile) = repository::user::get_user_by_username(&mut tx, "alice").await?;
+        let alice = alice.unwrap();
+        assert_eq!(
+            profile.unwrap().email,
+            Some("alice@example.com".to_string())
+        );
+        let group_roles = repository::user::get_user_group_roles_by_user(&mut tx, &alice).await?;
+        assert_eq!(group_roles.len(), 1);
+        assert_eq!(group_roles[0].role_id, Some(role.id));
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    model::{user::User, user_name_history::UserNameHistory},
+    repository::{
+        user::is_user_name_taken,
+        user_name_history::{create_user_name_history, is_user_name_reserved},
+    },
+};
+
+pub const DEFAULT_USER_NAME_RESERVE_DAYS: u64 = 90;
+
+/// Return the reason `user_name` can not be used by `user_id` (None for a new user),
+/// None when available. A user can always take back one of its own previous names.
+pub async fn check_user_name_available(
+    tx: &mut Transaction<'_, Postgres>,
+    user_name: &str,
+    user_id: Option<&Uuid>,
+    reserve_days: u64,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<Option<String>> {
+    if user_name.trim().is_empty() {
+        return Ok(Some("user_name is required".to_string()));
+    }
+    if is_user_name_taken(tx, user_name, user_id).await? {
+        return Ok(Some(format!("user name {} is taken", user_name)));
+    }
+    let since = *now - Duration::days(reserve_days as i64);
+    if is_user_name_reserved(tx, user_name, user_id, &since).await? {
+        return Ok(Some(format!(
+            "user name {} was used by another user in the last {} days",
+            user_name, reserve_days
+        )));
+    }
+    Ok(None)
+}
+
+/// Keep the current name of `user` in the history before it is renamed
+pub async fn record_user_name_change(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &User,
+    changed_by: Option<Uuid>,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserNameHistory> {
+    let history = UserNameHistory {
+        id: Uuid::now_v7(),
+        user_id: user.id,
+        user_name: user.user_name.clone(),
+        changed_by,
+        created_date: Some(*now),
+    };
+    create_user_name_history(tx, &history).await?;
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            session_store::create_redis_pool,
+            test_utils::generate_test_user,
+            user_name::{check_user_name_available, record_user_name_change},
+        },
+        settings::get_config,
+    };
+
+    #[sqlx::test]
+    async fn test_check_user_name_available(pool: PgPool) -> anyhow::Result<()> {
+        // Given user renamed from old_name, and another user
+        let config = get_config();
+        let mut redis_conn = create_redis_pool(&config)?.get().await?;
+        let mut db = pool.acquire().await?;
+        let renamed = generate_test_user(
+            &mut db,
+            &mut redis_conn,
+            config.clone(),
+            "old_name",
+            "password",
+        )
+        .await?;
+        let other =
+            generate_test_user(&mut db, &mut redis_conn, config, "other", "password").await?;
+        let now = Local::now().fixed_offset();
+        let mut tx = pool.begin().await?;
+        record_user_name_change(&mut tx, &renamed.user, None, &now).await?;
+        sqlx::query("UPDATE public.user SET user_name = 'new_name' WHERE id = $1")
+            .bind(renamed.user.id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Expect taken and reserved names rejected for others only
+        let other_id = Some(&other.user.id);
+        assert!(
+            check_user_name_available(&mut tx, "new_name", other_id, 90, &now)
+                .await?
+                .is_some()
+        );
+        assert!(
+            check_user_name_available(&mut tx, "old_name", other_id, 90, &now)
+                .await?
+                .is_some()
+        );
+        assert!(
+            check_user_name_available(&mut tx, "old_name", None, 90, &now)
+                .await?
+                .is_some()
+        );
+        assert!(
+            check_user_name_available(&mut tx, "old_name", Some(&renamed.user.id), 90, &now)
+                .await?
+                .is_none()
+        );
+        assert!(
+            check_user_name_available(&mut tx, "other", other_id, 90, &now)
+                .await?
+                .is_none()
+        );
+
+        // Expect released after the reserve period
+        let later = now + Duration::days(91);
+        assert!(
+            check_user_name_available(&mut tx, "old_name", other_id, 90, &later)
+                .await?
+                .is_none()
+        );
+        Ok(())
+    }
+}
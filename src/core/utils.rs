@@ -1,21 +1,65 @@
-use chrono::{DateTime, FixedOffset};
+use std::sync::LazyLock;
 
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    core::{locale::UserLocale, request_id::request_timezone},
+    settings::get_config,
+};
+
+/// DEFAULT_TIMEZONE, for requests without a user and outside of a request
+static DEFAULT_RESPONSE_TIMEZONE: LazyLock<Tz> =
+    LazyLock::new(|| UserLocale::resolve(&get_config(), None).timezone);
+
+/// Current time in UTC, stored timestamps do not depend on the server timezone
+pub fn utc_now() -> DateTime<FixedOffset> {
+    Utc::now().fixed_offset()
+}
+
+/// Timezone the api reads and writes timestamps in, the profile timezone of the request
+/// user or DEFAULT_TIMEZONE, see set_request_timezone
+pub fn response_timezone() -> Tz {
+    request_timezone().unwrap_or(*DEFAULT_RESPONSE_TIMEZONE)
+}
+
+/// "%Y-%m-%d %H:%M:%S" in the response timezone
 pub fn datetime_to_string(datetime: DateTime<FixedOffset>) -> String {
-    let offset = FixedOffset::east_opt(7 * 60 * 60).unwrap(); // +0700
     datetime
-        .with_timezone(&offset)
+        .with_timezone(&response_timezone())
         .format("%Y-%m-%d %H:%M:%S")
         .to_string()
 }
 
 pub fn datetime_to_string_opt(datetime: Option<DateTime<FixedOffset>>) -> Option<String> {
-    datetime?;
-    let offset = FixedOffset::east_opt(7 * 60 * 60).unwrap(); // +0700
-    Some(
-        datetime
-            .unwrap()
-            .with_timezone(&offset)
-            .format("%Y-%m-%d %H:%M:%S")
-            .to_string(),
-    )
+    datetime.map(datetime_to_string)
+}
+
+/// Parse RFC 3339 or the "%Y-%m-%d %H:%M:%S" format returned by the api, read in the
+/// response timezone
+pub fn string_to_datetime(val: &str) -> anyhow::Result<DateTime<FixedOffset>> {
+    if let Ok(val) = DateTime::parse_from_rfc3339(val) {
+        return Ok(val);
+    }
+    let naive = NaiveDateTime::parse_from_str(val, "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| anyhow::anyhow!("invalid datetime {}", val))?;
+    // the earlier of the two readings when clocks are turned back
+    naive
+        .and_local_timezone(response_timezone())
+        .earliest()
+        .map(|x| x.fixed_offset())
+        .ok_or(anyhow::anyhow!(
+            "{} does not exist in {}",
+            val,
+            response_timezone()
+        ))
+}
+
+/// Midnight UTC of the date as HTTP-date, e.g. for Sunset headers
+pub fn date_to_http_date(date: NaiveDate) -> String {
+    date.and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
 }
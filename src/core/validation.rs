@@ -0,0 +1,227 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::{core::email::is_valid_email, schema::common::UnprocessableEntityResponse};
+
+pub const USER_NAME_MAX_LENGTH: usize = 64;
+/// Names of roles, groups, permissions and the like
+pub const NAME_MAX_LENGTH: usize = 128;
+pub const DESCRIPTION_MAX_LENGTH: usize = 1024;
+/// Profile fields such as first_name and address
+pub const PROFILE_FIELD_MAX_LENGTH: usize = 255;
+/// RFC 5321 path limit
+pub const EMAIL_MAX_LENGTH: usize = 254;
+pub const PASSWORD_MAX_LENGTH: usize = 128;
+pub const PASSWORD_MIN_LENGTH: usize = 8;
+
+/// Letters, digits and . _ - + @ so emails can be used as user names
+static USER_NAME_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z0-9._+@-]+$").unwrap());
+
+/// Request bodies checked field by field before the handler touches the database
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+
+    /// Field level errors located under body, None when the request is valid
+    fn validation_errors(&self) -> Option<UnprocessableEntityResponse> {
+        let mut v = Validator::new("body");
+        self.validate(&mut v);
+        v.finish()
+    }
+}
+
+/// Collects every invalid field instead of stopping at the first one
+pub struct Validator {
+    loc: Vec<String>,
+    errors: UnprocessableEntityResponse,
+}
+
+impl Validator {
+    pub fn new(root: &str) -> Self {
+        Self {
+            loc: vec![root.to_string()],
+            errors: UnprocessableEntityResponse::new(),
+        }
+    }
+
+    pub fn finish(self) -> Option<UnprocessableEntityResponse> {
+        if self.errors.is_has_error() {
+            return Some(self.errors);
+        }
+        None
+    }
+
+    pub fn add_error(&mut self, field: &str, msg: String) -> &mut Self {
+        let mut loc = self.loc.clone();
+        loc.push(field.to_string());
+        self.errors.add_error(loc, msg);
+        self
+    }
+
+    /// Validate the items of a list, errors are located at field.index
+    pub fn each<T>(&mut self, field: &str, items: &[T], f: impl Fn(&mut Self, &T)) -> &mut Self {
+        for (index, item) in items.iter().enumerate() {
+            self.loc.push(field.to_string());
+            self.loc.push(index.to_string());
+            f(self, item);
+            self.loc.truncate(self.loc.len() - 2);
+        }
+        self
+    }
+
+    pub fn required(&mut self, field: &str, val: &str) -> &mut Self {
+        if val.trim().is_empty() {
+            return self.add_error(field, format!("{} is required", field));
+        }
+        self
+    }
+
+    pub fn max_length(&mut self, field: &str, val: Option<&str>, max: usize) -> &mut Self {
+        match val {
+            Some(val) if val.chars().count() > max => self.add_error(
+                field,
+                format!("{} must be at most {} characters", field, max),
+            ),
+            _ => self,
+        }
+    }
+
+    pub fn email(&mut self, field: &str, val: Option<&str>) -> &mut Self {
+        match val.map(str::trim).filter(|x| !x.is_empty()) {
+            Some(val) if val.len() > EMAIL_MAX_LENGTH || !is_valid_email(val) => {
+                self.add_error(field, format!("{} is not a valid email address", val))
+            }
+            _ => self,
+        }
+    }
+
+    pub fn user_name(&mut self, field: &str, val: &str) -> &mut Self {
+        if val.trim().is_empty() {
+            return self.required(field, val);
+        }
+        if !USER_NAME_PATTERN.is_match(val) {
+            return self.add_error(
+                field,
+                format!("{} may only contain letters, digits and . _ - + @", field),
+            );
+        }
+        self.max_length(field, Some(val), USER_NAME_MAX_LENGTH)
+    }
+
+    /// Password policy of passwords chosen by the user: length bounds, at least one letter
+    /// and one digit
+    pub fn password(&mut self, field: &str, val: &str) -> &mut Self {
+        let length = val.chars().count();
+        if length < PASSWORD_MIN_LENGTH {
+            return self.add_error(
+                field,
+                format!(
+                    "{} must be at least {} characters",
+                    field, PASSWORD_MIN_LENGTH
+                ),
+            );
+        }
+        if length > PASSWORD_MAX_LENGTH {
+            return self.max_length(field, Some(val), PASSWORD_MAX_LENGTH);
+        }
+        if !val.chars().any(char::is_alphabetic) || !val.chars().any(|c| c.is_ascii_digit()) {
+            return self.add_error(
+                field,
+                format!("{} must contain a letter and a digit", field),
+            );
+        }
+        self
+    }
+
+    pub fn uuid(&mut self, field: &str, val: &str) -> &mut Self {
+        if Uuid::parse_str(val.trim()).is_err() {
+            return self.add_error(field, format!("{} is not a valid uuid", val));
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poem_openapi::types::ToJSON;
+
+    use crate::core::validation::{Validate, Validator};
+
+    struct Member {
+        user_name: String,
+        email: Option<String>,
+        group_ids: Vec<String>,
+    }
+
+    impl Validate for Member {
+        fn validate(&self, v: &mut Validator) {
+            v.user_name("user_name", &self.user_name)
+                .email("email", self.email.as_deref())
+                .each("group_ids", &self.group_ids, |v, x| {
+                    v.uuid("id", x);
+                });
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        // Given
+        let valid = Member {
+            user_name: "jane.doe@example.com".to_string(),
+            email: None,
+            group_ids: vec!["0195f3c2-8a4b-7c1e-9d2f-3a4b5c6d7e8f".to_string()],
+        };
+        let invalid = Member {
+            user_name: "jane doe".to_string(),
+            email: Some("jane".to_string()),
+            group_ids: vec!["1".to_string()],
+        };
+
+        // Expect
+        assert!(valid.validation_errors().is_none());
+        let errors = invalid.validation_errors().unwrap().to_json().unwrap();
+        assert_eq!(
+            errors["detail"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x["loc"].clone())
+                .collect::<Vec<_>>(),
+            vec![
+                serde_json::json!(["body", "user_name"]),
+                serde_json::json!(["body", "email"]),
+                serde_json::json!(["body", "group_ids", "0", "id"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_password() {
+        // Given
+        let check = |val: &str| {
+            let mut v = Validator::new("body");
+            v.password("password", val);
+            v.finish()
+                .map(|x| x.to_json().unwrap()["detail"][0]["msg"].clone())
+        };
+
+        // Expect
+        assert_eq!(check("s3cret-password"), None);
+        assert_eq!(
+            check("s3cret"),
+            Some(serde_json::json!("password must be at least 8 characters"))
+        );
+        assert_eq!(
+            check("password"),
+            Some(serde_json::json!(
+                "password must contain a letter and a digit"
+            ))
+        );
+        assert_eq!(
+            check(&format!("{}1", "a".repeat(128))),
+            Some(serde_json::json!("password must be at most 128 characters"))
+        );
+    }
+}
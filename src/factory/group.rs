@@ -1,14 +1,29 @@
 use chrono::{DateTime, FixedOffset};
 use fake::{Dummy, Fake, Faker};
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::model::group::{Group, TABLE_NAME};
+use crate::{
+    core::utils::utc_now,
+    model::{
+        group::{Group, TABLE_NAME},
+        group_permission::GroupPermission,
+        permission::Permission,
+        permission_attribute::PermissionAttribute,
+    },
+    repository::group_permission::create_group_permission,
+};
 
 pub struct GroupFactory<T: Clone> {
     modifier_one: fn(x: &Group, ext: T) -> Group,
     modifier_many: fn(x: &Group, idx: usize, ext: T) -> Group,
+    group_name: Option<String>,
+    description: Option<String>,
+    is_active: bool,
+    is_deleted: bool,
+    /// (permission_id, attribute_id) granted on create
+    permissions: Vec<(Uuid, Uuid)>,
 }
 
 impl<T: Clone> Default for GroupFactory<T> {
@@ -22,6 +37,11 @@ impl<T: Clone> GroupFactory<T> {
         Self {
             modifier_one: |x, _| x.clone(),
             modifier_many: |x, _, _| x.clone(),
+            group_name: None,
+            description: None,
+            is_active: true,
+            is_deleted: false,
+            permissions: vec![],
         }
     }
 
@@ -85,6 +105,84 @@ impl<T: Clone> GroupFactory<T> {
     }
 }
 
+/// Fluent builder, e.g. GroupFactory::new().group_name("staff").with_permission(&p, &a).create(&mut tx)
+impl GroupFactory<()> {
+    pub fn group_name(mut self, val: &str) -> Self {
+        self.group_name = Some(val.to_string());
+        self
+    }
+
+    pub fn description(mut self, val: &str) -> Self {
+        self.description = Some(val.to_string());
+        self
+    }
+
+    pub fn active(mut self, val: bool) -> Self {
+        self.is_active = val;
+        self
+    }
+
+    /// Soft deleted group
+    pub fn deleted(mut self) -> Self {
+        self.is_deleted = true;
+        self
+    }
+
+    pub fn with_permission(
+        mut self,
+        permission: &Permission,
+        attribute: &PermissionAttribute,
+    ) -> Self {
+        self.permissions.push((permission.id, attribute.id));
+        self
+    }
+
+    /// Insert the group and its permissions in tx
+    pub async fn create(&self, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Group> {
+        let now = utc_now();
+        let mut data = GroupDummy::new().generate_one();
+        if let Some(val) = &self.group_name {
+            data.group_name = val.clone();
+        }
+        if let Some(val) = &self.description {
+            data.description = Some(val.clone());
+        }
+        data.is_active = Some(self.is_active);
+        data.created_date = Some(now);
+        data.updated_date = Some(now);
+        data.deleted_date = self.is_deleted.then_some(now);
+        sqlx::query(format!(r#"
+        INSERT INTO {} (id, group_name, description, is_active, created_by, updated_by, created_date, updated_date, deleted_date) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#, TABLE_NAME).as_str())
+        .bind(data.id)
+        .bind(&data.group_name)
+        .bind(&data.description)
+        .bind(data.is_active)
+        .bind(data.created_by)
+        .bind(data.updated_by)
+        .bind(data.created_date)
+        .bind(data.updated_date)
+        .bind(data.deleted_date)
+        .execute(&mut **tx).await?;
+        for (permission_id, attribute_id) in self.permissions.iter() {
+            create_group_permission(
+                tx,
+                &GroupPermission {
+                    group_id: data.id,
+                    permission_id: *permission_id,
+                    attribute_id: *attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                },
+            )
+            .await?;
+        }
+        Ok(data)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default, Deserialize, Dummy, Clone)]
 struct GroupDummy {
@@ -116,6 +214,7 @@ impl GroupDummy {
             created_date: dummy.created_date,
             updated_date: dummy.updated_date,
             deleted_date: None,
+            deleted_by: None,
         }
     }
 
@@ -133,6 +232,7 @@ impl GroupDummy {
                 created_date: dummy.created_date,
                 updated_date: dummy.updated_date,
                 deleted_date: None,
+                deleted_by: None,
             });
         }
         result
@@ -146,8 +246,14 @@ mod tests {
     use uuid::Uuid;
 
     use crate::{
-        factory::group::GroupFactory,
-        model::group::{Group, TABLE_NAME},
+        factory::{
+            group::GroupFactory, permission::PermissionFactory,
+            permission_attribute::PermissionAttributeFactory,
+        },
+        model::{
+            group::{Group, TABLE_NAME},
+            group_permission::TABLE_NAME as GROUP_PERMISSION_TABLE_NAME,
+        },
     };
 
     #[derive(Clone)]
@@ -186,6 +292,7 @@ mod tests {
             created_date: Some(ext.created_date),
             updated_date: Some(ext.updated_date),
             deleted_date: None,
+            deleted_by: None,
         });
         let now = Local::now().fixed_offset();
         let ext = ExtData {
@@ -242,6 +349,7 @@ mod tests {
             created_date: Some(ext.created_date),
             updated_date: Some(ext.updated_date),
             deleted_date: None,
+            deleted_by: None,
         });
         let now = Local::now().fixed_offset();
         let ext = ExtData {
@@ -269,4 +377,48 @@ mod tests {
         }
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let mut tx = pool.begin().await?;
+        let attribute = PermissionAttributeFactory::new().create(&mut tx).await?;
+        let permission = PermissionFactory::new()
+            .with_attribute(&attribute)
+            .create(&mut tx)
+            .await?;
+
+        // When
+        let group = GroupFactory::new()
+            .group_name("finance")
+            .active(false)
+            .with_permission(&permission, &attribute)
+            .create(&mut tx)
+            .await?;
+
+        // Expect
+        let res: (String, Option<bool>) = sqlx::query_as(
+            format!(
+                "SELECT group_name, is_active FROM {} WHERE id = $1",
+                TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(group.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(res, ("finance".to_string(), Some(false)));
+        let num_data: (i64,) = sqlx::query_as(
+            format!(
+                "SELECT COUNT(*) FROM {} WHERE group_id = $1",
+                GROUP_PERMISSION_TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(group.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(num_data.0, 1);
+        Ok(())
+    }
 }
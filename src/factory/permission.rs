@@ -1,14 +1,31 @@
 use chrono::{DateTime, FixedOffset};
 use fake::{Dummy, Fake, Faker};
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::model::permission::{Permission, TABLE_NAME};
+use crate::{
+    core::utils::utc_now,
+    model::{
+        permission::{Permission, TABLE_NAME},
+        permission_attribute::PermissionAttribute,
+        permission_attribute_list::PermissionAttributeList,
+    },
+    repository::{
+        permission::create_permission, permission_attribute_list::create_permission_attribute_list,
+    },
+};
 
 pub struct PermissionFactory<T: Clone> {
     modifier_one: fn(x: &Permission, ext: T) -> Permission,
     modifier_many: fn(x: &Permission, idx: usize, ext: T) -> Permission,
+    permission_name: Option<String>,
+    description: Option<String>,
+    is_user: bool,
+    is_role: bool,
+    is_group: bool,
+    /// Attributes the permission can be granted with, permission_attribute_list rows
+    attribute_ids: Vec<Uuid>,
 }
 
 impl<T: Clone> Default for PermissionFactory<T> {
@@ -22,6 +39,12 @@ impl<T: Clone> PermissionFactory<T> {
         Self {
             modifier_one: |x, _| x.clone(),
             modifier_many: |x, _, _| x.clone(),
+            permission_name: None,
+            description: None,
+            is_user: true,
+            is_role: true,
+            is_group: true,
+            attribute_ids: vec![],
         }
     }
 
@@ -91,6 +114,61 @@ impl<T: Clone> PermissionFactory<T> {
     }
 }
 
+/// Fluent builder, e.g. PermissionFactory::new().permission_name("user").with_attribute(&a).create(&mut tx)
+impl PermissionFactory<()> {
+    pub fn permission_name(mut self, val: &str) -> Self {
+        self.permission_name = Some(val.to_string());
+        self
+    }
+
+    pub fn description(mut self, val: &str) -> Self {
+        self.description = Some(val.to_string());
+        self
+    }
+
+    /// Whether users, roles and groups can be granted the permission, all by default
+    pub fn grantable_to(mut self, is_user: bool, is_role: bool, is_group: bool) -> Self {
+        self.is_user = is_user;
+        self.is_role = is_role;
+        self.is_group = is_group;
+        self
+    }
+
+    pub fn with_attribute(mut self, attribute: &PermissionAttribute) -> Self {
+        self.attribute_ids.push(attribute.id);
+        self
+    }
+
+    /// Insert the permission and its attribute list in tx
+    pub async fn create(&self, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Permission> {
+        let now = utc_now();
+        let mut data = PermissionDummy::new().generate_one();
+        if let Some(val) = &self.permission_name {
+            data.permission_name = val.clone();
+        }
+        if let Some(val) = &self.description {
+            data.description = Some(val.clone());
+        }
+        data.is_user = Some(self.is_user);
+        data.is_role = Some(self.is_role);
+        data.is_group = Some(self.is_group);
+        data.created_date = Some(now);
+        data.updated_date = Some(now);
+        create_permission(tx, &data).await?;
+        for attribute_id in self.attribute_ids.iter() {
+            create_permission_attribute_list(
+                tx,
+                &PermissionAttributeList {
+                    permission_id: data.id,
+                    attribute_id: *attribute_id,
+                },
+            )
+            .await?;
+        }
+        Ok(data)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default, Deserialize, Dummy, Clone)]
 struct PermissionDummy {
@@ -156,8 +234,13 @@ mod tests {
 
     use crate::{
         core::utils::{datetime_to_string, datetime_to_string_opt},
-        factory::permission::PermissionFactory,
-        model::permission::{Permission, TABLE_NAME},
+        factory::{
+            permission::PermissionFactory, permission_attribute::PermissionAttributeFactory,
+        },
+        model::{
+            permission::{Permission, TABLE_NAME},
+            permission_attribute_list::TABLE_NAME as PERMISSION_ATTRIBUTE_LIST_TABLE_NAME,
+        },
     };
 
     #[derive(Clone)]
@@ -287,4 +370,52 @@ mod tests {
         }
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let mut tx = pool.begin().await?;
+        let read = PermissionAttributeFactory::new()
+            .name("read")
+            .create(&mut tx)
+            .await?;
+        let write = PermissionAttributeFactory::new()
+            .name("write")
+            .create(&mut tx)
+            .await?;
+
+        // When
+        let permission = PermissionFactory::new()
+            .permission_name("report")
+            .grantable_to(true, false, false)
+            .with_attribute(&read)
+            .with_attribute(&write)
+            .create(&mut tx)
+            .await?;
+
+        // Expect
+        let res: (String, Option<bool>, Option<bool>) = sqlx::query_as(
+            format!(
+                "SELECT permission_name, is_user, is_role FROM {} WHERE id = $1",
+                TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(permission.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(res, ("report".to_string(), Some(true), Some(false)));
+        let num_data: (i64,) = sqlx::query_as(
+            format!(
+                "SELECT COUNT(*) FROM {} WHERE permission_id = $1",
+                PERMISSION_ATTRIBUTE_LIST_TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(permission.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        assert_eq!(num_data.0, 2);
+        Ok(())
+    }
 }
@@ -1,14 +1,21 @@
 use chrono::{DateTime, FixedOffset};
 use fake::{Dummy, Fake, Faker};
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::model::permission_attribute::{PermissionAttribute, TABLE_NAME};
+use crate::{
+    core::utils::utc_now,
+    model::permission_attribute::{PermissionAttribute, TABLE_NAME},
+    repository::permission_attribute::create_permission_attribute,
+};
 
 pub struct PermissionAttributeFactory<T: Clone> {
     modifier_one: fn(x: &PermissionAttribute, ext: T) -> PermissionAttribute,
     modifier_many: fn(x: &PermissionAttribute, idx: usize, ext: T) -> PermissionAttribute,
+    name: Option<String>,
+    description: Option<String>,
+    category: Option<String>,
 }
 
 impl<T: Clone> Default for PermissionAttributeFactory<T> {
@@ -22,6 +29,9 @@ impl<T: Clone> PermissionAttributeFactory<T> {
         Self {
             modifier_one: |x, _| x.clone(),
             modifier_many: |x, _, _| x.clone(),
+            name: None,
+            description: None,
+            category: None,
         }
     }
 
@@ -50,8 +60,8 @@ impl<T: Clone> PermissionAttributeFactory<T> {
         sqlx::query(
             format!(
                 r#"
-        INSERT INTO {} (id, name, description, created_date, updated_date) 
-        VALUES ($1, $2, $3, $4, $5)"#,
+        INSERT INTO {} (id, name, description, category, created_date, updated_date) 
+        VALUES ($1, $2, $3, $4, $5, $6)"#,
                 TABLE_NAME
             )
             .as_str(),
@@ -59,6 +69,7 @@ impl<T: Clone> PermissionAttributeFactory<T> {
         .bind(data.id)
         .bind(&data.name)
         .bind(&data.description)
+        .bind(&data.category)
         .bind(data.created_date)
         .bind(data.updated_date)
         .execute(db)
@@ -83,8 +94,8 @@ impl<T: Clone> PermissionAttributeFactory<T> {
             sqlx::query(
                 format!(
                     r#"
-            INSERT INTO {} (id, name, description, created_date, updated_date) 
-            VALUES ($1, $2, $3, $4, $5)"#,
+            INSERT INTO {} (id, name, description, category, created_date, updated_date) 
+            VALUES ($1, $2, $3, $4, $5, $6)"#,
                     TABLE_NAME
                 )
                 .as_str(),
@@ -92,6 +103,7 @@ impl<T: Clone> PermissionAttributeFactory<T> {
             .bind(item.id)
             .bind(&item.name)
             .bind(&item.description)
+            .bind(&item.category)
             .bind(item.created_date)
             .bind(item.updated_date)
             .execute(&mut *tx)
@@ -102,12 +114,52 @@ impl<T: Clone> PermissionAttributeFactory<T> {
     }
 }
 
+/// Fluent builder, e.g. PermissionAttributeFactory::new().name("read").create(&mut tx)
+impl PermissionAttributeFactory<()> {
+    pub fn name(mut self, val: &str) -> Self {
+        self.name = Some(val.to_string());
+        self
+    }
+
+    pub fn description(mut self, val: &str) -> Self {
+        self.description = Some(val.to_string());
+        self
+    }
+
+    pub fn category(mut self, val: &str) -> Self {
+        self.category = Some(val.to_string());
+        self
+    }
+
+    pub async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> anyhow::Result<PermissionAttribute> {
+        let now = utc_now();
+        let mut data = PermissionAttributeDummy::new().generate_one();
+        if let Some(val) = &self.name {
+            data.name = val.clone();
+        }
+        if let Some(val) = &self.description {
+            data.description = Some(val.clone());
+        }
+        if let Some(val) = &self.category {
+            data.category = Some(val.clone());
+        }
+        data.created_date = Some(now);
+        data.updated_date = Some(now);
+        create_permission_attribute(tx, &data).await?;
+        Ok(data)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default, Deserialize, Dummy, Clone)]
 struct PermissionAttributeDummy {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
+    pub category: Option<String>,
     pub created_date: Option<DateTime<FixedOffset>>,
     pub updated_date: Option<DateTime<FixedOffset>>,
 }
@@ -123,8 +175,10 @@ impl PermissionAttributeDummy {
             id: dummy.id,
             name: dummy.name,
             description: dummy.description,
+            category: dummy.category,
             created_date: Some(Faker.fake::<DateTime<FixedOffset>>()),
             updated_date: Some(Faker.fake::<DateTime<FixedOffset>>()),
+            deleted_date: None,
         }
     }
 
@@ -136,8 +190,10 @@ impl PermissionAttributeDummy {
                 id: dummy.id,
                 name: dummy.name,
                 description: dummy.description,
+                category: dummy.category,
                 created_date: Some(Faker.fake::<DateTime<FixedOffset>>()),
                 updated_date: Some(Faker.fake::<DateTime<FixedOffset>>()),
+                deleted_date: None,
             });
         }
         result
@@ -186,8 +242,10 @@ mod tests {
             id: ext.id,
             name: "test_permission".to_string(),
             description: Some("description".to_string()),
+            category: None,
             created_date: Some(ext.created_date),
             updated_date: Some(ext.updated_date),
+            deleted_date: None,
         });
         let now = Local::now().fixed_offset();
         let ext = ExtData {
@@ -240,8 +298,10 @@ mod tests {
             id: data.id,
             name: data.name.clone(),
             description: Some("description".to_string()),
+            category: None,
             created_date: Some(ext.created_date),
             updated_date: Some(ext.updated_date),
+            deleted_date: None,
         });
         let now = Local::now().fixed_offset();
         let ext = ExtData {
@@ -1,14 +1,41 @@
 use chrono::{DateTime, FixedOffset};
 use fake::{Dummy, Fake, Faker};
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::model::user::User;
+use crate::{
+    core::{security::hash_password, utils::utc_now},
+    model::{
+        group::Group,
+        permission::Permission,
+        permission_attribute::PermissionAttribute,
+        role::Role,
+        user::{User, UserStatus},
+        user_group_roles::UserGroupRoles,
+        user_permission::UserPermission,
+        user_profile::UserProfile,
+    },
+    repository::{
+        user::create_user, user_group_roles::add_user_group_roles,
+        user_permission::create_user_permission,
+    },
+};
 
 pub struct UserFactory<T: Clone> {
     modifier_one: fn(x: &User, ext: T) -> User,
     modifier_many: fn(x: &User, idx: usize, ext: T) -> User,
+    user_name: Option<String>,
+    password: Option<String>,
+    is_active: bool,
+    expires_at: Option<DateTime<FixedOffset>>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    email: Option<String>,
+    /// (group_id, role_id) of the user_group_roles rows
+    group_roles: Vec<(Option<Uuid>, Option<Uuid>)>,
+    /// (permission_id, attribute_id) granted directly on create
+    permissions: Vec<(Uuid, Uuid)>,
 }
 
 impl<T: Clone> Default for UserFactory<T> {
@@ -22,6 +49,15 @@ impl<T: Clone> UserFactory<T> {
         Self {
             modifier_one: |x, _| x.clone(),
             modifier_many: |x, _, _| x.clone(),
+            user_name: None,
+            password: None,
+            is_active: true,
+            expires_at: None,
+            first_name: None,
+            last_name: None,
+            email: None,
+            group_roles: vec![],
+            permissions: vec![],
         }
     }
 
@@ -38,18 +74,20 @@ impl<T: Clone> UserFactory<T> {
         let data = data.generate_one();
         let data = (self.modifier_one)(&data, ext);
         sqlx::query(r#"
-        INSERT INTO public.user (id, user_name, password, is_active, is_2faenabled, created_by, updated_by, created_date, updated_date, deleted_date) 
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#)
+        INSERT INTO public.user (id, user_name, password, is_active, is_2faenabled, created_by, updated_by, created_date, updated_date, deleted_date, status, expires_at) 
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#)
         .bind(data.id)
         .bind(&data.user_name)
         .bind(&data.password)
-        .bind(data.is_active)
+        .bind(data.is_active())
         .bind(data.is_2faenabled)
         .bind(data.created_by)
         .bind(data.updated_by)
         .bind(data.created_date)
         .bind(data.updated_date)
         .bind(data.deleted_date)
+        .bind(data.status)
+        .bind(data.expires_at)
         .execute(db).await?;
         Ok(data.clone())
     }
@@ -68,18 +106,20 @@ impl<T: Clone> UserFactory<T> {
         }
         let mut tx = db.begin().await?;
         for item in result.clone() {
-            sqlx::query(r#"INSERT INTO public.user (id, user_name, password, is_active, is_2faenabled, created_by, updated_by, created_date, updated_date, deleted_date) 
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#)
+            sqlx::query(r#"INSERT INTO public.user (id, user_name, password, is_active, is_2faenabled, created_by, updated_by, created_date, updated_date, deleted_date, status, expires_at) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#)
             .bind(item.id)
             .bind(&item.user_name)
             .bind(&item.password)
-            .bind(item.is_active)
+            .bind(item.is_active())
             .bind(item.is_2faenabled)
             .bind(item.created_by)
             .bind(item.updated_by)
             .bind(item.created_date)
             .bind(item.updated_date)
             .bind(item.deleted_date)
+            .bind(item.status)
+            .bind(item.expires_at)
             .execute(&mut *tx).await?;
         }
         tx.commit().await?;
@@ -87,6 +127,123 @@ impl<T: Clone> UserFactory<T> {
     }
 }
 
+/// Fluent builder, e.g. UserFactory::new().with_group(&g).with_role(&r).active(false).create(&mut tx)
+impl UserFactory<()> {
+    pub fn user_name(mut self, val: &str) -> Self {
+        self.user_name = Some(val.to_string());
+        self
+    }
+
+    /// Plain password, hashed on create so the user can log in with it
+    pub fn password(mut self, val: &str) -> Self {
+        self.password = Some(val.to_string());
+        self
+    }
+
+    /// Active or suspended status
+    pub fn active(mut self, val: bool) -> Self {
+        self.is_active = val;
+        self
+    }
+
+    pub fn expires_at(mut self, val: DateTime<FixedOffset>) -> Self {
+        self.expires_at = Some(val);
+        self
+    }
+
+    pub fn name(mut self, first_name: &str, last_name: &str) -> Self {
+        self.first_name = Some(first_name.to_string());
+        self.last_name = Some(last_name.to_string());
+        self
+    }
+
+    pub fn email(mut self, val: &str) -> Self {
+        self.email = Some(val.to_string());
+        self
+    }
+
+    pub fn with_group(mut self, group: &Group) -> Self {
+        self.group_roles.push((Some(group.id), None));
+        self
+    }
+
+    /// Role in the group added last, or a role outside any group when that one already has a role
+    pub fn with_role(mut self, role: &Role) -> Self {
+        match self.group_roles.last_mut() {
+            Some((Some(_), role_id @ None)) => *role_id = Some(role.id),
+            _ => self.group_roles.push((None, Some(role.id))),
+        }
+        self
+    }
+
+    pub fn with_permission(
+        mut self,
+        permission: &Permission,
+        attribute: &PermissionAttribute,
+    ) -> Self {
+        self.permissions.push((permission.id, attribute.id));
+        self
+    }
+
+    /// Insert the user, its profile, group roles and permissions in tx
+    pub async fn create(&self, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<User> {
+        let now = utc_now();
+        let mut data = UserDummy::new().generate_one();
+        if let Some(val) = &self.user_name {
+            data.user_name = val.clone();
+        }
+        if let Some(val) = &self.password {
+            data.password = hash_password(val).map_err(|err| anyhow::anyhow!(err.to_string()))?;
+        }
+        data.status = UserStatus::from_active(self.is_active);
+        data.expires_at = self.expires_at;
+        data.is_2faenabled = Some(false);
+        data.created_date = Some(now);
+        data.updated_date = Some(now);
+        let profile = UserProfile {
+            id: data.id,
+            user_id: data.id,
+            first_name: self.first_name.clone(),
+            last_name: self.last_name.clone(),
+            address: None,
+            email: self.email.clone(),
+            locale: None,
+            timezone: None,
+            email_verified_at: None,
+        };
+        create_user(tx, &data, &profile).await?;
+        for (group_id, role_id) in self.group_roles.iter() {
+            add_user_group_roles(
+                tx,
+                &UserGroupRoles {
+                    id: Uuid::now_v7(),
+                    user_id: Some(data.id),
+                    group_id: *group_id,
+                    role_id: *role_id,
+                },
+            )
+            .await?;
+        }
+        for (permission_id, attribute_id) in self.permissions.iter() {
+            create_user_permission(
+                tx,
+                &UserPermission {
+                    user_id: data.id,
+                    permission_id: *permission_id,
+                    attribute_id: *attribute_id,
+                    created_by: None,
+                    updated_by: None,
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                    expires_at: None,
+                },
+            )
+            .await?;
+        }
+        Ok(data)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default, Deserialize, Dummy, Clone)]
 struct UserDummy {
@@ -113,13 +270,15 @@ impl UserDummy {
             id: dummy.id,
             user_name: dummy.user_name,
             password: dummy.password,
-            is_active: dummy.is_active,
+            status: UserStatus::from_active(dummy.is_active == Some(true)),
+            expires_at: None,
             is_2faenabled: dummy.is_2faenabled,
             created_by: None,
             updated_by: None,
             created_date: dummy.created_date,
             updated_date: dummy.updated_date,
             deleted_date: None,
+            scopes: None,
         }
     }
 
@@ -131,13 +290,15 @@ impl UserDummy {
                 id: dummy.id,
                 user_name: dummy.user_name,
                 password: dummy.password,
-                is_active: dummy.is_active,
+                status: UserStatus::from_active(dummy.is_active == Some(true)),
+                expires_at: None,
                 is_2faenabled: dummy.is_2faenabled,
                 created_by: None,
                 updated_by: None,
                 created_date: dummy.created_date,
                 updated_date: dummy.updated_date,
                 deleted_date: None,
+                scopes: None,
             });
         }
         result
@@ -151,7 +312,24 @@ mod tests {
     use sqlx::PgPool;
     use uuid::Uuid;
 
-    use crate::{factory::user::UserFactory, model::user::User};
+    use crate::{
+        factory::{group::GroupFactory, role::RoleFactory, user::UserFactory},
+        model::{
+            user::{User, UserStatus},
+            user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+        },
+    };
+
+    type UserRow = (
+        Uuid,
+        String,
+        String,
+        bool,
+        Option<bool>,
+        Option<DateTime<FixedOffset>>,
+        Option<DateTime<FixedOffset>>,
+        Option<DateTime<FixedOffset>>,
+    );
 
     #[derive(Clone)]
     struct ExtData {
@@ -182,13 +360,15 @@ mod tests {
             id: ext.id,
             user_name: "test_user".to_string(),
             password: data.password.clone(),
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             is_2faenabled: Some(false),
             created_by: None,
             updated_by: None,
             created_date: Some(ext.created_date),
             updated_date: Some(ext.updated_date),
             deleted_date: None,
+            scopes: None,
         });
         let now = Local::now().fixed_offset();
         let ext = ExtData {
@@ -199,16 +379,7 @@ mod tests {
         factory.generate_one(&pool, ext.clone()).await?;
 
         // Expect
-        let res: (
-            Uuid,
-            String,
-            String,
-            bool,
-            Option<bool>,
-            Option<DateTime<FixedOffset>>,
-            Option<DateTime<FixedOffset>>,
-            Option<DateTime<FixedOffset>>,
-        ) = sqlx::query_as(
+        let res: UserRow = sqlx::query_as(
             r#"SELECT id, user_name, password, is_active, is_2faenabled, 
         created_date, updated_date, deleted_date 
         FROM public.user"#,
@@ -256,13 +427,15 @@ mod tests {
             id: data.id,
             user_name: data.user_name.clone(),
             password: data.password.clone(),
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             is_2faenabled: Some(false),
             created_by: None,
             updated_by: None,
             created_date: Some(ext.created_date),
             updated_date: Some(ext.updated_date),
             deleted_date: is_deleted(idx % 2 == 0),
+            scopes: None,
         });
         let now = Local::now().fixed_offset();
         let ext = ExtData {
@@ -273,16 +446,7 @@ mod tests {
         factory.generate_many(&pool, 5, ext.clone()).await?;
 
         // Expect
-        let res: Vec<(
-            Uuid,
-            String,
-            String,
-            bool,
-            Option<bool>,
-            Option<DateTime<FixedOffset>>,
-            Option<DateTime<FixedOffset>>,
-            Option<DateTime<FixedOffset>>,
-        )> = sqlx::query_as(
+        let res: Vec<UserRow> = sqlx::query_as(
             r#"SELECT id, user_name, password, is_active, is_2faenabled, 
         created_date, updated_date, deleted_date
         FROM public.user"#,
@@ -302,4 +466,46 @@ mod tests {
         }
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn test_create(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let mut tx = pool.begin().await?;
+        let group = GroupFactory::new().create(&mut tx).await?;
+        let role = RoleFactory::new().create(&mut tx).await?;
+        let other_role = RoleFactory::new().create(&mut tx).await?;
+
+        // When
+        let user = UserFactory::new()
+            .user_name("jane")
+            .with_group(&group)
+            .with_role(&role)
+            .with_role(&other_role)
+            .active(false)
+            .create(&mut tx)
+            .await?;
+
+        // Expect
+        let res: (String, String) =
+            sqlx::query_as(r#"SELECT user_name, status FROM public.user WHERE id = $1"#)
+                .bind(user.id)
+                .fetch_one(&mut *tx)
+                .await?;
+        assert_eq!(res, ("jane".to_string(), UserStatus::Suspended.to_string()));
+        let res: Vec<(Option<Uuid>, Option<Uuid>)> = sqlx::query_as(
+            format!(
+                "SELECT group_id, role_id FROM {} WHERE user_id = $1 ORDER BY id",
+                USER_GROUP_ROLES_TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(user.id)
+        .fetch_all(&mut *tx)
+        .await?;
+        assert_eq!(
+            res,
+            vec![(Some(group.id), Some(role.id)), (None, Some(other_role.id))]
+        );
+        Ok(())
+    }
 }
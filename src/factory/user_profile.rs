@@ -41,8 +41,8 @@ impl<T: Clone> UserProfileFactory<T> {
         let data = (self.modifier_one)(&data, ext);
         sqlx::query(
             r#"
-        INSERT INTO public.user_profile (id, user_id, first_name, last_name, address, email) 
-        VALUES ($1, $2, $3, $4, $5, $6)"#,
+        INSERT INTO public.user_profile (id, user_id, first_name, last_name, address, email, locale, timezone)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
         )
         .bind(data.id)
         .bind(data.user_id)
@@ -50,6 +50,8 @@ impl<T: Clone> UserProfileFactory<T> {
         .bind(&data.last_name)
         .bind(&data.address)
         .bind(&data.email)
+        .bind(&data.locale)
+        .bind(&data.timezone)
         .execute(db)
         .await?;
         Ok(data.clone())
@@ -71,8 +73,8 @@ impl<T: Clone> UserProfileFactory<T> {
         for item in result.clone() {
             sqlx::query(
                 r#"
-            INSERT INTO public.user_profile (id, user_id, first_name, last_name, address, email) 
-            VALUES ($1, $2, $3, $4, $5, $6)"#,
+            INSERT INTO public.user_profile (id, user_id, first_name, last_name, address, email, locale, timezone)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
             )
             .bind(item.id)
             .bind(item.user_id)
@@ -80,6 +82,8 @@ impl<T: Clone> UserProfileFactory<T> {
             .bind(item.last_name)
             .bind(item.address)
             .bind(item.email)
+            .bind(item.locale)
+            .bind(item.timezone)
             .execute(&mut *tx)
             .await?;
         }
@@ -112,6 +116,9 @@ impl UserProfileDummy {
             last_name: dummy.last_name,
             address: dummy.address,
             email: dummy.email,
+            locale: None,
+            timezone: None,
+            email_verified_at: None,
         }
     }
 
@@ -126,6 +133,9 @@ impl UserProfileDummy {
                 last_name: dummy.last_name,
                 address: dummy.address,
                 email: dummy.email,
+                locale: None,
+                timezone: None,
+                email_verified_at: None,
             });
         }
         result
@@ -139,7 +149,10 @@ mod tests {
 
     use crate::{
         factory::{user::UserFactory, user_profile::UserProfileFactory},
-        model::{user::User, user_profile::UserProfile},
+        model::{
+            user::{User, UserStatus},
+            user_profile::UserProfile,
+        },
     };
 
     #[sqlx::test]
@@ -150,16 +163,18 @@ mod tests {
             id: ext,
             user_name: data.user_name.clone(),
             password: data.password.clone(),
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             is_2faenabled: data.is_2faenabled,
             created_by: None,
             updated_by: None,
             created_date: data.created_date,
             updated_date: data.updated_date,
             deleted_date: None,
+            scopes: None,
         });
         let user_id = Uuid::now_v7();
-        user_factory.generate_one(&pool, user_id.clone()).await?;
+        user_factory.generate_one(&pool, user_id).await?;
         let mut factory = UserProfileFactory::<Uuid>::new();
         factory.modified_one(|data, ext| UserProfile {
             id: data.id,
@@ -168,6 +183,9 @@ mod tests {
             last_name: data.last_name.clone(),
             address: data.address.clone(),
             email: data.email.clone(),
+            locale: data.locale.clone(),
+            timezone: data.timezone.clone(),
+            email_verified_at: data.email_verified_at,
         });
         factory.generate_one(&pool, user_id).await?;
 
@@ -187,16 +205,18 @@ mod tests {
             id: ext,
             user_name: data.user_name.clone(),
             password: data.password.clone(),
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             is_2faenabled: data.is_2faenabled,
             created_by: None,
             updated_by: None,
             created_date: data.created_date,
             updated_date: data.updated_date,
             deleted_date: None,
+            scopes: None,
         });
         let user_id = Uuid::now_v7();
-        user_factory.generate_one(&pool, user_id.clone()).await?;
+        user_factory.generate_one(&pool, user_id).await?;
         let mut factory = UserProfileFactory::<Uuid>::new();
         factory.modified_one(|data, ext| UserProfile {
             id: data.id,
@@ -205,8 +225,11 @@ mod tests {
             last_name: data.last_name.clone(),
             address: data.address.clone(),
             email: data.email.clone(),
+            locale: data.locale.clone(),
+            timezone: data.timezone.clone(),
+            email_verified_at: data.email_verified_at,
         });
-        factory.generate_one(&pool, user_id.clone()).await?;
+        factory.generate_one(&pool, user_id).await?;
 
         // Expect
         let res: (Uuid, Uuid, Option<String>) =
@@ -226,16 +249,18 @@ mod tests {
             id: ext,
             user_name: data.user_name.clone(),
             password: data.password.clone(),
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             is_2faenabled: data.is_2faenabled,
             created_by: None,
             updated_by: None,
             created_date: data.created_date,
             updated_date: data.updated_date,
             deleted_date: None,
+            scopes: None,
         });
         let user_id = Uuid::now_v7();
-        user_factory.generate_one(&pool, user_id.clone()).await?;
+        user_factory.generate_one(&pool, user_id).await?;
         let mut factory = UserProfileFactory::new();
         factory.modified_many(|data, _, ext| UserProfile {
             id: data.id,
@@ -244,6 +269,9 @@ mod tests {
             last_name: data.last_name.clone(),
             address: data.address.clone(),
             email: data.email.clone(),
+            locale: data.locale.clone(),
+            timezone: data.timezone.clone(),
+            email_verified_at: data.email_verified_at,
         });
         factory.generate_many(&pool, 10, user_id).await?;
 
@@ -263,16 +291,18 @@ mod tests {
             id: ext,
             user_name: data.user_name.clone(),
             password: data.password.clone(),
-            is_active: Some(true),
+            status: UserStatus::Active,
+            expires_at: None,
             is_2faenabled: data.is_2faenabled,
             created_by: None,
             updated_by: None,
             created_date: data.created_date,
             updated_date: data.updated_date,
             deleted_date: None,
+            scopes: None,
         });
         let user_id = Uuid::now_v7();
-        user_factory.generate_one(&pool, user_id.clone()).await?;
+        user_factory.generate_one(&pool, user_id).await?;
         let mut factory = UserProfileFactory::<Uuid>::new();
         factory.modified_many(|data, _, ext| UserProfile {
             id: data.id,
@@ -281,8 +311,11 @@ mod tests {
             last_name: Some("last".to_string()),
             address: data.address.clone(),
             email: data.email.clone(),
+            locale: data.locale.clone(),
+            timezone: data.timezone.clone(),
+            email_verified_at: data.email_verified_at,
         });
-        factory.generate_many(&pool, 5, user_id.clone()).await?;
+        factory.generate_many(&pool, 5, user_id).await?;
 
         // Expect
         let res: Vec<(Uuid, Uuid, Option<String>, Option<String>)> = sqlx::query_as(
@@ -0,0 +1,333 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    sync::Mutex,
+};
+
+use async_graphql::Context;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    graphql::{db, internal},
+    model::{
+        group::Group, permission::Permission, permission_attribute::PermissionAttribute,
+        role::Role, user_group_roles::UserGroupRoles, user_profile::UserProfile,
+    },
+    repository::{
+        group::get_groups_by_ids,
+        group_permission::get_group_permissions_by_group_ids,
+        permission::get_permissions_by_ids,
+        permission_attribute::get_permission_attribute_by_ids,
+        role::get_roles_by_ids,
+        role_permission::get_role_permissions_by_role_ids,
+        user::{get_user_group_roles_by_user_ids, get_user_profiles_by_user_ids},
+        user_permission::get_user_permissions_by_user_ids,
+    },
+};
+
+/// Fetches the values of many keys in one query, keys without a row are left out
+pub trait Loader: Default + Send + Sync {
+    type Key: Clone + Eq + Hash + Send + Sync;
+    type Value: Clone + Send + Sync;
+
+    /// Identifier of the query in the logged error
+    const NAME: &'static str;
+
+    fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Self::Key],
+    ) -> impl Future<Output = anyhow::Result<HashMap<Self::Key, Self::Value>>> + Send;
+}
+
+/// Batches the keys requested by sibling resolvers into one Loader query and caches the
+/// values for the rest of the request
+pub struct DataLoader<L: Loader> {
+    loader: L,
+    pending: Mutex<HashSet<L::Key>>,
+    cache: tokio::sync::Mutex<HashMap<L::Key, Option<L::Value>>>,
+}
+
+impl<L: Loader> Default for DataLoader<L> {
+    fn default() -> Self {
+        Self {
+            loader: L::default(),
+            pending: Mutex::new(HashSet::new()),
+            cache: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<L: Loader> DataLoader<L> {
+    pub async fn load_one(
+        &self,
+        ctx: &Context<'_>,
+        key: L::Key,
+    ) -> async_graphql::Result<Option<L::Value>> {
+        Ok(self.load_many(ctx, [key]).await?.into_values().next())
+    }
+
+    pub async fn load_many(
+        &self,
+        ctx: &Context<'_>,
+        keys: impl IntoIterator<Item = L::Key>,
+    ) -> async_graphql::Result<HashMap<L::Key, L::Value>> {
+        let keys: Vec<L::Key> = keys.into_iter().collect();
+        {
+            let cache = self.cache.lock().await;
+            if keys.iter().all(|x| cache.contains_key(x)) {
+                return Ok(cached(&cache, keys));
+            }
+        }
+        self.pending.lock().unwrap().extend(keys.iter().cloned());
+        // sibling resolvers are polled before this one resumes, their keys join the batch
+        tokio::task::yield_now().await;
+
+        let mut cache = self.cache.lock().await;
+        let mut batch: HashSet<L::Key> = self.pending.lock().unwrap().drain().collect();
+        batch.extend(keys.iter().cloned());
+        let batch: Vec<L::Key> = batch
+            .into_iter()
+            .filter(|x| !cache.contains_key(x))
+            .collect();
+        if !batch.is_empty() {
+            let mut values = {
+                let mut conn = db(ctx).await?;
+                self.loader
+                    .load(&mut conn, &batch)
+                    .await
+                    .map_err(|err| internal("DataLoader", L::NAME, err))?
+            };
+            for key in batch {
+                let value = values.remove(&key);
+                cache.insert(key, value);
+            }
+        }
+        Ok(cached(&cache, keys))
+    }
+}
+
+fn cached<K: Clone + Eq + Hash, V: Clone>(
+    cache: &HashMap<K, Option<V>>,
+    keys: Vec<K>,
+) -> HashMap<K, V> {
+    keys.into_iter()
+        .filter_map(|key| {
+            let value = cache.get(&key)?.clone()?;
+            Some((key, value))
+        })
+        .collect()
+}
+
+fn group_by<T, K: Eq + Hash, V>(
+    rows: Vec<T>,
+    key: impl Fn(&T) -> K,
+    value: impl Fn(T) -> V,
+) -> HashMap<K, Vec<V>> {
+    let mut res: HashMap<K, Vec<V>> = HashMap::new();
+    for row in rows {
+        res.entry(key(&row)).or_default().push(value(row));
+    }
+    res
+}
+
+/// Loaders of one request, see RequestContext
+#[derive(Default)]
+pub struct Loaders {
+    pub user_profiles: DataLoader<UserProfileLoader>,
+    pub user_group_roles: DataLoader<UserGroupRolesLoader>,
+    pub roles: DataLoader<RoleLoader>,
+    pub groups: DataLoader<GroupLoader>,
+    pub permissions: DataLoader<PermissionLoader>,
+    pub permission_attributes: DataLoader<PermissionAttributeLoader>,
+    pub user_grants: DataLoader<UserGrantLoader>,
+    pub role_grants: DataLoader<RoleGrantLoader>,
+    pub group_grants: DataLoader<GroupGrantLoader>,
+}
+
+/// Profile by user id
+#[derive(Default)]
+pub struct UserProfileLoader;
+
+impl Loader for UserProfileLoader {
+    type Key = Uuid;
+    type Value = UserProfile;
+    const NAME: &'static str = "get_user_profiles_by_user_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, UserProfile>> {
+        let data = get_user_profiles_by_user_ids(conn, keys).await?;
+        Ok(data.into_iter().map(|x| (x.user_id, x)).collect())
+    }
+}
+
+/// Group role assignments by user id
+#[derive(Default)]
+pub struct UserGroupRolesLoader;
+
+impl Loader for UserGroupRolesLoader {
+    type Key = Uuid;
+    type Value = Vec<UserGroupRoles>;
+    const NAME: &'static str = "get_user_group_roles_by_user_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Vec<UserGroupRoles>>> {
+        let data = get_user_group_roles_by_user_ids(conn, keys).await?;
+        let mut res: HashMap<Uuid, Vec<UserGroupRoles>> = HashMap::new();
+        for item in data {
+            if let Some(user_id) = item.user_id {
+                res.entry(user_id).or_default().push(item);
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Default)]
+pub struct RoleLoader;
+
+impl Loader for RoleLoader {
+    type Key = Uuid;
+    type Value = Role;
+    const NAME: &'static str = "get_roles_by_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Role>> {
+        let data = get_roles_by_ids(conn, keys).await?;
+        Ok(data.into_iter().map(|x| (x.id, x)).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct GroupLoader;
+
+impl Loader for GroupLoader {
+    type Key = Uuid;
+    type Value = Group;
+    const NAME: &'static str = "get_groups_by_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Group>> {
+        let data = get_groups_by_ids(conn, keys).await?;
+        Ok(data.into_iter().map(|x| (x.id, x)).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct PermissionLoader;
+
+impl Loader for PermissionLoader {
+    type Key = Uuid;
+    type Value = Permission;
+    const NAME: &'static str = "get_permissions_by_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Permission>> {
+        let data = get_permissions_by_ids(conn, keys).await?;
+        Ok(data.into_iter().map(|x| (x.id, x)).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct PermissionAttributeLoader;
+
+impl Loader for PermissionAttributeLoader {
+    type Key = Uuid;
+    type Value = PermissionAttribute;
+    const NAME: &'static str = "get_permission_attribute_by_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, PermissionAttribute>> {
+        let data = get_permission_attribute_by_ids(conn, keys.to_vec()).await?;
+        Ok(data.into_iter().map(|x| (x.id, x)).collect())
+    }
+}
+
+/// Direct (permission_id, attribute_id) grants by user id
+#[derive(Default)]
+pub struct UserGrantLoader;
+
+impl Loader for UserGrantLoader {
+    type Key = Uuid;
+    type Value = Vec<(Uuid, Uuid)>;
+    const NAME: &'static str = "get_user_permissions_by_user_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Vec<(Uuid, Uuid)>>> {
+        let data = get_user_permissions_by_user_ids(conn, keys).await?;
+        Ok(group_by(
+            data,
+            |x| x.user_id,
+            |x| (x.permission_id, x.attribute_id),
+        ))
+    }
+}
+
+/// (permission_id, attribute_id) grants by role id
+#[derive(Default)]
+pub struct RoleGrantLoader;
+
+impl Loader for RoleGrantLoader {
+    type Key = Uuid;
+    type Value = Vec<(Uuid, Uuid)>;
+    const NAME: &'static str = "get_role_permissions_by_role_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Vec<(Uuid, Uuid)>>> {
+        let data = get_role_permissions_by_role_ids(conn, keys).await?;
+        Ok(group_by(
+            data,
+            |x| x.role_id,
+            |x| (x.permission_id, x.attribute_id),
+        ))
+    }
+}
+
+/// (permission_id, attribute_id) grants by group id
+#[derive(Default)]
+pub struct GroupGrantLoader;
+
+impl Loader for GroupGrantLoader {
+    type Key = Uuid;
+    type Value = Vec<(Uuid, Uuid)>;
+    const NAME: &'static str = "get_group_permissions_by_group_ids";
+
+    async fn load(
+        &self,
+        conn: &mut PgConnection,
+        keys: &[Uuid],
+    ) -> anyhow::Result<HashMap<Uuid, Vec<(Uuid, Uuid)>>> {
+        let data = get_group_permissions_by_group_ids(conn, keys).await?;
+        Ok(group_by(
+            data,
+            |x| x.group_id,
+            |x| (x.permission_id, x.attribute_id),
+        ))
+    }
+}
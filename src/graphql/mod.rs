@@ -0,0 +1,309 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{Context, EmptyMutation, EmptySubscription, ErrorExtensions, Guard, Schema};
+use async_graphql_poem::{GraphQLRequest, GraphQLResponse};
+use poem::{handler, web::Data, Request};
+use sqlx::{pool::PoolConnection, Postgres};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::{
+    core::{
+        security::{bearer_token, get_user_from_token, permission_required, require_permission},
+        session_store::SessionConn,
+    },
+    model::user::User,
+    schema::common::InternalServerErrorResponse,
+    AppState,
+};
+
+pub mod loader;
+pub mod object;
+pub mod query;
+
+use loader::Loaders;
+
+use query::Query;
+
+pub type CoreSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub const ATTRIBUTE_READ: &str = "read";
+pub const USER_PERMISSION_NAME: &str = "user";
+pub const ROLE_PERMISSION_NAME: &str = "role";
+pub const GROUP_PERMISSION_NAME: &str = "group";
+pub const PERMISSION_PERMISSION_NAME: &str = "permission";
+
+/// Nesting allowed in one query, user -> roles -> permissions -> permission is 4
+const MAX_DEPTH: usize = 8;
+
+/// State shared by the resolvers of one request, they take turns on a single database
+/// and redis connection
+pub struct RequestContext {
+    /// Caller of the query, None without a valid bearer token
+    pub user: Option<User>,
+    pub loaders: Loaders,
+    db: Mutex<PoolConnection<Postgres>>,
+    redis_conn: Mutex<SessionConn>,
+    /// Answers of require_permission, guards of nested fields run once per parent object
+    permissions: Mutex<HashMap<String, bool>>,
+}
+
+pub fn init_schema(app_state: Arc<AppState>) -> CoreSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(app_state)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+/// Logged like the rest handlers, see InternalServerErrorResponse
+pub(crate) fn internal(
+    function: &str,
+    identifier: &str,
+    err: impl ToString,
+) -> async_graphql::Error {
+    let res = InternalServerErrorResponse::new("graphql", function, identifier, &err.to_string());
+    let error = async_graphql::Error::new(res.detail.unwrap_or(res.message));
+    match res.request_id {
+        Some(request_id) => error.extend_with(|_, e| e.set("request_id", request_id)),
+        None => error,
+    }
+}
+
+pub(crate) fn request_context<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a RequestContext> {
+    ctx.data::<RequestContext>()
+}
+
+/// Connection of the request, held until the guard is dropped so keep it out of awaits
+/// on loaders
+pub(crate) async fn db<'a>(
+    ctx: &Context<'a>,
+) -> async_graphql::Result<MutexGuard<'a, PoolConnection<Postgres>>> {
+    Ok(request_context(ctx)?.db.lock().await)
+}
+
+pub(crate) fn request_user<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a User> {
+    ctx.data_opt::<RequestContext>()
+        .and_then(|x| x.user.as_ref())
+        .ok_or(async_graphql::Error::new("unauthorized"))
+}
+
+/// Caller is the user or holds user read permission, guards personal fields
+pub(crate) async fn authorize_self_or_read(
+    ctx: &Context<'_>,
+    user_id: &uuid::Uuid,
+) -> async_graphql::Result<()> {
+    if request_user(ctx)?.id == *user_id {
+        return Ok(());
+    }
+    PermissionGuard::new(USER_PERMISSION_NAME, ATTRIBUTE_READ)
+        .check(ctx)
+        .await
+}
+
+/// Field level authorization, the caller must hold the permission attribute within the
+/// scopes of its token
+pub struct PermissionGuard {
+    permission_name: &'static str,
+    attribute_name: &'static str,
+}
+
+impl PermissionGuard {
+    pub fn new(permission_name: &'static str, attribute_name: &'static str) -> Self {
+        Self {
+            permission_name,
+            attribute_name,
+        }
+    }
+}
+
+impl Guard for PermissionGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let user = request_user(ctx)?;
+        let request = request_context(ctx)?;
+        let permission = format!("{}.{}", self.permission_name, self.attribute_name);
+        let mut permissions = request.permissions.lock().await;
+        let is_allowed = match permissions.get(&permission) {
+            Some(val) => *val,
+            None => {
+                let mut conn = request.db.lock().await;
+                let mut redis_conn = request.redis_conn.lock().await;
+                let val = require_permission(&mut conn, &mut *redis_conn, user, &permission)
+                    .await
+                    .map_err(|err| internal("PermissionGuard", "require_permission", err))?;
+                permissions.insert(permission.clone(), val);
+                val
+            }
+        };
+        if !is_allowed {
+            return Err(async_graphql::Error::new(permission_required(&permission)));
+        }
+        Ok(())
+    }
+}
+
+/// POST {prefix}/graphql, the bearer token resolves the caller the guards check
+#[handler]
+pub async fn graphql_handler(
+    schema: Data<&CoreSchema>,
+    state: Data<&Arc<AppState>>,
+    req: &Request,
+    gql_req: GraphQLRequest,
+) -> GraphQLResponse {
+    let request = match request_context_from(&state, bearer_token(req)).await {
+        Ok(val) => val,
+        Err(err) => {
+            return async_graphql::Response::from_errors(vec![
+                err.into_server_error(async_graphql::Pos::default())
+            ])
+            .into()
+        }
+    };
+    schema.execute(gql_req.0.data(request)).await.into()
+}
+
+async fn request_context_from(
+    state: &Arc<AppState>,
+    token: Option<String>,
+) -> async_graphql::Result<RequestContext> {
+    let mut conn = state
+        .db
+        .acquire()
+        .await
+        .map_err(|err| internal("graphql_handler", "acquire connection", err))?;
+    let mut redis_conn = state
+        .redis_conn
+        .get()
+        .await
+        .map_err(|err| internal("graphql_handler", "get redis pool connection", err))?;
+    let user = get_user_from_token(&mut conn, &mut redis_conn, token)
+        .await
+        .map_err(|err| internal("graphql_handler", "get user from token", err))?;
+    Ok(RequestContext {
+        user,
+        loaders: Loaders::default(),
+        db: Mutex::new(conn),
+        redis_conn: Mutex::new(redis_conn),
+        permissions: Mutex::new(HashMap::new()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use poem::test::TestClient;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+
+    use crate::{
+        core::{
+            session_store::create_redis_pool,
+            test_utils::{generate_test_user, grant_test_permissions},
+        },
+        factory::role::RoleFactory,
+        init_openapi_route,
+        settings::get_config,
+        AppState,
+    };
+
+    #[sqlx::test]
+    async fn test_graphql_handler(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let mut config = get_config();
+        config.prefix = Some("/api".to_string());
+        let redis_pool = create_redis_pool(&config).unwrap();
+        let app_state = Arc::new(AppState {
+            db: pool,
+            redis_conn: redis_pool.into(),
+        });
+        let mut db = app_state.db.acquire().await?;
+        let mut redis_conn = app_state.redis_conn.get().await?;
+        let test_user = generate_test_user(
+            &mut db,
+            &mut redis_conn,
+            config.clone(),
+            "test_user",
+            "password",
+        )
+        .await?;
+        let mut tx = app_state.db.begin().await?;
+        RoleFactory::new()
+            .role_name("auditor")
+            .create(&mut tx)
+            .await?;
+        tx.commit().await?;
+        let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+
+        // When the caller asks for itself and for roles without role read permission
+        let resp = cli
+            .post("/api/graphql")
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .body_json(
+                &json!({"query": "{ me { userName profile { email } } roles { roleName } }"}),
+            )
+            .send()
+            .await;
+
+        // Expect own data, roles rejected
+        resp.assert_status_is_ok();
+        let body: Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+        assert_eq!(body["data"]["me"]["userName"], "test_user");
+        assert_eq!(body["data"]["roles"], Value::Null);
+        assert_eq!(
+            body["errors"][0]["message"],
+            "role read permission required"
+        );
+
+        // When the caller holds role and permission read, with a lowercase scheme
+        let grants = grant_test_permissions(
+            &app_state.db,
+            &mut redis_conn,
+            &test_user.user.id,
+            &["role.read", "permission.read"],
+        )
+        .await?;
+        let resp = cli
+            .post("/api/graphql")
+            .header("authorization", format!("bearer {}", test_user.token))
+            .body_json(&json!({"query": "{ roles { roleName permissions { permission { permissionName } attribute { name } } } }"}))
+            .send()
+            .await;
+
+        // Expect the roles with their nested grants
+        resp.assert_status_is_ok();
+        let body: Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+        assert_eq!(body["errors"], Value::Null);
+        let roles = body["data"]["roles"].as_array().unwrap();
+        let auditor = roles.iter().find(|x| x["roleName"] == "auditor").unwrap();
+        assert_eq!(auditor["permissions"], json!([]));
+        let granted = roles
+            .iter()
+            .find(|x| x["roleName"] == grants.role_name.as_str())
+            .unwrap();
+        let mut names: Vec<String> = granted["permissions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| {
+                format!(
+                    "{}.{}",
+                    x["permission"]["permissionName"].as_str().unwrap(),
+                    x["attribute"]["name"].as_str().unwrap()
+                )
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["permission.read", "role.read"]);
+
+        // When without a token
+        let resp = cli
+            .post("/api/graphql")
+            .body_json(&json!({"query": "{ me { userName } }"}))
+            .send()
+            .await;
+
+        // Expect
+        let body: Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+        assert_eq!(body["errors"][0]["message"], "unauthorized");
+        Ok(())
+    }
+}
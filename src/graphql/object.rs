@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use async_graphql::{Context, Object, ID};
+use uuid::Uuid;
+
+use crate::{
+    core::utils::datetime_to_string_opt,
+    graphql::{
+        authorize_self_or_read,
+        loader::{DataLoader, Loader, Loaders},
+        request_context, PermissionGuard, ATTRIBUTE_READ, GROUP_PERMISSION_NAME,
+        PERMISSION_PERMISSION_NAME, ROLE_PERMISSION_NAME,
+    },
+    model::{
+        group::Group, permission::Permission, permission_attribute::PermissionAttribute,
+        role::Role, user::User, user_profile::UserProfile,
+    },
+};
+
+fn loaders<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Loaders> {
+    Ok(&request_context(ctx)?.loaders)
+}
+
+/// Rows of the ids in the order of the ids, missing rows are skipped
+async fn load_ordered<L: Loader<Key = Uuid>>(
+    ctx: &Context<'_>,
+    loader: &DataLoader<L>,
+    mut ids: Vec<Uuid>,
+) -> async_graphql::Result<Vec<L::Value>> {
+    ids.sort();
+    ids.dedup();
+    let mut rows: HashMap<Uuid, L::Value> = loader.load_many(ctx, ids.clone()).await?;
+    Ok(ids.iter().filter_map(|x| rows.remove(x)).collect())
+}
+
+/// (permission_id, attribute_id) pairs resolved to their rows, missing rows are skipped
+async fn grants(
+    ctx: &Context<'_>,
+    ids: Vec<(Uuid, Uuid)>,
+) -> async_graphql::Result<Vec<GrantObject>> {
+    let loaders = loaders(ctx)?;
+    let permissions = loaders
+        .permissions
+        .load_many(ctx, ids.iter().map(|x| x.0))
+        .await?;
+    let attributes = loaders
+        .permission_attributes
+        .load_many(ctx, ids.iter().map(|x| x.1))
+        .await?;
+    Ok(ids
+        .into_iter()
+        .filter_map(|(permission_id, attribute_id)| {
+            Some(GrantObject {
+                permission: PermissionObject(permissions.get(&permission_id)?.clone()),
+                attribute: PermissionAttributeObject(attributes.get(&attribute_id)?.clone()),
+            })
+        })
+        .collect())
+}
+
+pub struct UserObject(pub User);
+
+#[Object(name = "User")]
+impl UserObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn user_name(&self) -> &str {
+        &self.0.user_name
+    }
+
+    async fn status(&self) -> &str {
+        self.0.status.as_str()
+    }
+
+    async fn is_active(&self) -> Option<bool> {
+        Some(self.0.is_active())
+    }
+
+    async fn expires_at(&self) -> Option<String> {
+        datetime_to_string_opt(self.0.expires_at)
+    }
+
+    async fn created_date(&self) -> Option<String> {
+        datetime_to_string_opt(self.0.created_date)
+    }
+
+    async fn updated_date(&self) -> Option<String> {
+        datetime_to_string_opt(self.0.updated_date)
+    }
+
+    /// Name, email and address, the user itself or user read permission
+    async fn profile(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserProfileObject>> {
+        authorize_self_or_read(ctx, &self.0.id).await?;
+        let user_profile = loaders(ctx)?.user_profiles.load_one(ctx, self.0.id).await?;
+        Ok(user_profile.map(UserProfileObject))
+    }
+
+    /// Roles assigned directly or within a group
+    #[graphql(guard = "PermissionGuard::new(ROLE_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn roles(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vec<RoleObject>>> {
+        let loaders = loaders(ctx)?;
+        let assignments = loaders
+            .user_group_roles
+            .load_one(ctx, self.0.id)
+            .await?
+            .unwrap_or_default();
+        let role_ids = assignments.iter().filter_map(|x| x.role_id).collect();
+        let roles = load_ordered(ctx, &loaders.roles, role_ids).await?;
+        Ok(Some(roles.into_iter().map(RoleObject).collect()))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(GROUP_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn groups(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vec<GroupObject>>> {
+        let loaders = loaders(ctx)?;
+        let assignments = loaders
+            .user_group_roles
+            .load_one(ctx, self.0.id)
+            .await?
+            .unwrap_or_default();
+        let group_ids = assignments.iter().filter_map(|x| x.group_id).collect();
+        let groups = load_ordered(ctx, &loaders.groups, group_ids).await?;
+        Ok(Some(groups.into_iter().map(GroupObject).collect()))
+    }
+
+    /// Granted to the user directly, not through roles or groups
+    #[graphql(guard = "PermissionGuard::new(PERMISSION_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn permissions(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<Vec<GrantObject>>> {
+        let ids = loaders(ctx)?
+            .user_grants
+            .load_one(ctx, self.0.id)
+            .await?
+            .unwrap_or_default();
+        Ok(Some(grants(ctx, ids).await?))
+    }
+}
+
+pub struct UserProfileObject(pub UserProfile);
+
+#[Object(name = "UserProfile")]
+impl UserProfileObject {
+    async fn first_name(&self) -> Option<&str> {
+        self.0.first_name.as_deref()
+    }
+
+    async fn last_name(&self) -> Option<&str> {
+        self.0.last_name.as_deref()
+    }
+
+    async fn email(&self) -> Option<&str> {
+        self.0.email.as_deref()
+    }
+
+    async fn address(&self) -> Option<&str> {
+        self.0.address.as_deref()
+    }
+}
+
+pub struct RoleObject(pub Role);
+
+#[Object(name = "Role")]
+impl RoleObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn role_name(&self) -> &str {
+        &self.0.role_name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn is_active(&self) -> Option<bool> {
+        self.0.is_active
+    }
+
+    #[graphql(guard = "PermissionGuard::new(PERMISSION_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn permissions(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<Vec<GrantObject>>> {
+        let ids = loaders(ctx)?
+            .role_grants
+            .load_one(ctx, self.0.id)
+            .await?
+            .unwrap_or_default();
+        Ok(Some(grants(ctx, ids).await?))
+    }
+}
+
+pub struct GroupObject(pub Group);
+
+#[Object(name = "Group")]
+impl GroupObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn group_name(&self) -> &str {
+        &self.0.group_name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn is_active(&self) -> Option<bool> {
+        self.0.is_active
+    }
+
+    #[graphql(guard = "PermissionGuard::new(PERMISSION_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn permissions(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<Vec<GrantObject>>> {
+        let ids = loaders(ctx)?
+            .group_grants
+            .load_one(ctx, self.0.id)
+            .await?
+            .unwrap_or_default();
+        Ok(Some(grants(ctx, ids).await?))
+    }
+}
+
+pub struct PermissionObject(pub Permission);
+
+#[Object(name = "Permission")]
+impl PermissionObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn permission_name(&self) -> &str {
+        &self.0.permission_name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn is_user(&self) -> Option<bool> {
+        self.0.is_user
+    }
+
+    async fn is_role(&self) -> Option<bool> {
+        self.0.is_role
+    }
+
+    async fn is_group(&self) -> Option<bool> {
+        self.0.is_group
+    }
+}
+
+pub struct PermissionAttributeObject(pub PermissionAttribute);
+
+#[Object(name = "PermissionAttribute")]
+impl PermissionAttributeObject {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn category(&self) -> Option<&str> {
+        self.0.category.as_deref()
+    }
+}
+
+/// Permission attribute granted to a user, role or group
+pub struct GrantObject {
+    permission: PermissionObject,
+    attribute: PermissionAttributeObject,
+}
+
+#[Object(name = "Grant")]
+impl GrantObject {
+    async fn permission(&self) -> &PermissionObject {
+        &self.permission
+    }
+
+    async fn attribute(&self) -> &PermissionAttributeObject {
+        &self.attribute
+    }
+}
@@ -0,0 +1,170 @@
+use async_graphql::{Context, Object, ID};
+use uuid::Uuid;
+
+use crate::{
+    core::sqlx_utils::Sort,
+    graphql::{
+        db, internal,
+        object::{GroupObject, PermissionObject, RoleObject, UserObject},
+        request_user, PermissionGuard, ATTRIBUTE_READ, GROUP_PERMISSION_NAME,
+        PERMISSION_PERMISSION_NAME, ROLE_PERMISSION_NAME, USER_PERMISSION_NAME,
+    },
+    repository::{
+        group::{get_group_by_id, paginate_group},
+        permission::{get_all_permission, get_permission_by_id},
+        role::{get_role_by_id, paginate_role},
+        user::{get_all_user, get_user_by_id, UserFilter},
+    },
+};
+
+/// Largest page_size accepted by the list fields
+const MAX_PAGE_SIZE: u32 = 100;
+
+fn parse_id(id: &ID) -> async_graphql::Result<Uuid> {
+    Uuid::parse_str(id).map_err(|_| async_graphql::Error::new("invalid id"))
+}
+
+fn page_args(page: Option<u32>, page_size: Option<u32>) -> (u32, u32) {
+    (
+        page.unwrap_or(1).max(1),
+        page_size.unwrap_or(10).clamp(1, MAX_PAGE_SIZE),
+    )
+}
+
+/// List and detail fields are nullable so a rejected field leaves the rest of the query intact
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Caller of the query
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<UserObject> {
+        Ok(UserObject(request_user(ctx)?.clone()))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(USER_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<u32>,
+        page_size: Option<u32>,
+        search: Option<String>,
+    ) -> async_graphql::Result<Option<Vec<UserObject>>> {
+        let (page, page_size) = page_args(page, page_size);
+        let mut conn = db(ctx).await?;
+        let filter = UserFilter {
+            search,
+            ..Default::default()
+        };
+        let (data, _, _) = get_all_user(&mut conn, page, page_size, filter, None, Sort::default())
+            .await
+            .map_err(|err| internal("users", "get_all_user", err))?;
+        Ok(Some(data.into_iter().map(|x| UserObject(x.data)).collect()))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(USER_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn user(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<UserObject>> {
+        let id = parse_id(&id)?;
+        let mut conn = db(ctx).await?;
+        let (user, _) = get_user_by_id(&mut conn, &id, None)
+            .await
+            .map_err(|err| internal("user", "get_user_by_id", err))?;
+        Ok(user.map(UserObject))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(ROLE_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn roles(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<u32>,
+        page_size: Option<u32>,
+        search: Option<String>,
+    ) -> async_graphql::Result<Option<Vec<RoleObject>>> {
+        let (page, page_size) = page_args(page, page_size);
+        let mut conn = db(ctx).await?;
+        let (data, _, _) = paginate_role(&mut conn, page, page_size, search, Sort::default())
+            .await
+            .map_err(|err| internal("roles", "paginate_role", err))?;
+        Ok(Some(data.into_iter().map(|x| RoleObject(x.data)).collect()))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(ROLE_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn role(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<RoleObject>> {
+        let id = parse_id(&id)?;
+        let mut conn = db(ctx).await?;
+        let role = get_role_by_id(&mut conn, &id)
+            .await
+            .map_err(|err| internal("role", "get_role_by_id", err))?;
+        Ok(role.map(RoleObject))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(GROUP_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn groups(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<u32>,
+        page_size: Option<u32>,
+        search: Option<String>,
+    ) -> async_graphql::Result<Option<Vec<GroupObject>>> {
+        let (page, page_size) = page_args(page, page_size);
+        let mut conn = db(ctx).await?;
+        let (data, _, _) = paginate_group(&mut conn, page, page_size, search, Sort::default())
+            .await
+            .map_err(|err| internal("groups", "paginate_group", err))?;
+        Ok(Some(
+            data.into_iter().map(|x| GroupObject(x.data)).collect(),
+        ))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(GROUP_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn group(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<GroupObject>> {
+        let id = parse_id(&id)?;
+        let mut conn = db(ctx).await?;
+        let group = get_group_by_id(&mut conn, &id)
+            .await
+            .map_err(|err| internal("group", "get_group_by_id", err))?;
+        Ok(group.map(GroupObject))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(PERMISSION_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn permissions(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<u32>,
+        page_size: Option<u32>,
+        search: Option<String>,
+    ) -> async_graphql::Result<Option<Vec<PermissionObject>>> {
+        let (page, page_size) = page_args(page, page_size);
+        let mut conn = db(ctx).await?;
+        let (data, _, _) = get_all_permission(
+            &mut conn,
+            Some(page),
+            Some(page_size),
+            search,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Sort::default(),
+        )
+        .await
+        .map_err(|err| internal("permissions", "get_all_permission", err))?;
+        Ok(Some(
+            data.into_iter().map(|x| PermissionObject(x.data)).collect(),
+        ))
+    }
+
+    #[graphql(guard = "PermissionGuard::new(PERMISSION_PERMISSION_NAME, ATTRIBUTE_READ)")]
+    async fn permission(
+        &self,
+        ctx: &Context<'_>,
+        id: ID,
+    ) -> async_graphql::Result<Option<PermissionObject>> {
+        let id = parse_id(&id)?;
+        let mut conn = db(ctx).await?;
+        let permission = get_permission_by_id(&mut conn, &id)
+            .await
+            .map_err(|err| internal("permission", "get_permission_by_id", err))?;
+        Ok(permission.map(PermissionObject))
+    }
+}
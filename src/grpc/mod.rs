@@ -0,0 +1,346 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use sqlx::{Postgres, Transaction};
+use tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status};
+use uuid::Uuid;
+
+use crate::{
+    core::security::get_user_from_token,
+    model::{
+        user::User,
+        user_contact::{PERMISSION_ATTRIBUTE_READ, PERMISSION_NAME},
+        user_profile::UserProfile,
+    },
+    repository::{user::get_user_by_id, user_permission::user_has_permission},
+    schema::common::InternalServerErrorResponse,
+    AppState,
+};
+
+pub mod proto {
+    tonic::include_proto!("core.v1");
+}
+
+use proto::{
+    core_server::{Core, CoreServer},
+    CheckPermissionRequest, CheckPermissionResponse, GetUserRequest, ValidateTokenRequest,
+    ValidateTokenResponse,
+};
+
+/// Logged like the rest handlers, see InternalServerErrorResponse
+fn internal(function: &str, identifier: &str, err: impl ToString) -> Status {
+    let res = InternalServerErrorResponse::new("grpc", function, identifier, &err.to_string());
+    Status::internal(res.error)
+}
+
+fn parse_id(val: &str, field: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(val).map_err(|_| Status::invalid_argument(format!("invalid {}", field)))
+}
+
+fn to_proto_user(user: User, user_profile: Option<UserProfile>) -> proto::User {
+    let user_profile = user_profile.unwrap_or(UserProfile {
+        id: user.id,
+        user_id: user.id,
+        first_name: None,
+        last_name: None,
+        address: None,
+        email: None,
+        locale: None,
+        timezone: None,
+        email_verified_at: None,
+    });
+    proto::User {
+        id: user.id.to_string(),
+        is_active: user.is_active(),
+        user_name: user.user_name,
+        status: user.status.to_string(),
+        first_name: user_profile.first_name,
+        last_name: user_profile.last_name,
+        email: user_profile.email,
+    }
+}
+
+/// Auth validation, user lookup and authorization check for internal services
+pub struct CoreService {
+    app_state: Arc<AppState>,
+}
+
+impl CoreService {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+
+    /// Caller from the `authorization: Bearer <token>` metadata
+    async fn request_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        func: &str,
+        metadata: &MetadataMap,
+    ) -> Result<User, Status> {
+        let token = metadata
+            .get("authorization")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .map(|x| x.to_string());
+        let mut redis_conn = self
+            .app_state
+            .redis_conn
+            .get()
+            .await
+            .map_err(|err| internal(func, "get redis pool connection", err))?;
+        get_user_from_token(tx, &mut redis_conn, token)
+            .await
+            .map_err(|err| internal(func, "get user from token", err))?
+            .ok_or(Status::unauthenticated("invalid token"))
+    }
+
+    /// Same user or user read permission within the scopes of the token
+    async fn authorize_user_read(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        func: &str,
+        request_user: &User,
+        user_id: &Uuid,
+    ) -> Result<(), Status> {
+        if request_user.id == *user_id {
+            return Ok(());
+        }
+        let is_allowed = request_user.has_scope(PERMISSION_NAME, PERMISSION_ATTRIBUTE_READ)
+            && user_has_permission(
+                tx,
+                &request_user.id,
+                PERMISSION_NAME,
+                PERMISSION_ATTRIBUTE_READ,
+            )
+            .await
+            .map_err(|err| internal(func, "user_has_permission", err))?;
+        if !is_allowed {
+            return Err(Status::permission_denied(format!(
+                "{} {} permission required",
+                PERMISSION_NAME, PERMISSION_ATTRIBUTE_READ
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Core for CoreService {
+    async fn validate_token(
+        &self,
+        request: Request<ValidateTokenRequest>,
+    ) -> Result<Response<ValidateTokenResponse>, Status> {
+        let token = request.into_inner().token;
+        let mut tx = self
+            .app_state
+            .db
+            .begin()
+            .await
+            .map_err(|err| internal("validate_token", "begin transaction", err))?;
+        let mut redis_conn = self
+            .app_state
+            .redis_conn
+            .get()
+            .await
+            .map_err(|err| internal("validate_token", "get redis pool connection", err))?;
+        let user = get_user_from_token(&mut tx, &mut redis_conn, Some(token))
+            .await
+            .map_err(|err| internal("validate_token", "get user from token", err))?;
+        let user = match user {
+            Some(val) => {
+                let (_, user_profile) = get_user_by_id(&mut tx, &val.id, None)
+                    .await
+                    .map_err(|err| internal("validate_token", "get_user_by_id", err))?;
+                Some(to_proto_user(val, user_profile))
+            }
+            None => None,
+        };
+        Ok(Response::new(ValidateTokenResponse {
+            valid: user.is_some(),
+            user,
+        }))
+    }
+
+    async fn get_user(
+        &self,
+        request: Request<GetUserRequest>,
+    ) -> Result<Response<proto::User>, Status> {
+        let mut tx = self
+            .app_state
+            .db
+            .begin()
+            .await
+            .map_err(|err| internal("get_user", "begin transaction", err))?;
+        let request_user = self
+            .request_user(&mut tx, "get_user", request.metadata())
+            .await?;
+        let user_id = parse_id(&request.get_ref().id, "id")?;
+        self.authorize_user_read(&mut tx, "get_user", &request_user, &user_id)
+            .await?;
+        let (user, user_profile) = get_user_by_id(&mut tx, &user_id, None)
+            .await
+            .map_err(|err| internal("get_user", "get_user_by_id", err))?;
+        match user {
+            Some(val) => Ok(Response::new(to_proto_user(val, user_profile))),
+            None => Err(Status::not_found(format!(
+                "user with id {} not found",
+                user_id
+            ))),
+        }
+    }
+
+    async fn check_permission(
+        &self,
+        request: Request<CheckPermissionRequest>,
+    ) -> Result<Response<CheckPermissionResponse>, Status> {
+        let mut tx = self
+            .app_state
+            .db
+            .begin()
+            .await
+            .map_err(|err| internal("check_permission", "begin transaction", err))?;
+        let request_user = self
+            .request_user(&mut tx, "check_permission", request.metadata())
+            .await?;
+        let data = request.into_inner();
+        let user_id = match data.user_id.as_str() {
+            "" => request_user.id,
+            val => parse_id(val, "user_id")?,
+        };
+        self.authorize_user_read(&mut tx, "check_permission", &request_user, &user_id)
+            .await?;
+        // the caller asking about itself gets the answer its token is limited to
+        let in_scope = user_id != request_user.id
+            || request_user.has_scope(&data.permission_name, &data.attribute_name);
+        let allowed = in_scope
+            && user_has_permission(
+                &mut tx,
+                &user_id,
+                &data.permission_name,
+                &data.attribute_name,
+            )
+            .await
+            .map_err(|err| internal("check_permission", "user_has_permission", err))?;
+        Ok(Response::new(CheckPermissionResponse { allowed }))
+    }
+}
+
+/// Serve the grpc api on its own port until the process exits
+pub async fn serve(app_state: Arc<AppState>, addr: SocketAddr) -> anyhow::Result<()> {
+    Server::builder()
+        .add_service(CoreServer::new(CoreService::new(app_state)))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sqlx::PgPool;
+    use tonic::{Code, Request};
+
+    use crate::{
+        core::{session_store::create_redis_pool, test_utils::generate_test_user},
+        grpc::{
+            proto::{
+                core_server::Core, CheckPermissionRequest, GetUserRequest, ValidateTokenRequest,
+            },
+            CoreService,
+        },
+        settings::get_config,
+        AppState,
+    };
+
+    #[sqlx::test]
+    async fn test_core_service(pool: PgPool) -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let redis_pool = create_redis_pool(&config).unwrap();
+        let app_state = Arc::new(AppState {
+            db: pool,
+            redis_conn: redis_pool.into(),
+        });
+        let mut db = app_state.db.acquire().await?;
+        let mut redis_conn = app_state.redis_conn.get().await?;
+        let test_user = generate_test_user(
+            &mut db,
+            &mut redis_conn,
+            config.clone(),
+            "test_user",
+            "password",
+        )
+        .await?;
+        let other_user =
+            generate_test_user(&mut db, &mut redis_conn, config, "other_user", "password").await?;
+        let service = CoreService::new(app_state.clone());
+        let authorized = |mut req: Request<_>| {
+            req.metadata_mut().insert(
+                "authorization",
+                format!("Bearer {}", test_user.token).parse().unwrap(),
+            );
+            req
+        };
+
+        // When validating tokens
+        let valid = service
+            .validate_token(Request::new(ValidateTokenRequest {
+                token: test_user.token.clone(),
+            }))
+            .await?
+            .into_inner();
+        let invalid = service
+            .validate_token(Request::new(ValidateTokenRequest {
+                token: "invalid".to_string(),
+            }))
+            .await?
+            .into_inner();
+
+        // Expect
+        assert!(valid.valid);
+        assert_eq!(valid.user.unwrap().id, test_user.user.id.to_string());
+        assert!(!invalid.valid);
+        assert!(invalid.user.is_none());
+
+        // When looking up self and another user without user read permission
+        let user = service
+            .get_user(authorized(Request::new(GetUserRequest {
+                id: test_user.user.id.to_string(),
+            })))
+            .await?
+            .into_inner();
+        let err = service
+            .get_user(authorized(Request::new(GetUserRequest {
+                id: other_user.user.id.to_string(),
+            })))
+            .await
+            .unwrap_err();
+
+        // Expect
+        assert_eq!(user.user_name, "test_user");
+        assert_eq!(err.code(), Code::PermissionDenied);
+
+        // When checking a permission the caller lacks, and without a token
+        let res = service
+            .check_permission(authorized(Request::new(CheckPermissionRequest {
+                user_id: "".to_string(),
+                permission_name: "user".to_string(),
+                attribute_name: "read".to_string(),
+            })))
+            .await?
+            .into_inner();
+        let err = service
+            .check_permission(Request::new(CheckPermissionRequest {
+                user_id: "".to_string(),
+                permission_name: "user".to_string(),
+                attribute_name: "read".to_string(),
+            }))
+            .await
+            .unwrap_err();
+
+        // Expect
+        assert!(!res.allowed);
+        assert_eq!(err.code(), Code::Unauthenticated);
+        Ok(())
+    }
+}
@@ -1,40 +1,123 @@
 use std::sync::Arc;
 
+use crate::core::{
+    api_version::{
+        latest_api_version, parse_deprecated_versions, versioned_prefix, ApiVersionHeaders,
+        API_VERSIONS, LEGACY_API_VERSION,
+    },
+    db::init_pool,
+    deprecation::{parse_route_sunsets, DeprecatedRouteHeaders},
+    i18n::{LocalizedMessages, LocalizedMessagesEndpoint},
+    rate_limit::{RateLimit, RateLimitEndpoint},
+    request_id::{RequestId, RequestIdEndpoint, REQUEST_ID_HEADER},
+    security::jwks_json,
+    session_store::{
+        create_redis_pool, spawn_memory_store_sweeper, MemoryStore, SessionPool,
+        MEMORY_STORE_SWEEP_INTERVAL, SESSION_STORE_MEMORY,
+    },
+};
 use poem::{
+    get,
     middleware::{AddData, AddDataEndpoint, Cors, CorsEndpoint},
     EndpointExt, Route,
 };
-use poem_openapi::OpenApiService;
-use r2d2::Pool as r2d2Pool;
-use redis::Client;
+use poem_openapi::{OpenApi, OpenApiService};
 use route::{
-    auth::ApiAuth, group::ApiGroup, group_permission::ApiGroupPermission,
+    auth::ApiAuth, cache::ApiCache, consent::ApiConsent,
+    data_classification::ApiDataClassification, directory_source::ApiDirectorySource,
+    dormant_account::ApiDormantAccount, email_change::ApiEmailChange,
+    email_verification::ApiEmailVerification, group::ApiGroup,
+    group_permission::ApiGroupPermission, notification_template::ApiNotificationTemplate,
     permission::ApiPermission, permission_attribute::ApiPermissionAttribute, role::ApiRole,
-    role_permission::ApiRolePermission, user::ApiUser, user_permission::ApiUserPermission,
+    role_permission::ApiRolePermission, scim_target::ApiScimTarget,
+    service_account::ApiServiceAccount, sso_provider::ApiSsoProvider, terms::ApiTerms,
+    user::ApiUser, user_contact::ApiUserContact, user_data_export::ApiUserDataExport,
+    user_device::ApiUserDevice, user_permission::ApiUserPermission,
+    user_preference::ApiUserPreference, user_recovery_code::ApiUserRecoveryCode,
+    version::ApiVersionInfo,
 };
 use settings::Config;
 use sqlx::{Pool, Postgres};
 
 pub mod cli;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod core;
 pub mod factory;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod model;
 pub mod repository;
 pub mod route;
 pub mod schema;
 pub mod settings;
+#[cfg(feature = "token-verifier")]
+pub mod verifier;
 
 pub struct AppState {
     pub db: Pool<Postgres>,
-    pub redis_conn: r2d2Pool<Client>,
+    pub redis_conn: SessionPool,
 }
 
-pub fn init_openapi_route(
-    app_state: Arc<AppState>,
-    config: &Config,
-) -> CorsEndpoint<AddDataEndpoint<Route, Arc<AppState>>> {
-    let prefix = config.prefix.clone().unwrap_or("/".to_string());
-    let openapi_route = OpenApiService::new(
+impl AppState {
+    pub fn new(db: Pool<Postgres>, redis_conn: impl Into<SessionPool>) -> Self {
+        Self {
+            db,
+            redis_conn: redis_conn.into(),
+        }
+    }
+
+    /// Embedding services can hand over their own pools, the rest are created from config
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct AppStateBuilder {
+    db: Option<Pool<Postgres>>,
+    redis_conn: Option<SessionPool>,
+}
+
+impl AppStateBuilder {
+    pub fn db(mut self, val: Pool<Postgres>) -> Self {
+        self.db = Some(val);
+        self
+    }
+
+    /// Redis pool or MemoryStore
+    pub fn redis_conn(mut self, val: impl Into<SessionPool>) -> Self {
+        self.redis_conn = Some(val.into());
+        self
+    }
+
+    /// Connect DATABASE_URL and REDIS_URL, or SESSION_STORE=memory, for the pools not given
+    pub async fn build(self, config: &Config) -> anyhow::Result<AppState> {
+        let db = match self.db {
+            Some(val) => val,
+            None => init_pool(config).await,
+        };
+        let redis_conn = match self.redis_conn {
+            Some(val) => val,
+            None => match config.session_store.as_deref() {
+                Some(SESSION_STORE_MEMORY) => {
+                    let store = MemoryStore::new();
+                    spawn_memory_store_sweeper(&store, MEMORY_STORE_SWEEP_INTERVAL);
+                    store.into()
+                }
+                _ => create_redis_pool(config)?.into(),
+            },
+        };
+        Ok(AppState::new(db, redis_conn))
+    }
+}
+
+/// OpenAPI service of one api version, `prefix` is the url it is mounted on.
+/// Versions share the handlers until a breaking change lands in the latest one.
+pub fn init_openapi_service(prefix: &str, version: &str) -> OpenApiService<impl OpenApi, ()> {
+    OpenApiService::new(
         (
             ApiAuth,
             ApiUser,
@@ -45,17 +128,213 @@ pub fn init_openapi_route(
             ApiRolePermission,
             ApiGroupPermission,
             ApiUserPermission,
+            ApiServiceAccount,
+            // grouped, OpenApi is implemented for tuples of up to 16 apis
+            (ApiScimTarget, ApiDirectorySource, ApiSsoProvider),
+            (
+                ApiUserDataExport,
+                ApiConsent,
+                ApiTerms,
+                ApiDataClassification,
+                ApiDormantAccount,
+                ApiEmailChange,
+                ApiEmailVerification,
+                ApiUserContact,
+                ApiUserPreference,
+                ApiNotificationTemplate,
+                ApiUserDevice,
+                ApiCache,
+                ApiUserRecoveryCode,
+            ),
+            ApiVersionInfo,
         ),
         "Core",
-        "1.0",
+        format!("{}.0", version.trim_start_matches('v')),
+    )
+    .server(prefix)
+}
+
+/// OpenAPI service of a single domain for library consumers, mount it under `prefix` and
+/// wrap the route with [`with_core_middleware`]
+fn domain_service<T: OpenApi>(api: T, domain: &str, prefix: &str) -> OpenApiService<T, ()> {
+    OpenApiService::new(
+        api,
+        format!("Core {}", domain),
+        format!("{}.0", latest_api_version().trim_start_matches('v')),
+    )
+    .server(prefix)
+}
+
+pub fn auth_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service(ApiAuth, "auth", prefix)
+}
+
+/// Users and everything owned by one, contacts, preferences, devices, exports, and
+/// service accounts
+pub fn user_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service(
+        (
+            ApiUser,
+            ApiUserPermission,
+            ApiUserContact,
+            ApiUserPreference,
+            ApiUserDevice,
+            ApiUserDataExport,
+            ApiEmailChange,
+            ApiEmailVerification,
+            ApiDormantAccount,
+            ApiUserRecoveryCode,
+            ApiServiceAccount,
+        ),
+        "user",
+        prefix,
     )
-    .server(prefix.clone());
+}
+
+pub fn role_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service((ApiRole, ApiRolePermission), "role", prefix)
+}
+
+pub fn group_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service((ApiGroup, ApiGroupPermission), "group", prefix)
+}
+
+pub fn permission_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service(
+        (ApiPermission, ApiPermissionAttribute, ApiCache),
+        "permission",
+        prefix,
+    )
+}
+
+/// Scim targets, directory sources and sso providers
+pub fn provisioning_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service(
+        (ApiScimTarget, ApiDirectorySource, ApiSsoProvider),
+        "provisioning",
+        prefix,
+    )
+}
+
+/// Consent, terms and data classification
+pub fn compliance_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service(
+        (ApiConsent, ApiTerms, ApiDataClassification),
+        "compliance",
+        prefix,
+    )
+}
+
+pub fn notification_routes(prefix: &str) -> OpenApiService<impl OpenApi, ()> {
+    domain_service(ApiNotificationTemplate, "notification", prefix)
+}
+
+/// Routes wrapped in the middleware every deployment runs, see with_core_middleware
+pub type CoreEndpoint = RequestIdEndpoint<
+    CorsEndpoint<
+        RateLimitEndpoint<AddDataEndpoint<LocalizedMessagesEndpoint<Route>, Arc<AppState>>>,
+    >,
+>;
+
+pub fn init_openapi_route(app_state: Arc<AppState>, config: &Config) -> CoreEndpoint {
+    let prefix = config.prefix.clone().unwrap_or("/".to_string());
+    let deprecated_versions = parse_deprecated_versions(config.api_deprecated_versions.as_deref())
+        .expect("invalid API_DEPRECATED_VERSIONS");
+    let successor_prefix = versioned_prefix(&prefix, latest_api_version());
+    let redoc = config.openapi_redoc.unwrap_or(false);
+    let deprecation_headers = config.deprecation_headers.unwrap_or(true);
+    let route_sunsets = parse_route_sunsets(config.deprecated_route_sunset.as_deref())
+        .expect("invalid DEPRECATED_ROUTE_SUNSET");
+    let mut route = Route::new();
+    for version in API_VERSIONS {
+        let version_prefix = versioned_prefix(&prefix, version);
+        let mut openapi_route = init_openapi_service(&version_prefix, version);
+        if let Some(sunset) = deprecated_versions.get(version) {
+            openapi_route = openapi_route.description(match sunset {
+                Some(date) => format!("Deprecated, removed on {}", date),
+                None => "Deprecated".to_string(),
+            });
+        }
+        let openapi_json_endpoint = openapi_route.spec_endpoint();
+        let ui = openapi_route.swagger_ui();
+        let deprecated_routes =
+            DeprecatedRouteHeaders::new(&openapi_route.spec(), &route_sunsets, deprecation_headers)
+                .expect("invalid openapi spec");
+        if redoc {
+            route = route.nest(format!("/redoc/{}", version), openapi_route.redoc());
+        }
+        route = route
+            .nest(
+                &version_prefix,
+                openapi_route
+                    .with(deprecated_routes)
+                    .with(ApiVersionHeaders::new(
+                        &deprecated_versions,
+                        version,
+                        &successor_prefix,
+                    )),
+            )
+            .nest(format!("/docs/{}", version), ui)
+            .at(format!("/{}/openapi.json", version), openapi_json_endpoint);
+    }
+    #[cfg(feature = "graphql")]
+    {
+        let schema = graphql::init_schema(app_state.clone());
+        route = route.at(
+            format!("{}/graphql", prefix.trim_end_matches('/')),
+            poem::post(graphql::graphql_handler).data(schema),
+        );
+    }
+    // Unversioned prefix kept for existing clients
+    let openapi_route = init_openapi_service(&prefix, LEGACY_API_VERSION);
     let openapi_json_endpoint = openapi_route.spec_endpoint();
     let ui = openapi_route.swagger_ui();
-    Route::new()
-        .nest(prefix, openapi_route)
+    let deprecated_routes =
+        DeprecatedRouteHeaders::new(&openapi_route.spec(), &route_sunsets, deprecation_headers)
+            .expect("invalid openapi spec");
+    if redoc {
+        route = route.nest("/redoc", openapi_route.redoc());
+    }
+    let route = route
+        .nest(
+            prefix,
+            openapi_route
+                .with(deprecated_routes)
+                .with(ApiVersionHeaders::new(
+                    &deprecated_versions,
+                    LEGACY_API_VERSION,
+                    &successor_prefix,
+                )),
+        )
         .nest("/docs", ui)
         .at("openapi.json", openapi_json_endpoint)
+        .at("/.well-known/jwks.json", get(jwks_json));
+    with_core_middleware(route, app_state, config)
+}
+
+/// Locale, app state, rate limit, cors and request id every core handler expects, for
+/// routes composed from the per-domain builders e.g.
+/// `with_core_middleware(Route::new().nest("/api", auth_routes("/api")), app_state, &config)`
+pub fn with_core_middleware(
+    route: Route,
+    app_state: Arc<AppState>,
+    config: &Config,
+) -> CoreEndpoint {
+    let profile = config.profile().expect("invalid APP_ENV profile");
+    route
+        .with(LocalizedMessages::new(config.default_locale.as_deref()))
         .with(AddData::new(app_state))
-        .with(Cors::new())
+        .with(RateLimit::new(profile.rate_limit_per_minute))
+        .with(init_cors(&profile.cors_allow_origins))
+        .with(RequestId::new(profile.debug_errors))
+}
+
+/// Any origin when `*` is listed, otherwise only the listed origins
+fn init_cors(origins: &[String]) -> Cors {
+    let cors = Cors::new().expose_header(REQUEST_ID_HEADER);
+    if origins.iter().any(|x| x == "*") {
+        return cors;
+    }
+    let origins = origins.to_vec();
+    cors.allow_origins_fn(move |origin| origins.iter().any(|x| x == origin))
 }
@@ -0,0 +1,11 @@
+use sqlx::FromRow;
+
+/// Row of a list query with the user names of its creator and last updater, joined in
+/// the same query, see core::sqlx_utils::audited_from. None when unset or soft deleted
+#[derive(Clone, Debug, FromRow)]
+pub struct Audited<T> {
+    #[sqlx(flatten)]
+    pub data: T,
+    pub created_by_name: Option<String>,
+    pub updated_by_name: Option<String>,
+}
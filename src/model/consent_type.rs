@@ -0,0 +1,20 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.consent_type";
+
+/// Purpose a user may consent to, `version` is bumped when its terms change
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct ConsentType {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub version: i32,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.directory_source";
+
+pub const PROVIDER_LDAP: &str = "ldap";
+pub const PROVIDER_AZURE_AD: &str = "azure_ad";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct DirectorySource {
+    pub id: Uuid,
+    pub name: String,
+    pub provider: String,
+    pub is_active: Option<bool>,
+    pub sync_interval: i32, // minutes
+    pub ldap_url: Option<String>,
+    pub ldap_bind_dn: Option<String>,
+    pub ldap_bind_password: Option<String>,
+    pub ldap_base_dn: Option<String>,
+    pub ldap_user_filter: Option<String>,
+    pub ldap_group_filter: Option<String>,
+    pub azure_tenant_id: Option<String>,
+    pub azure_client_id: Option<String>,
+    pub azure_client_secret: Option<String>,
+    pub azure_authority_url: Option<String>,
+    pub azure_graph_url: Option<String>,
+    pub last_sync_date: Option<DateTime<FixedOffset>>,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+    pub deleted_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.directory_sync_run";
+
+pub const STATUS_SUCCESS: &str = "success";
+pub const STATUS_FAILED: &str = "failed";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct DirectorySyncRun {
+    pub id: Uuid,
+    pub source_id: Uuid,
+    pub status: String,
+    pub users_created: i32,
+    pub users_updated: i32,
+    pub users_deactivated: i32,
+    pub groups_created: i32,
+    pub memberships_added: i32,
+    pub memberships_removed: i32,
+    pub error: Option<String>,
+    pub started_date: Option<DateTime<FixedOffset>>,
+    pub finished_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,54 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.notification_template";
+
+pub const EVENT_INVITE: &str = "invite";
+pub const EVENT_PASSWORD_RESET: &str = "password_reset";
+pub const EVENT_EMAIL_CHANGE_CONFIRM: &str = "email_change_confirm";
+/// Security alert sent to the previous address of an email change
+pub const EVENT_EMAIL_CHANGE_NOTICE: &str = "email_change_notice";
+/// Security alert pushed to the devices of a user after a password login
+pub const EVENT_NEW_LOGIN: &str = "new_login";
+pub const EVENT_PASSWORD_CHANGED: &str = "password_changed";
+/// Link sent to the email of a new user, see core::email_verification
+pub const EVENT_EMAIL_VERIFICATION: &str = "email_verification";
+pub const EVENTS: [&str; 7] = [
+    EVENT_INVITE,
+    EVENT_PASSWORD_RESET,
+    EVENT_EMAIL_CHANGE_CONFIRM,
+    EVENT_EMAIL_CHANGE_NOTICE,
+    EVENT_NEW_LOGIN,
+    EVENT_PASSWORD_CHANGED,
+    EVENT_EMAIL_VERIFICATION,
+];
+
+pub const CHANNEL_EMAIL: &str = "email";
+pub const CHANNEL_SMS: &str = "sms";
+/// Mobile push to the registered devices of the user, `subject` is the title
+pub const CHANNEL_PUSH: &str = "push";
+pub const CHANNELS: [&str; 3] = [CHANNEL_EMAIL, CHANNEL_SMS, CHANNEL_PUSH];
+
+pub const PERMISSION_NAME: &str = "notification_template";
+pub const PERMISSION_ATTRIBUTE_CREATE: &str = "create";
+pub const PERMISSION_ATTRIBUTE_READ: &str = "read";
+pub const PERMISSION_ATTRIBUTE_UPDATE: &str = "update";
+pub const PERMISSION_ATTRIBUTE_DELETE: &str = "delete";
+
+/// Handlebars template overriding the built-in one of an event, channel and locale.
+/// `subject` is not used by sms.
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct NotificationTemplate {
+    pub id: Uuid,
+    pub event: String,
+    pub channel: String,
+    pub locale: String,
+    pub subject: Option<String>,
+    pub body: String,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
@@ -10,6 +10,9 @@ pub struct PermissionAttribute {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
+    /// Groups attributes in admin UIs, e.g. billing or reporting, none when uncategorized
+    pub category: Option<String>,
     pub created_date: Option<DateTime<FixedOffset>>,
     pub updated_date: Option<DateTime<FixedOffset>>,
+    pub deleted_date: Option<DateTime<FixedOffset>>,
 }
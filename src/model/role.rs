@@ -16,4 +16,5 @@ pub struct Role {
     pub created_date: Option<DateTime<FixedOffset>>,
     pub updated_date: Option<DateTime<FixedOffset>>,
     pub deleted_date: Option<DateTime<FixedOffset>>,
+    pub deleted_by: Option<Uuid>,
 }
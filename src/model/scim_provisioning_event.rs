@@ -0,0 +1,28 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.scim_provisioning_event";
+
+pub const EVENT_CREATE: &str = "create";
+pub const EVENT_UPDATE: &str = "update";
+pub const EVENT_DEACTIVATE: &str = "deactivate";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_SUCCESS: &str = "success";
+pub const STATUS_FAILED: &str = "failed";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct ScimProvisioningEvent {
+    pub id: Uuid,
+    pub target_id: Uuid,
+    pub user_id: Uuid,
+    pub event_type: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_date: Option<DateTime<FixedOffset>>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
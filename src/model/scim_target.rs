@@ -0,0 +1,21 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.scim_target";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct ScimTarget {
+    pub id: Uuid,
+    pub name: String,
+    pub base_url: String,
+    pub bearer_token: Option<String>,
+    pub is_active: Option<bool>,
+    pub max_attempts: i32,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+    pub deleted_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,15 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.scim_target_user";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct ScimTargetUser {
+    pub target_id: Uuid,
+    pub user_id: Uuid,
+    pub external_id: String,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,39 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.service_account";
+
+/// Prefix of the user name of the backing user, so machine identities stand out in user lists
+pub const USER_NAME_PREFIX: &str = "sa:";
+
+/// Machine identity authenticating with an api key instead of a password. Requests run as
+/// the backing `user_id`, every permission it holds directly, through a role or through a
+/// group is a scope of the account.
+/// Only the sha256 hash of the key is kept, `key_prefix` is its first characters so an
+/// account can be told apart from a leaked key
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct ServiceAccount {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub user_id: Uuid,
+    pub key_prefix: String,
+    pub key_hash: String,
+    /// The key is rejected from this date, the scopes expire with it
+    pub expires_at: Option<DateTime<FixedOffset>>,
+    /// Updated at most once a minute, see core::service_account
+    pub last_used_at: Option<DateTime<FixedOffset>>,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+    pub deleted_date: Option<DateTime<FixedOffset>>,
+}
+
+impl ServiceAccount {
+    pub fn is_expired(&self, now: &DateTime<FixedOffset>) -> bool {
+        self.expires_at.is_some_and(|x| x <= *now)
+    }
+}
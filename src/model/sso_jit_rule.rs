@@ -0,0 +1,21 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.sso_jit_rule";
+
+/// Group/role granted to users provisioned on their first sso login.
+/// Rule without claim apply to every provisioned user, rule without value
+/// only require the claim to be present.
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct SsoJitRule {
+    pub id: Uuid,
+    pub provider_id: Uuid,
+    pub claim: Option<String>,
+    pub value: Option<String>,
+    pub group_id: Option<Uuid>,
+    pub role_id: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,45 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.sso_provider";
+
+/// Generic OpenID Connect provider, users come from a verified id token
+pub const PROTOCOL_OIDC: &str = "oidc";
+/// OpenID Connect with the endpoints of Google filled in
+pub const PROTOCOL_GOOGLE: &str = "google";
+/// Plain OAuth2 without id token, users come from the userinfo endpoint (GitHub api)
+pub const PROTOCOL_GITHUB: &str = "github";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct SsoProvider {
+    pub id: Uuid,
+    pub name: String,
+    pub protocol: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub jwks_url: Option<String>,
+    pub username_claim: String,
+    pub email_claim: String,
+    pub is_active: Option<bool>,
+    pub jit_enabled: bool,
+    pub created_by: Option<Uuid>,
+    pub updated_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+    pub deleted_date: Option<DateTime<FixedOffset>>,
+    pub client_secret: Option<String>,
+    pub authorization_url: Option<String>,
+    pub token_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    /// Space separated, requested on the authorization url
+    pub scopes: Option<String>,
+}
+
+impl SsoProvider {
+    /// Users are read from an id token, otherwise from the userinfo endpoint
+    pub fn is_oidc(&self) -> bool {
+        self.protocol != PROTOCOL_GITHUB
+    }
+}
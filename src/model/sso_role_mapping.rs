@@ -0,0 +1,20 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.sso_role_mapping";
+
+/// Group/role held by a sso user as long as the idp claim carries the value,
+/// reconciled on every sso login
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct SsoRoleMapping {
+    pub id: Uuid,
+    pub provider_id: Uuid,
+    pub claim: String,
+    pub value: String,
+    pub group_id: Option<Uuid>,
+    pub role_id: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
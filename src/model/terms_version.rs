@@ -0,0 +1,27 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.terms_version";
+
+pub const KIND_TERMS_OF_SERVICE: &str = "terms_of_service";
+pub const KIND_PRIVACY_POLICY: &str = "privacy_policy";
+pub const KINDS: [&str; 2] = [KIND_TERMS_OF_SERVICE, KIND_PRIVACY_POLICY];
+
+/// Permission required to publish a new version
+pub const PERMISSION_NAME: &str = "terms_version";
+pub const PERMISSION_ATTRIBUTE: &str = "create";
+
+/// Published document, the most recently published one of each kind is the
+/// version users must accept
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct TermsVersion {
+    pub id: Uuid,
+    pub kind: String,
+    pub version: String,
+    pub url: Option<String>,
+    pub content: Option<String>,
+    pub published_by: Option<Uuid>,
+    pub published_date: Option<DateTime<FixedOffset>>,
+}
@@ -1,20 +1,90 @@
+use std::fmt;
+
 use chrono::{DateTime, FixedOffset};
-use serde::Deserialize;
+use poem_openapi::Enum;
+use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
 pub const TABLE_NAME: &str = "public.user";
 
+/// Lifecycle state of a user, deprovisioned is terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Enum, sqlx::Type)]
+#[oai(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum UserStatus {
+    Pending,
+    Active,
+    Suspended,
+    /// Set when the account is anonymized
+    Deprovisioned,
+}
+
+impl UserStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Pending => "pending",
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Deprovisioned => "deprovisioned",
+        }
+    }
+
+    /// Status of users created from a legacy active flag
+    pub fn from_active(is_active: bool) -> Self {
+        match is_active {
+            true => UserStatus::Active,
+            false => UserStatus::Suspended,
+        }
+    }
+}
+
+impl fmt::Display for UserStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `status` is the lifecycle state, the stored `is_active` column is written from it for
+/// existing filters and clients, see [`User::is_active`]
 #[derive(Clone, Debug, Deserialize, FromRow)]
 pub struct User {
     pub id: Uuid,
     pub user_name: String,
     pub password: String,
-    pub is_active: Option<bool>,
     pub is_2faenabled: Option<bool>,
     pub created_by: Option<Uuid>,
     pub updated_by: Option<Uuid>,
     pub created_date: Option<DateTime<FixedOffset>>,
     pub updated_date: Option<DateTime<FixedOffset>>,
     pub deleted_date: Option<DateTime<FixedOffset>>,
+    pub status: UserStatus,
+    /// Logins are rejected from this date and the expiry job suspends the user
+    pub expires_at: Option<DateTime<FixedOffset>>,
+    /// Scopes of the token the request user authenticated with, None for unscoped tokens.
+    /// Not stored, see core::security::get_user_from_token
+    #[serde(skip)]
+    #[sqlx(skip)]
+    pub scopes: Option<Vec<String>>,
+}
+
+impl User {
+    pub fn is_expired(&self, now: &DateTime<FixedOffset>) -> bool {
+        self.expires_at.is_some_and(|x| x <= *now)
+    }
+
+    /// Whether the token scopes, if any, include `permission_name.attribute_name`. Granted
+    /// permissions outside of the scopes are denied to scoped tokens
+    pub fn has_scope(&self, permission_name: &str, attribute_name: &str) -> bool {
+        self.scopes.as_ref().is_none_or(|scopes| {
+            scopes
+                .iter()
+                .any(|x| x.rsplit_once('.') == Some((permission_name, attribute_name)))
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
 }
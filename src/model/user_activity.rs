@@ -0,0 +1,36 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_activity";
+
+/// Dormant accounts are listed with user read and exempted with user update
+pub const PERMISSION_NAME: &str = "user";
+pub const PERMISSION_ATTRIBUTE_READ: &str = "read";
+pub const PERMISSION_ATTRIBUTE_UPDATE: &str = "update";
+
+/// Login activity of a user, rows are created on first login or exemption
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserActivity {
+    pub user_id: Uuid,
+    pub last_login_date: Option<DateTime<FixedOffset>>,
+    /// Set when the dormant warning was sent, cleared on login
+    pub dormant_warning_date: Option<DateTime<FixedOffset>>,
+    /// Set when DORMANT_ACCOUNT_ACTION=flag marked the user dormant, cleared on login
+    pub dormant_flagged_date: Option<DateTime<FixedOffset>>,
+    /// Service accounts never considered dormant
+    pub dormant_exempt: bool,
+    pub dormant_exempt_reason: Option<String>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
+
+/// Active user with its last activity, the created date when never logged in
+#[derive(Clone, Debug, FromRow)]
+pub struct DormantCandidate {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub last_activity_date: Option<DateTime<FixedOffset>>,
+    pub dormant_warning_date: Option<DateTime<FixedOffset>>,
+    pub dormant_flagged_date: Option<DateTime<FixedOffset>>,
+}
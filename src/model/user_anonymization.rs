@@ -0,0 +1,19 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_anonymization";
+
+/// Audit entry of an irreversible anonymization, at most one per user
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserAnonymization {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub anonymized_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+}
+
+/// Permission and attribute required to anonymize users
+pub const PERMISSION_NAME: &str = "user_anonymize";
+pub const PERMISSION_ATTRIBUTE: &str = "delete";
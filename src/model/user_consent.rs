@@ -0,0 +1,18 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_consent";
+
+/// Accept or withdraw record, rows are never updated so the latest one per
+/// consent type is the user's current decision
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserConsent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub consent_type_id: Uuid,
+    pub version: i32,
+    pub accepted: bool,
+    pub created_date: Option<DateTime<FixedOffset>>,
+}
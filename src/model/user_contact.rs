@@ -0,0 +1,29 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_contact";
+
+pub const KIND_EMAIL: &str = "email";
+pub const KIND_PHONE: &str = "phone";
+pub const KINDS: [&str; 2] = [KIND_EMAIL, KIND_PHONE];
+
+/// Users manage their own contacts, contacts of others need user read / update
+pub const PERMISSION_NAME: &str = "user";
+pub const PERMISSION_ATTRIBUTE_READ: &str = "read";
+pub const PERMISSION_ATTRIBUTE_UPDATE: &str = "update";
+
+/// Additional email or phone of a user, `value` is encrypted like the profile email.
+/// At most one primary contact per kind.
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserContact {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: String,
+    pub value: String,
+    pub is_primary: bool,
+    pub verified_date: Option<DateTime<FixedOffset>>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,23 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_data_export";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_COMPLETED: &str = "completed";
+pub const STATUS_FAILED: &str = "failed";
+
+/// Export of everything stored about a user, `payload` is the json document once completed
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserDataExport {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub status: String,
+    pub payload: Option<String>,
+    pub last_error: Option<String>,
+    pub requested_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub completed_date: Option<DateTime<FixedOffset>>,
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_device";
+
+/// Firebase Cloud Messaging, android and web
+pub const PLATFORM_FCM: &str = "fcm";
+/// Apple Push Notification service
+pub const PLATFORM_APNS: &str = "apns";
+pub const PLATFORMS: [&str; 2] = [PLATFORM_FCM, PLATFORM_APNS];
+
+/// Push token registered by a mobile app, a token belongs to the user that registered it last
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserDevice {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub platform: String,
+    pub token: String,
+    pub name: Option<String>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
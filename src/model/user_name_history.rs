@@ -0,0 +1,16 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_name_history";
+
+/// Previous user name of a user, reserved for USER_NAME_RESERVE_DAYS after the change
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserNameHistory {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub changed_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+}
@@ -3,6 +3,8 @@ use serde::Deserialize;
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
+use crate::model::user::UserStatus;
+
 pub const TABLE_NAME: &str = "public.user_permission";
 
 #[derive(Clone, Debug, Deserialize, FromRow)]
@@ -14,4 +16,26 @@ pub struct UserPermission {
     pub updated_by: Option<Uuid>,
     pub created_date: Option<DateTime<FixedOffset>>,
     pub updated_date: Option<DateTime<FixedOffset>>,
+    /// Temporary grant, ignored once passed and removed by the permission expiry worker
+    pub expires_at: Option<DateTime<FixedOffset>>,
+}
+
+pub const GRANT_VIA_DIRECT: &str = "direct";
+pub const GRANT_VIA_ROLE: &str = "role";
+pub const GRANT_VIA_GROUP: &str = "group";
+
+/// One path through which a user holds a permission attribute, see get_permission_holders
+#[derive(Clone, Debug, FromRow)]
+pub struct PermissionHolder {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub status: UserStatus,
+    pub attribute_id: Uuid,
+    pub attribute_name: String,
+    /// GRANT_VIA_DIRECT, GRANT_VIA_ROLE or GRANT_VIA_GROUP
+    pub via: String,
+    /// Role or group the grant comes from, None for direct grants
+    pub source_id: Option<Uuid>,
+    pub source_name: Option<String>,
+    pub expires_at: Option<DateTime<FixedOffset>>,
 }
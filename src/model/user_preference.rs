@@ -0,0 +1,17 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_preference";
+
+/// One preference of a user, `value` is any json for app specific keys
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserPreference {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub key: String,
+    pub value: serde_json::Value,
+    pub created_date: Option<DateTime<FixedOffset>>,
+    pub updated_date: Option<DateTime<FixedOffset>>,
+}
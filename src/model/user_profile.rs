@@ -1,3 +1,4 @@
+use chrono::{DateTime, FixedOffset};
 use serde::Deserialize;
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -12,4 +13,10 @@ pub struct UserProfile {
     pub last_name: Option<String>,
     pub address: Option<String>,
     pub email: Option<String>,
+    /// e.g. id-ID, notification emails fall back to DEFAULT_LOCALE when empty
+    pub locale: Option<String>,
+    /// IANA name e.g. Asia/Jakarta, falls back to DEFAULT_TIMEZONE when empty
+    pub timezone: Option<String>,
+    /// When the current email was confirmed, cleared when the email changes
+    pub email_verified_at: Option<DateTime<FixedOffset>>,
 }
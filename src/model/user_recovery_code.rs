@@ -0,0 +1,20 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_recovery_codes";
+
+/// Codes issued per generation, generating again replaces the previous set
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// One time code accepted at login in place of the authenticator, only the argon2 hash
+/// of the code is kept
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserRecoveryCode {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub code_hash: String,
+    pub used_date: Option<DateTime<FixedOffset>>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+}
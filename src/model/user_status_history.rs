@@ -0,0 +1,28 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+use crate::model::user::UserStatus;
+
+pub const TABLE_NAME: &str = "public.user_status_history";
+
+/// Lifecycle status change of a user, `changed_by` is empty for automatic changes
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserStatusHistory {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub from_status: UserStatus,
+    pub to_status: UserStatus,
+    pub reason: Option<String>,
+    pub changed_by: Option<Uuid>,
+    pub created_date: Option<DateTime<FixedOffset>>,
+}
+
+impl UserStatusHistory {
+    /// The change took an active user out of active, their sessions are revoked once it is
+    /// committed, see core::lifecycle::revoke_deactivated_sessions
+    pub fn left_active(&self) -> bool {
+        self.from_status == UserStatus::Active && self.to_status != UserStatus::Active
+    }
+}
@@ -0,0 +1,14 @@
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+pub const TABLE_NAME: &str = "public.user_terms_acceptance";
+
+#[derive(Clone, Debug, Deserialize, FromRow)]
+pub struct UserTermsAcceptance {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub terms_version_id: Uuid,
+    pub accepted_date: Option<DateTime<FixedOffset>>,
+}
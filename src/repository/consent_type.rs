@@ -0,0 +1,100 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::consent_type::{ConsentType, TABLE_NAME};
+
+pub async fn get_all_consent_type(
+    conn: &mut PgConnection,
+    is_active: Option<bool>,
+) -> anyhow::Result<Vec<ConsentType>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE ($1::bool IS NULL OR is_active = $1)
+            ORDER BY name ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(is_active)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_consent_type_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<ConsentType>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn get_consent_type_by_name(
+    conn: &mut PgConnection,
+    name: &str,
+) -> anyhow::Result<Option<ConsentType>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE name = $1", TABLE_NAME).as_str())
+            .bind(name)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn create_consent_type(
+    tx: &mut Transaction<'_, Postgres>,
+    consent_type: &ConsentType,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+    INSERT INTO {} (id, name, description, version, is_active, created_by, updated_by,
+    created_date, updated_date)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(consent_type.id)
+    .bind(&consent_type.name)
+    .bind(&consent_type.description)
+    .bind(consent_type.version)
+    .bind(consent_type.is_active)
+    .bind(consent_type.created_by)
+    .bind(consent_type.updated_by)
+    .bind(consent_type.created_date)
+    .bind(consent_type.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_consent_type(
+    tx: &mut Transaction<'_, Postgres>,
+    consent_type: &ConsentType,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+        UPDATE {}
+        SET name = $1, description = $2, version = $3, is_active = $4,
+        updated_by = $5, updated_date = $6
+        WHERE id = $7"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&consent_type.name)
+    .bind(&consent_type.description)
+    .bind(consent_type.version)
+    .bind(consent_type.is_active)
+    .bind(consent_type.updated_by)
+    .bind(consent_type.updated_date)
+    .bind(consent_type.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+        utils::utc_now,
+    },
+    model::{
+        directory_source::{DirectorySource, TABLE_NAME},
+        user::User,
+    },
+};
+
+pub async fn paginate_directory_source(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    search: Option<String>,
+) -> anyhow::Result<(Vec<DirectorySource>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec![];
+
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("name ilike ${}", binds.len()));
+    }
+    filters.push("deleted_date IS NULL".to_string());
+
+    let limit = page_size;
+    let offset = (page - 1) * page_size;
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec!["updated_date DESC".to_string()],
+        Some(limit),
+        Some(offset),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+
+    let q = binds_query_as::<DirectorySource>(&stmt, binds.clone());
+    let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page))
+}
+
+pub async fn get_directory_source_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<DirectorySource>> {
+    let binds: Vec<SqlxBinds> = vec![SqlxBinds::Uuid(*id)];
+    let filters: Vec<String> = vec!["id = $1".to_string(), "deleted_date IS NULL".to_string()];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<DirectorySource>(&stmt, binds);
+    Ok(q.fetch_optional(&mut *conn).await?)
+}
+
+/// Active sources whose last sync is older than their sync interval
+pub async fn get_due_directory_source(
+    conn: &mut PgConnection,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<Vec<DirectorySource>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {}
+            WHERE is_active = true AND deleted_date IS NULL
+            AND (last_sync_date IS NULL
+                OR last_sync_date + make_interval(mins => sync_interval) <= $1)
+            ORDER BY last_sync_date ASC NULLS FIRST"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(now)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn create_directory_source(
+    tx: &mut Transaction<'_, Postgres>,
+    directory_source: &DirectorySource,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+    INSERT INTO {} (id, name, provider, is_active, sync_interval,
+    ldap_url, ldap_bind_dn, ldap_bind_password, ldap_base_dn, ldap_user_filter, ldap_group_filter,
+    azure_tenant_id, azure_client_id, azure_client_secret, azure_authority_url, azure_graph_url,
+    last_sync_date, created_by, updated_by, created_date, updated_date, deleted_date)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+    $20, $21, $22)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(directory_source.id)
+    .bind(&directory_source.name)
+    .bind(&directory_source.provider)
+    .bind(directory_source.is_active)
+    .bind(directory_source.sync_interval)
+    .bind(&directory_source.ldap_url)
+    .bind(&directory_source.ldap_bind_dn)
+    .bind(&directory_source.ldap_bind_password)
+    .bind(&directory_source.ldap_base_dn)
+    .bind(&directory_source.ldap_user_filter)
+    .bind(&directory_source.ldap_group_filter)
+    .bind(&directory_source.azure_tenant_id)
+    .bind(&directory_source.azure_client_id)
+    .bind(&directory_source.azure_client_secret)
+    .bind(&directory_source.azure_authority_url)
+    .bind(&directory_source.azure_graph_url)
+    .bind(directory_source.last_sync_date)
+    .bind(directory_source.created_by)
+    .bind(directory_source.updated_by)
+    .bind(directory_source.created_date)
+    .bind(directory_source.updated_date)
+    .bind(directory_source.deleted_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_directory_source(
+    tx: &mut Transaction<'_, Postgres>,
+    directory_source: &DirectorySource,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+        UPDATE {}
+        SET name = $1, provider = $2, is_active = $3, sync_interval = $4,
+        ldap_url = $5, ldap_bind_dn = $6, ldap_bind_password = $7, ldap_base_dn = $8,
+        ldap_user_filter = $9, ldap_group_filter = $10,
+        azure_tenant_id = $11, azure_client_id = $12, azure_client_secret = $13,
+        azure_authority_url = $14, azure_graph_url = $15,
+        updated_by = $16, updated_date = $17
+        WHERE id = $18"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&directory_source.name)
+    .bind(&directory_source.provider)
+    .bind(directory_source.is_active)
+    .bind(directory_source.sync_interval)
+    .bind(&directory_source.ldap_url)
+    .bind(&directory_source.ldap_bind_dn)
+    .bind(&directory_source.ldap_bind_password)
+    .bind(&directory_source.ldap_base_dn)
+    .bind(&directory_source.ldap_user_filter)
+    .bind(&directory_source.ldap_group_filter)
+    .bind(&directory_source.azure_tenant_id)
+    .bind(&directory_source.azure_client_id)
+    .bind(&directory_source.azure_client_secret)
+    .bind(&directory_source.azure_authority_url)
+    .bind(&directory_source.azure_graph_url)
+    .bind(directory_source.updated_by)
+    .bind(directory_source.updated_date)
+    .bind(directory_source.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_directory_source_last_sync(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            "UPDATE {} SET last_sync_date = $1 WHERE id = $2",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(now)
+    .bind(id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn soft_delete_directory_source(
+    tx: &mut Transaction<'_, Postgres>,
+    directory_source: &mut DirectorySource,
+    request_user: User,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<()> {
+    let now = now.unwrap_or(utc_now());
+    directory_source.updated_by = Some(request_user.id);
+    directory_source.updated_date = Some(now);
+    directory_source.deleted_date = Some(now);
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+    SET updated_by = $1, updated_date = $2, deleted_date = $3
+    WHERE id = $4"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(directory_source.updated_by)
+    .bind(directory_source.updated_date)
+    .bind(directory_source.deleted_date)
+    .bind(directory_source.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+    model::directory_sync_run::{DirectorySyncRun, TABLE_NAME},
+};
+
+pub async fn paginate_directory_sync_run(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    source_id: Option<Uuid>,
+    status: Option<String>,
+) -> anyhow::Result<(Vec<DirectorySyncRun>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec![];
+
+    if let Some(source_id) = source_id {
+        binds.push(SqlxBinds::Uuid(source_id));
+        filters.push(format!("source_id = ${}", binds.len()));
+    }
+    if let Some(status) = status {
+        binds.push(SqlxBinds::String(status));
+        filters.push(format!("status = ${}", binds.len()));
+    }
+
+    let limit = page_size;
+    let offset = (page - 1) * page_size;
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec!["started_date DESC".to_string()],
+        Some(limit),
+        Some(offset),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+
+    let q = binds_query_as::<DirectorySyncRun>(&stmt, binds.clone());
+    let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page))
+}
+
+pub async fn get_latest_directory_sync_run(
+    conn: &mut PgConnection,
+    source_id: &Uuid,
+) -> anyhow::Result<Option<DirectorySyncRun>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE source_id = $1 ORDER BY started_date DESC LIMIT 1",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(source_id)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+pub async fn create_directory_sync_run(
+    tx: &mut Transaction<'_, Postgres>,
+    run: &DirectorySyncRun,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, source_id, status, users_created, users_updated,
+            users_deactivated, groups_created, memberships_added, memberships_removed, error,
+            started_date, finished_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(run.id)
+    .bind(run.source_id)
+    .bind(&run.status)
+    .bind(run.users_created)
+    .bind(run.users_updated)
+    .bind(run.users_deactivated)
+    .bind(run.groups_created)
+    .bind(run.memberships_added)
+    .bind(run.memberships_removed)
+    .bind(&run.error)
+    .bind(run.started_date)
+    .bind(run.finished_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use sqlx::{Postgres, Transaction};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
@@ -7,7 +7,7 @@ use crate::{
 };
 
 pub async fn get_all_group_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: Option<u32>,
     page_size: Option<u32>,
     group_id: &Uuid,
@@ -50,8 +50,8 @@ pub async fn get_all_group_permission(
 
     let q = binds_query_as::<GroupPermission>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = match all {
         true => 0,
         false => (count.0 as u32).div_ceil(page_size),
@@ -59,8 +59,28 @@ pub async fn get_all_group_permission(
     Ok((data, count.0 as u32, num_page as u32))
 }
 
+/// Every permission of the groups, in a single query
+pub async fn get_group_permissions_by_group_ids(
+    conn: &mut PgConnection,
+    group_ids: &[Uuid],
+) -> anyhow::Result<Vec<GroupPermission>> {
+    if group_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE group_id = ANY($1) ORDER BY updated_date DESC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(group_ids)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
 pub async fn get_detail_group_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     group_id: &Uuid,
     permission_id: &Uuid,
     attribute_id: &Uuid,
@@ -75,7 +95,7 @@ pub async fn get_detail_group_permission(
     .bind(group_id)
     .bind(permission_id)
     .bind(attribute_id)
-    .fetch_optional(&mut **tx)
+    .fetch_optional(&mut *conn)
     .await?)
 }
 
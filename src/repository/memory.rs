@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::{
+    core::utils::utc_now,
+    model::{
+        group::Group, group_permission::GroupPermission, permission::Permission,
+        permission_attribute::PermissionAttribute, role::Role, role_permission::RolePermission,
+        user_group_roles::UserGroupRoles, user_permission::UserPermission,
+    },
+    repository::rbac::RbacRepository,
+};
+
+/// HashMap backed repository so handler logic can be unit tested without postgres,
+/// rows are inserted straight into the public fields
+#[derive(Clone, Debug, Default)]
+pub struct MemoryRepository {
+    pub roles: HashMap<Uuid, Role>,
+    pub groups: HashMap<Uuid, Group>,
+    pub permissions: HashMap<Uuid, Permission>,
+    pub permission_attributes: HashMap<Uuid, PermissionAttribute>,
+    pub user_group_roles: Vec<UserGroupRoles>,
+    pub user_permissions: Vec<UserPermission>,
+    pub role_permissions: Vec<RolePermission>,
+    pub group_permissions: Vec<GroupPermission>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn active_role(&self, id: Option<Uuid>) -> Option<&Role> {
+        self.roles.get(&id?).filter(|x| x.deleted_date.is_none())
+    }
+
+    fn active_group(&self, id: Option<Uuid>) -> Option<&Group> {
+        self.groups.get(&id?).filter(|x| x.deleted_date.is_none())
+    }
+}
+
+impl RbacRepository for MemoryRepository {
+    async fn get_role_by_id(&mut self, id: &Uuid) -> anyhow::Result<Option<Role>> {
+        Ok(self.active_role(Some(*id)).cloned())
+    }
+
+    async fn get_group_by_id(&mut self, id: &Uuid) -> anyhow::Result<Option<Group>> {
+        Ok(self.active_group(Some(*id)).cloned())
+    }
+
+    async fn get_permission_by_id(&mut self, id: &Uuid) -> anyhow::Result<Option<Permission>> {
+        Ok(self.permissions.get(id).cloned())
+    }
+
+    async fn get_permission_attribute_by_id(
+        &mut self,
+        id: &Uuid,
+    ) -> anyhow::Result<Option<PermissionAttribute>> {
+        Ok(self.permission_attributes.get(id).cloned())
+    }
+
+    async fn user_has_permission(
+        &mut self,
+        user_id: &Uuid,
+        permission_name: &str,
+        attribute_name: &str,
+    ) -> anyhow::Result<bool> {
+        let permission_ids: Vec<Uuid> = self
+            .permissions
+            .values()
+            .filter(|x| x.permission_name == permission_name)
+            .map(|x| x.id)
+            .collect();
+        let attribute_ids: Vec<Uuid> = self
+            .permission_attributes
+            .values()
+            .filter(|x| x.name == attribute_name && x.deleted_date.is_none())
+            .map(|x| x.id)
+            .collect();
+        let now = utc_now();
+        let is_granted = |permission_id: &Uuid, attribute_id: &Uuid| {
+            permission_ids.contains(permission_id) && attribute_ids.contains(attribute_id)
+        };
+        if self.user_permissions.iter().any(|x| {
+            x.user_id == *user_id
+                && x.expires_at.is_none_or(|x| x > now)
+                && is_granted(&x.permission_id, &x.attribute_id)
+        }) {
+            return Ok(true);
+        }
+        let assignments = self
+            .user_group_roles
+            .iter()
+            .filter(|x| x.user_id == Some(*user_id));
+        for assignment in assignments {
+            if let Some(role) = self.active_role(assignment.role_id) {
+                if self
+                    .role_permissions
+                    .iter()
+                    .any(|x| x.role_id == role.id && is_granted(&x.permission_id, &x.attribute_id))
+                {
+                    return Ok(true);
+                }
+            }
+            if let Some(group) = self.active_group(assignment.group_id) {
+                if self.group_permissions.iter().any(|x| {
+                    x.group_id == group.id && is_granted(&x.permission_id, &x.attribute_id)
+                }) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{
+        core::utils::utc_now,
+        model::{
+            permission::Permission, permission_attribute::PermissionAttribute, role::Role,
+            role_permission::RolePermission, user_group_roles::UserGroupRoles,
+        },
+        repository::{memory::MemoryRepository, rbac::RbacRepository},
+    };
+
+    #[tokio::test]
+    async fn test_user_has_permission() -> anyhow::Result<()> {
+        // Given a user with a role granting report read
+        let mut repo = MemoryRepository::new();
+        let user_id = Uuid::now_v7();
+        let role = Role {
+            id: Uuid::now_v7(),
+            role_name: "auditor".to_string(),
+            description: None,
+            is_active: Some(true),
+            created_by: None,
+            updated_by: None,
+            created_date: None,
+            updated_date: None,
+            deleted_date: None,
+            deleted_by: None,
+        };
+        let permission = Permission {
+            id: Uuid::now_v7(),
+            permission_name: "report".to_string(),
+            is_user: Some(true),
+            is_role: Some(true),
+            is_group: Some(true),
+            description: None,
+            created_by: None,
+            updated_by: None,
+            created_date: None,
+            updated_date: None,
+        };
+        let attribute = PermissionAttribute {
+            id: Uuid::now_v7(),
+            name: "read".to_string(),
+            description: None,
+            category: None,
+            created_date: None,
+            updated_date: None,
+            deleted_date: None,
+        };
+        repo.role_permissions.push(RolePermission {
+            role_id: role.id,
+            permission_id: permission.id,
+            attribute_id: attribute.id,
+            created_by: None,
+            updated_by: None,
+            created_date: None,
+            updated_date: None,
+        });
+        repo.user_group_roles.push(UserGroupRoles {
+            id: Uuid::now_v7(),
+            user_id: Some(user_id),
+            group_id: None,
+            role_id: Some(role.id),
+        });
+        repo.roles.insert(role.id, role.clone());
+        repo.permissions.insert(permission.id, permission);
+        repo.permission_attributes.insert(attribute.id, attribute);
+
+        // Expect
+        assert!(repo.user_has_permission(&user_id, "report", "read").await?);
+        assert!(
+            !repo
+                .user_has_permission(&user_id, "report", "update")
+                .await?
+        );
+        assert!(
+            !repo
+                .user_has_permission(&Uuid::now_v7(), "report", "read")
+                .await?
+        );
+
+        // When the role is soft deleted
+        repo.roles.get_mut(&role.id).unwrap().deleted_date = Some(utc_now());
+
+        // Expect nothing granted
+        assert!(!repo.user_has_permission(&user_id, "report", "read").await?);
+        assert!(repo.get_role_by_id(&role.id).await?.is_none());
+        Ok(())
+    }
+}
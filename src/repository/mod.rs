@@ -1,10 +1,36 @@
+pub mod consent_type;
+pub mod directory_source;
+pub mod directory_sync_run;
 pub mod group;
 pub mod group_permission;
+#[cfg(any(test, feature = "memory-repository"))]
+pub mod memory;
+pub mod notification_template;
 pub mod permission;
 pub mod permission_attribute;
 pub mod permission_attribute_list;
+pub mod rbac;
 pub mod role;
 pub mod role_permission;
+pub mod scim_provisioning_event;
+pub mod scim_target;
+pub mod service_account;
+pub mod sso_jit_rule;
+pub mod sso_provider;
+pub mod sso_role_mapping;
+pub mod terms_version;
 pub mod user;
+pub mod user_activity;
+pub mod user_anonymization;
+pub mod user_consent;
+pub mod user_contact;
+pub mod user_data_export;
+pub mod user_device;
 pub mod user_group_roles;
+pub mod user_identity;
+pub mod user_name_history;
 pub mod user_permission;
+pub mod user_preference;
+pub mod user_recovery_code;
+pub mod user_status_history;
+pub mod user_terms_acceptance;
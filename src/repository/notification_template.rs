@@ -0,0 +1,119 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::notification_template::{NotificationTemplate, TABLE_NAME};
+
+pub async fn get_all_notification_template(
+    conn: &mut PgConnection,
+    event: Option<&str>,
+) -> anyhow::Result<Vec<NotificationTemplate>> {
+    let data: Vec<NotificationTemplate> = sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE ($1::varchar IS NULL OR "event" = $1)
+            ORDER BY "event" ASC, channel ASC, locale ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(event)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(data)
+}
+
+pub async fn get_notification_template_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<NotificationTemplate>> {
+    let data: Option<NotificationTemplate> =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    Ok(data)
+}
+
+pub async fn get_notification_template(
+    conn: &mut PgConnection,
+    event: &str,
+    channel: &str,
+    locale: &str,
+) -> anyhow::Result<Option<NotificationTemplate>> {
+    let data: Option<NotificationTemplate> = sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE "event" = $1 AND channel = $2 AND locale = $3"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(event)
+    .bind(channel)
+    .bind(locale)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(data)
+}
+
+pub async fn create_notification_template(
+    tx: &mut Transaction<'_, Postgres>,
+    template: &NotificationTemplate,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, "event", channel, locale, subject, body, created_by, updated_by, created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(template.id)
+    .bind(&template.event)
+    .bind(&template.channel)
+    .bind(&template.locale)
+    .bind(&template.subject)
+    .bind(&template.body)
+    .bind(template.created_by)
+    .bind(template.updated_by)
+    .bind(template.created_date)
+    .bind(template.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_notification_template(
+    tx: &mut Transaction<'_, Postgres>,
+    template: &NotificationTemplate,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {} SET "event" = $1, channel = $2, locale = $3, subject = $4, body = $5,
+            updated_by = $6, updated_date = $7
+            WHERE id = $8"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&template.event)
+    .bind(&template.channel)
+    .bind(&template.locale)
+    .bind(&template.subject)
+    .bind(&template.body)
+    .bind(template.updated_by)
+    .bind(template.updated_date)
+    .bind(template.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_notification_template(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -1,14 +1,29 @@
-use sqlx::{Postgres, Transaction};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
-    model::permission::{Permission, TABLE_NAME},
+    core::sqlx_utils::{
+        audited_from, audited_select, binds_query_as, query_builder, Sort, SqlxBinds,
+    },
+    model::{
+        audited::Audited,
+        permission::{Permission, TABLE_NAME},
+    },
 };
 
+/// Columns get_all_permission can be sorted by
+pub const SORT_COLUMNS: &[&str] = &[
+    "permission_name",
+    "is_user",
+    "is_role",
+    "is_group",
+    "created_date",
+    "updated_date",
+];
+
 #[allow(clippy::too_many_arguments)]
 pub async fn get_all_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: Option<u32>,
     page_size: Option<u32>,
     search: Option<String>,
@@ -17,7 +32,9 @@ pub async fn get_all_permission(
     is_group: Option<bool>,
     limit: Option<u32>,
     all: Option<bool>,
-) -> anyhow::Result<(Vec<Permission>, u32, u32)> {
+    sort: Sort,
+) -> anyhow::Result<(Vec<Audited<Permission>>, u32, u32)> {
+    const ALIAS: &str = "p";
     let page = page.unwrap_or(1);
     let page_size = page_size.unwrap_or(10);
     let all = all.unwrap_or(false);
@@ -25,21 +42,21 @@ pub async fn get_all_permission(
     let mut binds: Vec<SqlxBinds> = vec![];
     let mut filters: Vec<String> = vec![];
 
-    if search.is_some() {
-        binds.push(SqlxBinds::String(format!("%{}%", search.unwrap())));
-        filters.push(format!("permission_name = ${}", binds.len()));
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("{}.permission_name = ${}", ALIAS, binds.len()));
     }
-    if is_user.is_some() {
-        binds.push(SqlxBinds::Bool(is_user.unwrap()));
-        filters.push(format!("is_user = ${}", binds.len()));
+    if let Some(is_user) = is_user {
+        binds.push(SqlxBinds::Bool(is_user));
+        filters.push(format!("{}.is_user = ${}", ALIAS, binds.len()));
     }
-    if is_role.is_some() {
-        binds.push(SqlxBinds::Bool(is_role.unwrap()));
-        filters.push(format!("is_role = ${}", binds.len()));
+    if let Some(is_role) = is_role {
+        binds.push(SqlxBinds::Bool(is_role));
+        filters.push(format!("{}.is_role = ${}", ALIAS, binds.len()));
     }
-    if is_group.is_some() {
-        binds.push(SqlxBinds::Bool(is_group.unwrap()));
-        filters.push(format!("is_group = ${}", binds.len()));
+    if let Some(is_group) = is_group {
+        binds.push(SqlxBinds::Bool(is_group));
+        filters.push(format!("{}.is_group = ${}", ALIAS, binds.len()));
     }
 
     let mut limit = match all {
@@ -54,26 +71,26 @@ pub async fn get_all_permission(
         limit = limit_param;
     }
     let stmt = query_builder(
-        None,
-        TABLE_NAME,
+        Some(audited_select(ALIAS)),
+        &audited_from(TABLE_NAME, ALIAS),
         &filters,
-        vec!["updated_date DESC".to_string()],
+        sort.order_by_aliased(ALIAS, SORT_COLUMNS, "updated_date DESC")?,
         limit,
         offset,
     );
     let stmt_count = query_builder(
-        Some("count(id)".to_string()),
-        TABLE_NAME,
+        Some(format!("count({}.id)", ALIAS)),
+        &format!("{} AS {}", TABLE_NAME, ALIAS),
         &filters,
         vec![],
         None,
         None,
     );
 
-    let q = binds_query_as::<Permission>(&stmt, binds.clone());
+    let q = binds_query_as::<Audited<Permission>>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = match all {
         true => 0,
         false => (count.0 as u32).div_ceil(page_size),
@@ -82,13 +99,45 @@ pub async fn get_all_permission(
 }
 
 pub async fn get_permission_by_id(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     id: &Uuid,
 ) -> anyhow::Result<Option<Permission>> {
     Ok(
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
             .bind(id)
-            .fetch_optional(&mut **tx)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+/// Permissions among the ids, in a single query
+pub async fn get_permissions_by_ids(
+    conn: &mut PgConnection,
+    ids: &[Uuid],
+) -> anyhow::Result<Vec<Permission>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE id = ANY($1) ORDER BY updated_date DESC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(ids)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_permission_by_name(
+    conn: &mut PgConnection,
+    permission_name: &str,
+) -> anyhow::Result<Option<Permission>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE permission_name = $1", TABLE_NAME).as_str())
+            .bind(permission_name)
+            .fetch_optional(&mut *conn)
             .await?,
     )
 }
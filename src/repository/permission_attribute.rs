@@ -1,16 +1,32 @@
-use sqlx::{Postgres, Transaction};
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     core::sqlx_utils::{binds_query_as, in_helper, query_builder, SqlxBinds},
-    model::permission_attribute::{PermissionAttribute, TABLE_NAME},
+    model::{
+        group_permission::TABLE_NAME as GROUP_PERMISSION_TABLE_NAME,
+        permission_attribute::{PermissionAttribute, TABLE_NAME},
+        permission_attribute_list::TABLE_NAME as PERMISSION_ATTRIBUTE_LIST_TABLE_NAME,
+        role_permission::TABLE_NAME as ROLE_PERMISSION_TABLE_NAME,
+        user_permission::TABLE_NAME as USER_PERMISSION_TABLE_NAME,
+    },
 };
 
+/// Tables granting a permission attribute, (usage kind, table)
+const USAGE_TABLES: [(&str, &str); 4] = [
+    ("permission", PERMISSION_ATTRIBUTE_LIST_TABLE_NAME),
+    ("user_permission", USER_PERMISSION_TABLE_NAME),
+    ("role_permission", ROLE_PERMISSION_TABLE_NAME),
+    ("group_permission", GROUP_PERMISSION_TABLE_NAME),
+];
+
 pub async fn get_all_permission_attribute(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: Option<u32>,
     page_size: Option<u32>,
     search: Option<String>,
+    category: Option<String>,
     limit: Option<u32>,
     all: Option<bool>,
 ) -> anyhow::Result<(Vec<PermissionAttribute>, u32, u32)> {
@@ -19,11 +35,15 @@ pub async fn get_all_permission_attribute(
     let all = all.unwrap_or(false);
     let limit_param = limit;
     let mut binds: Vec<SqlxBinds> = vec![];
-    let mut filters: Vec<String> = vec![];
-    if search.is_some() {
-        binds.push(SqlxBinds::String(format!("%{}%", search.unwrap())));
+    let mut filters: Vec<String> = vec!["deleted_date IS NULL".to_string()];
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
         filters.push(format!("name ilike ${}", binds.len()));
     }
+    if let Some(category) = category {
+        binds.push(SqlxBinds::String(category));
+        filters.push(format!("category = ${}", binds.len()));
+    }
 
     let mut limit = match all {
         true => None,
@@ -55,8 +75,8 @@ pub async fn get_all_permission_attribute(
 
     let q = binds_query_as::<PermissionAttribute>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = match all {
         true => 0,
         false => (count.0 as u32).div_ceil(page_size),
@@ -64,20 +84,72 @@ pub async fn get_all_permission_attribute(
     Ok((data, count.0 as u32, num_page as u32))
 }
 
+/// Distinct categories with their number of attributes, uncategorized (None) last
+pub async fn get_permission_attribute_categories(
+    conn: &mut PgConnection,
+) -> anyhow::Result<Vec<(Option<String>, i64)>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT category, count(id) FROM {} WHERE deleted_date IS NULL GROUP BY category ORDER BY category ASC NULLS LAST",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
 pub async fn get_permission_attribute_by_id(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     id: &Uuid,
 ) -> anyhow::Result<Option<PermissionAttribute>> {
-    Ok(
-        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(id)
-            .fetch_optional(&mut **tx)
-            .await?,
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE id = $1 AND deleted_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+/// Soft deleted attribute, None when it does not exist or is not deleted
+pub async fn get_deleted_permission_attribute_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<PermissionAttribute>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE id = $1 AND deleted_date IS NOT NULL",
+            TABLE_NAME
+        )
+        .as_str(),
     )
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+pub async fn get_permission_attribute_by_name(
+    conn: &mut PgConnection,
+    name: &str,
+) -> anyhow::Result<Option<PermissionAttribute>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE name = $1 AND deleted_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(name)
+    .fetch_optional(&mut *conn)
+    .await?)
 }
 
 pub async fn get_permission_attribute_by_ids(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     ids: Vec<Uuid>,
 ) -> anyhow::Result<Vec<PermissionAttribute>> {
     let mut ins: Vec<SqlxBinds> = vec![];
@@ -87,6 +159,7 @@ pub async fn get_permission_attribute_by_ids(
     let mut binds: Vec<SqlxBinds> = vec![];
     let mut filters: Vec<String> = vec![];
     in_helper(&mut binds, &mut filters, ins, "id");
+    filters.push("deleted_date IS NULL".to_string());
     let stmt = query_builder(
         None,
         TABLE_NAME,
@@ -96,7 +169,7 @@ pub async fn get_permission_attribute_by_ids(
         None,
     );
     let q = binds_query_as::<PermissionAttribute>(&stmt, binds.clone());
-    let data = q.fetch_all(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
     Ok(data)
 }
 
@@ -104,10 +177,11 @@ pub async fn create_permission_attribute(
     tx: &mut Transaction<'_, Postgres>,
     permission_attribute: &PermissionAttribute,
 ) -> anyhow::Result<()> {
-    sqlx::query(format!("INSERT INTO {} (id, name, description, created_date, updated_date) VALUES ($1, $2, $3, $4, $5)", TABLE_NAME).as_str())
+    sqlx::query(format!("INSERT INTO {} (id, name, description, category, created_date, updated_date) VALUES ($1, $2, $3, $4, $5, $6)", TABLE_NAME).as_str())
         .bind(permission_attribute.id)
         .bind(&permission_attribute.name)
         .bind(&permission_attribute.description)
+        .bind(&permission_attribute.category)
         .bind(permission_attribute.created_date)
         .bind(permission_attribute.updated_date)
         .execute(&mut **tx)
@@ -119,9 +193,10 @@ pub async fn update_permission_attribute(
     tx: &mut Transaction<'_, Postgres>,
     permission_attribute: &PermissionAttribute,
 ) -> anyhow::Result<()> {
-    sqlx::query(format!("UPDATE {} SET name = $1, description = $2, created_date = $3, updated_date = $4 WHERE id = $5", TABLE_NAME).as_str())
+    sqlx::query(format!("UPDATE {} SET name = $1, description = $2, category = $3, created_date = $4, updated_date = $5 WHERE id = $6", TABLE_NAME).as_str())
         .bind(&permission_attribute.name)
         .bind(&permission_attribute.description)
+        .bind(&permission_attribute.category)
         .bind(permission_attribute.created_date)
         .bind(permission_attribute.updated_date)
         .bind(permission_attribute.id)
@@ -140,3 +215,84 @@ pub async fn delete_permission_attribute(
         .await?;
     Ok(())
 }
+
+/// Number of rows referencing the attribute per usage kind, kinds without rows are left out
+pub async fn get_permission_attribute_usages(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Vec<(String, i64)>> {
+    let mut res = vec![];
+    for (kind, table_name) in USAGE_TABLES {
+        let count: (i64,) = sqlx::query_as(
+            format!(
+                "SELECT count(*) FROM {} WHERE attribute_id = $1",
+                table_name
+            )
+            .as_str(),
+        )
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+        if count.0 > 0 {
+            res.push((kind.to_string(), count.0));
+        }
+    }
+    Ok(res)
+}
+
+/// Remove every grant of the attribute, see get_permission_attribute_usages
+pub async fn delete_permission_attribute_usages(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    for (_, table_name) in USAGE_TABLES {
+        sqlx::query(format!("DELETE FROM {} WHERE attribute_id = $1", table_name).as_str())
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn soft_delete_permission_attribute(
+    tx: &mut Transaction<'_, Postgres>,
+    permission_attribute: &mut PermissionAttribute,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    permission_attribute.updated_date = Some(now);
+    permission_attribute.deleted_date = Some(now);
+    sqlx::query(
+        format!(
+            "UPDATE {} SET updated_date = $1, deleted_date = $2 WHERE id = $3",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(permission_attribute.updated_date)
+    .bind(permission_attribute.deleted_date)
+    .bind(permission_attribute.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn restore_permission_attribute(
+    tx: &mut Transaction<'_, Postgres>,
+    permission_attribute: &mut PermissionAttribute,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    permission_attribute.updated_date = Some(now);
+    permission_attribute.deleted_date = None;
+    sqlx::query(
+        format!(
+            "UPDATE {} SET updated_date = $1, deleted_date = NULL WHERE id = $2",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(permission_attribute.updated_date)
+    .bind(permission_attribute.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
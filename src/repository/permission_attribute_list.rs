@@ -1,4 +1,4 @@
-use sqlx::{Postgres, Transaction};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
@@ -11,24 +11,24 @@ use crate::{
 };
 
 pub async fn get_all_permission_attribute_list(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     permission_id: Option<&Uuid>,
     attribute_id: Option<&Uuid>,
 ) -> anyhow::Result<Vec<PermissionAttributeList>> {
     let mut binds: Vec<SqlxBinds> = vec![];
     let mut filters: Vec<String> = vec![];
 
-    if permission_id.is_some() {
-        binds.push(SqlxBinds::Uuid(*permission_id.unwrap()));
+    if let Some(permission_id) = permission_id {
+        binds.push(SqlxBinds::Uuid(*permission_id));
         filters.push(format!("permission_id = ${}", binds.len()));
     }
-    if attribute_id.is_some() {
-        binds.push(SqlxBinds::Uuid(*attribute_id.unwrap()));
+    if let Some(attribute_id) = attribute_id {
+        binds.push(SqlxBinds::Uuid(*attribute_id));
         filters.push(format!("attribute_id = ${}", binds.len()));
     }
     let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
     let q = binds_query_as::<PermissionAttributeList>(&stmt, binds.clone());
-    let data = q.fetch_all(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
     Ok(data)
 }
 
@@ -74,3 +74,21 @@ pub async fn update_permssion_attribute_list_by_permission(
     }
     Ok(())
 }
+
+pub async fn delete_permission_attribute_list(
+    tx: &mut Transaction<'_, Postgres>,
+    permission_attribute_list: &PermissionAttributeList,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            "DELETE FROM {} WHERE permission_id = $1 AND attribute_id = $2",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(permission_attribute_list.permission_id)
+    .bind(permission_attribute_list.attribute_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
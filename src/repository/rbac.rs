@@ -0,0 +1,76 @@
+use std::future::Future;
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    model::{
+        group::Group, permission::Permission, permission_attribute::PermissionAttribute, role::Role,
+    },
+    repository::{group, permission, permission_attribute, role, user_permission},
+};
+
+/// Role, group and permission lookups handler logic depends on, implemented for a postgres
+/// connection (pass a transaction as `&mut *tx`) and, behind the memory-repository feature,
+/// by repository::memory for unit tests
+pub trait RbacRepository {
+    /// Not soft deleted role
+    fn get_role_by_id(
+        &mut self,
+        id: &Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<Role>>> + Send;
+
+    /// Not soft deleted group
+    fn get_group_by_id(
+        &mut self,
+        id: &Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<Group>>> + Send;
+
+    fn get_permission_by_id(
+        &mut self,
+        id: &Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<Permission>>> + Send;
+
+    fn get_permission_attribute_by_id(
+        &mut self,
+        id: &Uuid,
+    ) -> impl Future<Output = anyhow::Result<Option<PermissionAttribute>>> + Send;
+
+    /// See repository::user_permission::user_has_permission
+    fn user_has_permission(
+        &mut self,
+        user_id: &Uuid,
+        permission_name: &str,
+        attribute_name: &str,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
+}
+
+impl RbacRepository for PgConnection {
+    async fn get_role_by_id(&mut self, id: &Uuid) -> anyhow::Result<Option<Role>> {
+        role::get_role_by_id(self, id).await
+    }
+
+    async fn get_group_by_id(&mut self, id: &Uuid) -> anyhow::Result<Option<Group>> {
+        group::get_group_by_id(self, id).await
+    }
+
+    async fn get_permission_by_id(&mut self, id: &Uuid) -> anyhow::Result<Option<Permission>> {
+        permission::get_permission_by_id(self, id).await
+    }
+
+    async fn get_permission_attribute_by_id(
+        &mut self,
+        id: &Uuid,
+    ) -> anyhow::Result<Option<PermissionAttribute>> {
+        permission_attribute::get_permission_attribute_by_id(self, id).await
+    }
+
+    async fn user_has_permission(
+        &mut self,
+        user_id: &Uuid,
+        permission_name: &str,
+        attribute_name: &str,
+    ) -> anyhow::Result<bool> {
+        user_permission::user_has_permission(self, user_id, permission_name, attribute_name).await
+    }
+}
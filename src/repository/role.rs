@@ -1,59 +1,70 @@
 use anyhow::Ok;
-use chrono::{DateTime, FixedOffset, Local};
-use sqlx::{Postgres, Transaction};
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+    core::{
+        sqlx_utils::{
+            audited_from, audited_select, binds_query_as, query_builder, Sort, SqlxBinds,
+        },
+        utils::utc_now,
+    },
     model::{
+        audited::Audited,
         role::{Role, TABLE_NAME},
         user::User,
     },
 };
 
+/// Columns paginate_role can be sorted by
+pub const SORT_COLUMNS: &[&str] = &["role_name", "is_active", "created_date", "updated_date"];
+
 pub async fn paginate_role(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: u32,
     page_size: u32,
     search: Option<String>,
-) -> anyhow::Result<(Vec<Role>, u32, u32)> {
+    sort: Sort,
+) -> anyhow::Result<(Vec<Audited<Role>>, u32, u32)> {
+    const ALIAS: &str = "r";
     let mut binds: Vec<SqlxBinds> = vec![];
     let mut filters: Vec<String> = vec![];
 
-    if search.is_some() {
-        binds.push(SqlxBinds::String(format!("%{}%", search.unwrap())));
-        filters.push(format!("role_name = ${}", binds.len()));
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("{}.role_name = ${}", ALIAS, binds.len()));
     }
-    filters.push("deleted_date IS NULL".to_string());
+    filters.push(format!("{}.deleted_date IS NULL", ALIAS));
 
     let limit = page_size;
     let offset = (page - 1) * page_size;
     let stmt = query_builder(
-        None,
-        TABLE_NAME,
+        Some(audited_select(ALIAS)),
+        &audited_from(TABLE_NAME, ALIAS),
         &filters,
-        vec!["updated_date DESC".to_string()],
+        sort.order_by_aliased(ALIAS, SORT_COLUMNS, "updated_date DESC")?,
         Some(limit),
         Some(offset),
     );
     let stmt_count = query_builder(
-        Some("count(id)".to_string()),
-        TABLE_NAME,
+        Some(format!("count({}.id)", ALIAS)),
+        &format!("{} AS {}", TABLE_NAME, ALIAS),
         &filters,
         vec![],
         None,
         None,
     );
 
-    let q = binds_query_as::<Role>(&stmt, binds.clone());
+    let q = binds_query_as::<Audited<Role>>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = (count.0 as u32).div_ceil(page_size);
     Ok((data, count.0 as u32, num_page as u32))
 }
 
-pub async fn get_all_role(tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Vec<Role>> {
+pub async fn get_all_role(conn: &mut PgConnection) -> anyhow::Result<Vec<Role>> {
     let filters: Vec<String> = vec!["deleted_date IS NULL".to_string()];
     let stmt = query_builder(
         None,
@@ -64,20 +75,20 @@ pub async fn get_all_role(tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<
         None,
     );
     let q = binds_query_as::<Role>(&stmt, vec![]);
-    let data = q.fetch_all(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
     Ok(data)
 }
 
 pub async fn get_dropdown_role(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     limit: Option<u32>,
     search: Option<String>,
 ) -> anyhow::Result<Vec<Role>> {
     let mut binds: Vec<SqlxBinds> = vec![];
     let mut filters: Vec<String> = vec!["deleted_date IS NULL".to_string()];
 
-    if search.is_some() {
-        binds.push(SqlxBinds::String(format!("%{}%", search.unwrap())));
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
         filters.push(format!("role_name = ${}", binds.len()));
     }
 
@@ -92,14 +103,11 @@ pub async fn get_dropdown_role(
         None,
     );
     let q = binds_query_as::<Role>(&stmt, vec![]);
-    let data = q.fetch_all(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
     Ok(data)
 }
 
-pub async fn get_role_by_id(
-    tx: &mut Transaction<'_, Postgres>,
-    id: &Uuid,
-) -> anyhow::Result<Option<Role>> {
+pub async fn get_role_by_id(conn: &mut PgConnection, id: &Uuid) -> anyhow::Result<Option<Role>> {
     let binds: Vec<SqlxBinds> = vec![SqlxBinds::Uuid(*id)];
     let filters: Vec<String> = vec!["id = $1".to_string(), "deleted_date IS NULL".to_string()];
     let stmt = query_builder(
@@ -111,7 +119,39 @@ pub async fn get_role_by_id(
         None,
     );
     let q = binds_query_as::<Role>(&stmt, binds);
-    let data = q.fetch_optional(&mut **tx).await?;
+    let data = q.fetch_optional(&mut *conn).await?;
+    Ok(data)
+}
+
+/// Roles not soft deleted among the ids, in a single query
+pub async fn get_roles_by_ids(conn: &mut PgConnection, ids: &[Uuid]) -> anyhow::Result<Vec<Role>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE id = ANY($1) AND deleted_date IS NULL ORDER BY updated_date DESC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(ids)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_role_by_name(
+    conn: &mut PgConnection,
+    role_name: &str,
+) -> anyhow::Result<Option<Role>> {
+    let binds: Vec<SqlxBinds> = vec![SqlxBinds::String(role_name.to_string())];
+    let filters: Vec<String> = vec![
+        "role_name = $1".to_string(),
+        "deleted_date IS NULL".to_string(),
+    ];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<Role>(&stmt, binds);
+    let data = q.fetch_optional(&mut *conn).await?;
     Ok(data)
 }
 
@@ -124,7 +164,7 @@ pub async fn create_role(
     request_user: User,
     now: Option<DateTime<FixedOffset>>,
 ) -> anyhow::Result<Role> {
-    let now = now.unwrap_or(Local::now().fixed_offset());
+    let now = now.unwrap_or(utc_now());
     let new_role = Role {
         id: id.unwrap_or(Uuid::now_v7()),
         role_name,
@@ -135,6 +175,7 @@ pub async fn create_role(
         created_date: Some(now),
         updated_date: Some(now),
         deleted_date: None,
+        deleted_by: None,
     };
     sqlx::query(
         format!(
@@ -169,7 +210,7 @@ pub async fn update_role(
     request_user: User,
     now: Option<DateTime<FixedOffset>>,
 ) -> anyhow::Result<()> {
-    let now = now.unwrap_or(Local::now().fixed_offset());
+    let now = now.unwrap_or(utc_now());
     role.role_name = role_name;
     role.description = description;
     role.is_active = is_active;
@@ -202,15 +243,16 @@ pub async fn soft_delete_role(
     request_user: User,
     now: Option<DateTime<FixedOffset>>,
 ) -> anyhow::Result<()> {
-    let now = now.unwrap_or(Local::now().fixed_offset());
+    let now = now.unwrap_or(utc_now());
     role.updated_by = Some(request_user.id);
     role.updated_date = Some(now);
     role.deleted_date = Some(now);
+    role.deleted_by = Some(request_user.id);
     sqlx::query(
         format!(
             r#"UPDATE {}
-    SET updated_by = $1, updated_date = $2, deleted_date = $3
-    WHERE id = $4"#,
+    SET updated_by = $1, updated_date = $2, deleted_date = $3, deleted_by = $4
+    WHERE id = $5"#,
             TABLE_NAME
         )
         .as_str(),
@@ -218,6 +260,93 @@ pub async fn soft_delete_role(
     .bind(role.updated_by)
     .bind(role.updated_date)
     .bind(role.deleted_date)
+    .bind(role.deleted_by)
+    .bind(role.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Soft deleted roles, most recently deleted first
+pub async fn paginate_deleted_role(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    search: Option<String>,
+) -> anyhow::Result<(Vec<Role>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec!["deleted_date IS NOT NULL".to_string()];
+
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("role_name ILIKE ${}", binds.len()));
+    }
+
+    let limit = page_size;
+    let offset = (page - 1) * page_size;
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec!["deleted_date DESC".to_string()],
+        Some(limit),
+        Some(offset),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+
+    let q = binds_query_as::<Role>(&stmt, binds.clone());
+    let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page as u32))
+}
+
+pub async fn get_deleted_role_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<Role>> {
+    let binds: Vec<SqlxBinds> = vec![SqlxBinds::Uuid(*id)];
+    let filters: Vec<String> = vec![
+        "id = $1".to_string(),
+        "deleted_date IS NOT NULL".to_string(),
+    ];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<Role>(&stmt, binds);
+    let data = q.fetch_optional(&mut *conn).await?;
+    Ok(data)
+}
+
+/// Undo soft_delete_role, permission grants and members were kept so they apply again
+pub async fn restore_role(
+    tx: &mut Transaction<'_, Postgres>,
+    role: &mut Role,
+    request_user: User,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<()> {
+    let now = now.unwrap_or(utc_now());
+    role.updated_by = Some(request_user.id);
+    role.updated_date = Some(now);
+    role.deleted_date = None;
+    role.deleted_by = None;
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+    SET updated_by = $1, updated_date = $2, deleted_date = NULL, deleted_by = NULL
+    WHERE id = $3"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(role.updated_by)
+    .bind(role.updated_date)
     .bind(role.id)
     .execute(&mut **tx)
     .await?;
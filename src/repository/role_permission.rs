@@ -1,13 +1,17 @@
-use sqlx::{Postgres, Transaction};
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
-    model::role_permission::{RolePermission, TABLE_NAME},
+    model::{
+        permission_attribute::TABLE_NAME as PERMISSION_ATTRIBUTE_TABLE_NAME,
+        role_permission::{RolePermission, TABLE_NAME},
+    },
 };
 
 pub async fn get_all_role_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: Option<u32>,
     page_size: Option<u32>,
     role_id: &Uuid,
@@ -50,8 +54,8 @@ pub async fn get_all_role_permission(
 
     let q = binds_query_as::<RolePermission>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = match all {
         true => 0,
         false => (count.0 as u32).div_ceil(page_size),
@@ -59,8 +63,28 @@ pub async fn get_all_role_permission(
     Ok((data, count.0 as u32, num_page as u32))
 }
 
+/// Every permission of the roles, in a single query
+pub async fn get_role_permissions_by_role_ids(
+    conn: &mut PgConnection,
+    role_ids: &[Uuid],
+) -> anyhow::Result<Vec<RolePermission>> {
+    if role_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE role_id = ANY($1) ORDER BY updated_date DESC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(role_ids)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
 pub async fn get_detail_role_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     role_id: &Uuid,
     permission_id: &Uuid,
     attribute_id: &Uuid,
@@ -75,7 +99,7 @@ pub async fn get_detail_role_permission(
     .bind(role_id)
     .bind(permission_id)
     .bind(attribute_id)
-    .fetch_optional(&mut **tx)
+    .fetch_optional(&mut *conn)
     .await?)
 }
 
@@ -114,3 +138,45 @@ pub async fn delete_role_permission(
     .await?;
     Ok(())
 }
+
+/// Remove every grant of the role, return the number removed
+pub async fn delete_role_permissions_by_role(
+    tx: &mut Transaction<'_, Postgres>,
+    role_id: &Uuid,
+) -> anyhow::Result<u64> {
+    let res = sqlx::query(format!("DELETE FROM {} WHERE role_id = $1", TABLE_NAME).as_str())
+        .bind(role_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// Grant the target role every permission attribute of the source role it does not hold
+/// yet, grants of soft deleted attributes are skipped. Return the number added.
+pub async fn copy_role_permissions(
+    tx: &mut Transaction<'_, Postgres>,
+    source_role_id: &Uuid,
+    target_role_id: &Uuid,
+    request_user_id: &Uuid,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<u64> {
+    let res = sqlx::query(
+        format!(
+            r#"INSERT INTO {role_permission} (role_id, permission_id, attribute_id, created_by, updated_by, created_date, updated_date)
+            SELECT $2, rp.permission_id, rp.attribute_id, $3, $3, $4, $4 FROM {role_permission} rp
+            JOIN {permission_attribute} pa ON pa.id = rp.attribute_id AND pa.deleted_date IS NULL
+            WHERE rp.role_id = $1
+            ON CONFLICT (role_id, permission_id, attribute_id) DO NOTHING"#,
+            role_permission = TABLE_NAME,
+            permission_attribute = PERMISSION_ATTRIBUTE_TABLE_NAME,
+        )
+        .as_str(),
+    )
+    .bind(source_role_id)
+    .bind(target_role_id)
+    .bind(request_user_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,235 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+        utils::utc_now,
+    },
+    model::{
+        scim_provisioning_event::{ScimProvisioningEvent, STATUS_PENDING, TABLE_NAME},
+        scim_target::TABLE_NAME as SCIM_TARGET_TABLE_NAME,
+        scim_target_user::{ScimTargetUser, TABLE_NAME as SCIM_TARGET_USER_TABLE_NAME},
+    },
+};
+
+/// Queue a provisioning event for every active scim target
+pub async fn enqueue_scim_event(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    event_type: &str,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<u64> {
+    let now = now.unwrap_or(utc_now());
+    let target_ids: Vec<(Uuid,)> = sqlx::query_as(
+        format!(
+            "SELECT id FROM {} WHERE is_active = true AND deleted_date IS NULL",
+            SCIM_TARGET_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    for (target_id,) in target_ids.iter() {
+        sqlx::query(
+            format!(
+                r#"INSERT INTO {} (id, target_id, user_id, event_type, status, attempts,
+                last_error, next_attempt_date, created_date, updated_date)
+                VALUES ($1, $2, $3, $4, $5, 0, NULL, $6, $6, $6)"#,
+                TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(Uuid::now_v7())
+        .bind(target_id)
+        .bind(user_id)
+        .bind(event_type)
+        .bind(STATUS_PENDING)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(target_ids.len() as u64)
+}
+
+pub async fn paginate_scim_provisioning_event(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    target_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    status: Option<String>,
+) -> anyhow::Result<(Vec<ScimProvisioningEvent>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec![];
+
+    if let Some(target_id) = target_id {
+        binds.push(SqlxBinds::Uuid(target_id));
+        filters.push(format!("target_id = ${}", binds.len()));
+    }
+    if let Some(user_id) = user_id {
+        binds.push(SqlxBinds::Uuid(user_id));
+        filters.push(format!("user_id = ${}", binds.len()));
+    }
+    if let Some(status) = status {
+        binds.push(SqlxBinds::String(status));
+        filters.push(format!("status = ${}", binds.len()));
+    }
+
+    let limit = page_size;
+    let offset = (page - 1) * page_size;
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec!["created_date DESC".to_string()],
+        Some(limit),
+        Some(offset),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+
+    let q = binds_query_as::<ScimProvisioningEvent>(&stmt, binds.clone());
+    let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page))
+}
+
+pub async fn get_scim_provisioning_event_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<ScimProvisioningEvent>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+/// Claim a batch of due pending events by moving their next_attempt_date to `claim_until`,
+/// so other workers skip them while they are delivered. Rows locked by another worker are
+/// skipped, events left pending by a crashed worker are due again after `claim_until`
+pub async fn claim_due_scim_provisioning_event(
+    tx: &mut Transaction<'_, Postgres>,
+    now: &DateTime<FixedOffset>,
+    claim_until: &DateTime<FixedOffset>,
+    limit: u32,
+) -> anyhow::Result<Vec<ScimProvisioningEvent>> {
+    let mut events: Vec<ScimProvisioningEvent> = sqlx::query_as(
+        format!(
+            r#"UPDATE {0} SET next_attempt_date = $3
+            WHERE id IN (
+                SELECT id FROM {0}
+                WHERE status = $1 AND (next_attempt_date IS NULL OR next_attempt_date <= $2)
+                ORDER BY created_date ASC
+                LIMIT $4
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(STATUS_PENDING)
+    .bind(now)
+    .bind(claim_until)
+    .bind(limit as i64)
+    .fetch_all(&mut **tx)
+    .await?;
+    // RETURNING does not keep the order of the subquery
+    events.sort_by_key(|x| x.created_date);
+    Ok(events)
+}
+
+pub async fn update_scim_provisioning_event(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &ScimProvisioningEvent,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+            SET status = $1, attempts = $2, last_error = $3, next_attempt_date = $4, updated_date = $5
+            WHERE id = $6"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&event.status)
+    .bind(event.attempts)
+    .bind(&event.last_error)
+    .bind(event.next_attempt_date)
+    .bind(event.updated_date)
+    .bind(event.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Count events of a target grouped by status
+pub async fn count_scim_provisioning_event_by_status(
+    conn: &mut PgConnection,
+    target_id: &Uuid,
+) -> anyhow::Result<Vec<(String, i64)>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT status, count(id) FROM {} WHERE target_id = $1 GROUP BY status",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(target_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_scim_target_user(
+    conn: &mut PgConnection,
+    target_id: &Uuid,
+    user_id: &Uuid,
+) -> anyhow::Result<Option<ScimTargetUser>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE target_id = $1 AND user_id = $2",
+            SCIM_TARGET_USER_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(target_id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+pub async fn upsert_scim_target_user(
+    tx: &mut Transaction<'_, Postgres>,
+    target_user: &ScimTargetUser,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (target_id, user_id, external_id, created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (target_id, user_id)
+            DO UPDATE SET external_id = EXCLUDED.external_id, updated_date = EXCLUDED.updated_date"#,
+            SCIM_TARGET_USER_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(target_user.target_id)
+    .bind(target_user.user_id)
+    .bind(&target_user.external_id)
+    .bind(target_user.created_date)
+    .bind(target_user.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
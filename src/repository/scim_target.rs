@@ -0,0 +1,204 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+        utils::utc_now,
+    },
+    model::{
+        scim_target::{ScimTarget, TABLE_NAME},
+        user::User,
+    },
+};
+
+pub async fn paginate_scim_target(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    search: Option<String>,
+) -> anyhow::Result<(Vec<ScimTarget>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec![];
+
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("name ilike ${}", binds.len()));
+    }
+    filters.push("deleted_date IS NULL".to_string());
+
+    let limit = page_size;
+    let offset = (page - 1) * page_size;
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec!["updated_date DESC".to_string()],
+        Some(limit),
+        Some(offset),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+
+    let q = binds_query_as::<ScimTarget>(&stmt, binds.clone());
+    let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page))
+}
+
+pub async fn get_active_scim_target(conn: &mut PgConnection) -> anyhow::Result<Vec<ScimTarget>> {
+    let filters: Vec<String> = vec![
+        "is_active = true".to_string(),
+        "deleted_date IS NULL".to_string(),
+    ];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<ScimTarget>(&stmt, vec![]);
+    Ok(q.fetch_all(&mut *conn).await?)
+}
+
+pub async fn get_scim_target_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<ScimTarget>> {
+    let binds: Vec<SqlxBinds> = vec![SqlxBinds::Uuid(*id)];
+    let filters: Vec<String> = vec!["id = $1".to_string(), "deleted_date IS NULL".to_string()];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<ScimTarget>(&stmt, binds);
+    Ok(q.fetch_optional(&mut *conn).await?)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_scim_target(
+    tx: &mut Transaction<'_, Postgres>,
+    id: Option<Uuid>,
+    name: String,
+    base_url: String,
+    bearer_token: Option<String>,
+    is_active: Option<bool>,
+    max_attempts: Option<i32>,
+    request_user: User,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<ScimTarget> {
+    let now = now.unwrap_or(utc_now());
+    let new_target = ScimTarget {
+        id: id.unwrap_or(Uuid::now_v7()),
+        name,
+        base_url,
+        bearer_token,
+        is_active,
+        max_attempts: max_attempts.unwrap_or(5),
+        created_by: Some(request_user.id),
+        updated_by: Some(request_user.id),
+        created_date: Some(now),
+        updated_date: Some(now),
+        deleted_date: None,
+    };
+    sqlx::query(
+        format!(
+            r#"
+    INSERT INTO {} (id, name, base_url, bearer_token, is_active, max_attempts,
+    created_by, updated_by, created_date, updated_date, deleted_date)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(new_target.id)
+    .bind(&new_target.name)
+    .bind(&new_target.base_url)
+    .bind(&new_target.bearer_token)
+    .bind(new_target.is_active)
+    .bind(new_target.max_attempts)
+    .bind(new_target.created_by)
+    .bind(new_target.updated_by)
+    .bind(new_target.created_date)
+    .bind(new_target.updated_date)
+    .bind(new_target.deleted_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(new_target)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn update_scim_target(
+    tx: &mut Transaction<'_, Postgres>,
+    scim_target: &mut ScimTarget,
+    name: String,
+    base_url: String,
+    bearer_token: Option<String>,
+    is_active: Option<bool>,
+    max_attempts: Option<i32>,
+    request_user: User,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<()> {
+    let now = now.unwrap_or(utc_now());
+    scim_target.name = name;
+    scim_target.base_url = base_url;
+    // keep the stored token when the request doesn't send a new one
+    if bearer_token.is_some() {
+        scim_target.bearer_token = bearer_token;
+    }
+    scim_target.is_active = is_active;
+    scim_target.max_attempts = max_attempts.unwrap_or(scim_target.max_attempts);
+    scim_target.updated_by = Some(request_user.id);
+    scim_target.updated_date = Some(now);
+    sqlx::query(
+        format!(
+            r#"
+        UPDATE {}
+        SET name = $1, base_url = $2, bearer_token = $3, is_active = $4, max_attempts = $5,
+        updated_by = $6, updated_date = $7
+        WHERE id = $8"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&scim_target.name)
+    .bind(&scim_target.base_url)
+    .bind(&scim_target.bearer_token)
+    .bind(scim_target.is_active)
+    .bind(scim_target.max_attempts)
+    .bind(scim_target.updated_by)
+    .bind(scim_target.updated_date)
+    .bind(scim_target.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn soft_delete_scim_target(
+    tx: &mut Transaction<'_, Postgres>,
+    scim_target: &mut ScimTarget,
+    request_user: User,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<()> {
+    let now = now.unwrap_or(utc_now());
+    scim_target.updated_by = Some(request_user.id);
+    scim_target.updated_date = Some(now);
+    scim_target.deleted_date = Some(now);
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+    SET updated_by = $1, updated_date = $2, deleted_date = $3
+    WHERE id = $4"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(scim_target.updated_by)
+    .bind(scim_target.updated_date)
+    .bind(scim_target.deleted_date)
+    .bind(scim_target.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
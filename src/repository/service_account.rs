@@ -0,0 +1,177 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+    model::service_account::{ServiceAccount, TABLE_NAME},
+};
+
+pub async fn paginate_service_account(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    search: Option<String>,
+) -> anyhow::Result<(Vec<ServiceAccount>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec!["deleted_date IS NULL".to_string()];
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!(r#""name" ILIKE ${}"#, binds.len()));
+    }
+
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec![r#""name" ASC"#.to_string()],
+        Some(page_size),
+        Some((page - 1) * page_size),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+    let data = binds_query_as::<ServiceAccount>(&stmt, binds.clone())
+        .fetch_all(&mut *conn)
+        .await?;
+    let count = binds_query_as::<(i64,)>(&stmt_count, binds)
+        .fetch_one(&mut *conn)
+        .await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page))
+}
+
+pub async fn get_service_account_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<ServiceAccount>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE id = $1 AND deleted_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+/// Account of an api key, deleted accounts are left out but expired ones are not
+pub async fn get_service_account_by_key_hash(
+    conn: &mut PgConnection,
+    key_hash: &str,
+) -> anyhow::Result<Option<ServiceAccount>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE key_hash = $1 AND deleted_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(key_hash)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+pub async fn create_service_account(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &ServiceAccount,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, "name", description, user_id, key_prefix, key_hash, expires_at, last_used_at, created_by, updated_by, created_date, updated_date, deleted_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(account.id)
+    .bind(&account.name)
+    .bind(&account.description)
+    .bind(account.user_id)
+    .bind(&account.key_prefix)
+    .bind(&account.key_hash)
+    .bind(account.expires_at)
+    .bind(account.last_used_at)
+    .bind(account.created_by)
+    .bind(account.updated_by)
+    .bind(account.created_date)
+    .bind(account.updated_date)
+    .bind(account.deleted_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Store a rotated key, the previous one stops working on commit
+pub async fn update_service_account_key(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &ServiceAccount,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {} SET key_prefix = $1, key_hash = $2, updated_by = $3, updated_date = $4
+            WHERE id = $5"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&account.key_prefix)
+    .bind(&account.key_hash)
+    .bind(account.updated_by)
+    .bind(account.updated_date)
+    .bind(account.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn soft_delete_service_account(
+    tx: &mut Transaction<'_, Postgres>,
+    account: &ServiceAccount,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            "UPDATE {} SET updated_by = $1, updated_date = $2, deleted_date = $3 WHERE id = $4",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(account.updated_by)
+    .bind(account.updated_date)
+    .bind(account.deleted_date)
+    .bind(account.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Set last_used_at unless it was set less than `interval_seconds` ago, so busy keys do not
+/// write on every request
+pub async fn touch_service_account(
+    conn: &mut PgConnection,
+    id: &Uuid,
+    now: &DateTime<FixedOffset>,
+    interval_seconds: i64,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {} SET last_used_at = $1
+            WHERE id = $2 AND (last_used_at IS NULL OR last_used_at < $1 - make_interval(secs => $3))"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(now)
+    .bind(id)
+    .bind(interval_seconds as f64)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
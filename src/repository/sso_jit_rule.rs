@@ -0,0 +1,69 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::sso_jit_rule::{SsoJitRule, TABLE_NAME};
+
+pub async fn get_sso_jit_rule_by_provider(
+    conn: &mut PgConnection,
+    provider_id: &Uuid,
+) -> anyhow::Result<Vec<SsoJitRule>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE provider_id = $1 ORDER BY created_date ASC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(provider_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_sso_jit_rule_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<SsoJitRule>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn create_sso_jit_rule(
+    tx: &mut Transaction<'_, Postgres>,
+    sso_jit_rule: &SsoJitRule,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, provider_id, claim, value, group_id, role_id,
+            created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(sso_jit_rule.id)
+    .bind(sso_jit_rule.provider_id)
+    .bind(&sso_jit_rule.claim)
+    .bind(&sso_jit_rule.value)
+    .bind(sso_jit_rule.group_id)
+    .bind(sso_jit_rule.role_id)
+    .bind(sso_jit_rule.created_date)
+    .bind(sso_jit_rule.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_sso_jit_rule(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+        utils::utc_now,
+    },
+    model::{
+        sso_provider::{SsoProvider, TABLE_NAME},
+        user::User,
+    },
+};
+
+pub async fn paginate_sso_provider(
+    conn: &mut PgConnection,
+    page: u32,
+    page_size: u32,
+    search: Option<String>,
+) -> anyhow::Result<(Vec<SsoProvider>, u32, u32)> {
+    let mut binds: Vec<SqlxBinds> = vec![];
+    let mut filters: Vec<String> = vec![];
+
+    if let Some(search) = search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("name ilike ${}", binds.len()));
+    }
+    filters.push("deleted_date IS NULL".to_string());
+
+    let limit = page_size;
+    let offset = (page - 1) * page_size;
+    let stmt = query_builder(
+        None,
+        TABLE_NAME,
+        &filters,
+        vec!["updated_date DESC".to_string()],
+        Some(limit),
+        Some(offset),
+    );
+    let stmt_count = query_builder(
+        Some("count(id)".to_string()),
+        TABLE_NAME,
+        &filters,
+        vec![],
+        None,
+        None,
+    );
+
+    let q = binds_query_as::<SsoProvider>(&stmt, binds.clone());
+    let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
+    let num_page = (count.0 as u32).div_ceil(page_size);
+    Ok((data, count.0 as u32, num_page))
+}
+
+pub async fn get_sso_provider_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<SsoProvider>> {
+    let binds: Vec<SqlxBinds> = vec![SqlxBinds::Uuid(*id)];
+    let filters: Vec<String> = vec!["id = $1".to_string(), "deleted_date IS NULL".to_string()];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<SsoProvider>(&stmt, binds);
+    Ok(q.fetch_optional(&mut *conn).await?)
+}
+
+pub async fn get_active_sso_provider_by_name(
+    conn: &mut PgConnection,
+    name: &str,
+) -> anyhow::Result<Option<SsoProvider>> {
+    let binds: Vec<SqlxBinds> = vec![SqlxBinds::String(name.to_string())];
+    let filters: Vec<String> = vec![
+        "name = $1".to_string(),
+        "is_active = true".to_string(),
+        "deleted_date IS NULL".to_string(),
+    ];
+    let stmt = query_builder(None, TABLE_NAME, &filters, vec![], None, None);
+    let q = binds_query_as::<SsoProvider>(&stmt, binds);
+    Ok(q.fetch_optional(&mut *conn).await?)
+}
+
+pub async fn create_sso_provider(
+    tx: &mut Transaction<'_, Postgres>,
+    sso_provider: &SsoProvider,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+    INSERT INTO {} (id, name, protocol, issuer, client_id, jwks_url, username_claim, email_claim,
+    is_active, jit_enabled, created_by, updated_by, created_date, updated_date, deleted_date,
+    client_secret, authorization_url, token_url, userinfo_url, scopes)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+    $20)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(sso_provider.id)
+    .bind(&sso_provider.name)
+    .bind(&sso_provider.protocol)
+    .bind(&sso_provider.issuer)
+    .bind(&sso_provider.client_id)
+    .bind(&sso_provider.jwks_url)
+    .bind(&sso_provider.username_claim)
+    .bind(&sso_provider.email_claim)
+    .bind(sso_provider.is_active)
+    .bind(sso_provider.jit_enabled)
+    .bind(sso_provider.created_by)
+    .bind(sso_provider.updated_by)
+    .bind(sso_provider.created_date)
+    .bind(sso_provider.updated_date)
+    .bind(sso_provider.deleted_date)
+    .bind(&sso_provider.client_secret)
+    .bind(&sso_provider.authorization_url)
+    .bind(&sso_provider.token_url)
+    .bind(&sso_provider.userinfo_url)
+    .bind(&sso_provider.scopes)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_sso_provider(
+    tx: &mut Transaction<'_, Postgres>,
+    sso_provider: &SsoProvider,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+        UPDATE {}
+        SET name = $1, protocol = $2, issuer = $3, client_id = $4, jwks_url = $5,
+        username_claim = $6, email_claim = $7, is_active = $8, jit_enabled = $9,
+        updated_by = $10, updated_date = $11, client_secret = $12, authorization_url = $13,
+        token_url = $14, userinfo_url = $15, scopes = $16
+        WHERE id = $17"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&sso_provider.name)
+    .bind(&sso_provider.protocol)
+    .bind(&sso_provider.issuer)
+    .bind(&sso_provider.client_id)
+    .bind(&sso_provider.jwks_url)
+    .bind(&sso_provider.username_claim)
+    .bind(&sso_provider.email_claim)
+    .bind(sso_provider.is_active)
+    .bind(sso_provider.jit_enabled)
+    .bind(sso_provider.updated_by)
+    .bind(sso_provider.updated_date)
+    .bind(&sso_provider.client_secret)
+    .bind(&sso_provider.authorization_url)
+    .bind(&sso_provider.token_url)
+    .bind(&sso_provider.userinfo_url)
+    .bind(&sso_provider.scopes)
+    .bind(sso_provider.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn soft_delete_sso_provider(
+    tx: &mut Transaction<'_, Postgres>,
+    sso_provider: &mut SsoProvider,
+    request_user: User,
+    now: Option<DateTime<FixedOffset>>,
+) -> anyhow::Result<()> {
+    let now = now.unwrap_or(utc_now());
+    sso_provider.updated_by = Some(request_user.id);
+    sso_provider.updated_date = Some(now);
+    sso_provider.deleted_date = Some(now);
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+    SET updated_by = $1, updated_date = $2, deleted_date = $3
+    WHERE id = $4"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(sso_provider.updated_by)
+    .bind(sso_provider.updated_date)
+    .bind(sso_provider.deleted_date)
+    .bind(sso_provider.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
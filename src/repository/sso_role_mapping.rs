@@ -0,0 +1,69 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::sso_role_mapping::{SsoRoleMapping, TABLE_NAME};
+
+pub async fn get_sso_role_mapping_by_provider(
+    conn: &mut PgConnection,
+    provider_id: &Uuid,
+) -> anyhow::Result<Vec<SsoRoleMapping>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE provider_id = $1 ORDER BY created_date ASC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(provider_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_sso_role_mapping_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<SsoRoleMapping>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn create_sso_role_mapping(
+    tx: &mut Transaction<'_, Postgres>,
+    sso_role_mapping: &SsoRoleMapping,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, provider_id, claim, value, group_id, role_id,
+            created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(sso_role_mapping.id)
+    .bind(sso_role_mapping.provider_id)
+    .bind(&sso_role_mapping.claim)
+    .bind(&sso_role_mapping.value)
+    .bind(sso_role_mapping.group_id)
+    .bind(sso_role_mapping.role_id)
+    .bind(sso_role_mapping.created_date)
+    .bind(sso_role_mapping.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_sso_role_mapping(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
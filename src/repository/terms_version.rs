@@ -0,0 +1,75 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::terms_version::{TermsVersion, TABLE_NAME};
+
+pub async fn create_terms_version(
+    tx: &mut Transaction<'_, Postgres>,
+    terms_version: &TermsVersion,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"
+    INSERT INTO {} (id, kind, version, url, content, published_by, published_date)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(terms_version.id)
+    .bind(&terms_version.kind)
+    .bind(&terms_version.version)
+    .bind(&terms_version.url)
+    .bind(&terms_version.content)
+    .bind(terms_version.published_by)
+    .bind(terms_version.published_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_terms_version_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<TermsVersion>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn get_terms_version_by_kind_version(
+    conn: &mut PgConnection,
+    kind: &str,
+    version: &str,
+) -> anyhow::Result<Option<TermsVersion>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE kind = $1 AND version = $2",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(kind)
+    .bind(version)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+/// Most recently published version of each kind
+pub async fn get_latest_terms_version(
+    conn: &mut PgConnection,
+) -> anyhow::Result<Vec<TermsVersion>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT DISTINCT ON (kind) * FROM {}
+            ORDER BY kind, published_date DESC, id DESC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut *conn)
+    .await?)
+}
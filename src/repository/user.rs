@@ -1,64 +1,213 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, FixedOffset};
-use sqlx::{Postgres, Transaction};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
+    core::{
+        pii::{decrypt_pii, decrypt_user_profile, encrypt_pii, encrypt_user_profile},
+        sqlx_utils::{
+            audited_from, audited_select, binds_query_as, query_builder, Sort, SqlxBinds,
+        },
+    },
     model::{
-        user::{User, TABLE_NAME},
+        audited::Audited,
+        user::{User, UserStatus, TABLE_NAME},
+        user_contact::TABLE_NAME as USER_CONTACT_TABLE_NAME,
+        user_data_export::TABLE_NAME as USER_DATA_EXPORT_TABLE_NAME,
+        user_device::TABLE_NAME as USER_DEVICE_TABLE_NAME,
         user_group_roles::{UserGroupRoles, TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME},
+        user_identity::TABLE_NAME as USER_IDENTITY_TABLE_NAME,
+        user_name_history::TABLE_NAME as USER_NAME_HISTORY_TABLE_NAME,
+        user_preference::TABLE_NAME as USER_PREFERENCE_TABLE_NAME,
         user_profile::{UserProfile, TABLE_NAME as USER_PROFILE_TABLE_NAME},
+        user_recovery_code::TABLE_NAME as USER_RECOVERY_CODE_TABLE_NAME,
     },
 };
 
+/// Columns get_all_user can be sorted by
+pub const SORT_COLUMNS: &[&str] = &[
+    "user_name",
+    "status",
+    "is_active",
+    "expires_at",
+    "created_date",
+    "updated_date",
+];
+
+/// Filters of get_all_user, unset fields match every user
+#[derive(Clone, Debug, Default)]
+pub struct UserFilter {
+    pub search: Option<String>,
+    /// Not yet expired, expiring on or before the date
+    pub expiring_before: Option<DateTime<FixedOffset>>,
+    pub is_active: Option<bool>,
+    pub is_2faenabled: Option<bool>,
+    /// Created on or after the date
+    pub created_from: Option<DateTime<FixedOffset>>,
+    /// Created on or before the date
+    pub created_to: Option<DateTime<FixedOffset>>,
+    /// Member of the group
+    pub group_id: Option<Uuid>,
+    /// Assigned the role, directly or within a group
+    pub role_id: Option<Uuid>,
+}
+
 pub async fn get_all_user(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: u32,
     page_size: u32,
-    search: Option<String>,
+    filter: UserFilter,
     exclude_soft_delete: Option<bool>,
-) -> anyhow::Result<(Vec<User>, u32, u32)> {
+    sort: Sort,
+) -> anyhow::Result<(Vec<Audited<User>>, u32, u32)> {
+    const ALIAS: &str = "u";
     let mut binds: Vec<SqlxBinds> = vec![];
     let mut filters: Vec<String> = vec![];
 
-    if search.is_some() {
-        binds.push(SqlxBinds::String(format!("%{}%", search.unwrap())));
-        filters.push(format!("user_name = ${}", binds.len()));
+    if let Some(search) = filter.search {
+        binds.push(SqlxBinds::String(format!("%{}%", search)));
+        filters.push(format!("{}.user_name = ${}", ALIAS, binds.len()));
+    }
+    if let Some(expiring_before) = filter.expiring_before {
+        binds.push(SqlxBinds::DateTimeFixedOffset(expiring_before));
+        filters.push(format!(
+            "{alias}.expires_at > now() AND {alias}.expires_at <= ${}",
+            binds.len(),
+            alias = ALIAS
+        ));
+    }
+    if let Some(is_active) = filter.is_active {
+        binds.push(SqlxBinds::Bool(is_active));
+        filters.push(format!("{}.is_active = ${}", ALIAS, binds.len()));
+    }
+    if let Some(is_2faenabled) = filter.is_2faenabled {
+        binds.push(SqlxBinds::Bool(is_2faenabled));
+        filters.push(format!("{}.is_2faenabled = ${}", ALIAS, binds.len()));
+    }
+    if let Some(created_from) = filter.created_from {
+        binds.push(SqlxBinds::DateTimeFixedOffset(created_from));
+        filters.push(format!("{}.created_date >= ${}", ALIAS, binds.len()));
+    }
+    if let Some(created_to) = filter.created_to {
+        binds.push(SqlxBinds::DateTimeFixedOffset(created_to));
+        filters.push(format!("{}.created_date <= ${}", ALIAS, binds.len()));
+    }
+    if let Some(group_id) = filter.group_id {
+        binds.push(SqlxBinds::Uuid(group_id));
+        filters.push(format!(
+            "{}.id IN (SELECT user_id FROM {} WHERE group_id = ${})",
+            ALIAS,
+            USER_GROUP_ROLES_TABLE_NAME,
+            binds.len()
+        ));
+    }
+    if let Some(role_id) = filter.role_id {
+        binds.push(SqlxBinds::Uuid(role_id));
+        filters.push(format!(
+            "{}.id IN (SELECT user_id FROM {} WHERE role_id = ${})",
+            ALIAS,
+            USER_GROUP_ROLES_TABLE_NAME,
+            binds.len()
+        ));
     }
     let exclude_soft_delete = exclude_soft_delete.unwrap_or(true);
     if exclude_soft_delete {
-        filters.push("deleted_date IS NULL".to_string());
+        filters.push(format!("{}.deleted_date IS NULL", ALIAS));
     }
 
     let limit = page_size;
     let offset = (page - 1) * page_size;
     let stmt = query_builder(
-        None,
-        TABLE_NAME,
+        Some(audited_select(ALIAS)),
+        &audited_from(TABLE_NAME, ALIAS),
         &filters,
-        vec!["updated_date DESC".to_string()],
+        sort.order_by_aliased(ALIAS, SORT_COLUMNS, "updated_date DESC")?,
         Some(limit),
         Some(offset),
     );
     let stmt_count = query_builder(
-        Some("count(id)".to_string()),
-        TABLE_NAME,
+        Some(format!("count({}.id)", ALIAS)),
+        &format!("{} AS {}", TABLE_NAME, ALIAS),
         &filters,
         vec![],
         None,
         None,
     );
 
-    let q = binds_query_as::<User>(&stmt, binds.clone());
+    let q = binds_query_as::<Audited<User>>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = (count.0 as u32).div_ceil(page_size);
     Ok((data, count.0 as u32, num_page as u32))
 }
 
+/// Users not soft deleted among the ids, in a single query
+pub async fn get_users_by_ids(conn: &mut PgConnection, ids: &[Uuid]) -> anyhow::Result<Vec<User>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE id = ANY($1) AND deleted_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(ids)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// Decrypted profiles of the users, in a single query
+pub async fn get_user_profiles_by_user_ids(
+    conn: &mut PgConnection,
+    user_ids: &[Uuid],
+) -> anyhow::Result<Vec<UserProfile>> {
+    if user_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let user_profiles: Vec<UserProfile> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = ANY($1)",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+    user_profiles
+        .into_iter()
+        .map(decrypt_user_profile)
+        .collect()
+}
+
+/// Users referenced by a page of rows, e.g. their created_by and updated_by, loaded
+/// together instead of one get_user_by_id per row
+pub struct UserLookup(HashMap<Uuid, User>);
+
+impl UserLookup {
+    pub async fn load(
+        conn: &mut PgConnection,
+        ids: impl IntoIterator<Item = Option<Uuid>>,
+    ) -> anyhow::Result<Self> {
+        let mut ids: Vec<Uuid> = ids.into_iter().flatten().collect();
+        ids.sort();
+        ids.dedup();
+        let users = get_users_by_ids(conn, &ids).await?;
+        Ok(Self(users.into_iter().map(|x| (x.id, x)).collect()))
+    }
+
+    pub fn get(&self, id: Option<Uuid>) -> Option<&User> {
+        self.0.get(&id?)
+    }
+}
+
 pub async fn get_user_by_id(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     id: &Uuid,
     exclude_soft_delete: Option<bool>,
 ) -> anyhow::Result<(Option<User>, Option<UserProfile>)> {
@@ -80,13 +229,16 @@ pub async fn get_user_by_id(
     );
     let user_query = binds_query_as::<User>(&user_stmt, binds.clone());
     let user_profile_query = binds_query_as::<UserProfile>(&user_profile_stmt, binds);
-    let user = user_query.fetch_optional(&mut **tx).await?;
-    let user_profile = user_profile_query.fetch_optional(&mut **tx).await?;
+    let user = user_query.fetch_optional(&mut *conn).await?;
+    let user_profile = match user_profile_query.fetch_optional(&mut *conn).await? {
+        Some(val) => Some(decrypt_user_profile(val)?),
+        None => None,
+    };
     Ok((user, user_profile))
 }
 
 pub async fn get_user_by_username(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     username: &str,
 ) -> anyhow::Result<(Option<User>, Option<UserProfile>)> {
     let res_user: Option<User> = sqlx::query_as(
@@ -96,7 +248,7 @@ pub async fn get_user_by_username(
         "#,
     )
     .bind(username)
-    .fetch_optional(&mut **tx)
+    .fetch_optional(&mut *conn)
     .await?;
     if res_user.is_none() {
         return Ok((None, None));
@@ -109,11 +261,35 @@ pub async fn get_user_by_username(
         "#,
     )
     .bind(res_user.clone().unwrap().id)
-    .fetch_optional(&mut **tx)
+    .fetch_optional(&mut *conn)
     .await?;
+    let res_user_profile = match res_user_profile {
+        Some(val) => Some(decrypt_user_profile(val)?),
+        None => None,
+    };
     Ok((res_user, res_user_profile))
 }
 
+/// Any user row holds `user_name`, soft deleted included since the column is unique
+pub async fn is_user_name_taken(
+    conn: &mut PgConnection,
+    user_name: &str,
+    exclude_id: Option<&Uuid>,
+) -> anyhow::Result<bool> {
+    let res: (bool,) = sqlx::query_as(
+        format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE user_name = $1 AND ($2::uuid IS NULL OR id <> $2))",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_name)
+    .bind(exclude_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(res.0)
+}
+
 pub async fn create_user(
     tx: &mut Transaction<'_, Postgres>,
     user: &User,
@@ -121,28 +297,31 @@ pub async fn create_user(
 ) -> anyhow::Result<()> {
     sqlx::query(
         format!(r#"
-        INSERT INTO {} (id, user_name, password, is_active, is_2faenabled, created_by, updated_by, created_date, updated_date, deleted_date)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        INSERT INTO {} (id, user_name, password, is_active, is_2faenabled, created_by, updated_by, created_date, updated_date, deleted_date, status, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
         "#, TABLE_NAME).as_str(),
     )
     .bind(user.id)
     .bind(&user.user_name)
     .bind(&user.password)
-    .bind(user.is_active)
+    .bind(user.is_active())
     .bind(user.is_2faenabled)
     .bind(user.created_by)
     .bind(user.updated_by)
     .bind(user.created_date)
     .bind(user.updated_date)
     .bind(user.deleted_date)
+    .bind(user.status)
+    .bind(user.expires_at)
     .execute(&mut **tx)
     .await?;
 
+    let user_profile = encrypt_user_profile(user_profile)?;
     sqlx::query(
         format!(
             r#"
-        INSERT INTO {} (id, user_id, first_name, last_name, address, email)
-        VALUES ($1, $2, $3, $4, $5, $6)
+        INSERT INTO {} (id, user_id, first_name, last_name, address, email, locale, timezone)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         "#,
             USER_PROFILE_TABLE_NAME
         )
@@ -154,6 +333,8 @@ pub async fn create_user(
     .bind(&user_profile.last_name)
     .bind(&user_profile.address)
     .bind(&user_profile.email)
+    .bind(&user_profile.locale)
+    .bind(&user_profile.timezone)
     .execute(&mut **tx)
     .await?;
     Ok(())
@@ -172,26 +353,43 @@ pub async fn update_user(
         format!(
             r#"UPDATE {} 
             SET user_name = $1, password = $2, is_active = $3, is_2faenabled = $4, updated_by = $5, 
-            updated_date = $6
-            WHERE id = $7"#,
+            updated_date = $6, status = $7, expires_at = $8
+            WHERE id = $9"#,
             TABLE_NAME
         )
         .as_str(),
     )
     .bind(&user.user_name)
     .bind(&user.password)
-    .bind(user.is_active)
+    .bind(user.is_active())
     .bind(user.is_2faenabled)
     .bind(request_user.id)
     .bind(now)
+    .bind(user.status)
+    .bind(user.expires_at)
     .bind(user.id)
     .execute(&mut **tx)
     .await?;
+    // a changed email has to be verified again, stored emails are encrypted with a random
+    // nonce so they are compared decrypted
+    let current_email: Option<Option<String>> = sqlx::query_scalar(
+        format!(
+            "SELECT email FROM {} WHERE user_id = $1",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user.id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    let email_changed = decrypt_pii(current_email.flatten())? != user_profile.email;
+    let user_profile = encrypt_user_profile(user_profile)?;
     sqlx::query(
         format!(
             r#"UPDATE {}
-            SET first_name = $1, last_name = $2, address = $3, email = $4
-            WHERE user_id = $5"#,
+            SET first_name = $1, last_name = $2, address = $3, email = $4, locale = $5,
+            timezone = $6, email_verified_at = CASE WHEN $7 THEN NULL ELSE email_verified_at END
+            WHERE user_id = $8"#,
             USER_PROFILE_TABLE_NAME
         )
         .as_str(),
@@ -200,12 +398,150 @@ pub async fn update_user(
     .bind(&user_profile.last_name)
     .bind(&user_profile.address)
     .bind(&user_profile.email)
+    .bind(&user_profile.locale)
+    .bind(&user_profile.timezone)
+    .bind(email_changed)
+    .bind(user.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Only after the new address is confirmed, see core::email_change. Confirming from the
+/// new address verifies it
+pub async fn update_user_email(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &mut User,
+    email: &str,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    user.updated_date = Some(*now);
+    sqlx::query(format!("UPDATE {} SET updated_date = $1 WHERE id = $2", TABLE_NAME).as_str())
+        .bind(now)
+        .bind(user.id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        format!(
+            "UPDATE {} SET email = $1, email_verified_at = $2 WHERE user_id = $3",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(encrypt_pii(&Some(email.to_string()))?)
+    .bind(now)
+    .bind(user.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Mark the current email of the user as verified, see core::email_verification
+pub async fn verify_user_email(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            "UPDATE {} SET email_verified_at = $1 WHERE user_id = $2",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(now)
+    .bind(user_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Password hash chosen by the user, see /auth/change-password/
+pub async fn update_user_password(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &mut User,
+    password: &str,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    user.password = password.to_string();
+    user.updated_by = Some(user.id);
+    user.updated_date = Some(*now);
+    sqlx::query(
+        format!(
+            "UPDATE {} SET password = $1, updated_by = $2, updated_date = $3 WHERE id = $4",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&user.password)
+    .bind(user.updated_by)
+    .bind(now)
+    .bind(user.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Store `status` and the matching `is_active`, transitions are checked by core::lifecycle
+pub async fn update_user_status(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &User,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+            SET status = $1, is_active = $2, updated_by = $3, updated_date = $4
+            WHERE id = $5"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user.status)
+    .bind(user.is_active())
+    .bind(user.updated_by)
+    .bind(user.updated_date)
     .bind(user.id)
     .execute(&mut **tx)
     .await?;
     Ok(())
 }
 
+/// Active users whose expires_at has passed
+pub async fn get_expired_users(
+    conn: &mut PgConnection,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<Vec<User>> {
+    let data = sqlx::query_as::<_, User>(
+        format!(
+            r#"SELECT * FROM {}
+            WHERE status = $1 AND expires_at <= $2 AND deleted_date IS NULL
+            ORDER BY expires_at"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(UserStatus::Active)
+    .bind(now)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(data)
+}
+
+/// Ids of active, not deleted users, the ones able to log in
+pub async fn get_active_user_ids(conn: &mut PgConnection) -> anyhow::Result<Vec<Uuid>> {
+    let data: Vec<(Uuid,)> = sqlx::query_as(
+        format!(
+            "SELECT id FROM {} WHERE status = $1 AND deleted_date IS NULL ORDER BY id",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(UserStatus::Active)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(data.into_iter().map(|x| x.0).collect())
+}
+
 pub async fn soft_delete_user(
     tx: &mut Transaction<'_, Postgres>,
     user: &mut User,
@@ -230,8 +566,75 @@ pub async fn soft_delete_user(
     Ok(())
 }
 
-pub async fn get_user_group_roles_by_user(
+/// Scrub personal data of a user while keeping the row, so created_by / updated_by
+/// references elsewhere stay valid. `password` should be a hash nobody knows the
+/// plaintext of. Linked external identities, generated data exports and previous user
+/// names are removed.
+pub async fn anonymize_user(
     tx: &mut Transaction<'_, Postgres>,
+    user: &mut User,
+    password: &str,
+    request_user: &User,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    user.user_name = format!("anonymized-{}", user.id.simple());
+    user.password = password.to_string();
+    user.status = UserStatus::Deprovisioned;
+    user.is_2faenabled = Some(false);
+    user.updated_by = Some(request_user.id);
+    user.updated_date = Some(*now);
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+            SET user_name = $1, password = $2, is_active = $3, is_2faenabled = $4,
+            updated_by = $5, updated_date = $6, status = $7
+            WHERE id = $8"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&user.user_name)
+    .bind(&user.password)
+    .bind(user.is_active())
+    .bind(user.is_2faenabled)
+    .bind(request_user.id)
+    .bind(now)
+    .bind(user.status)
+    .bind(user.id)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+            SET first_name = NULL, last_name = NULL, address = NULL, email = NULL,
+            email_verified_at = NULL
+            WHERE user_id = $1"#,
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user.id)
+    .execute(&mut **tx)
+    .await?;
+    for table_name in [
+        USER_IDENTITY_TABLE_NAME,
+        USER_DATA_EXPORT_TABLE_NAME,
+        USER_NAME_HISTORY_TABLE_NAME,
+        USER_CONTACT_TABLE_NAME,
+        USER_PREFERENCE_TABLE_NAME,
+        USER_DEVICE_TABLE_NAME,
+        USER_RECOVERY_CODE_TABLE_NAME,
+    ] {
+        sqlx::query(format!("DELETE FROM {} WHERE user_id = $1", table_name).as_str())
+            .bind(user.id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn get_user_group_roles_by_user(
+    conn: &mut PgConnection,
     user: &User,
 ) -> anyhow::Result<Vec<UserGroupRoles>> {
     Ok(sqlx::query_as(
@@ -242,7 +645,27 @@ pub async fn get_user_group_roles_by_user(
         .as_str(),
     )
     .bind(user.id)
-    .fetch_all(&mut **tx)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// Every group role of the users, in a single query
+pub async fn get_user_group_roles_by_user_ids(
+    conn: &mut PgConnection,
+    user_ids: &[Uuid],
+) -> anyhow::Result<Vec<UserGroupRoles>> {
+    if user_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = ANY($1)",
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_ids)
+    .fetch_all(&mut *conn)
     .await?)
 }
 
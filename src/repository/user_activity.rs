@@ -0,0 +1,157 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::{
+    user::{UserStatus, TABLE_NAME as USER_TABLE_NAME},
+    user_activity::{DormantCandidate, UserActivity, TABLE_NAME},
+};
+
+/// Store the login date and clear pending dormant warning or flag
+pub async fn record_user_login(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (user_id, last_login_date, updated_date)
+            VALUES ($1, $2, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET last_login_date = $2, dormant_warning_date = NULL, dormant_flagged_date = NULL,
+            updated_date = $2"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_user_activity_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Option<UserActivity>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE user_id = $1", TABLE_NAME).as_str())
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn set_user_dormant_exemption(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    exempt: bool,
+    reason: Option<String>,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserActivity> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"INSERT INTO {} (user_id, dormant_exempt, dormant_exempt_reason, updated_date)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id) DO UPDATE
+            SET dormant_exempt = $2, dormant_exempt_reason = $3, dormant_warning_date = NULL,
+            dormant_flagged_date = NULL, updated_date = $4
+            RETURNING *"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(exempt)
+    .bind(reason)
+    .bind(now)
+    .fetch_one(&mut **tx)
+    .await?)
+}
+
+pub async fn set_user_dormant_warning(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (user_id, dormant_warning_date, updated_date)
+            VALUES ($1, $2, $2)
+            ON CONFLICT (user_id) DO UPDATE SET dormant_warning_date = $2, updated_date = $2"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_user_dormant_flagged(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (user_id, dormant_flagged_date, updated_date)
+            VALUES ($1, $2, $2)
+            ON CONFLICT (user_id) DO UPDATE SET dormant_flagged_date = $2, updated_date = $2"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Active, non exempt users whose last activity is before `inactive_since`
+pub async fn get_dormant_candidates(
+    conn: &mut PgConnection,
+    inactive_since: &DateTime<FixedOffset>,
+) -> anyhow::Result<Vec<DormantCandidate>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT u.id AS user_id, u.user_name,
+            COALESCE(a.last_login_date, u.created_date) AS last_activity_date,
+            a.dormant_warning_date, a.dormant_flagged_date
+            FROM {} u
+            LEFT JOIN {} a ON a.user_id = u.id
+            WHERE u.status = $1 AND u.deleted_date IS NULL
+            AND a.dormant_exempt IS NOT TRUE
+            AND COALESCE(a.last_login_date, u.created_date) < $2
+            ORDER BY last_activity_date ASC"#,
+            USER_TABLE_NAME, TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(UserStatus::Active)
+    .bind(inactive_since)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// Users warned, flagged or exempt, most recent first
+pub async fn get_dormant_user_activity(
+    conn: &mut PgConnection,
+) -> anyhow::Result<Vec<UserActivity>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {}
+            WHERE dormant_warning_date IS NOT NULL OR dormant_flagged_date IS NOT NULL
+            OR dormant_exempt
+            ORDER BY updated_date DESC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .fetch_all(&mut *conn)
+    .await?)
+}
@@ -0,0 +1,40 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::user_anonymization::{UserAnonymization, TABLE_NAME};
+
+pub async fn create_user_anonymization(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    anonymized_by: Option<Uuid>,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserAnonymization> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"INSERT INTO {} (id, user_id, anonymized_by, created_date)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(user_id)
+    .bind(anonymized_by)
+    .bind(now)
+    .fetch_one(&mut **tx)
+    .await?)
+}
+
+pub async fn get_user_anonymization_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Option<UserAnonymization>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE user_id = $1", TABLE_NAME).as_str())
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::{
+    consent_type::ConsentType,
+    user_consent::{UserConsent, TABLE_NAME},
+};
+
+pub async fn create_user_consent(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    consent_type: &ConsentType,
+    accepted: bool,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserConsent> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"INSERT INTO {} (id, user_id, consent_type_id, version, accepted, created_date)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(user_id)
+    .bind(consent_type.id)
+    .bind(consent_type.version)
+    .bind(accepted)
+    .bind(now)
+    .fetch_one(&mut **tx)
+    .await?)
+}
+
+/// Latest record of each consent type the user decided on
+pub async fn get_latest_user_consent_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserConsent>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT DISTINCT ON (consent_type_id) * FROM {}
+            WHERE user_id = $1
+            ORDER BY consent_type_id, created_date DESC, id DESC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// Every accept and withdraw of the user, oldest first
+pub async fn get_user_consent_history_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserConsent>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE user_id = $1
+            ORDER BY created_date ASC, id ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
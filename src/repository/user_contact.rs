@@ -0,0 +1,130 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::pii::{decrypt_pii, encrypt_pii},
+    model::user_contact::{UserContact, TABLE_NAME},
+};
+
+fn decrypt_user_contact(mut contact: UserContact) -> anyhow::Result<UserContact> {
+    contact.value = decrypt_pii(Some(contact.value))?.unwrap_or_default();
+    Ok(contact)
+}
+
+/// Contacts of a user, primary first within each kind
+pub async fn get_user_contacts_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserContact>> {
+    let data: Vec<UserContact> = sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE user_id = $1
+            ORDER BY kind ASC, is_primary DESC, created_date ASC, id ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    data.into_iter().map(decrypt_user_contact).collect()
+}
+
+pub async fn get_user_contact_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<UserContact>> {
+    let data: Option<UserContact> =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    data.map(decrypt_user_contact).transpose()
+}
+
+/// Drop the primary flag of the other contacts of the same kind
+async fn clear_primary_user_contact(
+    tx: &mut Transaction<'_, Postgres>,
+    contact: &UserContact,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {} SET is_primary = false, updated_date = $1
+            WHERE user_id = $2 AND kind = $3 AND id <> $4 AND is_primary"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(contact.updated_date)
+    .bind(contact.user_id)
+    .bind(&contact.kind)
+    .bind(contact.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn create_user_contact(
+    tx: &mut Transaction<'_, Postgres>,
+    contact: &UserContact,
+) -> anyhow::Result<()> {
+    if contact.is_primary {
+        clear_primary_user_contact(tx, contact).await?;
+    }
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, kind, value, is_primary, verified_date, created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(contact.id)
+    .bind(contact.user_id)
+    .bind(&contact.kind)
+    .bind(encrypt_pii(&Some(contact.value.clone()))?)
+    .bind(contact.is_primary)
+    .bind(contact.verified_date)
+    .bind(contact.created_date)
+    .bind(contact.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_user_contact(
+    tx: &mut Transaction<'_, Postgres>,
+    contact: &UserContact,
+) -> anyhow::Result<()> {
+    if contact.is_primary {
+        clear_primary_user_contact(tx, contact).await?;
+    }
+    sqlx::query(
+        format!(
+            r#"UPDATE {} SET kind = $1, value = $2, is_primary = $3, verified_date = $4, updated_date = $5
+            WHERE id = $6"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&contact.kind)
+    .bind(encrypt_pii(&Some(contact.value.clone()))?)
+    .bind(contact.is_primary)
+    .bind(contact.verified_date)
+    .bind(contact.updated_date)
+    .bind(contact.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_user_contact(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::{
+    group::TABLE_NAME as GROUP_TABLE_NAME,
+    permission::TABLE_NAME as PERMISSION_TABLE_NAME,
+    permission_attribute::TABLE_NAME as PERMISSION_ATTRIBUTE_TABLE_NAME,
+    role::TABLE_NAME as ROLE_TABLE_NAME,
+    scim_target::TABLE_NAME as SCIM_TARGET_TABLE_NAME,
+    scim_target_user::TABLE_NAME as SCIM_TARGET_USER_TABLE_NAME,
+    user_data_export::{UserDataExport, STATUS_PENDING, TABLE_NAME},
+    user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+    user_identity::{UserIdentity, TABLE_NAME as USER_IDENTITY_TABLE_NAME},
+    user_permission::TABLE_NAME as USER_PERMISSION_TABLE_NAME,
+};
+
+pub async fn create_user_data_export(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    requested_by: Option<Uuid>,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<UserDataExport> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"INSERT INTO {} (id, user_id, status, payload, last_error, requested_by,
+            created_date, completed_date)
+            VALUES ($1, $2, $3, NULL, NULL, $4, $5, NULL)
+            RETURNING *"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(user_id)
+    .bind(STATUS_PENDING)
+    .bind(requested_by)
+    .bind(now)
+    .fetch_one(&mut **tx)
+    .await?)
+}
+
+pub async fn get_user_data_export_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<UserDataExport>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+/// Previous export requests of a user, payload excluded
+pub async fn get_user_data_export_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserDataExport>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT id, user_id, status, NULL AS payload, last_error, requested_by,
+            created_date, completed_date
+            FROM {} WHERE user_id = $1 ORDER BY created_date ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// Lock a batch of pending exports, rows locked by another worker are skipped
+pub async fn get_pending_user_data_export(
+    tx: &mut Transaction<'_, Postgres>,
+    limit: u32,
+) -> anyhow::Result<Vec<UserDataExport>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {}
+            WHERE status = $1
+            ORDER BY created_date ASC
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(STATUS_PENDING)
+    .bind(limit as i64)
+    .fetch_all(&mut **tx)
+    .await?)
+}
+
+pub async fn update_user_data_export(
+    tx: &mut Transaction<'_, Postgres>,
+    export: &UserDataExport,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"UPDATE {}
+            SET status = $1, payload = $2, last_error = $3, completed_date = $4
+            WHERE id = $5"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&export.status)
+    .bind(&export.payload)
+    .bind(&export.last_error)
+    .bind(export.completed_date)
+    .bind(export.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// (group name, role name) of every assignment, either side may be empty
+pub async fn get_group_role_names_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<(Option<String>, Option<String>)>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT g.group_name, r.role_name FROM {} ugr
+            LEFT JOIN {} g ON g.id = ugr.group_id
+            LEFT JOIN {} r ON r.id = ugr.role_id
+            WHERE ugr.user_id = $1
+            ORDER BY g.group_name, r.role_name"#,
+            USER_GROUP_ROLES_TABLE_NAME, GROUP_TABLE_NAME, ROLE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// (permission name, attribute name) granted directly to the user
+pub async fn get_permission_names_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<(String, String)>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT p.permission_name, pa.name FROM {} up
+            JOIN {} p ON p.id = up.permission_id
+            JOIN {} pa ON pa.id = up.attribute_id AND pa.deleted_date IS NULL
+            WHERE up.user_id = $1
+            ORDER BY p.permission_name, pa.name"#,
+            USER_PERMISSION_TABLE_NAME, PERMISSION_TABLE_NAME, PERMISSION_ATTRIBUTE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_user_identity_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserIdentity>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 ORDER BY created_date ASC",
+            USER_IDENTITY_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// (scim target name, external id) of downstream apps the user was provisioned to
+pub async fn get_scim_links_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<(String, String)>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT t.name, tu.external_id FROM {} tu
+            JOIN {} t ON t.id = tu.target_id
+            WHERE tu.user_id = $1
+            ORDER BY t.name"#,
+            SCIM_TARGET_USER_TABLE_NAME, SCIM_TARGET_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
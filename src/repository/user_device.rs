@@ -0,0 +1,72 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::user_device::{UserDevice, TABLE_NAME};
+
+pub async fn get_user_devices_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserDevice>> {
+    let data: Vec<UserDevice> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 ORDER BY created_date ASC, id ASC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(data)
+}
+
+pub async fn get_user_device_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<UserDevice>> {
+    let data: Option<UserDevice> =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    Ok(data)
+}
+
+/// Insert the device, or move an already registered token to the user
+pub async fn upsert_user_device(
+    tx: &mut Transaction<'_, Postgres>,
+    device: &UserDevice,
+) -> anyhow::Result<UserDevice> {
+    let data: UserDevice = sqlx::query_as(
+        format!(
+            r#"INSERT INTO {} (id, user_id, platform, "token", "name", created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (platform, "token") DO UPDATE SET user_id = EXCLUDED.user_id,
+            "name" = EXCLUDED."name", updated_date = EXCLUDED.updated_date
+            RETURNING *"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(device.id)
+    .bind(device.user_id)
+    .bind(&device.platform)
+    .bind(&device.token)
+    .bind(&device.name)
+    .bind(device.created_date)
+    .bind(device.updated_date)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(data)
+}
+
+pub async fn delete_user_device(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use sqlx::{Postgres, Transaction};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
 
 use crate::model::{
     group::Group,
@@ -8,7 +9,7 @@ use crate::model::{
 };
 
 pub async fn get_detail_user_group_roles(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     user: &User,
     role: &Role,
     group: &Group,
@@ -24,7 +25,7 @@ pub async fn get_detail_user_group_roles(
     .bind(user.id)
     .bind(role.id)
     .bind(group.id)
-    .fetch_optional(&mut **tx)
+    .fetch_optional(&mut *conn)
     .await?)
 }
 
@@ -70,3 +71,67 @@ pub async fn delete_user_group_roles(
     .await?;
     Ok(())
 }
+
+/// Group memberships of a user that are not tied to any role
+pub async fn get_user_group_memberships(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserGroupRoles>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 AND group_id IS NOT NULL AND role_id IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn delete_user_group_roles_by_id(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Users assigned the role, within a group or not
+pub async fn get_user_ids_by_role(
+    conn: &mut PgConnection,
+    role_id: &Uuid,
+) -> anyhow::Result<Vec<Uuid>> {
+    let res: Vec<(Uuid,)> = sqlx::query_as(
+        format!(
+            "SELECT DISTINCT user_id FROM {} WHERE role_id = $1 AND user_id IS NOT NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(role_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(res.into_iter().map(|x| x.0).collect())
+}
+
+/// Members of the group, with or without a role in it
+pub async fn get_user_ids_by_group(
+    conn: &mut PgConnection,
+    group_id: &Uuid,
+) -> anyhow::Result<Vec<Uuid>> {
+    let res: Vec<(Uuid,)> = sqlx::query_as(
+        format!(
+            "SELECT DISTINCT user_id FROM {} WHERE group_id = $1 AND user_id IS NOT NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(group_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(res.into_iter().map(|x| x.0).collect())
+}
@@ -0,0 +1,126 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::sso::SSO_IDENTITY_PREFIX,
+    model::user_identity::{UserIdentity, TABLE_NAME},
+};
+
+pub async fn get_user_identity(
+    conn: &mut PgConnection,
+    provider: &str,
+    subject: &str,
+) -> anyhow::Result<Option<UserIdentity>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE provider = $1 AND subject = $2",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(provider)
+    .bind(subject)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+pub async fn get_user_identity_by_id(
+    conn: &mut PgConnection,
+    id: &Uuid,
+) -> anyhow::Result<Option<UserIdentity>> {
+    Ok(
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?,
+    )
+}
+
+pub async fn get_user_identity_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    provider: &str,
+) -> anyhow::Result<Option<UserIdentity>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 AND provider = $2 LIMIT 1",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(provider)
+    .fetch_optional(&mut *conn)
+    .await?)
+}
+
+/// Identities linked by the user through an sso provider, directory sync ones left out
+pub async fn get_sso_user_identity_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserIdentity>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE user_id = $1 AND directory_source_id IS NULL
+            AND provider LIKE $2 ORDER BY created_date ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(format!("{}%", SSO_IDENTITY_PREFIX))
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn get_user_identity_by_directory_source(
+    conn: &mut PgConnection,
+    directory_source_id: &Uuid,
+) -> anyhow::Result<Vec<UserIdentity>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE directory_source_id = $1",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(directory_source_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+pub async fn create_user_identity(
+    tx: &mut Transaction<'_, Postgres>,
+    user_identity: &UserIdentity,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, provider, subject, directory_source_id,
+            created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_identity.id)
+    .bind(user_identity.user_id)
+    .bind(&user_identity.provider)
+    .bind(&user_identity.subject)
+    .bind(user_identity.directory_source_id)
+    .bind(user_identity.created_date)
+    .bind(user_identity.updated_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_user_identity(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE id = $1", TABLE_NAME).as_str())
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::user_name_history::{UserNameHistory, TABLE_NAME};
+
+pub async fn create_user_name_history(
+    tx: &mut Transaction<'_, Postgres>,
+    history: &UserNameHistory,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, user_name, changed_by, created_date)
+            VALUES ($1, $2, $3, $4, $5)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(history.id)
+    .bind(history.user_id)
+    .bind(&history.user_name)
+    .bind(history.changed_by)
+    .bind(history.created_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Previous user names of a user, newest first
+pub async fn get_user_name_history_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserNameHistory>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 ORDER BY created_date DESC, id DESC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
+/// Whether another user gave up `user_name` after `since`
+pub async fn is_user_name_reserved(
+    conn: &mut PgConnection,
+    user_name: &str,
+    user_id: Option<&Uuid>,
+    since: &DateTime<FixedOffset>,
+) -> anyhow::Result<bool> {
+    let res: (bool,) = sqlx::query_as(
+        format!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM {} WHERE user_name = $1 AND created_date > $2
+                AND ($3::uuid IS NULL OR user_id <> $3)
+            )"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_name)
+    .bind(since)
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(res.0)
+}
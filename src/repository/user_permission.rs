@@ -1,16 +1,31 @@
-use sqlx::{Postgres, Transaction};
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     core::sqlx_utils::{binds_query_as, query_builder, SqlxBinds},
-    model::user_permission::{UserPermission, TABLE_NAME},
+    model::{
+        group::TABLE_NAME as GROUP_TABLE_NAME,
+        group_permission::TABLE_NAME as GROUP_PERMISSION_TABLE_NAME,
+        permission::TABLE_NAME as PERMISSION_TABLE_NAME,
+        permission_attribute::TABLE_NAME as PERMISSION_ATTRIBUTE_TABLE_NAME,
+        role::TABLE_NAME as ROLE_TABLE_NAME,
+        role_permission::TABLE_NAME as ROLE_PERMISSION_TABLE_NAME,
+        user::TABLE_NAME as USER_TABLE_NAME,
+        user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+        user_permission::{
+            PermissionHolder, UserPermission, GRANT_VIA_DIRECT, GRANT_VIA_GROUP, GRANT_VIA_ROLE,
+            TABLE_NAME,
+        },
+    },
 };
 
 pub async fn get_all_user_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     page: Option<u32>,
     page_size: Option<u32>,
     user_id: &Uuid,
+    expiring_before: Option<DateTime<FixedOffset>>,
     all: Option<bool>,
 ) -> anyhow::Result<(Vec<UserPermission>, u32, u32)> {
     let page = page.unwrap_or(1);
@@ -21,6 +36,14 @@ pub async fn get_all_user_permission(
 
     binds.push(SqlxBinds::Uuid(*user_id));
     filters.push(format!("user_id = ${}", binds.len()));
+    // not expired yet but will be by then
+    if let Some(expiring_before) = expiring_before {
+        binds.push(SqlxBinds::DateTimeFixedOffset(expiring_before));
+        filters.push(format!(
+            "expires_at > now() AND expires_at <= ${}",
+            binds.len()
+        ));
+    }
 
     let limit = match all {
         true => None,
@@ -50,8 +73,8 @@ pub async fn get_all_user_permission(
 
     let q = binds_query_as::<UserPermission>(&stmt, binds.clone());
     let q_count = binds_query_as::<(i64,)>(&stmt_count, binds);
-    let data = q.fetch_all(&mut **tx).await?;
-    let count = q_count.fetch_one(&mut **tx).await?;
+    let data = q.fetch_all(&mut *conn).await?;
+    let count = q_count.fetch_one(&mut *conn).await?;
     let num_page = match all {
         true => 0,
         false => (count.0 as u32).div_ceil(page_size),
@@ -59,8 +82,28 @@ pub async fn get_all_user_permission(
     Ok((data, count.0 as u32, num_page as u32))
 }
 
+/// Every direct permission of the users, in a single query
+pub async fn get_user_permissions_by_user_ids(
+    conn: &mut PgConnection,
+    user_ids: &[Uuid],
+) -> anyhow::Result<Vec<UserPermission>> {
+    if user_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = ANY($1) ORDER BY updated_date DESC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_ids)
+    .fetch_all(&mut *conn)
+    .await?)
+}
+
 pub async fn get_detail_user_permission(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     user_id: &Uuid,
     permission_id: &Uuid,
     attribute_id: &Uuid,
@@ -75,7 +118,7 @@ pub async fn get_detail_user_permission(
     .bind(user_id)
     .bind(permission_id)
     .bind(attribute_id)
-    .fetch_optional(&mut **tx)
+    .fetch_optional(&mut *conn)
     .await?)
 }
 
@@ -83,7 +126,7 @@ pub async fn create_user_permission(
     tx: &mut Transaction<'_, Postgres>,
     user_permission: &UserPermission,
 ) -> anyhow::Result<()> {
-    sqlx::query(format!("INSERT INTO {} (user_id, permission_id, attribute_id, created_by, updated_by, created_date, updated_date) VALUES ($1, $2, $3, $4, $5, $6, $7)", TABLE_NAME).as_str())
+    sqlx::query(format!("INSERT INTO {} (user_id, permission_id, attribute_id, created_by, updated_by, created_date, updated_date, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)", TABLE_NAME).as_str())
         .bind(user_permission.user_id)
         .bind(user_permission.permission_id)
         .bind(user_permission.attribute_id)
@@ -91,6 +134,7 @@ pub async fn create_user_permission(
         .bind(user_permission.updated_by)
         .bind(user_permission.created_date)
         .bind(user_permission.updated_date)
+        .bind(user_permission.expires_at)
         .execute(&mut **tx)
         .await?;
     Ok(())
@@ -114,3 +158,171 @@ pub async fn delete_user_permission(
     .await?;
     Ok(())
 }
+
+/// Whether the user holds the permission attribute directly, through a role or through a group
+/// assigned to them, soft deleted roles, groups and attributes
+/// and expired direct grants grant nothing
+pub async fn user_has_permission(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    permission_name: &str,
+    attribute_name: &str,
+) -> anyhow::Result<bool> {
+    let res: (bool,) = sqlx::query_as(
+        format!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM {permission} p
+                JOIN {permission_attribute} pa ON pa.name = $3 AND pa.deleted_date IS NULL
+                WHERE p.permission_name = $2 AND (
+                    EXISTS (
+                        SELECT 1 FROM {user_permission} up
+                        WHERE up.user_id = $1 AND up.permission_id = p.id
+                        AND up.attribute_id = pa.id
+                        AND (up.expires_at IS NULL OR up.expires_at > now())
+                    )
+                    OR EXISTS (
+                        SELECT 1 FROM {user_group_roles} ugr
+                        JOIN {role} r ON r.id = ugr.role_id AND r.deleted_date IS NULL
+                        JOIN {role_permission} rp ON rp.role_id = r.id
+                        WHERE ugr.user_id = $1 AND rp.permission_id = p.id
+                        AND rp.attribute_id = pa.id
+                    )
+                    OR EXISTS (
+                        SELECT 1 FROM {user_group_roles} ugr
+                        JOIN {group} g ON g.id = ugr.group_id AND g.deleted_date IS NULL
+                        JOIN {group_permission} gp ON gp.group_id = g.id
+                        WHERE ugr.user_id = $1 AND gp.permission_id = p.id
+                        AND gp.attribute_id = pa.id
+                    )
+                )
+            )"#,
+            user_permission = TABLE_NAME,
+            user_group_roles = USER_GROUP_ROLES_TABLE_NAME,
+            role = ROLE_TABLE_NAME,
+            role_permission = ROLE_PERMISSION_TABLE_NAME,
+            group = GROUP_TABLE_NAME,
+            group_permission = GROUP_PERMISSION_TABLE_NAME,
+            permission = PERMISSION_TABLE_NAME,
+            permission_attribute = PERMISSION_ATTRIBUTE_TABLE_NAME,
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(permission_name)
+    .bind(attribute_name)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(res.0)
+}
+
+/// (permission_name, attribute_name) the user holds directly, through a role or through a
+/// group, with the same rules as user_has_permission
+pub async fn get_user_permission_names(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let res: Vec<(String, String)> = sqlx::query_as(
+        format!(
+            r#"SELECT p.permission_name, pa.name FROM (
+                SELECT up.permission_id, up.attribute_id FROM {user_permission} up
+                WHERE up.user_id = $1 AND (up.expires_at IS NULL OR up.expires_at > now())
+                UNION
+                SELECT rp.permission_id, rp.attribute_id FROM {user_group_roles} ugr
+                JOIN {role} r ON r.id = ugr.role_id AND r.deleted_date IS NULL
+                JOIN {role_permission} rp ON rp.role_id = r.id
+                WHERE ugr.user_id = $1
+                UNION
+                SELECT gp.permission_id, gp.attribute_id FROM {user_group_roles} ugr
+                JOIN {group} g ON g.id = ugr.group_id AND g.deleted_date IS NULL
+                JOIN {group_permission} gp ON gp.group_id = g.id
+                WHERE ugr.user_id = $1
+            ) h
+            JOIN {permission} p ON p.id = h.permission_id
+            JOIN {permission_attribute} pa ON pa.id = h.attribute_id AND pa.deleted_date IS NULL
+            ORDER BY p.permission_name, pa.name"#,
+            user_permission = TABLE_NAME,
+            user_group_roles = USER_GROUP_ROLES_TABLE_NAME,
+            role = ROLE_TABLE_NAME,
+            role_permission = ROLE_PERMISSION_TABLE_NAME,
+            group = GROUP_TABLE_NAME,
+            group_permission = GROUP_PERMISSION_TABLE_NAME,
+            permission = PERMISSION_TABLE_NAME,
+            permission_attribute = PERMISSION_ATTRIBUTE_TABLE_NAME,
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(res)
+}
+
+/// Every active user holding the permission directly, through a role or through a group,
+/// one row per grant path. Deleted users, roles, groups and attributes and expired direct
+/// grants are left out
+pub async fn get_permission_holders(
+    conn: &mut PgConnection,
+    permission_id: &Uuid,
+    attribute_id: Option<Uuid>,
+) -> anyhow::Result<Vec<PermissionHolder>> {
+    let res: Vec<PermissionHolder> = sqlx::query_as(
+        format!(
+            r#"SELECT u.id AS user_id, u.user_name, u.status, pa.id AS attribute_id,
+                pa.name AS attribute_name, h.via, h.source_id, h.source_name, h.expires_at
+            FROM (
+                SELECT up.user_id, up.attribute_id, '{via_direct}' AS via,
+                    NULL::uuid AS source_id, NULL::varchar AS source_name, up.expires_at
+                FROM {user_permission} up
+                WHERE up.permission_id = $1
+                AND (up.expires_at IS NULL OR up.expires_at > now())
+                UNION
+                SELECT ugr.user_id, rp.attribute_id, '{via_role}', r.id, r.role_name,
+                    NULL::timestamptz
+                FROM {user_group_roles} ugr
+                JOIN {role} r ON r.id = ugr.role_id AND r.deleted_date IS NULL
+                JOIN {role_permission} rp ON rp.role_id = r.id
+                WHERE rp.permission_id = $1
+                UNION
+                SELECT ugr.user_id, gp.attribute_id, '{via_group}', g.id, g.group_name,
+                    NULL::timestamptz
+                FROM {user_group_roles} ugr
+                JOIN {group} g ON g.id = ugr.group_id AND g.deleted_date IS NULL
+                JOIN {group_permission} gp ON gp.group_id = g.id
+                WHERE gp.permission_id = $1
+            ) h
+            JOIN {user} u ON u.id = h.user_id AND u.deleted_date IS NULL
+            JOIN {permission_attribute} pa ON pa.id = h.attribute_id AND pa.deleted_date IS NULL
+            WHERE ($2::uuid IS NULL OR h.attribute_id = $2)
+            ORDER BY u.user_name, pa.name, h.via, h.source_name"#,
+            via_direct = GRANT_VIA_DIRECT,
+            via_role = GRANT_VIA_ROLE,
+            via_group = GRANT_VIA_GROUP,
+            user = USER_TABLE_NAME,
+            user_permission = TABLE_NAME,
+            user_group_roles = USER_GROUP_ROLES_TABLE_NAME,
+            role = ROLE_TABLE_NAME,
+            role_permission = ROLE_PERMISSION_TABLE_NAME,
+            group = GROUP_TABLE_NAME,
+            group_permission = GROUP_PERMISSION_TABLE_NAME,
+            permission_attribute = PERMISSION_ATTRIBUTE_TABLE_NAME,
+        )
+        .as_str(),
+    )
+    .bind(permission_id)
+    .bind(attribute_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(res)
+}
+
+/// Remove direct grants whose expires_at has passed, return the number removed
+pub async fn delete_expired_user_permissions(
+    tx: &mut Transaction<'_, Postgres>,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<u64> {
+    let res = sqlx::query(format!("DELETE FROM {} WHERE expires_at <= $1", TABLE_NAME).as_str())
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::user_preference::{UserPreference, TABLE_NAME};
+
+pub async fn get_user_preferences_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserPreference>> {
+    let data: Vec<UserPreference> = sqlx::query_as(
+        format!(
+            r#"SELECT * FROM {} WHERE user_id = $1 ORDER BY "key" ASC"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(data)
+}
+
+/// Insert the preference or replace the value of the existing key
+pub async fn upsert_user_preference(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    key: &str,
+    value: &serde_json::Value,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, "key", value, created_date, updated_date)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            ON CONFLICT (user_id, "key") DO UPDATE SET value = EXCLUDED.value, updated_date = EXCLUDED.updated_date"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(user_id)
+    .bind(key)
+    .bind(value)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Return false when the user has no such preference
+pub async fn delete_user_preference(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    key: &str,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query(
+        format!(
+            r#"DELETE FROM {} WHERE user_id = $1 AND "key" = $2"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .bind(key)
+    .execute(&mut **tx)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
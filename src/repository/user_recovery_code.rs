@@ -0,0 +1,89 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::user_recovery_code::{UserRecoveryCode, TABLE_NAME};
+
+/// Drop every code of the user and store the new hashes
+pub async fn replace_user_recovery_codes(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    code_hashes: &[String],
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(format!("DELETE FROM {} WHERE user_id = $1", TABLE_NAME).as_str())
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?;
+    for code_hash in code_hashes {
+        sqlx::query(
+            format!(
+                r#"INSERT INTO {} (id, user_id, code_hash, used_date, created_date)
+                VALUES ($1, $2, $3, NULL, $4)"#,
+                TABLE_NAME
+            )
+            .as_str(),
+        )
+        .bind(Uuid::now_v7())
+        .bind(user_id)
+        .bind(code_hash)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn get_unused_user_recovery_codes(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserRecoveryCode>> {
+    let data: Vec<UserRecoveryCode> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 AND used_date IS NULL ORDER BY id ASC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(data)
+}
+
+pub async fn count_unused_user_recovery_codes(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<i64> {
+    let count: (i64,) = sqlx::query_as(
+        format!(
+            "SELECT count(id) FROM {} WHERE user_id = $1 AND used_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(count.0)
+}
+
+/// Mark the code used, false when another login used it first
+pub async fn use_user_recovery_code(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query(
+        format!(
+            "UPDATE {} SET used_date = $1 WHERE id = $2 AND used_date IS NULL",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(now)
+    .bind(id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
@@ -0,0 +1,45 @@
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::user_status_history::{UserStatusHistory, TABLE_NAME};
+
+pub async fn create_user_status_history(
+    tx: &mut Transaction<'_, Postgres>,
+    history: &UserStatusHistory,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, from_status, to_status, reason, changed_by, created_date)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(history.id)
+    .bind(history.user_id)
+    .bind(history.from_status)
+    .bind(history.to_status)
+    .bind(&history.reason)
+    .bind(history.changed_by)
+    .bind(history.created_date)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Status changes of a user, oldest first
+pub async fn get_user_status_history_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<UserStatusHistory>> {
+    Ok(sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 ORDER BY created_date ASC, id ASC",
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, FixedOffset};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::{
+    terms_version::{TermsVersion, TABLE_NAME as TERMS_VERSION_TABLE_NAME},
+    user_terms_acceptance::TABLE_NAME,
+};
+
+/// Record the acceptance, accepting the same version twice keeps the first date
+pub async fn accept_terms_version(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    terms_version_id: &Uuid,
+    now: &DateTime<FixedOffset>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, terms_version_id, accepted_date)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, terms_version_id) DO NOTHING"#,
+            TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(user_id)
+    .bind(terms_version_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Latest version of each kind the user has not accepted yet
+pub async fn get_pending_terms_version_by_user(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<Vec<TermsVersion>> {
+    Ok(sqlx::query_as(
+        format!(
+            r#"SELECT * FROM (
+                SELECT DISTINCT ON (kind) * FROM {}
+                ORDER BY kind, published_date DESC, id DESC
+            ) tv
+            WHERE NOT EXISTS (
+                SELECT 1 FROM {} uta WHERE uta.terms_version_id = tv.id AND uta.user_id = $1
+            )
+            ORDER BY kind"#,
+            TERMS_VERSION_TABLE_NAME, TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await?)
+}
@@ -1,27 +1,78 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
-use chrono::{Duration, FixedOffset, Local};
-use poem::web::Data;
-use poem_openapi::{payload::Json, OpenApi, Tags};
+use chrono::Duration;
+use poem::{web::Data, Request};
+use poem_openapi::{
+    param::Query,
+    payload::{Form, Json},
+    OpenApi, Tags,
+};
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
 
 use crate::{
     core::{
+        error::{respond, AppError},
+        ldap_auth::{ldap_login, LdapAuth, LdapLogin},
+        locale::use_user_timezone,
+        login_lockout::{
+            clear_login_failures, get_login_lock, login_locked_message, record_login_failure,
+            LoginLockout,
+        },
+        permission_cache::{get_user_permissions, login_permissions_changed},
+        push::spawn_user_devices_notification,
+        recovery_code::find_recovery_code,
         security::{
-            generate_refresh_token_from_user, generate_token_from_user,
-            get_user_from_refresh_token, get_user_from_token, verify_hash_password,
-            BearerAuthorization,
+            bearer_token, decode_token, generate_refresh_token_from_user, generate_token_from_user,
+            get_user_from_refresh_token, get_user_from_token, hash_password, jwt_keys,
+            verify_hash_password, AuthContext, BearerAuthorization, ReadAuthContext,
+        },
+        service_account::{issue_client_credentials_token, ClientCredentialsError},
+        session::{add_session, remove_session, revoke_other_user_sessions, take_refresh_token},
+        session_store::SessionStore,
+        sso::{
+            authorization_code_claims, link_sso_identity, resolve_sso_user, start_authorization,
+            supports_authorization_code, take_authorization, verify_id_token, SsoClaims,
+            SsoLinkOutcome, SsoLoginOutcome, SSO_IDENTITY_PREFIX,
+        },
+        terms::{accept_and_get_pending_terms, pending_terms_message},
+        utils::{datetime_to_string, datetime_to_string_opt, utc_now},
+        validation::Validate,
+    },
+    model::{
+        notification_template::{EVENT_NEW_LOGIN, EVENT_PASSWORD_CHANGED},
+        sso_provider::SsoProvider,
+        user::UserStatus,
+        user_identity::UserIdentity,
+    },
+    repository::{
+        sso_provider::{get_active_sso_provider_by_name, get_sso_provider_by_id},
+        user::{get_user_by_username, update_user_password},
+        user_activity::record_user_login,
+        user_identity::{
+            delete_user_identity, get_sso_user_identity_by_user, get_user_identity_by_id,
         },
-        session::{add_session, remove_session},
+        user_recovery_code::use_user_recovery_code,
+        user_terms_acceptance::get_pending_terms_version_by_user,
     },
-    repository::user::get_user_by_username,
     schema::{
         auth::{
-            LoginRequest, LoginResponse, LoginResponses, LogoutResponses, RefreshTokenRequest,
-            RefreshTokenResponse, RefreshTokenResponses,
+            ChangePasswordRequest, ChangePasswordResponse, ChangePasswordResponses,
+            IntrospectRequest, IntrospectResponse, IntrospectResponses, LoginRequest,
+            LoginResponse, LoginResponses, LogoutResponses, OAuthErrorResponse,
+            PermissionCheckRequest, PermissionCheckResponse, PermissionCheckResponses,
+            PermissionCheckResult, RefreshTokenRequest, RefreshTokenResponses, TokenRequestBody,
+            TokenResponse, TokenResponses,
+        },
+        common::{BadRequestResponse, UnauthorizedResponse},
+        sso_provider::{
+            SsoAuthorizeResponse, SsoAuthorizeResponses, SsoCallbackRequest,
+            SsoIdentityLinkRequest, SsoLoginRequest, SsoLoginResponses,
+            UserIdentityDeleteResponses, UserIdentityLinkResponses, UserIdentityListResponses,
+            UserIdentityResponse,
         },
-        common::{BadRequestResponse, InternalServerErrorResponse, UnauthorizedResponse},
     },
-    settings::get_config,
+    settings::{get_config, Config},
     AppState,
 };
 
@@ -32,340 +83,907 @@ enum ApiAuthTags {
 
 pub struct ApiAuth;
 
+fn login_response(config: &Config, token: String, refresh_token: String) -> LoginResponse {
+    let now = utc_now();
+    let exp = now + Duration::minutes(config.jwt_exp as i64);
+    let exp_refresh_token = now + Duration::minutes(config.jwt_refresh_exp as i64);
+    LoginResponse {
+        exp: datetime_to_string(exp),
+        exp_in: now.timestamp() as i32 + config.jwt_exp as i32,
+        exp_refresh_token: datetime_to_string(exp_refresh_token),
+        refresh_token,
+        token,
+        token_type: "Bearer".to_string(),
+    }
+}
+
+fn identity_to_response(identity: UserIdentity) -> UserIdentityResponse {
+    UserIdentityResponse {
+        id: identity.id.to_string(),
+        provider: identity.provider,
+        subject: identity.subject,
+        created_date: datetime_to_string_opt(identity.created_date),
+    }
+}
+
+/// Active provider of the name with the endpoints of the authorization code flow
+async fn get_authorization_code_provider(
+    conn: &mut PgConnection,
+    name: &str,
+) -> Result<SsoProvider, AppError> {
+    let provider = get_active_sso_provider_by_name(conn, name)
+        .await?
+        .ok_or_else(|| AppError::bad_request(format!("sso provider {} not found", name)))?;
+    if !supports_authorization_code(&provider) {
+        return Err(AppError::bad_request(format!(
+            "sso provider {} does not support the authorization code flow",
+            name
+        )));
+    }
+    Ok(provider)
+}
+
+fn validate_redirect_uri(redirect_uri: &str) -> Result<(), AppError> {
+    match reqwest::Url::parse(redirect_uri) {
+        Ok(val) if ["http", "https"].contains(&val.scheme()) => Ok(()),
+        _ => Err(AppError::bad_request(
+            "redirect_uri must be an http or https url",
+        )),
+    }
+}
+
+/// Provider and claims of the code an authorization request came back with. The state is
+/// single use and only completes the flow it was started for, login or linking to the user
+async fn complete_authorization<C: SessionStore>(
+    conn: &mut PgConnection,
+    redis_conn: &mut C,
+    sso_state: &str,
+    code: &str,
+    link_user_id: Option<&Uuid>,
+) -> Result<(SsoProvider, SsoClaims), AppError> {
+    let invalid_state = || AppError::bad_request("invalid or expired state");
+    let pending = take_authorization(redis_conn, sso_state)
+        .await?
+        .ok_or_else(invalid_state)?;
+    if pending.link_user_id != link_user_id.map(|x| x.to_string()) {
+        return Err(invalid_state());
+    }
+    let provider_id = Uuid::parse_str(&pending.provider_id).map_err(|_| invalid_state())?;
+    let provider = get_sso_provider_by_id(conn, &provider_id)
+        .await?
+        .filter(|x| x.is_active == Some(true))
+        .ok_or_else(invalid_state)?;
+    match authorization_code_claims(&provider, &pending, code).await {
+        Ok(claims) => Ok((provider, claims)),
+        Err(err) => {
+            tracing::info!("sso authorization on {} rejected: {}", provider.name, err);
+            Err(AppError::unauthorized())
+        }
+    }
+}
+
+/// Tokens of the user an sso login resolves to, after accepting the terms sent with it.
+/// Commits the transaction, the terms accepted are kept even when others are still pending
+async fn sso_login<C: SessionStore>(
+    mut tx: Transaction<'_, Postgres>,
+    redis_conn: &mut C,
+    provider: &SsoProvider,
+    claims: &SsoClaims,
+    accept_terms: &[String],
+) -> Result<LoginResponse, AppError> {
+    let now = utc_now();
+    let (user, grants_changed) = match resolve_sso_user(&mut tx, provider, claims, &now).await? {
+        SsoLoginOutcome::LoggedIn {
+            user,
+            grants_changed,
+        }
+        | SsoLoginOutcome::Provisioned {
+            user,
+            grants_changed,
+        } => (user, grants_changed),
+        SsoLoginOutcome::NotProvisioned => {
+            return Err(AppError::Unauthorized(UnauthorizedResponse::new(
+                "user is not provisioned".to_string(),
+            )))
+        }
+        SsoLoginOutcome::Inactive => {
+            return Err(AppError::Unauthorized(UnauthorizedResponse::new(
+                "user is inactive".to_string(),
+            )))
+        }
+        SsoLoginOutcome::UserNameTaken(user_name) => {
+            return Err(AppError::conflict(format!(
+                "user name {} is already used by another user",
+                user_name
+            )))
+        }
+        SsoLoginOutcome::MissingUserName => {
+            return Err(AppError::bad_request(format!(
+                "sso provider returned no {} or {} claim",
+                provider.username_claim, provider.email_claim
+            )))
+        }
+    };
+    let config = get_config();
+    let mut pending = vec![];
+    if config.terms_acceptance_required.unwrap_or(false) {
+        pending = accept_and_get_pending_terms(&mut tx, &user.id, accept_terms, &now).await?;
+    }
+    if pending.is_empty() {
+        record_user_login(&mut tx, &user.id, &now).await?;
+    }
+    use_user_timezone(&mut tx, &config, &user.id).await?;
+    tx.commit().await?;
+    if grants_changed {
+        login_permissions_changed(redis_conn, &user.id).await;
+    }
+    if !pending.is_empty() {
+        return Err(AppError::forbidden(pending_terms_message(&pending)));
+    }
+
+    let token = generate_token_from_user(user.clone(), config.clone()).await?;
+    let refresh_token = generate_refresh_token_from_user(user.clone(), config.clone()).await?;
+    add_session(
+        redis_conn,
+        &user,
+        &config,
+        token.clone(),
+        refresh_token.clone(),
+    )
+    .await?;
+    Ok(login_response(&config, token, refresh_token))
+}
+
 #[OpenApi]
 impl ApiAuth {
+    /// Login with user name and password
+    ///
+    /// Returns an access token for the `Authorization: Bearer` header and a refresh token.
     #[oai(path = "/auth/login", method = "post", tag = "ApiAuthTags::Auth")]
     async fn auth_login(
         &self,
         json: Json<LoginRequest>,
         state: Data<&Arc<AppState>>,
     ) -> LoginResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return LoginResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_login",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+
+            // get usename on db
+            let (user, user_profile) = get_user_by_username(&mut tx, &json.user_name).await?;
+            let config = get_config();
+            let ldap_auth = LdapAuth::from_config(&config)?;
+            // user names unknown locally may still log in through ldap
+            let existing = user.zip(user_profile);
+            if existing.is_none() && ldap_auth.is_none() {
+                return Ok(LoginResponses::BadRequet(Json(BadRequestResponse::new(
+                    "Invalid credentials".to_string(),
+                ))));
             }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return LoginResponses::InternalServerError(Json(InternalServerErrorResponse::new(
-                    "route.auth",
-                    "auth_login",
-                    "get redis pool connection",
-                    &err.to_string(),
-                )))
+
+            // locked after too many failed logins, even with the right password
+            let lockout = LoginLockout::from_config(&config);
+            if let (Some(_), Some((user, _))) = (&lockout, &existing) {
+                if let Some(retry_after) = get_login_lock(&mut redis_conn, &user.id).await? {
+                    return Err(AppError::forbidden(login_locked_message(retry_after)));
+                }
             }
-        };
-
-        // get usename on db
-        let (user, user_profile) = match get_user_by_username(&mut tx, &json.user_name).await {
-            Ok(val) => val,
-            Err(err) => {
-                return LoginResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_login",
-                        "check user on database",
-                        &err.to_string(),
-                    ),
-                ));
+
+            // validate user password, by a bind on the directory for users of ldap
+            let now = utc_now();
+            let ldap_outcome = match &ldap_auth {
+                Some(ldap_auth) => {
+                    ldap_login(
+                        &mut tx,
+                        ldap_auth,
+                        existing.as_ref().map(|(user, profile)| (user, profile)),
+                        &json.user_name,
+                        &json.password,
+                        &now,
+                    )
+                    .await?
+                }
+                None => LdapLogin::Local,
+            };
+            let mut groups_changed = false;
+            let (user, is_valid) = match (ldap_outcome, existing) {
+                (
+                    LdapLogin::Accepted {
+                        user,
+                        groups_changed: changed,
+                    },
+                    _,
+                ) => {
+                    groups_changed = changed;
+                    (*user, true)
+                }
+                (LdapLogin::Rejected, Some((user, _))) => (user, false),
+                (LdapLogin::Local, Some((user, _))) => {
+                    let is_valid =
+                        verify_hash_password(&json.password, &user.password).map_err(|err| {
+                            AppError::Internal(anyhow::anyhow!("verify_hash_password: {}", err))
+                        })?;
+                    (user, is_valid)
+                }
+                (_, None) => {
+                    return Ok(LoginResponses::BadRequet(Json(BadRequestResponse::new(
+                        "Invalid credentials".to_string(),
+                    ))));
+                }
+            };
+            if !is_valid {
+                if let Some(lockout) = &lockout {
+                    if record_login_failure(&mut redis_conn, lockout, &user.id).await? {
+                        return Err(AppError::forbidden(login_locked_message(lockout.duration)));
+                    }
+                }
+                return Ok(LoginResponses::BadRequet(Json(BadRequestResponse::new(
+                    "Invalid credentials".to_string(),
+                ))));
             }
-        };
-        if user.is_none() || user_profile.is_none() {
-            return LoginResponses::BadRequet(Json(BadRequestResponse {
-                message: "Invalid credentials".to_string(),
-            }));
-        }
-        let user = user.unwrap();
-        // let user_profile = user_profile.unwrap();
-
-        // validate user password
-        let is_valid = match verify_hash_password(&json.password, &user.password) {
-            Ok(val) => val,
-            Err(err) => {
-                return LoginResponses::InternalServerError(Json(InternalServerErrorResponse::new(
-                    "route.auth",
-                    "auth_login",
-                    "validate user password",
-                    &err.to_string(),
-                )))
+            if user.status != UserStatus::Active {
+                return Err(AppError::forbidden(format!("user is {}", user.status)));
+            }
+            if user.is_expired(&now) {
+                return Err(AppError::forbidden("user account expired".to_string()));
+            }
+            // a recovery code stands in for the authenticator of 2FA users, it is not required
+            // while there is no TOTP to fall back from
+            let mut recovery_code_id = None;
+            if let (true, Some(recovery_code)) =
+                (user.is_2faenabled.unwrap_or(false), &json.recovery_code)
+            {
+                recovery_code_id = find_recovery_code(&mut tx, &user.id, recovery_code).await?;
+                if recovery_code_id.is_none() {
+                    if let Some(lockout) = &lockout {
+                        if record_login_failure(&mut redis_conn, lockout, &user.id).await? {
+                            return Err(AppError::forbidden(login_locked_message(
+                                lockout.duration,
+                            )));
+                        }
+                    }
+                    return Ok(LoginResponses::BadRequet(Json(BadRequestResponse::new(
+                        "Invalid recovery code".to_string(),
+                    ))));
+                }
             }
-        };
-        if !is_valid {
-            return LoginResponses::BadRequet(Json(BadRequestResponse {
-                message: "Invalid credentials".to_string(),
-            }));
-        }
 
-        let config = get_config();
-        let token = match generate_token_from_user(user.clone(), config.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return LoginResponses::InternalServerError(Json(InternalServerErrorResponse::new(
-                    "route.auth",
-                    "auth_login",
-                    "generate token",
-                    &err.to_string(),
-                )))
+            let mut pending = vec![];
+            if config.terms_acceptance_required.unwrap_or(false) {
+                pending = accept_and_get_pending_terms(
+                    &mut tx,
+                    &user.id,
+                    json.accept_terms.as_deref().unwrap_or_default(),
+                    &now,
+                )
+                .await?;
             }
-        };
-
-        let refresh_token = match generate_refresh_token_from_user(user.clone(), config.clone())
-            .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return LoginResponses::InternalServerError(Json(InternalServerErrorResponse::new(
-                    "route.auth",
-                    "auth_login",
-                    "generate refresh token",
-                    &err.to_string(),
-                )))
+            if pending.is_empty() {
+                // the code is used up only by a login that gets its tokens
+                if let Some(recovery_code_id) = &recovery_code_id {
+                    if !use_user_recovery_code(&mut tx, recovery_code_id, &now).await? {
+                        return Ok(LoginResponses::BadRequet(Json(BadRequestResponse::new(
+                            "Invalid recovery code".to_string(),
+                        ))));
+                    }
+                }
+                record_user_login(&mut tx, &user.id, &now).await?;
             }
-        };
-
-        if let Err(err) = add_session(
-            &mut redis_conn,
-            &user,
-            &config,
-            token.clone(),
-            refresh_token.clone(),
-        ) {
-            return LoginResponses::InternalServerError(Json(InternalServerErrorResponse::new(
-                "route.auth",
-                "auth_login",
-                "add_session to redis",
-                &err.to_string(),
-            )));
-        }
-        let now = Local::now();
-        let exp = now + Duration::minutes(config.jwt_exp as i64);
-        let exp_refresh_token = now + Duration::minutes(config.jwt_refresh_exp as i64);
-        let offset = FixedOffset::east_opt(7 * 60 * 60).unwrap(); // +0700
-        LoginResponses::Ok(Json(LoginResponse {
-            exp: exp
-                .with_timezone(&offset)
-                .format("%Y-%m-%d %H:%M:%S")
-                .to_string(),
-            exp_in: now.timestamp() as i32 + config.jwt_exp as i32,
-            exp_refresh_token: exp_refresh_token
-                .with_timezone(&offset)
-                .format("%Y-%m-%d %H:%M:%S")
-                .to_string(),
-            refresh_token,
-            token,
-            token_type: "Bearer".to_string(),
-        }))
+            use_user_timezone(&mut tx, &config, &user.id).await?;
+            // keep acceptances given with this request even when others are still pending
+            tx.commit().await?;
+            if groups_changed {
+                login_permissions_changed(&mut redis_conn, &user.id).await;
+            }
+            if !pending.is_empty() {
+                return Err(AppError::forbidden(pending_terms_message(&pending)));
+            }
+            if lockout.is_some() {
+                clear_login_failures(&mut redis_conn, &user.id).await?;
+            }
+            let token = generate_token_from_user(user.clone(), config.clone()).await?;
+
+            let refresh_token =
+                generate_refresh_token_from_user(user.clone(), config.clone()).await?;
+
+            add_session(
+                &mut redis_conn,
+                &user,
+                &config,
+                token.clone(),
+                refresh_token.clone(),
+            )
+            .await?;
+            spawn_user_devices_notification(
+                state.db.clone(),
+                config.clone(),
+                user.id,
+                EVENT_NEW_LOGIN,
+            );
+            Ok(LoginResponses::Ok(Json(login_response(
+                &config,
+                token,
+                refresh_token,
+            ))))
+        })
+        .await
     }
 
+    /// Login with an id token issued by a configured sso provider,
+    /// unknown users are provisioned when the provider has jit enabled
+    #[oai(path = "/auth/sso/login", method = "post", tag = "ApiAuthTags::Auth")]
+    async fn auth_sso_login(
+        &self,
+        json: Json<SsoLoginRequest>,
+        state: Data<&Arc<AppState>>,
+    ) -> SsoLoginResponses {
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+
+            let provider = get_active_sso_provider_by_name(&mut tx, &json.provider).await?;
+            if provider.is_none() {
+                return Err(AppError::bad_request(format!(
+                    "sso provider {} not found",
+                    json.provider
+                )));
+            }
+            let provider = provider.unwrap();
+
+            let claims = match verify_id_token(&provider, &json.id_token).await {
+                Ok(val) => val,
+                Err(err) => {
+                    tracing::info!("sso login on {} rejected: {}", provider.name, err);
+                    return Err(AppError::unauthorized());
+                }
+            };
+
+            let res = sso_login(
+                tx,
+                &mut redis_conn,
+                &provider,
+                &claims,
+                json.accept_terms.as_deref().unwrap_or_default(),
+            )
+            .await?;
+            Ok(SsoLoginResponses::Ok(Json(res)))
+        })
+        .await
+    }
+
+    /// Start a login through the authorization code flow of an sso provider
+    ///
+    /// Returns the provider url to send the user to, the provider redirects back to
+    /// redirect_uri with code and state. Finish with POST /auth/sso/callback/ within
+    /// 10 minutes.
     #[oai(
-        path = "/auth/refresh-token",
+        path = "/auth/sso/authorize/",
+        method = "get",
+        tag = "ApiAuthTags::Auth"
+    )]
+    async fn auth_sso_authorize(
+        &self,
+        Query(provider): Query<String>,
+        Query(redirect_uri): Query<String>,
+        state: Data<&Arc<AppState>>,
+    ) -> SsoAuthorizeResponses {
+        respond(async move {
+            let mut db = state.db.acquire().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+            validate_redirect_uri(&redirect_uri)?;
+            let provider = get_authorization_code_provider(&mut db, &provider).await?;
+            let (authorization_url, sso_state) =
+                start_authorization(&mut redis_conn, &provider, &redirect_uri, None).await?;
+            Ok(SsoAuthorizeResponses::Ok(Json(SsoAuthorizeResponse {
+                authorization_url,
+                state: sso_state,
+            })))
+        })
+        .await
+    }
+
+    /// Finish a login started with GET /auth/sso/authorize/
+    ///
+    /// Users are resolved like on POST /auth/sso/login: linked identities log in, unknown
+    /// users are provisioned when the provider has jit enabled. A local user with the same
+    /// name is never taken over, it has to log in and link the identity instead.
+    #[oai(
+        path = "/auth/sso/callback/",
         method = "post",
         tag = "ApiAuthTags::Auth"
     )]
-    async fn auth_refresh_token(
+    async fn auth_sso_callback(
+        &self,
+        Json(json): Json<SsoCallbackRequest>,
+        state: Data<&Arc<AppState>>,
+    ) -> SsoLoginResponses {
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+            let (provider, claims) =
+                complete_authorization(&mut tx, &mut redis_conn, &json.state, &json.code, None)
+                    .await?;
+            let res = sso_login(
+                tx,
+                &mut redis_conn,
+                &provider,
+                &claims,
+                json.accept_terms.as_deref().unwrap_or_default(),
+            )
+            .await?;
+            Ok(SsoLoginResponses::Ok(Json(res)))
+        })
+        .await
+    }
+
+    /// Sso identities linked to the request user
+    #[oai(
+        path = "/auth/sso/identity/",
+        method = "get",
+        tag = "ApiAuthTags::Auth"
+    )]
+    async fn auth_sso_identity_list(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserIdentityListResponses {
+        respond(async move {
+            let ReadAuthContext { mut db, user, .. } = ctx;
+            let identities = get_sso_user_identity_by_user(&mut db, &user.id).await?;
+            Ok(UserIdentityListResponses::Ok(Json(
+                identities.into_iter().map(identity_to_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Start linking an sso provider account to the request user
+    ///
+    /// Same flow as GET /auth/sso/authorize/, finish with POST /auth/sso/identity/.
+    #[oai(
+        path = "/auth/sso/identity/authorize/",
+        method = "get",
+        tag = "ApiAuthTags::Auth"
+    )]
+    async fn auth_sso_identity_authorize(
+        &self,
+        Query(provider): Query<String>,
+        Query(redirect_uri): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoAuthorizeResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            validate_redirect_uri(&redirect_uri)?;
+            let provider = get_authorization_code_provider(&mut db, &provider).await?;
+            let (authorization_url, sso_state) =
+                start_authorization(&mut redis_conn, &provider, &redirect_uri, Some(&user.id))
+                    .await?;
+            Ok(SsoAuthorizeResponses::Ok(Json(SsoAuthorizeResponse {
+                authorization_url,
+                state: sso_state,
+            })))
+        })
+        .await
+    }
+
+    /// Link the sso provider account of a code to the request user
+    ///
+    /// Later logins through the provider resolve to the request user. An account linked to
+    /// another user returns 409.
+    #[oai(
+        path = "/auth/sso/identity/",
+        method = "post",
+        tag = "ApiAuthTags::Auth"
+    )]
+    async fn auth_sso_identity_link(
+        &self,
+        Json(json): Json<SsoIdentityLinkRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserIdentityLinkResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            let (provider, claims) = complete_authorization(
+                &mut tx,
+                &mut redis_conn,
+                &json.state,
+                &json.code,
+                Some(&user.id),
+            )
+            .await?;
+            let identity =
+                match link_sso_identity(&mut tx, &provider, &user, &claims, &utc_now()).await? {
+                    SsoLinkOutcome::Linked(val) | SsoLinkOutcome::AlreadyLinked(val) => val,
+                    SsoLinkOutcome::LinkedToOther => {
+                        return Err(AppError::conflict(format!(
+                            "account of sso provider {} is linked to another user",
+                            provider.name
+                        )))
+                    }
+                };
+            tx.commit().await?;
+            Ok(UserIdentityLinkResponses::Created(Json(
+                identity_to_response(identity),
+            )))
+        })
+        .await
+    }
+
+    /// Unlink an sso identity of the request user
+    #[oai(
+        path = "/auth/sso/identity/",
+        method = "delete",
+        tag = "ApiAuthTags::Auth"
+    )]
+    async fn auth_sso_identity_delete(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserIdentityDeleteResponses {
+        respond(async move {
+            let AuthContext { mut tx, user, .. } = ctx;
+            let not_found = || AppError::not_found(format!("identity with id = {} not found", id));
+            let identity_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            // identities of directory sync and ldap login are managed by the directory
+            let identity = get_user_identity_by_id(&mut tx, &identity_id)
+                .await?
+                .filter(|x| {
+                    x.user_id == user.id
+                        && x.directory_source_id.is_none()
+                        && x.provider.starts_with(SSO_IDENTITY_PREFIX)
+                })
+                .ok_or_else(not_found)?;
+            delete_user_identity(&mut tx, &identity.id).await?;
+            tx.commit().await?;
+            Ok(UserIdentityDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Exchange a refresh token for a new token pair
+    ///
+    /// Refresh tokens are single use, the one sent is invalidated and the response carries
+    /// its replacement. Sending a used refresh token again returns 401.
+    #[oai(path = "/auth/refresh", method = "post", tag = "ApiAuthTags::Auth")]
+    async fn auth_refresh(
         &self,
         json: Json<RefreshTokenRequest>,
         state: Data<&Arc<AppState>>,
     ) -> RefreshTokenResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return RefreshTokenResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_refresh_token",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+
+            // rotation, a refresh token is exchanged once
+            let is_valid = take_refresh_token(&mut redis_conn, &json.refresh_token).await?;
+            if !is_valid {
+                return Err(AppError::unauthorized());
             }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return RefreshTokenResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_refresh_token",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+
+            let config = get_config();
+            let refresh_token_user =
+                get_user_from_refresh_token(&mut tx, Some(json.refresh_token.clone())).await?;
+            if refresh_token_user.is_none() {
+                return Err(AppError::unauthorized());
             }
-        };
-
-        let config = get_config();
-        let refresh_token_user = match get_user_from_refresh_token(
-            &mut tx,
-            Some(json.refresh_token.clone()),
-            config.clone(),
-        )
-        .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return RefreshTokenResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_refresh_token",
-                        "get user from refresh token",
-                        &err.to_string(),
-                    ),
-                ))
+            let refresh_token_user = refresh_token_user.unwrap();
+            if refresh_token_user.status != UserStatus::Active {
+                return Err(AppError::forbidden(format!(
+                    "user is {}",
+                    refresh_token_user.status
+                )));
             }
-        };
-        if refresh_token_user.is_none() {
-            return RefreshTokenResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let refresh_token_user = refresh_token_user.unwrap();
-
-        let token = match generate_token_from_user(refresh_token_user.clone(), config.clone()).await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return RefreshTokenResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_refresh_token",
-                        "generate token",
-                        &err.to_string(),
-                    ),
-                ))
+            if refresh_token_user.is_expired(&utc_now()) {
+                return Err(AppError::forbidden("user account expired".to_string()));
+            }
+            if config.terms_acceptance_required.unwrap_or(false) {
+                let pending =
+                    get_pending_terms_version_by_user(&mut tx, &refresh_token_user.id).await?;
+                if !pending.is_empty() {
+                    return Err(AppError::forbidden(pending_terms_message(&pending)));
+                }
             }
-        };
 
-        let refresh_token = match generate_refresh_token_from_user(
-            refresh_token_user.clone(),
-            config.clone(),
-        )
+            use_user_timezone(&mut tx, &config, &refresh_token_user.id).await?;
+
+            let token =
+                generate_token_from_user(refresh_token_user.clone(), config.clone()).await?;
+
+            let refresh_token =
+                generate_refresh_token_from_user(refresh_token_user.clone(), config.clone())
+                    .await?;
+
+            add_session(
+                &mut redis_conn,
+                &refresh_token_user,
+                &config,
+                token.clone(),
+                refresh_token.clone(),
+            )
+            .await?;
+            Ok(RefreshTokenResponses::Ok(Json(
+                login_response(&config, token, refresh_token).into(),
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return RefreshTokenResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_refresh_token",
-                        "generate refresh token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        if let Err(err) = add_session(
-            &mut redis_conn,
-            &refresh_token_user,
-            &config,
-            token.clone(),
-            refresh_token.clone(),
-        ) {
-            return RefreshTokenResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.auth",
-                    "auth_refresh_token",
-                    "add_session to redis",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        let now = Local::now();
-        let exp = now + Duration::minutes(config.clone().jwt_exp as i64);
-        let exp_refresh_token = now + Duration::minutes(config.clone().jwt_refresh_exp as i64);
-        let offset = FixedOffset::east_opt(7 * 60 * 60).unwrap(); // +0700
-        RefreshTokenResponses::Ok(Json(RefreshTokenResponse {
-            exp: exp
-                .with_timezone(&offset)
-                .format("%Y-%m-%d %H:%M:%S")
-                .to_string(),
-            exp_in: now.timestamp() as i32 + config.clone().jwt_exp as i32,
-            exp_refresh_token: exp_refresh_token
-                .with_timezone(&offset)
-                .format("%Y-%m-%d %H:%M:%S")
-                .to_string(),
-            refresh_token,
-            token,
-            token_type: "Bearer".to_string(),
-        }))
     }
 
+    /// Exchange a refresh token for a new token pair, use /auth/refresh instead
+    #[oai(
+        path = "/auth/refresh-token",
+        method = "post",
+        tag = "ApiAuthTags::Auth",
+        deprecated
+    )]
+    async fn auth_refresh_token(
+        &self,
+        json: Json<RefreshTokenRequest>,
+        state: Data<&Arc<AppState>>,
+    ) -> RefreshTokenResponses {
+        self.auth_refresh(json, state).await
+    }
+
+    /// Revoke the session of the bearer token
     #[oai(path = "/auth/logout", method = "post", tag = "ApiAuthTags::Auth")]
     async fn auth_logout(
         &self,
-        state: Data<&Arc<AppState>>,
+        ctx: ReadAuthContext,
         auth: BearerAuthorization,
     ) -> LogoutResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return LogoutResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_logout",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
+        respond(async move {
+            let ReadAuthContext { mut redis_conn, .. } = ctx;
+            remove_session(&mut redis_conn, auth.0.token.unwrap_or_default()).await?;
+            Ok(LogoutResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Token introspection for services validating tokens without database access
+    ///
+    /// Active only when the token is signed, not expired, its session is not revoked and
+    /// the user is active. The caller authenticates with a bearer token or a service
+    /// account api key, without one every token is reported inactive (RFC 7662). Used by
+    /// the `token-verifier` feature middleware.
+    #[oai(path = "/auth/introspect", method = "post", tag = "ApiAuthTags::Auth")]
+    async fn auth_introspect(
+        &self,
+        json: Json<IntrospectRequest>,
+        state: Data<&Arc<AppState>>,
+        req: &Request,
+    ) -> IntrospectResponses {
+        let caller_token = bearer_token(req);
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+
+            // tokens are not probed anonymously
+            let caller = get_user_from_token(&mut tx, &mut redis_conn, caller_token).await?;
+            if caller.is_none() {
+                return Ok(IntrospectResponses::Ok(
+                    Json(IntrospectResponse::inactive()),
+                ));
+            }
+            let claims = match decode_token(&json.token, jwt_keys()) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Ok(IntrospectResponses::Ok(
+                        Json(IntrospectResponse::inactive()),
+                    ))
+                }
+            };
+
+            // Session must still exist, logout and refresh revoke it
+            let user =
+                get_user_from_token(&mut tx, &mut redis_conn, Some(json.token.clone())).await?;
+            Ok(match user {
+                Some(user) if user.status == UserStatus::Active => {
+                    IntrospectResponses::Ok(Json(IntrospectResponse {
+                        active: true,
+                        sub: Some(user.id.to_string()),
+                        user_name: Some(user.user_name),
+                        exp: Some(claims.exp),
+                        scopes: claims.scopes,
+                    }))
+                }
+                _ => IntrospectResponses::Ok(Json(IntrospectResponse::inactive())),
+            })
+        })
+        .await
+    }
+
+    /// Check many permissions of the request user at once
+    ///
+    /// Answers allow or deny per pair in request order, so clients can decide what to show
+    /// without fetching the full permission list.
+    #[oai(path = "/auth/check/", method = "post", tag = "ApiAuthTags::Auth")]
+    async fn auth_check(
+        &self,
+        Json(json): Json<PermissionCheckRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> PermissionCheckResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            if let Some(errors) = json.validation_errors() {
+                return Ok(PermissionCheckResponses::UnprocessableEntity(Json(errors)));
+            }
+
+            // one lookup of the permission set answers every pair
+            let names =
+                get_user_permissions(&mut tx, &mut redis_conn, &get_config(), &request_user.id)
+                    .await?;
+            let names: HashSet<(String, String)> = names.into_iter().collect();
+            let results = json
+                .permissions
+                .into_iter()
+                .map(|x| PermissionCheckResult {
+                    allowed: names.contains(&(x.permission_name.clone(), x.attribute.clone()))
+                        && request_user.has_scope(&x.permission_name, &x.attribute),
+                    permission_name: x.permission_name,
+                    attribute: x.attribute,
+                })
+                .collect();
+            Ok(PermissionCheckResponses::Ok(Json(
+                PermissionCheckResponse { results },
+            )))
+        })
+        .await
+    }
+
+    /// Change the password of the current user
+    ///
+    /// Requires the current password, the new one has to meet the password policy. Every
+    /// other session of the user is signed out, the one of this request stays valid.
+    #[oai(
+        path = "/auth/change-password/",
+        method = "post",
+        tag = "ApiAuthTags::Auth"
+    )]
+    async fn auth_change_password(
+        &self,
+        Json(json): Json<ChangePasswordRequest>,
+        state: Data<&Arc<AppState>>,
+        ctx: AuthContext,
+        auth: BearerAuthorization,
+    ) -> ChangePasswordResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: mut request_user,
+            } = ctx;
+            if let Some(errors) = json.validation_errors() {
+                return Ok(ChangePasswordResponses::UnprocessableEntity(Json(errors)));
+            }
+
+            // wrong passwords count toward the login lockout, guessing here is no cheaper
+            let config = get_config();
+            let lockout = LoginLockout::from_config(&config);
+            if lockout.is_some() {
+                if let Some(retry_after) = get_login_lock(&mut redis_conn, &request_user.id).await?
+                {
+                    return Err(AppError::forbidden(login_locked_message(retry_after)));
+                }
+            }
+
+            // validate current password
+            let is_valid = verify_hash_password(&json.current_password, &request_user.password)
+                .map_err(|err| {
+                    AppError::Internal(anyhow::anyhow!("verify_hash_password: {}", err))
+                })?;
+            if !is_valid {
+                if let Some(lockout) = &lockout {
+                    if record_login_failure(&mut redis_conn, lockout, &request_user.id).await? {
+                        return Err(AppError::forbidden(login_locked_message(lockout.duration)));
+                    }
+                }
+                return Err(AppError::bad_request(
+                    "current password is incorrect".to_string(),
                 ));
             }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return LogoutResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_logout",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+            if lockout.is_some() {
+                clear_login_failures(&mut redis_conn, &request_user.id).await?;
             }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return LogoutResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.auth",
-                        "auth_logout",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
+
+            let password = hash_password(&json.new_password)
+                .map_err(|err| AppError::Internal(anyhow::anyhow!("hash_password: {}", err)))?;
+            update_user_password(&mut tx, &mut request_user, &password, &utc_now()).await?;
+            tx.commit().await?;
+
+            // sign out every other session, jwt_exp is in minutes
+            revoke_other_user_sessions(
+                &mut redis_conn,
+                &request_user.id,
+                &auth.0.token.unwrap_or_default(),
+                config.jwt_exp as u64 * 60,
+            )
+            .await?;
+            spawn_user_devices_notification(
+                state.db.clone(),
+                config,
+                request_user.id,
+                EVENT_PASSWORD_CHANGED,
+            );
+
+            Ok(ChangePasswordResponses::Ok(Json(ChangePasswordResponse {
+                message: "password changed successfully".to_string(),
+            })))
+        })
+        .await
+    }
+
+    /// OAuth2 token endpoint for machine to machine calls
+    ///
+    /// Exchanges the id and api key of a service account for a short lived access token
+    /// carrying its scopes, which other services can verify locally. Only the
+    /// client_credentials grant is supported, credentials are sent in the body.
+    #[oai(path = "/auth/token/", method = "post", tag = "ApiAuthTags::Auth")]
+    async fn auth_token(
+        &self,
+        body: TokenRequestBody,
+        state: Data<&Arc<AppState>>,
+    ) -> TokenResponses {
+        respond(async move {
+            let json = match body {
+                TokenRequestBody::Form(Form(val)) => val,
+                TokenRequestBody::Json(Json(val)) => val,
+            };
+            if json.grant_type != "client_credentials" {
+                return Ok(TokenResponses::BadRequest(Json(OAuthErrorResponse::new(
+                    "unsupported_grant_type",
+                    format!("grant_type {} is not supported", json.grant_type),
+                ))));
             }
-        };
-        if user.is_none() {
-            return LogoutResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        if let Err(err) = remove_session(&mut redis_conn, jwt_token.unwrap()) {
-            return LogoutResponses::InternalServerError(Json(InternalServerErrorResponse::new(
-                "route.auth",
-                "auth_logout",
-                "remove session",
-                &err.to_string(),
-            )));
-        }
-        LogoutResponses::NoContent
+
+            let mut db = state.db.acquire().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+            Ok(
+                match issue_client_credentials_token(
+                    &mut db,
+                    &mut redis_conn,
+                    &get_config(),
+                    &json.client_id,
+                    &json.client_secret,
+                    json.scope.as_deref(),
+                )
+                .await
+                {
+                    Ok(token) => TokenResponses::Ok(Json(TokenResponse {
+                        access_token: token.access_token,
+                        token_type: "Bearer".to_string(),
+                        expires_in: token.expires_in,
+                        scope: token.scopes.join(" "),
+                    })),
+                    Err(ClientCredentialsError::InvalidClient) => {
+                        TokenResponses::Unauthorized(Json(OAuthErrorResponse::new(
+                            "invalid_client",
+                            "client authentication failed",
+                        )))
+                    }
+                    Err(ClientCredentialsError::InvalidScope(message)) => {
+                        TokenResponses::BadRequest(Json(OAuthErrorResponse::new(
+                            "invalid_scope",
+                            message,
+                        )))
+                    }
+                    Err(ClientCredentialsError::Internal(err)) => return Err(err.into()),
+                },
+            )
+        })
+        .await
     }
 }
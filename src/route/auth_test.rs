@@ -1,15 +1,24 @@
 use std::sync::Arc;
 
+use chrono::{DateTime, Duration, FixedOffset, Local};
 use poem::{http::StatusCode, test::TestClient};
 use serde_json::json;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    core::security::{get_user_from_token, hash_password},
+    core::{
+        login_lockout::{login_locked_message, LoginLockout},
+        security::{get_user_from_token, hash_password, jwt_keys},
+        session_store::{create_redis_pool, SessionStore},
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
     factory::{user::UserFactory, user_profile::UserProfileFactory},
     init_openapi_route,
-    model::{user::User, user_profile::UserProfile},
+    model::{
+        user::{User, UserStatus},
+        user_profile::UserProfile,
+    },
     settings::get_config,
     AppState,
 };
@@ -19,29 +28,28 @@ async fn test_login_then_logout(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut user_factory = UserFactory::<Uuid>::new();
     user_factory.modified_one(|data, ext| User {
         id: ext,
         user_name: "test_user".to_string(),
         password: hash_password("password").unwrap(),
-        is_active: Some(true),
+        status: UserStatus::Active,
+        expires_at: None,
         is_2faenabled: Some(false),
         created_by: None,
         updated_by: None,
         created_date: data.created_date,
         updated_date: data.updated_date,
         deleted_date: None,
+        scopes: None,
     });
     let user_id = Uuid::now_v7();
-    user_factory
-        .generate_one(&app_state.db, user_id.clone())
-        .await?;
+    user_factory.generate_one(&app_state.db, user_id).await?;
     let mut user_profile_factory = UserProfileFactory::<Uuid>::new();
     user_profile_factory.modified_one(|data, ext| UserProfile {
         id: data.id,
@@ -50,6 +58,9 @@ async fn test_login_then_logout(pool: PgPool) -> anyhow::Result<()> {
         last_name: data.last_name.clone(),
         address: data.address.clone(),
         email: data.email.clone(),
+        locale: data.locale.clone(),
+        timezone: data.timezone.clone(),
+        email_verified_at: data.email_verified_at,
     });
     user_profile_factory
         .generate_one(&app_state.db, user_id)
@@ -75,11 +86,11 @@ async fn test_login_then_logout(pool: PgPool) -> anyhow::Result<()> {
     assert!(token.is_some());
     let token: String = token.unwrap().deserialize();
     let mut tx = app_state.db.begin().await?;
-    let mut redis_conn = app_state.redis_conn.get().unwrap();
+    let mut redis_conn = app_state.redis_conn.get().await.unwrap();
     let user_in_token = get_user_from_token(&mut tx, &mut redis_conn, Some(token.clone())).await?;
     assert!(user_in_token.is_some());
     assert_eq!(user_in_token.unwrap().id, user_id);
-    let res: Option<String> = redis::cmd("GET").arg(&token).query(&mut redis_conn)?;
+    let res = redis_conn.get(&token).await?;
     assert!(res.is_some());
 
     // When logout
@@ -91,7 +102,7 @@ async fn test_login_then_logout(pool: PgPool) -> anyhow::Result<()> {
 
     // Expect logout
     resp.assert_status(StatusCode::NO_CONTENT);
-    let res: Option<String> = redis::cmd("GET").arg(&token).query(&mut redis_conn)?;
+    let res = redis_conn.get(&token).await?;
     assert!(res.is_none());
 
     // When second logout
@@ -111,29 +122,28 @@ async fn test_login_then_refresh(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut user_factory = UserFactory::<Uuid>::new();
     user_factory.modified_one(|data, ext| User {
         id: ext,
         user_name: "test_user".to_string(),
         password: hash_password("password").unwrap(),
-        is_active: Some(true),
+        status: UserStatus::Active,
+        expires_at: None,
         is_2faenabled: Some(false),
         created_by: None,
         updated_by: None,
         created_date: data.created_date,
         updated_date: data.updated_date,
         deleted_date: None,
+        scopes: None,
     });
     let user_id = Uuid::now_v7();
-    user_factory
-        .generate_one(&app_state.db, user_id.clone())
-        .await?;
+    user_factory.generate_one(&app_state.db, user_id).await?;
     let mut user_profile_factory = UserProfileFactory::<Uuid>::new();
     user_profile_factory.modified_one(|data, ext| UserProfile {
         id: data.id,
@@ -142,6 +152,9 @@ async fn test_login_then_refresh(pool: PgPool) -> anyhow::Result<()> {
         last_name: data.last_name.clone(),
         address: data.address.clone(),
         email: data.email.clone(),
+        locale: data.locale.clone(),
+        timezone: data.timezone.clone(),
+        email_verified_at: data.email_verified_at,
     });
     user_profile_factory
         .generate_one(&app_state.db, user_id)
@@ -172,7 +185,7 @@ async fn test_login_then_refresh(pool: PgPool) -> anyhow::Result<()> {
         "refresh_token": refresh_token,
     });
     let resp = cli
-        .post("/api/auth/refresh-token")
+        .post("/api/auth/refresh")
         .body_json(&json_payload)
         .send()
         .await;
@@ -183,12 +196,24 @@ async fn test_login_then_refresh(pool: PgPool) -> anyhow::Result<()> {
     let token = json.value().object().get_opt("token");
     assert!(token.is_some());
     let token: String = token.unwrap().deserialize();
+    let new_refresh_token: String = json.value().object().get("refresh_token").deserialize();
+    assert_ne!(new_refresh_token, refresh_token);
+
+    // When the used refresh token is sent again
+    let resp = cli
+        .post("/api/auth/refresh")
+        .body_json(&json_payload)
+        .send()
+        .await;
+
+    // Expect it was rotated out
+    resp.assert_status(StatusCode::UNAUTHORIZED);
     let mut tx = app_state.db.begin().await?;
-    let mut redis_conn = app_state.redis_conn.get().unwrap();
+    let mut redis_conn = app_state.redis_conn.get().await.unwrap();
     let user_in_token = get_user_from_token(&mut tx, &mut redis_conn, Some(token.clone())).await?;
     assert!(user_in_token.is_some());
     assert_eq!(user_in_token.unwrap().id, user_id);
-    let res: Option<String> = redis::cmd("GET").arg(&token).query(&mut redis_conn)?;
+    let res = redis_conn.get(&token).await?;
     assert!(res.is_some());
 
     // When logout
@@ -198,7 +223,499 @@ async fn test_login_then_refresh(pool: PgPool) -> anyhow::Result<()> {
         .send()
         .await;
 
-    // Expect logout
+    // Expect logout, the refresh token of the session is revoked too
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .post("/api/auth/refresh-token")
+        .body_json(&json!({"refresh_token": new_refresh_token}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_login_expired_user(pool: PgPool) -> anyhow::Result<()> {
+    // Given user expired yesterday
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut user_factory = UserFactory::<DateTime<FixedOffset>>::new();
+    user_factory.modified_one(|data, ext| User {
+        user_name: "test_user".to_string(),
+        password: hash_password("password").unwrap(),
+        status: UserStatus::Active,
+        expires_at: Some(ext),
+        deleted_date: None,
+        ..data.clone()
+    });
+    let user = user_factory
+        .generate_one(
+            &app_state.db,
+            Local::now().fixed_offset() - Duration::days(1),
+        )
+        .await?;
+    let mut user_profile_factory = UserProfileFactory::<Uuid>::new();
+    user_profile_factory.modified_one(|data, ext| UserProfile {
+        user_id: ext,
+        ..data.clone()
+    });
+    user_profile_factory
+        .generate_one(&app_state.db, user.id)
+        .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When login
+    let resp = cli
+        .post("/api/auth/login")
+        .body_json(&json!({
+            "user_name": "test_user",
+            "password": "password"
+        }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+    resp.assert_json(&json!({"code": "ACCOUNT_EXPIRED", "message": "user account expired"}))
+        .await;
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_introspect(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let caller = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "caller",
+        "password",
+    )
+    .await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+
+    // When without credentials of the caller
+    let resp = cli
+        .post("/api/auth/introspect")
+        .body_json(&json!({"token": test_user.token}))
+        .send()
+        .await;
+
+    // Expect nothing learned about the token
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("active")
+        .assert_bool(false);
+
+    // When
+    let resp = cli
+        .post("/api/auth/introspect")
+        .header("authorization", format!("Bearer {}", caller.token))
+        .body_json(&json!({"token": test_user.token}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value().object().get("active").assert_bool(true);
+    json.value()
+        .object()
+        .get("sub")
+        .assert_string(&test_user.user.id.to_string());
+
+    // When the session is revoked and for a garbage token
+    cli.post("/api/auth/logout")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    let revoked = cli
+        .post("/api/auth/introspect")
+        .header("authorization", format!("Bearer {}", caller.token))
+        .body_json(&json!({"token": test_user.token}))
+        .send()
+        .await;
+    let garbage = cli
+        .post("/api/auth/introspect")
+        .header("authorization", format!("Bearer {}", caller.token))
+        .body_json(&json!({"token": "not a token"}))
+        .send()
+        .await;
+
+    // Expect
+    revoked.assert_status_is_ok();
+    revoked
+        .json()
+        .await
+        .value()
+        .object()
+        .get("active")
+        .assert_bool(false);
+    garbage.assert_status_is_ok();
+    garbage
+        .json()
+        .await
+        .value()
+        .object()
+        .get("active")
+        .assert_bool(false);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_auth_check(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        &["user.read"],
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When
+    let resp = cli
+        .post("/api/auth/check")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"permissions": [
+            {"permission_name": "user", "attribute": "read"},
+            {"permission_name": "role", "attribute": "create"},
+        ]}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({"results": [
+        {"permission_name": "user", "attribute": "read", "allowed": true},
+        {"permission_name": "role", "attribute": "create", "allowed": false},
+    ]}))
+    .await;
+
+    // When nothing to check
+    let resp = cli
+        .post("/api/auth/check")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"permissions": []}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_login_lockout(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let Some(lockout) = LoginLockout::from_config(&config) else {
+        return Ok(());
+    };
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let admin = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "admin",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &admin.user.id,
+        &["user.update"],
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When wrong password until the last attempt
+    let wrong = json!({"user_name": "test_user", "password": "wrong"});
+    for _ in 1..lockout.attempts {
+        let resp = cli.post("/api/auth/login").body_json(&wrong).send().await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+    }
+    let resp = cli.post("/api/auth/login").body_json(&wrong).send().await;
+
+    // Expect locked, even with the right password
+    resp.assert_status(StatusCode::FORBIDDEN);
+    resp.assert_json(&json!({
+        "code": "ACCOUNT_LOCKED",
+        "message": login_locked_message(lockout.duration),
+    }))
+    .await;
+    let right = json!({"user_name": "test_user", "password": "password"});
+    let resp = cli.post("/api/auth/login").body_json(&right).send().await;
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When unlocked without permission
+    let resp = cli
+        .post(format!("/api/user/unlock?id={}", test_user.user.id))
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When unlocked by admin
+    let resp = cli
+        .post(format!("/api/user/unlock?id={}", test_user.user.id))
+        .header("authorization", format!("Bearer {}", admin.token))
+        .send()
+        .await;
+
+    // Expect login again
     resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli.post("/api/auth/login").body_json(&right).send().await;
+    resp.assert_status_is_ok();
+
+    // When wrong current password on change password until the last attempt
+    let change = json!({
+        "current_password": "wrong",
+        "new_password": "n3w-password",
+        "confirm_new_password": "n3w-password",
+    });
+    for _ in 1..lockout.attempts {
+        let resp = cli
+            .post("/api/auth/change-password")
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .body_json(&change)
+            .send()
+            .await;
+        resp.assert_status(StatusCode::BAD_REQUEST);
+    }
+    let resp = cli
+        .post("/api/auth/change-password")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&change)
+        .send()
+        .await;
+
+    // Expect locked like failed logins
+    resp.assert_status(StatusCode::FORBIDDEN);
+    let resp = cli.post("/api/auth/login").body_json(&right).send().await;
+    resp.assert_status(StatusCode::FORBIDDEN);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_change_password(pool: PgPool) -> anyhow::Result<()> {
+    // Given a user signed in twice
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .post("/api/auth/login")
+        .body_json(&json!({"user_name": "test_user", "password": "password"}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let other_token = json.value().object().get("token").string().to_string();
+    let other_refresh_token = json
+        .value()
+        .object()
+        .get("refresh_token")
+        .string()
+        .to_string();
+
+    // When wrong current password
+    let resp = cli
+        .post("/api/auth/change-password")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "current_password": "wrong",
+            "new_password": "n3w-password",
+            "confirm_new_password": "n3w-password",
+        }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When the new password does not meet the policy
+    let resp = cli
+        .post("/api/auth/change-password")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "current_password": "password",
+            "new_password": "short",
+            "confirm_new_password": "short",
+        }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When
+    let resp = cli
+        .post("/api/auth/change-password")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "current_password": "password",
+            "new_password": "n3w-password",
+            "confirm_new_password": "n3w-password",
+        }))
+        .send()
+        .await;
+
+    // Expect changed, the other session signed out and this one kept
+    resp.assert_status_is_ok();
+    let check = json!({"permissions": [{"permission_name": "user", "attribute": "read"}]});
+    let resp = cli
+        .post("/api/auth/check")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&check)
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = cli
+        .post("/api/auth/check")
+        .header("authorization", format!("Bearer {}", other_token))
+        .body_json(&check)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+    let resp = cli
+        .post("/api/auth/refresh")
+        .body_json(&json!({"refresh_token": other_refresh_token}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+    let resp = cli
+        .post("/api/auth/refresh")
+        .body_json(&json!({"refresh_token": test_user.refresh_token}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = cli
+        .post("/api/auth/login")
+        .body_json(&json!({"user_name": "test_user", "password": "password"}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    let resp = cli
+        .post("/api/auth/login")
+        .body_json(&json!({"user_name": "test_user", "password": "n3w-password"}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_jwks(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When
+    let resp = cli.get("/.well-known/jwks.json").send().await;
+
+    // Expect the public keys of the configured signing key, outside the prefix
+    resp.assert_status_is_ok();
+    resp.assert_json(&serde_json::to_value(jwt_keys().jwks())?)
+        .await;
+    let header = jsonwebtoken::decode_header(&test_user.token)?;
+    assert_eq!(header.kid.as_deref(), Some(jwt_keys().kid()));
     Ok(())
 }
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        permission_cache::{
+            get_permission_cache_rebuild, save_permission_cache_rebuild,
+            spawn_permission_cache_rebuild, PermissionCacheRebuild, DEFAULT_PERMISSION_CACHE_TTL,
+            PERMISSION_ATTRIBUTE, PERMISSION_NAME,
+        },
+        security::{ensure_permission, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string, datetime_to_string_opt},
+        validation::Validate,
+    },
+    schema::cache::{
+        CacheRebuildDetailResponses, CacheRebuildRequest, CacheRebuildResponse,
+        CacheRebuildResponses,
+    },
+    settings::get_config,
+    AppState,
+};
+
+#[derive(Tags)]
+enum ApiCacheTags {
+    Cache,
+}
+
+fn cache_rebuild_response(rebuild: PermissionCacheRebuild) -> CacheRebuildResponse {
+    CacheRebuildResponse {
+        id: rebuild.id.to_string(),
+        status: rebuild.status,
+        total: rebuild.total,
+        done: rebuild.done,
+        failed: rebuild.failed,
+        error: rebuild.error,
+        created_date: datetime_to_string(rebuild.created_date),
+        finished_date: datetime_to_string_opt(rebuild.finished_date),
+    }
+}
+
+/// Required by both endpoints, see ensure_permission
+fn cache_permission() -> String {
+    format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE)
+}
+
+pub struct ApiCache;
+
+#[OpenApi]
+impl ApiCache {
+    /// Rebuild the cached permission sets of every active user, or of the given users
+    ///
+    /// Use after bulk RBAC changes so the caches are warm before traffic hits them.
+    /// The rebuild runs in background, poll it with the returned id.
+    #[oai(
+        path = "/admin/cache/rebuild/",
+        method = "post",
+        tag = "ApiCacheTags::Cache"
+    )]
+    async fn cache_rebuild_api(
+        &self,
+        Json(json): Json<CacheRebuildRequest>,
+        state: Data<&Arc<AppState>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> CacheRebuildResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, &cache_permission()).await?;
+
+            if let Some(errors) = json.validation_errors() {
+                return Ok(CacheRebuildResponses::UnprocessableEntity(Json(errors)));
+            }
+            let config = get_config();
+            if config
+                .permission_cache_ttl
+                .unwrap_or(DEFAULT_PERMISSION_CACHE_TTL)
+                == 0
+            {
+                return Err(AppError::bad_request("permission cache is disabled"));
+            }
+
+            let user_ids = json.user_ids.map(|ids| {
+                let mut res: Vec<Uuid> = ids
+                    .iter()
+                    .map(|x| Uuid::parse_str(x.trim()).unwrap())
+                    .collect();
+                res.sort();
+                res.dedup();
+                res
+            });
+            // saved up front so the rebuild can be polled as soon as the id is returned
+            let rebuild = PermissionCacheRebuild::new();
+            save_permission_cache_rebuild(&mut redis_conn, &rebuild)
+                .await
+                .context("save_permission_cache_rebuild")?;
+            spawn_permission_cache_rebuild(
+                state.db.clone(),
+                state.redis_conn.clone(),
+                config,
+                user_ids,
+                rebuild.clone(),
+            );
+
+            Ok(CacheRebuildResponses::Accepted(Json(
+                cache_rebuild_response(rebuild),
+            )))
+        })
+        .await
+    }
+
+    /// Progress of a permission cache rebuild
+    #[oai(
+        path = "/admin/cache/rebuild/",
+        method = "get",
+        tag = "ApiCacheTags::Cache"
+    )]
+    async fn cache_rebuild_detail_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> CacheRebuildDetailResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, &cache_permission()).await?;
+
+            let not_found =
+                || AppError::not_found(format!("cache rebuild with id = {} not found", id));
+            let rebuild_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let rebuild = get_permission_cache_rebuild(&mut redis_conn, &rebuild_id)
+                .await
+                .context("get_permission_cache_rebuild")?
+                .ok_or_else(not_found)?;
+            Ok(CacheRebuildDetailResponses::Ok(Json(
+                cache_rebuild_response(rebuild),
+            )))
+        })
+        .await
+    }
+}
@@ -0,0 +1,139 @@
+use std::{sync::Arc, time::Duration};
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    cli::seed::{parse_seed_data, seed, DEFAULT_SEED},
+    core::{
+        permission_cache::invalidate_user_permissions,
+        session_store::{create_redis_pool, SessionStore},
+        test_utils::generate_test_user,
+    },
+    init_openapi_route,
+    model::user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_cache_rebuild_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let other_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "other_user",
+        "password",
+    )
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let payload = json!({"user_ids": [other_user.user.id.to_string()]});
+
+    // When request user lacks the permission
+    let resp = cli
+        .post("/api/admin/cache/rebuild")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When request user is admin with an invalid id
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[other_user.user.id]).await?;
+    let resp = cli
+        .post("/api/admin/cache/rebuild")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"user_ids": ["abc"]}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When rebuild the other user
+    let resp = cli
+        .post("/api/admin/cache/rebuild")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect accepted then done
+    resp.assert_status(StatusCode::ACCEPTED);
+    let json = resp.json().await;
+    let id = json.value().object().get("id").string().to_string();
+    let mut status = String::new();
+    for _ in 0..50 {
+        let resp = cli
+            .get("/api/admin/cache/rebuild")
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .query("id", &id)
+            .send()
+            .await;
+        resp.assert_status_is_ok();
+        let json = resp.json().await;
+        let rebuild = json.value().object();
+        status = rebuild.get("status").string().to_string();
+        if status != "running" {
+            rebuild.get("total").assert_i64(1);
+            rebuild.get("done").assert_i64(1);
+            rebuild.get("failed").assert_i64(0);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(status, "done");
+    let cached = redis_conn
+        .get(&format!("core:permissions:{}", other_user.user.id))
+        .await?;
+    assert_eq!(cached, Some("[]".to_string()));
+
+    // When unknown rebuild
+    let resp = cli
+        .get("/api/admin/cache/rebuild")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &Uuid::now_v7().to_string())
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::{consent_type::ConsentType, user_consent::UserConsent},
+    repository::{
+        consent_type::{
+            create_consent_type, get_all_consent_type, get_consent_type_by_id,
+            get_consent_type_by_name, update_consent_type,
+        },
+        user_consent::{
+            create_user_consent, get_latest_user_consent_by_user, get_user_consent_history_by_user,
+        },
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        consent::{
+            ConsentTypeCreateRequest, ConsentTypeCreateResponses, ConsentTypeListResponses,
+            ConsentTypeResponse, ConsentTypeUpdateRequest, ConsentTypeUpdateResponses,
+            UserConsentCreateRequest, UserConsentCreateResponses, UserConsentHistoryResponses,
+            UserConsentListResponses, UserConsentResponse, UserConsentStatusResponse,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiConsentTags {
+    Consent,
+}
+
+fn consent_type_response(consent_type: ConsentType) -> ConsentTypeResponse {
+    ConsentTypeResponse {
+        id: consent_type.id.to_string(),
+        name: consent_type.name,
+        description: consent_type.description,
+        version: consent_type.version,
+        is_active: consent_type.is_active,
+        created_date: datetime_to_string_opt(consent_type.created_date),
+        updated_date: datetime_to_string_opt(consent_type.updated_date),
+    }
+}
+
+fn user_consent_response(user_consent: UserConsent) -> UserConsentResponse {
+    UserConsentResponse {
+        id: user_consent.id.to_string(),
+        consent_type_id: user_consent.consent_type_id.to_string(),
+        version: user_consent.version,
+        accepted: user_consent.accepted,
+        created_date: datetime_to_string_opt(user_consent.created_date),
+    }
+}
+
+pub struct ApiConsent;
+
+#[OpenApi]
+impl ApiConsent {
+    /// List consent types
+    #[oai(
+        path = "/consent/type/",
+        method = "get",
+        tag = "ApiConsentTags::Consent"
+    )]
+    async fn get_all_consent_type_api(
+        &self,
+        Query(is_active): Query<Option<bool>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> ConsentTypeListResponses {
+        respond(async move {
+            let ReadAuthContext { mut db, .. } = ctx;
+            let data = get_all_consent_type(&mut db, is_active).await?;
+
+            Ok(ConsentTypeListResponses::Ok(Json(
+                data.into_iter().map(consent_type_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Create a consent type, starts at version 1
+    #[oai(
+        path = "/consent/type/",
+        method = "post",
+        tag = "ApiConsentTags::Consent"
+    )]
+    async fn create_consent_type_api(
+        &self,
+        Json(json): Json<ConsentTypeCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ConsentTypeCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "consent_type.create",
+            )
+            .await?;
+
+            let name = json.name.trim().to_string();
+            if name.is_empty() {
+                return Ok(ConsentTypeCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["name"],
+                        "name is required".to_string(),
+                    ),
+                )));
+            }
+            if get_consent_type_by_name(&mut tx, &name).await?.is_some() {
+                return Err(AppError::conflict(format!(
+                    "consent type with name = {} already exists",
+                    name
+                )));
+            }
+
+            let now = utc_now();
+            let consent_type = ConsentType {
+                id: Uuid::now_v7(),
+                name,
+                description: json.description,
+                version: 1,
+                is_active: json.is_active.unwrap_or(true),
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+            };
+            create_consent_type(&mut tx, &consent_type).await?;
+            tx.commit().await?;
+
+            Ok(ConsentTypeCreateResponses::Ok(Json(consent_type_response(
+                consent_type,
+            ))))
+        })
+        .await
+    }
+
+    /// Update a consent type
+    ///
+    /// With `new_version` the version is bumped and users must accept the new terms again.
+    #[oai(
+        path = "/consent/type/",
+        method = "put",
+        tag = "ApiConsentTags::Consent"
+    )]
+    async fn update_consent_type_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<ConsentTypeUpdateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ConsentTypeUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "consent_type.update",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "consent type with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_consent_type_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "consent type with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            let name = json.name.trim().to_string();
+            if name.is_empty() {
+                return Ok(ConsentTypeUpdateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["name"],
+                        "name is required".to_string(),
+                    ),
+                )));
+            }
+            match get_consent_type_by_name(&mut tx, &name).await? {
+                Some(val) if val.id != data.id => {
+                    return Err(AppError::conflict(format!(
+                        "consent type with name = {} already exists",
+                        name
+                    )))
+                }
+                _ => {}
+            }
+
+            data.name = name;
+            data.description = json.description;
+            data.is_active = json.is_active.unwrap_or(data.is_active);
+            if json.new_version.unwrap_or(false) {
+                data.version += 1;
+            }
+            data.updated_by = Some(request_user.id);
+            data.updated_date = Some(utc_now());
+            update_consent_type(&mut tx, &data).await?;
+            tx.commit().await?;
+
+            Ok(ConsentTypeUpdateResponses::Ok(Json(consent_type_response(
+                data,
+            ))))
+        })
+        .await
+    }
+
+    /// Consents of the request user
+    ///
+    /// One entry per active consent type, `accepted` is empty when the user never decided.
+    #[oai(path = "/consent/", method = "get", tag = "ApiConsentTags::Consent")]
+    async fn get_user_consent_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserConsentListResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                user: request_user,
+                ..
+            } = ctx;
+            let consent_types = get_all_consent_type(&mut db, Some(true)).await?;
+            let mut latest: HashMap<Uuid, UserConsent> =
+                get_latest_user_consent_by_user(&mut db, &request_user.id)
+                    .await?
+                    .into_iter()
+                    .map(|x| (x.consent_type_id, x))
+                    .collect();
+
+            Ok(UserConsentListResponses::Ok(Json(
+                consent_types
+                    .into_iter()
+                    .map(|consent_type| {
+                        let decision = latest.remove(&consent_type.id);
+                        UserConsentStatusResponse {
+                            outdated: decision
+                                .as_ref()
+                                .is_some_and(|x| x.accepted && x.version < consent_type.version),
+                            accepted: decision.as_ref().map(|x| x.accepted),
+                            version: decision.as_ref().map(|x| x.version),
+                            decided_date: decision
+                                .and_then(|x| datetime_to_string_opt(x.created_date)),
+                            consent_type: consent_type_response(consent_type),
+                        }
+                    })
+                    .collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Every accept and withdraw of the request user, oldest first
+    #[oai(
+        path = "/consent/history/",
+        method = "get",
+        tag = "ApiConsentTags::Consent"
+    )]
+    async fn get_user_consent_history_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserConsentHistoryResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                user: request_user,
+                ..
+            } = ctx;
+            let data = get_user_consent_history_by_user(&mut db, &request_user.id).await?;
+
+            Ok(UserConsentHistoryResponses::Ok(Json(
+                data.into_iter().map(user_consent_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Accept or withdraw a consent for the request user
+    #[oai(path = "/consent/", method = "post", tag = "ApiConsentTags::Consent")]
+    async fn create_user_consent_api(
+        &self,
+        Json(json): Json<UserConsentCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserConsentCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            let consent_type_id = match Uuid::parse_str(&json.consent_type_id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "consent type with id = {} not found",
+                        json.consent_type_id
+                    )))
+                }
+            };
+            let consent_type = get_consent_type_by_id(&mut tx, &consent_type_id).await?;
+            let consent_type = match consent_type {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "consent type with id = {} not found",
+                        consent_type_id
+                    )))
+                }
+            };
+            // withdrawing stays possible after a consent type is retired
+            if json.accepted && !consent_type.is_active {
+                return Err(AppError::bad_request(format!(
+                    "consent type {} is not active",
+                    consent_type.name
+                )));
+            }
+
+            let now = utc_now();
+            let data = create_user_consent(
+                &mut tx,
+                &request_user.id,
+                &consent_type,
+                json.accepted,
+                &now,
+            )
+            .await?;
+            tx.commit().await?;
+
+            Ok(UserConsentCreateResponses::Ok(Json(user_consent_response(
+                data,
+            ))))
+        })
+        .await
+    }
+}
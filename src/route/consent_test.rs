@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["consent_type.create", "consent_type.update"];
+
+#[sqlx::test]
+async fn test_consent_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .post("/api/consent/type")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "marketing", "description": "Marketing emails"}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let consent_type_id = resp
+        .json()
+        .await
+        .value()
+        .object()
+        .get("id")
+        .string()
+        .to_string();
+    let resp = cli
+        .post("/api/consent/type")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "marketing"}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CONFLICT);
+
+    // When accept
+    let resp = cli
+        .post("/api/consent")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"consent_type_id": consent_type_id, "accepted": true}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::CREATED);
+    let resp = cli
+        .get("/api/consent")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(1);
+    let item = list.get(0).object();
+    item.get("accepted").assert_bool(true);
+    item.get("version").assert_i64(1);
+    item.get("outdated").assert_bool(false);
+
+    // When terms change
+    let resp = cli
+        .put("/api/consent/type")
+        .query("id", &consent_type_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "marketing", "new_version": true}))
+        .send()
+        .await;
+
+    // Expect previous acceptance outdated
+    resp.assert_status_is_ok();
+    let resp = cli
+        .get("/api/consent")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    let json = resp.json().await;
+    let item = json.value().array().get(0).object();
+    item.get("outdated").assert_bool(true);
+    item.get("consent_type")
+        .object()
+        .get("version")
+        .assert_i64(2);
+
+    // When withdraw
+    let resp = cli
+        .post("/api/consent")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"consent_type_id": consent_type_id, "accepted": false}))
+        .send()
+        .await;
+
+    // Expect history keeps both records
+    resp.assert_status(StatusCode::CREATED);
+    let resp = cli
+        .get("/api/consent/history")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(2);
+    list.get(0).object().get("accepted").assert_bool(true);
+    list.get(1).object().get("accepted").assert_bool(false);
+    list.get(1).object().get("version").assert_i64(2);
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use poem::{http::Method, test::TestClient};
+use serde_json::{json, Map, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        api_version::LEGACY_API_VERSION, session_store::create_redis_pool,
+        test_utils::generate_test_user,
+    },
+    init_openapi_route, init_openapi_service,
+    settings::get_config,
+    AppState,
+};
+
+const PREFIX: &str = "/api";
+
+/// Schema behind a #/components/schemas reference
+fn resolve<'a>(spec: &'a Value, schema: &'a Value) -> &'a Value {
+    match schema.get("$ref").and_then(Value::as_str) {
+        Some(val) => {
+            let name = val.trim_start_matches("#/components/schemas/");
+            resolve(spec, &spec["components"]["schemas"][name])
+        }
+        None => schema,
+    }
+}
+
+/// Schema of the json media type of a request body or response, poem names it with a
+/// charset e.g. `application/json; charset=utf-8`
+fn json_schema(item: &Value) -> Option<&Value> {
+    item.get("content")?
+        .as_object()?
+        .iter()
+        .find(|(media_type, _)| media_type.starts_with("application/json"))
+        .and_then(|(_, media)| media.get("schema"))
+}
+
+/// Smallest value the schema accepts, only required properties are filled
+fn sample(spec: &Value, schema: &Value) -> Value {
+    let schema = resolve(spec, schema);
+    if let Some(val) = schema.get("enum").and_then(|x| x.get(0)) {
+        return val.clone();
+    }
+    for key in ["allOf", "oneOf", "anyOf"] {
+        if let Some(val) = schema.get(key).and_then(|x| x.get(0)) {
+            return sample(spec, val);
+        }
+    }
+    match schema.get("type").and_then(Value::as_str) {
+        Some("object") => {
+            let mut obj = Map::new();
+            let required = schema.get("required").and_then(Value::as_array);
+            for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                obj.insert(name.to_string(), sample(spec, &schema["properties"][name]));
+            }
+            Value::Object(obj)
+        }
+        Some("array") => json!([]),
+        Some("integer") | Some("number") => json!(1),
+        Some("boolean") => json!(false),
+        // uuid strings pass as ids, names and codes alike
+        Some("string") => match schema.get("format").and_then(Value::as_str) {
+            Some("email") => json!("contract@example.com"),
+            Some("date") => json!("2025-01-01"),
+            Some("date-time") => json!("2025-01-01T00:00:00Z"),
+            _ => json!(Uuid::now_v7().to_string()),
+        },
+        _ => Value::Null,
+    }
+}
+
+/// Collect where value does not match schema, optional properties may be null
+fn conform(spec: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = resolve(spec, schema);
+    if let Some(items) = schema.get("allOf").and_then(Value::as_array) {
+        for item in items {
+            conform(spec, item, value, path, errors);
+        }
+        return;
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(items) = schema.get(key).and_then(Value::as_array) {
+            let is_match = items.iter().any(|item| {
+                let mut item_errors = vec![];
+                conform(spec, item, value, path, &mut item_errors);
+                item_errors.is_empty()
+            });
+            if !is_match {
+                errors.push(format!("{} matches none of {}", path, key));
+            }
+            return;
+        }
+    }
+    let is_type = match schema.get("type").and_then(Value::as_str) {
+        Some("object") => {
+            let Some(obj) = value.as_object() else {
+                errors.push(format!("{} expected object got {}", path, value));
+                return;
+            };
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .collect();
+            for name in required.iter() {
+                if !obj.contains_key(*name) {
+                    errors.push(format!("{}.{} is required", path, name));
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, val) in obj {
+                let val_path = format!("{}.{}", path, name);
+                match (
+                    properties.and_then(|x| x.get(name)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(_), _) if val.is_null() && !required.contains(&name.as_str()) => {}
+                    (Some(prop), _) => conform(spec, prop, val, &val_path, errors),
+                    (None, Some(extra)) if extra.is_object() => {
+                        conform(spec, extra, val, &val_path, errors)
+                    }
+                    (None, _) if properties.is_some() => {
+                        errors.push(format!("{} is not documented", val_path))
+                    }
+                    (None, _) => {}
+                }
+            }
+            return;
+        }
+        Some("array") => {
+            let Some(items) = value.as_array() else {
+                errors.push(format!("{} expected array got {}", path, value));
+                return;
+            };
+            for (idx, item) in items.iter().enumerate() {
+                conform(
+                    spec,
+                    &schema["items"],
+                    item,
+                    &format!("{}[{}]", path, idx),
+                    errors,
+                );
+            }
+            return;
+        }
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+    if !is_type {
+        errors.push(format!(
+            "{} expected {} got {}",
+            path, schema["type"], value
+        ));
+        return;
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            errors.push(format!("{} {} is not a documented value", path, value));
+        }
+    }
+}
+
+#[sqlx::test]
+async fn test_responses_conform_to_openapi_spec(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some(PREFIX.to_string());
+    config.rate_limit_per_minute = Some(0);
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let spec: Value =
+        serde_json::from_str(&init_openapi_service(PREFIX, LEGACY_API_VERSION).spec())?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+    let mut operations: Vec<(&String, &String, &Value)> = vec![];
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for (method, operation) in item.as_object().unwrap() {
+            operations.push((path, method, operation));
+        }
+    }
+    // logout revokes the token every other call uses
+    operations.sort_by_key(|(path, _, _)| path.ends_with("/logout"));
+
+    // When every operation is called with the smallest request its schema accepts
+    let mut errors: Vec<String> = vec![];
+    for (path, method, operation) in operations {
+        let name = format!("{} {}", method.to_uppercase(), path);
+        let mut req = cli
+            .request(
+                Method::from_bytes(method.to_uppercase().as_bytes())?,
+                format!("{}{}", PREFIX, path),
+            )
+            .header("authorization", format!("Bearer {}", test_user.token));
+        for param in operation["parameters"].as_array().into_iter().flatten() {
+            if param["in"] != "query" || param["required"] != true {
+                continue;
+            }
+            let val = match sample(&spec, &param["schema"]) {
+                Value::String(val) => val,
+                val => val.to_string(),
+            };
+            req = req.query(param["name"].as_str().unwrap(), &val);
+        }
+        if let Some(schema) = json_schema(&operation["requestBody"]) {
+            req = req.body_json(&sample(&spec, schema));
+        }
+        let resp = req.send().await;
+
+        // Expect a documented status with a body matching its schema
+        let status = resp.0.status().as_u16().to_string();
+        let Some(documented) = operation["responses"].get(&status) else {
+            errors.push(format!("{} answered undocumented {}", name, status));
+            continue;
+        };
+        let is_json = resp
+            .0
+            .headers()
+            .get("content-type")
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.starts_with("application/json"));
+        let body = resp.0.into_body().into_string().await?;
+        match json_schema(documented) {
+            Some(schema) if is_json => conform(
+                &spec,
+                schema,
+                &serde_json::from_str(&body)?,
+                &format!("{} {}", name, status),
+                &mut errors,
+            ),
+            Some(_) => errors.push(format!("{} answered {} without json", name, status)),
+            None => {}
+        }
+    }
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::str::FromStr;
+
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+
+use crate::{
+    core::{
+        classification::{attributes_of_class, effective_protection, DataClass},
+        error::respond,
+        security::{ensure_permission, BearerAuthorization, ReadAuthContext},
+    },
+    schema::{
+        common::BadRequestResponse,
+        data_classification::{
+            ClassifiedAttributeResponse, DataClassReportResponse, DataClassificationReportResponses,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiDataClassificationTags {
+    DataClassification,
+}
+
+pub struct ApiDataClassification;
+
+#[OpenApi]
+impl ApiDataClassification {
+    /// Where each class of data (pii, sensitive, public) is stored and how it is protected
+    #[oai(
+        path = "/data-classification/",
+        method = "get",
+        tag = "ApiDataClassificationTags::DataClassification"
+    )]
+    async fn data_classification_report_api(
+        &self,
+        Query(class): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> DataClassificationReportResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
+
+            let classes = match class {
+                Some(val) => match DataClass::from_str(&val) {
+                    Ok(val) => vec![val],
+                    Err(err) => {
+                        return Ok(DataClassificationReportResponses::BadRequest(Json(
+                            BadRequestResponse::new(format!("class {}", err)),
+                        )))
+                    }
+                },
+                None => DataClass::ALL.to_vec(),
+            };
+
+            Ok(DataClassificationReportResponses::Ok(Json(
+                classes
+                    .into_iter()
+                    .map(|class| DataClassReportResponse {
+                        class: class.to_string(),
+                        attributes: attributes_of_class(class)
+                            .into_iter()
+                            .map(|x| ClassifiedAttributeResponse {
+                                store: x.store.to_string(),
+                                location: x.location.to_string(),
+                                attribute: x.attribute.to_string(),
+                                protection: effective_protection(x).to_string(),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            )))
+        })
+        .await
+    }
+}
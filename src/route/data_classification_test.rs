@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use sqlx::PgPool;
+
+use crate::{
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["user.read"];
+
+#[sqlx::test]
+async fn test_data_classification_report_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When
+    let resp = cli
+        .get("/api/data-classification")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect every class reported
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(3);
+    list.get(0).object().get("class").assert_string("pii");
+
+    // When filter by class
+    let resp = cli
+        .get("/api/data-classification")
+        .query("class", &"sensitive")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(1);
+    let report = list.get(0).object();
+    report.get("class").assert_string("sensitive");
+    assert!(report
+        .get("attributes")
+        .array()
+        .iter()
+        .any(|x| x.object().get("attribute").string() == "password"
+            && x.object().get("protection").string() == "hashed"));
+
+    // When unknown class
+    let resp = cli
+        .get("/api/data-classification")
+        .query("class", &"secret")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    Ok(())
+}
@@ -0,0 +1,590 @@
+use std::sync::Arc;
+
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        directory_sync::run_directory_sync,
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::{
+        directory_source::{DirectorySource, PROVIDER_AZURE_AD, PROVIDER_LDAP},
+        directory_sync_run::{DirectorySyncRun, STATUS_FAILED, STATUS_SUCCESS},
+        user::User,
+    },
+    repository::{
+        directory_source::{
+            create_directory_source, get_directory_source_by_id, paginate_directory_source,
+            soft_delete_directory_source, update_directory_source,
+        },
+        directory_sync_run::{get_latest_directory_sync_run, paginate_directory_sync_run},
+        user::{get_user_by_id, UserLookup},
+    },
+    schema::{
+        common::{BadRequestResponse, PaginateResponse, UnprocessableEntityResponse},
+        directory_source::{
+            DirectorySourceCreateRequest, DirectorySourceCreateResponses,
+            DirectorySourceDeleteResponses, DirectorySourceDetailResponses,
+            DirectorySourceDetailSuccessResponse, DirectorySourceDetailUser,
+            DirectorySourceResponse, DirectorySourceUpdateRequest, DirectorySourceUpdateResponses,
+            DirectorySyncResponses, DirectorySyncRunResponse, PaginateDirectorySourceResponses,
+            PaginateDirectorySyncRunResponses,
+        },
+    },
+    AppState,
+};
+
+#[derive(Tags)]
+enum ApiDirectorySourceTags {
+    DirectorySource,
+}
+
+pub struct ApiDirectorySource;
+
+fn validate_url(field: &str, value: &Option<String>, schemes: &[&str]) -> Option<String> {
+    match value {
+        Some(value) => match reqwest::Url::parse(value) {
+            Ok(val) if schemes.contains(&val.scheme()) => None,
+            Ok(_) => Some(format!(
+                "{} must use {} scheme",
+                field,
+                schemes.join(" or ")
+            )),
+            Err(err) => Some(format!("invalid {}: {}", field, err)),
+        },
+        None => None,
+    }
+}
+
+/// Check the settings required by the source provider
+fn validate_directory_source(source: &DirectorySource) -> Option<String> {
+    if source.sync_interval < 1 {
+        return Some("sync_interval must be at least 1 minute".to_string());
+    }
+    match source.provider.as_str() {
+        PROVIDER_LDAP => {
+            if source.ldap_url.is_none() || source.ldap_base_dn.is_none() {
+                return Some("ldap provider require ldap_url and ldap_base_dn".to_string());
+            }
+            validate_url("ldap_url", &source.ldap_url, &["ldap", "ldaps"])
+        }
+        PROVIDER_AZURE_AD => {
+            if source.azure_tenant_id.is_none()
+                || source.azure_client_id.is_none()
+                || source.azure_client_secret.is_none()
+            {
+                return Some(
+                    "azure_ad provider require azure_tenant_id, azure_client_id and azure_client_secret"
+                        .to_string(),
+                );
+            }
+            validate_url(
+                "azure_authority_url",
+                &source.azure_authority_url,
+                &["http", "https"],
+            )
+            .or(validate_url(
+                "azure_graph_url",
+                &source.azure_graph_url,
+                &["http", "https"],
+            ))
+        }
+        _ => Some(format!(
+            "provider must be one of {}, {}",
+            PROVIDER_LDAP, PROVIDER_AZURE_AD
+        )),
+    }
+}
+
+async fn get_detail_user(
+    conn: &mut PgConnection,
+    id: Option<Uuid>,
+) -> anyhow::Result<Option<DirectorySourceDetailUser>> {
+    let user = match id {
+        Some(id) => get_user_by_id(conn, &id, None).await?.0,
+        None => None,
+    };
+    Ok(user.as_ref().map(detail_user))
+}
+
+fn detail_user(user: &User) -> DirectorySourceDetailUser {
+    DirectorySourceDetailUser {
+        id: user.id.to_string(),
+        user_name: user.user_name.clone(),
+    }
+}
+
+fn source_to_response(
+    source: DirectorySource,
+    created_by: Option<DirectorySourceDetailUser>,
+    updated_by: Option<DirectorySourceDetailUser>,
+) -> DirectorySourceResponse {
+    DirectorySourceResponse {
+        id: source.id.to_string(),
+        name: source.name,
+        provider: source.provider,
+        is_active: source.is_active,
+        sync_interval: source.sync_interval,
+        ldap_url: source.ldap_url,
+        ldap_bind_dn: source.ldap_bind_dn,
+        has_ldap_bind_password: source.ldap_bind_password.is_some(),
+        ldap_base_dn: source.ldap_base_dn,
+        ldap_user_filter: source.ldap_user_filter,
+        ldap_group_filter: source.ldap_group_filter,
+        azure_tenant_id: source.azure_tenant_id,
+        azure_client_id: source.azure_client_id,
+        has_azure_client_secret: source.azure_client_secret.is_some(),
+        azure_authority_url: source.azure_authority_url,
+        azure_graph_url: source.azure_graph_url,
+        last_sync_date: datetime_to_string_opt(source.last_sync_date),
+        created_by,
+        updated_by,
+        created_date: datetime_to_string_opt(source.created_date),
+        updated_date: datetime_to_string_opt(source.updated_date),
+    }
+}
+
+fn run_to_response(run: DirectorySyncRun) -> DirectorySyncRunResponse {
+    DirectorySyncRunResponse {
+        id: run.id.to_string(),
+        source_id: run.source_id.to_string(),
+        status: run.status,
+        users_created: run.users_created,
+        users_updated: run.users_updated,
+        users_deactivated: run.users_deactivated,
+        groups_created: run.groups_created,
+        memberships_added: run.memberships_added,
+        memberships_removed: run.memberships_removed,
+        error: run.error,
+        started_date: datetime_to_string_opt(run.started_date),
+        finished_date: datetime_to_string_opt(run.finished_date),
+    }
+}
+
+#[OpenApi]
+impl ApiDirectorySource {
+    #[oai(
+        path = "/directory-source/",
+        method = "get",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    async fn paginate_directory_source_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(search): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateDirectorySourceResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                paginate_directory_source(&mut db, page, page_size, search).await?;
+
+            let users = UserLookup::load(
+                &mut db,
+                data.iter().flat_map(|x| [x.created_by, x.updated_by]),
+            )
+            .await?;
+            let mut results: Vec<DirectorySourceResponse> = vec![];
+            for item in data {
+                let created_by = users.get(item.created_by).map(detail_user);
+                let updated_by = users.get(item.updated_by).map(detail_user);
+                results.push(source_to_response(item, created_by, updated_by));
+            }
+
+            Ok(PaginateDirectorySourceResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results,
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/directory-source/detail/",
+        method = "get",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    async fn get_detail_directory_source_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> DirectorySourceDetailResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "directory source with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_directory_source_by_id(&mut db, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "directory source with id = {} not found",
+                    id
+                )));
+            }
+            let data = data.unwrap();
+
+            let last_run = get_latest_directory_sync_run(&mut db, &data.id).await?;
+            let created_by = get_detail_user(&mut db, data.created_by).await?;
+            let updated_by = get_detail_user(&mut db, data.updated_by).await?;
+            Ok(DirectorySourceDetailResponses::Ok(Json(
+                DirectorySourceDetailSuccessResponse {
+                    source: source_to_response(data, created_by, updated_by),
+                    last_run: last_run.map(run_to_response),
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/directory-source/",
+        method = "post",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    async fn create_directory_source_api(
+        &self,
+        Json(json): Json<DirectorySourceCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> DirectorySourceCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.create",
+            )
+            .await?;
+
+            let now = utc_now();
+            let new_source = DirectorySource {
+                id: Uuid::now_v7(),
+                name: json.name,
+                provider: json.provider,
+                is_active: json.is_active,
+                sync_interval: json.sync_interval.unwrap_or(60),
+                ldap_url: json.ldap_url,
+                ldap_bind_dn: json.ldap_bind_dn,
+                ldap_bind_password: json.ldap_bind_password,
+                ldap_base_dn: json.ldap_base_dn,
+                ldap_user_filter: json.ldap_user_filter,
+                ldap_group_filter: json.ldap_group_filter,
+                azure_tenant_id: json.azure_tenant_id,
+                azure_client_id: json.azure_client_id,
+                azure_client_secret: json.azure_client_secret,
+                azure_authority_url: json.azure_authority_url,
+                azure_graph_url: json.azure_graph_url,
+                last_sync_date: None,
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+                deleted_date: None,
+            };
+            if let Some(message) = validate_directory_source(&new_source) {
+                return Ok(DirectorySourceCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&[], message),
+                )));
+            }
+
+            create_directory_source(&mut tx, &new_source).await?;
+            tx.commit().await?;
+            let detail_user = DirectorySourceDetailUser {
+                id: request_user.id.to_string(),
+                user_name: request_user.user_name,
+            };
+            Ok(DirectorySourceCreateResponses::Ok(Json(
+                source_to_response(new_source, Some(detail_user), None),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/directory-source/",
+        method = "put",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    async fn update_directory_source_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<DirectorySourceUpdateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> DirectorySourceUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.update",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "directory source with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_directory_source_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "directory source with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            data.name = json.name;
+            data.provider = json.provider;
+            data.is_active = json.is_active;
+            data.sync_interval = json.sync_interval.unwrap_or(data.sync_interval);
+            data.ldap_url = json.ldap_url;
+            data.ldap_bind_dn = json.ldap_bind_dn;
+            if json.ldap_bind_password.is_some() {
+                data.ldap_bind_password = json.ldap_bind_password;
+            }
+            data.ldap_base_dn = json.ldap_base_dn;
+            data.ldap_user_filter = json.ldap_user_filter;
+            data.ldap_group_filter = json.ldap_group_filter;
+            data.azure_tenant_id = json.azure_tenant_id;
+            data.azure_client_id = json.azure_client_id;
+            if json.azure_client_secret.is_some() {
+                data.azure_client_secret = json.azure_client_secret;
+            }
+            data.azure_authority_url = json.azure_authority_url;
+            data.azure_graph_url = json.azure_graph_url;
+            data.updated_by = Some(request_user.id);
+            data.updated_date = Some(utc_now());
+            if let Some(message) = validate_directory_source(&data) {
+                return Ok(DirectorySourceUpdateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&[], message),
+                )));
+            }
+
+            update_directory_source(&mut tx, &data).await?;
+            let created_by = get_detail_user(&mut tx, data.created_by).await?;
+
+            tx.commit().await?;
+            let updated_by = DirectorySourceDetailUser {
+                id: request_user.id.to_string(),
+                user_name: request_user.user_name,
+            };
+            Ok(DirectorySourceUpdateResponses::Ok(Json(
+                source_to_response(data, created_by, Some(updated_by)),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/directory-source/",
+        method = "delete",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    async fn delete_directory_source_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> DirectorySourceDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.delete",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "directory source with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_directory_source_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "directory source with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            soft_delete_directory_source(&mut tx, &mut data, request_user, None).await?;
+
+            tx.commit().await?;
+            Ok(DirectorySourceDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Run the sync now and return its summary, failed runs are returned with status failed
+    #[oai(
+        path = "/directory-source/sync/",
+        method = "post",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    async fn sync_directory_source_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        state: Data<&Arc<AppState>>,
+        _auth: BearerAuthorization,
+    ) -> DirectorySyncResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.update").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "directory source with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_directory_source_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "directory source with id = {} not found",
+                    id
+                )));
+            }
+            let data = data.unwrap();
+            // sync manage its own transactions
+            tx.commit().await?;
+
+            let run = run_directory_sync(&state.db, &mut redis_conn, &data).await?;
+            Ok(DirectorySyncResponses::Ok(Json(run_to_response(run))))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/directory-source/sync-run/",
+        method = "get",
+        tag = "ApiDirectorySourceTags::DirectorySource"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn paginate_directory_sync_run_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(source_id): Query<Option<String>>,
+        Query(status): Query<Option<String>>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateDirectorySyncRunResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.read").await?;
+
+            let source_id = match source_id {
+                Some(val) => match Uuid::parse_str(&val) {
+                    Ok(val) => Some(val),
+                    Err(_) => {
+                        return Ok(PaginateDirectorySyncRunResponses::BadRequest(Json(
+                            BadRequestResponse::new(format!("invalid source_id {}", val)),
+                        )))
+                    }
+                },
+                None => None,
+            };
+            if let Some(val) = &status {
+                if ![STATUS_SUCCESS, STATUS_FAILED].contains(&val.as_str()) {
+                    return Ok(PaginateDirectorySyncRunResponses::BadRequest(Json(
+                        BadRequestResponse::new(format!(
+                            "status must be one of {}, {}",
+                            STATUS_SUCCESS, STATUS_FAILED
+                        )),
+                    )));
+                }
+            }
+
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                paginate_directory_sync_run(&mut tx, page, page_size, source_id, status).await?;
+
+            Ok(PaginateDirectorySyncRunResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results: data.into_iter().map(run_to_response).collect(),
+                },
+            )))
+        })
+        .await
+    }
+}
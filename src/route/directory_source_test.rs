@@ -0,0 +1,433 @@
+use std::sync::Arc;
+
+use chrono::Local;
+use poem::{
+    get, handler,
+    http::StatusCode,
+    listener::{Acceptor, TcpAcceptor},
+    post,
+    test::TestClient,
+    web::{Json, Path},
+    Request, Route, Server,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    factory::user::UserFactory,
+    init_openapi_route,
+    model::{
+        directory_source::{DirectorySource, TABLE_NAME},
+        group::{Group, TABLE_NAME as GROUP_TABLE_NAME},
+        user::{User, UserStatus, TABLE_NAME as USER_TABLE_NAME},
+        user_group_roles::{UserGroupRoles, TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME},
+        user_profile::{UserProfile, TABLE_NAME as USER_PROFILE_TABLE_NAME},
+    },
+    settings::get_config,
+    AppState,
+};
+
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &[
+    "provisioning.create",
+    "provisioning.read",
+    "provisioning.update",
+    "provisioning.delete",
+];
+
+#[handler]
+fn mock_graph_token() -> Json<Value> {
+    Json(json!({"access_token": "graph-token", "token_type": "Bearer", "expires_in": 3600}))
+}
+
+#[handler]
+fn mock_graph_users(req: &Request) -> Json<Value> {
+    // second page is served through @odata.nextLink
+    if req.uri().query().is_some_and(|x| x.contains("skiptoken")) {
+        return Json(json!({"value": [{
+            "id": "b1",
+            "userPrincipalName": "bob@corp.local",
+            "givenName": "Bob",
+            "surname": null,
+            "mail": "bob@corp.local",
+            "accountEnabled": false,
+        }]}));
+    }
+    let host = req.headers().get("host").unwrap().to_str().unwrap();
+    Json(json!({
+        "value": [{
+            "id": "a1",
+            "userPrincipalName": "alice@corp.local",
+            "givenName": "Alice",
+            "surname": "Smith",
+            "mail": "alice@corp.local",
+            "accountEnabled": true,
+        }],
+        "@odata.nextLink": format!("http://{}/v1.0/users?$skiptoken=2", host),
+    }))
+}
+
+#[handler]
+fn mock_graph_groups() -> Json<Value> {
+    Json(json!({"value": [{"id": "g1", "displayName": "Engineering"}]}))
+}
+
+#[handler]
+fn mock_graph_group_members(Path(id): Path<String>) -> Json<Value> {
+    match id.as_str() {
+        "g1" => Json(json!({"value": [{"id": "a1"}]})),
+        _ => Json(json!({"value": []})),
+    }
+}
+
+/// Run a fake microsoft graph api, return its base url
+async fn run_mock_graph_server() -> anyhow::Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let acceptor = TcpAcceptor::from_tokio(listener)?;
+    let addr = acceptor.local_addr()[0].clone();
+    let app = Route::new()
+        .at("/:tenant/oauth2/v2.0/token", post(mock_graph_token))
+        .at("/v1.0/users", get(mock_graph_users))
+        .at("/v1.0/groups", get(mock_graph_groups))
+        .at("/v1.0/groups/:id/members", get(mock_graph_group_members));
+    tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+    Ok(format!("http://{}", addr.as_socket_addr().unwrap()))
+}
+
+#[sqlx::test]
+async fn test_crud_directory_source_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When Create without required ldap settings
+    let resp = cli
+        .post("/api/directory-source")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "corp",
+            "provider": "ldap",
+            "ldap_url": "ldap://ldap.corp.local",
+        }))
+        .send()
+        .await;
+
+    // Expect Create without required ldap settings
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When Create
+    let resp = cli
+        .post("/api/directory-source")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "corp",
+            "provider": "ldap",
+            "is_active": true,
+            "ldap_url": "ldaps://ldap.corp.local",
+            "ldap_bind_dn": "cn=reader,dc=corp,dc=local",
+            "ldap_bind_password": "secret",
+            "ldap_base_dn": "dc=corp,dc=local",
+        }))
+        .send()
+        .await;
+
+    // Expect Create
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("has_ldap_bind_password").assert_bool(true);
+    json.get("sync_interval").assert_i64(60);
+    let source: DirectorySource =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE name = $1", TABLE_NAME).as_str())
+            .bind("corp")
+            .fetch_one(&mut *db)
+            .await?;
+    assert_eq!(source.ldap_bind_password, Some("secret".to_string()));
+
+    // When Update without password
+    let resp = cli
+        .put("/api/directory-source")
+        .query("id", &source.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "corp-2",
+            "provider": "ldap",
+            "is_active": false,
+            "sync_interval": 15,
+            "ldap_url": "ldaps://ldap.corp.local",
+            "ldap_bind_dn": "cn=reader,dc=corp,dc=local",
+            "ldap_base_dn": "dc=corp,dc=local",
+            "ldap_group_filter": "(objectClass=groupOfNames)",
+        }))
+        .send()
+        .await;
+
+    // Expect Update keep stored password
+    resp.assert_status_is_ok();
+    let updated: DirectorySource =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(source.id)
+            .fetch_one(&mut *db)
+            .await?;
+    assert_eq!(updated.name, "corp-2".to_string());
+    assert_eq!(updated.ldap_bind_password, Some("secret".to_string()));
+    assert_eq!(updated.sync_interval, 15);
+    assert_eq!(
+        updated.ldap_group_filter,
+        Some("(objectClass=groupOfNames)".to_string())
+    );
+
+    // When Delete
+    let resp = cli
+        .delete("/api/directory-source")
+        .query("id", &source.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect Delete
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .get("/api/directory-source")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("counts")
+        .assert_i64(0);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_sync_directory_source_from_azure_ad(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let graph_url = run_mock_graph_server().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .post("/api/directory-source")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "azure",
+            "provider": "azure_ad",
+            "is_active": true,
+            "azure_tenant_id": "tenant",
+            "azure_client_id": "client",
+            "azure_client_secret": "secret",
+            "azure_authority_url": graph_url,
+            "azure_graph_url": graph_url,
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let source_id: Uuid = resp.json().await.value().object().get("id").deserialize();
+
+    // local account with the same user name is adopted
+    let mut user_factory = UserFactory::<()>::new();
+    user_factory.modified_one(|x, _| {
+        let mut x = x.clone();
+        x.user_name = "alice@corp.local".to_string();
+        x.status = UserStatus::Active;
+        x.deleted_date = None;
+        x
+    });
+    let alice = user_factory.generate_one(&app_state.db, ()).await?;
+    sqlx::query(
+        r#"INSERT INTO public.user_profile (id, user_id, first_name, last_name, address, email)
+        VALUES ($1, $1, 'Al', NULL, NULL, NULL)"#,
+    )
+    .bind(alice.id)
+    .execute(&mut *db)
+    .await?;
+    // previously synced user no longer in directory
+    user_factory.modified_one(|x, _| {
+        let mut x = x.clone();
+        x.user_name = "gone@corp.local".to_string();
+        x.status = UserStatus::Active;
+        x.deleted_date = None;
+        x
+    });
+    let gone = user_factory.generate_one(&app_state.db, ()).await?;
+    let now = Local::now().fixed_offset();
+    sqlx::query(
+        r#"INSERT INTO public.user_identity (id, user_id, provider, subject, directory_source_id,
+        created_date, updated_date) VALUES ($1, $2, 'azure_ad', 'gone', $3, $4, $4)"#,
+    )
+    .bind(Uuid::now_v7())
+    .bind(gone.id)
+    .bind(source_id)
+    .bind(now)
+    .execute(&mut *db)
+    .await?;
+
+    // When
+    let resp = cli
+        .post("/api/directory-source/sync")
+        .query("id", &source_id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("status").assert_string("success");
+    json.get("users_created").assert_i64(1);
+    json.get("users_updated").assert_i64(1);
+    json.get("users_deactivated").assert_i64(1);
+    json.get("groups_created").assert_i64(1);
+    json.get("memberships_added").assert_i64(1);
+    json.get("memberships_removed").assert_i64(0);
+
+    let bob: User =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE user_name = $1", USER_TABLE_NAME).as_str())
+            .bind("bob@corp.local")
+            .fetch_one(&mut *db)
+            .await?;
+    assert!(!bob.is_active());
+    let alice_profile: UserProfile = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(alice.id)
+    .fetch_one(&mut *db)
+    .await?;
+    assert_eq!(alice_profile.first_name, Some("Alice".to_string()));
+    assert_eq!(alice_profile.last_name, Some("Smith".to_string()));
+    assert_eq!(alice_profile.email, Some("alice@corp.local".to_string()));
+    let gone: User =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", USER_TABLE_NAME).as_str())
+            .bind(gone.id)
+            .fetch_one(&mut *db)
+            .await?;
+    assert!(!gone.is_active());
+    let group: Group = sqlx::query_as(
+        format!("SELECT * FROM {} WHERE group_name = $1", GROUP_TABLE_NAME).as_str(),
+    )
+    .bind("Engineering")
+    .fetch_one(&mut *db)
+    .await?;
+    let memberships: Vec<UserGroupRoles> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE group_id = $1",
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(group.id)
+    .fetch_all(&mut *db)
+    .await?;
+    assert_eq!(memberships.len(), 1);
+    assert_eq!(memberships[0].user_id, Some(alice.id));
+
+    // When sync again
+    let resp = cli
+        .post("/api/directory-source/sync")
+        .query("id", &source_id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect nothing left to reconcile
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("status").assert_string("success");
+    json.get("users_created").assert_i64(0);
+    json.get("users_updated").assert_i64(0);
+    json.get("users_deactivated").assert_i64(0);
+    json.get("memberships_added").assert_i64(0);
+
+    // When get detail and sync history
+    let resp = cli
+        .get("/api/directory-source/detail")
+        .query("id", &source_id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("has_azure_client_secret").assert_bool(true);
+    json.get("last_run")
+        .object()
+        .get("status")
+        .assert_string("success");
+    let resp = cli
+        .get("/api/directory-source/sync-run")
+        .query("source_id", &source_id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("counts")
+        .assert_i64(2);
+    Ok(())
+}
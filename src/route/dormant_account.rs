@@ -0,0 +1,115 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::user_activity::{
+        UserActivity, PERMISSION_ATTRIBUTE_READ, PERMISSION_ATTRIBUTE_UPDATE, PERMISSION_NAME,
+    },
+    repository::{
+        user::get_user_by_id,
+        user_activity::{get_dormant_user_activity, set_user_dormant_exemption},
+    },
+    schema::dormant_account::{
+        DormantAccountExemptionRequest, DormantAccountExemptionResponses,
+        DormantAccountListResponses, DormantAccountResponse,
+    },
+};
+
+#[derive(Tags)]
+enum ApiDormantAccountTags {
+    DormantAccount,
+}
+
+pub struct ApiDormantAccount;
+
+fn dormant_account_response(activity: UserActivity) -> DormantAccountResponse {
+    DormantAccountResponse {
+        user_id: activity.user_id.to_string(),
+        last_login_date: datetime_to_string_opt(activity.last_login_date),
+        dormant_warning_date: datetime_to_string_opt(activity.dormant_warning_date),
+        dormant_flagged_date: datetime_to_string_opt(activity.dormant_flagged_date),
+        dormant_exempt: activity.dormant_exempt,
+        dormant_exempt_reason: activity.dormant_exempt_reason,
+    }
+}
+
+#[OpenApi]
+impl ApiDormantAccount {
+    /// Users warned or flagged by the dormant account check, and exempt users
+    #[oai(
+        path = "/dormant-account/",
+        method = "get",
+        tag = "ApiDormantAccountTags::DormantAccount"
+    )]
+    async fn get_dormant_account_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> DormantAccountListResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            let permission = format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE_READ);
+            ensure_permission(&mut db, &mut redis_conn, &user, &permission).await?;
+
+            let data = get_dormant_user_activity(&mut db).await?;
+            Ok(DormantAccountListResponses::Ok(Json(
+                data.into_iter().map(dormant_account_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Exempt a user from the dormant account check, or remove the exemption
+    #[oai(
+        path = "/dormant-account/exemption/",
+        method = "put",
+        tag = "ApiDormantAccountTags::DormantAccount"
+    )]
+    async fn update_dormant_account_exemption_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<DormantAccountExemptionRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> DormantAccountExemptionResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE);
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+
+            // get user on db
+            let not_found = || AppError::not_found(format!("user with id = {} not found", id));
+            let user_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            get_user_by_id(&mut tx, &user_id, None)
+                .await?
+                .0
+                .ok_or_else(not_found)?;
+
+            let now = utc_now();
+            let reason = match json.exempt {
+                true => json.reason.filter(|x| !x.trim().is_empty()),
+                false => None,
+            };
+            let activity =
+                set_user_dormant_exemption(&mut tx, &user_id, json.exempt, reason, &now).await?;
+            tx.commit().await?;
+            Ok(DormantAccountExemptionResponses::Ok(Json(
+                dormant_account_response(activity),
+            )))
+        })
+        .await
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    cli::seed::{parse_seed_data, seed, DEFAULT_SEED},
+    core::{
+        permission_cache::invalidate_user_permissions, session_store::create_redis_pool,
+        test_utils::generate_test_user,
+    },
+    init_openapi_route,
+    model::user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_dormant_account_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let service_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "service_user",
+        "password",
+    )
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let payload = json!({"exempt": true, "reason": "service account"});
+
+    // When request user lacks the permission
+    let resp = cli
+        .put("/api/dormant-account/exemption")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &service_user.user.id.to_string())
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When request user is admin
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    let resp = cli
+        .put("/api/dormant-account/exemption")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &service_user.user.id.to_string())
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect exempt and listed
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let exemption = json.value().object();
+    exemption.get("dormant_exempt").assert_bool(true);
+    exemption
+        .get("dormant_exempt_reason")
+        .assert_string("service account");
+    let resp = cli
+        .get("/api/dormant-account")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(1);
+    list.get(0)
+        .object()
+        .get("user_id")
+        .assert_string(&service_user.user.id.to_string());
+
+    // When unknown user
+    let resp = cli
+        .put("/api/dormant-account/exemption")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &Uuid::now_v7().to_string())
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use poem::web::Data;
+use poem_openapi::{payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        email_change::{request_email_change, take_pending_email_change, DEFAULT_EMAIL_CHANGE_TTL},
+        error::{respond, AppError},
+        locale::UserLocale,
+        security::{verify_hash_password, AuthContext, BearerAuthorization},
+        utils::utc_now,
+        validation::Validate,
+    },
+    model::scim_provisioning_event::EVENT_UPDATE,
+    repository::{
+        scim_provisioning_event::enqueue_scim_event,
+        user::{get_user_by_id, update_user_email},
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        email_change::{
+            EmailChangeConfirmRequest, EmailChangeConfirmResponse, EmailChangeConfirmResponses,
+            EmailChangeRequest, EmailChangeResponse, EmailChangeResponses,
+        },
+    },
+    settings::get_config,
+    AppState,
+};
+
+#[derive(Tags)]
+enum ApiEmailChangeTags {
+    EmailChange,
+}
+
+pub struct ApiEmailChange;
+
+#[OpenApi]
+impl ApiEmailChange {
+    /// Change the email of the request user
+    ///
+    /// A confirmation link is sent to the new address and a notice to the old one, the
+    /// profile keeps the old address until the link is confirmed.
+    #[oai(
+        path = "/auth/email-change/",
+        method = "post",
+        tag = "ApiEmailChangeTags::EmailChange"
+    )]
+    async fn email_change_api(
+        &self,
+        Json(json): Json<EmailChangeRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> EmailChangeResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            if let Some(errors) = json.validation_errors() {
+                return Ok(EmailChangeResponses::UnprocessableEntity(Json(errors)));
+            }
+
+            let email = json.email.trim().to_string();
+            match verify_hash_password(&json.password, &request_user.password).map_err(|err| {
+                AppError::Internal(anyhow::anyhow!("verify_hash_password: {}", err))
+            })? {
+                true => {}
+                false => return Err(AppError::bad_request("Invalid credentials".to_string())),
+            }
+            let (_, user_profile) = get_user_by_id(&mut tx, &request_user.id, None).await?;
+            let config = get_config();
+            let locale = UserLocale::resolve(&config, user_profile.as_ref());
+            let old_email = user_profile.and_then(|x| x.email);
+            if old_email.as_deref() == Some(email.as_str()) {
+                return Ok(EmailChangeResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["email"],
+                        "email is unchanged".to_string(),
+                    ),
+                )));
+            }
+
+            request_email_change(
+                &mut tx,
+                &mut redis_conn,
+                &config,
+                &request_user,
+                &locale,
+                old_email.as_deref(),
+                &email,
+            )
+            .await?;
+            Ok(EmailChangeResponses::Accepted(Json(EmailChangeResponse {
+                email: old_email,
+                pending_email: email,
+                expires_in: config.email_change_ttl.unwrap_or(DEFAULT_EMAIL_CHANGE_TTL),
+            })))
+        })
+        .await
+    }
+
+    /// Confirm an email change with the token from the confirmation link
+    #[oai(
+        path = "/auth/email-change/confirm/",
+        method = "post",
+        tag = "ApiEmailChangeTags::EmailChange"
+    )]
+    async fn email_change_confirm_api(
+        &self,
+        Json(json): Json<EmailChangeConfirmRequest>,
+        state: Data<&Arc<AppState>>,
+    ) -> EmailChangeConfirmResponses {
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+
+            let invalid_token = || AppError::bad_request("invalid or expired token".to_string());
+            let pending = take_pending_email_change(&mut redis_conn, &json.token)
+                .await?
+                .ok_or_else(invalid_token)?;
+            let user_id = Uuid::parse_str(&pending.user_id).map_err(|_| invalid_token())?;
+            let (user, _) = get_user_by_id(&mut tx, &user_id, None).await?;
+            let mut user = user.ok_or_else(invalid_token)?;
+
+            let now = utc_now();
+            update_user_email(&mut tx, &mut user, &pending.new_email, &now).await?;
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &user.id, EVENT_UPDATE, Some(now)).await?;
+
+            tx.commit().await?;
+            Ok(EmailChangeConfirmResponses::Ok(Json(
+                EmailChangeConfirmResponse {
+                    user_id: user.id.to_string(),
+                    email: pending.new_email,
+                },
+            )))
+        })
+        .await
+    }
+}
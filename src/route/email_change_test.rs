@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{
+        email_change::USER_KEY_PREFIX,
+        session_store::{create_redis_pool, SessionStore},
+        test_utils::generate_test_user,
+    },
+    init_openapi_route,
+    model::user_profile::TABLE_NAME as USER_PROFILE_TABLE_NAME,
+    repository::user::get_user_by_id,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_email_change_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given user with an email
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    sqlx::query(
+        format!(
+            "UPDATE {} SET email = 'old@example.com' WHERE user_id = $1",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When wrong password
+    let resp = cli
+        .post("/api/auth/email-change")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"email": "new@example.com", "password": "wrong"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When requested
+    let resp = cli
+        .post("/api/auth/email-change")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"email": "new@example.com", "password": "password"}))
+        .send()
+        .await;
+
+    // Expect email unchanged until confirmed
+    resp.assert_status(StatusCode::ACCEPTED);
+    let json = resp.json().await;
+    let res = json.value().object();
+    res.get("email").assert_string("old@example.com");
+    res.get("pending_email").assert_string("new@example.com");
+    let mut tx = app_state.db.begin().await?;
+    let (_, user_profile) = get_user_by_id(&mut tx, &test_user.user.id, None).await?;
+    assert_eq!(
+        user_profile.unwrap().email,
+        Some("old@example.com".to_string())
+    );
+    tx.rollback().await?;
+
+    // When confirmed with the emailed token
+    let token = redis_conn
+        .get(&format!("{}{}", USER_KEY_PREFIX, test_user.user.id))
+        .await?
+        .unwrap();
+    let resp = cli
+        .post("/api/auth/email-change/confirm")
+        .body_json(&json!({ "token": token }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let mut tx = app_state.db.begin().await?;
+    let (_, user_profile) = get_user_by_id(&mut tx, &test_user.user.id, None).await?;
+    assert_eq!(
+        user_profile.unwrap().email,
+        Some("new@example.com".to_string())
+    );
+
+    // When the token is reused
+    let resp = cli
+        .post("/api/auth/email-change/confirm")
+        .body_json(&json!({ "token": token }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use poem::web::Data;
+use poem_openapi::{payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        email_verification::take_pending_email_verification,
+        error::{respond, AppError},
+        utils::{datetime_to_string, utc_now},
+    },
+    repository::user::{get_user_by_id, verify_user_email},
+    schema::email_verification::{VerifyEmailRequest, VerifyEmailResponse, VerifyEmailResponses},
+    AppState,
+};
+
+#[derive(Tags)]
+enum ApiEmailVerificationTags {
+    EmailVerification,
+}
+
+pub struct ApiEmailVerification;
+
+#[OpenApi]
+impl ApiEmailVerification {
+    /// Verify the email of a user with the token from the verification link
+    ///
+    /// Links are sent to the email of new users with EMAIL_VERIFICATION_ENABLED.
+    #[oai(
+        path = "/auth/verify-email/",
+        method = "post",
+        tag = "ApiEmailVerificationTags::EmailVerification"
+    )]
+    async fn verify_email_api(
+        &self,
+        Json(json): Json<VerifyEmailRequest>,
+        state: Data<&Arc<AppState>>,
+    ) -> VerifyEmailResponses {
+        respond(async move {
+            let mut tx = state.db.begin().await?;
+            let mut redis_conn = state.redis_conn.get().await?;
+            let invalid_token = || AppError::bad_request("invalid or expired token");
+            let pending = take_pending_email_verification(&mut redis_conn, &json.token)
+                .await?
+                .ok_or_else(invalid_token)?;
+            let user_id = Uuid::parse_str(&pending.user_id).map_err(|_| invalid_token())?;
+            // the link only verifies the address it was sent to
+            let user_profile = match get_user_by_id(&mut tx, &user_id, None).await? {
+                (Some(_), Some(val)) => val,
+                _ => return Err(invalid_token()),
+            };
+            if user_profile.email.as_deref() != Some(pending.email.as_str()) {
+                return Err(invalid_token());
+            }
+
+            let now = utc_now();
+            verify_user_email(&mut tx, &user_id, &now).await?;
+            tx.commit().await?;
+            Ok(VerifyEmailResponses::Ok(Json(VerifyEmailResponse {
+                user_id: user_id.to_string(),
+                email: pending.email,
+                email_verified_at: datetime_to_string(now),
+            })))
+        })
+        .await
+    }
+}
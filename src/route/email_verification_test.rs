@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{
+        email_verification::add_pending_email_verification,
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+        utils::utc_now,
+    },
+    init_openapi_route,
+    model::user_profile::TABLE_NAME as USER_PROFILE_TABLE_NAME,
+    repository::user::{get_user_by_id, update_user},
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_verify_email_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given user with an unverified email
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        &["user.read"],
+    )
+    .await?;
+    sqlx::query(
+        format!(
+            "UPDATE {} SET email = 'jane@example.com' WHERE user_id = $1",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When unknown token
+    let resp = cli
+        .post("/api/auth/verify-email")
+        .body_json(&json!({"token": "unknown"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When the link was sent to a previous email
+    let token =
+        add_pending_email_verification(&mut redis_conn, &test_user.user.id, "old@example.com", 60)
+            .await?;
+    let resp = cli
+        .post("/api/auth/verify-email")
+        .body_json(&json!({ "token": token }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When
+    let token =
+        add_pending_email_verification(&mut redis_conn, &test_user.user.id, "jane@example.com", 60)
+            .await?;
+    let resp = cli
+        .post("/api/auth/verify-email")
+        .body_json(&json!({ "token": token }))
+        .send()
+        .await;
+
+    // Expect verified once
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value()
+        .object()
+        .get("email")
+        .assert_string("jane@example.com");
+    let resp = cli
+        .post("/api/auth/verify-email")
+        .body_json(&json!({ "token": token }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    let resp = cli
+        .get(format!("/api/user/detail?id={}", test_user.user.id))
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value()
+        .object()
+        .get("user_profile")
+        .object()
+        .get("email_verified_at")
+        .string();
+
+    // When the email changes
+    let mut tx = app_state.db.begin().await?;
+    let (user, user_profile) = get_user_by_id(&mut tx, &test_user.user.id, None).await?;
+    let (mut user, mut user_profile) = (user.unwrap(), user_profile.unwrap());
+    assert!(user_profile.email_verified_at.is_some());
+    user_profile.email = Some("jane.doe@example.com".to_string());
+    update_user(
+        &mut tx,
+        &mut user,
+        &user_profile,
+        &test_user.user,
+        &utc_now(),
+    )
+    .await?;
+
+    // Expect it has to be verified again
+    let (_, user_profile) = get_user_by_id(&mut tx, &test_user.user.id, None).await?;
+    assert!(user_profile.unwrap().email_verified_at.is_none());
+    Ok(())
+}
@@ -1,35 +1,36 @@
-use std::sync::Arc;
-
-use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
 use uuid::Uuid;
 
 use crate::{
     core::{
-        security::{get_user_from_token, BearerAuthorization},
+        error::{respond, AppError},
+        permission_cache::permissions_changed,
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        sqlx_utils::Sort,
         utils::datetime_to_string_opt,
+        validation::Validate,
     },
-    model::user::User,
+    model::{audited::Audited, user::User},
     repository::{
         group::{
-            create_group, get_all_group, get_dropdown_group, get_group_by_id, paginate_group,
+            create_group, get_all_group, get_deleted_group_by_id, get_dropdown_group,
+            get_group_by_id, paginate_deleted_group, paginate_group, restore_group,
             soft_delete_group, update_group,
         },
-        user::get_user_by_id,
+        user::{get_user_by_id, UserLookup},
+        user_group_roles::get_user_ids_by_group,
     },
     schema::{
-        common::{
-            InternalServerErrorResponse, NotFoundResponse, PaginateResponse, UnauthorizedResponse,
-        },
+        common::PaginateResponse,
         group::{
-            DetailGroupPagination, GroupAllResponse, GroupAllResponses, GroupCreateRequest,
-            GroupCreateResponse, GroupCreateResponses, GroupDeleteResponses, GroupDetailResponses,
-            GroupDetailSuccessResponse, GroupDetailUser, GroupDropdownResponse,
-            GroupDropdownResponses, GroupUpdateRequest, GroupUpdateResponse, GroupUpdateResponses,
-            PaginateGroupResponses,
+            DeletedGroupResponse, DetailGroupPagination, GroupAllResponse, GroupAllResponses,
+            GroupCreateRequest, GroupCreateResponse, GroupCreateResponses, GroupDeleteResponses,
+            GroupDetailResponses, GroupDetailSuccessResponse, GroupDetailUser,
+            GroupDropdownResponse, GroupDropdownResponses, GroupRestoreResponses,
+            GroupUpdateRequest, GroupUpdateResponse, GroupUpdateResponses,
+            PaginateDeletedGroupResponses, PaginateGroupResponses,
         },
     },
-    AppState,
 };
 
 #[derive(Tags)]
@@ -42,274 +43,122 @@ pub struct ApiGroup;
 #[OpenApi]
 impl ApiGroup {
     #[oai(path = "/group/", method = "get", tag = "ApiGroupTags::Group")]
+    #[allow(clippy::too_many_arguments)]
     async fn paginate_group_api(
         &self,
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(search): Query<Option<String>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// One of group_name, is_active, created_date, updated_date
+        Query(sort_by): Query<Option<String>>,
+        /// asc or desc
+        Query(sort_dir): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PaginateGroupResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateGroupResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "paginate_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateGroupResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "paginate_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateGroupResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "paginate_group_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PaginateGroupResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) =
-            match paginate_group(&mut tx, page, page_size, search).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return PaginateGroupResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group",
-                            "paginate_group_api",
-                            "paginate_group",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-
-        let mut results: Vec<DetailGroupPagination> = vec![];
-        for item in data {
-            let mut created_by: Option<User> = None;
-            if let Some(created_by_id) = item.created_by {
-                (created_by, _) = match get_user_by_id(&mut tx, &created_by_id, None).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return PaginateGroupResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.group",
-                                "paginate_group_api",
-                                "get created_by",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
-            }
-            let mut updated_by: Option<User> = None;
-            if let Some(updated_by_id) = item.updated_by {
-                (updated_by, _) = match get_user_by_id(&mut tx, &updated_by_id, None).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return PaginateGroupResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.group",
-                                "paginate_group_api",
-                                "get updated_by",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
-            }
-            results.push(DetailGroupPagination {
-                id: item.id.to_string(),
-                group_name: item.group_name,
-                description: item.description,
-                is_active: item.is_active,
-                created_by: match created_by {
-                    Some(val) => Some(GroupDetailUser {
-                        id: val.id.to_string(),
-                        user_name: val.user_name,
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "group.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) = paginate_group(
+                &mut db,
+                page,
+                page_size,
+                search,
+                Sort::new(sort_by, sort_dir),
+            )
+            .await?;
+
+            let mut results: Vec<DetailGroupPagination> = vec![];
+            for Audited {
+                data: item,
+                created_by_name,
+                updated_by_name,
+            } in data
+            {
+                results.push(DetailGroupPagination {
+                    id: item.id.to_string(),
+                    group_name: item.group_name,
+                    description: item.description,
+                    is_active: item.is_active,
+                    created_by: item.created_by.zip(created_by_name).map(|(id, user_name)| {
+                        GroupDetailUser {
+                            id: id.to_string(),
+                            user_name,
+                        }
                     }),
-                    None => None,
-                },
-                updated_by: match updated_by {
-                    Some(val) => Some(GroupDetailUser {
-                        id: val.id.to_string(),
-                        user_name: val.user_name,
+                    updated_by: item.updated_by.zip(updated_by_name).map(|(id, user_name)| {
+                        GroupDetailUser {
+                            id: id.to_string(),
+                            user_name,
+                        }
                     }),
-                    None => None,
-                },
-                created_date: datetime_to_string_opt(item.created_date),
-                updated_date: datetime_to_string_opt(item.updated_date),
-            });
-        }
+                    created_date: datetime_to_string_opt(item.created_date),
+                    updated_date: datetime_to_string_opt(item.updated_date),
+                });
+            }
 
-        PaginateGroupResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results,
-        }))
+            Ok(PaginateGroupResponses::Ok(Json(PaginateResponse {
+                counts,
+                page,
+                page_count,
+                page_size,
+                results,
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/group/all/", method = "get", tag = "ApiGroupTags::Group")]
     async fn get_all_group_api(
         &self,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> GroupAllResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupAllResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_all_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupAllResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_all_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupAllResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_all_group_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return GroupAllResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-
-        let data = match get_all_group(&mut tx).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupAllResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_all_group_api",
-                        "get_all_group",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        let mut results: Vec<GroupAllResponse> = vec![];
-        for item in data {
-            let mut created_by: Option<User> = None;
-            if let Some(created_by_id) = item.created_by {
-                (created_by, _) = match get_user_by_id(&mut tx, &created_by_id, None).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return GroupAllResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.group",
-                                "get_all_group_api",
-                                "get created_by",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
-            }
-            let mut updated_by: Option<User> = None;
-            if let Some(updated_by_id) = item.updated_by {
-                (updated_by, _) = match get_user_by_id(&mut tx, &updated_by_id, None).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return GroupAllResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.group",
-                                "get_all_group_api",
-                                "get updated_by",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
-            }
-            results.push(GroupAllResponse {
-                id: item.id.to_string(),
-                group_name: item.group_name,
-                description: item.description,
-                is_active: item.is_active,
-                created_by: match created_by {
-                    Some(val) => Some(GroupDetailUser {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "group.read").await?;
+            let data = get_all_group(&mut db).await?;
+
+            let users = UserLookup::load(
+                &mut db,
+                data.iter().flat_map(|x| [x.created_by, x.updated_by]),
+            )
+            .await?;
+            let mut results: Vec<GroupAllResponse> = vec![];
+            for item in data {
+                let created_by = users.get(item.created_by);
+                let updated_by = users.get(item.updated_by);
+                results.push(GroupAllResponse {
+                    id: item.id.to_string(),
+                    group_name: item.group_name,
+                    description: item.description,
+                    is_active: item.is_active,
+                    created_by: created_by.map(|val| GroupDetailUser {
                         id: val.id.to_string(),
-                        user_name: val.user_name,
+                        user_name: val.user_name.clone(),
                     }),
-                    None => None,
-                },
-                updated_by: match updated_by {
-                    Some(val) => Some(GroupDetailUser {
+                    updated_by: updated_by.map(|val| GroupDetailUser {
                         id: val.id.to_string(),
-                        user_name: val.user_name,
+                        user_name: val.user_name.clone(),
                     }),
-                    None => None,
-                },
-                created_date: datetime_to_string_opt(item.created_date),
-                updated_date: datetime_to_string_opt(item.updated_date),
-            });
-        }
+                    created_date: datetime_to_string_opt(item.created_date),
+                    updated_date: datetime_to_string_opt(item.updated_date),
+                });
+            }
 
-        GroupAllResponses::Ok(Json(results))
+            Ok(GroupAllResponses::Ok(Json(results)))
+        })
+        .await
     }
 
     #[oai(path = "/group/dropdown/", method = "get", tag = "ApiGroupTags::Group")]
@@ -317,313 +166,116 @@ impl ApiGroup {
         &self,
         Query(limit): Query<Option<u32>>,
         Query(search): Query<Option<String>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> GroupDropdownResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDropdownResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_dropdown_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDropdownResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_dropdown_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDropdownResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_dropdown_group_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return GroupDropdownResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-
-        let data = match get_dropdown_group(&mut tx, limit, search).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDropdownResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_dropdown_group_api",
-                        "get_dropdown_group",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        GroupDropdownResponses::Ok(Json(
-            data.iter()
-                .map(|x| GroupDropdownResponse {
-                    id: x.id.to_string(),
-                    group_name: x.group_name.clone(),
-                })
-                .collect(),
-        ))
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "group.read").await?;
+            let data = get_dropdown_group(&mut db, limit, search).await?;
+
+            Ok(GroupDropdownResponses::Ok(Json(
+                data.iter()
+                    .map(|x| GroupDropdownResponse {
+                        id: x.id.to_string(),
+                        group_name: x.group_name.clone(),
+                    })
+                    .collect(),
+            )))
+        })
+        .await
     }
 
     #[oai(path = "/group/detail/", method = "get", tag = "ApiGroupTags::Group")]
     async fn get_detail_group_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> GroupDetailResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_detail_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "group.read").await?;
+            let not_found = || AppError::not_found(format!("group with id = {} not found", id));
+            let group_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let data = get_group_by_id(&mut db, &group_id)
+                .await?
+                .ok_or_else(not_found)?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_detail_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_detail_group_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return GroupDetailResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return GroupDetailResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("role with id = {} not found", id),
-                }))
+            let mut created_by: Option<User> = None;
+            if let Some(created_by_id) = data.created_by {
+                (created_by, _) = get_user_by_id(&mut db, &created_by_id, None).await?;
             }
-        };
-
-        let data = match get_group_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "get_detail_group_api",
-                        "get_group_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+            let mut updated_by: Option<User> = None;
+            if let Some(updated_by_id) = data.updated_by {
+                (updated_by, _) = get_user_by_id(&mut db, &updated_by_id, None).await?;
             }
-        };
-        if data.is_none() {
-            return GroupDetailResponses::NotFound(Json(NotFoundResponse {
-                message: format!("role with id = {} not found", id),
-            }));
-        }
-        let data = data.unwrap();
-        let mut created_by: Option<User> = None;
-        if let Some(created_by_id) = data.created_by {
-            (created_by, _) = match get_user_by_id(&mut tx, &created_by_id, None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GroupDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group",
-                            "get_detail_group_api",
-                            "get created_by",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        }
-        let mut updated_by: Option<User> = None;
-        if let Some(updated_by_id) = data.updated_by {
-            (updated_by, _) = match get_user_by_id(&mut tx, &updated_by_id, None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GroupDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group",
-                            "get_detail_group_api",
-                            "get updated_by",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        }
-        GroupDetailResponses::Ok(Json(GroupDetailSuccessResponse {
-            id: data.id.to_string(),
-            group_name: data.group_name,
-            description: data.description,
-            is_active: data.is_active,
-            created_date: datetime_to_string_opt(data.created_date),
-            updated_date: datetime_to_string_opt(data.updated_date),
-            created_by: created_by.map(|x| GroupDetailUser {
-                id: x.id.to_string(),
-                user_name: x.user_name,
-            }),
-            updated_by: updated_by.map(|x| GroupDetailUser {
-                id: x.id.to_string(),
-                user_name: x.user_name,
-            }),
-        }))
+            Ok(GroupDetailResponses::Ok(Json(GroupDetailSuccessResponse {
+                id: data.id.to_string(),
+                group_name: data.group_name,
+                description: data.description,
+                is_active: data.is_active,
+                created_date: datetime_to_string_opt(data.created_date),
+                updated_date: datetime_to_string_opt(data.updated_date),
+                created_by: created_by.map(|x| GroupDetailUser {
+                    id: x.id.to_string(),
+                    user_name: x.user_name,
+                }),
+                updated_by: updated_by.map(|x| GroupDetailUser {
+                    id: x.id.to_string(),
+                    user_name: x.user_name,
+                }),
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/group/", method = "post", tag = "ApiGroupTags::Group")]
     async fn create_group_api(
         &self,
         Json(json): Json<GroupCreateRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> GroupCreateResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "create_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "create_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GroupCreateResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group",
-                            "create_group_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return GroupCreateResponses::Unauthorized(Json(UnauthorizedResponse::default()));
+        if let Some(errors) = json.validation_errors() {
+            return GroupCreateResponses::UnprocessableEntity(Json(errors));
         }
-        let request_user = request_user.unwrap();
-
-        let new_group = match create_group(
-            &mut tx,
-            None,
-            json.group_name,
-            json.description,
-            json.is_active,
-            request_user,
-            None,
-        )
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "group.create").await?;
+
+            // a duplicate group name is returned as a conflict, see AppError
+            let new_group = create_group(
+                &mut tx,
+                None,
+                json.group_name,
+                json.description,
+                json.is_active,
+                request_user,
+                None,
+            )
+            .await?;
+            tx.commit().await?;
+            Ok(GroupCreateResponses::Ok(Json(GroupCreateResponse {
+                id: new_group.id.to_string(),
+                group_name: new_group.group_name,
+                description: new_group.description,
+                is_active: new_group.is_active,
+            })))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "create_group_api",
-                        "create_group",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if let Err(err) = tx.commit().await {
-            return GroupCreateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group",
-                    "create_group_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        GroupCreateResponses::Ok(Json(GroupCreateResponse {
-            id: new_group.id.to_string(),
-            group_name: new_group.group_name,
-            description: new_group.description,
-            is_active: new_group.is_active,
-        }))
     }
 
     #[oai(path = "/group/", method = "put", tag = "ApiGroupTags::Group")]
@@ -631,236 +283,159 @@ impl ApiGroup {
         &self,
         Query(id): Query<String>,
         Json(json): Json<GroupUpdateRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> GroupUpdateResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "update_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "update_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GroupUpdateResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group",
-                            "update_group_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return GroupUpdateResponses::Unauthorized(Json(UnauthorizedResponse::default()));
+        if let Some(errors) = json.validation_errors() {
+            return GroupUpdateResponses::UnprocessableEntity(Json(errors));
         }
-        let request_user = request_user.unwrap();
-
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return GroupUpdateResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("role with id = {} not found", id),
-                }))
-            }
-        };
-
-        let data = match get_group_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "update_group_api",
-                        "get_group_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if data.is_none() {
-            return GroupUpdateResponses::NotFound(Json(NotFoundResponse {
-                message: format!("role with id = {} not found", id),
-            }));
-        }
-        let mut data = data.unwrap();
-
-        if let Err(err) = update_group(
-            &mut tx,
-            &mut data,
-            json.group_name,
-            json.description,
-            json.is_active,
-            request_user,
-            None,
-        )
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "group.update").await?;
+            let not_found = || AppError::not_found(format!("group with id = {} not found", id));
+            let group_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let mut data = get_group_by_id(&mut tx, &group_id)
+                .await?
+                .ok_or_else(not_found)?;
+
+            update_group(
+                &mut tx,
+                &mut data,
+                json.group_name,
+                json.description,
+                json.is_active,
+                request_user,
+                None,
+            )
+            .await?;
+            tx.commit().await?;
+            Ok(GroupUpdateResponses::Ok(Json(GroupUpdateResponse {
+                id: data.id.to_string(),
+                group_name: data.group_name,
+                description: data.description,
+                is_active: data.is_active,
+            })))
+        })
         .await
-        {
-            return GroupUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group",
-                    "update_group_api",
-                    "update_group",
-                    &err.to_string(),
-                ),
-            ));
-        }
-
-        if let Err(err) = tx.commit().await {
-            return GroupUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group",
-                    "update_group_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        GroupUpdateResponses::Ok(Json(GroupUpdateResponse {
-            id: data.id.to_string(),
-            group_name: data.group_name,
-            description: data.description,
-            is_active: data.is_active,
-        }))
     }
 
     #[oai(path = "/group/", method = "delete", tag = "ApiGroupTags::Group")]
     async fn delete_group_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> GroupDeleteResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "delete_group_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "delete_group_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GroupDeleteResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group",
-                            "delete_group_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return GroupDeleteResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let request_user = request_user.unwrap();
-
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return GroupDeleteResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("role with id = {} not found", id),
-                }))
-            }
-        };
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "group.delete").await?;
+            let not_found = || AppError::not_found(format!("group with id = {} not found", id));
+            let group_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let mut data = get_group_by_id(&mut tx, &group_id)
+                .await?
+                .ok_or_else(not_found)?;
+
+            soft_delete_group(&mut tx, &mut data, request_user, None).await?;
+            let user_ids = get_user_ids_by_group(&mut tx, &group_id).await?;
+            tx.commit().await?;
+            // Members lose the group permissions, their cached sets are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(GroupDeleteResponses::NoContent)
+        })
+        .await
+    }
 
-        let data = match get_group_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return GroupDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group",
-                        "delete_group_api",
-                        "get_group_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+    /// Soft deleted groups with who deleted them and when
+    #[oai(path = "/group/deleted/", method = "get", tag = "ApiGroupTags::Group")]
+    async fn paginate_deleted_group_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(search): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateDeletedGroupResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "group.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                paginate_deleted_group(&mut db, page, page_size, search).await?;
+
+            let users = UserLookup::load(&mut db, data.iter().map(|x| x.deleted_by)).await?;
+            let mut results: Vec<DeletedGroupResponse> = vec![];
+            for item in data {
+                let deleted_by = users.get(item.deleted_by);
+                results.push(DeletedGroupResponse {
+                    id: item.id.to_string(),
+                    group_name: item.group_name,
+                    description: item.description,
+                    is_active: item.is_active,
+                    deleted_by: deleted_by.map(|val| GroupDetailUser {
+                        id: val.id.to_string(),
+                        user_name: val.user_name.clone(),
+                    }),
+                    deleted_date: datetime_to_string_opt(item.deleted_date),
+                });
             }
-        };
-        if data.is_none() {
-            return GroupDeleteResponses::NotFound(Json(NotFoundResponse {
-                message: format!("role with id = {} not found", id),
-            }));
-        }
-        let mut data = data.unwrap();
 
-        if let Err(err) = soft_delete_group(&mut tx, &mut data, request_user, None).await {
-            return GroupDeleteResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group",
-                    "delete_group_api",
-                    "soft_delete_group",
-                    &err.to_string(),
-                ),
-            ));
-        }
+            Ok(PaginateDeletedGroupResponses::Ok(Json(PaginateResponse {
+                counts,
+                page,
+                page_count,
+                page_size,
+                results,
+            })))
+        })
+        .await
+    }
 
-        if let Err(err) = tx.commit().await {
-            return GroupDeleteResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group",
-                    "delete_group_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        GroupDeleteResponses::NoContent
+    /// Restore a soft deleted group, its permission grants and members apply again
+    #[oai(path = "/group/restore/", method = "post", tag = "ApiGroupTags::Group")]
+    async fn restore_group_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> GroupRestoreResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "group.update").await?;
+            let not_found = || AppError::not_found(format!("group with id = {} not found", id));
+            let group_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let mut data = get_deleted_group_by_id(&mut tx, &group_id)
+                .await?
+                .ok_or_else(not_found)?;
+
+            restore_group(&mut tx, &mut data, request_user, None).await?;
+            let user_ids = get_user_ids_by_group(&mut tx, &group_id).await?;
+            tx.commit().await?;
+            // Members get the group permissions back
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(GroupRestoreResponses::Ok(Json(GroupUpdateResponse {
+                id: data.id.to_string(),
+                group_name: data.group_name,
+                description: data.description,
+                is_active: data.is_active,
+            })))
+        })
+        .await
     }
 }
@@ -1,12 +1,19 @@
 use std::sync::Arc;
 
-use chrono::Local;
 use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
 use uuid::Uuid;
 
 use crate::{
-    core::security::{get_user_from_token, BearerAuthorization},
+    core::{
+        db_error::retry_transaction,
+        error::{respond, AppError},
+        permission_cache::permissions_changed,
+        permission_grant::resolve_grant_target,
+        security::{ensure_permission, BearerAuthorization, ReadAuthContext},
+        utils::utc_now,
+        validation::{Validate, Validator},
+    },
     model::group_permission::GroupPermission,
     repository::{
         group::get_group_by_id,
@@ -16,18 +23,17 @@ use crate::{
         },
         permission::get_permission_by_id,
         permission_attribute::get_permission_attribute_by_id,
+        user_group_roles::get_user_ids_by_group,
     },
     schema::{
-        common::{
-            BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-            UnauthorizedResponse,
-        },
+        common::PaginateResponse,
         group_permission::{
-            CreateGroupPermissionResponses, DeleteGroupPermissionResponses,
-            DetailGroupGroupPermission, DetailGroupPermission,
+            BulkCreateGroupPermissionResponses, CreateGroupPermissionResponses,
+            DeleteGroupPermissionResponses, DetailGroupGroupPermission, DetailGroupPermission,
             DetailPermissionAttributeGroupPermission, DetailPermissionGroupPermission,
-            GroupPermissionCreateRequest, GroupPermissionCreateResponse,
-            PaginateGroupPermissionResponses,
+            GroupPermissionBulkCreateRequest, GroupPermissionBulkCreateResponse,
+            GroupPermissionBulkItemResult, GroupPermissionCreateRequest,
+            GroupPermissionCreateResponse, PaginateGroupPermissionResponses,
         },
     },
     AppState,
@@ -53,162 +59,77 @@ impl ApiGroupPermission {
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(all): Query<Option<bool>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PaginateGroupPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "paginate_group_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "group.read").await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "paginate_group_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
+            // Validasi
+            let group_id = match Uuid::parse_str(&group_id) {
                 Ok(val) => val,
-                Err(err) => {
-                    return PaginateGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "paginate_group_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
+                Err(_) => {
+                    return Err(AppError::bad_request(format!(
+                        "group with id = {} not found",
+                        group_id
+                    )))
                 }
             };
-        if request_user.is_none() {
-            return PaginateGroupPermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-
-        // Validasi
-        let group_id = match Uuid::parse_str(&group_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return PaginateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("group with id = {} not found", group_id),
-                }))
-            }
-        };
-        let group = match get_group_by_id(&mut tx, &group_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "paginate_group_permission_api",
-                        "get_group_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+            let group = get_group_by_id(&mut db, &group_id).await?;
+            if group.is_none() {
+                return Err(AppError::bad_request(format!(
+                    "group with id = {} not found",
+                    group_id
+                )));
             }
-        };
-        if group.is_none() {
-            return PaginateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("group with id = {} not found", group_id),
-            }));
-        }
-        let group = group.unwrap();
+            let group = group.unwrap();
 
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) =
-            match get_all_group_permission(&mut tx, Some(page), Some(page_size), &group_id, all)
-                .await
-            {
-                Ok(val) => val,
-                Err(err) => {
-                    return PaginateGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "paginate_group_permission_api",
-                            "get_all_group_permission",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                get_all_group_permission(&mut db, Some(page), Some(page_size), &group_id, all)
+                    .await?;
 
-        let mut results: Vec<DetailGroupPermission> = vec![];
-        for item in data {
-            let permission = match get_permission_by_id(&mut tx, &item.permission_id).await {
-                Ok(val) => val.unwrap(),
-                Err(err) => {
-                    return PaginateGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "paginate_group_permission_api",
-                            "get_permission_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-            let attribute = match get_permission_attribute_by_id(&mut tx, &item.attribute_id).await
-            {
-                Ok(val) => val.unwrap(),
-                Err(err) => {
-                    return PaginateGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "paginate_group_permission_api",
-                            "get_permission_attribute_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-            results.push(DetailGroupPermission {
-                group: DetailGroupGroupPermission {
-                    id: group.id.to_string(),
-                    group_name: group.group_name.clone(),
-                },
-                permission: DetailPermissionGroupPermission {
-                    id: permission.id.to_string(),
-                    permission_name: permission.permission_name,
-                },
-                permission_attribute: DetailPermissionAttributeGroupPermission {
-                    id: attribute.id.to_string(),
-                    name: attribute.name,
+            let mut results: Vec<DetailGroupPermission> = vec![];
+            for item in data {
+                let permission = get_permission_by_id(&mut db, &item.permission_id)
+                    .await?
+                    .unwrap();
+                let attribute = get_permission_attribute_by_id(&mut db, &item.attribute_id)
+                    .await?
+                    .unwrap();
+                results.push(DetailGroupPermission {
+                    group: DetailGroupGroupPermission {
+                        id: group.id.to_string(),
+                        group_name: group.group_name.clone(),
+                    },
+                    permission: DetailPermissionGroupPermission {
+                        id: permission.id.to_string(),
+                        permission_name: permission.permission_name,
+                    },
+                    permission_attribute: DetailPermissionAttributeGroupPermission {
+                        id: attribute.id.to_string(),
+                        name: attribute.name,
+                    },
+                });
+            }
+            Ok(PaginateGroupPermissionResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results,
                 },
-            });
-        }
-        PaginateGroupPermissionResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results,
-        }))
+            )))
+        })
+        .await
     }
-
     #[oai(
         path = "/group-permissions",
         method = "post",
@@ -217,197 +138,72 @@ impl ApiGroupPermission {
     async fn create_group_permission_api(
         &self,
         Json(json): Json<GroupPermissionCreateRequest>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> CreateGroupPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "create_group_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "create_group_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return CreateGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "create_group_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return CreateGroupPermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
+        if let Some(errors) = json.validation_errors() {
+            return CreateGroupPermissionResponses::UnprocessableEntity(Json(errors));
         }
-        let request_user = request_user.unwrap();
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "group.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let (new_group_permision, user_ids) = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        // Validate
-        let group_id = match Uuid::parse_str(&json.group_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("group with id {} not found", json.group_id),
-                }));
-            }
-        };
-        let group = match get_group_by_id(&mut tx, &group_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "create_group_permission_api",
-                        "get_group_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if group.is_none() {
-            return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("group with id {} not found", json.group_id),
-            }));
-        }
-
-        let permission_id = match Uuid::parse_str(&json.permission_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission with id {} not found", json.permission_id),
-                }));
-            }
-        };
-        let permission = match get_permission_by_id(&mut tx, &permission_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "create_group_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if permission.is_none() {
-            return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("permission with id {} not found", json.permission_id),
-            }));
-        }
+                // Validate
+                let not_found =
+                    || AppError::bad_request(format!("group with id {} not found", json.group_id));
+                let group_id = Uuid::parse_str(&json.group_id).map_err(|_| not_found())?;
+                get_group_by_id(&mut tx, &group_id)
+                    .await?
+                    .ok_or_else(not_found)?;
 
-        let attribute_id = match Uuid::parse_str(&json.attribute_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("attribute with id {} not found", json.attribute_id),
-                }));
-            }
-        };
-        let attribute = match get_permission_attribute_by_id(&mut tx, &attribute_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "create_group_permission_api",
-                        "get_permission_attribute_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if attribute.is_none() {
-            return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("attribute with id {} not found", json.attribute_id),
-            }));
-        }
-        let group_permission =
-            match get_detail_group_permission(&mut tx, &group_id, &permission_id, &attribute_id)
-                .await
-            {
-                Ok(val) => val,
-                Err(err) => {
-                    return CreateGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "create_group_permission_api",
-                            "get_detail_group_permission",
-                            &err.to_string(),
-                        ),
-                    ))
+                let (permission, attribute) =
+                    resolve_grant_target(&mut *tx, &json.permission_id, &json.attribute_id)
+                        .await?;
+                let group_permission =
+                    get_detail_group_permission(&mut tx, &group_id, &permission.id, &attribute.id)
+                        .await?;
+                if group_permission.is_some() {
+                    return Err(AppError::conflict(format!(
+                        "group_permission with group_id = {}, permission_id = {}, attribute_id = {} already exists",
+                        json.group_id, json.permission_id, json.attribute_id
+                    )));
                 }
-            };
-        if group_permission.is_some() {
-            return CreateGroupPermissionResponses::BadRequest(Json(BadRequestResponse { message: format!("group_permission with group_id = {}, permission_id = {}, attribute_id = {} already exists", json.group_id, json.permission_id, json.attribute_id)}));
-        }
-        let now = Local::now().fixed_offset();
-        let new_group_permision = GroupPermission {
-            group_id,
-            permission_id,
-            attribute_id,
-            created_by: Some(request_user.id),
-            updated_by: Some(request_user.id),
-            created_date: Some(now),
-            updated_date: Some(now),
-        };
-        if let Err(err) = create_group_permission(&mut tx, &new_group_permision).await {
-            return CreateGroupPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group_permission",
-                    "create_group_permission_api",
-                    "create_group_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return CreateGroupPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group_permission",
-                    "create_group_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        CreateGroupPermissionResponses::Ok(Json(GroupPermissionCreateResponse {
-            group_id: new_group_permision.group_id.to_string(),
-            permission_id: new_group_permision.permission_id.to_string(),
-            attribute_id: new_group_permision.attribute_id.to_string(),
-        }))
+                let now = utc_now();
+                let new_group_permision = GroupPermission {
+                    group_id,
+                    permission_id: permission.id,
+                    attribute_id: attribute.id,
+                    created_by: Some(request_user.id),
+                    updated_by: Some(request_user.id),
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                };
+                create_group_permission(&mut tx, &new_group_permision).await?;
+                let user_ids = get_user_ids_by_group(&mut tx, &group_id).await?;
+                tx.commit().await?;
+                Ok((new_group_permision, user_ids))
+            })
+            .await?;
+            // Cached permission sets of the group members are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(CreateGroupPermissionResponses::Ok(Json(
+                GroupPermissionCreateResponse {
+                    group_id: new_group_permision.group_id.to_string(),
+                    permission_id: new_group_permision.permission_id.to_string(),
+                    attribute_id: new_group_permision.attribute_id.to_string(),
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -420,183 +216,177 @@ impl ApiGroupPermission {
         Query(group_id): Query<String>,
         Query(permission_id): Query<String>,
         Query(attribute_id): Query<String>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> DeleteGroupPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "delete_group_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "group.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let user_ids = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "delete_group_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
+                // Validate
+                let not_found =
+                    || AppError::bad_request(format!("group with id {} not found", group_id));
+                let group_id = Uuid::parse_str(&group_id).map_err(|_| not_found())?;
+                get_group_by_id(&mut tx, &group_id)
+                    .await?
+                    .ok_or_else(not_found)?;
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "delete_group_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return DeleteGroupPermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
+                let (permission, attribute) =
+                    resolve_grant_target(&mut *tx, &permission_id, &attribute_id).await?;
+                let group_permission =
+                    get_detail_group_permission(&mut tx, &group_id, &permission.id, &attribute.id)
+                        .await?
+                        .ok_or_else(|| {
+                            AppError::not_found(format!(
+                                "group_permission with group_id = {}, permission_id = {}, attribute_id = {} not exists",
+                                group_id, permission.id, attribute.id
+                            ))
+                        })?;
+                delete_group_permission(&mut tx, &group_permission).await?;
+                let user_ids = get_user_ids_by_group(&mut tx, &group_id).await?;
+                tx.commit().await?;
+                Ok::<_, AppError>(user_ids)
+            })
+            .await?;
+            // Cached permission sets of the group members are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(DeleteGroupPermissionResponses::NoContent)
+        })
+        .await
+    }
 
-        // Validate
-        let group_id = match Uuid::parse_str(&group_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("group with id {} not found", group_id),
-                }));
-            }
-        };
-        let group = match get_group_by_id(&mut tx, &group_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "delete_group_permission_api",
-                        "get_group_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if group.is_none() {
-            return DeleteGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("group with id {} not found", group_id),
-            }));
+    /// Grant a group many (permission_id, attribute_id) pairs at once. Every pair is checked
+    /// before anything is inserted, pairs the group already has are skipped.
+    #[oai(
+        path = "/group-permissions/bulk/",
+        method = "post",
+        tag = "ApiGroupPermissionTags::GroupPermission"
+    )]
+    async fn bulk_create_group_permission_api(
+        &self,
+        Json(json): Json<GroupPermissionBulkCreateRequest>,
+        ctx: ReadAuthContext,
+        state: Data<&Arc<AppState>>,
+        _auth: BearerAuthorization,
+    ) -> BulkCreateGroupPermissionResponses {
+        if let Some(errors) = json.validation_errors() {
+            return BulkCreateGroupPermissionResponses::UnprocessableEntity(Json(errors));
         }
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "group.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let (res, user_ids) = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        let permission_id = match Uuid::parse_str(&permission_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission with id {} not found", permission_id),
-                }));
-            }
-        };
-        let permission = match get_permission_by_id(&mut tx, &permission_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "delete_group_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if permission.is_none() {
-            return DeleteGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("permission with id {} not found", permission_id),
-            }));
-        }
+                // Validate, ids are valid uuids after validation_errors
+                let group_id = Uuid::parse_str(&json.group_id).unwrap_or_default();
+                if get_group_by_id(&mut tx, &group_id).await?.is_none() {
+                    return Err(AppError::bad_request(format!(
+                        "group with id {} not found",
+                        json.group_id
+                    )));
+                }
+                let mut pairs: Vec<(Uuid, Uuid)> = vec![];
+                let mut errors: Vec<Vec<(&str, String)>> = vec![];
+                for item in json.permissions.iter() {
+                    let permission_id = Uuid::parse_str(&item.permission_id).unwrap_or_default();
+                    let attribute_id = Uuid::parse_str(&item.attribute_id).unwrap_or_default();
+                    let mut item_errors = vec![];
+                    if get_permission_by_id(&mut tx, &permission_id)
+                        .await?
+                        .is_none()
+                    {
+                        item_errors.push((
+                            "permission_id",
+                            format!("permission with id {} not found", item.permission_id),
+                        ));
+                    }
+                    if get_permission_attribute_by_id(&mut tx, &attribute_id)
+                        .await?
+                        .is_none()
+                    {
+                        item_errors.push((
+                            "attribute_id",
+                            format!("attribute with id {} not found", item.attribute_id),
+                        ));
+                    }
+                    pairs.push((permission_id, attribute_id));
+                    errors.push(item_errors);
+                }
+                let mut v = Validator::new("body");
+                v.each("permissions", &errors, |v, x| {
+                    for (field, msg) in x.iter() {
+                        v.add_error(field, msg.clone());
+                    }
+                });
+                if let Some(errors) = v.finish() {
+                    return Ok((
+                        BulkCreateGroupPermissionResponses::UnprocessableEntity(Json(errors)),
+                        vec![],
+                    ));
+                }
 
-        let attribute_id = match Uuid::parse_str(&attribute_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("attribute with id {} not found", attribute_id),
-                }));
-            }
-        };
-        let attribute = match get_permission_attribute_by_id(&mut tx, &attribute_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteGroupPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.group_permission",
-                        "delete_group_permission_api",
-                        "get_permission_attribute_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if attribute.is_none() {
-            return DeleteGroupPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("attribute with id {} not found", attribute_id),
-            }));
-        }
-        let group_permission =
-            match get_detail_group_permission(&mut tx, &group_id, &permission_id, &attribute_id)
-                .await
-            {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteGroupPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.group_permission",
-                            "delete_group_permission_api",
-                            "get_detail_group_permission",
-                            &err.to_string(),
-                        ),
-                    ))
+                let now = utc_now();
+                let mut results = vec![];
+                for (permission_id, attribute_id) in pairs {
+                    let group_permission = get_detail_group_permission(
+                        &mut tx,
+                        &group_id,
+                        &permission_id,
+                        &attribute_id,
+                    )
+                    .await?;
+                    // repeated pairs of the request land here too, after the first is inserted
+                    if group_permission.is_none() {
+                        let new_group_permision = GroupPermission {
+                            group_id,
+                            permission_id,
+                            attribute_id,
+                            created_by: Some(request_user.id),
+                            updated_by: Some(request_user.id),
+                            created_date: Some(now),
+                            updated_date: Some(now),
+                        };
+                        create_group_permission(&mut tx, &new_group_permision).await?;
+                    }
+                    results.push(GroupPermissionBulkItemResult {
+                        permission_id: permission_id.to_string(),
+                        attribute_id: attribute_id.to_string(),
+                        created: group_permission.is_none(),
+                    });
                 }
-            };
-        if group_permission.is_none() {
-            return DeleteGroupPermissionResponses::NotFound(Json(NotFoundResponse{
-                message: format!("group_permission with group_id = {}, permission_id = {}, attribute_id = {} not exists", group_id, permission_id, attribute_id)
-            }));
-        }
-        if let Err(err) = delete_group_permission(&mut tx, &group_permission.unwrap()).await {
-            return DeleteGroupPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group_permission",
-                    "delete_group_permission_api",
-                    "delete_group_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return DeleteGroupPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.group_permission",
-                    "delete_group_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        DeleteGroupPermissionResponses::NoContent
+                let user_ids = get_user_ids_by_group(&mut tx, &group_id).await?;
+                tx.commit().await?;
+                Ok((
+                    BulkCreateGroupPermissionResponses::Ok(Json(
+                        GroupPermissionBulkCreateResponse {
+                            group_id: group_id.to_string(),
+                            results,
+                        },
+                    )),
+                    user_ids,
+                ))
+            })
+            .await?;
+            // Cached permission sets of the group members are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(res)
+        })
+        .await
     }
 }
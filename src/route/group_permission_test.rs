@@ -5,7 +5,10 @@ use serde_json::json;
 use sqlx::PgPool;
 
 use crate::{
-    core::test_utils::generate_test_user,
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
     factory::{
         group::GroupFactory, permission::PermissionFactory,
         permission_attribute::PermissionAttributeFactory,
@@ -15,19 +18,21 @@ use crate::{
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["group.read", "group.update"];
+
 #[sqlx::test]
 async fn group_permission_test(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -36,6 +41,13 @@ async fn group_permission_test(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut group_factory = GroupFactory::new();
     let group = group_factory.generate_one(&app_state.db, ()).await?;
     let mut permission_factory = PermissionFactory::new();
@@ -130,3 +142,123 @@ async fn group_permission_test(pool: PgPool) -> anyhow::Result<()> {
     .await;
     Ok(())
 }
+
+#[sqlx::test]
+async fn bulk_group_permission_test(pool: PgPool) -> anyhow::Result<()> {
+    // Given a group already granted read
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let read = PermissionAttributeFactory::new()
+        .name("read")
+        .create(&mut tx)
+        .await?;
+    let write = PermissionAttributeFactory::new()
+        .name("write")
+        .create(&mut tx)
+        .await?;
+    let permission = PermissionFactory::new()
+        .permission_name("invoice")
+        .with_attribute(&read)
+        .with_attribute(&write)
+        .create(&mut tx)
+        .await?;
+    let group = GroupFactory::new()
+        .group_name("finance")
+        .with_permission(&permission, &read)
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+    let missing = uuid::Uuid::now_v7().to_string();
+
+    // When one pair does not exist
+    let resp = cli
+        .post("/api/group-permissions/bulk")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "group_id": group.id.to_string(),
+            "permissions": [
+                {"permission_id": permission.id.to_string(), "attribute_id": write.id.to_string()},
+                {"permission_id": missing, "attribute_id": write.id.to_string()},
+            ],
+        }))
+        .send()
+        .await;
+
+    // Expect nothing inserted
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    resp.assert_json(&json!({
+        "code": "VALIDATION_FAILED",
+        "detail": [{
+            "loc": ["body", "permissions", "1", "permission_id"],
+            "msg": format!("permission with id {} not found", missing),
+        }],
+    }))
+    .await;
+    let resp = cli
+        .get("/api/group-permissions")
+        .query("group_id", &group.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let body: serde_json::Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+    assert_eq!(body["counts"], 1);
+
+    // When every pair exists
+    let resp = cli
+        .post("/api/group-permissions/bulk")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "group_id": group.id.to_string(),
+            "permissions": [
+                {"permission_id": permission.id.to_string(), "attribute_id": read.id.to_string()},
+                {"permission_id": permission.id.to_string(), "attribute_id": write.id.to_string()},
+            ],
+        }))
+        .send()
+        .await;
+
+    // Expect write added, read skipped
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({
+        "group_id": group.id.to_string(),
+        "results": [
+            {
+                "permission_id": permission.id.to_string(),
+                "attribute_id": read.id.to_string(),
+                "created": false,
+            },
+            {
+                "permission_id": permission.id.to_string(),
+                "attribute_id": write.id.to_string(),
+                "created": true,
+            },
+        ],
+    }))
+    .await;
+    Ok(())
+}
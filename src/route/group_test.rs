@@ -8,7 +8,8 @@ use uuid::Uuid;
 
 use crate::{
     core::{
-        test_utils::{generate_random, generate_test_user},
+        session_store::create_redis_pool,
+        test_utils::{generate_random, generate_test_user, grant_test_permissions},
         utils::datetime_to_string_opt,
     },
     factory::group::GroupFactory,
@@ -23,19 +24,21 @@ use crate::{
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["group.create", "group.read", "group.update", "group.delete"];
+
 #[sqlx::test]
 async fn test_paginate_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -44,6 +47,13 @@ async fn test_paginate_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = GroupFactory::new();
     role_factory.modified_many(|data, _, _| Group {
         id: data.id,
@@ -55,6 +65,7 @@ async fn test_paginate_group_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let mut roles = role_factory.generate_many(&app_state.db, 10, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -126,14 +137,13 @@ async fn test_get_all_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -142,6 +152,13 @@ async fn test_get_all_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = GroupFactory::new();
     role_factory.modified_many(|data, _, _| Group {
         id: data.id,
@@ -153,6 +170,7 @@ async fn test_get_all_group_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let mut roles = role_factory.generate_many(&app_state.db, 10, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -217,14 +235,13 @@ async fn test_dropdown_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -233,6 +250,13 @@ async fn test_dropdown_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = GroupFactory::new();
     role_factory.modified_many(|data, _, _| Group {
         id: data.id,
@@ -244,6 +268,7 @@ async fn test_dropdown_group_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let mut roles = role_factory.generate_many(&app_state.db, 10, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -281,14 +306,13 @@ async fn test_get_detail_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -297,6 +321,13 @@ async fn test_get_detail_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = GroupFactory::new();
     role_factory.modified_one(|data, _| Group {
         id: data.id,
@@ -308,6 +339,7 @@ async fn test_get_detail_group_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -353,14 +385,13 @@ async fn test_create_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -369,6 +400,13 @@ async fn test_create_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -416,14 +454,13 @@ async fn test_update_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -432,6 +469,13 @@ async fn test_update_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = GroupFactory::new();
     role_factory.modified_one(|data, _| Group {
         id: data.id,
@@ -443,6 +487,7 @@ async fn test_update_group_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -505,14 +550,13 @@ async fn test_delete_group_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -521,6 +565,13 @@ async fn test_delete_group_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = GroupFactory::new();
     role_factory.modified_one(|data, _| Group {
         id: data.id,
@@ -532,6 +583,7 @@ async fn test_delete_group_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -576,3 +628,112 @@ async fn test_delete_group_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status(StatusCode::NOT_FOUND);
     Ok(())
 }
+
+#[sqlx::test]
+async fn test_restore_group_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given a group in the recycle bin
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let group = GroupFactory::new()
+        .group_name("auditors")
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .delete("/api/group")
+        .query("id", &group.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NO_CONTENT);
+
+    // When list deleted groups
+    let resp = cli
+        .get("/api/group/deleted")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect deleted by test user
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let results = json.value().object().get("results").array();
+    results.assert_len(1);
+    let deleted = results.get(0).object();
+    deleted.get("id").assert_string(&group.id.to_string());
+    deleted
+        .get("deleted_by")
+        .object()
+        .get("user_name")
+        .assert_string("test_user");
+    deleted.get("deleted_date").assert_not_null();
+
+    // When restore
+    let resp = cli
+        .post("/api/group/restore")
+        .query("id", &group.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect active again and out of the recycle bin
+    resp.assert_status_is_ok();
+    let resp = cli
+        .get("/api/group/detail")
+        .query("id", &group.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = cli
+        .get("/api/group/deleted")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value().object().get("results").array().assert_len(0);
+    let deleted_by: (Option<Uuid>,) =
+        sqlx::query_as(format!("SELECT deleted_by FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(group.id)
+            .fetch_one(&mut *db)
+            .await?;
+    assert!(deleted_by.0.is_none());
+
+    // When restore a group that is not deleted
+    let resp = cli
+        .post("/api/group/restore")
+        .query("id", &group.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
@@ -1,12 +1,38 @@
 pub mod auth;
 #[cfg(test)]
 mod auth_test;
+pub mod cache;
+#[cfg(test)]
+mod cache_test;
+pub mod consent;
+#[cfg(test)]
+mod consent_test;
+#[cfg(test)]
+mod contract_test;
+pub mod data_classification;
+#[cfg(test)]
+mod data_classification_test;
+pub mod directory_source;
+#[cfg(test)]
+mod directory_source_test;
+pub mod dormant_account;
+#[cfg(test)]
+mod dormant_account_test;
+pub mod email_change;
+#[cfg(test)]
+mod email_change_test;
+pub mod email_verification;
+#[cfg(test)]
+mod email_verification_test;
 pub mod group;
 pub mod group_permission;
 #[cfg(test)]
 mod group_permission_test;
 #[cfg(test)]
 mod group_test;
+pub mod notification_template;
+#[cfg(test)]
+mod notification_template_test;
 pub mod permission;
 pub mod permission_attribute;
 #[cfg(test)]
@@ -19,9 +45,39 @@ pub mod role_permission;
 mod role_permission_test;
 #[cfg(test)]
 mod role_test;
+pub mod scim_target;
+#[cfg(test)]
+mod scim_target_test;
+pub mod service_account;
+#[cfg(test)]
+mod service_account_test;
+pub mod sso_provider;
+#[cfg(test)]
+mod sso_provider_test;
+pub mod terms;
+#[cfg(test)]
+mod terms_test;
 pub mod user;
+pub mod user_contact;
+#[cfg(test)]
+mod user_contact_test;
+pub mod user_data_export;
+#[cfg(test)]
+mod user_data_export_test;
+pub mod user_device;
+#[cfg(test)]
+mod user_device_test;
 pub mod user_permission;
 #[cfg(test)]
 mod user_permission_test;
+pub mod user_preference;
+#[cfg(test)]
+mod user_preference_test;
+pub mod user_recovery_code;
+#[cfg(test)]
+mod user_recovery_code_test;
 #[cfg(test)]
 mod user_test;
+pub mod version;
+#[cfg(test)]
+mod version_test;
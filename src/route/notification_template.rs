@@ -0,0 +1,362 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        locale::{normalize_locale, UserLocale},
+        notifications::{check_template, render_notification},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::notification_template::{
+        NotificationTemplate, CHANNELS, CHANNEL_SMS, EVENTS, PERMISSION_ATTRIBUTE_CREATE,
+        PERMISSION_ATTRIBUTE_DELETE, PERMISSION_ATTRIBUTE_READ, PERMISSION_ATTRIBUTE_UPDATE,
+        PERMISSION_NAME,
+    },
+    repository::notification_template::{
+        create_notification_template, delete_notification_template, get_all_notification_template,
+        get_notification_template, get_notification_template_by_id, update_notification_template,
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        notification_template::{
+            NotificationTemplateCreateResponses, NotificationTemplateDeleteResponses,
+            NotificationTemplateListResponses, NotificationTemplatePreviewRequest,
+            NotificationTemplatePreviewResponse, NotificationTemplatePreviewResponses,
+            NotificationTemplateRequest, NotificationTemplateResponse,
+            NotificationTemplateUpdateResponses,
+        },
+    },
+    settings::get_config,
+};
+
+#[derive(Tags)]
+enum ApiNotificationTemplateTags {
+    NotificationTemplate,
+}
+
+pub struct ApiNotificationTemplate;
+
+fn notification_template_response(template: NotificationTemplate) -> NotificationTemplateResponse {
+    NotificationTemplateResponse {
+        id: template.id.to_string(),
+        event: template.event,
+        channel: template.channel,
+        locale: template.locale,
+        subject: template.subject,
+        body: template.body,
+        created_date: datetime_to_string_opt(template.created_date),
+        updated_date: datetime_to_string_opt(template.updated_date),
+    }
+}
+
+/// Return the reason the event and channel are rejected, None when valid
+fn check_event_channel(event: &str, channel: &str) -> Option<String> {
+    if !EVENTS.contains(&event) {
+        return Some(format!("event must be one of {}", EVENTS.join(", ")));
+    }
+    if !CHANNELS.contains(&channel) {
+        return Some(format!("channel must be one of {}", CHANNELS.join(", ")));
+    }
+    None
+}
+
+/// Validate the request, returning the normalized locale
+fn normalize_template_request(json: &NotificationTemplateRequest) -> Result<String, String> {
+    if let Some(err) = check_event_channel(&json.event, &json.channel) {
+        return Err(err);
+    }
+    let locale = match normalize_locale(&json.locale) {
+        Some(val) => val,
+        None => return Err(format!("locale {} is not supported", json.locale)),
+    };
+    let subject = json.subject.as_deref().filter(|x| !x.trim().is_empty());
+    if json.channel != CHANNEL_SMS && subject.is_none() {
+        return Err(format!("subject is required for {}", json.channel));
+    }
+    if json.body.trim().is_empty() {
+        return Err("body is required".to_string());
+    }
+    for template in subject.into_iter().chain([json.body.as_str()]) {
+        if let Some(err) = check_template(template) {
+            return Err(format!("invalid template: {}", err));
+        }
+    }
+    Ok(locale)
+}
+
+/// Whether another template is stored for the event, channel and locale
+async fn is_duplicate_template(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &str,
+    channel: &str,
+    locale: &str,
+    exclude_id: Option<&Uuid>,
+) -> anyhow::Result<bool> {
+    let existing = get_notification_template(tx, event, channel, locale).await?;
+    Ok(existing.is_some_and(|x| Some(&x.id) != exclude_id))
+}
+
+/// Permission of the attribute on notification templates, see ensure_permission
+fn template_permission(attribute: &str) -> String {
+    format!("{}.{}", PERMISSION_NAME, attribute)
+}
+
+/// Stored template of the id, 404 when the id is not a uuid or not found
+async fn get_template_or_not_found(
+    tx: &mut Transaction<'_, Postgres>,
+    id: &str,
+) -> Result<NotificationTemplate, AppError> {
+    let not_found =
+        || AppError::not_found(format!("notification template with id = {} not found", id));
+    let template_id = Uuid::parse_str(id).map_err(|_| not_found())?;
+    get_notification_template_by_id(tx, &template_id)
+        .await?
+        .ok_or_else(not_found)
+}
+
+#[OpenApi]
+impl ApiNotificationTemplate {
+    /// Stored templates, built-in templates are used for events without one
+    #[oai(
+        path = "/notification-template/",
+        method = "get",
+        tag = "ApiNotificationTemplateTags::NotificationTemplate"
+    )]
+    async fn get_notification_template_api(
+        &self,
+        Query(event): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> NotificationTemplateListResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            let permission = template_permission(PERMISSION_ATTRIBUTE_READ);
+            ensure_permission(&mut db, &mut redis_conn, &user, &permission).await?;
+
+            let data = get_all_notification_template(&mut db, event.as_deref()).await?;
+            Ok(NotificationTemplateListResponses::Ok(Json(
+                data.into_iter()
+                    .map(notification_template_response)
+                    .collect(),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/notification-template/",
+        method = "post",
+        tag = "ApiNotificationTemplateTags::NotificationTemplate"
+    )]
+    async fn create_notification_template_api(
+        &self,
+        Json(json): Json<NotificationTemplateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> NotificationTemplateCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = template_permission(PERMISSION_ATTRIBUTE_CREATE);
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+
+            let locale = match normalize_template_request(&json) {
+                Ok(val) => val,
+                Err(message) => {
+                    return Ok(NotificationTemplateCreateResponses::UnprocessableEntity(
+                        Json(UnprocessableEntityResponse::body_error(&[], message)),
+                    ))
+                }
+            };
+            if is_duplicate_template(&mut tx, &json.event, &json.channel, &locale, None).await? {
+                return Err(AppError::conflict(format!(
+                    "{} {} template for {} already exists",
+                    json.event, json.channel, locale
+                )));
+            }
+
+            let now = utc_now();
+            let template = NotificationTemplate {
+                id: Uuid::now_v7(),
+                event: json.event,
+                channel: json.channel,
+                locale,
+                subject: json.subject.filter(|x| !x.trim().is_empty()),
+                body: json.body,
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+            };
+            create_notification_template(&mut tx, &template).await?;
+            tx.commit().await?;
+            Ok(NotificationTemplateCreateResponses::Created(Json(
+                notification_template_response(template),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/notification-template/",
+        method = "put",
+        tag = "ApiNotificationTemplateTags::NotificationTemplate"
+    )]
+    async fn update_notification_template_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<NotificationTemplateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> NotificationTemplateUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = template_permission(PERMISSION_ATTRIBUTE_UPDATE);
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+            let template = get_template_or_not_found(&mut tx, &id).await?;
+
+            let locale = match normalize_template_request(&json) {
+                Ok(val) => val,
+                Err(message) => {
+                    return Ok(NotificationTemplateUpdateResponses::UnprocessableEntity(
+                        Json(UnprocessableEntityResponse::body_error(&[], message)),
+                    ))
+                }
+            };
+            if is_duplicate_template(
+                &mut tx,
+                &json.event,
+                &json.channel,
+                &locale,
+                Some(&template.id),
+            )
+            .await?
+            {
+                return Err(AppError::conflict(format!(
+                    "{} {} template for {} already exists",
+                    json.event, json.channel, locale
+                )));
+            }
+
+            let template = NotificationTemplate {
+                event: json.event,
+                channel: json.channel,
+                locale,
+                subject: json.subject.filter(|x| !x.trim().is_empty()),
+                body: json.body,
+                updated_by: Some(request_user.id),
+                updated_date: Some(utc_now()),
+                ..template
+            };
+            update_notification_template(&mut tx, &template).await?;
+            tx.commit().await?;
+            Ok(NotificationTemplateUpdateResponses::Ok(Json(
+                notification_template_response(template),
+            )))
+        })
+        .await
+    }
+
+    /// Delete a stored template, the event falls back to the built-in one
+    #[oai(
+        path = "/notification-template/",
+        method = "delete",
+        tag = "ApiNotificationTemplateTags::NotificationTemplate"
+    )]
+    async fn delete_notification_template_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> NotificationTemplateDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = template_permission(PERMISSION_ATTRIBUTE_DELETE);
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+            let template = get_template_or_not_found(&mut tx, &id).await?;
+
+            delete_notification_template(&mut tx, &template.id).await?;
+            tx.commit().await?;
+            Ok(NotificationTemplateDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Render the template a user with the locale would receive
+    #[oai(
+        path = "/notification-template/preview/",
+        method = "post",
+        tag = "ApiNotificationTemplateTags::NotificationTemplate"
+    )]
+    async fn preview_notification_template_api(
+        &self,
+        Json(json): Json<NotificationTemplatePreviewRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> NotificationTemplatePreviewResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = template_permission(PERMISSION_ATTRIBUTE_READ);
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+
+            if let Some(message) = check_event_channel(&json.event, &json.channel) {
+                return Ok(NotificationTemplatePreviewResponses::UnprocessableEntity(
+                    Json(UnprocessableEntityResponse::body_error(&[], message)),
+                ));
+            }
+            let mut locale = UserLocale::resolve(&get_config(), None);
+            if let Some(val) = json.locale.as_deref().filter(|x| !x.trim().is_empty()) {
+                match normalize_locale(val) {
+                    Some(val) => locale.locale = val,
+                    None => {
+                        return Ok(NotificationTemplatePreviewResponses::UnprocessableEntity(
+                            Json(UnprocessableEntityResponse::body_error(
+                                &["locale"],
+                                format!("locale {} is not supported", val),
+                            )),
+                        ))
+                    }
+                }
+            }
+            // a template that fails to render is a client error, not a 500
+            Ok(
+                match render_notification(&mut tx, &json.event, &json.channel, &locale, &json.data)
+                    .await
+                {
+                    Ok(val) => NotificationTemplatePreviewResponses::Ok(Json(
+                        NotificationTemplatePreviewResponse {
+                            subject: val.subject,
+                            body: val.body,
+                        },
+                    )),
+                    Err(err) => NotificationTemplatePreviewResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(&[], err.to_string()),
+                    )),
+                },
+            )
+        })
+        .await
+    }
+}
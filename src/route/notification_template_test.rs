@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    cli::seed::{parse_seed_data, seed, DEFAULT_SEED},
+    core::{
+        permission_cache::invalidate_user_permissions, session_store::create_redis_pool,
+        test_utils::generate_test_user,
+    },
+    init_openapi_route,
+    model::user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_notification_template_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let payload = json!({
+        "event": "invite",
+        "channel": "email",
+        "locale": "id",
+        "subject": "Selamat datang {{user_name}}",
+        "body": "Atur kata sandi: {{link}}"
+    });
+
+    // When request user lacks the permission
+    let resp = cli
+        .post("/api/notification-template")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When request user is admin
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    let resp = cli
+        .post("/api/notification-template")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect created, a second one for the same locale rejected
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let template_id = json.value().object().get("id").string().to_string();
+    let resp = cli
+        .post("/api/notification-template")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CONFLICT);
+
+    // When invalid template
+    let resp = cli
+        .post("/api/notification-template")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "event": "invite",
+            "channel": "email",
+            "locale": "en",
+            "subject": "Welcome",
+            "body": "{{#if link}}"
+        }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When preview for a regional locale
+    let resp = cli
+        .post("/api/notification-template/preview")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "event": "invite",
+            "channel": "email",
+            "locale": "id-ID",
+            "data": {"user_name": "budi", "link": "https://example.com"}
+        }))
+        .send()
+        .await;
+
+    // Expect stored template of the language
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let preview = json.value().object();
+    preview.get("subject").assert_string("Selamat datang budi");
+    preview
+        .get("body")
+        .assert_string("Atur kata sandi: https://example.com");
+
+    // When deleted
+    let resp = cli
+        .delete("/api/notification-template")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &template_id)
+        .send()
+        .await;
+
+    // Expect built-in template used again
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .post("/api/notification-template/preview")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "event": "invite",
+            "channel": "email",
+            "locale": "id",
+            "data": {"user_name": "budi", "link": "https://example.com", "expires_date": "2025-04-17"}
+        }))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value()
+        .object()
+        .get("subject")
+        .assert_string("Akun Anda sudah siap");
+    Ok(())
+}
@@ -1,17 +1,22 @@
 use std::sync::Arc;
 
-use chrono::Local;
 use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::PgConnection;
 use uuid::Uuid;
 
 use crate::{
     core::{
-        security::{get_user_from_token, BearerAuthorization},
-        utils::datetime_to_string_opt,
+        db_error::retry_transaction,
+        error::{respond, AppError},
+        permission_cache::all_permissions_changed,
+        security::{ensure_permission, BearerAuthorization, ReadAuthContext},
+        sqlx_utils::Sort,
+        utils::{datetime_to_string_opt, utc_now},
+        validation::Validate,
     },
     model::{
-        permission::Permission, permission_attribute::PermissionAttribute,
+        audited::Audited, permission::Permission, permission_attribute::PermissionAttribute,
         permission_attribute_list::PermissionAttributeList, user::User,
     },
     repository::{
@@ -25,19 +30,19 @@ use crate::{
             update_permssion_attribute_list_by_permission,
         },
         user::get_user_by_id,
+        user_permission::get_permission_holders,
     },
     schema::{
-        common::{
-            BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-            UnauthorizedResponse,
-        },
+        common::PaginateResponse,
         permission::{
             AllPermissionResponses, DetailPermission, DetailUserPermission,
             DropdownPermissionResponses, PaginatePermissionResponses, PermissionAllResponse,
             PermissionAttributeListPermissionDetail, PermissionCreateRequest,
             PermissionCreateResponse, PermissionCreateResponses, PermissionDeleteResponses,
             PermissionDetailResponse, PermissionDetailResponses, PermissionDropdownResponse,
-            PermissionUpdateRequest, PermissionUpdateResponse, PermissionUpdateResponses,
+            PermissionHolderGrant, PermissionHolderItem, PermissionHolderResponse,
+            PermissionHoldersResponse, PermissionHoldersResponses, PermissionUpdateRequest,
+            PermissionUpdateResponse, PermissionUpdateResponses,
         },
     },
     AppState,
@@ -63,141 +68,76 @@ impl ApiPermission {
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(search): Query<Option<String>>,
+        /// One of permission_name, is_user, is_role, is_group, created_date, updated_date
+        Query(sort_by): Query<Option<String>>,
+        /// asc or desc
+        Query(sort_dir): Query<Option<String>>,
         Query(is_user): Query<Option<bool>>,
         Query(is_role): Query<Option<bool>>,
         Query(is_group): Query<Option<bool>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PaginatePermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PaginatePermissionResponses::Unauthorized(
-                Json(UnauthorizedResponse::default()),
-            );
-        }
-        let (data, counts, page_count) = match get_all_permission(
-            &mut tx, page, page_size, search, is_user, is_role, is_group, None, None,
-        )
-        .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_api",
-                        "get_all_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        let mut results: Vec<DetailPermission> = vec![];
-        for item in data {
-            let mut created_by: Option<User> = None;
-            if item.created_by.is_some() {
-                (created_by, _) =
-                    match get_user_by_id(&mut tx, &item.created_by.unwrap(), Some(true)).await {
-                        Ok(val) => val,
-                        Err(err) => {
-                            return PaginatePermissionResponses::InternalServerError(Json(
-                                InternalServerErrorResponse::new(
-                                    "route.permission",
-                                    "paginate_permission_api",
-                                    "get user created_by",
-                                    &err.to_string(),
-                                ),
-                            ))
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+            let (data, counts, page_count) = get_all_permission(
+                &mut db,
+                page,
+                page_size,
+                search,
+                is_user,
+                is_role,
+                is_group,
+                None,
+                None,
+                Sort::new(sort_by, sort_dir),
+            )
+            .await?;
+            let mut results: Vec<DetailPermission> = vec![];
+            for Audited {
+                data: item,
+                created_by_name,
+                updated_by_name,
+            } in data
+            {
+                results.push(DetailPermission {
+                    id: item.id.to_string(),
+                    permission_name: item.permission_name,
+                    description: item.description,
+                    is_user: item.is_user.unwrap_or(false),
+                    is_role: item.is_role.unwrap_or(false),
+                    is_group: item.is_group.unwrap_or(false),
+                    created_by: item.created_by.zip(created_by_name).map(|(id, user_name)| {
+                        DetailUserPermission {
+                            id: id.to_string(),
+                            user_name,
                         }
-                    };
-            }
-            let mut updated_by: Option<User> = None;
-            if item.updated_by.is_some() {
-                (updated_by, _) =
-                    match get_user_by_id(&mut tx, &item.updated_by.unwrap(), Some(true)).await {
-                        Ok(val) => val,
-                        Err(err) => {
-                            return PaginatePermissionResponses::InternalServerError(Json(
-                                InternalServerErrorResponse::new(
-                                    "route.permission",
-                                    "paginate_permission_api",
-                                    "get user updated_by",
-                                    &err.to_string(),
-                                ),
-                            ))
+                    }),
+                    updated_by: item.updated_by.zip(updated_by_name).map(|(id, user_name)| {
+                        DetailUserPermission {
+                            id: id.to_string(),
+                            user_name,
                         }
-                    };
-            }
-            results.push(DetailPermission {
-                id: item.id.to_string(),
-                permission_name: item.permission_name,
-                description: item.description,
-                is_user: item.is_user.unwrap_or(false),
-                is_role: item.is_role.unwrap_or(false),
-                is_group: item.is_group.unwrap_or(false),
-                created_date: datetime_to_string_opt(item.created_date),
-                updated_date: datetime_to_string_opt(item.updated_date),
-                created_by: created_by.map(|x| DetailUserPermission {
-                    id: x.id.to_string(),
-                    user_name: x.user_name,
-                }),
-                updated_by: updated_by.map(|x| DetailUserPermission {
-                    id: x.id.to_string(),
-                    user_name: x.user_name,
-                }),
-            });
-        }
-        PaginatePermissionResponses::Ok(Json(PaginateResponse {
-            counts,
-            page: page.unwrap_or(1),
-            page_count,
-            page_size: page_size.unwrap_or(10),
-            results,
-        }))
+                    }),
+                    created_date: datetime_to_string_opt(item.created_date),
+                    updated_date: datetime_to_string_opt(item.updated_date),
+                });
+            }
+
+            Ok(PaginatePermissionResponses::Ok(Json(PaginateResponse {
+                counts,
+                page: page.unwrap_or(1),
+                page_count,
+                page_size: page_size.unwrap_or(10),
+                results,
+            })))
+        })
+        .await
     }
 
     #[oai(
@@ -207,96 +147,46 @@ impl ApiPermission {
     )]
     async fn get_all_permission_api(
         &self,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> AllPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return AllPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return AllPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return AllPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return AllPermissionResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let (data, _, _) = match get_all_permission(
-            &mut tx,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            Some(true),
-        )
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+            let (data, _, _) = get_all_permission(
+                &mut db,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(true),
+                Sort::default(),
+            )
+            .await?;
+            Ok(AllPermissionResponses::Ok(Json(
+                data.iter()
+                    .map(|x| &x.data)
+                    .map(|x| PermissionAllResponse {
+                        id: x.id.to_string(),
+                        permission_name: x.permission_name.clone(),
+                        description: x.description.clone(),
+                        is_user: x.is_user.unwrap_or(false),
+                        is_role: x.is_role.unwrap_or(false),
+                        is_group: x.is_group.unwrap_or(false),
+                        created_date: datetime_to_string_opt(x.created_date),
+                        updated_date: datetime_to_string_opt(x.updated_date),
+                    })
+                    .collect(),
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return AllPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "get_all_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        AllPermissionResponses::Ok(Json(
-            data.iter()
-                .map(|x| PermissionAllResponse {
-                    id: x.id.to_string(),
-                    permission_name: x.permission_name.clone(),
-                    description: x.description.clone(),
-                    is_user: x.is_user.unwrap_or(false),
-                    is_role: x.is_role.unwrap_or(false),
-                    is_group: x.is_group.unwrap_or(false),
-                    created_date: datetime_to_string_opt(x.created_date),
-                    updated_date: datetime_to_string_opt(x.updated_date),
-                })
-                .collect(),
-        ))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -307,97 +197,45 @@ impl ApiPermission {
     )]
     async fn get_dropdown_permission_api(
         &self,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
         Query(search): Query<Option<String>>,
         Query(is_user): Query<Option<bool>>,
         Query(is_role): Query<Option<bool>>,
         Query(is_group): Query<Option<bool>>,
         Query(limit): Query<Option<u32>>,
     ) -> DropdownPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return DropdownPermissionResponses::Unauthorized(
-                Json(UnauthorizedResponse::default()),
-            );
-        }
-        let (data, _, _) = match get_all_permission(
-            &mut tx,
-            None,
-            None,
-            search,
-            is_user,
-            is_role,
-            is_group,
-            limit,
-            Some(true),
-        )
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+            let (data, _, _) = get_all_permission(
+                &mut db,
+                None,
+                None,
+                search,
+                is_user,
+                is_role,
+                is_group,
+                limit,
+                Some(true),
+                Sort::default(),
+            )
+            .await?;
+            Ok(DropdownPermissionResponses::Ok(Json(
+                data.iter()
+                    .map(|x| &x.data)
+                    .map(|x| PermissionDropdownResponse {
+                        id: x.id.to_string(),
+                        permission_name: x.permission_name.clone(),
+                    })
+                    .collect(),
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_all_permission_api",
-                        "get_all_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        DropdownPermissionResponses::Ok(Json(
-            data.iter()
-                .map(|x| PermissionDropdownResponse {
-                    id: x.id.to_string(),
-                    permission_name: x.permission_name.clone(),
-                })
-                .collect(),
-        ))
     }
 
     #[oai(
@@ -408,180 +246,185 @@ impl ApiPermission {
     async fn get_detail_permission_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PermissionDetailResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_detail_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+
+            // get detail permission
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "permission with id = {} not found",
+                        id
+                    )))
+                }
+            };
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_detail_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+            let data = get_permission_by_id(&mut db, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "permission with id = {} not found",
+                    id
+                )));
             }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_detail_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
+            let data = data.unwrap();
+            let mut created_by: Option<User> = None;
+            if data.created_by.is_some() {
+                (created_by, _) = get_user_by_id(&mut db, &data.id, Some(true)).await?;
             }
-        };
-        if user.is_none() {
-            return PermissionDetailResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-
-        // get detail permission
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return PermissionDetailResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("permission with id = {} not found", id),
-                }))
+            let mut updated_by: Option<User> = None;
+            if data.updated_by.is_some() {
+                (updated_by, _) = get_user_by_id(&mut db, &data.id, Some(true)).await?;
             }
-        };
+            let permission_attribute_lists =
+                get_all_permission_attribute_list(&mut db, Some(&data.id), None).await?;
+            let attribute_ids: Vec<Uuid> = permission_attribute_lists
+                .iter()
+                .map(|x| x.attribute_id)
+                .collect();
+            let mut permission_attributes: Vec<PermissionAttribute> = vec![];
+            if !attribute_ids.is_empty() {
+                permission_attributes =
+                    get_permission_attribute_by_ids(&mut db, attribute_ids).await?;
+            }
+            Ok(PermissionDetailResponses::Ok(Json(
+                PermissionDetailResponse {
+                    id: data.id.to_string(),
+                    permission_name: data.permission_name,
+                    description: data.description,
+                    is_user: data.is_user.unwrap_or(false),
+                    is_role: data.is_role.unwrap_or(false),
+                    is_group: data.is_group.unwrap_or(false),
+                    created_date: datetime_to_string_opt(data.created_date),
+                    updated_date: datetime_to_string_opt(data.updated_date),
+                    created_by: created_by.map(|x| DetailUserPermission {
+                        id: x.id.to_string(),
+                        user_name: x.user_name,
+                    }),
+                    updated_by: updated_by.map(|x| DetailUserPermission {
+                        id: x.id.to_string(),
+                        user_name: x.user_name,
+                    }),
+                    permission_attribute_ids: permission_attributes
+                        .iter()
+                        .map(|x| PermissionAttributeListPermissionDetail {
+                            id: x.id.to_string(),
+                            name: x.name.clone(),
+                            description: x.description.clone(),
+                        })
+                        .collect(),
+                },
+            )))
+        })
+        .await
+    }
 
-        let data = match get_permission_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "get_detail_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if data.is_none() {
-            return PermissionDetailResponses::NotFound(Json(NotFoundResponse {
-                message: format!("permission with id = {} not found", id),
-            }));
-        }
-        let data = data.unwrap();
-        let mut created_by: Option<User> = None;
-        if data.created_by.is_some() {
-            (created_by, _) = match get_user_by_id(&mut tx, &data.id, Some(true)).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return PermissionDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.permission",
-                            "get_detail_permission_api",
-                            "get user created_by",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
+    /// Users holding the permission directly, through a role or through a group with the
+    /// grant path of each attribute, for access reviews
+    #[oai(
+        path = "/permissions/holders/",
+        method = "get",
+        tag = "ApiPermissionTags::Permission"
+    )]
+    async fn get_permission_holders_api(
+        &self,
+        Query(id): Query<String>,
+        Query(attribute_id): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PermissionHoldersResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+
+            // get permission
+            let permission = match Uuid::parse_str(&id) {
+                Ok(val) => get_permission_by_id(&mut db, &val).await?,
+                Err(_) => None,
             };
-        }
-        let mut updated_by: Option<User> = None;
-        if data.updated_by.is_some() {
-            (updated_by, _) = match get_user_by_id(&mut tx, &data.id, Some(true)).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return PermissionDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.permission",
-                            "get_detail_permission_api",
-                            "get user updated_by",
-                            &err.to_string(),
-                        ),
-                    ))
+            let permission = match permission {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "permission with id = {} not found",
+                        id
+                    )))
                 }
             };
-        }
-        let permission_attribute_lists =
-            match get_all_permission_attribute_list(&mut tx, Some(&data.id), None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return PermissionDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.permission",
-                            "get_detail_permission_api",
-                            "get_all_permission_attribute_list",
-                            &err.to_string(),
-                        ),
-                    ))
+
+            // get permission attribute filter
+            let attribute_id = match attribute_id {
+                Some(attribute_id) => {
+                    let attribute = match Uuid::parse_str(&attribute_id) {
+                        Ok(val) => get_permission_attribute_by_id(&mut db, &val).await?,
+                        Err(_) => None,
+                    };
+                    match attribute {
+                        Some(val) => Some(val.id),
+                        None => {
+                            return Err(AppError::not_found(format!(
+                                "permission attribute with id = {} not found",
+                                attribute_id
+                            )))
+                        }
+                    }
                 }
+                None => None,
             };
-        let attribute_ids: Vec<Uuid> = permission_attribute_lists
-            .iter()
-            .map(|x| x.attribute_id)
-            .collect();
-        let mut permission_attributes: Vec<PermissionAttribute> = vec![];
-        if !attribute_ids.is_empty() {
-            permission_attributes =
-                match get_permission_attribute_by_ids(&mut tx, attribute_ids).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return PermissionDetailResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.permission",
-                                "get_detail_permission_api",
-                                "get_permission_attribute_by_ids",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
+
+            // get holders, rows are ordered by user so grants of a user are adjacent
+            let rows = get_permission_holders(&mut db, &permission.id, attribute_id).await?;
+            let mut holders: Vec<PermissionHolderResponse> = vec![];
+            for row in rows {
+                let grant = PermissionHolderGrant {
+                    attribute: PermissionHolderItem {
+                        id: row.attribute_id.to_string(),
+                        name: row.attribute_name,
+                    },
+                    via: row.via,
+                    source: row.source_id.zip(row.source_name).map(|(id, name)| {
+                        PermissionHolderItem {
+                            id: id.to_string(),
+                            name,
+                        }
+                    }),
+                    expires_at: datetime_to_string_opt(row.expires_at),
                 };
-        }
-        PermissionDetailResponses::Ok(Json(PermissionDetailResponse {
-            id: data.id.to_string(),
-            permission_name: data.permission_name,
-            description: data.description,
-            is_user: data.is_user.unwrap_or(false),
-            is_role: data.is_role.unwrap_or(false),
-            is_group: data.is_group.unwrap_or(false),
-            created_date: datetime_to_string_opt(data.created_date),
-            updated_date: datetime_to_string_opt(data.updated_date),
-            created_by: created_by.map(|x| DetailUserPermission {
-                id: x.id.to_string(),
-                user_name: x.user_name,
-            }),
-            updated_by: updated_by.map(|x| DetailUserPermission {
-                id: x.id.to_string(),
-                user_name: x.user_name,
-            }),
-            permission_attribute_ids: permission_attributes
-                .iter()
-                .map(|x| PermissionAttributeListPermissionDetail {
-                    id: x.id.to_string(),
-                    name: x.name.clone(),
-                    description: x.description.clone(),
-                })
-                .collect(),
-        }))
+                let user_id = row.user_id.to_string();
+                match holders.last_mut() {
+                    Some(holder) if holder.user.id == user_id => holder.grants.push(grant),
+                    _ => holders.push(PermissionHolderResponse {
+                        user: DetailUserPermission {
+                            id: user_id,
+                            user_name: row.user_name,
+                        },
+                        status: row.status,
+                        grants: vec![grant],
+                    }),
+                }
+            }
+
+            Ok(PermissionHoldersResponses::Ok(Json(
+                PermissionHoldersResponse {
+                    id: permission.id.to_string(),
+                    permission_name: permission.permission_name,
+                    holders,
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -592,150 +435,67 @@ impl ApiPermission {
     async fn create_permission_api(
         &self,
         Json(json): Json<PermissionCreateRequest>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> PermissionCreateResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "create_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "create_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "create_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PermissionCreateResponses::Unauthorized(Json(UnauthorizedResponse::default()));
+        if let Some(errors) = json.validation_errors() {
+            return PermissionCreateResponses::UnprocessableEntity(Json(errors));
         }
-        // Validate json request
-        let mut permission_attributes: Vec<PermissionAttribute> = vec![];
-        for item in json.permission_attribute_ids {
-            let permission_attribute_id = match Uuid::parse_str(&item) {
-                Ok(val) => val,
-                Err(_) => {
-                    return PermissionCreateResponses::BadRequest(Json(BadRequestResponse {
-                        message: format!("permission attribute id = {} not found", item),
-                    }));
-                }
-            };
-            let permission_attribute =
-                match get_permission_attribute_by_id(&mut tx, &permission_attribute_id).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return PermissionCreateResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.permission",
-                                "create_permission_api",
-                                "get_permission_attribute_by_id",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "permission.create").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let new_permission = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
+
+                // Validate json request
+                let permission_attributes =
+                    get_permission_attributes(&mut tx, &json.permission_attribute_ids).await?;
+                // Create permission
+                let now = utc_now();
+                let new_permission = Permission {
+                    id: Uuid::now_v7(),
+                    permission_name: json.permission_name.clone(),
+                    is_user: Some(json.is_user),
+                    is_role: Some(json.is_role),
+                    is_group: Some(json.is_group),
+                    description: json.description.clone(),
+                    created_by: Some(request_user.id),
+                    updated_by: Some(request_user.id),
+                    created_date: Some(now),
+                    updated_date: Some(now),
                 };
-            if permission_attribute.is_none() {
-                return PermissionCreateResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission attribute id = {} not found", item),
-                }));
-            }
-            permission_attributes.push(permission_attribute.unwrap());
-        }
-        // Create permission
-        let request_user = user.unwrap();
-        let now = Local::now().fixed_offset();
-        let new_permission = Permission {
-            id: Uuid::now_v7(),
-            permission_name: json.permission_name,
-            is_user: Some(json.is_user),
-            is_role: Some(json.is_role),
-            is_group: Some(json.is_group),
-            description: json.description,
-            created_by: Some(request_user.id),
-            updated_by: Some(request_user.id),
-            created_date: Some(now),
-            updated_date: Some(now),
-        };
-        if let Err(err) = create_permission(&mut tx, &new_permission).await {
-            return PermissionCreateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "create_permission_api",
-                    "create_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        for item in permission_attributes {
-            let new_permission_attribute_list = PermissionAttributeList {
-                permission_id: new_permission.id,
-                attribute_id: item.id,
-            };
-            if let Err(err) =
-                create_permission_attribute_list(&mut tx, &new_permission_attribute_list).await
-            {
-                return PermissionCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "create_permission_api",
-                        "create_permission_attribute_list",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        }
-        if let Err(err) = tx.commit().await {
-            return PermissionCreateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "create_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        PermissionCreateResponses::Created(Json(PermissionCreateResponse {
-            id: new_permission.id.to_string(),
-            permission_name: new_permission.permission_name,
-            description: new_permission.description,
-            is_user: new_permission.is_user.unwrap(),
-            is_role: new_permission.is_role.unwrap(),
-            is_group: new_permission.is_group.unwrap(),
-        }))
+                create_permission(&mut tx, &new_permission).await?;
+                for item in permission_attributes {
+                    let new_permission_attribute_list = PermissionAttributeList {
+                        permission_id: new_permission.id,
+                        attribute_id: item.id,
+                    };
+                    create_permission_attribute_list(&mut tx, &new_permission_attribute_list)
+                        .await?;
+                }
+                tx.commit().await?;
+                Ok::<_, AppError>(new_permission)
+            })
+            .await?;
+            Ok(PermissionCreateResponses::Created(Json(
+                PermissionCreateResponse {
+                    id: new_permission.id.to_string(),
+                    permission_name: new_permission.permission_name,
+                    description: new_permission.description,
+                    is_user: new_permission.is_user.unwrap(),
+                    is_role: new_permission.is_role.unwrap(),
+                    is_group: new_permission.is_group.unwrap(),
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -747,171 +507,70 @@ impl ApiPermission {
         &self,
         Query(id): Query<String>,
         Json(json): Json<PermissionUpdateRequest>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> PermissionUpdateResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "update_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "update_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "update_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PermissionUpdateResponses::Unauthorized(Json(UnauthorizedResponse::default()));
+        if let Some(errors) = json.validation_errors() {
+            return PermissionUpdateResponses::UnprocessableEntity(Json(errors));
         }
-        let request_user = user.unwrap();
-
-        // get detail permission
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return PermissionUpdateResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("permission with id = {} not found", id),
-                }))
-            }
-        };
-
-        let data = match get_permission_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "update_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if data.is_none() {
-            return PermissionUpdateResponses::NotFound(Json(NotFoundResponse {
-                message: format!("permission with id = {} not found", id),
-            }));
-        }
-        // Validate json request
-        let mut permission_attributes: Vec<PermissionAttribute> = vec![];
-        for item in json.permission_attribute_ids {
-            let permission_attribute_id = match Uuid::parse_str(&item) {
-                Ok(val) => val,
-                Err(_) => {
-                    return PermissionUpdateResponses::BadRequest(Json(BadRequestResponse {
-                        message: format!("permission attribute id = {} not found", item),
-                    }));
-                }
-            };
-            let permission_attribute =
-                match get_permission_attribute_by_id(&mut tx, &permission_attribute_id).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return PermissionUpdateResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.permission",
-                                "create_permission_api",
-                                "get_permission_attribute_by_id",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
-            if permission_attribute.is_none() {
-                return PermissionUpdateResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission attribute id = {} not found", item),
-                }));
-            }
-            permission_attributes.push(permission_attribute.unwrap());
-        }
-        // Update permission
-        let mut data = data.unwrap();
-        let now = Local::now().fixed_offset();
-        data.permission_name = json.permission_name;
-        data.description = json.description;
-        data.is_user = Some(json.is_user);
-        data.is_role = Some(json.is_role);
-        data.is_group = Some(json.is_group);
-        data.updated_by = Some(request_user.id);
-        data.updated_date = Some(now);
-        if let Err(err) = update_permission(&mut tx, &data).await {
-            return PermissionUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "update_permission_api",
-                    "update_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) =
-            update_permssion_attribute_list_by_permission(&mut tx, &data, permission_attributes)
-                .await
-        {
-            return PermissionUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "update_permission_api",
-                    "update_permssion_attribute_list_by_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return PermissionUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "update_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-
-        PermissionUpdateResponses::Ok(Json(PermissionUpdateResponse {
-            id: data.id.to_string(),
-            permission_name: data.permission_name,
-            description: data.description,
-            is_user: data.is_user.unwrap_or(false),
-            is_role: data.is_role.unwrap_or(false),
-            is_group: data.is_group.unwrap_or(false),
-        }))
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "permission.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let data = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
+
+                // get detail permission
+                let not_found =
+                    || AppError::not_found(format!("permission with id = {} not found", id));
+                let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+                let mut data = get_permission_by_id(&mut tx, &id)
+                    .await?
+                    .ok_or_else(not_found)?;
+                // Validate json request
+                let permission_attributes =
+                    get_permission_attributes(&mut tx, &json.permission_attribute_ids).await?;
+                // Update permission
+                let now = utc_now();
+                data.permission_name = json.permission_name.clone();
+                data.description = json.description.clone();
+                data.is_user = Some(json.is_user);
+                data.is_role = Some(json.is_role);
+                data.is_group = Some(json.is_group);
+                data.updated_by = Some(request_user.id);
+                data.updated_date = Some(now);
+                update_permission(&mut tx, &data).await?;
+                update_permssion_attribute_list_by_permission(
+                    &mut tx,
+                    &data,
+                    permission_attributes,
+                )
+                .await?;
+                tx.commit().await?;
+                Ok::<_, AppError>(data)
+            })
+            .await?;
+            // Cached permission sets hold permission names
+            all_permissions_changed(&mut redis_conn).await;
+
+            Ok(PermissionUpdateResponses::Ok(Json(
+                PermissionUpdateResponse {
+                    id: data.id.to_string(),
+                    permission_name: data.permission_name,
+                    description: data.description,
+                    is_user: data.is_user.unwrap_or(false),
+                    is_role: data.is_role.unwrap_or(false),
+                    is_group: data.is_group.unwrap_or(false),
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -922,107 +581,57 @@ impl ApiPermission {
     async fn delete_permission_api(
         &self,
         Query(id): Query<String>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> PermissionDeleteResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "delete_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "delete_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "delete_permission_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PermissionDeleteResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-
-        // get detail permission
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return PermissionDeleteResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("permission with id = {} not found", id),
-                }))
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "permission.delete").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
+
+                // get detail permission
+                let not_found =
+                    || AppError::not_found(format!("permission with id = {} not found", id));
+                let id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+                let data = get_permission_by_id(&mut tx, &id)
+                    .await?
+                    .ok_or_else(not_found)?;
+                delete_permission(&mut tx, &data).await?;
+                tx.commit().await?;
+                Ok::<_, AppError>(())
+            })
+            .await?;
+            // Cached permission sets hold permission names
+            all_permissions_changed(&mut redis_conn).await;
+            Ok(PermissionDeleteResponses::NoContent)
+        })
+        .await
+    }
+}
 
-        let data = match get_permission_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PermissionDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "delete_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if data.is_none() {
-            return PermissionDeleteResponses::NotFound(Json(NotFoundResponse {
-                message: format!("permission with id = {} not found", id),
-            }));
-        }
-        let data = data.unwrap();
-        if let Err(err) = delete_permission(&mut tx, &data).await {
-            return PermissionDeleteResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "delete_permission_api",
-                    "delete_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return PermissionDeleteResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission",
-                    "delete_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        PermissionDeleteResponses::NoContent
+/// Looks up the permission attributes of a create or update request, the
+/// first id that does not exist is a bad request
+async fn get_permission_attributes(
+    conn: &mut PgConnection,
+    ids: &[String],
+) -> Result<Vec<PermissionAttribute>, AppError> {
+    let mut permission_attributes = vec![];
+    for item in ids {
+        let not_found =
+            || AppError::bad_request(format!("permission attribute id = {} not found", item));
+        let permission_attribute_id = Uuid::parse_str(item).map_err(|_| not_found())?;
+        let permission_attribute = get_permission_attribute_by_id(conn, &permission_attribute_id)
+            .await?
+            .ok_or_else(not_found)?;
+        permission_attributes.push(permission_attribute);
     }
+    Ok(permission_attributes)
 }
@@ -1,30 +1,35 @@
-use std::sync::Arc;
-
-use chrono::Local;
-use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
 use uuid::Uuid;
 
 use crate::{
-    core::security::{get_user_from_token, BearerAuthorization},
+    core::{
+        error::{respond, AppError},
+        permission_cache::all_permissions_changed,
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::utc_now,
+    },
     model::permission_attribute::PermissionAttribute,
     repository::permission_attribute::{
-        create_permission_attribute, delete_permission_attribute, get_all_permission_attribute,
-        get_permission_attribute_by_id, update_permission_attribute,
+        create_permission_attribute, delete_permission_attribute_usages,
+        get_all_permission_attribute, get_deleted_permission_attribute_by_id,
+        get_permission_attribute_by_id, get_permission_attribute_by_name,
+        get_permission_attribute_categories, get_permission_attribute_usages,
+        restore_permission_attribute, soft_delete_permission_attribute,
+        update_permission_attribute,
     },
     schema::{
-        common::{
-            InternalServerErrorResponse, NotFoundResponse, PaginateResponse, UnauthorizedResponse,
-        },
+        common::{ConflictResponse, PaginateResponse},
         permission_attribute::{
             CreatePermissionAttributeRequest, CreatePermissionAttributeResponses,
             DeletePermissionAttributeResponses, DetailPermissionAttribute,
             DetailPermissionAttributeResponses, DropdownPermissionAttributeResponses,
-            PaginatePermissionAttributeResponses, UpdatePermissionAttributeRequest,
-            UpdatePermissionAttributeResponses,
+            GroupedPermissionAttribute, GroupedPermissionAttributeResponses,
+            PaginatePermissionAttributeResponses, PermissionAttributeCategory,
+            PermissionAttributeCategoryResponses, PermissionAttributeInUseResponse,
+            PermissionAttributeUsage, RestorePermissionAttributeResponses,
+            UpdatePermissionAttributeRequest, UpdatePermissionAttributeResponses,
         },
     },
-    AppState,
 };
 
 #[derive(Tags)]
@@ -46,97 +51,49 @@ impl ApiPermissionAttribute {
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(search): Query<Option<String>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// Only attributes of this category
+        Query(category): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PaginatePermissionAttributeResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_attribute_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_attribute_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PaginatePermissionAttributeResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) = match get_all_permission_attribute(
-            &mut tx,
-            Some(page),
-            Some(page_size),
-            search,
-            None,
-            None,
-        )
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) = get_all_permission_attribute(
+                &mut db,
+                Some(page),
+                Some(page_size),
+                search,
+                category,
+                None,
+                None,
+            )
+            .await?;
+            Ok(PaginatePermissionAttributeResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results: data
+                        .iter()
+                        .map(|x| DetailPermissionAttribute {
+                            id: x.id.to_string(),
+                            name: x.name.clone(),
+                            description: x.description.clone(),
+                            category: x.category.clone(),
+                        })
+                        .collect(),
+                },
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "paginate_permission_api",
-                        "get_all_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        PaginatePermissionAttributeResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results: data
-                .iter()
-                .map(|x| DetailPermissionAttribute {
-                    id: x.id.to_string(),
-                    name: x.name.clone(),
-                    description: x.description.clone(),
-                })
-                .collect(),
-        }))
     }
 
     #[oai(
@@ -147,92 +104,128 @@ impl ApiPermissionAttribute {
     async fn dropdown_permission_attribute_api(
         &self,
         Query(limit): Query<Option<u32>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// Only attributes of this category
+        Query(category): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> DropdownPermissionAttributeResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "dropdown_permission_attribute_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "dropdown_permission_attribute_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
+            let (data, _, _) = get_all_permission_attribute(
+                &mut db,
+                None,
+                None,
+                None,
+                category,
+                limit,
+                Some(true),
+            )
+            .await?;
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "dropdown_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return DropdownPermissionAttributeResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
+            Ok(DropdownPermissionAttributeResponses::Ok(Json(
+                data.iter()
+                    .map(|x| DetailPermissionAttribute {
+                        id: x.id.to_string(),
+                        name: x.name.clone(),
+                        description: x.description.clone(),
+                        category: x.category.clone(),
+                    })
+                    .collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Categories in use with their number of attributes
+    #[oai(
+        path = "/permission-attribute/category/",
+        method = "get",
+        tag = "ApiPermissionAttributeTags::PermissionAttribute"
+    )]
+    async fn category_permission_attribute_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PermissionAttributeCategoryResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+
+            let data = get_permission_attribute_categories(&mut db).await?;
 
-        let (data, _, _) = match get_all_permission_attribute(
-            &mut tx,
-            None,
-            None,
-            None,
-            limit,
-            Some(true),
-        )
+            Ok(PermissionAttributeCategoryResponses::Ok(Json(
+                data.into_iter()
+                    .map(|(category, counts)| PermissionAttributeCategory {
+                        category,
+                        counts: counts as u32,
+                    })
+                    .collect(),
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return DropdownPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission",
-                        "dropdown_permission_attribute_api",
-                        "get_all_permission",
-                        &err.to_string(),
-                    ),
+    }
+
+    /// Every attribute grouped by category, uncategorized last
+    #[oai(
+        path = "/permission-attribute/grouped/",
+        method = "get",
+        tag = "ApiPermissionAttributeTags::PermissionAttribute"
+    )]
+    async fn grouped_permission_attribute_api(
+        &self,
+        Query(search): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> GroupedPermissionAttributeResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+
+            let (mut data, _, _) =
+                get_all_permission_attribute(&mut db, None, None, search, None, None, Some(true))
+                    .await?;
+            // Categories by name, None sorts first on Option so it is moved last
+            data.sort_by(|a, b| {
+                (a.category.is_none(), &a.category, &a.name).cmp(&(
+                    b.category.is_none(),
+                    &b.category,
+                    &b.name,
                 ))
+            });
+            let mut groups: Vec<GroupedPermissionAttribute> = vec![];
+            for x in data {
+                let attribute = DetailPermissionAttribute {
+                    id: x.id.to_string(),
+                    name: x.name,
+                    description: x.description,
+                    category: x.category.clone(),
+                };
+                match groups.last_mut() {
+                    Some(group) if group.category == x.category => group.attributes.push(attribute),
+                    _ => groups.push(GroupedPermissionAttribute {
+                        category: x.category,
+                        attributes: vec![attribute],
+                    }),
+                }
             }
-        };
 
-        DropdownPermissionAttributeResponses::Ok(Json(
-            data.iter()
-                .map(|x| DetailPermissionAttribute {
-                    id: x.id.to_string(),
-                    name: x.name.clone(),
-                    description: x.description.clone(),
-                })
-                .collect(),
-        ))
+            Ok(GroupedPermissionAttributeResponses::Ok(Json(groups)))
+        })
+        .await
     }
 
     #[oai(
@@ -243,91 +236,43 @@ impl ApiPermissionAttribute {
     async fn detail_permission_attribute_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> DetailPermissionAttributeResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DetailPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "detail_permission_attribute_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DetailPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "detail_permission_attribute_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "permission.read").await?;
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "permission_attribute_id with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_permission_attribute_by_id(&mut db, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "permission_attribute_id with id = {} not found",
+                    id
+                )));
             }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DetailPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "detail_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return DetailPermissionAttributeResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DetailPermissionAttributeResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("permission_attribute_id with id = {} not found", id),
-                }))
-            }
-        };
-        let data = match get_permission_attribute_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DetailPermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "detail_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if data.is_none() {
-            return DetailPermissionAttributeResponses::NotFound(Json(NotFoundResponse {
-                message: format!("permission_attribute_id with id = {} not found", id),
-            }));
-        }
-        let data = data.unwrap();
-        DetailPermissionAttributeResponses::Ok(Json(DetailPermissionAttribute {
-            id: data.id.to_string(),
-            name: data.name,
-            description: data.description,
-        }))
+            let data = data.unwrap();
+            Ok(DetailPermissionAttributeResponses::Ok(Json(
+                DetailPermissionAttribute {
+                    id: data.id.to_string(),
+                    name: data.name,
+                    description: data.description,
+                    category: data.category,
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -338,92 +283,38 @@ impl ApiPermissionAttribute {
     async fn create_permission_attribute_api(
         &self,
         Json(json): Json<CreatePermissionAttributeRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> CreatePermissionAttributeResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "create_permission_attribute_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return CreatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "create_permission_attribute_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "create_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return CreatePermissionAttributeResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        let now = Local::now().fixed_offset();
-        let new_permission = PermissionAttribute {
-            id: Uuid::now_v7(),
-            name: json.name,
-            description: json.description,
-            created_date: Some(now),
-            updated_date: Some(now),
-        };
-        if let Err(err) = create_permission_attribute(&mut tx, &new_permission).await {
-            return CreatePermissionAttributeResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission_attribute",
-                    "create_permission_attribute_api",
-                    "create_permission_attribute",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return CreatePermissionAttributeResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission_attribute",
-                    "create_permission_attribute_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        CreatePermissionAttributeResponses::Ok(Json(DetailPermissionAttribute {
-            id: new_permission.id.to_string(),
-            name: new_permission.name,
-            description: new_permission.description,
-        }))
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "permission.create").await?;
+            let now = utc_now();
+            let new_permission = PermissionAttribute {
+                id: Uuid::now_v7(),
+                name: json.name,
+                description: json.description,
+                category: json.category,
+                created_date: Some(now),
+                updated_date: Some(now),
+                deleted_date: None,
+            };
+            create_permission_attribute(&mut tx, &new_permission).await?;
+            tx.commit().await?;
+            Ok(CreatePermissionAttributeResponses::Ok(Json(
+                DetailPermissionAttribute {
+                    id: new_permission.id.to_string(),
+                    name: new_permission.name,
+                    description: new_permission.description,
+                    category: new_permission.category,
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -435,115 +326,52 @@ impl ApiPermissionAttribute {
         &self,
         Query(id): Query<String>,
         Json(json): Json<UpdatePermissionAttributeRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> UpdatePermissionAttributeResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return UpdatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "update_permission_attribute_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return UpdatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "update_permission_attribute_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return UpdatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "update_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "permission.update").await?;
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "permission_attribute_id with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_permission_attribute_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "permission_attribute_id with id = {} not found",
+                    id
+                )));
             }
-        };
-        if user.is_none() {
-            return UpdatePermissionAttributeResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return UpdatePermissionAttributeResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("permission_attribute_id with id = {} not found", id),
-                }))
-            }
-        };
-        let data = match get_permission_attribute_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return UpdatePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "update_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if data.is_none() {
-            return UpdatePermissionAttributeResponses::NotFound(Json(NotFoundResponse {
-                message: format!("permission_attribute_id with id = {} not found", id),
-            }));
-        }
-        let mut data = data.unwrap();
-        let now = Local::now().fixed_offset();
-        data.name = json.name;
-        data.description = json.description;
-        data.updated_date = Some(now);
-        if let Err(err) = update_permission_attribute(&mut tx, &data).await {
-            return UpdatePermissionAttributeResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission_attribute",
-                    "update_permission_attribute_api",
-                    "update_permission_attribute",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return UpdatePermissionAttributeResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission_attribute",
-                    "update_permission_attribute_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        UpdatePermissionAttributeResponses::Ok(Json(DetailPermissionAttribute {
-            id: data.id.to_string(),
-            name: data.name,
-            description: data.description,
-        }))
+            let mut data = data.unwrap();
+            let now = utc_now();
+            data.name = json.name;
+            data.description = json.description;
+            data.category = json.category;
+            data.updated_date = Some(now);
+            update_permission_attribute(&mut tx, &data).await?;
+            tx.commit().await?;
+            // Cached permission sets hold attribute names
+            all_permissions_changed(&mut redis_conn).await;
+            Ok(UpdatePermissionAttributeResponses::Ok(Json(
+                DetailPermissionAttribute {
+                    id: data.id.to_string(),
+                    name: data.name,
+                    description: data.description,
+                    category: data.category,
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -554,106 +382,126 @@ impl ApiPermissionAttribute {
     async fn delete_permission_attribute_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// Remove the grants of an attribute in use instead of answering 409
+        Query(cascade): Query<Option<bool>>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> DeletePermissionAttributeResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeletePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "delete_permission_attribute_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "permission.delete").await?;
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "permission_attribute_id with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_permission_attribute_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "permission_attribute_id with id = {} not found",
+                    id
+                )));
             }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DeletePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "delete_permission_attribute_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+            let mut data = data.unwrap();
+            let usages = get_permission_attribute_usages(&mut tx, &id).await?;
+            if !usages.is_empty() {
+                if !cascade.unwrap_or(false) {
+                    return Ok(DeletePermissionAttributeResponses::Conflict(Json(
+                        PermissionAttributeInUseResponse::new(
+                            format!("permission attribute {} is in use", data.name),
+                            usages
+                                .into_iter()
+                                .map(|(kind, counts)| PermissionAttributeUsage {
+                                    kind,
+                                    counts: counts as u32,
+                                })
+                                .collect(),
+                        ),
+                    )));
+                }
+                delete_permission_attribute_usages(&mut tx, &id).await?;
             }
-        };
+            soft_delete_permission_attribute(&mut tx, &mut data, utc_now()).await?;
+            tx.commit().await?;
+            // Cached permission sets hold attribute names
+            all_permissions_changed(&mut redis_conn).await;
+            Ok(DeletePermissionAttributeResponses::NoContent)
+        })
+        .await
+    }
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let user = match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeletePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "delete_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return DeletePermissionAttributeResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeletePermissionAttributeResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("permission_attribute_id with id = {} not found", id),
-                }))
-            }
-        };
-        let data = match get_permission_attribute_by_id(&mut tx, &id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeletePermissionAttributeResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.permission_attribute",
-                        "delete_permission_attribute_api",
-                        "get user from token",
-                        &err.to_string(),
-                    ),
-                ))
+    #[oai(
+        path = "/permission-attribute/restore/",
+        method = "post",
+        tag = "ApiPermissionAttributeTags::PermissionAttribute"
+    )]
+    async fn restore_permission_attribute_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> RestorePermissionAttributeResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "permission.update").await?;
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "permission_attribute_id with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_deleted_permission_attribute_by_id(&mut tx, &id).await?;
+            let mut data = match data {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "permission_attribute_id with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            // Another attribute may have taken the name since it was deleted
+            if get_permission_attribute_by_name(&mut tx, &data.name)
+                .await?
+                .is_some()
+            {
+                return Ok(RestorePermissionAttributeResponses::Conflict(Json(
+                    ConflictResponse::new(format!(
+                        "permission attribute {} already exists",
+                        data.name
+                    ))
+                    .with_field("name".to_string()),
+                )));
             }
-        };
-        if data.is_none() {
-            return DeletePermissionAttributeResponses::NotFound(Json(NotFoundResponse {
-                message: format!("permission_attribute_id with id = {} not found", id),
-            }));
-        }
-        let data = data.unwrap();
-        if let Err(err) = delete_permission_attribute(&mut tx, &data).await {
-            return DeletePermissionAttributeResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission_attribute",
-                    "delete_permission_attribute_api",
-                    "delete_permission_attribute",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return DeletePermissionAttributeResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.permission_attribute",
-                    "delete_permission_attribute_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        DeletePermissionAttributeResponses::NoContent
+            restore_permission_attribute(&mut tx, &mut data, utc_now()).await?;
+            tx.commit().await?;
+            // Cached permission sets hold attribute names
+            all_permissions_changed(&mut redis_conn).await;
+            Ok(RestorePermissionAttributeResponses::Ok(Json(
+                DetailPermissionAttribute {
+                    id: data.id.to_string(),
+                    name: data.name,
+                    description: data.description,
+                    category: data.category,
+                },
+            )))
+        })
+        .await
     }
 }
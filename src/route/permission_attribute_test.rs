@@ -6,28 +6,58 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    core::test_utils::generate_test_user,
-    factory::permission_attribute::PermissionAttributeFactory,
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    factory::{permission::PermissionFactory, permission_attribute::PermissionAttributeFactory},
     init_openapi_route,
-    model::permission_attribute::{PermissionAttribute, TABLE_NAME},
+    model::{
+        permission_attribute::{PermissionAttribute, TABLE_NAME},
+        user_permission::TABLE_NAME as USER_PERMISSION_TABLE_NAME,
+    },
+    repository::permission_attribute::{
+        get_permission_attribute_by_name, get_permission_attribute_usages,
+    },
     schema::permission_attribute::DetailPermissionAttribute,
     settings::get_config,
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &[
+    "permission.create",
+    "permission.read",
+    "permission.update",
+    "permission.delete",
+];
+
+/// Attributes of PERMISSIONS created by grant_test_permissions by name, listings include them
+async fn granted_attributes(db: &PgPool) -> anyhow::Result<Vec<PermissionAttribute>> {
+    let mut conn = db.acquire().await?;
+    let mut res = vec![];
+    for name in ["create", "delete", "read", "update"] {
+        res.push(
+            get_permission_attribute_by_name(&mut conn, name)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("attribute {} not granted", name))?,
+        );
+    }
+    Ok(res)
+}
+
 #[sqlx::test]
 async fn test_paginate_permission_attribute_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -36,10 +66,18 @@ async fn test_paginate_permission_attribute_api(pool: PgPool) -> anyhow::Result<
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_attribute_factory = PermissionAttributeFactory::new();
     let mut permission_attributes = permission_attribute_factory
         .generate_many(&app_state.db, 5, ())
         .await?;
+    permission_attributes.extend(granted_attributes(&app_state.db).await?);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -60,7 +98,7 @@ async fn test_paginate_permission_attribute_api(pool: PgPool) -> anyhow::Result<
         }
     });
     resp.assert_json(&json!({
-        "counts":5,
+        "counts":9,
         "page":1,
         "page_count":1,
         "page_size":10,
@@ -69,6 +107,7 @@ async fn test_paginate_permission_attribute_api(pool: PgPool) -> anyhow::Result<
             id: x.id.to_string(),
             name: x.name.clone(),
             description: x.description.clone(),
+            category: x.category.clone(),
         })
         .collect::<Vec<DetailPermissionAttribute>>(),
     }))
@@ -81,14 +120,13 @@ async fn test_dropdown_permission_attribute_api(pool: PgPool) -> anyhow::Result<
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -97,10 +135,18 @@ async fn test_dropdown_permission_attribute_api(pool: PgPool) -> anyhow::Result<
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_attribute_factory = PermissionAttributeFactory::new();
     let mut permission_attributes = permission_attribute_factory
         .generate_many(&app_state.db, 5, ())
         .await?;
+    permission_attributes.extend(granted_attributes(&app_state.db).await?);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -127,6 +173,7 @@ async fn test_dropdown_permission_attribute_api(pool: PgPool) -> anyhow::Result<
                 id: x.id.to_string(),
                 name: x.name.clone(),
                 description: x.description.clone(),
+                category: x.category.clone(),
             })
             .collect::<Vec<DetailPermissionAttribute>>(),
     )
@@ -139,14 +186,13 @@ async fn test_detail_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -155,6 +201,13 @@ async fn test_detail_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_attribute_factory = PermissionAttributeFactory::new();
     let permission_attribute = permission_attribute_factory
         .generate_one(&app_state.db, ())
@@ -176,6 +229,7 @@ async fn test_detail_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
         id: permission_attribute.id.to_string(),
         name: permission_attribute.name,
         description: permission_attribute.description,
+        category: permission_attribute.category,
     };
     resp.assert_json(&json!(&json_response)).await;
     Ok(())
@@ -186,14 +240,13 @@ async fn test_create_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -202,6 +255,13 @@ async fn test_create_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_attribute_factory = PermissionAttributeFactory::new();
     let _ = permission_attribute_factory
         .generate_one(&app_state.db, ())
@@ -228,7 +288,7 @@ async fn test_create_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
     let id: Uuid = id.unwrap().deserialize();
     let new_permission_attribute: Option<PermissionAttribute> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(&id)
+            .bind(id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(new_permission_attribute.is_some());
@@ -246,14 +306,13 @@ async fn test_update_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -262,6 +321,13 @@ async fn test_update_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_attribute_factory = PermissionAttributeFactory::new();
     let permission_attribute = permission_attribute_factory
         .generate_one(&app_state.db, ())
@@ -285,7 +351,7 @@ async fn test_update_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
     resp.assert_status_is_ok();
     let updated_permission_attribute: Option<PermissionAttribute> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(&permission_attribute.id)
+            .bind(permission_attribute.id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(updated_permission_attribute.is_some());
@@ -303,14 +369,13 @@ async fn test_delete_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -319,6 +384,13 @@ async fn test_delete_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_attribute_factory = PermissionAttributeFactory::new();
     let permission_attribute = permission_attribute_factory
         .generate_one(&app_state.db, ())
@@ -334,13 +406,240 @@ async fn test_delete_permission_attribute_api(pool: PgPool) -> anyhow::Result<()
         .send()
         .await;
 
-    // Expect
+    // Expect soft deleted
     resp.assert_status(StatusCode::NO_CONTENT);
     let deleted_permission_attribute: Option<PermissionAttribute> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(&permission_attribute.id)
+            .bind(permission_attribute.id)
             .fetch_optional(&mut *db)
             .await?;
-    assert!(deleted_permission_attribute.is_none());
+    assert!(deleted_permission_attribute.unwrap().deleted_date.is_some());
+    let resp = cli
+        .get("/api/permission-attribute/detail")
+        .query("id", &permission_attribute.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_delete_used_permission_attribute_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given an attribute listed on a permission and granted to a user
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let attribute = PermissionAttributeFactory::new()
+        .name("approve")
+        .create(&mut tx)
+        .await?;
+    let permission = PermissionFactory::new()
+        .permission_name("invoice")
+        .with_attribute(&attribute)
+        .create(&mut tx)
+        .await?;
+    sqlx::query(
+        format!(
+            "INSERT INTO {} (permission_id, user_id, attribute_id) VALUES ($1, $2, $3)",
+            USER_PERMISSION_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(permission.id)
+    .bind(test_user.user.id)
+    .bind(attribute.id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+
+    // When
+    let resp = cli
+        .delete("/api/permission-attribute")
+        .query("id", &attribute.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect blocked with the usages
+    resp.assert_status(StatusCode::CONFLICT);
+    resp.assert_json(&json!({
+        "code": "ATTRIBUTE_IN_USE",
+        "message": "permission attribute approve is in use",
+        "usages": [
+            {"kind": "permission", "counts": 1},
+            {"kind": "user_permission", "counts": 1},
+        ],
+    }))
+    .await;
+
+    // When confirmed
+    let resp = cli
+        .delete("/api/permission-attribute")
+        .query("id", &attribute.id.to_string())
+        .query("cascade", &true)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect grants removed
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let mut tx = app_state.db.begin().await?;
+    assert!(get_permission_attribute_usages(&mut tx, &attribute.id)
+        .await?
+        .is_empty());
+    tx.commit().await?;
+
+    // When restored
+    let resp = cli
+        .post("/api/permission-attribute/restore")
+        .query("id", &attribute.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({
+        "id": attribute.id.to_string(),
+        "name": "approve",
+        "description": attribute.description,
+        "category": attribute.category,
+    }))
+    .await;
+    let resp = cli
+        .post("/api/permission-attribute/restore")
+        .query("id", &attribute.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_category_permission_attribute_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let invoice = PermissionAttributeFactory::new()
+        .name("invoice")
+        .category("billing")
+        .create(&mut tx)
+        .await?;
+    let refund = PermissionAttributeFactory::new()
+        .name("refund")
+        .category("billing")
+        .create(&mut tx)
+        .await?;
+    let export = PermissionAttributeFactory::new()
+        .name("export")
+        .category("reporting")
+        .create(&mut tx)
+        .await?;
+    let mut misc = PermissionAttributeFactory::new()
+        .name("misc")
+        .create(&mut tx)
+        .await?;
+    misc.category = None;
+    sqlx::query(format!("UPDATE {} SET category = NULL WHERE id = $1", TABLE_NAME).as_str())
+        .bind(misc.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    let granted = granted_attributes(&app_state.db).await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+    let detail = |x: &PermissionAttribute| DetailPermissionAttribute {
+        id: x.id.to_string(),
+        name: x.name.clone(),
+        description: x.description.clone(),
+        category: x.category.clone(),
+    };
+
+    // When
+    let categories = cli
+        .get("/api/permission-attribute/category")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    let grouped = cli
+        .get("/api/permission-attribute/grouped")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    let dropdown = cli
+        .get("/api/permission-attribute/dropdown")
+        .query("category", &"reporting")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    categories.assert_status_is_ok();
+    categories
+        .assert_json(&json!([
+            {"category": "access", "counts": 4},
+            {"category": "billing", "counts": 2},
+            {"category": "reporting", "counts": 1},
+            {"category": null, "counts": 1},
+        ]))
+        .await;
+    grouped.assert_status_is_ok();
+    grouped
+        .assert_json(&json!([
+            {"category": "access", "attributes": granted.iter().map(detail).collect::<Vec<_>>()},
+            {"category": "billing", "attributes": [detail(&invoice), detail(&refund)]},
+            {"category": "reporting", "attributes": [detail(&export)]},
+            {"category": null, "attributes": [detail(&misc)]},
+        ]))
+        .await;
+    dropdown.assert_status_is_ok();
+    dropdown.assert_json(&json!([detail(&export)])).await;
     Ok(())
 }
@@ -9,10 +9,16 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    core::{test_utils::generate_test_user, utils::datetime_to_string_opt},
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+        utils::datetime_to_string_opt,
+    },
     factory::{
-        permission::PermissionFactory, permission_attribute::PermissionAttributeFactory,
-        permission_attribute_list::PermissionAttributeListFactory,
+        group::GroupFactory, permission::PermissionFactory,
+        permission_attribute::PermissionAttributeFactory,
+        permission_attribute_list::PermissionAttributeListFactory, role::RoleFactory,
+        user::UserFactory,
     },
     init_openapi_route,
     model::{
@@ -23,6 +29,7 @@ use crate::{
         },
         user::User,
     },
+    repository::permission::get_permission_by_name,
     schema::permission::{
         DetailPermission, DetailUserPermission, PermissionAllResponse, PermissionDropdownResponse,
     },
@@ -30,6 +37,21 @@ use crate::{
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &[
+    "permission.create",
+    "permission.read",
+    "permission.update",
+    "permission.delete",
+];
+
+/// Permission of PERMISSIONS created by grant_test_permissions, listings include it
+async fn granted_permission(db: &PgPool) -> anyhow::Result<Permission> {
+    get_permission_by_name(&mut *db.acquire().await?, "permission")
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("permission not granted"))
+}
+
 #[derive(Clone)]
 struct ExtData {
     pub created_by: User,
@@ -41,14 +63,13 @@ async fn test_paginate_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -57,6 +78,13 @@ async fn test_paginate_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_factory = PermissionFactory::<ExtData>::new();
     permission_factory.modified_many(|data, _, ext| Permission {
         id: data.id,
@@ -80,6 +108,7 @@ async fn test_paginate_permission_api(pool: PgPool) -> anyhow::Result<()> {
             },
         )
         .await?;
+    permissions.push(granted_permission(&app_state.db).await?);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -110,18 +139,18 @@ async fn test_paginate_permission_api(pool: PgPool) -> anyhow::Result<()> {
             is_group: item.is_group.unwrap_or(false),
             created_date: datetime_to_string_opt(item.created_date),
             updated_date: datetime_to_string_opt(item.updated_date),
-            created_by: Some(DetailUserPermission {
+            created_by: item.created_by.map(|_| DetailUserPermission {
                 id: test_user.user.id.to_string(),
                 user_name: test_user.user.user_name.clone(),
             }),
-            updated_by: Some(DetailUserPermission {
+            updated_by: item.updated_by.map(|_| DetailUserPermission {
                 id: test_user.user.id.to_string(),
                 user_name: test_user.user.user_name.clone(),
             }),
         });
     }
     resp.assert_json(&json!({
-        "counts": 5,
+        "counts": 6,
         "page": 1,
         "page_count": 1,
         "page_size": 10,
@@ -136,14 +165,13 @@ async fn test_get_all_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -152,6 +180,13 @@ async fn test_get_all_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_factory = PermissionFactory::<ExtData>::new();
     permission_factory.modified_many(|data, _, ext| Permission {
         id: data.id,
@@ -175,6 +210,7 @@ async fn test_get_all_permission_api(pool: PgPool) -> anyhow::Result<()> {
             },
         )
         .await?;
+    permissions.push(granted_permission(&app_state.db).await?);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -216,14 +252,13 @@ async fn test_get_dropdown_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -232,6 +267,13 @@ async fn test_get_dropdown_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_factory = PermissionFactory::<ExtData>::new();
     permission_factory.modified_many(|data, _, ext| Permission {
         id: data.id,
@@ -255,6 +297,7 @@ async fn test_get_dropdown_permission_api(pool: PgPool) -> anyhow::Result<()> {
             },
         )
         .await?;
+    permissions.push(granted_permission(&app_state.db).await?);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -290,14 +333,13 @@ async fn test_detail_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -306,6 +348,13 @@ async fn test_detail_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_factory = PermissionFactory::<ExtData>::new();
     permission_factory.modified_one(|data, ext| Permission {
         id: data.id,
@@ -394,18 +443,17 @@ async fn test_create_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
     let mut attribute_factory = PermissionAttributeFactory::new();
     let attributes = attribute_factory
         .generate_many(&app_state.db, 2, ())
         .await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -414,6 +462,13 @@ async fn test_create_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -440,7 +495,7 @@ async fn test_create_permission_api(pool: PgPool) -> anyhow::Result<()> {
     let new_permission_id: Uuid = new_permission_id.unwrap().deserialize();
     let new_permission: Option<Permission> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(&new_permission_id)
+            .bind(new_permission_id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(new_permission.is_some());
@@ -457,7 +512,7 @@ async fn test_create_permission_api(pool: PgPool) -> anyhow::Result<()> {
         )
         .as_str(),
     )
-    .bind(&new_permission.id)
+    .bind(new_permission.id)
     .fetch_all(&mut *db)
     .await?;
     assert_eq!(permission_atribute_list.len(), 2);
@@ -469,8 +524,8 @@ async fn test_create_permission_api(pool: PgPool) -> anyhow::Result<()> {
             )
             .as_str(),
         )
-        .bind(&new_permission.id)
-        .bind(&item.id)
+        .bind(new_permission.id)
+        .bind(item.id)
         .fetch_optional(&mut *db)
         .await?;
         assert!(permission_atribute_list.is_some());
@@ -483,14 +538,13 @@ async fn test_update_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -499,6 +553,13 @@ async fn test_update_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_factory = PermissionFactory::<ExtData>::new();
     permission_factory.modified_one(|data, ext| Permission {
         id: data.id,
@@ -548,7 +609,7 @@ async fn test_update_permission_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status_is_ok();
     let updated_permission: Option<Permission> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id=$1", TABLE_NAME).as_str())
-            .bind(&permission.id)
+            .bind(permission.id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(updated_permission.is_some());
@@ -568,7 +629,7 @@ async fn test_update_permission_api(pool: PgPool) -> anyhow::Result<()> {
         )
         .as_str(),
     )
-    .bind(&updated_permission.id)
+    .bind(updated_permission.id)
     .fetch_all(&mut *db)
     .await?;
     assert_eq!(permision_attribute.len(), 2);
@@ -580,7 +641,7 @@ async fn test_update_permission_api(pool: PgPool) -> anyhow::Result<()> {
             )
             .as_str(),
         )
-        .bind(&updated_permission.id)
+        .bind(updated_permission.id)
         .bind(item.id)
         .fetch_optional(&mut *db)
         .await?;
@@ -594,14 +655,13 @@ async fn test_delete_permission_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -610,6 +670,13 @@ async fn test_delete_permission_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut permission_factory = PermissionFactory::<ExtData>::new();
     permission_factory.modified_one(|data, ext| Permission {
         id: data.id,
@@ -667,9 +734,163 @@ async fn test_delete_permission_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status(StatusCode::NO_CONTENT);
     let permission: Option<Permission> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id=$1", TABLE_NAME).as_str())
-            .bind(&permission.id)
+            .bind(permission.id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(permission.is_none());
     Ok(())
 }
+
+#[sqlx::test]
+async fn test_permission_holders_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given a user holding invoice read directly and through a role, another through a group
+    // and one whose only grant is through a deleted role
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let read = PermissionAttributeFactory::new()
+        .name("read")
+        .create(&mut tx)
+        .await?;
+    let write = PermissionAttributeFactory::new()
+        .name("write")
+        .create(&mut tx)
+        .await?;
+    let permission = PermissionFactory::new()
+        .permission_name("invoice")
+        .with_attribute(&read)
+        .with_attribute(&write)
+        .create(&mut tx)
+        .await?;
+    let cashier = RoleFactory::new()
+        .role_name("cashier")
+        .with_permission(&permission, &read)
+        .create(&mut tx)
+        .await?;
+    let retired = RoleFactory::new()
+        .role_name("retired")
+        .with_permission(&permission, &read)
+        .deleted()
+        .create(&mut tx)
+        .await?;
+    let finance = GroupFactory::new()
+        .group_name("finance")
+        .with_permission(&permission, &write)
+        .create(&mut tx)
+        .await?;
+    let alice = UserFactory::new()
+        .user_name("alice")
+        .with_permission(&permission, &read)
+        .with_role(&cashier)
+        .create(&mut tx)
+        .await?;
+    let bob = UserFactory::new()
+        .user_name("bob")
+        .with_group(&finance)
+        .create(&mut tx)
+        .await?;
+    UserFactory::new()
+        .user_name("carol")
+        .with_role(&retired)
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+
+    // When
+    let resp = cli
+        .get("/api/permissions/holders")
+        .query("id", &permission.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect every grant path, carol left out
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({
+        "id": permission.id.to_string(),
+        "permission_name": "invoice",
+        "holders": [
+            {
+                "user": {"id": alice.id.to_string(), "user_name": "alice"},
+                "status": alice.status,
+                "grants": [
+                    {
+                        "attribute": {"id": read.id.to_string(), "name": "read"},
+                        "via": "direct",
+                        "source": Null,
+                        "expires_at": Null,
+                    },
+                    {
+                        "attribute": {"id": read.id.to_string(), "name": "read"},
+                        "via": "role",
+                        "source": {"id": cashier.id.to_string(), "name": "cashier"},
+                        "expires_at": Null,
+                    },
+                ],
+            },
+            {
+                "user": {"id": bob.id.to_string(), "user_name": "bob"},
+                "status": bob.status,
+                "grants": [
+                    {
+                        "attribute": {"id": write.id.to_string(), "name": "write"},
+                        "via": "group",
+                        "source": {"id": finance.id.to_string(), "name": "finance"},
+                        "expires_at": Null,
+                    },
+                ],
+            },
+        ],
+    }))
+    .await;
+
+    // When filtered by attribute
+    let resp = cli
+        .get("/api/permissions/holders")
+        .query("id", &permission.id.to_string())
+        .query("attribute_id", &write.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let body: Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+    assert_eq!(body["holders"].as_array().map(|x| x.len()), Some(1));
+    assert_eq!(body["holders"][0]["user"]["user_name"], "bob");
+
+    // When the permission does not exist
+    let resp = cli
+        .get("/api/permissions/holders")
+        .query("id", &Uuid::now_v7().to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
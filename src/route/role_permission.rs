@@ -1,32 +1,38 @@
 use std::sync::Arc;
 
-use chrono::Local;
 use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
 use uuid::Uuid;
 
 use crate::{
-    core::security::{get_user_from_token, BearerAuthorization},
+    core::{
+        db_error::retry_transaction,
+        error::{respond, AppError},
+        permission_cache::permissions_changed,
+        permission_grant::resolve_grant_target,
+        security::{ensure_permission, BearerAuthorization, ReadAuthContext},
+        utils::utc_now,
+        validation::Validate,
+    },
     model::role_permission::RolePermission,
     repository::{
         permission::get_permission_by_id,
         permission_attribute::get_permission_attribute_by_id,
         role::get_role_by_id,
         role_permission::{
-            create_role_permission, delete_role_permission, get_all_role_permission,
-            get_detail_role_permission,
+            copy_role_permissions, create_role_permission, delete_role_permission,
+            delete_role_permissions_by_role, get_all_role_permission, get_detail_role_permission,
         },
+        user_group_roles::get_user_ids_by_role,
     },
     schema::{
-        common::{
-            BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-            UnauthorizedResponse,
-        },
+        common::PaginateResponse,
         role_permission::{
-            CreateRolePermissionResponses, DeleteRolePermissionResponses,
-            DetailPermissionAttributeRolePermission, DetailPermissionRolePermission,
-            DetailRolePermission, DetailRoleRolePermission, PaginateRolePermissionResponses,
-            RolePermissionCreateRequest, RolePermissionCreateResponse,
+            CopyRolePermissionResponses, CreateRolePermissionResponses,
+            DeleteRolePermissionResponses, DetailPermissionAttributeRolePermission,
+            DetailPermissionRolePermission, DetailRolePermission, DetailRoleRolePermission,
+            PaginateRolePermissionResponses, RolePermissionCopyMode, RolePermissionCopyRequest,
+            RolePermissionCopyResponse, RolePermissionCreateRequest, RolePermissionCreateResponse,
         },
     },
     AppState,
@@ -52,165 +58,76 @@ impl ApiRolePermission {
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(all): Query<Option<bool>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PaginateRolePermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "paginate_role_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "role.read").await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "paginate_role_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
+            // Validasi
+            let role_id = match Uuid::parse_str(&role_id) {
                 Ok(val) => val,
-                Err(err) => {
-                    return PaginateRolePermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.role_permission",
-                            "paginate_role_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
+                Err(_) => {
+                    return Err(AppError::bad_request(format!(
+                        "role with id = {} not found",
+                        role_id
+                    )))
                 }
             };
-        if request_user.is_none() {
-            return PaginateRolePermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-
-        // Validasi
-        let role_id = match Uuid::parse_str(&role_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return PaginateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("role with id = {} not found", role_id),
-                }))
-            }
-        };
-        let role = match get_role_by_id(&mut tx, &role_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "paginate_role_permission_api",
-                        "get_role_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+            let role = get_role_by_id(&mut db, &role_id).await?;
+            if role.is_none() {
+                return Err(AppError::bad_request(format!(
+                    "role with id = {} not found",
+                    role_id
+                )));
             }
-        };
-        if role.is_none() {
-            return PaginateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("role with id = {} not found", role_id),
-            }));
-        }
-        let role = role.unwrap();
+            let role = role.unwrap();
 
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) = match get_all_role_permission(
-            &mut tx,
-            Some(page),
-            Some(page_size),
-            &role_id,
-            all,
-        )
-        .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "paginate_role_permission_api",
-                        "get_all_role_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                get_all_role_permission(&mut db, Some(page), Some(page_size), &role_id, all)
+                    .await?;
 
-        let mut results: Vec<DetailRolePermission> = vec![];
-        for item in data {
-            let permission = match get_permission_by_id(&mut tx, &item.permission_id).await {
-                Ok(val) => val.unwrap(),
-                Err(err) => {
-                    return PaginateRolePermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.role_permission",
-                            "paginate_role_permission_api",
-                            "get_permission_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-            let attribute = match get_permission_attribute_by_id(&mut tx, &item.attribute_id).await
-            {
-                Ok(val) => val.unwrap(),
-                Err(err) => {
-                    return PaginateRolePermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.role_permission",
-                            "paginate_role_permission_api",
-                            "get_permission_attribute_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-            results.push(DetailRolePermission {
-                role: DetailRoleRolePermission {
-                    id: role.id.to_string(),
-                    role_name: role.role_name.clone(),
-                },
-                permission: DetailPermissionRolePermission {
-                    id: permission.id.to_string(),
-                    permission_name: permission.permission_name,
-                },
-                permission_attribute: DetailPermissionAttributeRolePermission {
-                    id: attribute.id.to_string(),
-                    name: attribute.name,
+            let mut results: Vec<DetailRolePermission> = vec![];
+            for item in data {
+                let permission = get_permission_by_id(&mut db, &item.permission_id)
+                    .await?
+                    .unwrap();
+                let attribute = get_permission_attribute_by_id(&mut db, &item.attribute_id)
+                    .await?
+                    .unwrap();
+                results.push(DetailRolePermission {
+                    role: DetailRoleRolePermission {
+                        id: role.id.to_string(),
+                        role_name: role.role_name.clone(),
+                    },
+                    permission: DetailPermissionRolePermission {
+                        id: permission.id.to_string(),
+                        permission_name: permission.permission_name,
+                    },
+                    permission_attribute: DetailPermissionAttributeRolePermission {
+                        id: attribute.id.to_string(),
+                        name: attribute.name,
+                    },
+                });
+            }
+            Ok(PaginateRolePermissionResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results,
                 },
-            });
-        }
-        PaginateRolePermissionResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results,
-        }))
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -221,201 +138,72 @@ impl ApiRolePermission {
     async fn create_role_permission_api(
         &self,
         Json(json): Json<RolePermissionCreateRequest>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> CreateRolePermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "create_role_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "create_role_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return CreateRolePermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.role_permission",
-                            "create_role_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return CreateRolePermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
+        if let Some(errors) = json.validation_errors() {
+            return CreateRolePermissionResponses::UnprocessableEntity(Json(errors));
         }
-        let request_user = request_user.unwrap();
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "role.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let (new_role_permision, user_ids) = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        // Validate
-        let role_id = match Uuid::parse_str(&json.role_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("role with id {} not found", json.role_id),
-                }));
-            }
-        };
-        let role = match get_role_by_id(&mut tx, &role_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "create_role_permission_api",
-                        "get_role_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if role.is_none() {
-            return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("role with id {} not found", json.role_id),
-            }));
-        }
+                // Validate
+                let not_found =
+                    || AppError::bad_request(format!("role with id {} not found", json.role_id));
+                let role_id = Uuid::parse_str(&json.role_id).map_err(|_| not_found())?;
+                get_role_by_id(&mut tx, &role_id)
+                    .await?
+                    .ok_or_else(not_found)?;
 
-        let permission_id = match Uuid::parse_str(&json.permission_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission with id {} not found", json.permission_id),
-                }));
-            }
-        };
-        let permission = match get_permission_by_id(&mut tx, &permission_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "create_role_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if permission.is_none() {
-            return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("permission with id {} not found", json.permission_id),
-            }));
-        }
-
-        let attribute_id = match Uuid::parse_str(&json.attribute_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("attribute with id {} not found", json.attribute_id),
-                }));
-            }
-        };
-        let attribute = match get_permission_attribute_by_id(&mut tx, &attribute_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "create_role_permission_api",
-                        "get_permission_attribute_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if attribute.is_none() {
-            return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("attribute with id {} not found", json.attribute_id),
-            }));
-        }
-        let role_permission = match get_detail_role_permission(
-            &mut tx,
-            &role_id,
-            &permission_id,
-            &attribute_id,
-        )
+                let (permission, attribute) =
+                    resolve_grant_target(&mut *tx, &json.permission_id, &json.attribute_id)
+                        .await?;
+                let role_permission =
+                    get_detail_role_permission(&mut tx, &role_id, &permission.id, &attribute.id)
+                        .await?;
+                if role_permission.is_some() {
+                    return Err(AppError::conflict(format!(
+                        "role_permission with role_id = {}, permission_id = {}, attribute_id = {} already exists",
+                        json.role_id, json.permission_id, json.attribute_id
+                    )));
+                }
+                let now = utc_now();
+                let new_role_permision = RolePermission {
+                    role_id,
+                    permission_id: permission.id,
+                    attribute_id: attribute.id,
+                    created_by: Some(request_user.id),
+                    updated_by: Some(request_user.id),
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                };
+                create_role_permission(&mut tx, &new_role_permision).await?;
+                let user_ids = get_user_ids_by_role(&mut tx, &role_id).await?;
+                tx.commit().await?;
+                Ok((new_role_permision, user_ids))
+            })
+            .await?;
+            // Cached permission sets of the role members are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(CreateRolePermissionResponses::Ok(Json(
+                RolePermissionCreateResponse {
+                    role_id: new_role_permision.role_id.to_string(),
+                    permission_id: new_role_permision.permission_id.to_string(),
+                    attribute_id: new_role_permision.attribute_id.to_string(),
+                },
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "create_role_permission_api",
-                        "get_detail_role_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if role_permission.is_some() {
-            return CreateRolePermissionResponses::BadRequest(Json(BadRequestResponse { message: format!("role_permission with role_id = {}, permission_id = {}, attribute_id = {} already exists", json.role_id, json.permission_id, json.attribute_id)}));
-        }
-        let now = Local::now().fixed_offset();
-        let new_role_permision = RolePermission {
-            role_id,
-            permission_id,
-            attribute_id,
-            created_by: Some(request_user.id),
-            updated_by: Some(request_user.id),
-            created_date: Some(now),
-            updated_date: Some(now),
-        };
-        if let Err(err) = create_role_permission(&mut tx, &new_role_permision).await {
-            return CreateRolePermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.role_permission",
-                    "create_role_permission_api",
-                    "create_role_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return CreateRolePermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.role_permission",
-                    "create_role_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        CreateRolePermissionResponses::Ok(Json(RolePermissionCreateResponse {
-            role_id: new_role_permision.role_id.to_string(),
-            permission_id: new_role_permision.permission_id.to_string(),
-            attribute_id: new_role_permision.attribute_id.to_string(),
-        }))
     }
 
     #[oai(
@@ -428,187 +216,131 @@ impl ApiRolePermission {
         Query(role_id): Query<String>,
         Query(permission_id): Query<String>,
         Query(attribute_id): Query<String>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> DeleteRolePermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "delete_role_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "role.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let user_ids = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "delete_role_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
+                // Validate
+                let not_found =
+                    || AppError::bad_request(format!("role with id {} not found", role_id));
+                let role_id = Uuid::parse_str(&role_id).map_err(|_| not_found())?;
+                get_role_by_id(&mut tx, &role_id)
+                    .await?
+                    .ok_or_else(not_found)?;
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteRolePermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.role_permission",
-                            "delete_role_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return DeleteRolePermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
+                let (permission, attribute) =
+                    resolve_grant_target(&mut *tx, &permission_id, &attribute_id).await?;
+                let role_permission =
+                    get_detail_role_permission(&mut tx, &role_id, &permission.id, &attribute.id)
+                        .await?
+                        .ok_or_else(|| {
+                            AppError::not_found(format!(
+                                "role_permission with role_id = {}, permission_id = {}, attribute_id = {} not exists",
+                                role_id, permission.id, attribute.id
+                            ))
+                        })?;
+                delete_role_permission(&mut tx, &role_permission).await?;
+                let user_ids = get_user_ids_by_role(&mut tx, &role_id).await?;
+                tx.commit().await?;
+                Ok::<_, AppError>(user_ids)
+            })
+            .await?;
+            // Cached permission sets of the role members are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(DeleteRolePermissionResponses::NoContent)
+        })
+        .await
+    }
 
-        // Validate
-        let role_id = match Uuid::parse_str(&role_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("role with id {} not found", role_id),
-                }));
-            }
-        };
-        let role = match get_role_by_id(&mut tx, &role_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "delete_role_permission_api",
-                        "get_role_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if role.is_none() {
-            return DeleteRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("role with id {} not found", role_id),
-            }));
+    /// Grant the target role the permission attributes of the source role, replace drops
+    /// the grants the target had before. Everything happens in one transaction.
+    #[oai(
+        path = "/role-permissions/copy/",
+        method = "post",
+        tag = "ApiRolePermissionTags::RolePermission"
+    )]
+    async fn copy_role_permission_api(
+        &self,
+        Json(json): Json<RolePermissionCopyRequest>,
+        ctx: ReadAuthContext,
+        state: Data<&Arc<AppState>>,
+        _auth: BearerAuthorization,
+    ) -> CopyRolePermissionResponses {
+        if let Some(errors) = json.validation_errors() {
+            return CopyRolePermissionResponses::UnprocessableEntity(Json(errors));
         }
+        let mode = json.mode.unwrap_or_default();
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "role.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let (source_role_id, target_role_id, copied, removed, user_ids) =
+                retry_transaction(|| async {
+                    let mut tx = state.db.begin().await?;
 
-        let permission_id = match Uuid::parse_str(&permission_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission with id {} not found", permission_id),
-                }));
-            }
-        };
-        let permission = match get_permission_by_id(&mut tx, &permission_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "delete_role_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if permission.is_none() {
-            return DeleteRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("permission with id {} not found", permission_id),
-            }));
-        }
+                    // Validate, ids are valid uuids after validation_errors
+                    let source_role_id = Uuid::parse_str(&json.source_role_id).unwrap_or_default();
+                    let target_role_id = Uuid::parse_str(&json.target_role_id).unwrap_or_default();
+                    for (role_id, raw_id) in [
+                        (&source_role_id, &json.source_role_id),
+                        (&target_role_id, &json.target_role_id),
+                    ] {
+                        if get_role_by_id(&mut tx, role_id).await?.is_none() {
+                            return Err(AppError::bad_request(format!(
+                                "role with id {} not found",
+                                raw_id
+                            )));
+                        }
+                    }
 
-        let attribute_id = match Uuid::parse_str(&attribute_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("attribute with id {} not found", attribute_id),
-                }));
-            }
-        };
-        let attribute = match get_permission_attribute_by_id(&mut tx, &attribute_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "delete_role_permission_api",
-                        "get_permission_attribute_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if attribute.is_none() {
-            return DeleteRolePermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("attribute with id {} not found", attribute_id),
-            }));
-        }
-        let role_permission = match get_detail_role_permission(
-            &mut tx,
-            &role_id,
-            &permission_id,
-            &attribute_id,
-        )
+                    let removed = match mode {
+                        RolePermissionCopyMode::Merge => 0,
+                        RolePermissionCopyMode::Replace => {
+                            delete_role_permissions_by_role(&mut tx, &target_role_id).await?
+                        }
+                    };
+                    let copied = copy_role_permissions(
+                        &mut tx,
+                        &source_role_id,
+                        &target_role_id,
+                        &request_user.id,
+                        utc_now(),
+                    )
+                    .await?;
+                    let user_ids = get_user_ids_by_role(&mut tx, &target_role_id).await?;
+                    tx.commit().await?;
+                    Ok((source_role_id, target_role_id, copied, removed, user_ids))
+                })
+                .await?;
+            // Cached permission sets of the role members are stale now
+            permissions_changed(&mut redis_conn, &user_ids).await;
+            Ok(CopyRolePermissionResponses::Ok(Json(
+                RolePermissionCopyResponse {
+                    source_role_id: source_role_id.to_string(),
+                    target_role_id: target_role_id.to_string(),
+                    mode,
+                    copied: copied as u32,
+                    removed: removed as u32,
+                },
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteRolePermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.role_permission",
-                        "delete_role_permission_api",
-                        "get_detail_role_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if role_permission.is_none() {
-            return DeleteRolePermissionResponses::NotFound(Json(NotFoundResponse{
-                message: format!("role_permission with role_id = {}, permission_id = {}, attribute_id = {} not exists", role_id, permission_id, attribute_id)
-            }));
-        }
-        if let Err(err) = delete_role_permission(&mut tx, &role_permission.unwrap()).await {
-            return DeleteRolePermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.role_permission",
-                    "delete_role_permission_api",
-                    "delete_role_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return DeleteRolePermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.role_permission",
-                    "delete_role_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        DeleteRolePermissionResponses::NoContent
     }
 }
@@ -5,29 +5,35 @@ use serde_json::json;
 use sqlx::PgPool;
 
 use crate::{
-    core::test_utils::generate_test_user,
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
     factory::{
         permission::PermissionFactory, permission_attribute::PermissionAttributeFactory,
         role::RoleFactory,
     },
     init_openapi_route,
+    repository::role_permission::get_all_role_permission,
     settings::get_config,
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["role.read", "role.update"];
+
 #[sqlx::test]
 async fn role_permission_test(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -36,6 +42,13 @@ async fn role_permission_test(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let mut permission_factory = PermissionFactory::new();
@@ -127,3 +140,162 @@ async fn role_permission_test(pool: PgPool) -> anyhow::Result<()> {
     .await;
     Ok(())
 }
+
+#[sqlx::test]
+async fn copy_role_permission_test(pool: PgPool) -> anyhow::Result<()> {
+    // Given a source role with read and write, a target role with delete and write
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let read = PermissionAttributeFactory::new()
+        .name("read")
+        .create(&mut tx)
+        .await?;
+    let write = PermissionAttributeFactory::new()
+        .name("write")
+        .create(&mut tx)
+        .await?;
+    let delete = PermissionAttributeFactory::new()
+        .name("delete")
+        .create(&mut tx)
+        .await?;
+    let permission = PermissionFactory::new()
+        .permission_name("invoice")
+        .with_attribute(&read)
+        .with_attribute(&write)
+        .with_attribute(&delete)
+        .create(&mut tx)
+        .await?;
+    let source = RoleFactory::new()
+        .role_name("cashier")
+        .with_permission(&permission, &read)
+        .with_permission(&permission, &write)
+        .create(&mut tx)
+        .await?;
+    let target = RoleFactory::new()
+        .role_name("senior_cashier")
+        .with_permission(&permission, &delete)
+        .with_permission(&permission, &write)
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+    let attributes_of = |role_id: uuid::Uuid| {
+        let db = app_state.db.clone();
+        async move {
+            let mut tx = db.begin().await?;
+            let (data, _, _) =
+                get_all_role_permission(&mut tx, None, None, &role_id, Some(true)).await?;
+            let mut res: Vec<uuid::Uuid> = data.into_iter().map(|x| x.attribute_id).collect();
+            res.sort();
+            anyhow::Ok(res)
+        }
+    };
+
+    // When merged
+    let resp = cli
+        .post("/api/role-permissions/copy")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "source_role_id": source.id.to_string(),
+            "target_role_id": target.id.to_string(),
+        }))
+        .send()
+        .await;
+
+    // Expect read added, delete kept
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({
+        "source_role_id": source.id.to_string(),
+        "target_role_id": target.id.to_string(),
+        "mode": "merge",
+        "copied": 1,
+        "removed": 0,
+    }))
+    .await;
+    let mut expected = vec![read.id, write.id, delete.id];
+    expected.sort();
+    assert_eq!(attributes_of(target.id).await?, expected);
+
+    // When replaced
+    let resp = cli
+        .post("/api/role-permissions/copy")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "source_role_id": source.id.to_string(),
+            "target_role_id": target.id.to_string(),
+            "mode": "replace",
+        }))
+        .send()
+        .await;
+
+    // Expect same grants as the source
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({
+        "source_role_id": source.id.to_string(),
+        "target_role_id": target.id.to_string(),
+        "mode": "replace",
+        "copied": 2,
+        "removed": 3,
+    }))
+    .await;
+    assert_eq!(
+        attributes_of(target.id).await?,
+        attributes_of(source.id).await?
+    );
+
+    // When copied onto itself
+    let resp = cli
+        .post("/api/role-permissions/copy")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "source_role_id": source.id.to_string(),
+            "target_role_id": source.id.to_string(),
+        }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When replaced onto itself written in another case
+    let resp = cli
+        .post("/api/role-permissions/copy")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "source_role_id": source.id.to_string(),
+            "target_role_id": source.id.to_string().to_uppercase(),
+            "mode": "replace",
+        }))
+        .send()
+        .await;
+
+    // Expect rejected, the grants of the role are kept
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    let mut expected = vec![read.id, write.id];
+    expected.sort();
+    assert_eq!(attributes_of(source.id).await?, expected);
+    Ok(())
+}
@@ -1,14 +1,15 @@
 use std::{cmp::Ordering, sync::Arc};
 
 use chrono::{DateTime, FixedOffset};
-use poem::{http::StatusCode, test::TestClient};
+use poem::{http::StatusCode, test::TestClient, Route};
 use serde_json::{json, Value, Value::Null};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
     core::{
-        test_utils::{generate_random, generate_test_user},
+        session_store::create_redis_pool,
+        test_utils::{generate_random, generate_test_user, grant_test_permissions},
         utils::datetime_to_string_opt,
     },
     factory::role::RoleFactory,
@@ -18,24 +19,27 @@ use crate::{
         user::User,
     },
     repository::user::get_user_by_id,
+    role_routes,
     schema::role::{DetailRolePagination, RoleAllResponse, RoleDetailUser},
     settings::get_config,
-    AppState,
+    with_core_middleware, AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["role.create", "role.read", "role.update", "role.delete"];
+
 #[sqlx::test]
 async fn test_paginate_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -44,6 +48,13 @@ async fn test_paginate_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    let grants = grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     role_factory.modified_many(|data, _, _| Role {
         id: data.id,
@@ -55,8 +66,10 @@ async fn test_paginate_role_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let mut roles = role_factory.generate_many(&app_state.db, 10, ()).await?;
+    roles.push(grants);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -78,7 +91,7 @@ async fn test_paginate_role_api(pool: PgPool) -> anyhow::Result<()> {
     });
     let mut tx = app_state.db.begin().await?;
     let mut results: Vec<DetailRolePagination> = vec![];
-    for item in roles {
+    for item in roles.into_iter().take(10) {
         let mut created_by: Option<User> = None;
         if let Some(created_by_id) = item.created_by {
             (created_by, _) = get_user_by_id(&mut tx, &created_by_id, None).await?;
@@ -111,9 +124,9 @@ async fn test_paginate_role_api(pool: PgPool) -> anyhow::Result<()> {
         });
     }
     resp.assert_json(&json!({
-        "counts": 10,
+        "counts": 11,
         "page": 1,
-        "page_count": 1,
+        "page_count": 2,
         "page_size": 10,
         "results": results
     }))
@@ -126,14 +139,13 @@ async fn test_get_all_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -142,6 +154,13 @@ async fn test_get_all_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    let grants = grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     role_factory.modified_many(|data, _, _| Role {
         id: data.id,
@@ -153,8 +172,10 @@ async fn test_get_all_role_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let mut roles = role_factory.generate_many(&app_state.db, 10, ()).await?;
+    roles.push(grants);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -217,14 +238,13 @@ async fn test_dropdown_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -233,6 +253,13 @@ async fn test_dropdown_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    let grants = grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     role_factory.modified_many(|data, _, _| Role {
         id: data.id,
@@ -244,8 +271,10 @@ async fn test_dropdown_role_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let mut roles = role_factory.generate_many(&app_state.db, 10, ()).await?;
+    roles.push(grants);
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -266,7 +295,7 @@ async fn test_dropdown_role_api(pool: PgPool) -> anyhow::Result<()> {
         }
     });
     let mut results: Vec<Value> = vec![];
-    for item in roles {
+    for item in roles.into_iter().take(10) {
         results.push(json!( {
             "id": item.id.to_string(),
             "role_name": item.role_name,
@@ -281,14 +310,13 @@ async fn test_get_detail_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -297,6 +325,13 @@ async fn test_get_detail_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     role_factory.modified_one(|data, _| Role {
         id: data.id,
@@ -308,6 +343,7 @@ async fn test_get_detail_role_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -353,14 +389,13 @@ async fn test_create_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -369,6 +404,13 @@ async fn test_create_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -408,6 +450,27 @@ async fn test_create_role_api(pool: PgPool) -> anyhow::Result<()> {
     assert_eq!(new_role.0, "new_role".to_string());
     assert_eq!(new_role.1, Some("role description".to_string()));
     assert_eq!(new_role.2, Some(true));
+
+    // When role_name already taken
+    let resp = cli
+        .post("/api/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "role_name": "new_role",
+            "description": "another description",
+            "is_active": true
+        }))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::CONFLICT);
+    resp.assert_json(json!({
+        "code": "DUPLICATE_VALUE",
+        "message": "role_name already exists",
+        "field": "role_name"
+    }))
+    .await;
     Ok(())
 }
 
@@ -416,14 +479,13 @@ async fn test_update_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -432,6 +494,13 @@ async fn test_update_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     role_factory.modified_one(|data, _| Role {
         id: data.id,
@@ -443,6 +512,7 @@ async fn test_update_role_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -505,14 +575,13 @@ async fn test_delete_role_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -521,6 +590,13 @@ async fn test_delete_role_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut role_factory = RoleFactory::new();
     role_factory.modified_one(|data, _| Role {
         id: data.id,
@@ -532,6 +608,7 @@ async fn test_delete_role_api(pool: PgPool) -> anyhow::Result<()> {
         created_date: data.created_date,
         updated_date: Some(generate_random::<DateTime<FixedOffset>>()),
         deleted_date: None,
+        deleted_by: None,
     });
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -576,3 +653,323 @@ async fn test_delete_role_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status(StatusCode::NOT_FOUND);
     Ok(())
 }
+
+#[sqlx::test]
+async fn test_restore_role_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given a role in the recycle bin
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let role = RoleFactory::new()
+        .role_name("auditor")
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .delete("/api/role")
+        .query("id", &role.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NO_CONTENT);
+
+    // When list deleted roles
+    let resp = cli
+        .get("/api/role/deleted")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect deleted by test user
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let results = json.value().object().get("results").array();
+    results.assert_len(1);
+    let deleted = results.get(0).object();
+    deleted.get("id").assert_string(&role.id.to_string());
+    deleted
+        .get("deleted_by")
+        .object()
+        .get("user_name")
+        .assert_string("test_user");
+    deleted.get("deleted_date").assert_not_null();
+
+    // When restore
+    let resp = cli
+        .post("/api/role/restore")
+        .query("id", &role.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect active again and out of the recycle bin
+    resp.assert_status_is_ok();
+    let resp = cli
+        .get("/api/role/detail")
+        .query("id", &role.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = cli
+        .get("/api/role/deleted")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value().object().get("results").array().assert_len(0);
+    let deleted_by: (Option<Uuid>,) =
+        sqlx::query_as(format!("SELECT deleted_by FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(role.id)
+            .fetch_one(&mut *db)
+            .await?;
+    assert!(deleted_by.0.is_none());
+
+    // When restore a role that is not deleted
+    let resp = cli
+        .post("/api/role/restore")
+        .query("id", &role.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_role_routes(pool: PgPool) -> anyhow::Result<()> {
+    // Given only the role routes embedded in another service
+    let config = get_config();
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(
+        AppState::builder()
+            .db(pool)
+            .redis_conn(redis_pool)
+            .build(&config)
+            .await?,
+    );
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let route = Route::new().nest("/core", role_routes("/core"));
+    let cli = TestClient::new(with_core_middleware(route, app_state.clone(), &config));
+
+    // When
+    let resp_role = cli
+        .get("/core/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    let resp_user = cli
+        .get("/core/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp_role.assert_status_is_ok();
+    resp_user.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_role_api_requires_permission(pool: PgPool) -> anyhow::Result<()> {
+    // Given a user who may only read roles
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When the token is unknown
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", "Bearer unknown")
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("message")
+        .assert_string("unauthorized");
+
+    // When without any permission
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When read only
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        &["role.read"],
+    )
+    .await?;
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = cli
+        .post("/api/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"role_name": "new_role"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("message")
+        .assert_string("role create permission required");
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_paginate_role_sort_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let grants = grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    for role_name in ["cashier", "auditor", "manager"] {
+        RoleFactory::new()
+            .role_name(role_name)
+            .create(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("sort_by", &"role_name")
+        .query("sort_dir", &"desc")
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let results = json.value().object().get("results").array();
+    results.assert_len(4);
+    let role_names = [grants.role_name.as_str(), "manager", "cashier", "auditor"];
+    for (idx, role_name) in role_names.iter().enumerate() {
+        results
+            .get(idx)
+            .object()
+            .get("role_name")
+            .assert_string(role_name);
+    }
+
+    // When sort by a column outside the whitelist
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("sort_by", &"deleted_by")
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("field")
+        .assert_string("sort_by");
+    Ok(())
+}
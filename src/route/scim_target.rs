@@ -0,0 +1,542 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::{
+        scim_provisioning_event::{
+            ScimProvisioningEvent, STATUS_FAILED, STATUS_PENDING, STATUS_SUCCESS,
+        },
+        user::User,
+    },
+    repository::{
+        scim_provisioning_event::{
+            count_scim_provisioning_event_by_status, get_scim_provisioning_event_by_id,
+            paginate_scim_provisioning_event, update_scim_provisioning_event,
+        },
+        scim_target::{
+            create_scim_target, get_scim_target_by_id, paginate_scim_target,
+            soft_delete_scim_target, update_scim_target,
+        },
+        user::{get_user_by_id, UserLookup},
+    },
+    schema::{
+        common::{BadRequestResponse, PaginateResponse, UnprocessableEntityResponse},
+        scim_target::{
+            DetailScimTargetPagination, PaginateScimProvisioningEventResponses,
+            PaginateScimTargetResponses, ScimProvisioningEventResponse,
+            ScimProvisioningEventRetryResponses, ScimTargetCreateRequest, ScimTargetCreateResponse,
+            ScimTargetCreateResponses, ScimTargetDeleteResponses, ScimTargetDetailResponses,
+            ScimTargetDetailSuccessResponse, ScimTargetDetailUser, ScimTargetEventSummary,
+            ScimTargetUpdateRequest, ScimTargetUpdateResponse, ScimTargetUpdateResponses,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiScimTargetTags {
+    ScimTarget,
+}
+
+pub struct ApiScimTarget;
+
+fn validate_base_url(base_url: &str) -> Option<String> {
+    match reqwest::Url::parse(base_url) {
+        Ok(val) if val.scheme() == "http" || val.scheme() == "https" => None,
+        Ok(_) => Some("base_url must use http or https scheme".to_string()),
+        Err(_) => Some(format!("base_url {} is not a valid url", base_url)),
+    }
+}
+
+fn event_to_response(event: ScimProvisioningEvent) -> ScimProvisioningEventResponse {
+    ScimProvisioningEventResponse {
+        id: event.id.to_string(),
+        target_id: event.target_id.to_string(),
+        user_id: event.user_id.to_string(),
+        event_type: event.event_type,
+        status: event.status,
+        attempts: event.attempts,
+        last_error: event.last_error,
+        next_attempt_date: datetime_to_string_opt(event.next_attempt_date),
+        created_date: datetime_to_string_opt(event.created_date),
+        updated_date: datetime_to_string_opt(event.updated_date),
+    }
+}
+
+#[OpenApi]
+impl ApiScimTarget {
+    #[oai(
+        path = "/scim-target/",
+        method = "get",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    async fn paginate_scim_target_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(search): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateScimTargetResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                paginate_scim_target(&mut db, page, page_size, search).await?;
+
+            let users = UserLookup::load(
+                &mut db,
+                data.iter().flat_map(|x| [x.created_by, x.updated_by]),
+            )
+            .await?;
+            let mut results: Vec<DetailScimTargetPagination> = vec![];
+            for item in data {
+                let created_by = users.get(item.created_by);
+                let updated_by = users.get(item.updated_by);
+                results.push(DetailScimTargetPagination {
+                    id: item.id.to_string(),
+                    name: item.name,
+                    base_url: item.base_url,
+                    has_bearer_token: item.bearer_token.is_some(),
+                    is_active: item.is_active,
+                    max_attempts: item.max_attempts,
+                    created_by: created_by.map(|x| ScimTargetDetailUser {
+                        id: x.id.to_string(),
+                        user_name: x.user_name.clone(),
+                    }),
+                    updated_by: updated_by.map(|x| ScimTargetDetailUser {
+                        id: x.id.to_string(),
+                        user_name: x.user_name.clone(),
+                    }),
+                    created_date: datetime_to_string_opt(item.created_date),
+                    updated_date: datetime_to_string_opt(item.updated_date),
+                });
+            }
+
+            Ok(PaginateScimTargetResponses::Ok(Json(PaginateResponse {
+                counts,
+                page,
+                page_count,
+                page_size,
+                results,
+            })))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/scim-target/detail/",
+        method = "get",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    async fn get_detail_scim_target_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> ScimTargetDetailResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "scim target with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_scim_target_by_id(&mut db, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "scim target with id = {} not found",
+                    id
+                )));
+            }
+            let data = data.unwrap();
+
+            let status_counts = count_scim_provisioning_event_by_status(&mut db, &data.id).await?;
+            let mut event_summary = ScimTargetEventSummary::default();
+            for (status, count) in status_counts {
+                match status.as_str() {
+                    STATUS_PENDING => event_summary.pending = count as u32,
+                    STATUS_SUCCESS => event_summary.success = count as u32,
+                    STATUS_FAILED => event_summary.failed = count as u32,
+                    _ => {}
+                }
+            }
+
+            let mut created_by: Option<User> = None;
+            if let Some(created_by_id) = data.created_by {
+                (created_by, _) = get_user_by_id(&mut db, &created_by_id, None).await?;
+            }
+            let mut updated_by: Option<User> = None;
+            if let Some(updated_by_id) = data.updated_by {
+                (updated_by, _) = get_user_by_id(&mut db, &updated_by_id, None).await?;
+            }
+            Ok(ScimTargetDetailResponses::Ok(Json(
+                ScimTargetDetailSuccessResponse {
+                    id: data.id.to_string(),
+                    name: data.name,
+                    base_url: data.base_url,
+                    has_bearer_token: data.bearer_token.is_some(),
+                    is_active: data.is_active,
+                    max_attempts: data.max_attempts,
+                    event_summary,
+                    created_date: datetime_to_string_opt(data.created_date),
+                    updated_date: datetime_to_string_opt(data.updated_date),
+                    created_by: created_by.map(|x| ScimTargetDetailUser {
+                        id: x.id.to_string(),
+                        user_name: x.user_name,
+                    }),
+                    updated_by: updated_by.map(|x| ScimTargetDetailUser {
+                        id: x.id.to_string(),
+                        user_name: x.user_name,
+                    }),
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/scim-target/",
+        method = "post",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    async fn create_scim_target_api(
+        &self,
+        Json(json): Json<ScimTargetCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ScimTargetCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.create",
+            )
+            .await?;
+
+            if let Some(message) = validate_base_url(&json.base_url) {
+                return Ok(ScimTargetCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&["base_url"], message),
+                )));
+            }
+
+            let new_target = create_scim_target(
+                &mut tx,
+                None,
+                json.name,
+                json.base_url,
+                json.bearer_token,
+                json.is_active,
+                json.max_attempts,
+                request_user,
+                None,
+            )
+            .await?;
+            tx.commit().await?;
+            Ok(ScimTargetCreateResponses::Ok(Json(
+                ScimTargetCreateResponse {
+                    id: new_target.id.to_string(),
+                    name: new_target.name,
+                    base_url: new_target.base_url,
+                    has_bearer_token: new_target.bearer_token.is_some(),
+                    is_active: new_target.is_active,
+                    max_attempts: new_target.max_attempts,
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/scim-target/",
+        method = "put",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    async fn update_scim_target_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<ScimTargetUpdateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ScimTargetUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.update",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "scim target with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_scim_target_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "scim target with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            if let Some(message) = validate_base_url(&json.base_url) {
+                return Ok(ScimTargetUpdateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&["base_url"], message),
+                )));
+            }
+
+            update_scim_target(
+                &mut tx,
+                &mut data,
+                json.name,
+                json.base_url,
+                json.bearer_token,
+                json.is_active,
+                json.max_attempts,
+                request_user,
+                None,
+            )
+            .await?;
+
+            tx.commit().await?;
+            Ok(ScimTargetUpdateResponses::Ok(Json(
+                ScimTargetUpdateResponse {
+                    id: data.id.to_string(),
+                    name: data.name,
+                    base_url: data.base_url,
+                    has_bearer_token: data.bearer_token.is_some(),
+                    is_active: data.is_active,
+                    max_attempts: data.max_attempts,
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/scim-target/",
+        method = "delete",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    async fn delete_scim_target_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ScimTargetDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.delete",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "scim target with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_scim_target_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "scim target with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            soft_delete_scim_target(&mut tx, &mut data, request_user, None).await?;
+
+            tx.commit().await?;
+            Ok(ScimTargetDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/scim-target/events/",
+        method = "get",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn paginate_scim_provisioning_event_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(target_id): Query<Option<String>>,
+        Query(user_id): Query<Option<String>>,
+        Query(status): Query<Option<String>>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateScimProvisioningEventResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.read").await?;
+
+            let target_id = match target_id {
+                Some(val) => match Uuid::parse_str(&val) {
+                    Ok(val) => Some(val),
+                    Err(_) => {
+                        return Ok(PaginateScimProvisioningEventResponses::BadRequest(Json(
+                            BadRequestResponse::new(format!("invalid target_id {}", val)),
+                        )))
+                    }
+                },
+                None => None,
+            };
+            let user_id = match user_id {
+                Some(val) => match Uuid::parse_str(&val) {
+                    Ok(val) => Some(val),
+                    Err(_) => {
+                        return Ok(PaginateScimProvisioningEventResponses::BadRequest(Json(
+                            BadRequestResponse::new(format!("invalid user_id {}", val)),
+                        )))
+                    }
+                },
+                None => None,
+            };
+            if let Some(val) = &status {
+                if ![STATUS_PENDING, STATUS_SUCCESS, STATUS_FAILED].contains(&val.as_str()) {
+                    return Ok(PaginateScimProvisioningEventResponses::BadRequest(Json(
+                        BadRequestResponse::new(format!(
+                            "status must be one of {}, {}, {}",
+                            STATUS_PENDING, STATUS_SUCCESS, STATUS_FAILED
+                        )),
+                    )));
+                }
+            }
+
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) = paginate_scim_provisioning_event(
+                &mut tx, page, page_size, target_id, user_id, status,
+            )
+            .await?;
+
+            Ok(PaginateScimProvisioningEventResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results: data.into_iter().map(event_to_response).collect(),
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/scim-target/events/retry/",
+        method = "post",
+        tag = "ApiScimTargetTags::ScimTarget"
+    )]
+    async fn retry_scim_provisioning_event_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ScimProvisioningEventRetryResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.update").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "scim provisioning event with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let event = get_scim_provisioning_event_by_id(&mut tx, &id).await?;
+            if event.is_none() {
+                return Err(AppError::not_found(format!(
+                    "scim provisioning event with id = {} not found",
+                    id
+                )));
+            }
+            let mut event = event.unwrap();
+            if event.status != STATUS_FAILED {
+                return Err(AppError::bad_request(
+                    "only failed event can be retried".to_string(),
+                ));
+            }
+
+            // Requeue event with fresh attempts budget
+            let now = utc_now();
+            event.status = STATUS_PENDING.to_string();
+            event.attempts = 0;
+            event.next_attempt_date = Some(now);
+            event.updated_date = Some(now);
+            update_scim_provisioning_event(&mut tx, &event).await?;
+
+            tx.commit().await?;
+            Ok(ScimProvisioningEventRetryResponses::Ok(Json(
+                event_to_response(event),
+            )))
+        })
+        .await
+    }
+}
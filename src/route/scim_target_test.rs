@@ -0,0 +1,450 @@
+use std::sync::Arc;
+
+use poem::{
+    handler,
+    http::StatusCode,
+    listener::{Acceptor, TcpAcceptor},
+    post,
+    test::TestClient,
+    web::Json,
+    Route, Server,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        scim::process_pending_scim_events,
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    init_openapi_route,
+    model::{
+        scim_provisioning_event::{ScimProvisioningEvent, TABLE_NAME as EVENT_TABLE_NAME},
+        scim_target::{ScimTarget, TABLE_NAME},
+        scim_target_user::{ScimTargetUser, TABLE_NAME as SCIM_TARGET_USER_TABLE_NAME},
+    },
+    settings::get_config,
+    AppState,
+};
+
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &[
+    "provisioning.create",
+    "provisioning.read",
+    "provisioning.update",
+    "provisioning.delete",
+    "user.create",
+];
+
+#[handler]
+fn mock_scim_create_user(Json(body): Json<Value>) -> (StatusCode, Json<Value>) {
+    let mut body = body;
+    body["id"] = json!("downstream-1");
+    (StatusCode::CREATED, Json(body))
+}
+
+/// Run a fake downstream scim app, return its base url
+async fn run_mock_scim_server() -> anyhow::Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let acceptor = TcpAcceptor::from_tokio(listener)?;
+    let addr = acceptor.local_addr()[0].clone();
+    let app = Route::new().at("/scim/v2/Users", post(mock_scim_create_user));
+    tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+    Ok(format!("http://{}/scim/v2", addr.as_socket_addr().unwrap()))
+}
+
+#[sqlx::test]
+async fn test_crud_scim_target_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When Create with invalid url
+    let resp = cli
+        .post("/api/scim-target")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "crm",
+            "base_url": "not a url",
+        }))
+        .send()
+        .await;
+
+    // Expect Create with invalid url
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When Create
+    let resp = cli
+        .post("/api/scim-target")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "crm",
+            "base_url": "https://crm.local/scim/v2",
+            "bearer_token": "secret",
+            "is_active": true,
+        }))
+        .send()
+        .await;
+
+    // Expect Create
+    resp.assert_status(StatusCode::CREATED);
+    let target: Option<ScimTarget> =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE name = $1", TABLE_NAME).as_str())
+            .bind("crm")
+            .fetch_optional(&mut *db)
+            .await?;
+    assert!(target.is_some());
+    let target = target.unwrap();
+    assert_eq!(target.bearer_token, Some("secret".to_string()));
+    assert_eq!(target.max_attempts, 5);
+
+    // When Detail
+    let resp = cli
+        .get("/api/scim-target/detail")
+        .query("id", &target.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect Detail
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("has_bearer_token").assert_bool(true);
+    let event_summary = json.get("event_summary").object();
+    event_summary.get("pending").assert_i64(0);
+    event_summary.get("success").assert_i64(0);
+    event_summary.get("failed").assert_i64(0);
+
+    // When Update without token
+    let resp = cli
+        .put("/api/scim-target")
+        .query("id", &target.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "crm-2",
+            "base_url": "https://crm2.local/scim/v2",
+            "is_active": false,
+            "max_attempts": 3,
+        }))
+        .send()
+        .await;
+
+    // Expect Update keep stored token
+    resp.assert_status_is_ok();
+    let updated: ScimTarget =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(target.id)
+            .fetch_one(&mut *db)
+            .await?;
+    assert_eq!(updated.name, "crm-2".to_string());
+    assert_eq!(updated.bearer_token, Some("secret".to_string()));
+    assert_eq!(updated.is_active, Some(false));
+    assert_eq!(updated.max_attempts, 3);
+
+    // When Delete
+    let resp = cli
+        .delete("/api/scim-target")
+        .query("id", &target.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect Delete
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .get("/api/scim-target")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("counts")
+        .assert_i64(0);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_scim_provisioning_on_user_create(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let base_url = run_mock_scim_server().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .post("/api/scim-target")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "downstream",
+            "base_url": base_url,
+            "is_active": true,
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    // unreachable target, delivery to it must fail and be retried later
+    let resp = cli
+        .post("/api/scim-target")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "unreachable",
+            "base_url": "http://127.0.0.1:1/scim/v2",
+            "is_active": true,
+            "max_attempts": 1,
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+
+    // When
+    let resp = cli
+        .post("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "first_name": "first",
+            "last_name": "last",
+            "email": "email@local.com",
+            "is_active": true,
+            "password": "password",
+            "user_name": "scim_user",
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let new_user_id: Uuid = resp.json().await.value().object().get("id").deserialize();
+    let processed = process_pending_scim_events(&app_state.db, 50).await?;
+
+    // Expect
+    assert_eq!(processed, 2);
+    let events: Vec<ScimProvisioningEvent> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1 ORDER BY status DESC",
+            EVENT_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(new_user_id)
+    .fetch_all(&mut *db)
+    .await?;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].status, "success".to_string());
+    assert_eq!(events[0].event_type, "create".to_string());
+    assert_eq!(events[1].status, "failed".to_string());
+    assert_eq!(events[1].attempts, 1);
+    assert!(events[1].last_error.is_some());
+    let link: ScimTargetUser = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1",
+            SCIM_TARGET_USER_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(new_user_id)
+    .fetch_one(&mut *db)
+    .await?;
+    assert_eq!(link.external_id, "downstream-1".to_string());
+
+    // When retry failed event
+    let resp = cli
+        .post("/api/scim-target/events/retry")
+        .query("id", &events[1].id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect event requeued
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("status").assert_string("pending");
+    json.get("attempts").assert_i64(0);
+
+    // When list failed events
+    let resp = cli
+        .get("/api/scim-target/events")
+        .query("user_id", &new_user_id.to_string())
+        .query("status", &"pending")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("counts")
+        .assert_i64(1);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_scim_provisioning_failure_keeps_earlier_deliveries(
+    pool: PgPool,
+) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let base_url = run_mock_scim_server().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .post("/api/scim-target")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "name": "downstream",
+            "base_url": base_url,
+            "is_active": true,
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let mut user_ids: Vec<Uuid> = vec![];
+    for user_name in ["scim_user_1", "scim_user_2"] {
+        let resp = cli
+            .post("/api/user")
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .body_json(&json!({
+                "first_name": "first",
+                "last_name": "last",
+                "email": format!("{}@local.com", user_name),
+                "is_active": true,
+                "password": "password",
+                "user_name": user_name,
+            }))
+            .send()
+            .await;
+        resp.assert_status(StatusCode::CREATED);
+        user_ids.push(resp.json().await.value().object().get("id").deserialize());
+    }
+    // recording the outcome of the second user's event fails after its delivery
+    sqlx::query(
+        r#"CREATE FUNCTION fail_scim_event_update() RETURNS trigger AS $$
+        BEGIN RAISE EXCEPTION 'scim event update failed'; END $$ LANGUAGE plpgsql"#,
+    )
+    .execute(&mut *db)
+    .await?;
+    sqlx::query(
+        format!(
+            r#"CREATE TRIGGER fail_scim_event_update BEFORE UPDATE ON {}
+            FOR EACH ROW WHEN (NEW.attempts <> OLD.attempts AND NEW.user_id = '{}')
+            EXECUTE FUNCTION fail_scim_event_update()"#,
+            EVENT_TABLE_NAME, user_ids[1]
+        )
+        .as_str(),
+    )
+    .execute(&mut *db)
+    .await?;
+
+    // When
+    let processed = process_pending_scim_events(&app_state.db, 50).await?;
+
+    // Expect the first delivery and its link are kept
+    assert_eq!(processed, 1);
+    let events: Vec<ScimProvisioningEvent> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = ANY($1) ORDER BY created_date ASC",
+            EVENT_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&user_ids)
+    .fetch_all(&mut *db)
+    .await?;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].user_id, user_ids[0]);
+    assert_eq!(events[0].status, "success".to_string());
+    assert_eq!(events[1].status, "pending".to_string());
+    assert_eq!(events[1].attempts, 0);
+    // claimed, not picked up again before the claim runs out
+    assert!(events[1].next_attempt_date.unwrap() > chrono::Local::now().fixed_offset());
+    assert_eq!(process_pending_scim_events(&app_state.db, 50).await?, 0);
+    let links: Vec<ScimTargetUser> = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = ANY($1)",
+            SCIM_TARGET_USER_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(&user_ids)
+    .fetch_all(&mut *db)
+    .await?;
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].user_id, user_ids[0]);
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        permission_cache::permissions_changed,
+        security::{
+            ensure_permission, hash_password, AuthContext, BearerAuthorization, ReadAuthContext,
+        },
+        service_account::{api_key_display_prefix, generate_api_key, hash_api_key, parse_scope},
+        utils::{datetime_to_string_opt, string_to_datetime, utc_now},
+        validation::Validate,
+    },
+    model::{
+        service_account::{ServiceAccount, USER_NAME_PREFIX},
+        user::{User, UserStatus},
+        user_permission::UserPermission,
+        user_profile::UserProfile,
+    },
+    repository::{
+        permission::get_permission_by_name,
+        permission_attribute::get_permission_attribute_by_name,
+        service_account::{
+            create_service_account, get_service_account_by_id, paginate_service_account,
+            soft_delete_service_account, update_service_account_key,
+        },
+        user::{create_user, get_user_by_id, is_user_name_taken, soft_delete_user},
+        user_permission::{create_user_permission, get_user_permission_names},
+    },
+    schema::{
+        common::{PaginateResponse, UnprocessableEntityResponse},
+        service_account::{
+            PaginateServiceAccountResponses, ServiceAccountCreateRequest,
+            ServiceAccountCreateResponses, ServiceAccountDeleteResponses,
+            ServiceAccountDetailResponses, ServiceAccountKeyResponse, ServiceAccountResponse,
+            ServiceAccountRotateKeyResponses,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiServiceAccountTags {
+    ServiceAccount,
+}
+
+pub struct ApiServiceAccount;
+
+/// Response of the account with its scopes, the permissions the backing user holds directly,
+/// through a role or through a group
+async fn service_account_response(
+    conn: &mut PgConnection,
+    account: ServiceAccount,
+) -> Result<ServiceAccountResponse, AppError> {
+    let scopes = get_user_permission_names(conn, &account.user_id)
+        .await?
+        .into_iter()
+        .map(|(permission, attribute)| format!("{}.{}", permission, attribute))
+        .collect();
+    Ok(ServiceAccountResponse {
+        id: account.id.to_string(),
+        name: account.name,
+        description: account.description,
+        user_id: account.user_id.to_string(),
+        key_prefix: account.key_prefix,
+        scopes,
+        expires_at: datetime_to_string_opt(account.expires_at),
+        last_used_at: datetime_to_string_opt(account.last_used_at),
+        created_date: datetime_to_string_opt(account.created_date),
+        updated_date: datetime_to_string_opt(account.updated_date),
+    })
+}
+
+#[OpenApi]
+impl ApiServiceAccount {
+    #[oai(
+        path = "/service-account/",
+        method = "get",
+        tag = "ApiServiceAccountTags::ServiceAccount"
+    )]
+    async fn paginate_service_account_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(search): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateServiceAccountResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "service_account.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                paginate_service_account(&mut db, page, page_size, search).await?;
+
+            let mut results: Vec<ServiceAccountResponse> = vec![];
+            for item in data {
+                results.push(service_account_response(&mut db, item).await?);
+            }
+            Ok(PaginateServiceAccountResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results,
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/service-account/detail/",
+        method = "get",
+        tag = "ApiServiceAccountTags::ServiceAccount"
+    )]
+    async fn get_detail_service_account_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> ServiceAccountDetailResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "service_account.read").await?;
+            let not_found =
+                || AppError::not_found(format!("service account with id = {} not found", id));
+            let account_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let data = get_service_account_by_id(&mut db, &account_id)
+                .await?
+                .ok_or_else(not_found)?;
+
+            Ok(ServiceAccountDetailResponses::Ok(Json(
+                service_account_response(&mut db, data).await?,
+            )))
+        })
+        .await
+    }
+
+    /// Create a service account and its first api key. Scopes become direct permission
+    /// grants of its backing user, the request user must hold each of them. Roles or groups
+    /// given to the backing user later add their permissions to the scopes
+    #[oai(
+        path = "/service-account/",
+        method = "post",
+        tag = "ApiServiceAccountTags::ServiceAccount"
+    )]
+    async fn create_service_account_api(
+        &self,
+        Json(json): Json<ServiceAccountCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ServiceAccountCreateResponses {
+        if let Some(errors) = json.validation_errors() {
+            return ServiceAccountCreateResponses::UnprocessableEntity(Json(errors));
+        }
+        let now = utc_now();
+        let expires_at = match json.expires_at.as_deref().map(string_to_datetime) {
+            Some(Err(err)) => {
+                return ServiceAccountCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["expires_at"],
+                        format!("expires_at: {}", err),
+                    ),
+                ))
+            }
+            Some(Ok(val)) if val <= now => {
+                return ServiceAccountCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["expires_at"],
+                        "expires_at must be in the future".to_string(),
+                    ),
+                ))
+            }
+            Some(Ok(val)) => Some(val),
+            None => None,
+        };
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "service_account.create",
+            )
+            .await?;
+
+            // Nobody hands out more than they hold
+            let mut grants: Vec<(Uuid, Uuid)> = vec![];
+            for scope in json.scopes.iter() {
+                let unknown = || AppError::bad_request(format!("unknown scope {}", scope.trim()));
+                let (permission_name, attribute_name) = parse_scope(scope).ok_or_else(unknown)?;
+                let permission = get_permission_by_name(&mut tx, permission_name)
+                    .await?
+                    .ok_or_else(unknown)?;
+                let attribute = get_permission_attribute_by_name(&mut tx, attribute_name)
+                    .await?
+                    .ok_or_else(unknown)?;
+                ensure_permission(
+                    &mut tx,
+                    &mut redis_conn,
+                    &request_user,
+                    &format!("{}.{}", permission_name, attribute_name),
+                )
+                .await?;
+                if !grants.contains(&(permission.id, attribute.id)) {
+                    grants.push((permission.id, attribute.id));
+                }
+            }
+
+            let user_name = format!("{}{}", USER_NAME_PREFIX, json.name);
+            if is_user_name_taken(&mut tx, &user_name, None).await? {
+                return Err(AppError::conflict(format!(
+                    "service account {} already exists",
+                    json.name
+                )));
+            }
+            // Backing user nobody can log in as, the password is a hash of a discarded key
+            let password = hash_password(&generate_api_key())
+                .map_err(|err| AppError::Internal(anyhow::anyhow!("hash_password: {}", err)))?;
+            let user_id = Uuid::now_v7();
+            let user = User {
+                id: user_id,
+                user_name,
+                password,
+                is_2faenabled: Some(false),
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+                deleted_date: None,
+                status: UserStatus::Active,
+                expires_at: None,
+                scopes: None,
+            };
+            let user_profile = UserProfile {
+                id: Uuid::now_v7(),
+                user_id,
+                first_name: None,
+                last_name: None,
+                address: None,
+                email: None,
+                locale: None,
+                timezone: None,
+                email_verified_at: None,
+            };
+            create_user(&mut tx, &user, &user_profile).await?;
+            for (permission_id, attribute_id) in grants {
+                create_user_permission(
+                    &mut tx,
+                    &UserPermission {
+                        user_id,
+                        permission_id,
+                        attribute_id,
+                        created_by: Some(request_user.id),
+                        updated_by: Some(request_user.id),
+                        created_date: Some(now),
+                        updated_date: Some(now),
+                        expires_at,
+                    },
+                )
+                .await?;
+            }
+
+            let api_key = generate_api_key();
+            let account = ServiceAccount {
+                id: Uuid::now_v7(),
+                name: json.name,
+                description: json.description,
+                user_id,
+                key_prefix: api_key_display_prefix(&api_key),
+                key_hash: hash_api_key(&api_key),
+                expires_at,
+                last_used_at: None,
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+                deleted_date: None,
+            };
+            // a duplicate name is returned as a conflict, see AppError
+            create_service_account(&mut tx, &account).await?;
+            let service_account = service_account_response(&mut tx, account).await?;
+            tx.commit().await?;
+            Ok(ServiceAccountCreateResponses::Created(Json(
+                ServiceAccountKeyResponse {
+                    api_key,
+                    service_account,
+                },
+            )))
+        })
+        .await
+    }
+
+    /// Replace the api key, the previous one stops working at once
+    #[oai(
+        path = "/service-account/rotate-key/",
+        method = "post",
+        tag = "ApiServiceAccountTags::ServiceAccount"
+    )]
+    async fn rotate_service_account_key_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ServiceAccountRotateKeyResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "service_account.update",
+            )
+            .await?;
+            let not_found =
+                || AppError::not_found(format!("service account with id = {} not found", id));
+            let account_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let mut account = get_service_account_by_id(&mut tx, &account_id)
+                .await?
+                .ok_or_else(not_found)?;
+
+            let api_key = generate_api_key();
+            account.key_prefix = api_key_display_prefix(&api_key);
+            account.key_hash = hash_api_key(&api_key);
+            account.updated_by = Some(request_user.id);
+            account.updated_date = Some(utc_now());
+            update_service_account_key(&mut tx, &account).await?;
+            let service_account = service_account_response(&mut tx, account).await?;
+            tx.commit().await?;
+            Ok(ServiceAccountRotateKeyResponses::Ok(Json(
+                ServiceAccountKeyResponse {
+                    api_key,
+                    service_account,
+                },
+            )))
+        })
+        .await
+    }
+
+    /// Soft delete the account, its key stops working and its backing user is deleted
+    #[oai(
+        path = "/service-account/",
+        method = "delete",
+        tag = "ApiServiceAccountTags::ServiceAccount"
+    )]
+    async fn delete_service_account_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> ServiceAccountDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "service_account.delete",
+            )
+            .await?;
+            let not_found =
+                || AppError::not_found(format!("service account with id = {} not found", id));
+            let account_id = Uuid::parse_str(&id).map_err(|_| not_found())?;
+            let mut account = get_service_account_by_id(&mut tx, &account_id)
+                .await?
+                .ok_or_else(not_found)?;
+
+            let now = utc_now();
+            account.updated_by = Some(request_user.id);
+            account.updated_date = Some(now);
+            account.deleted_date = Some(now);
+            soft_delete_service_account(&mut tx, &account).await?;
+            let (user, _) = get_user_by_id(&mut tx, &account.user_id, None).await?;
+            if let Some(mut user) = user {
+                soft_delete_user(&mut tx, &mut user, &request_user, &now).await?;
+            }
+            tx.commit().await?;
+            permissions_changed(&mut redis_conn, &[account.user_id]).await;
+            Ok(ServiceAccountDeleteResponses::NoContent)
+        })
+        .await
+    }
+}
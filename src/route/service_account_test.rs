@@ -0,0 +1,367 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    cli::seed::{parse_seed_data, seed, DEFAULT_SEED},
+    core::{
+        permission_cache::invalidate_user_permissions, session_store::create_redis_pool,
+        test_utils::generate_test_user,
+    },
+    init_openapi_route,
+    model::{
+        service_account::TABLE_NAME as SERVICE_ACCOUNT_TABLE_NAME,
+        user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+    },
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_service_account_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When scope unknown or not held by the request user
+    let resp = cli
+        .post("/api/service-account")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "billing-sync", "scopes": ["missing.read"]}))
+        .send()
+        .await;
+    let forbidden = cli
+        .post("/api/service-account")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "billing-sync", "scopes": ["user_anonymize.read"]}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    forbidden.assert_status(StatusCode::FORBIDDEN);
+
+    // When
+    let resp = cli
+        .post("/api/service-account")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "billing-sync", "scopes": ["role.read"]}))
+        .send()
+        .await;
+
+    // Expect the key returned once
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let api_key = json.value().object().get("api_key").string().to_string();
+    let account = json.value().object().get("service_account").object();
+    let account_id = account.get("id").string().to_string();
+    assert!(api_key.starts_with("sa_"));
+    assert!(api_key.starts_with(account.get("key_prefix").string()));
+    let scopes = account.get("scopes").array();
+    scopes.assert_len(1);
+    scopes.get(0).assert_string("role.read");
+
+    // When authenticated with the key
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", api_key))
+        .send()
+        .await;
+    let denied = cli
+        .post("/api/role")
+        .header("authorization", format!("Bearer {}", api_key))
+        .body_json(&json!({"role_name": "from_key"}))
+        .send()
+        .await;
+
+    // Expect only the scopes granted
+    resp.assert_status_is_ok();
+    denied.assert_status(StatusCode::FORBIDDEN);
+    let resp = cli
+        .get("/api/service-account/detail")
+        .query("id", &account_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value().object().get("last_used_at").assert_not_null();
+
+    // When key rotated
+    let resp = cli
+        .post("/api/service-account/rotate-key")
+        .query("id", &account_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let rotated = resp
+        .json()
+        .await
+        .value()
+        .object()
+        .get("api_key")
+        .string()
+        .to_string();
+
+    // Expect the previous key rejected
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", api_key))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", rotated))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+
+    // When expired
+    sqlx::query(
+        format!(
+            "UPDATE {} SET expires_at = now() - interval '1 minute' WHERE id = $1",
+            SERVICE_ACCOUNT_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::parse_str(&account_id)?)
+    .execute(&mut *db)
+    .await?;
+
+    // Expect
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", rotated))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::UNAUTHORIZED);
+
+    // When deleted
+    let resp = cli
+        .delete("/api/service-account")
+        .query("id", &account_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect gone, the name stays taken by the deleted backing user
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .get("/api/service-account/detail")
+        .query("id", &account_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    let resp = cli
+        .post("/api/service-account")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "billing-sync", "scopes": ["role.read"]}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CONFLICT);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_client_credentials_grant(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let resp = cli
+        .post("/api/service-account")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"name": "billing-sync", "scopes": ["role.read", "role.create"]}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let client_secret = json.value().object().get("api_key").string().to_string();
+    let client_id = json
+        .value()
+        .object()
+        .get("service_account")
+        .object()
+        .get("id")
+        .string()
+        .to_string();
+
+    // When unsupported grant, wrong secret or scope not granted
+    let grant = cli
+        .post("/api/auth/token")
+        .body_json(&json!({"grant_type": "password", "client_id": client_id, "client_secret": client_secret}))
+        .send()
+        .await;
+    let secret = cli
+        .post("/api/auth/token")
+        .body_json(&json!({"grant_type": "client_credentials", "client_id": client_id, "client_secret": "sa_wrong"}))
+        .send()
+        .await;
+    let scope = cli
+        .post("/api/auth/token")
+        .body_json(&json!({"grant_type": "client_credentials", "client_id": client_id, "client_secret": client_secret, "scope": "user.read"}))
+        .send()
+        .await;
+
+    // Expect
+    grant.assert_status(StatusCode::BAD_REQUEST);
+    grant
+        .json()
+        .await
+        .value()
+        .object()
+        .get("error")
+        .assert_string("unsupported_grant_type");
+    secret.assert_status(StatusCode::UNAUTHORIZED);
+    secret
+        .json()
+        .await
+        .value()
+        .object()
+        .get("error")
+        .assert_string("invalid_client");
+    scope.assert_status(StatusCode::BAD_REQUEST);
+    scope
+        .json()
+        .await
+        .value()
+        .object()
+        .get("error")
+        .assert_string("invalid_scope");
+
+    // When form encoded with a narrower scope
+    let resp = cli
+        .post("/api/auth/token")
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+            ("scope", "role.read"),
+        ])
+        .send()
+        .await;
+
+    // Expect a token carrying the scope, usable as a bearer token
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let body = json.value().object();
+    body.get("token_type").assert_string("Bearer");
+    body.get("scope").assert_string("role.read");
+    assert!(body.get("expires_in").i64() > 0);
+    let access_token = body.get("access_token").string().to_string();
+    let resp = cli
+        .post("/api/auth/introspect")
+        .header("authorization", format!("Bearer {}", access_token))
+        .body_json(&json!({"token": access_token}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    json.value().object().get("active").assert_bool(true);
+    let scopes = json.value().object().get("scopes").array();
+    scopes.assert_len(1);
+    scopes.get(0).assert_string("role.read");
+    let resp = cli
+        .get("/api/role")
+        .header("authorization", format!("Bearer {}", access_token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+
+    // When the account holds role.create outside of the token scope
+    let resp = cli
+        .post("/api/role")
+        .header("authorization", format!("Bearer {}", access_token))
+        .body_json(
+            &json!({"role_name": "new_role", "description": "role description", "is_active": true}),
+        )
+        .send()
+        .await;
+
+    // Expect forbidden
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When no scope requested
+    let resp = cli
+        .post("/api/auth/token")
+        .body_json(&json!({"grant_type": "client_credentials", "client_id": client_id, "client_secret": client_secret}))
+        .send()
+        .await;
+
+    // Expect every scope of the account
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("scope")
+        .assert_string("role.create role.read");
+    Ok(())
+}
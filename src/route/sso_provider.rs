@@ -0,0 +1,820 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        sso::apply_protocol_defaults,
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::{
+        sso_jit_rule::SsoJitRule,
+        sso_provider::{SsoProvider, PROTOCOL_GITHUB, PROTOCOL_GOOGLE, PROTOCOL_OIDC},
+        sso_role_mapping::SsoRoleMapping,
+        user::User,
+    },
+    repository::{
+        group::get_group_by_id,
+        role::get_role_by_id,
+        sso_jit_rule::{
+            create_sso_jit_rule, delete_sso_jit_rule, get_sso_jit_rule_by_id,
+            get_sso_jit_rule_by_provider,
+        },
+        sso_provider::{
+            create_sso_provider, get_sso_provider_by_id, paginate_sso_provider,
+            soft_delete_sso_provider, update_sso_provider,
+        },
+        sso_role_mapping::{
+            create_sso_role_mapping, delete_sso_role_mapping, get_sso_role_mapping_by_id,
+            get_sso_role_mapping_by_provider,
+        },
+        user::{get_user_by_id, UserLookup},
+    },
+    schema::{
+        common::{PaginateResponse, UnprocessableEntityResponse},
+        sso_provider::{
+            PaginateSsoProviderResponses, SsoJitRuleCreateRequest, SsoJitRuleCreateResponses,
+            SsoJitRuleDeleteResponses, SsoJitRuleResponse, SsoProviderCreateRequest,
+            SsoProviderCreateResponses, SsoProviderDeleteResponses, SsoProviderDetailResponses,
+            SsoProviderDetailSuccessResponse, SsoProviderDetailUser, SsoProviderResponse,
+            SsoProviderUpdateRequest, SsoProviderUpdateResponses, SsoRoleMappingCreateRequest,
+            SsoRoleMappingCreateResponses, SsoRoleMappingDeleteResponses,
+            SsoRoleMappingListResponses, SsoRoleMappingResponse,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiSsoProviderTags {
+    SsoProvider,
+}
+
+pub struct ApiSsoProvider;
+
+fn validate_url(field: &str, value: &str) -> Option<String> {
+    match reqwest::Url::parse(value) {
+        Ok(val) if ["http", "https"].contains(&val.scheme()) => None,
+        Ok(_) => Some(format!("{} must use http or https scheme", field)),
+        Err(err) => Some(format!("invalid {}: {}", field, err)),
+    }
+}
+
+fn validate_optional_url(field: &str, value: &Option<String>) -> Option<String> {
+    value.as_deref().and_then(|x| validate_url(field, x))
+}
+
+fn validate_sso_provider(provider: &SsoProvider) -> Option<String> {
+    if ![PROTOCOL_OIDC, PROTOCOL_GOOGLE, PROTOCOL_GITHUB].contains(&provider.protocol.as_str()) {
+        return Some(format!(
+            "protocol must be one of {}, {}, {}",
+            PROTOCOL_OIDC, PROTOCOL_GOOGLE, PROTOCOL_GITHUB
+        ));
+    }
+    if provider.name.trim().is_empty() || provider.client_id.trim().is_empty() {
+        return Some("name and client_id must not be empty".to_string());
+    }
+    if provider.username_claim.trim().is_empty() || provider.email_claim.trim().is_empty() {
+        return Some("username_claim and email_claim must not be empty".to_string());
+    }
+    if provider.is_oidc() && provider.jwks_url.is_none() {
+        return Some(format!("{} provider require jwks_url", provider.protocol));
+    }
+    if provider.authorization_url.is_some() != provider.token_url.is_some() {
+        return Some("authorization_url and token_url must be set together".to_string());
+    }
+    validate_url("issuer", &provider.issuer)
+        .or(validate_optional_url("jwks_url", &provider.jwks_url))
+        .or(validate_optional_url(
+            "authorization_url",
+            &provider.authorization_url,
+        ))
+        .or(validate_optional_url("token_url", &provider.token_url))
+        .or(validate_optional_url(
+            "userinfo_url",
+            &provider.userinfo_url,
+        ))
+}
+
+async fn get_detail_user(
+    conn: &mut PgConnection,
+    id: Option<Uuid>,
+) -> anyhow::Result<Option<SsoProviderDetailUser>> {
+    let user = match id {
+        Some(id) => get_user_by_id(conn, &id, None).await?.0,
+        None => None,
+    };
+    Ok(user.as_ref().map(detail_user))
+}
+
+fn detail_user(user: &User) -> SsoProviderDetailUser {
+    SsoProviderDetailUser {
+        id: user.id.to_string(),
+        user_name: user.user_name.clone(),
+    }
+}
+
+fn provider_to_response(
+    provider: SsoProvider,
+    created_by: Option<SsoProviderDetailUser>,
+    updated_by: Option<SsoProviderDetailUser>,
+) -> SsoProviderResponse {
+    SsoProviderResponse {
+        id: provider.id.to_string(),
+        name: provider.name,
+        protocol: provider.protocol,
+        issuer: provider.issuer,
+        client_id: provider.client_id,
+        jwks_url: provider.jwks_url,
+        has_client_secret: provider.client_secret.is_some(),
+        authorization_url: provider.authorization_url,
+        token_url: provider.token_url,
+        userinfo_url: provider.userinfo_url,
+        scopes: provider.scopes,
+        username_claim: provider.username_claim,
+        email_claim: provider.email_claim,
+        is_active: provider.is_active,
+        jit_enabled: provider.jit_enabled,
+        created_by,
+        updated_by,
+        created_date: datetime_to_string_opt(provider.created_date),
+        updated_date: datetime_to_string_opt(provider.updated_date),
+    }
+}
+
+fn jit_rule_to_response(rule: SsoJitRule) -> SsoJitRuleResponse {
+    SsoJitRuleResponse {
+        id: rule.id.to_string(),
+        provider_id: rule.provider_id.to_string(),
+        claim: rule.claim,
+        value: rule.value,
+        group_id: rule.group_id.map(|x| x.to_string()),
+        role_id: rule.role_id.map(|x| x.to_string()),
+        created_date: datetime_to_string_opt(rule.created_date),
+    }
+}
+
+fn role_mapping_to_response(mapping: SsoRoleMapping) -> SsoRoleMappingResponse {
+    SsoRoleMappingResponse {
+        id: mapping.id.to_string(),
+        provider_id: mapping.provider_id.to_string(),
+        claim: mapping.claim,
+        value: mapping.value,
+        group_id: mapping.group_id.map(|x| x.to_string()),
+        role_id: mapping.role_id.map(|x| x.to_string()),
+        created_date: datetime_to_string_opt(mapping.created_date),
+    }
+}
+
+#[OpenApi]
+impl ApiSsoProvider {
+    #[oai(
+        path = "/sso-provider/",
+        method = "get",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn paginate_sso_provider_api(
+        &self,
+        Query(page): Query<Option<u32>>,
+        Query(page_size): Query<Option<u32>>,
+        Query(search): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> PaginateSsoProviderResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) =
+                paginate_sso_provider(&mut db, page, page_size, search).await?;
+
+            let users = UserLookup::load(
+                &mut db,
+                data.iter().flat_map(|x| [x.created_by, x.updated_by]),
+            )
+            .await?;
+            let mut results: Vec<SsoProviderResponse> = vec![];
+            for item in data {
+                let created_by = users.get(item.created_by).map(detail_user);
+                let updated_by = users.get(item.updated_by).map(detail_user);
+                results.push(provider_to_response(item, created_by, updated_by));
+            }
+
+            Ok(PaginateSsoProviderResponses::Ok(Json(PaginateResponse {
+                counts,
+                page,
+                page_count,
+                page_size,
+                results,
+            })))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/detail/",
+        method = "get",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn get_detail_sso_provider_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoProviderDetailResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "sso provider with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_sso_provider_by_id(&mut db, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "sso provider with id = {} not found",
+                    id
+                )));
+            }
+            let data = data.unwrap();
+
+            let jit_rules = get_sso_jit_rule_by_provider(&mut db, &data.id).await?;
+            let role_mappings = get_sso_role_mapping_by_provider(&mut db, &data.id).await?;
+            let created_by = get_detail_user(&mut db, data.created_by).await?;
+            let updated_by = get_detail_user(&mut db, data.updated_by).await?;
+            Ok(SsoProviderDetailResponses::Ok(Json(
+                SsoProviderDetailSuccessResponse {
+                    provider: provider_to_response(data, created_by, updated_by),
+                    jit_rules: jit_rules.into_iter().map(jit_rule_to_response).collect(),
+                    role_mappings: role_mappings
+                        .into_iter()
+                        .map(role_mapping_to_response)
+                        .collect(),
+                },
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/",
+        method = "post",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn create_sso_provider_api(
+        &self,
+        Json(json): Json<SsoProviderCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoProviderCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.create",
+            )
+            .await?;
+
+            let now = utc_now();
+            let mut new_provider = SsoProvider {
+                id: Uuid::now_v7(),
+                name: json.name,
+                protocol: json.protocol.unwrap_or(PROTOCOL_OIDC.to_string()),
+                issuer: json.issuer.unwrap_or_default(),
+                client_id: json.client_id,
+                jwks_url: json.jwks_url,
+                username_claim: json.username_claim.unwrap_or_default(),
+                email_claim: json.email_claim.unwrap_or_default(),
+                is_active: json.is_active,
+                jit_enabled: json.jit_enabled.unwrap_or(false),
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+                deleted_date: None,
+                client_secret: json.client_secret,
+                authorization_url: json.authorization_url,
+                token_url: json.token_url,
+                userinfo_url: json.userinfo_url,
+                scopes: json.scopes,
+            };
+            apply_protocol_defaults(&mut new_provider);
+            if let Some(message) = validate_sso_provider(&new_provider) {
+                return Ok(SsoProviderCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&[], message),
+                )));
+            }
+
+            create_sso_provider(&mut tx, &new_provider).await?;
+            tx.commit().await?;
+            let detail_user = SsoProviderDetailUser {
+                id: request_user.id.to_string(),
+                user_name: request_user.user_name,
+            };
+            Ok(SsoProviderCreateResponses::Ok(Json(provider_to_response(
+                new_provider,
+                Some(detail_user),
+                None,
+            ))))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/",
+        method = "put",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn update_sso_provider_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<SsoProviderUpdateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoProviderUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.update",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "sso provider with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_sso_provider_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "sso provider with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            data.name = json.name;
+            data.protocol = json.protocol.unwrap_or(PROTOCOL_OIDC.to_string());
+            data.issuer = json.issuer.unwrap_or_default();
+            data.client_id = json.client_id;
+            data.jwks_url = json.jwks_url;
+            if json.client_secret.is_some() {
+                data.client_secret = json.client_secret;
+            }
+            data.authorization_url = json.authorization_url;
+            data.token_url = json.token_url;
+            data.userinfo_url = json.userinfo_url;
+            data.scopes = json.scopes;
+            data.username_claim = json.username_claim.unwrap_or_default();
+            data.email_claim = json.email_claim.unwrap_or_default();
+            data.is_active = json.is_active;
+            data.jit_enabled = json.jit_enabled.unwrap_or(false);
+            data.updated_by = Some(request_user.id);
+            data.updated_date = Some(utc_now());
+            apply_protocol_defaults(&mut data);
+            if let Some(message) = validate_sso_provider(&data) {
+                return Ok(SsoProviderUpdateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&[], message),
+                )));
+            }
+
+            update_sso_provider(&mut tx, &data).await?;
+            let created_by = get_detail_user(&mut tx, data.created_by).await?;
+
+            tx.commit().await?;
+            let updated_by = SsoProviderDetailUser {
+                id: request_user.id.to_string(),
+                user_name: request_user.user_name,
+            };
+            Ok(SsoProviderUpdateResponses::Ok(Json(provider_to_response(
+                data,
+                created_by,
+                Some(updated_by),
+            ))))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/",
+        method = "delete",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn delete_sso_provider_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoProviderDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                "provisioning.delete",
+            )
+            .await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "sso provider with id = {} not found",
+                        id
+                    )))
+                }
+            };
+
+            let data = get_sso_provider_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "sso provider with id = {} not found",
+                    id
+                )));
+            }
+            let mut data = data.unwrap();
+
+            soft_delete_sso_provider(&mut tx, &mut data, request_user, None).await?;
+
+            tx.commit().await?;
+            Ok(SsoProviderDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Add a rule granting a group and/or role to users provisioned through the provider
+    #[oai(
+        path = "/sso-provider/jit-rule/",
+        method = "post",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn create_sso_jit_rule_api(
+        &self,
+        Json(json): Json<SsoJitRuleCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoJitRuleCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.update").await?;
+
+            let provider_id = match Uuid::parse_str(&json.provider_id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "sso provider with id = {} not found",
+                        json.provider_id
+                    )))
+                }
+            };
+            let provider = get_sso_provider_by_id(&mut tx, &provider_id).await?;
+            if provider.is_none() {
+                return Err(AppError::not_found(format!(
+                    "sso provider with id = {} not found",
+                    provider_id
+                )));
+            }
+
+            if json.group_id.is_none() && json.role_id.is_none() {
+                return Ok(SsoJitRuleCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &[],
+                        "group_id or role_id is required".to_string(),
+                    ),
+                )));
+            }
+            if json.claim.is_none() && json.value.is_some() {
+                return Ok(SsoJitRuleCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["claim"],
+                        "value require claim".to_string(),
+                    ),
+                )));
+            }
+
+            let mut group_id: Option<Uuid> = None;
+            if let Some(item) = &json.group_id {
+                let group = match Uuid::parse_str(item) {
+                    Ok(val) => get_group_by_id(&mut tx, &val).await?,
+                    Err(_) => None,
+                };
+                if group.is_none() {
+                    return Err(AppError::bad_request(format!(
+                        "group with id = {} not found",
+                        item
+                    )));
+                }
+                group_id = group.map(|x| x.id);
+            }
+            let mut role_id: Option<Uuid> = None;
+            if let Some(item) = &json.role_id {
+                let role = match Uuid::parse_str(item) {
+                    Ok(val) => get_role_by_id(&mut tx, &val).await?,
+                    Err(_) => None,
+                };
+                if role.is_none() {
+                    return Err(AppError::bad_request(format!(
+                        "role with id = {} not found",
+                        item
+                    )));
+                }
+                role_id = role.map(|x| x.id);
+            }
+
+            let now = utc_now();
+            let new_rule = SsoJitRule {
+                id: Uuid::now_v7(),
+                provider_id,
+                claim: json.claim,
+                value: json.value,
+                group_id,
+                role_id,
+                created_date: Some(now),
+                updated_date: Some(now),
+            };
+            create_sso_jit_rule(&mut tx, &new_rule).await?;
+            tx.commit().await?;
+            Ok(SsoJitRuleCreateResponses::Ok(Json(jit_rule_to_response(
+                new_rule,
+            ))))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/jit-rule/",
+        method = "delete",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn delete_sso_jit_rule_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoJitRuleDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.update").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "jit rule with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_sso_jit_rule_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "jit rule with id = {} not found",
+                    id
+                )));
+            }
+
+            delete_sso_jit_rule(&mut tx, &id).await?;
+            tx.commit().await?;
+            Ok(SsoJitRuleDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/role-mapping/",
+        method = "get",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn get_sso_role_mapping_api(
+        &self,
+        Query(provider_id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoRoleMappingListResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &user, "provisioning.read").await?;
+
+            let provider_id = match Uuid::parse_str(&provider_id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "sso provider with id = {} not found",
+                        provider_id
+                    )))
+                }
+            };
+            let provider = get_sso_provider_by_id(&mut db, &provider_id).await?;
+            if provider.is_none() {
+                return Err(AppError::not_found(format!(
+                    "sso provider with id = {} not found",
+                    provider_id
+                )));
+            }
+
+            let data = get_sso_role_mapping_by_provider(&mut db, &provider_id).await?;
+            Ok(SsoRoleMappingListResponses::Ok(Json(
+                data.into_iter().map(role_mapping_to_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Map an idp claim value to a group and/or role, applied on every sso login
+    #[oai(
+        path = "/sso-provider/role-mapping/",
+        method = "post",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn create_sso_role_mapping_api(
+        &self,
+        Json(json): Json<SsoRoleMappingCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoRoleMappingCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.update").await?;
+
+            let provider_id = match Uuid::parse_str(&json.provider_id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "sso provider with id = {} not found",
+                        json.provider_id
+                    )))
+                }
+            };
+            let provider = get_sso_provider_by_id(&mut tx, &provider_id).await?;
+            if provider.is_none() {
+                return Err(AppError::not_found(format!(
+                    "sso provider with id = {} not found",
+                    provider_id
+                )));
+            }
+
+            if json.group_id.is_none() && json.role_id.is_none() {
+                return Ok(SsoRoleMappingCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &[],
+                        "group_id or role_id is required".to_string(),
+                    ),
+                )));
+            }
+            if json.claim.trim().is_empty() || json.value.trim().is_empty() {
+                return Ok(SsoRoleMappingCreateResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &[],
+                        "claim and value must not be empty".to_string(),
+                    ),
+                )));
+            }
+
+            let mut group_id: Option<Uuid> = None;
+            if let Some(item) = &json.group_id {
+                let group = match Uuid::parse_str(item) {
+                    Ok(val) => get_group_by_id(&mut tx, &val).await?,
+                    Err(_) => None,
+                };
+                if group.is_none() {
+                    return Err(AppError::bad_request(format!(
+                        "group with id = {} not found",
+                        item
+                    )));
+                }
+                group_id = group.map(|x| x.id);
+            }
+            let mut role_id: Option<Uuid> = None;
+            if let Some(item) = &json.role_id {
+                let role = match Uuid::parse_str(item) {
+                    Ok(val) => get_role_by_id(&mut tx, &val).await?,
+                    Err(_) => None,
+                };
+                if role.is_none() {
+                    return Err(AppError::bad_request(format!(
+                        "role with id = {} not found",
+                        item
+                    )));
+                }
+                role_id = role.map(|x| x.id);
+            }
+
+            let now = utc_now();
+            let new_mapping = SsoRoleMapping {
+                id: Uuid::now_v7(),
+                provider_id,
+                claim: json.claim,
+                value: json.value,
+                group_id,
+                role_id,
+                created_date: Some(now),
+                updated_date: Some(now),
+            };
+            create_sso_role_mapping(&mut tx, &new_mapping).await?;
+            tx.commit().await?;
+            Ok(SsoRoleMappingCreateResponses::Ok(Json(
+                role_mapping_to_response(new_mapping),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/sso-provider/role-mapping/",
+        method = "delete",
+        tag = "ApiSsoProviderTags::SsoProvider"
+    )]
+    async fn delete_sso_role_mapping_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> SsoRoleMappingDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &user, "provisioning.update").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "role mapping with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let data = get_sso_role_mapping_by_id(&mut tx, &id).await?;
+            if data.is_none() {
+                return Err(AppError::not_found(format!(
+                    "role mapping with id = {} not found",
+                    id
+                )));
+            }
+
+            delete_sso_role_mapping(&mut tx, &id).await?;
+            tx.commit().await?;
+            Ok(SsoRoleMappingDeleteResponses::NoContent)
+        })
+        .await
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use poem::web::Data;
+use poem_openapi::{payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::terms_version::{
+        TermsVersion, KINDS as TERMS_KINDS, PERMISSION_ATTRIBUTE as TERMS_PERMISSION_ATTRIBUTE,
+        PERMISSION_NAME as TERMS_PERMISSION_NAME,
+    },
+    repository::{
+        terms_version::{
+            create_terms_version, get_latest_terms_version, get_terms_version_by_id,
+            get_terms_version_by_kind_version,
+        },
+        user_terms_acceptance::{accept_terms_version, get_pending_terms_version_by_user},
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        terms::{
+            TermsAcceptRequest, TermsAcceptResponses, TermsVersionListResponses,
+            TermsVersionPublishRequest, TermsVersionPublishResponses, TermsVersionResponse,
+        },
+    },
+    AppState,
+};
+
+#[derive(Tags)]
+enum ApiTermsTags {
+    Terms,
+}
+
+fn terms_version_response(terms_version: TermsVersion) -> TermsVersionResponse {
+    TermsVersionResponse {
+        id: terms_version.id.to_string(),
+        kind: terms_version.kind,
+        version: terms_version.version,
+        url: terms_version.url,
+        content: terms_version.content,
+        published_date: datetime_to_string_opt(terms_version.published_date),
+    }
+}
+
+pub struct ApiTerms;
+
+#[OpenApi]
+impl ApiTerms {
+    /// Latest terms of service and privacy policy, no token required
+    #[oai(path = "/terms/", method = "get", tag = "ApiTermsTags::Terms")]
+    async fn get_latest_terms_api(&self, state: Data<&Arc<AppState>>) -> TermsVersionListResponses {
+        respond(async move {
+            let mut db = state.db.acquire().await.context("acquire connection")?;
+            let data = get_latest_terms_version(&mut db).await?;
+            Ok(TermsVersionListResponses::Ok(Json(
+                data.into_iter().map(terms_version_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Latest versions the request user has not accepted yet
+    #[oai(path = "/terms/pending/", method = "get", tag = "ApiTermsTags::Terms")]
+    async fn get_pending_terms_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> TermsVersionListResponses {
+        respond(async move {
+            let ReadAuthContext { mut db, user, .. } = ctx;
+            let data = get_pending_terms_version_by_user(&mut db, &user.id).await?;
+            Ok(TermsVersionListResponses::Ok(Json(
+                data.into_iter().map(terms_version_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Publish a new terms of service or privacy policy version
+    ///
+    /// Requires the terms_version create permission. Users must accept it before new tokens
+    /// are issued when TERMS_ACCEPTANCE_REQUIRED is enabled.
+    #[oai(path = "/terms/", method = "post", tag = "ApiTermsTags::Terms")]
+    async fn publish_terms_version_api(
+        &self,
+        Json(json): Json<TermsVersionPublishRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> TermsVersionPublishResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = format!("{}.{}", TERMS_PERMISSION_NAME, TERMS_PERMISSION_ATTRIBUTE);
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+
+            let kind = json.kind.trim().to_string();
+            let version = json.version.trim().to_string();
+            if !TERMS_KINDS.contains(&kind.as_str()) {
+                return Ok(TermsVersionPublishResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["kind"],
+                        format!("kind must be one of {}", TERMS_KINDS.join(", ")),
+                    ),
+                )));
+            }
+            if version.is_empty() {
+                return Ok(TermsVersionPublishResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["version"],
+                        "version is required".to_string(),
+                    ),
+                )));
+            }
+            if json.url.is_none() && json.content.is_none() {
+                return Ok(TermsVersionPublishResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &[],
+                        "url or content is required".to_string(),
+                    ),
+                )));
+            }
+            if get_terms_version_by_kind_version(&mut tx, &kind, &version)
+                .await?
+                .is_some()
+            {
+                return Err(AppError::conflict(format!(
+                    "{} version {} already published",
+                    kind, version
+                )));
+            }
+
+            let terms_version = TermsVersion {
+                id: Uuid::now_v7(),
+                kind,
+                version,
+                url: json.url,
+                content: json.content,
+                published_by: Some(request_user.id),
+                published_date: Some(utc_now()),
+            };
+            // a version published concurrently is returned as a conflict, see AppError
+            create_terms_version(&mut tx, &terms_version).await?;
+            tx.commit().await?;
+            Ok(TermsVersionPublishResponses::Ok(Json(
+                terms_version_response(terms_version),
+            )))
+        })
+        .await
+    }
+
+    /// Accept the latest version of a terms document for the request user
+    #[oai(path = "/terms/accept/", method = "post", tag = "ApiTermsTags::Terms")]
+    async fn accept_terms_api(
+        &self,
+        Json(json): Json<TermsAcceptRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> TermsAcceptResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            let not_found = || {
+                AppError::not_found(format!(
+                    "terms version with id = {} not found",
+                    json.terms_version_id
+                ))
+            };
+            let terms_version_id =
+                Uuid::parse_str(&json.terms_version_id).map_err(|_| not_found())?;
+            get_terms_version_by_id(&mut tx, &terms_version_id)
+                .await?
+                .ok_or_else(not_found)?;
+            let latest = get_latest_terms_version(&mut tx).await?;
+            if !latest.iter().any(|x| x.id == terms_version_id) {
+                return Err(AppError::bad_request(
+                    "only the latest version can be accepted",
+                ));
+            }
+
+            let now = utc_now();
+            accept_terms_version(&mut tx, &request_user.id, &terms_version_id, &now).await?;
+            let pending = get_pending_terms_version_by_user(&mut tx, &request_user.id).await?;
+            tx.commit().await?;
+            Ok(TermsAcceptResponses::Ok(Json(
+                pending.into_iter().map(terms_version_response).collect(),
+            )))
+        })
+        .await
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    cli::seed::{parse_seed_data, seed, DEFAULT_SEED},
+    core::{
+        permission_cache::invalidate_user_permissions, session_store::create_redis_pool,
+        test_utils::generate_test_user,
+    },
+    init_openapi_route,
+    model::user_group_roles::TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_terms_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let payload =
+        json!({"kind": "terms_of_service", "version": "2025-04", "url": "https://example.com/tos"});
+
+    // When request user lacks the permission
+    let resp = cli
+        .post("/api/terms")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When request user is admin
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    let resp = cli
+        .post("/api/terms")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect published and pending
+    resp.assert_status(StatusCode::CREATED);
+    let terms_version_id = resp
+        .json()
+        .await
+        .value()
+        .object()
+        .get("id")
+        .string()
+        .to_string();
+    let resp = cli
+        .post("/api/terms")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CONFLICT);
+    let resp = cli.get("/api/terms").send().await;
+    resp.assert_status_is_ok();
+    resp.json().await.value().array().assert_len(1);
+    let resp = cli
+        .get("/api/terms/pending")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(1);
+    list.get(0)
+        .object()
+        .get("id")
+        .assert_string(&terms_version_id);
+
+    // When accept
+    let resp = cli
+        .post("/api/terms/accept")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"terms_version_id": terms_version_id}))
+        .send()
+        .await;
+
+    // Expect nothing pending
+    resp.assert_status_is_ok();
+    resp.json().await.value().array().assert_len(0);
+    Ok(())
+}
@@ -1,45 +1,79 @@
 use std::sync::Arc;
 
-use chrono::Local;
+use chrono::Duration;
 use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::{PgConnection, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     core::{
-        security::{get_user_from_token, hash_password, BearerAuthorization},
-        utils::datetime_to_string_opt,
+        email::is_valid_email,
+        email_change::request_email_change,
+        email_verification::request_email_verification,
+        error::{respond, AppError},
+        lifecycle::{change_user_status, check_transition, revoke_deactivated_sessions},
+        locale::{normalize_profile_locale, UserLocale},
+        login_lockout::unlock_user,
+        permission_cache::permissions_changed,
+        push::spawn_user_devices_notification,
+        security::{
+            ensure_permission, hash_password, AuthContext, BearerAuthorization, ReadAuthContext,
+        },
+        sqlx_utils::Sort,
+        user_name::{
+            check_user_name_available, record_user_name_change, DEFAULT_USER_NAME_RESERVE_DAYS,
+        },
+        utils::{datetime_to_string_opt, string_to_datetime, utc_now},
+        validation::Validate,
     },
     model::{
-        group::Group, role::Role, user::User, user_group_roles::UserGroupRoles,
+        audited::Audited,
+        group::Group,
+        notification_template::EVENT_PASSWORD_CHANGED,
+        role::Role,
+        scim_provisioning_event::{EVENT_CREATE, EVENT_DEACTIVATE, EVENT_UPDATE},
+        user::{User, UserStatus},
+        user_anonymization::{
+            PERMISSION_ATTRIBUTE as ANONYMIZE_PERMISSION_ATTRIBUTE,
+            PERMISSION_NAME as ANONYMIZE_PERMISSION_NAME,
+        },
+        user_group_roles::UserGroupRoles,
         user_profile::UserProfile,
+        user_status_history::UserStatusHistory,
     },
     repository::{
         group::get_group_by_id,
         role::get_role_by_id,
+        scim_provisioning_event::enqueue_scim_event,
         user::{
-            create_user, get_all_user, get_user_by_id, get_user_group_roles_by_user,
-            soft_delete_user, update_user, upsert_user_group_roles,
+            anonymize_user, create_user, get_all_user, get_user_by_id,
+            get_user_group_roles_by_user, soft_delete_user, update_user, upsert_user_group_roles,
+            UserFilter,
         },
+        user_anonymization::{create_user_anonymization, get_user_anonymization_by_user},
         user_group_roles::{
             add_user_group_roles, delete_user_group_roles, get_detail_user_group_roles,
         },
+        user_name_history::get_user_name_history_by_user,
+        user_status_history::{create_user_status_history, get_user_status_history_by_user},
     },
     schema::{
-        common::{
-            BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-            UnauthorizedResponse,
-        },
+        common::{BadRequestResponse, PaginateResponse, UnprocessableEntityResponse},
         user::{
             AddUserGroupRoleRequest, AddUserGroupRoleResponse, AddUserGroupRoleResponses,
             ChangeStatusRequest, ChangeStatusResponses, DeleteUserGroupRoleResponses,
             DetailCreatedOrUpdatedUser, DetailGroup, DetailGroupRole, DetailRole, DetailUser,
-            DetailUserProfile, GetAllUserResponses, GetPaginateUserResponses, ResetPasswordRequest,
-            ResetPasswordResponse, ResetPasswordResponses, UserCreateRequest, UserCreateResponse,
-            UserCreateResponses, UserDeleteResponses, UserDetailResponse, UserDetailResponses,
-            UserUpdateRequest, UserUpdateResponse, UserUpdateResponses,
+            DetailUserProfile, GetAllUserResponses, GetPaginateUserResponses, GroupRole,
+            ResetPasswordRequest, ResetPasswordResponse, ResetPasswordResponses,
+            UserAnonymizeResponses, UserCreateRequest, UserCreateResponse, UserCreateResponses,
+            UserDeleteResponses, UserDetailResponse, UserDetailResponses,
+            UserLifecycleStatusRequest, UserLifecycleStatusResponses, UserStatusHistoryResponse,
+            UserStatusHistoryResponses, UserUnlockResponses, UserUpdateRequest, UserUpdateResponse,
+            UserUpdateResponses,
         },
     },
+    settings::get_config,
     AppState,
 };
 
@@ -50,124 +84,200 @@ enum ApiUserTags {
 
 pub struct ApiUser;
 
+fn user_status_history_response(history: UserStatusHistory) -> UserStatusHistoryResponse {
+    UserStatusHistoryResponse {
+        id: history.id.to_string(),
+        from_status: history.from_status,
+        to_status: history.to_status,
+        reason: history.reason,
+        changed_by: history.changed_by.map(|x| x.to_string()),
+        created_date: datetime_to_string_opt(history.created_date),
+    }
+}
+
+fn user_not_found(id: &str) -> AppError {
+    AppError::not_found(format!("user with id = {} not found", id))
+}
+
+/// Hash of the password, argon2 errors are not anyhow errors
+fn hash(password: &str) -> Result<String, AppError> {
+    hash_password(password)
+        .map_err(|err| AppError::Internal(anyhow::anyhow!("hash_password: {}", err)))
+}
+
+/// User of an id sent in the body or query, a bad request when it does not exist
+async fn find_user(conn: &mut PgConnection, id: &str) -> Result<User, AppError> {
+    let not_found = || AppError::bad_request(format!("user with id = {} not found", id));
+    let user_id = Uuid::parse_str(id).map_err(|_| not_found())?;
+    get_user_by_id(conn, &user_id, None)
+        .await?
+        .0
+        .ok_or_else(not_found)
+}
+
+/// Same as find_user for roles
+async fn find_role(conn: &mut PgConnection, id: &str) -> Result<Role, AppError> {
+    let not_found = || AppError::bad_request(format!("role with id = {} not found", id));
+    let role_id = Uuid::parse_str(id).map_err(|_| not_found())?;
+    get_role_by_id(conn, &role_id).await?.ok_or_else(not_found)
+}
+
+/// Same as find_user for groups
+async fn find_group(conn: &mut PgConnection, id: &str) -> Result<Group, AppError> {
+    let not_found = || AppError::bad_request(format!("group with id = {} not found", id));
+    let group_id = Uuid::parse_str(id).map_err(|_| not_found())?;
+    get_group_by_id(conn, &group_id)
+        .await?
+        .ok_or_else(not_found)
+}
+
+/// Replace every group role of the user with the requested ones
+async fn replace_group_roles(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &User,
+    group_roles: Vec<GroupRole>,
+) -> Result<Vec<DetailGroupRole>, AppError> {
+    let mut user_group_roles: Vec<UserGroupRoles> = vec![];
+    let mut res: Vec<DetailGroupRole> = vec![];
+    for item in group_roles {
+        let role = find_role(tx, &item.role_id).await?;
+        let group = find_group(tx, &item.group_id).await?;
+        user_group_roles.push(UserGroupRoles {
+            id: Uuid::now_v7(),
+            user_id: Some(user.id),
+            group_id: Some(group.id),
+            role_id: Some(role.id),
+        });
+        res.push(DetailGroupRole {
+            role: Some(DetailRole {
+                id: role.id.to_string(),
+                role_name: role.role_name,
+            }),
+            group: Some(DetailGroup {
+                id: group.id.to_string(),
+                group_name: group.group_name,
+            }),
+        });
+    }
+    upsert_user_group_roles(tx, user, &user_group_roles).await?;
+    Ok(res)
+}
+
 #[OpenApi]
 impl ApiUser {
     #[oai(path = "/user/", method = "get", tag = "ApiUserTags::User")]
+    #[allow(clippy::too_many_arguments)]
     async fn get_paginate_user_api(
         &self,
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(search): Query<Option<String>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// One of user_name, status, is_active, expires_at, created_date, updated_date
+        Query(sort_by): Query<Option<String>>,
+        /// asc or desc
+        Query(sort_dir): Query<Option<String>>,
+        /// Only users whose account expires within this many days
+        Query(expiring_within_days): Query<Option<u32>>,
+        Query(is_active): Query<Option<bool>>,
+        Query(is_2faenabled): Query<Option<bool>>,
+        /// Only users created on or after this date
+        Query(created_from): Query<Option<String>>,
+        /// Only users created on or before this date
+        Query(created_to): Query<Option<String>>,
+        /// Only members of this group
+        Query(group_id): Query<Option<String>>,
+        /// Only users assigned this role, directly or within a group
+        Query(role_id): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> GetPaginateUserResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GetPaginateUserResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "get_paginate_user_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GetPaginateUserResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "get_paginate_user_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+            let mut filter = UserFilter {
+                search,
+                expiring_before: expiring_within_days.map(|x| utc_now() + Duration::days(x as i64)),
+                is_active,
+                is_2faenabled,
+                ..Default::default()
+            };
+            for (field, val, dest) in [
+                ("created_from", created_from, &mut filter.created_from),
+                ("created_to", created_to, &mut filter.created_to),
+            ] {
+                if let Some(val) = val {
+                    *dest = Some(string_to_datetime(&val).map_err(|err| {
+                        AppError::BadRequest(
+                            BadRequestResponse::new(err.to_string()).with_field(field.to_string()),
+                        )
+                    })?);
+                }
             }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GetPaginateUserResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "get_paginate_user_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
+            for (field, val, dest) in [
+                ("group_id", group_id, &mut filter.group_id),
+                ("role_id", role_id, &mut filter.role_id),
+            ] {
+                if let Some(val) = val {
+                    *dest = Some(Uuid::parse_str(&val).map_err(|_| {
+                        AppError::BadRequest(
+                            BadRequestResponse::new(format!("invalid {} {}", field, val))
+                                .with_field(field.to_string()),
+                        )
+                    })?);
                 }
-            };
-        if request_user.is_none() {
-            return GetPaginateUserResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
+            }
 
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) =
-            match get_all_user(&mut tx, page, page_size, search, None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GetPaginateUserResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "get_paginate_user_api",
-                            "get_all_user",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) = get_all_user(
+                &mut db,
+                page,
+                page_size,
+                filter,
+                None,
+                Sort::new(sort_by, sort_dir),
+            )
+            .await?;
 
-        let mut results: Vec<DetailUser> = vec![];
-        for item in data {
-            let mut created_by: Option<User> = None;
-            if item.created_by.is_some() {
-                (created_by, _) =
-                    match get_user_by_id(&mut tx, &item.created_by.unwrap(), None).await {
-                        Ok(val) => val,
-                        Err(err) => {
-                            return GetPaginateUserResponses::InternalServerError(Json(
-                                InternalServerErrorResponse::new(
-                                    "route.user",
-                                    "get_paginate_user_api",
-                                    "get_user_detail for created_by",
-                                    &err.to_string(),
-                                ),
-                            ))
+            let mut results: Vec<DetailUser> = vec![];
+            for Audited {
+                data: item,
+                created_by_name,
+                ..
+            } in data
+            {
+                results.push(DetailUser {
+                    id: item.id.to_string(),
+                    is_active: Some(item.is_active()),
+                    user_name: item.user_name,
+                    status: item.status,
+                    expires_at: datetime_to_string_opt(item.expires_at),
+                    is_2faenabled: item.is_2faenabled,
+                    created_by: item.created_by.zip(created_by_name).map(|(id, user_name)| {
+                        DetailCreatedOrUpdatedUser {
+                            id: id.to_string(),
+                            user_name,
                         }
-                    };
+                    }),
+                    created_date: datetime_to_string_opt(item.created_date),
+                    updated_date: datetime_to_string_opt(item.updated_date),
+                });
             }
-            results.push(DetailUser {
-                id: item.id.to_string(),
-                user_name: item.user_name,
-                is_active: item.is_active,
-                is_2faenabled: item.is_2faenabled,
-                created_date: datetime_to_string_opt(item.created_date),
-                updated_date: datetime_to_string_opt(item.updated_date),
-                created_by: created_by.map(|x| DetailCreatedOrUpdatedUser {
-                    id: x.id.to_string(),
-                    user_name: x.user_name,
-                }),
-            });
-        }
 
-        GetPaginateUserResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results,
-        }))
+            Ok(GetPaginateUserResponses::Ok(Json(PaginateResponse {
+                counts,
+                page,
+                page_count,
+                page_size,
+                results,
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/user/all/", method = "get", tag = "ApiUserTags::User")]
@@ -176,535 +286,270 @@ impl ApiUser {
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(search): Query<Option<String>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// Only users whose account expires within this many days
+        Query(expiring_within_days): Query<Option<u32>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> GetAllUserResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return GetAllUserResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "get_all_user_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return GetAllUserResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "get_all_user_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GetAllUserResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "get_all_user_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return GetAllUserResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
 
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) =
-            match get_all_user(&mut tx, page, page_size, search, None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return GetAllUserResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "get_all_user_api",
-                            "get_all_user",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let filter = UserFilter {
+                search,
+                expiring_before: expiring_within_days.map(|x| utc_now() + Duration::days(x as i64)),
+                ..Default::default()
             };
+            let (data, counts, page_count) =
+                get_all_user(&mut db, page, page_size, filter, None, Sort::default()).await?;
 
-        let mut results: Vec<DetailUser> = vec![];
-        for item in data {
-            let mut created_by: Option<User> = None;
-            if item.created_by.is_some() {
-                (created_by, _) =
-                    match get_user_by_id(&mut tx, &item.created_by.unwrap(), None).await {
-                        Ok(val) => val,
-                        Err(err) => {
-                            return GetAllUserResponses::InternalServerError(Json(
-                                InternalServerErrorResponse::new(
-                                    "route.user",
-                                    "get_all_user_api",
-                                    "get_user_detail for created_by",
-                                    &err.to_string(),
-                                ),
-                            ))
+            let mut results: Vec<DetailUser> = vec![];
+            for Audited {
+                data: item,
+                created_by_name,
+                ..
+            } in data
+            {
+                results.push(DetailUser {
+                    id: item.id.to_string(),
+                    is_active: Some(item.is_active()),
+                    user_name: item.user_name,
+                    status: item.status,
+                    expires_at: datetime_to_string_opt(item.expires_at),
+                    is_2faenabled: item.is_2faenabled,
+                    created_date: datetime_to_string_opt(item.created_date),
+                    updated_date: datetime_to_string_opt(item.updated_date),
+                    created_by: item.created_by.zip(created_by_name).map(|(id, user_name)| {
+                        DetailCreatedOrUpdatedUser {
+                            id: id.to_string(),
+                            user_name,
                         }
-                    };
+                    }),
+                });
             }
-            results.push(DetailUser {
-                id: item.id.to_string(),
-                user_name: item.user_name,
-                is_active: item.is_active,
-                is_2faenabled: item.is_2faenabled,
-                created_date: datetime_to_string_opt(item.created_date),
-                updated_date: datetime_to_string_opt(item.updated_date),
-                created_by: created_by.map(|x| DetailCreatedOrUpdatedUser {
-                    id: x.id.to_string(),
-                    user_name: x.user_name,
-                }),
-            });
-        }
 
-        GetAllUserResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results,
-        }))
+            Ok(GetAllUserResponses::Ok(Json(PaginateResponse {
+                counts,
+                page,
+                page_count,
+                page_size,
+                results,
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/user/detail/", method = "get", tag = "ApiUserTags::User")]
     async fn user_detail_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> UserDetailResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_detail_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_detail_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return UserDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "user_detail_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return UserDetailResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return UserDetailResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("user with id = {} not found", &id),
-                }))
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            let (user, user_profile) = get_user_by_id(&mut db, &user_id, None).await?;
+            let user = user.ok_or_else(|| user_not_found(&id))?;
+            let mut created_by: Option<User> = None;
+            if let Some(created_by_id) = user.created_by {
+                (created_by, _) = get_user_by_id(&mut db, &created_by_id, None).await?;
             }
-        };
-        let (user, user_profile) = match get_user_by_id(&mut tx, &id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_detail_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+            let mut updated_by: Option<User> = None;
+            if let Some(updated_by_id) = user.updated_by {
+                (updated_by, _) = get_user_by_id(&mut db, &updated_by_id, None).await?;
             }
-        };
-        if user.is_none() {
-            return UserDetailResponses::NotFound(Json(NotFoundResponse {
-                message: format!("user with id = {} not found", &id),
-            }));
-        }
-        let user = user.unwrap();
-        let mut created_by: Option<User> = None;
-        if user.created_by.is_some() {
-            let (x, _) = match get_user_by_id(&mut tx, &user.created_by.unwrap(), None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return UserDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "user_detail_api",
-                            "get created_by user",
-                            &err.to_string(),
-                        ),
-                    ))
+
+            let user_group_roles = get_user_group_roles_by_user(&mut db, &user).await?;
+            let mut group_roles: Vec<DetailGroupRole> = vec![];
+            for item in user_group_roles {
+                let mut role: Option<Role> = None;
+                if let Some(role_id) = item.role_id {
+                    role = get_role_by_id(&mut db, &role_id).await?;
                 }
-            };
-            created_by = x
-        }
-        let mut updated_by: Option<User> = None;
-        if user.updated_by.is_some() {
-            let (x, _) = match get_user_by_id(&mut tx, &user.updated_by.unwrap(), None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return UserDetailResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "user_detail_api",
-                            "get updated_by user",
-                            &err.to_string(),
-                        ),
-                    ))
+                let mut group: Option<Group> = None;
+                if let Some(group_id) = item.group_id {
+                    group = get_group_by_id(&mut db, &group_id).await?;
                 }
-            };
-            updated_by = x
-        }
-
-        let user_group_roles = match get_user_group_roles_by_user(&mut tx, &user).await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDetailResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_detail_api",
-                        "get_user_group_roles_by_user",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        let mut group_roles: Vec<DetailGroupRole> = vec![];
-        for item in user_group_roles {
-            let mut role: Option<Role> = None;
-            if item.role_id.is_some() {
-                role = match get_role_by_id(&mut tx, &item.role_id.unwrap()).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return UserDetailResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.user",
-                                "user_detail_api",
-                                "get role from user_group_roles",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
-            }
-            let mut group: Option<Group> = None;
-            if item.group_id.is_some() {
-                group = match get_group_by_id(&mut tx, &item.group_id.unwrap()).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return UserDetailResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.user",
-                                "user_detail_api",
-                                "get group from user_role_groups",
-                                &err.to_string(),
-                            ),
-                        ))
-                    }
-                };
+                group_roles.push(DetailGroupRole {
+                    role: role.map(|x| DetailRole {
+                        id: x.id.to_string(),
+                        role_name: x.role_name,
+                    }),
+                    group: group.map(|x| DetailGroup {
+                        id: x.id.to_string(),
+                        group_name: x.group_name,
+                    }),
+                });
             }
-            group_roles.push(DetailGroupRole {
-                role: role.map(|x| DetailRole {
+
+            let user_name_history = get_user_name_history_by_user(&mut db, &user.id).await?;
+
+            Ok(UserDetailResponses::Ok(Json(UserDetailResponse {
+                id: user.id.to_string(),
+                is_active: Some(user.is_active()),
+                user_name: user.user_name,
+                status: user.status,
+                expires_at: datetime_to_string_opt(user.expires_at),
+                is_2faenabled: user.is_2faenabled,
+                created_date: datetime_to_string_opt(user.created_date),
+                updated_date: datetime_to_string_opt(user.updated_date),
+                user_profile: user_profile.map(|x| DetailUserProfile {
+                    first_name: x.first_name,
+                    last_name: x.last_name,
+                    email: x.email,
+                    address: x.address,
+                    locale: x.locale,
+                    timezone: x.timezone,
+                    email_verified_at: datetime_to_string_opt(x.email_verified_at),
+                }),
+                created_by: created_by.map(|x| DetailCreatedOrUpdatedUser {
                     id: x.id.to_string(),
-                    role_name: x.role_name,
+                    user_name: x.user_name,
                 }),
-                group: group.map(|x| DetailGroup {
+                updated_by: updated_by.map(|x| DetailCreatedOrUpdatedUser {
                     id: x.id.to_string(),
-                    group_name: x.group_name,
+                    user_name: x.user_name,
                 }),
-            });
-        }
-
-        UserDetailResponses::Ok(Json(UserDetailResponse {
-            id: user.id.to_string(),
-            user_name: user.user_name,
-            is_active: user.is_active,
-            is_2faenabled: user.is_2faenabled,
-            created_date: datetime_to_string_opt(user.created_date),
-            updated_date: datetime_to_string_opt(user.updated_date),
-            user_profile: user_profile.map(|x| DetailUserProfile {
-                first_name: x.first_name,
-                last_name: x.last_name,
-                email: x.email,
-                address: x.address,
-            }),
-            created_by: created_by.map(|x| DetailCreatedOrUpdatedUser {
-                id: x.id.to_string(),
-                user_name: x.user_name,
-            }),
-            updated_by: updated_by.map(|x| DetailCreatedOrUpdatedUser {
-                id: x.id.to_string(),
-                user_name: x.user_name,
-            }),
-            group_roles,
-        }))
+                previous_user_names: user_name_history.into_iter().map(|x| x.user_name).collect(),
+                group_roles,
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/user/", method = "post", tag = "ApiUserTags::User")]
     async fn user_create_api(
         &self,
         Json(json): Json<UserCreateRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> UserCreateResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_create_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return UserCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_create_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return UserCreateResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "user_create_api",
-                            "get user from token",
-                            &err.to_string(),
+        if let Some(errors) = json.validation_errors() {
+            return UserCreateResponses::UnprocessableEntity(Json(errors));
+        }
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.create").await?;
+            let now = utc_now();
+            let expires_at = match json.expires_at.as_deref().map(string_to_datetime) {
+                Some(Err(err)) => {
+                    return Ok(UserCreateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(
+                            &["expires_at"],
+                            format!("expires_at: {}", err),
                         ),
-                    ))
+                    )))
                 }
+                Some(Ok(val)) => Some(val),
+                None => None,
             };
-        if request_user.is_none() {
-            return UserCreateResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let now = Local::now().fixed_offset();
-        // Insert User and User Profile
-        let request_user = request_user.unwrap();
-        let hashed_password = match hash_password(&json.password) {
-            Ok(val) => val,
-            Err(err) => {
-                return UserCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_create_api",
-                        "hash_password",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-        let new_user = User {
-            id: Uuid::now_v7(),
-            user_name: json.user_name,
-            password: hashed_password,
-            is_active: Some(json.is_active),
-            is_2faenabled: Some(false),
-            created_by: Some(request_user.id),
-            updated_by: Some(request_user.id),
-            created_date: Some(now),
-            updated_date: Some(now),
-            deleted_date: None,
-        };
-        let new_user_profile = UserProfile {
-            id: Uuid::now_v7(),
-            user_id: new_user.id,
-            first_name: json.first_name,
-            last_name: json.last_name,
-            address: json.address,
-            email: json.email,
-        };
-        if let Err(err) = create_user(&mut tx, &new_user, &new_user_profile).await {
-            return UserCreateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "user_create_api",
-                    "create_user",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        // Insert User Group Roles
-        let mut user_group_roles: Vec<UserGroupRoles> = vec![];
-        let mut group_roles_res: Vec<DetailGroupRole> = vec![];
-        if json.group_roles.is_some() {
-            let group_roles = json.group_roles.unwrap();
-            for item in group_roles {
-                let role_id = match Uuid::parse_str(&item.role_id) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        return UserCreateResponses::BadRequest(Json(BadRequestResponse {
-                            message: format!("role with id = {} not found", &item.role_id),
-                        }))
-                    }
-                };
-                let role = match get_role_by_id(&mut tx, &role_id).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return UserCreateResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.user",
-                                "user_create_api",
-                                "check role",
-                                &err.to_string(),
-                            ),
-                        ));
-                    }
-                };
-                if role.is_none() {
-                    return UserCreateResponses::BadRequest(Json(BadRequestResponse {
-                        message: format!("role with id = {} not found", &item.role_id),
-                    }));
-                }
-                let role = role.unwrap();
-                let group_id = match Uuid::parse_str(&item.group_id) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        return UserCreateResponses::BadRequest(Json(BadRequestResponse {
-                            message: format!("group with id = {} not found", &item.group_id),
-                        }))
-                    }
-                };
-                let group = match get_group_by_id(&mut tx, &group_id).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return UserCreateResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.user",
-                                "user_create_api",
-                                "check group",
-                                &err.to_string(),
-                            ),
-                        ));
-                    }
-                };
-                if group.is_none() {
-                    return UserCreateResponses::BadRequest(Json(BadRequestResponse {
-                        message: format!("group with id = {} not found", &item.group_id),
-                    }));
+            let (locale, timezone) = match normalize_profile_locale(json.locale, json.timezone) {
+                Ok(val) => val,
+                Err(message) => {
+                    return Ok(UserCreateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(&[], message),
+                    )))
                 }
-                let group = group.unwrap();
-                user_group_roles.push(UserGroupRoles {
-                    id: Uuid::now_v7(),
-                    user_id: Some(new_user.id),
-                    group_id: Some(group_id),
-                    role_id: Some(role_id),
-                });
-                group_roles_res.push(DetailGroupRole {
-                    role: Some(DetailRole {
-                        id: role.id.to_string(),
-                        role_name: role.role_name,
-                    }),
-                    group: Some(DetailGroup {
-                        id: group.id.to_string(),
-                        group_name: group.group_name,
-                    }),
-                });
+            };
+            // Insert User and User Profile
+            let reserve_days = get_config()
+                .user_name_reserve_days
+                .unwrap_or(DEFAULT_USER_NAME_RESERVE_DAYS);
+            if let Some(message) =
+                check_user_name_available(&mut tx, &json.user_name, None, reserve_days, &now)
+                    .await?
+            {
+                return Err(AppError::conflict(message));
             }
-            if let Err(err) = upsert_user_group_roles(&mut tx, &new_user, &user_group_roles).await {
-                return UserCreateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_create_api",
-                        "upsert_user_group_roles",
-                        &err.to_string(),
-                    ),
-                ));
+            let new_user = User {
+                id: Uuid::now_v7(),
+                user_name: json.user_name,
+                password: hash(&json.password)?,
+                status: UserStatus::from_active(json.is_active),
+                is_2faenabled: Some(false),
+                created_by: Some(request_user.id),
+                updated_by: Some(request_user.id),
+                created_date: Some(now),
+                updated_date: Some(now),
+                deleted_date: None,
+                expires_at,
+                scopes: None,
+            };
+            let new_user_profile = UserProfile {
+                id: Uuid::now_v7(),
+                user_id: new_user.id,
+                first_name: json.first_name,
+                last_name: json.last_name,
+                address: json.address,
+                email: json.email,
+                locale,
+                timezone,
+                email_verified_at: None,
+            };
+            // a duplicate user name or email is returned as a conflict, see AppError
+            create_user(&mut tx, &new_user, &new_user_profile).await?;
+            // Insert User Group Roles
+            let mut group_roles_res: Vec<DetailGroupRole> = vec![];
+            if let Some(group_roles) = json.group_roles {
+                group_roles_res = replace_group_roles(&mut tx, &new_user, group_roles).await?;
             }
-        }
 
-        if let Err(err) = tx.commit().await {
-            return UserCreateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "user_create_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &new_user.id, EVENT_CREATE, Some(now)).await?;
+            // Verification link to the email of the new user
+            let config = get_config();
+            let email = new_user_profile.email.as_deref().filter(|x| !x.is_empty());
+            if let (true, Some(email)) = (config.email_verification_enabled.unwrap_or(false), email)
+            {
+                request_email_verification(
+                    &mut tx,
+                    &mut redis_conn,
+                    &config,
+                    &new_user,
+                    &UserLocale::resolve(&config, Some(&new_user_profile)),
+                    email,
+                )
+                .await?;
+            }
 
-        UserCreateResponses::Created(Json(UserCreateResponse {
-            id: new_user.id.to_string(),
-            user_name: new_user.user_name,
-            is_active: new_user.is_active,
-            group_roles: group_roles_res,
-            user_profile: Some(DetailUserProfile {
-                first_name: new_user_profile.first_name,
-                last_name: new_user_profile.last_name,
-                email: new_user_profile.email,
-                address: new_user_profile.address,
-            }),
-        }))
+            tx.commit().await?;
+
+            Ok(UserCreateResponses::Created(Json(UserCreateResponse {
+                id: new_user.id.to_string(),
+                is_active: Some(new_user.is_active()),
+                user_name: new_user.user_name,
+                status: new_user.status,
+                expires_at: datetime_to_string_opt(new_user.expires_at),
+                group_roles: group_roles_res,
+                user_profile: Some(DetailUserProfile {
+                    first_name: new_user_profile.first_name,
+                    last_name: new_user_profile.last_name,
+                    email: new_user_profile.email,
+                    address: new_user_profile.address,
+                    locale: new_user_profile.locale,
+                    timezone: new_user_profile.timezone,
+                    email_verified_at: datetime_to_string_opt(new_user_profile.email_verified_at),
+                }),
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/user/", method = "put", tag = "ApiUserTags::User")]
@@ -712,330 +557,295 @@ impl ApiUser {
         &self,
         Query(id): Query<String>,
         Json(json): Json<UserUpdateRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> UserUpdateResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_update_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return UserUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_update_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+        if let Some(errors) = json.validation_errors() {
+            return UserUpdateResponses::UnprocessableEntity(Json(errors));
+        }
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.update").await?;
+            // get user on db
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            let (user, user_profile) = get_user_by_id(&mut tx, &user_id, None).await?;
+            let (mut user, mut user_profile) =
+                user.zip(user_profile).ok_or_else(|| user_not_found(&id))?;
+            // Update user and user_profile
+            let now = utc_now();
+            let status = UserStatus::from_active(json.is_active);
+            let status_changed = user.status != status;
+            if status_changed {
+                if let Some(message) = check_transition(user.status, status) {
+                    return Err(AppError::bad_request(message));
+                }
             }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return UserUpdateResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "user_update_api",
-                            "get user from token",
-                            &err.to_string(),
+            user.expires_at = match json.expires_at.as_deref().map(string_to_datetime) {
+                Some(Err(err)) => {
+                    return Ok(UserUpdateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(
+                            &["expires_at"],
+                            format!("expires_at: {}", err),
                         ),
-                    ))
+                    )))
                 }
+                Some(Ok(val)) => Some(val),
+                None => None,
             };
-        if request_user.is_none() {
-            return UserUpdateResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let request_user = request_user.unwrap();
-        // get user on db
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return UserUpdateResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("user with id = {} not found", &id),
-                }))
-            }
-        };
-        let (user, user_profile) = match get_user_by_id(&mut tx, &id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_update_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+            let (locale, timezone) = match normalize_profile_locale(json.locale, json.timezone) {
+                Ok(val) => val,
+                Err(message) => {
+                    return Ok(UserUpdateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(&[], message),
+                    )))
+                }
+            };
+            if user.user_name != json.user_name {
+                let reserve_days = get_config()
+                    .user_name_reserve_days
+                    .unwrap_or(DEFAULT_USER_NAME_RESERVE_DAYS);
+                if let Some(message) = check_user_name_available(
+                    &mut tx,
+                    &json.user_name,
+                    Some(&user.id),
+                    reserve_days,
+                    &now,
+                )
+                .await?
+                {
+                    return Err(AppError::conflict(message));
+                }
+                record_user_name_change(&mut tx, &user, Some(request_user.id), &now).await?;
             }
-        };
-        if user.is_none() || user_profile.is_none() {
-            return UserUpdateResponses::NotFound(Json(NotFoundResponse {
-                message: format!("user with id = {} not found", &id),
-            }));
-        }
-        // Update user and user_profile
-        let now = Local::now().fixed_offset();
-        let mut user = user.unwrap();
-        user.user_name = json.user_name;
-        user.password = hash_password(&user.password).unwrap();
-        user.is_active = Some(json.is_active);
-        let mut user_profile = user_profile.unwrap();
-        user_profile.first_name = json.first_name;
-        user_profile.last_name = json.last_name;
-        user_profile.email = json.email;
-        user_profile.address = json.address;
-        if let Err(err) = update_user(&mut tx, &mut user, &user_profile, &request_user, &now).await
-        {
-            return UserUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "user_update_api",
-                    "update_user",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        // Upsert user_group_roles
-        let mut user_group_roles: Vec<UserGroupRoles> = vec![];
-        let mut group_roles_res: Vec<DetailGroupRole> = vec![];
-        if json.group_roles.is_some() {
-            let group_roles = json.group_roles.unwrap();
-            for item in group_roles {
-                let role_id = match Uuid::parse_str(&item.role_id) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        return UserUpdateResponses::BadRequest(Json(BadRequestResponse {
-                            message: format!("role with id = {} not found", &item.role_id),
-                        }))
-                    }
-                };
-                let role = match get_role_by_id(&mut tx, &role_id).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return UserUpdateResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.user",
-                                "user_update_api",
-                                "check role",
-                                &err.to_string(),
+            user.user_name = json.user_name;
+            user.password = hash(&user.password)?;
+            user_profile.first_name = json.first_name;
+            user_profile.last_name = json.last_name;
+            user_profile.locale = locale;
+            user_profile.timezone = timezone;
+            // a different address replaces the current one only once confirmed
+            let has_email = user_profile.email.as_deref().is_some_and(|x| !x.is_empty());
+            let pending_email = match json.email {
+                Some(val) if has_email && user_profile.email.as_deref() != Some(val.as_str()) => {
+                    if !is_valid_email(&val) {
+                        return Ok(UserUpdateResponses::UnprocessableEntity(Json(
+                            UnprocessableEntityResponse::body_error(
+                                &["email"],
+                                format!("{} is not a valid email address", val),
                             ),
-                        ));
+                        )));
                     }
-                };
-                if role.is_none() {
-                    return UserUpdateResponses::BadRequest(Json(BadRequestResponse {
-                        message: format!("role with id = {} not found", &item.role_id),
-                    }));
+                    Some(val)
                 }
-                let role = role.unwrap();
-                let group_id = match Uuid::parse_str(&item.group_id) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        return UserUpdateResponses::BadRequest(Json(BadRequestResponse {
-                            message: format!("group with id = {} not found", &item.group_id),
-                        }))
-                    }
-                };
-                let group = match get_group_by_id(&mut tx, &group_id).await {
-                    Ok(val) => val,
-                    Err(err) => {
-                        return UserUpdateResponses::InternalServerError(Json(
-                            InternalServerErrorResponse::new(
-                                "route.user",
-                                "user_update_api",
-                                "check group",
-                                &err.to_string(),
-                            ),
-                        ));
-                    }
-                };
-                if group.is_none() {
-                    return UserUpdateResponses::BadRequest(Json(BadRequestResponse {
-                        message: format!("group with id = {} not found", &item.group_id),
-                    }));
+                val => {
+                    user_profile.email = val;
+                    None
                 }
-                let group = group.unwrap();
-                user_group_roles.push(UserGroupRoles {
-                    id: Uuid::now_v7(),
-                    user_id: Some(user.id),
-                    group_id: Some(group_id),
-                    role_id: Some(role_id),
-                });
-                group_roles_res.push(DetailGroupRole {
-                    role: Some(DetailRole {
-                        id: role.id.to_string(),
-                        role_name: role.role_name,
-                    }),
-                    group: Some(DetailGroup {
-                        id: group.id.to_string(),
-                        group_name: group.group_name,
-                    }),
-                });
+            };
+            user_profile.address = json.address;
+            // a duplicate user name or email is returned as a conflict, see AppError
+            update_user(&mut tx, &mut user, &user_profile, &request_user, &now).await?;
+            let history = match status_changed {
+                true => Some(
+                    change_user_status(
+                        &mut tx,
+                        &mut user,
+                        status,
+                        None,
+                        Some(request_user.id),
+                        &now,
+                    )
+                    .await?,
+                ),
+                false => None,
+            };
+            // Upsert user_group_roles
+            let mut group_roles_res: Vec<DetailGroupRole> = vec![];
+            let group_roles_changed = json.group_roles.is_some();
+            if let Some(group_roles) = json.group_roles {
+                group_roles_res = replace_group_roles(&mut tx, &user, group_roles).await?;
             }
-            if let Err(err) = upsert_user_group_roles(&mut tx, &user, &user_group_roles).await {
-                return UserUpdateResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_update_api",
-                        "upsert_user_group_roles",
-                        &err.to_string(),
-                    ),
-                ));
+
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &user.id, EVENT_UPDATE, Some(now)).await?;
+
+            if let Some(email) = &pending_email {
+                let config = get_config();
+                request_email_change(
+                    &mut tx,
+                    &mut redis_conn,
+                    &config,
+                    &user,
+                    &UserLocale::resolve(&config, Some(&user_profile)),
+                    user_profile.email.as_deref(),
+                    email,
+                )
+                .await?;
             }
-        }
 
-        if let Err(err) = tx.commit().await {
-            return UserUpdateResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "user_update_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
+            tx.commit().await?;
+            if history.is_some_and(|x| x.left_active()) {
+                revoke_deactivated_sessions(&mut redis_conn, &[user.id]).await?;
+            }
+            if group_roles_changed {
+                // Cached permission set of the user is stale now
+                permissions_changed(&mut redis_conn, &[user.id]).await;
+            }
 
-        UserUpdateResponses::Ok(Json(UserUpdateResponse {
-            id: user.id.to_string(),
-            user_name: user.user_name,
-            is_active: user.is_active,
-            group_roles: group_roles_res,
-            user_profile: Some(DetailUserProfile {
-                first_name: user_profile.first_name,
-                last_name: user_profile.last_name,
-                email: user_profile.email,
-                address: user_profile.address,
-            }),
-        }))
+            Ok(UserUpdateResponses::Ok(Json(UserUpdateResponse {
+                id: user.id.to_string(),
+                is_active: Some(user.is_active()),
+                user_name: user.user_name,
+                status: user.status,
+                expires_at: datetime_to_string_opt(user.expires_at),
+                pending_email,
+                group_roles: group_roles_res,
+                user_profile: Some(DetailUserProfile {
+                    first_name: user_profile.first_name,
+                    last_name: user_profile.last_name,
+                    email: user_profile.email,
+                    address: user_profile.address,
+                    locale: user_profile.locale,
+                    timezone: user_profile.timezone,
+                    email_verified_at: datetime_to_string_opt(user_profile.email_verified_at),
+                }),
+            })))
+        })
+        .await
     }
 
     #[oai(path = "/user/", method = "delete", tag = "ApiUserTags::User")]
     async fn user_delete_api(
         &self,
         Query(id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> UserDeleteResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_delete_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.delete").await?;
+            // get user on db
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            let mut user = get_user_by_id(&mut tx, &user_id, None)
+                .await?
+                .0
+                .ok_or_else(|| user_not_found(&id))?;
+            // soft delete user
+            let now = utc_now();
+            soft_delete_user(&mut tx, &mut user, &request_user, &now).await?;
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &user.id, EVENT_DEACTIVATE, Some(now)).await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_delete_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+            tx.commit().await?;
+            Ok(UserDeleteResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Irreversibly scrub personal data of a user
+    ///
+    /// Unlike delete the user row is kept so records created or updated by the user
+    /// still resolve, but user name, profile, linked identities and data exports are
+    /// wiped and the user can no longer log in. Requires the user_anonymize permission.
+    #[oai(path = "/user/anonymize/", method = "post", tag = "ApiUserTags::User")]
+    async fn user_anonymize_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserAnonymizeResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            let permission = format!(
+                "{}.{}",
+                ANONYMIZE_PERMISSION_NAME, ANONYMIZE_PERMISSION_ATTRIBUTE
+            );
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, &permission).await?;
+
+            // get user on db, soft deleted users can be anonymized too
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            let mut user = get_user_by_id(&mut tx, &user_id, Some(false))
+                .await?
+                .0
+                .ok_or_else(|| user_not_found(&id))?;
+            if get_user_anonymization_by_user(&mut tx, &user.id)
+                .await?
+                .is_some()
+            {
+                return Err(AppError::bad_request(format!(
+                    "user with id = {} already anonymized",
+                    id
+                )));
             }
-        };
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return UserDeleteResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "user_delete_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return UserDeleteResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let request_user = request_user.unwrap();
-        // get user on db
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return UserDeleteResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("user with id = {} not found", &id),
-                }))
+            // password nobody knows
+            let password = hash(&Uuid::now_v7().to_string())?;
+            let now = utc_now();
+            let from_status = user.status;
+            anonymize_user(&mut tx, &mut user, &password, &request_user, &now).await?;
+            if from_status != user.status {
+                let history = UserStatusHistory {
+                    id: Uuid::now_v7(),
+                    user_id: user.id,
+                    from_status,
+                    to_status: user.status,
+                    reason: Some("anonymized".to_string()),
+                    changed_by: Some(request_user.id),
+                    created_date: Some(now),
+                };
+                create_user_status_history(&mut tx, &history).await?;
             }
-        };
-        let (user, _) = match get_user_by_id(&mut tx, &id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return UserDeleteResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "user_delete_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+            create_user_anonymization(&mut tx, &user.id, Some(request_user.id), &now).await?;
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &user.id, EVENT_DEACTIVATE, Some(now)).await?;
+
+            tx.commit().await?;
+            Ok(UserAnonymizeResponses::NoContent)
+        })
+        .await
+    }
+
+    /// Lift the lock of a user locked after too many failed logins
+    ///
+    /// Also resets the failed login count, see LOGIN_LOCKOUT_ATTEMPTS
+    #[oai(path = "/user/unlock/", method = "post", tag = "ApiUserTags::User")]
+    async fn user_unlock_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserUnlockResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.update").await?;
+            // get user on db
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            get_user_by_id(&mut db, &user_id, None)
+                .await?
+                .0
+                .ok_or_else(|| user_not_found(&id))?;
+            if unlock_user(&mut redis_conn, &user_id).await? {
+                tracing::info!("user {} unlocked by {}", user_id, request_user.id);
             }
-        };
-        if user.is_none() {
-            return UserDeleteResponses::NotFound(Json(NotFoundResponse {
-                message: format!("user with id = {} not found", &id),
-            }));
-        }
-        // soft delete user
-        let mut user = user.unwrap();
-        let now = Local::now().fixed_offset();
-        if let Err(err) = soft_delete_user(&mut tx, &mut user, &request_user, &now).await {
-            return UserDeleteResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "user_delete_api",
-                    "soft_delete_user",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return UserDeleteResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "user_delete_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        UserDeleteResponses::NoContent
+            Ok(UserUnlockResponses::NoContent)
+        })
+        .await
     }
 
     #[oai(
@@ -1048,135 +858,50 @@ impl ApiUser {
         Query(user_id): Query<String>,
         Json(json): Json<ResetPasswordRequest>,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> ResetPasswordResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return ResetPasswordResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "reset_password_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.update").await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return ResetPasswordResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "reset_password_api",
-                        "get redis pool connection",
-                        &err.to_string(),
+            // validate json request
+            if json.confirm_new_password != json.new_password {
+                return Ok(ResetPasswordResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(
+                        &["confirm_new_password"],
+                        "new_password and confirm_new_password must be same".to_string(),
                     ),
-                ))
+                )));
             }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return ResetPasswordResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "reset_password_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return ResetPasswordResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let request_user = request_user.unwrap();
-
-        // validate json request
-        if json.confirm_new_password != json.new_password {
-            return ResetPasswordResponses::BadRequest(Json(BadRequestResponse {
-                message: "new_password and confirm_new_password must be same".to_string(),
-            }));
-        }
 
-        // get user on db
-        let user_id = match Uuid::parse_str(&user_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return ResetPasswordResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("user with user_id = {} not found", &user_id),
-                }))
-            }
-        };
-        let (user, user_profile) = match get_user_by_id(&mut tx, &user_id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return ResetPasswordResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "reset_password_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() || user_profile.is_none() {
-            return ResetPasswordResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("user with user_id = {} not found", &user_id),
-            }));
-        }
-        let mut user = user.unwrap();
-        let user_profile = user_profile.unwrap();
-        user.password = match hash_password(&json.new_password) {
-            Ok(val) => val,
-            Err(err) => {
-                return ResetPasswordResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "reset_password_api",
-                        "hash_password",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        // update user
-        let now = Local::now().fixed_offset();
-        if let Err(err) = update_user(&mut tx, &mut user, &user_profile, &request_user, &now).await
-        {
-            return ResetPasswordResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "reset_password_api",
-                    "update_user",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return ResetPasswordResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "reset_password_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
+            // get user on db
+            let not_found =
+                || AppError::bad_request(format!("user with user_id = {} not found", user_id));
+            let id = Uuid::parse_str(&user_id).map_err(|_| not_found())?;
+            let (user, user_profile) = get_user_by_id(&mut tx, &id, None).await?;
+            let (mut user, user_profile) = user.zip(user_profile).ok_or_else(not_found)?;
+            user.password = hash(&json.new_password)?;
+            // update user
+            let now = utc_now();
+            update_user(&mut tx, &mut user, &user_profile, &request_user, &now).await?;
+            tx.commit().await?;
+            spawn_user_devices_notification(
+                state.db.clone(),
+                get_config(),
+                user.id,
+                EVENT_PASSWORD_CHANGED,
+            );
 
-        ResetPasswordResponses::Ok(Json(ResetPasswordResponse {
-            message: "user password updated successfully".to_string(),
-        }))
+            Ok(ResetPasswordResponses::Ok(Json(ResetPasswordResponse {
+                message: "user password updated successfully".to_string(),
+            })))
+        })
+        .await
     }
 
     #[oai(
@@ -1188,113 +913,154 @@ impl ApiUser {
         &self,
         Query(id): Query<String>,
         Json(json): Json<ChangeStatusRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> ChangeStatusResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return ChangeStatusResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "change_status_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.update").await?;
+            // get user on db
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            let (user, user_profile) = get_user_by_id(&mut tx, &user_id, None).await?;
+            let (mut user, _) = user.zip(user_profile).ok_or_else(|| user_not_found(&id))?;
+            // Update status user, true activates and false suspends
+            let now = utc_now();
+            let status = UserStatus::from_active(json.status);
+            let mut history = None;
+            if user.status != status {
+                if let Some(message) = check_transition(user.status, status) {
+                    return Err(AppError::bad_request(message));
+                }
+                history = Some(
+                    change_user_status(
+                        &mut tx,
+                        &mut user,
+                        status,
+                        None,
+                        Some(request_user.id),
+                        &now,
+                    )
+                    .await?,
+                );
             }
-        };
+            let scim_event = match json.status {
+                true => EVENT_UPDATE,
+                false => EVENT_DEACTIVATE,
+            };
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &user.id, scim_event, Some(now)).await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return ChangeStatusResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "change_status_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
+            tx.commit().await?;
+            if history.is_some_and(|x| x.left_active()) {
+                revoke_deactivated_sessions(&mut redis_conn, &[user.id]).await?;
             }
-        };
+            Ok(ChangeStatusResponses::NoContent)
+        })
+        .await
+    }
 
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return ChangeStatusResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "change_status_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return ChangeStatusResponses::Unauthorized(Json(UnauthorizedResponse::default()));
-        }
-        let request_user = request_user.unwrap();
-        // get user on db
-        let id = match Uuid::parse_str(&id) {
-            Ok(val) => val,
-            Err(_) => {
-                return ChangeStatusResponses::NotFound(Json(NotFoundResponse {
-                    message: format!("user with id = {} not found", &id),
-                }))
+    #[oai(
+        path = "/user/lifecycle-status/",
+        method = "put",
+        tag = "ApiUserTags::User"
+    )]
+    async fn change_lifecycle_status_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<UserLifecycleStatusRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserLifecycleStatusResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.update").await?;
+
+            // get user on db
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            let mut user = get_user_by_id(&mut tx, &user_id, None)
+                .await?
+                .0
+                .ok_or_else(|| user_not_found(&id))?;
+            let status = json.status;
+            if let Some(message) = check_transition(user.status, status) {
+                return Err(AppError::bad_request(message));
             }
-        };
-        let (user, user_profile) = match get_user_by_id(&mut tx, &id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return ChangeStatusResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "change_status_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
+
+            // Update status and record the change
+            let now = utc_now();
+            let reason = json
+                .reason
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty());
+            let history = change_user_status(
+                &mut tx,
+                &mut user,
+                status,
+                reason,
+                Some(request_user.id),
+                &now,
+            )
+            .await?;
+            let scim_event = match status == UserStatus::Active {
+                true => EVENT_UPDATE,
+                false => EVENT_DEACTIVATE,
+            };
+            // Queue outbound provisioning to scim targets
+            enqueue_scim_event(&mut tx, &user.id, scim_event, Some(now)).await?;
+
+            tx.commit().await?;
+            if history.left_active() {
+                revoke_deactivated_sessions(&mut redis_conn, &[user.id]).await?;
             }
-        };
-        if user.is_none() || user_profile.is_none() {
-            return ChangeStatusResponses::NotFound(Json(NotFoundResponse {
-                message: format!("user with id = {} not found", &id),
-            }));
-        }
-        // Update status user
-        let now = Local::now().fixed_offset();
-        let mut user = user.unwrap();
-        user.is_active = Some(json.status);
-        let user_profile = user_profile.unwrap();
-        if let Err(err) = update_user(&mut tx, &mut user, &user_profile, &request_user, &now).await
-        {
-            return ChangeStatusResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "change_status_api",
-                    "update_user",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return ChangeStatusResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "change_status_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        ChangeStatusResponses::NoContent
+            Ok(UserLifecycleStatusResponses::Ok(Json(
+                user_status_history_response(history),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/user/lifecycle-status/history/",
+        method = "get",
+        tag = "ApiUserTags::User"
+    )]
+    async fn user_status_history_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserStatusHistoryResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
+
+            // get user on db, soft deleted users keep their history
+            let user_id = Uuid::parse_str(&id).map_err(|_| user_not_found(&id))?;
+            get_user_by_id(&mut db, &user_id, Some(false))
+                .await?
+                .0
+                .ok_or_else(|| user_not_found(&id))?;
+            let history = get_user_status_history_by_user(&mut db, &user_id).await?;
+            Ok(UserStatusHistoryResponses::Ok(Json(
+                history
+                    .into_iter()
+                    .map(user_status_history_response)
+                    .collect(),
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -1305,197 +1071,55 @@ impl ApiUser {
     async fn add_user_group_role_api(
         &self,
         Json(json): Json<AddUserGroupRoleRequest>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> AddUserGroupRoleResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return AddUserGroupRoleResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "add_user_group_role_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return AddUserGroupRoleResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "add_user_group_role_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return AddUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "add_user_group_role_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return AddUserGroupRoleResponses::Unauthorized(Json(UnauthorizedResponse::default()));
+        if let Some(errors) = json.validation_errors() {
+            return AddUserGroupRoleResponses::UnprocessableEntity(Json(errors));
         }
-        // Validate json
-        let (user, _) = match Uuid::parse_str(&json.user_id) {
-            Ok(val) => match get_user_by_id(&mut tx, &val, None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return AddUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "add_user_group_role_api",
-                            "get_user_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            },
-            Err(_) => {
-                return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("user with id = {} not found", &json.user_id),
-                }))
-            }
-        };
-        if user.is_none() {
-            return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("user with id = {} not found", &json.user_id),
-            }));
-        }
-        let user = user.unwrap();
-
-        let role = match Uuid::parse_str(&json.role_id) {
-            Ok(val) => match get_role_by_id(&mut tx, &val).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return AddUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "add_user_group_role_api",
-                            "get_role_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            },
-            Err(_) => {
-                return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("role with id = {} not found", &json.role_id),
-                }))
-            }
-        };
-        if role.is_none() {
-            return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("role with id = {} not found", &json.role_id),
-            }));
-        }
-        let role = role.unwrap();
-
-        let group = match Uuid::parse_str(&json.group_id) {
-            Ok(val) => match get_group_by_id(&mut tx, &val).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return AddUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "add_user_group_group_api",
-                            "get_group_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            },
-            Err(_) => {
-                return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("group with id = {} not found", &json.group_id),
-                }))
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.update").await?;
+            // Validate json
+            let user = find_user(&mut tx, &json.user_id).await?;
+            let role = find_role(&mut tx, &json.role_id).await?;
+            let group = find_group(&mut tx, &json.group_id).await?;
+            if get_detail_user_group_roles(&mut tx, &user, &role, &group)
+                .await?
+                .is_some()
+            {
+                return Err(AppError::conflict(format!(
+                    "user_group_roles with user_id = {}, role_id = {}, group id = {} already exist",
+                    json.user_id, json.role_id, json.group_id
+                )));
             }
-        };
-        if group.is_none() {
-            return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("group with id = {} not found", &json.group_id),
-            }));
-        }
-        let group = group.unwrap();
 
-        let user_group_roles =
-            match get_detail_user_group_roles(&mut tx, &user, &role, &group).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return AddUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "add_user_group_api",
-                            "get_detail_user_group_roles",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
+            // add new user_group_roles
+            let new_user_group_roles = UserGroupRoles {
+                id: Uuid::now_v7(),
+                user_id: Some(user.id),
+                role_id: Some(role.id),
+                group_id: Some(group.id),
             };
-        if user_group_roles.is_some() {
-            return AddUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!(
-                    "user_group_roles with user_id = {}, role_id = {}, group id = {} already exist",
-                    &json.user_id, &json.role_id, &json.group_id
-                ),
-            }));
-        }
-
-        // add new user_group_roles
-        let new_user_group_roles = UserGroupRoles {
-            id: Uuid::now_v7(),
-            user_id: Some(user.id),
-            role_id: Some(role.id),
-            group_id: Some(group.id),
-        };
-        if let Err(err) = add_user_group_roles(&mut tx, &new_user_group_roles).await {
-            return AddUserGroupRoleResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "add_user_group_group_api",
-                    "add_user_group_roles",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return AddUserGroupRoleResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "add_user_group_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
+            add_user_group_roles(&mut tx, &new_user_group_roles).await?;
+            tx.commit().await?;
+            // Cached permission set of the user is stale now
+            permissions_changed(&mut redis_conn, &[user.id]).await;
 
-        AddUserGroupRoleResponses::Created(Json(AddUserGroupRoleResponse {
-            id: new_user_group_roles.id.to_string(),
-            user_id: new_user_group_roles.user_id.unwrap().to_string(),
-            role_id: new_user_group_roles.role_id.unwrap().to_string(),
-            group_id: new_user_group_roles.group_id.unwrap().to_string(),
-        }))
+            Ok(AddUserGroupRoleResponses::Created(Json(
+                AddUserGroupRoleResponse {
+                    id: new_user_group_roles.id.to_string(),
+                    user_id: user.id.to_string(),
+                    role_id: role.id.to_string(),
+                    group_id: group.id.to_string(),
+                },
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -1508,187 +1132,38 @@ impl ApiUser {
         Query(user_id): Query<String>,
         Query(role_id): Query<String>,
         Query(group_id): Query<String>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
     ) -> DeleteUserGroupRoleResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "delete_user_group_role_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user",
-                        "delete_user_group_role_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "delete_user_group_role_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return DeleteUserGroupRoleResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        // Validate json
-        let (user, _) = match Uuid::parse_str(&user_id) {
-            Ok(val) => match get_user_by_id(&mut tx, &val, None).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "delete_user_group_role_api",
-                            "get_user_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            },
-            Err(_) => {
-                return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("user with id = {} not found", &user_id),
-                }))
-            }
-        };
-        if user.is_none() {
-            return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("user with id = {} not found", &user_id),
-            }));
-        }
-        let user = user.unwrap();
-
-        let role = match Uuid::parse_str(&role_id) {
-            Ok(val) => match get_role_by_id(&mut tx, &val).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "delete_user_group_role_api",
-                            "get_role_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            },
-            Err(_) => {
-                return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("role with id = {} not found", &role_id),
-                }))
-            }
-        };
-        if role.is_none() {
-            return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("role with id = {} not found", &role_id),
-            }));
-        }
-        let role = role.unwrap();
-
-        let group = match Uuid::parse_str(&group_id) {
-            Ok(val) => match get_group_by_id(&mut tx, &val).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "delete_user_group_role_api",
-                            "get_group_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            },
-            Err(_) => {
-                return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("group with id = {} not found", &group_id),
-                }))
-            }
-        };
-        if group.is_none() {
-            return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("group with id = {} not found", &group_id),
-            }));
-        }
-        let group = group.unwrap();
-
-        let user_group_roles =
-            match get_detail_user_group_roles(&mut tx, &user, &role, &group).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user",
-                            "delete_user_group_role_api",
-                            "get_detail_user_group_roles",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if user_group_roles.is_none() {
-            return DeleteUserGroupRoleResponses::BadRequest(Json(BadRequestResponse {
-                message: format!(
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.update").await?;
+            // Validate json
+            let user = find_user(&mut tx, &user_id).await?;
+            let role = find_role(&mut tx, &role_id).await?;
+            let group = find_group(&mut tx, &group_id).await?;
+            if get_detail_user_group_roles(&mut tx, &user, &role, &group)
+                .await?
+                .is_none()
+            {
+                return Err(AppError::bad_request(format!(
                     "user_group_roles with user_id = {}, role_id = {}, group id = {} not found",
-                    &user_id, &role_id, &group_id
-                ),
-            }));
-        }
+                    user_id, role_id, group_id
+                )));
+            }
 
-        // Delete user group roles
-        if let Err(err) = delete_user_group_roles(&mut tx, &user, &role, &group).await {
-            return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "delete_user_group_role_api",
-                    "delete_user_group_roles",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return DeleteUserGroupRoleResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user",
-                    "delete_user_group_role_api",
-                    "commit to database",
-                    &err.to_string(),
-                ),
-            ));
-        }
+            // Delete user group roles
+            delete_user_group_roles(&mut tx, &user, &role, &group).await?;
+            tx.commit().await?;
+            // Cached permission set of the user is stale now
+            permissions_changed(&mut redis_conn, &[user.id]).await;
 
-        DeleteUserGroupRoleResponses::NoContent
+            Ok(DeleteUserGroupRoleResponses::NoContent)
+        })
+        .await
     }
 }
@@ -0,0 +1,364 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{require_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        user_contact::normalize_contact,
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::user_contact::{
+        UserContact, PERMISSION_ATTRIBUTE_READ, PERMISSION_ATTRIBUTE_UPDATE, PERMISSION_NAME,
+    },
+    repository::{
+        user::get_user_by_id,
+        user_contact::{
+            create_user_contact, delete_user_contact, get_user_contact_by_id,
+            get_user_contacts_by_user, update_user_contact,
+        },
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        user_contact::{
+            UserContactCreateResponses, UserContactDeleteResponses, UserContactListResponses,
+            UserContactRequest, UserContactResponse, UserContactUpdateResponses,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiUserContactTags {
+    UserContact,
+}
+
+pub struct ApiUserContact;
+
+fn user_contact_response(contact: UserContact) -> UserContactResponse {
+    UserContactResponse {
+        id: contact.id.to_string(),
+        user_id: contact.user_id.to_string(),
+        kind: contact.kind,
+        value: contact.value,
+        is_primary: contact.is_primary,
+        is_verified: contact.verified_date.is_some(),
+        verified_date: datetime_to_string_opt(contact.verified_date),
+        created_date: datetime_to_string_opt(contact.created_date),
+        updated_date: datetime_to_string_opt(contact.updated_date),
+    }
+}
+
+/// Whether `contacts` already hold the value, other than the contact being updated
+fn is_duplicate_contact(
+    contacts: &[UserContact],
+    kind: &str,
+    value: &str,
+    exclude_id: Option<&Uuid>,
+) -> bool {
+    contacts
+        .iter()
+        .any(|x| x.kind == kind && x.value.eq_ignore_ascii_case(value) && Some(&x.id) != exclude_id)
+}
+
+#[OpenApi]
+impl ApiUserContact {
+    /// Contacts of a user, the request user when user_id is empty
+    #[oai(
+        path = "/user/contact/",
+        method = "get",
+        tag = "ApiUserContactTags::UserContact"
+    )]
+    async fn get_user_contact_api(
+        &self,
+        Query(user_id): Query<Option<String>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserContactListResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            // get user on db, the request user when empty
+            let user_id = match user_id {
+                Some(user_id) => match Uuid::parse_str(&user_id) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(AppError::not_found(format!(
+                            "user with id = {} not found",
+                            user_id
+                        )))
+                    }
+                },
+                None => request_user.id,
+            };
+            let (user, _) = get_user_by_id(&mut db, &user_id, None).await?;
+            if user.is_none() {
+                return Err(AppError::not_found(format!(
+                    "user with id = {} not found",
+                    user_id
+                )));
+            }
+            let is_allowed = request_user.id == user_id
+                || require_permission(
+                    &mut db,
+                    &mut redis_conn,
+                    &request_user,
+                    &format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE_READ),
+                )
+                .await?;
+            if !is_allowed {
+                return Err(AppError::forbidden(format!(
+                    "{} {} permission required",
+                    PERMISSION_NAME, PERMISSION_ATTRIBUTE_READ
+                )));
+            }
+
+            let contacts = get_user_contacts_by_user(&mut db, &user_id).await?;
+            Ok(UserContactListResponses::Ok(Json(
+                contacts.into_iter().map(user_contact_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Add an email or phone to a user, the request user when user_id is empty
+    #[oai(
+        path = "/user/contact/",
+        method = "post",
+        tag = "ApiUserContactTags::UserContact"
+    )]
+    async fn create_user_contact_api(
+        &self,
+        Query(user_id): Query<Option<String>>,
+        Json(json): Json<UserContactRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserContactCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            // get user on db, the request user when empty
+            let user_id = match user_id {
+                Some(user_id) => match Uuid::parse_str(&user_id) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(AppError::not_found(format!(
+                            "user with id = {} not found",
+                            user_id
+                        )))
+                    }
+                },
+                None => request_user.id,
+            };
+            let (user, _) = get_user_by_id(&mut tx, &user_id, None).await?;
+            if user.is_none() {
+                return Err(AppError::not_found(format!(
+                    "user with id = {} not found",
+                    user_id
+                )));
+            }
+            let can_verify = require_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                &format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE),
+            )
+            .await?;
+            let is_allowed = can_verify || request_user.id == user_id;
+            if !is_allowed {
+                return Err(AppError::forbidden(format!(
+                    "{} {} permission required",
+                    PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE
+                )));
+            }
+
+            let value = match normalize_contact(&json.kind, &json.value) {
+                Ok(val) => val,
+                Err(message) => {
+                    return Ok(UserContactCreateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(&["value"], message),
+                    )))
+                }
+            };
+            let contacts = get_user_contacts_by_user(&mut tx, &user_id).await?;
+            if is_duplicate_contact(&contacts, &json.kind, &value, None) {
+                return Err(AppError::conflict(format!(
+                    "{} {} already added",
+                    json.kind, value
+                )));
+            }
+
+            let now = utc_now();
+            let contact = UserContact {
+                id: Uuid::now_v7(),
+                user_id,
+                kind: json.kind,
+                value,
+                is_primary: json.is_primary.unwrap_or(false),
+                verified_date: match can_verify && json.is_verified.unwrap_or(false) {
+                    true => Some(now),
+                    false => None,
+                },
+                created_date: Some(now),
+                updated_date: Some(now),
+            };
+            create_user_contact(&mut tx, &contact).await?;
+
+            tx.commit().await?;
+            Ok(UserContactCreateResponses::Created(Json(
+                user_contact_response(contact),
+            )))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/user/contact/",
+        method = "put",
+        tag = "ApiUserContactTags::UserContact"
+    )]
+    async fn update_user_contact_api(
+        &self,
+        Query(id): Query<String>,
+        Json(json): Json<UserContactRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserContactUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            // get contact on db
+            let contact = match Uuid::parse_str(&id) {
+                Ok(val) => get_user_contact_by_id(&mut tx, &val).await?,
+                Err(_) => None,
+            };
+            let contact = match contact {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "user contact with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let user_id = contact.user_id;
+            let can_verify = require_permission(
+                &mut tx,
+                &mut redis_conn,
+                &request_user,
+                &format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE),
+            )
+            .await?;
+            let is_allowed = can_verify || request_user.id == user_id;
+            if !is_allowed {
+                return Err(AppError::forbidden(format!(
+                    "{} {} permission required",
+                    PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE
+                )));
+            }
+
+            let value = match normalize_contact(&json.kind, &json.value) {
+                Ok(val) => val,
+                Err(message) => {
+                    return Ok(UserContactUpdateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(&["value"], message),
+                    )))
+                }
+            };
+            let contacts = get_user_contacts_by_user(&mut tx, &user_id).await?;
+            if is_duplicate_contact(&contacts, &json.kind, &value, Some(&contact.id)) {
+                return Err(AppError::conflict(format!(
+                    "{} {} already added",
+                    json.kind, value
+                )));
+            }
+
+            let now = utc_now();
+            let is_changed = contact.kind != json.kind || contact.value != value;
+            let verified_date = match (can_verify, json.is_verified) {
+                (true, Some(true)) => contact.verified_date.or(Some(now)),
+                (true, Some(false)) => None,
+                _ if is_changed => None,
+                _ => contact.verified_date,
+            };
+            let contact = UserContact {
+                kind: json.kind,
+                value,
+                is_primary: json.is_primary.unwrap_or(contact.is_primary),
+                verified_date,
+                updated_date: Some(now),
+                ..contact
+            };
+            update_user_contact(&mut tx, &contact).await?;
+
+            tx.commit().await?;
+            Ok(UserContactUpdateResponses::Ok(Json(user_contact_response(
+                contact,
+            ))))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/user/contact/",
+        method = "delete",
+        tag = "ApiUserContactTags::UserContact"
+    )]
+    async fn delete_user_contact_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserContactDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            // get contact on db
+            let contact = match Uuid::parse_str(&id) {
+                Ok(val) => get_user_contact_by_id(&mut tx, &val).await?,
+                Err(_) => None,
+            };
+            let contact = match contact {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "user contact with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            let user_id = contact.user_id;
+            let is_allowed = request_user.id == user_id
+                || require_permission(
+                    &mut tx,
+                    &mut redis_conn,
+                    &request_user,
+                    &format!("{}.{}", PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE),
+                )
+                .await?;
+            if !is_allowed {
+                return Err(AppError::forbidden(format!(
+                    "{} {} permission required",
+                    PERMISSION_NAME, PERMISSION_ATTRIBUTE_UPDATE
+                )));
+            }
+
+            delete_user_contact(&mut tx, &contact.id).await?;
+
+            tx.commit().await?;
+            Ok(UserContactDeleteResponses::NoContent)
+        })
+        .await
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_user_contact_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let other_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "other_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When user adds two primary emails
+    let resp = cli
+        .post("/api/user/contact")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "kind": "email",
+            "value": "first@example.com",
+            "is_primary": true,
+            "is_verified": true
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let first = json.value().object();
+    first.get("is_primary").assert_bool(true);
+    // own contacts can not be marked verified
+    first.get("is_verified").assert_bool(false);
+    let first_id = first.get("id").string().to_string();
+    let resp = cli
+        .post("/api/user/contact")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "kind": "email",
+            "value": "second@example.com",
+            "is_primary": true
+        }))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::CREATED);
+
+    // Expect the second email replaces the primary
+    let resp = cli
+        .get("/api/user/contact")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(2);
+    list.get(0)
+        .object()
+        .get("value")
+        .assert_string("second@example.com");
+    list.get(0).object().get("is_primary").assert_bool(true);
+    list.get(1).object().get("is_primary").assert_bool(false);
+
+    // When invalid phone
+    let resp = cli
+        .post("/api/user/contact")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"kind": "phone", "value": "call me"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When other user without permission
+    let resp = cli
+        .get("/api/user/contact")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .query("user_id", &test_user.user.id.to_string())
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+    let resp = cli
+        .delete("/api/user/contact")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .query("id", &first_id)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When other user is granted user read
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &other_user.user.id,
+        &["user.read"],
+    )
+    .await?;
+    let resp = cli
+        .get("/api/user/contact")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .query("user_id", &test_user.user.id.to_string())
+        .send()
+        .await;
+
+    // Expect contacts readable, still not deletable
+    resp.assert_status_is_ok();
+    let resp = cli
+        .delete("/api/user/contact")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .query("id", &first_id)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When user deletes own contact
+    let resp = cli
+        .delete("/api/user/contact")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &first_id)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .delete("/api/user/contact")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &first_id)
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
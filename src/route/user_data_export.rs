@@ -0,0 +1,202 @@
+use poem_openapi::{
+    param::Query,
+    payload::{Attachment, AttachmentType, Json},
+    OpenApi, Tags,
+};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{ensure_permission, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::user_data_export::{UserDataExport, STATUS_COMPLETED},
+    repository::{
+        user::get_user_by_id,
+        user_data_export::{create_user_data_export, get_user_data_export_by_id},
+    },
+    schema::user_data_export::{
+        CreateUserDataExportResponses, UserDataExportDetailResponses,
+        UserDataExportDownloadResponses, UserDataExportResponse,
+    },
+};
+
+#[derive(Tags)]
+enum ApiUserDataExportTags {
+    UserDataExport,
+}
+
+fn user_data_export_response(export: UserDataExport) -> UserDataExportResponse {
+    UserDataExportResponse {
+        id: export.id.to_string(),
+        user_id: export.user_id.to_string(),
+        status: export.status,
+        last_error: export.last_error,
+        requested_by: export.requested_by.map(|x| x.to_string()),
+        created_date: datetime_to_string_opt(export.created_date),
+        completed_date: datetime_to_string_opt(export.completed_date),
+    }
+}
+
+pub struct ApiUserDataExport;
+
+#[OpenApi]
+impl ApiUserDataExport {
+    /// Request an export of everything stored about a user
+    ///
+    /// The document is generated in background, poll the export until status is completed
+    /// then download it.
+    #[oai(
+        path = "/user/data-export/",
+        method = "post",
+        tag = "ApiUserDataExportTags::UserDataExport"
+    )]
+    async fn create_user_data_export_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> CreateUserDataExportResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut tx, &mut redis_conn, &request_user, "user.read").await?;
+
+            let id = match Uuid::parse_str(&id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "user with id = {} not found",
+                        &id
+                    )))
+                }
+            };
+            // soft deleted users can still ask for their data
+            let (user, _) = get_user_by_id(&mut tx, &id, Some(false)).await?;
+            if user.is_none() {
+                return Err(AppError::not_found(format!(
+                    "user with id = {} not found",
+                    &id
+                )));
+            }
+
+            let now = utc_now();
+            let export = create_user_data_export(&mut tx, &id, Some(request_user.id), &now).await?;
+            tx.commit().await?;
+
+            Ok(CreateUserDataExportResponses::Accepted(Json(
+                user_data_export_response(export),
+            )))
+        })
+        .await
+    }
+
+    /// Status of a user data export
+    #[oai(
+        path = "/user/data-export/",
+        method = "get",
+        tag = "ApiUserDataExportTags::UserDataExport"
+    )]
+    async fn user_data_export_detail_api(
+        &self,
+        Query(export_id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserDataExportDetailResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
+
+            let export_id = match Uuid::parse_str(&export_id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "data export with id = {} not found",
+                        &export_id
+                    )))
+                }
+            };
+            let export = get_user_data_export_by_id(&mut db, &export_id).await?;
+            let export = match export {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "data export with id = {} not found",
+                        &export_id
+                    )))
+                }
+            };
+
+            Ok(UserDataExportDetailResponses::Ok(Json(
+                user_data_export_response(export),
+            )))
+        })
+        .await
+    }
+
+    /// Download a completed user data export as a json file
+    #[oai(
+        path = "/user/data-export/download/",
+        method = "get",
+        tag = "ApiUserDataExportTags::UserDataExport"
+    )]
+    async fn user_data_export_download_api(
+        &self,
+        Query(export_id): Query<String>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserDataExportDownloadResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
+
+            let export_id = match Uuid::parse_str(&export_id) {
+                Ok(val) => val,
+                Err(_) => {
+                    return Err(AppError::not_found(format!(
+                        "data export with id = {} not found",
+                        &export_id
+                    )))
+                }
+            };
+            let export = get_user_data_export_by_id(&mut db, &export_id).await?;
+            let export = match export {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "data export with id = {} not found",
+                        &export_id
+                    )))
+                }
+            };
+            let payload = match (export.status.as_str(), export.payload) {
+                (STATUS_COMPLETED, Some(val)) => val,
+                (status, _) => {
+                    return Err(AppError::bad_request(format!(
+                        "data export is {}, not completed",
+                        status
+                    )))
+                }
+            };
+
+            Ok(UserDataExportDownloadResponses::Ok(
+                Attachment::new(payload.into_bytes())
+                    .attachment_type(AttachmentType::Attachment)
+                    .filename(format!("user-data-{}.json", export.user_id)),
+            ))
+        })
+        .await
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use sqlx::PgPool;
+
+use crate::{
+    core::{
+        data_export::{process_pending_user_data_exports, UserDataExportDocument},
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+    },
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["user.read"];
+
+#[sqlx::test]
+async fn test_user_data_export_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When request export of unknown user
+    let resp = cli
+        .post("/api/user/data-export")
+        .query("id", &uuid::Uuid::now_v7().to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+
+    // When request export
+    let resp = cli
+        .post("/api/user/data-export")
+        .query("id", &test_user.user.id.to_string())
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect pending
+    resp.assert_status(StatusCode::ACCEPTED);
+    let json = resp.json().await;
+    let json = json.value().object();
+    json.get("status").assert_string("pending");
+    let export_id = json.get("id").string().to_string();
+    let resp = cli
+        .get("/api/user/data-export/download")
+        .query("export_id", &export_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When worker runs
+    let processed = process_pending_user_data_exports(&app_state.db, 10).await?;
+
+    // Expect completed
+    assert_eq!(processed, 1);
+    let resp = cli
+        .get("/api/user/data-export")
+        .query("export_id", &export_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("status")
+        .assert_string("completed");
+
+    // When download
+    let resp = cli
+        .get("/api/user/data-export/download")
+        .query("export_id", &export_id)
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.assert_header(
+        "content-disposition",
+        format!(
+            "attachment; filename=\"user-data-{}.json\"",
+            test_user.user.id
+        ),
+    );
+    let document: UserDataExportDocument =
+        serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+    assert_eq!(document.export_id, export_id);
+    assert_eq!(document.user.user_name, "test_user");
+    assert_eq!(
+        document.profile.and_then(|x| x.email),
+        test_user.user_profile.email
+    );
+    assert_eq!(document.data_exports.len(), 1);
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        security::{AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, utc_now},
+    },
+    model::user_device::{UserDevice, PLATFORMS},
+    repository::user_device::{
+        delete_user_device, get_user_device_by_id, get_user_devices_by_user, upsert_user_device,
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        user_device::{
+            UserDeviceDeleteResponses, UserDeviceListResponses, UserDeviceRegisterResponses,
+            UserDeviceRequest, UserDeviceResponse,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiUserDeviceTags {
+    UserDevice,
+}
+
+pub struct ApiUserDevice;
+
+/// The token is not returned, apps keep their own
+fn user_device_response(device: UserDevice) -> UserDeviceResponse {
+    UserDeviceResponse {
+        id: device.id.to_string(),
+        platform: device.platform,
+        name: device.name,
+        created_date: datetime_to_string_opt(device.created_date),
+        updated_date: datetime_to_string_opt(device.updated_date),
+    }
+}
+
+/// Return the reason the device is rejected, None when valid
+fn check_user_device(json: &UserDeviceRequest) -> Option<String> {
+    if !PLATFORMS.contains(&json.platform.as_str()) {
+        return Some(format!("platform must be one of {}", PLATFORMS.join(", ")));
+    }
+    if json.token.trim().is_empty() || json.token.len() > 4096 {
+        return Some("token must be 1 to 4096 characters".to_string());
+    }
+    if json.name.as_ref().is_some_and(|x| x.len() > 255) {
+        return Some("name must be at most 255 characters".to_string());
+    }
+    None
+}
+
+#[OpenApi]
+impl ApiUserDevice {
+    /// Devices of the request user receiving security pushes
+    #[oai(
+        path = "/auth/me/device/",
+        method = "get",
+        tag = "ApiUserDeviceTags::UserDevice"
+    )]
+    async fn get_user_devices_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserDeviceListResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                user: request_user,
+                ..
+            } = ctx;
+            let devices = get_user_devices_by_user(&mut db, &request_user.id).await?;
+            Ok(UserDeviceListResponses::Ok(Json(
+                devices.into_iter().map(user_device_response).collect(),
+            )))
+        })
+        .await
+    }
+
+    /// Register the push token of a mobile app for the request user
+    #[oai(
+        path = "/auth/me/device/",
+        method = "post",
+        tag = "ApiUserDeviceTags::UserDevice"
+    )]
+    async fn register_user_device_api(
+        &self,
+        Json(json): Json<UserDeviceRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserDeviceRegisterResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            if let Some(message) = check_user_device(&json) {
+                return Ok(UserDeviceRegisterResponses::UnprocessableEntity(Json(
+                    UnprocessableEntityResponse::body_error(&[], message),
+                )));
+            }
+
+            let now = utc_now();
+            let device = UserDevice {
+                id: Uuid::now_v7(),
+                user_id: request_user.id,
+                platform: json.platform,
+                token: json.token.trim().to_string(),
+                name: json.name.filter(|x| !x.trim().is_empty()),
+                created_date: Some(now),
+                updated_date: Some(now),
+            };
+            let device = upsert_user_device(&mut tx, &device).await?;
+
+            tx.commit().await?;
+            Ok(UserDeviceRegisterResponses::Created(Json(
+                user_device_response(device),
+            )))
+        })
+        .await
+    }
+
+    /// Stop pushes to a device of the request user, e.g. on logout from the app
+    #[oai(
+        path = "/auth/me/device/",
+        method = "delete",
+        tag = "ApiUserDeviceTags::UserDevice"
+    )]
+    async fn delete_user_device_api(
+        &self,
+        Query(id): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserDeviceDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            // get device on db, devices of others are not found
+            let device = match Uuid::parse_str(&id) {
+                Ok(val) => get_user_device_by_id(&mut tx, &val)
+                    .await?
+                    .filter(|x| x.user_id == request_user.id),
+                Err(_) => None,
+            };
+            let device = match device {
+                Some(val) => val,
+                None => {
+                    return Err(AppError::not_found(format!(
+                        "user device with id = {} not found",
+                        id
+                    )))
+                }
+            };
+            delete_user_device(&mut tx, &device.id).await?;
+
+            tx.commit().await?;
+            Ok(UserDeviceDeleteResponses::NoContent)
+        })
+        .await
+    }
+}
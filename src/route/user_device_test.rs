@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{session_store::create_redis_pool, test_utils::generate_test_user},
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_user_device_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let other_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "other_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+    let payload = json!({"platform": "fcm", "token": "fcm-token", "name": "Pixel 8"});
+
+    // When
+    let resp = cli
+        .post("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let device_id = json.value().object().get("id").string().to_string();
+
+    // When unknown platform
+    let resp = cli
+        .post("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"platform": "mqtt", "token": "abc"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When the token is registered by another user
+    let resp = cli
+        .post("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .body_json(&payload)
+        .send()
+        .await;
+
+    // Expect it moved to that user
+    resp.assert_status(StatusCode::CREATED);
+    let resp = cli
+        .get("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!([])).await;
+    let resp = cli
+        .get("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(1);
+    list.get(0).object().get("id").assert_string(&device_id);
+
+    // When deleted by a user not owning it
+    let resp = cli
+        .delete("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &device_id)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NOT_FOUND);
+
+    // When deleted by the owner
+    let resp = cli
+        .delete("/api/auth/me/device")
+        .header("authorization", format!("Bearer {}", other_user.token))
+        .query("id", &device_id)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NO_CONTENT);
+    Ok(())
+}
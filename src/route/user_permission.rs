@@ -1,12 +1,20 @@
 use std::sync::Arc;
 
-use chrono::Local;
+use chrono::Duration;
 use poem::web::Data;
 use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
 use uuid::Uuid;
 
 use crate::{
-    core::security::{get_user_from_token, BearerAuthorization},
+    core::{
+        db_error::retry_transaction,
+        error::{respond, AppError},
+        permission_cache::permissions_changed,
+        permission_grant::resolve_grant_target,
+        security::{ensure_permission, BearerAuthorization, ReadAuthContext},
+        utils::{datetime_to_string_opt, string_to_datetime, utc_now},
+        validation::Validate,
+    },
     model::user_permission::UserPermission,
     repository::{
         permission::get_permission_by_id,
@@ -18,10 +26,7 @@ use crate::{
         },
     },
     schema::{
-        common::{
-            BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-            UnauthorizedResponse,
-        },
+        common::{PaginateResponse, UnprocessableEntityResponse},
         user_permission::{
             CreateUserPermissionResponses, DeleteUserPermissionResponses,
             DetailPermissionAttributeUserPermission, DetailPermissionUserPermission,
@@ -47,171 +52,92 @@ impl ApiUserPermission {
         method = "get",
         tag = "ApiUserPermissionTags::UserPermission"
     )]
+    #[allow(clippy::too_many_arguments)]
     async fn paginate_user_permission_api(
         &self,
         Query(user_id): Query<String>,
         Query(page): Query<Option<u32>>,
         Query(page_size): Query<Option<u32>>,
         Query(all): Query<Option<bool>>,
-        state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        /// Only temporary grants expiring within this many days
+        Query(expiring_within_days): Query<Option<u32>>,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
     ) -> PaginateUserPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "paginate_user_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.read").await?;
 
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "paginate_user_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
+            // Validasi
+            let user_id = match Uuid::parse_str(&user_id) {
                 Ok(val) => val,
-                Err(err) => {
-                    return PaginateUserPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user_permission",
-                            "paginate_user_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
+                Err(_) => {
+                    return Err(AppError::bad_request(format!(
+                        "user with id = {} not found",
+                        user_id
+                    )))
                 }
             };
-        if request_user.is_none() {
-            return PaginateUserPermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-
-        // Validasi
-        let user_id = match Uuid::parse_str(&user_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return PaginateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("user with id = {} not found", user_id),
-                }))
+            let (user, _) = get_user_by_id(&mut db, &user_id, None).await?;
+            if user.is_none() {
+                return Err(AppError::bad_request(format!(
+                    "user with id = {} not found",
+                    user_id
+                )));
             }
-        };
-        let (user, _) = match get_user_by_id(&mut tx, &user_id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "paginate_user_permission_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return PaginateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("user with id = {} not found", user_id),
-            }));
-        }
-        let user = user.unwrap();
+            let user = user.unwrap();
 
-        let page = page.unwrap_or(1);
-        let page_size = page_size.unwrap_or(10);
-        let (data, counts, page_count) = match get_all_user_permission(
-            &mut tx,
-            Some(page),
-            Some(page_size),
-            &user_id,
-            all,
-        )
-        .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return PaginateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "paginate_user_permission_api",
-                        "get_all_user_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
+            let page = page.unwrap_or(1);
+            let page_size = page_size.unwrap_or(10);
+            let (data, counts, page_count) = get_all_user_permission(
+                &mut db,
+                Some(page),
+                Some(page_size),
+                &user_id,
+                expiring_within_days.map(|x| utc_now() + Duration::days(x as i64)),
+                all,
+            )
+            .await?;
 
-        let mut results: Vec<DetailUserPermissionResponse> = vec![];
-        for item in data {
-            let permission = match get_permission_by_id(&mut tx, &item.permission_id).await {
-                Ok(val) => val.unwrap(),
-                Err(err) => {
-                    return PaginateUserPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user_permission",
-                            "paginate_user_permission_api",
-                            "get_permission_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-            let attribute = match get_permission_attribute_by_id(&mut tx, &item.attribute_id).await
-            {
-                Ok(val) => val.unwrap(),
-                Err(err) => {
-                    return PaginateUserPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user_permission",
-                            "paginate_user_permission_api",
-                            "get_permission_attribute_by_id",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-            results.push(DetailUserPermissionResponse {
-                user: DetailUserUserPermission {
-                    id: user.id.to_string(),
-                    user_name: user.user_name.clone(),
-                },
-                permission: DetailPermissionUserPermission {
-                    id: permission.id.to_string(),
-                    permission_name: permission.permission_name,
-                },
-                permission_attribute: DetailPermissionAttributeUserPermission {
-                    id: attribute.id.to_string(),
-                    name: attribute.name,
+            let mut results: Vec<DetailUserPermissionResponse> = vec![];
+            for item in data {
+                let permission = get_permission_by_id(&mut db, &item.permission_id)
+                    .await?
+                    .unwrap();
+                let attribute = get_permission_attribute_by_id(&mut db, &item.attribute_id)
+                    .await?
+                    .unwrap();
+                results.push(DetailUserPermissionResponse {
+                    user: DetailUserUserPermission {
+                        id: user.id.to_string(),
+                        user_name: user.user_name.clone(),
+                    },
+                    permission: DetailPermissionUserPermission {
+                        id: permission.id.to_string(),
+                        permission_name: permission.permission_name,
+                    },
+                    permission_attribute: DetailPermissionAttributeUserPermission {
+                        id: attribute.id.to_string(),
+                        name: attribute.name,
+                    },
+                    expires_at: datetime_to_string_opt(item.expires_at),
+                });
+            }
+            Ok(PaginateUserPermissionResponses::Ok(Json(
+                PaginateResponse {
+                    counts,
+                    page,
+                    page_count,
+                    page_size,
+                    results,
                 },
-            });
-        }
-        PaginateUserPermissionResponses::Ok(Json(PaginateResponse {
-            counts,
-            page,
-            page_count,
-            page_size,
-            results,
-        }))
+            )))
+        })
+        .await
     }
 
     #[oai(
@@ -222,201 +148,93 @@ impl ApiUserPermission {
     async fn create_user_permission_api(
         &self,
         Json(json): Json<UserPermissionCreateRequest>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> CreateUserPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "create_user_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "create_user_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return CreateUserPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user_permission",
-                            "create_user_permission_api",
-                            "get user from token",
-                            &err.to_string(),
+        if let Some(errors) = json.validation_errors() {
+            return CreateUserPermissionResponses::UnprocessableEntity(Json(errors));
+        }
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            // read in the timezone of the request user
+            let expires_at = match json.expires_at.as_deref().map(string_to_datetime) {
+                Some(Err(err)) => {
+                    return Ok(CreateUserPermissionResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(
+                            &["expires_at"],
+                            format!("expires_at: {}", err),
+                        ),
+                    )))
+                }
+                Some(Ok(val)) if val <= utc_now() => {
+                    return Ok(CreateUserPermissionResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(
+                            &["expires_at"],
+                            "expires_at must be in the future".to_string(),
                         ),
-                    ))
+                    )))
                 }
+                Some(Ok(val)) => Some(val),
+                None => None,
             };
-        if request_user.is_none() {
-            return CreateUserPermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
-        let request_user = request_user.unwrap();
-
-        // Validate
-        let user_id = match Uuid::parse_str(&json.user_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("user with id {} not found", json.user_id),
-                }));
-            }
-        };
-        let (user, _) = match get_user_by_id(&mut tx, &user_id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "create_user_permission_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("user with id {} not found", json.user_id),
-            }));
-        }
+            let new_user_permision = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        let permission_id = match Uuid::parse_str(&json.permission_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission with id {} not found", json.permission_id),
-                }));
-            }
-        };
-        let permission = match get_permission_by_id(&mut tx, &permission_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "create_user_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if permission.is_none() {
-            return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("permission with id {} not found", json.permission_id),
-            }));
-        }
+                // Validate
+                let not_found =
+                    || AppError::bad_request(format!("user with id {} not found", json.user_id));
+                let user_id = Uuid::parse_str(&json.user_id).map_err(|_| not_found())?;
+                let (user, _) = get_user_by_id(&mut tx, &user_id, None).await?;
+                user.ok_or_else(not_found)?;
 
-        let attribute_id = match Uuid::parse_str(&json.attribute_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("attribute with id {} not found", json.attribute_id),
-                }));
-            }
-        };
-        let attribute = match get_permission_attribute_by_id(&mut tx, &attribute_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "create_user_permission_api",
-                        "get_permission_attribute_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if attribute.is_none() {
-            return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("attribute with id {} not found", json.attribute_id),
-            }));
-        }
-        let user_permission = match get_detail_user_permission(
-            &mut tx,
-            &user_id,
-            &permission_id,
-            &attribute_id,
-        )
+                let (permission, attribute) =
+                    resolve_grant_target(&mut *tx, &json.permission_id, &json.attribute_id)
+                        .await?;
+                let user_permission =
+                    get_detail_user_permission(&mut tx, &user_id, &permission.id, &attribute.id)
+                        .await?;
+                if user_permission.is_some() {
+                    return Err(AppError::conflict(format!(
+                        "user_permission with user_id = {}, permission_id = {}, attribute_id = {} already exists",
+                        json.user_id, json.permission_id, json.attribute_id
+                    )));
+                }
+                let now = utc_now();
+                let new_user_permision = UserPermission {
+                    user_id,
+                    permission_id: permission.id,
+                    attribute_id: attribute.id,
+                    created_by: Some(request_user.id),
+                    updated_by: Some(request_user.id),
+                    created_date: Some(now),
+                    updated_date: Some(now),
+                    expires_at,
+                };
+                create_user_permission(&mut tx, &new_user_permision).await?;
+                tx.commit().await?;
+                Ok(new_user_permision)
+            })
+            .await?;
+            // Cached permission set of the user is stale now
+            permissions_changed(&mut redis_conn, &[new_user_permision.user_id]).await;
+            Ok(CreateUserPermissionResponses::Ok(Json(
+                UserPermissionCreateResponse {
+                    user_id: new_user_permision.user_id.to_string(),
+                    permission_id: new_user_permision.permission_id.to_string(),
+                    attribute_id: new_user_permision.attribute_id.to_string(),
+                    expires_at: datetime_to_string_opt(new_user_permision.expires_at),
+                },
+            )))
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return CreateUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "create_user_permission_api",
-                        "get_detail_user_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user_permission.is_some() {
-            return CreateUserPermissionResponses::BadRequest(Json(BadRequestResponse { message: format!("user_permission with user_id = {}, permission_id = {}, attribute_id = {} already exists", json.user_id, json.permission_id, json.attribute_id)}));
-        }
-        let now = Local::now().fixed_offset();
-        let new_user_permision = UserPermission {
-            user_id,
-            permission_id,
-            attribute_id,
-            created_by: Some(request_user.id),
-            updated_by: Some(request_user.id),
-            created_date: Some(now),
-            updated_date: Some(now),
-        };
-        if let Err(err) = create_user_permission(&mut tx, &new_user_permision).await {
-            return CreateUserPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user_permission",
-                    "create_user_permission_api",
-                    "create_user_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return CreateUserPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user_permission",
-                    "create_user_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        CreateUserPermissionResponses::Ok(Json(UserPermissionCreateResponse {
-            user_id: new_user_permision.user_id.to_string(),
-            permission_id: new_user_permision.permission_id.to_string(),
-            attribute_id: new_user_permision.attribute_id.to_string(),
-        }))
     }
 
     #[oai(
@@ -429,187 +247,49 @@ impl ApiUserPermission {
         Query(user_id): Query<String>,
         Query(permission_id): Query<String>,
         Query(attribute_id): Query<String>,
+        ctx: ReadAuthContext,
         state: Data<&Arc<AppState>>,
-        auth: BearerAuthorization,
+        _auth: BearerAuthorization,
     ) -> DeleteUserPermissionResponses {
-        // Begin db transaction
-        let mut tx = match state.db.begin().await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "delete_user_permission_api",
-                        "begin transaction",
-                        &err.to_string(),
-                    ),
-                ));
-            }
-        };
-
-        // get redis conn from pool
-        let mut redis_conn = match state.redis_conn.get() {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "delete_user_permission_api",
-                        "get redis pool connection",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-
-        // Validate user token
-        let jwt_token = auth.0.token;
-        let request_user =
-            match get_user_from_token(&mut tx, &mut redis_conn, jwt_token.clone()).await {
-                Ok(val) => val,
-                Err(err) => {
-                    return DeleteUserPermissionResponses::InternalServerError(Json(
-                        InternalServerErrorResponse::new(
-                            "route.user_permission",
-                            "delete_user_permission_api",
-                            "get user from token",
-                            &err.to_string(),
-                        ),
-                    ))
-                }
-            };
-        if request_user.is_none() {
-            return DeleteUserPermissionResponses::Unauthorized(Json(
-                UnauthorizedResponse::default(),
-            ));
-        }
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                mut redis_conn,
+                user: request_user,
+            } = ctx;
+            ensure_permission(&mut db, &mut redis_conn, &request_user, "user.update").await?;
+            // the retried transaction takes a connection of its own
+            drop(db);
+            let user_id = retry_transaction(|| async {
+                let mut tx = state.db.begin().await?;
 
-        // Validate
-        let user_id = match Uuid::parse_str(&user_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("user with id {} not found", user_id),
-                }));
-            }
-        };
-        let (user, _) = match get_user_by_id(&mut tx, &user_id, None).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "delete_user_permission_api",
-                        "get_user_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user.is_none() {
-            return DeleteUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("user with id {} not found", user_id),
-            }));
-        }
-
-        let permission_id = match Uuid::parse_str(&permission_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("permission with id {} not found", permission_id),
-                }));
-            }
-        };
-        let permission = match get_permission_by_id(&mut tx, &permission_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "delete_user_permission_api",
-                        "get_permission_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if permission.is_none() {
-            return DeleteUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("permission with id {} not found", permission_id),
-            }));
-        }
+                // Validate
+                let not_found =
+                    || AppError::bad_request(format!("user with id {} not found", user_id));
+                let user_id = Uuid::parse_str(&user_id).map_err(|_| not_found())?;
+                let (user, _) = get_user_by_id(&mut tx, &user_id, None).await?;
+                user.ok_or_else(not_found)?;
 
-        let attribute_id = match Uuid::parse_str(&attribute_id) {
-            Ok(val) => val,
-            Err(_) => {
-                return DeleteUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                    message: format!("attribute with id {} not found", attribute_id),
-                }));
-            }
-        };
-        let attribute = match get_permission_attribute_by_id(&mut tx, &attribute_id).await {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "delete_user_permission_api",
-                        "get_permission_attribute_by_id",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if attribute.is_none() {
-            return DeleteUserPermissionResponses::BadRequest(Json(BadRequestResponse {
-                message: format!("attribute with id {} not found", attribute_id),
-            }));
-        }
-        let user_permission = match get_detail_user_permission(
-            &mut tx,
-            &user_id,
-            &permission_id,
-            &attribute_id,
-        )
+                let (permission, attribute) =
+                    resolve_grant_target(&mut *tx, &permission_id, &attribute_id).await?;
+                let user_permission =
+                    get_detail_user_permission(&mut tx, &user_id, &permission.id, &attribute.id)
+                        .await?
+                        .ok_or_else(|| {
+                            AppError::not_found(format!(
+                                "user_permission with user_id = {}, permission_id = {}, attribute_id = {} not exists",
+                                user_id, permission.id, attribute.id
+                            ))
+                        })?;
+                delete_user_permission(&mut tx, &user_permission).await?;
+                tx.commit().await?;
+                Ok::<_, AppError>(user_id)
+            })
+            .await?;
+            // Cached permission set of the user is stale now
+            permissions_changed(&mut redis_conn, &[user_id]).await;
+            Ok(DeleteUserPermissionResponses::NoContent)
+        })
         .await
-        {
-            Ok(val) => val,
-            Err(err) => {
-                return DeleteUserPermissionResponses::InternalServerError(Json(
-                    InternalServerErrorResponse::new(
-                        "route.user_permission",
-                        "delete_user_permission_api",
-                        "get_detail_user_permission",
-                        &err.to_string(),
-                    ),
-                ))
-            }
-        };
-        if user_permission.is_none() {
-            return DeleteUserPermissionResponses::NotFound(Json(NotFoundResponse{
-                message: format!("user_permission with user_id = {}, permission_id = {}, attribute_id = {} not exists", user_id, permission_id, attribute_id)
-            }));
-        }
-        if let Err(err) = delete_user_permission(&mut tx, &user_permission.unwrap()).await {
-            return DeleteUserPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user_permission",
-                    "delete_user_permission_api",
-                    "delete_user_permission",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        if let Err(err) = tx.commit().await {
-            return DeleteUserPermissionResponses::InternalServerError(Json(
-                InternalServerErrorResponse::new(
-                    "route.user_permission",
-                    "delete_user_permission_api",
-                    "commit transaction",
-                    &err.to_string(),
-                ),
-            ));
-        }
-        DeleteUserPermissionResponses::NoContent
     }
 }
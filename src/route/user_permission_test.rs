@@ -1,30 +1,37 @@
 use std::sync::Arc;
 
+use chrono::Duration;
 use poem::{http::StatusCode, test::TestClient};
 use serde_json::json;
 use sqlx::PgPool;
 
 use crate::{
-    core::test_utils::generate_test_user,
+    core::{
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
+        utils::{datetime_to_string, utc_now},
+    },
     factory::{permission::PermissionFactory, permission_attribute::PermissionAttributeFactory},
     init_openapi_route,
     settings::get_config,
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["user.read", "user.update"];
+
 #[sqlx::test]
 async fn user_permission_test(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -33,6 +40,13 @@ async fn user_permission_test(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let user = test_user.user;
     let mut permission_factory = PermissionFactory::new();
     let permission = permission_factory.generate_one(&app_state.db, ()).await?;
@@ -84,7 +98,8 @@ async fn user_permission_test(pool: PgPool) -> anyhow::Result<()> {
                 "permission_attribute": {
                     "id": attribute.id.to_string(),
                     "name": attribute.name
-                }
+                },
+                "expires_at": null
             }
         ]
     }))
@@ -123,3 +138,93 @@ async fn user_permission_test(pool: PgPool) -> anyhow::Result<()> {
     .await;
     Ok(())
 }
+
+#[sqlx::test]
+async fn expiring_user_permission_test(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let user = test_user.user;
+    let mut tx = app_state.db.begin().await?;
+    let read = PermissionAttributeFactory::new()
+        .name("read")
+        .create(&mut tx)
+        .await?;
+    let write = PermissionAttributeFactory::new()
+        .name("write")
+        .create(&mut tx)
+        .await?;
+    let permission = PermissionFactory::new()
+        .permission_name("invoice")
+        .with_attribute(&read)
+        .with_attribute(&write)
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let cli = TestClient::new(init_openapi_route(app_state.clone(), &config));
+    let in_two_days = datetime_to_string(utc_now() + Duration::days(2));
+    let grant = |attribute_id: String, expires_at: Option<String>| {
+        cli.post("/api/user-permissions")
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .body_json(&json!({
+                "user_id": user.id.to_string(),
+                "permission_id": permission.id.to_string(),
+                "attribute_id": attribute_id,
+                "expires_at": expires_at,
+            }))
+            .send()
+    };
+
+    // When granted with an expiry in the past
+    let resp = grant(read.id.to_string(), Some("2020-01-01 00:00:00".to_string())).await;
+
+    // Expect
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+    // When read granted for two days, write permanently
+    let resp = grant(read.id.to_string(), Some(in_two_days.clone())).await;
+    resp.assert_status(StatusCode::CREATED);
+    let resp = grant(write.id.to_string(), None).await;
+    resp.assert_status(StatusCode::CREATED);
+    let expiring = |days: u32| {
+        cli.get("/api/user-permissions")
+            .query("user_id", &user.id.to_string())
+            .query("expiring_within_days", &days)
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .send()
+    };
+
+    // Expect only read expiring within a week
+    let resp = expiring(7).await;
+    resp.assert_status_is_ok();
+    let body: serde_json::Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+    assert_eq!(body["counts"], 1);
+    assert_eq!(body["results"][0]["permission_attribute"]["name"], "read");
+    assert_eq!(body["results"][0]["expires_at"], in_two_days);
+    let resp = expiring(1).await;
+    let body: serde_json::Value = serde_json::from_str(&resp.0.into_body().into_string().await?)?;
+    assert_eq!(body["counts"], 0);
+    Ok(())
+}
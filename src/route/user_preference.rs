@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use poem_openapi::{param::Query, payload::Json, OpenApi, Tags};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        preference::check_preference,
+        security::{AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::utc_now,
+    },
+    repository::user_preference::{
+        delete_user_preference, get_user_preferences_by_user, upsert_user_preference,
+    },
+    schema::{
+        common::UnprocessableEntityResponse,
+        user_preference::{
+            UserPreferenceDeleteResponses, UserPreferencesRequest, UserPreferencesResponse,
+            UserPreferencesResponses, UserPreferencesUpdateResponses,
+        },
+    },
+};
+
+#[derive(Tags)]
+enum ApiUserPreferenceTags {
+    UserPreference,
+}
+
+pub struct ApiUserPreference;
+
+async fn user_preferences_response(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+) -> anyhow::Result<UserPreferencesResponse> {
+    let preferences: BTreeMap<String, serde_json::Value> =
+        get_user_preferences_by_user(conn, user_id)
+            .await?
+            .into_iter()
+            .map(|x| (x.key, x.value))
+            .collect();
+    Ok(UserPreferencesResponse { preferences })
+}
+
+#[OpenApi]
+impl ApiUserPreference {
+    /// Preferences of the request user
+    #[oai(
+        path = "/auth/me/preferences/",
+        method = "get",
+        tag = "ApiUserPreferenceTags::UserPreference"
+    )]
+    async fn get_user_preferences_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserPreferencesResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                user: request_user,
+                ..
+            } = ctx;
+            let res = user_preferences_response(&mut db, &request_user.id).await?;
+            Ok(UserPreferencesResponses::Ok(Json(res)))
+        })
+        .await
+    }
+
+    /// Set preferences of the request user, keys left out are kept and null removes a key
+    #[oai(
+        path = "/auth/me/preferences/",
+        method = "put",
+        tag = "ApiUserPreferenceTags::UserPreference"
+    )]
+    async fn update_user_preferences_api(
+        &self,
+        Json(json): Json<UserPreferencesRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserPreferencesUpdateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            // validate every key before storing any
+            for (key, value) in json
+                .preferences
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+            {
+                if let Some(message) = check_preference(key, value) {
+                    return Ok(UserPreferencesUpdateResponses::UnprocessableEntity(Json(
+                        UnprocessableEntityResponse::body_error(
+                            &["preferences", key.as_str()],
+                            message,
+                        ),
+                    )));
+                }
+            }
+
+            let now = utc_now();
+            for (key, value) in json.preferences.iter() {
+                let res = match value.is_null() {
+                    true => delete_user_preference(&mut tx, &request_user.id, key)
+                        .await
+                        .map(|_| ()),
+                    false => {
+                        upsert_user_preference(&mut tx, &request_user.id, key, value, &now).await
+                    }
+                };
+                res?;
+            }
+            let res = user_preferences_response(&mut tx, &request_user.id).await?;
+
+            tx.commit().await?;
+            Ok(UserPreferencesUpdateResponses::Ok(Json(res)))
+        })
+        .await
+    }
+
+    #[oai(
+        path = "/auth/me/preferences/",
+        method = "delete",
+        tag = "ApiUserPreferenceTags::UserPreference"
+    )]
+    async fn delete_user_preference_api(
+        &self,
+        Query(key): Query<String>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> UserPreferenceDeleteResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            if !delete_user_preference(&mut tx, &request_user.id, &key).await? {
+                return Err(AppError::not_found(format!("preference {} not found", key)));
+            }
+
+            tx.commit().await?;
+            Ok(UserPreferenceDeleteResponses::NoContent)
+        })
+        .await
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{session_store::create_redis_pool, test_utils::generate_test_user},
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_user_preferences_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When
+    let resp = cli
+        .put("/api/auth/me/preferences")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"preferences": {
+            "theme": "dark",
+            "myapp.sidebar": {"collapsed": true, "width": 240}
+        }}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({"preferences": {
+        "myapp.sidebar": {"collapsed": true, "width": 240},
+        "theme": "dark"
+    }}))
+    .await;
+
+    // When invalid known key or un-namespaced key
+    for preferences in [json!({"theme": "blue"}), json!({"sidebar": true})] {
+        let resp = cli
+            .put("/api/auth/me/preferences")
+            .header("authorization", format!("Bearer {}", test_user.token))
+            .body_json(&json!({ "preferences": preferences }))
+            .send()
+            .await;
+
+        // Expect
+        resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    // When merged, null removes a key
+    let resp = cli
+        .put("/api/auth/me/preferences")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"preferences": {"theme": null, "page_size": 50}}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({"preferences": {
+        "myapp.sidebar": {"collapsed": true, "width": 240},
+        "page_size": 50
+    }}))
+    .await;
+
+    // When deleted
+    let resp = cli
+        .delete("/api/auth/me/preferences")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("key", &"myapp.sidebar")
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let resp = cli
+        .get("/api/auth/me/preferences")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({"preferences": {"page_size": 50}}))
+        .await;
+    let resp = cli
+        .delete("/api/auth/me/preferences")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("key", &"myapp.sidebar")
+        .send()
+        .await;
+    resp.assert_status(StatusCode::NOT_FOUND);
+    Ok(())
+}
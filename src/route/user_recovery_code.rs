@@ -0,0 +1,88 @@
+use poem_openapi::{payload::Json, OpenApi, Tags};
+
+use crate::{
+    core::{
+        error::{respond, AppError},
+        recovery_code::generate_user_recovery_codes,
+        security::{verify_hash_password, AuthContext, BearerAuthorization, ReadAuthContext},
+        utils::utc_now,
+    },
+    repository::user_recovery_code::count_unused_user_recovery_codes,
+    schema::user_recovery_code::{
+        RecoveryCodesCreateRequest, RecoveryCodesCreateResponse, RecoveryCodesCreateResponses,
+        RecoveryCodesStatusResponse, RecoveryCodesStatusResponses,
+    },
+};
+
+#[derive(Tags)]
+enum ApiUserRecoveryCodeTags {
+    UserRecoveryCode,
+}
+
+pub struct ApiUserRecoveryCode;
+
+#[OpenApi]
+impl ApiUserRecoveryCode {
+    /// Number of unused recovery codes of the request user
+    #[oai(
+        path = "/auth/me/recovery-codes/",
+        method = "get",
+        tag = "ApiUserRecoveryCodeTags::UserRecoveryCode"
+    )]
+    async fn get_recovery_codes_api(
+        &self,
+        ctx: ReadAuthContext,
+        _auth: BearerAuthorization,
+    ) -> RecoveryCodesStatusResponses {
+        respond(async move {
+            let ReadAuthContext {
+                mut db,
+                user: request_user,
+                ..
+            } = ctx;
+            let remaining = count_unused_user_recovery_codes(&mut db, &request_user.id).await?;
+            Ok(RecoveryCodesStatusResponses::Ok(Json(
+                RecoveryCodesStatusResponse { remaining },
+            )))
+        })
+        .await
+    }
+
+    /// Issue a new set of recovery codes for the request user
+    ///
+    /// The previous codes stop working. The codes are returned only in this response.
+    #[oai(
+        path = "/auth/me/recovery-codes/",
+        method = "post",
+        tag = "ApiUserRecoveryCodeTags::UserRecoveryCode"
+    )]
+    async fn create_recovery_codes_api(
+        &self,
+        Json(json): Json<RecoveryCodesCreateRequest>,
+        ctx: AuthContext,
+        _auth: BearerAuthorization,
+    ) -> RecoveryCodesCreateResponses {
+        respond(async move {
+            let AuthContext {
+                mut tx,
+                user: request_user,
+                ..
+            } = ctx;
+            // a stolen token alone must not be enough to take over the second factor
+            let is_valid =
+                verify_hash_password(&json.password, &request_user.password).map_err(|err| {
+                    AppError::Internal(anyhow::anyhow!("verify_hash_password: {}", err))
+                })?;
+            if !is_valid {
+                return Err(AppError::bad_request("Invalid password".to_string()));
+            }
+
+            let codes = generate_user_recovery_codes(&mut tx, &request_user.id, &utc_now()).await?;
+            tx.commit().await?;
+            Ok(RecoveryCodesCreateResponses::Created(Json(
+                RecoveryCodesCreateResponse { codes },
+            )))
+        })
+        .await
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use poem::{http::StatusCode, test::TestClient};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::{
+    core::{session_store::create_redis_pool, test_utils::generate_test_user},
+    init_openapi_route,
+    model::user::TABLE_NAME as USER_TABLE_NAME,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_recovery_codes_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When wrong password
+    let resp = cli
+        .post("/api/auth/me/recovery-codes")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"password": "wrong"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When
+    let resp = cli
+        .post("/api/auth/me/recovery-codes")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({"password": "password"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::CREATED);
+    let json = resp.json().await;
+    let codes = json.value().object().get("codes").array();
+    codes.assert_len(10);
+    let code = codes.get(0).string().to_string();
+
+    // When 2FA enabled, login without a recovery code or with a wrong one
+    sqlx::query(
+        format!(
+            "UPDATE {} SET is_2faenabled = true WHERE id = $1",
+            USER_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    let missing = cli
+        .post("/api/auth/login")
+        .body_json(&json!({"user_name": "test_user", "password": "password"}))
+        .send()
+        .await;
+    let wrong = cli
+        .post("/api/auth/login")
+        .body_json(
+            &json!({"user_name": "test_user", "password": "password", "recovery_code": "abcde-fghjk"}),
+        )
+        .send()
+        .await;
+
+    // Expect the code optional, a wrong one rejected
+    missing.assert_status_is_ok();
+    wrong.assert_status(StatusCode::BAD_REQUEST);
+
+    // When login with a recovery code
+    let payload = json!({"user_name": "test_user", "password": "password", "recovery_code": code});
+    let resp = cli.post("/api/auth/login").body_json(&payload).send().await;
+
+    // Expect accepted once
+    resp.assert_status_is_ok();
+    let resp = cli.post("/api/auth/login").body_json(&payload).send().await;
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    let resp = cli
+        .get("/api/auth/me/recovery-codes")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    resp.assert_json(&json!({"remaining": 9})).await;
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use chrono::{DateTime, Duration, FixedOffset, Local};
 use poem::{http::StatusCode, test::TestClient};
 use serde_json::{
     json,
@@ -9,35 +10,41 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
+    cli::seed::{parse_seed_data, seed, DEFAULT_SEED},
     core::{
+        permission_cache::invalidate_user_permissions,
         security::verify_hash_password,
-        test_utils::generate_test_user,
+        session_store::create_redis_pool,
+        test_utils::{generate_test_user, grant_test_permissions},
         utils::{datetime_to_string, datetime_to_string_opt},
     },
     factory::{group::GroupFactory, role::RoleFactory, user::UserFactory},
     init_openapi_route,
     model::{
-        user::{User, TABLE_NAME},
+        user::{User, UserStatus, TABLE_NAME},
         user_group_roles::{UserGroupRoles, TABLE_NAME as USER_GROUP_ROLES_TABLE_NAME},
         user_profile::{UserProfile, TABLE_NAME as USER_PROFILE_TABLE_NAME},
     },
+    repository::user_name_history::get_user_name_history_by_user,
     settings::get_config,
     AppState,
 };
 
+/// Held by test_user, the request user of these tests
+const PERMISSIONS: &[&str] = &["user.create", "user.read", "user.update", "user.delete"];
+
 #[sqlx::test]
 async fn test_paginate_user_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -46,6 +53,13 @@ async fn test_paginate_user_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut user_factory = UserFactory::new();
     user_factory.generate_many(&app_state.db, 10, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -61,7 +75,7 @@ async fn test_paginate_user_api(pool: PgPool) -> anyhow::Result<()> {
     // Expect
     resp.assert_status_is_ok();
     let data: Vec<User> =
-        sqlx::query_as("SELECT * FROM public.user ORDER BY updated_date DESC LIMIT 10")
+        sqlx::query_as("SELECT * FROM public.user ORDER BY updated_date DESC, id DESC LIMIT 10")
             .fetch_all(&mut *db)
             .await?;
     resp.assert_json(&json!({
@@ -72,7 +86,9 @@ async fn test_paginate_user_api(pool: PgPool) -> anyhow::Result<()> {
         "results": data.iter().map(|x| json!({
             "id": x.id.to_string(),
             "user_name": x.user_name,
-            "is_active": x.is_active,
+            "is_active": x.is_active(),
+            "status": x.status,
+            "expires_at": datetime_to_string_opt(x.expires_at),
             "is_2faenabled": x.is_2faenabled,
             "created_date": datetime_to_string_opt(x.created_date),
             "updated_date": datetime_to_string_opt(x.updated_date),
@@ -83,19 +99,197 @@ async fn test_paginate_user_api(pool: PgPool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[sqlx::test]
+async fn test_paginate_user_api_expiring_within_days(pool: PgPool) -> anyhow::Result<()> {
+    // Given users expiring in 10 days, in 60 days and already expired
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let now = Local::now().fixed_offset();
+    let mut user_factory = UserFactory::<DateTime<FixedOffset>>::new();
+    user_factory.modified_many(|x, i, now| User {
+        deleted_date: None,
+        expires_at: Some(match i {
+            0 => now + Duration::days(10),
+            1 => now + Duration::days(60),
+            _ => now - Duration::days(1),
+        }),
+        ..x.clone()
+    });
+    let users = user_factory.generate_many(&app_state.db, 3, now).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When
+    let resp = cli
+        .get("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("expiring_within_days", &30)
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let results = json.value().object().get("results").array();
+    results.assert_len(1);
+    results
+        .get(0)
+        .object()
+        .get("id")
+        .assert_string(&users[0].id.to_string());
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_paginate_user_api_filters(pool: PgPool) -> anyhow::Result<()> {
+    // Given an inactive and an active member of a group, and a user outside it
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let mut tx = app_state.db.begin().await?;
+    let group = GroupFactory::new()
+        .group_name("sales")
+        .create(&mut tx)
+        .await?;
+    let role = RoleFactory::new()
+        .role_name("cashier")
+        .create(&mut tx)
+        .await?;
+    let inactive = UserFactory::new()
+        .user_name("inactive")
+        .active(false)
+        .with_group(&group)
+        .with_role(&role)
+        .create(&mut tx)
+        .await?;
+    UserFactory::new()
+        .user_name("active")
+        .with_group(&group)
+        .create(&mut tx)
+        .await?;
+    UserFactory::new()
+        .user_name("outsider")
+        .active(false)
+        .create(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When inactive users in the group
+    let resp = cli
+        .get("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("is_active", &false)
+        .query("group_id", &group.id.to_string())
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let results = json.value().object().get("results").array();
+    results.assert_len(1);
+    results
+        .get(0)
+        .object()
+        .get("id")
+        .assert_string(&inactive.id.to_string());
+
+    // When users with the role created from tomorrow
+    let tomorrow = datetime_to_string(Local::now().fixed_offset() + Duration::days(1));
+    let resp = cli
+        .get("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("role_id", &role.id.to_string())
+        .query("created_from", &tomorrow)
+        .send()
+        .await;
+
+    // Expect none
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("results")
+        .array()
+        .assert_len(0);
+
+    // When the group id is invalid
+    let resp = cli
+        .get("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("group_id", &"sales")
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("field")
+        .assert_string("group_id");
+    Ok(())
+}
+
 #[sqlx::test]
 async fn test_get_all_user_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -104,6 +298,13 @@ async fn test_get_all_user_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut user_factory = UserFactory::new();
     user_factory.generate_many(&app_state.db, 10, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -119,7 +320,7 @@ async fn test_get_all_user_api(pool: PgPool) -> anyhow::Result<()> {
     // Expect
     resp.assert_status_is_ok();
     let data: Vec<User> =
-        sqlx::query_as("SELECT * FROM public.user ORDER BY updated_date DESC LIMIT 10")
+        sqlx::query_as("SELECT * FROM public.user ORDER BY updated_date DESC, id DESC LIMIT 10")
             .fetch_all(&mut *db)
             .await?;
     resp.assert_json(&json!({
@@ -130,7 +331,9 @@ async fn test_get_all_user_api(pool: PgPool) -> anyhow::Result<()> {
         "results": data.iter().map(|x| json!({
             "id": x.id.to_string(),
             "user_name": x.user_name,
-            "is_active": x.is_active,
+            "is_active": x.is_active(),
+            "status": x.status,
+            "expires_at": datetime_to_string_opt(x.expires_at),
             "is_2faenabled": x.is_2faenabled,
             "created_date": datetime_to_string_opt(x.created_date),
             "updated_date": datetime_to_string_opt(x.updated_date),
@@ -146,14 +349,13 @@ async fn test_user_detail_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -162,6 +364,13 @@ async fn test_user_detail_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    let grants = grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -180,7 +389,9 @@ async fn test_user_detail_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_json(&json!({
         "id": user.id.to_string(),
         "user_name": user.user_name,
-        "is_active": user.is_active,
+        "is_active": user.is_active(),
+        "status": user.status,
+        "expires_at": datetime_to_string_opt(user.expires_at),
         "is_2faenabled": user.is_2faenabled,
         "created_by": Null,
         "updated_by": Null,
@@ -190,11 +401,46 @@ async fn test_user_detail_api(pool: PgPool) -> anyhow::Result<()> {
             "address": user_profile.address,
             "email": user_profile.email,
             "first_name": user_profile.first_name,
-            "last_name": user_profile.last_name
+            "last_name": user_profile.last_name,
+            "locale": user_profile.locale,
+            "timezone": user_profile.timezone,
+            "email_verified_at": Null
         },
-        "group_roles": []
+        "group_roles": [{
+            "group": Null,
+            "role": {"id": grants.id.to_string(), "role_name": grants.role_name}
+        }],
+        "previous_user_names": []
     }))
     .await;
+
+    // When the request user has a profile timezone
+    sqlx::query("UPDATE public.user_profile SET timezone = 'UTC' WHERE user_id = $1")
+        .bind(user.id)
+        .execute(&app_state.db)
+        .await?;
+    let resp = cli
+        .get("/api/user/detail")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.id.to_string())
+        .send()
+        .await;
+
+    // Expect timestamps formatted in it
+    resp.assert_status_is_ok();
+    resp.json()
+        .await
+        .value()
+        .object()
+        .get("created_date")
+        .assert_string(
+            &user
+                .created_date
+                .unwrap()
+                .naive_utc()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        );
     Ok(())
 }
 
@@ -203,14 +449,13 @@ async fn test_create_user_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let mut role_factory = RoleFactory::new();
     let role = role_factory.generate_one(&app_state.db, ()).await?;
     let mut group_factory = GroupFactory::new();
@@ -223,6 +468,13 @@ async fn test_create_user_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let app = init_openapi_route(app_state.clone(), &config);
     let cli = TestClient::new(app);
 
@@ -263,7 +515,7 @@ async fn test_create_user_api(pool: PgPool) -> anyhow::Result<()> {
     assert!(new_user.is_some());
     let new_user = new_user.unwrap();
     assert_eq!(new_user.user_name, "user_name".to_string());
-    assert_eq!(new_user.is_active, Some(true));
+    assert!(new_user.is_active());
     assert!(verify_hash_password("password", &new_user.password).unwrap());
     // user profile
     let new_user_profile: Option<UserProfile> = sqlx::query_as(
@@ -295,6 +547,41 @@ async fn test_create_user_api(pool: PgPool) -> anyhow::Result<()> {
     assert_eq!(user_group_roles.len(), 1);
     assert_eq!(user_group_roles[0].role_id, Some(role.id));
     assert_eq!(user_group_roles[0].group_id, Some(group.id));
+
+    // When invalid fields
+    let resp = cli
+        .post("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .body_json(&json!({
+            "email": "not-an-email",
+            "is_active": true,
+            "password": "password",
+            "user_name": "user name",
+            "group_roles": [{"group_id": group.id.to_string(), "role_id": "1"}]
+        }))
+        .send()
+        .await;
+
+    // Expect every invalid field reported
+    resp.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    resp.assert_json(&json!({
+        "code": "VALIDATION_FAILED",
+        "detail": [
+            {
+                "loc": ["body", "user_name"],
+                "msg": "user_name may only contain letters, digits and . _ - + @",
+            },
+            {
+                "loc": ["body", "email"],
+                "msg": "not-an-email is not a valid email address",
+            },
+            {
+                "loc": ["body", "group_roles", "0", "role_id"],
+                "msg": "1 is not a valid uuid",
+            },
+        ]
+    }))
+    .await;
     Ok(())
 }
 
@@ -303,14 +590,13 @@ async fn test_user_update_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -319,6 +605,13 @@ async fn test_user_update_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let user =
         generate_test_user(&mut db, &mut redis_conn, config.clone(), "user", "password").await?;
     let mut role_factory = RoleFactory::new();
@@ -338,6 +631,9 @@ async fn test_user_update_api(pool: PgPool) -> anyhow::Result<()> {
             "last_name": "last",
             "email": "email@local.com",
             "is_active": true,
+            "expires_at": "2099-12-31 23:59:59",
+            "locale": "id_id",
+            "timezone": "Asia/Makassar",
             "password": "password",
             "user_name": "user_name",
             "address": Null,
@@ -361,11 +657,20 @@ async fn test_user_update_api(pool: PgPool) -> anyhow::Result<()> {
         )
         .as_str(),
     )
-    .bind(&user.user.id)
+    .bind(user.user.id)
     .fetch_one(&mut *db)
     .await?;
     assert_eq!(user.user_name, "user_name".to_string());
-    assert_eq!(user.is_active, Some(true));
+    assert!(user.is_active());
+    let mut tx = app_state.db.begin().await?;
+    let user_name_history = get_user_name_history_by_user(&mut tx, &user.id).await?;
+    tx.rollback().await?;
+    assert_eq!(user_name_history.len(), 1);
+    assert_eq!(user_name_history[0].user_name, "user");
+    assert_eq!(
+        datetime_to_string_opt(user.expires_at),
+        Some("2099-12-31 23:59:59".to_string())
+    );
     let user_profile: UserProfile = sqlx::query_as(
         format!(
             r#"SELECT * FROM {}
@@ -374,12 +679,14 @@ async fn test_user_update_api(pool: PgPool) -> anyhow::Result<()> {
         )
         .as_str(),
     )
-    .bind(&user.id)
+    .bind(user.id)
     .fetch_one(&mut *db)
     .await?;
     assert_eq!(user_profile.first_name, Some("first".to_string()));
     assert_eq!(user_profile.last_name, Some("last".to_string()));
     assert_eq!(user_profile.email, Some("email@local.com".to_string()));
+    assert_eq!(user_profile.locale, Some("id-ID".to_string()));
+    assert_eq!(user_profile.timezone, Some("Asia/Makassar".to_string()));
     // user_group_roles
     let user_group_roles: Vec<UserGroupRoles> = sqlx::query_as(
         format!(
@@ -388,12 +695,28 @@ async fn test_user_update_api(pool: PgPool) -> anyhow::Result<()> {
         )
         .as_str(),
     )
-    .bind(&user.id)
+    .bind(user.id)
     .fetch_all(&mut *db)
     .await?;
     assert_eq!(user_group_roles.len(), 1);
     assert_eq!(user_group_roles[0].role_id, Some(role.id));
     assert_eq!(user_group_roles[0].group_id, Some(group.id));
+
+    // When another user takes the previous user name
+    let resp = cli
+        .put("/api/user")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &test_user.user.id.to_string())
+        .body_json(&json!({
+            "is_active": true,
+            "password": "password",
+            "user_name": "user",
+        }))
+        .send()
+        .await;
+
+    // Expect reserved
+    resp.assert_status(StatusCode::CONFLICT);
     Ok(())
 }
 
@@ -402,14 +725,13 @@ async fn test_user_delete_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -418,6 +740,13 @@ async fn test_user_delete_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let mut user_factory = UserFactory::new();
     let user = user_factory.generate_one(&app_state.db, ()).await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -435,7 +764,7 @@ async fn test_user_delete_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status(StatusCode::NO_CONTENT);
     let user: User =
         sqlx::query_as(format!(r#"SELECT * FROM {} WHERE id = $1"#, TABLE_NAME).as_str())
-            .bind(&user.id)
+            .bind(user.id)
             .fetch_one(&mut *db)
             .await?;
     assert!(user.deleted_date.is_some());
@@ -443,19 +772,132 @@ async fn test_user_delete_api(pool: PgPool) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[sqlx::test]
+async fn test_user_anonymize_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    let user =
+        generate_test_user(&mut db, &mut redis_conn, config.clone(), "user", "password").await?;
+    sqlx::query(
+        format!(
+            "UPDATE {} SET first_name = 'Jane', email = 'jane@example.com' WHERE user_id = $1",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user.user.id)
+    .execute(&mut *db)
+    .await?;
+    seed(&app_state.db, &parse_seed_data(DEFAULT_SEED)?).await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When request user lacks the permission
+    let resp = cli
+        .post("/api/user/anonymize")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When request user is admin
+    sqlx::query(
+        format!(
+            r#"INSERT INTO {} (id, user_id, role_id, group_id)
+            SELECT $1, $2, id, NULL FROM public.role WHERE role_name = 'admin'"#,
+            USER_GROUP_ROLES_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(Uuid::now_v7())
+    .bind(test_user.user.id)
+    .execute(&mut *db)
+    .await?;
+    invalidate_user_permissions(&mut redis_conn, &config, &[test_user.user.id]).await?;
+    let resp = cli
+        .post("/api/user/anonymize")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::NO_CONTENT);
+    let anonymized: User =
+        sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
+            .bind(user.user.id)
+            .fetch_one(&mut *db)
+            .await?;
+    assert_eq!(
+        anonymized.user_name,
+        format!("anonymized-{}", user.user.id.simple())
+    );
+    assert!(!anonymized.is_active());
+    assert!(anonymized.deleted_date.is_none());
+    assert!(!verify_hash_password("password", &anonymized.password).unwrap());
+    let user_profile: UserProfile = sqlx::query_as(
+        format!(
+            "SELECT * FROM {} WHERE user_id = $1",
+            USER_PROFILE_TABLE_NAME
+        )
+        .as_str(),
+    )
+    .bind(user.user.id)
+    .fetch_one(&mut *db)
+    .await?;
+    assert!(user_profile.first_name.is_none());
+    assert!(user_profile.email.is_none());
+    let log: Option<(Option<Uuid>,)> =
+        sqlx::query_as("SELECT anonymized_by FROM public.user_anonymization WHERE user_id = $1")
+            .bind(user.user.id)
+            .fetch_optional(&mut *db)
+            .await?;
+    assert_eq!(log, Some((Some(test_user.user.id),)));
+
+    // When anonymized twice
+    let resp = cli
+        .post("/api/user/anonymize")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
 #[sqlx::test]
 async fn test_user_reset_password_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -464,6 +906,13 @@ async fn test_user_reset_password_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let user =
         generate_test_user(&mut db, &mut redis_conn, config.clone(), "user", "password").await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -485,7 +934,7 @@ async fn test_user_reset_password_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status_is_ok();
     let user: Option<User> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(&user.user.id)
+            .bind(user.user.id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(user.is_some());
@@ -500,14 +949,13 @@ async fn test_user_change_status_api(pool: PgPool) -> anyhow::Result<()> {
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -516,6 +964,13 @@ async fn test_user_change_status_api(pool: PgPool) -> anyhow::Result<()> {
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let user =
         generate_test_user(&mut db, &mut redis_conn, config.clone(), "user", "password").await?;
     let app = init_openapi_route(app_state.clone(), &config);
@@ -536,12 +991,126 @@ async fn test_user_change_status_api(pool: PgPool) -> anyhow::Result<()> {
     resp.assert_status(StatusCode::NO_CONTENT);
     let user: Option<User> =
         sqlx::query_as(format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME).as_str())
-            .bind(&user.user.id)
+            .bind(user.user.id)
             .fetch_optional(&mut *db)
             .await?;
     assert!(user.is_some());
     let user = user.unwrap();
-    assert_eq!(user.is_active, Some(false));
+    assert!(!user.is_active());
+    assert_eq!(user.status, UserStatus::Suspended);
+    Ok(())
+}
+
+#[sqlx::test]
+async fn test_user_lifecycle_status_api(pool: PgPool) -> anyhow::Result<()> {
+    // Given
+    let mut config = get_config();
+    config.prefix = Some("/api".to_string());
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let mut db = app_state.db.acquire().await?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
+    let test_user = generate_test_user(
+        &mut db,
+        &mut redis_conn,
+        config.clone(),
+        "test_user",
+        "password",
+    )
+    .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
+    let user =
+        generate_test_user(&mut db, &mut redis_conn, config.clone(), "user", "password").await?;
+    let app = init_openapi_route(app_state.clone(), &config);
+    let cli = TestClient::new(app);
+
+    // When suspend
+    let resp = cli
+        .put("/api/user/lifecycle-status")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .body_json(&json!({"status": "suspended", "reason": "policy violation"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let history = json.value().object();
+    history
+        .get("from_status")
+        .assert_string(UserStatus::Active.as_str());
+    history
+        .get("to_status")
+        .assert_string(UserStatus::Suspended.as_str());
+    history
+        .get("changed_by")
+        .assert_string(&test_user.user.id.to_string());
+    let resp = cli
+        .post("/api/auth/login")
+        .body_json(&json!({"user_name": "user", "password": "password"}))
+        .send()
+        .await;
+    resp.assert_status(StatusCode::FORBIDDEN);
+
+    // When transition not allowed
+    let resp = cli
+        .put("/api/user/lifecycle-status")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .body_json(&json!({"status": "pending"}))
+        .send()
+        .await;
+
+    // Expect
+    resp.assert_status(StatusCode::BAD_REQUEST);
+
+    // When deprovision then reactivate
+    let resp = cli
+        .put("/api/user/lifecycle-status")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .body_json(&json!({"status": "deprovisioned"}))
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let resp = cli
+        .put("/api/user/lifecycle-status")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .body_json(&json!({"status": "active"}))
+        .send()
+        .await;
+
+    // Expect deprovisioned is terminal and history kept in order
+    resp.assert_status(StatusCode::BAD_REQUEST);
+    let resp = cli
+        .get("/api/user/lifecycle-status/history")
+        .header("authorization", format!("Bearer {}", test_user.token))
+        .query("id", &user.user.id.to_string())
+        .send()
+        .await;
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let list = json.value().array();
+    list.assert_len(2);
+    list.get(0)
+        .object()
+        .get("reason")
+        .assert_string("policy violation");
+    list.get(1)
+        .object()
+        .get("to_status")
+        .assert_string(UserStatus::Deprovisioned.as_str());
     Ok(())
 }
 
@@ -552,14 +1121,13 @@ async fn test_add_user_group_role_api_and_delete_user_group_role_api(
     // Given
     let mut config = get_config();
     config.prefix = Some("/api".to_string());
-    let client = redis::Client::open(config.redis_url.clone()).unwrap();
-    let redis_pool = r2d2::Pool::builder().build(client).unwrap();
+    let redis_pool = create_redis_pool(&config).unwrap();
     let app_state = Arc::new(AppState {
         db: pool,
-        redis_conn: redis_pool,
+        redis_conn: redis_pool.into(),
     });
     let mut db = app_state.db.acquire().await?;
-    let mut redis_conn = app_state.redis_conn.get()?;
+    let mut redis_conn = app_state.redis_conn.get().await?;
     let test_user = generate_test_user(
         &mut db,
         &mut redis_conn,
@@ -568,6 +1136,13 @@ async fn test_add_user_group_role_api_and_delete_user_group_role_api(
         "password",
     )
     .await?;
+    grant_test_permissions(
+        &app_state.db,
+        &mut redis_conn,
+        &test_user.user.id,
+        PERMISSIONS,
+    )
+    .await?;
     let user =
         generate_test_user(&mut db, &mut redis_conn, config.clone(), "user", "password").await?;
     let mut role_factory = RoleFactory::new();
@@ -598,9 +1173,9 @@ async fn test_add_user_group_role_api_and_delete_user_group_role_api(
         )
         .as_str(),
     )
-    .bind(&user.user.id)
-    .bind(&role.id)
-    .bind(&group.id)
+    .bind(user.user.id)
+    .bind(role.id)
+    .bind(group.id)
     .fetch_optional(&mut *db)
     .await?;
     assert!(user_group_roles.is_some());
@@ -624,9 +1199,9 @@ async fn test_add_user_group_role_api_and_delete_user_group_role_api(
         )
         .as_str(),
     )
-    .bind(&user.user.id)
-    .bind(&role.id)
-    .bind(&group.id)
+    .bind(user.user.id)
+    .bind(role.id)
+    .bind(group.id)
     .fetch_optional(&mut *db)
     .await?;
     assert!(user_group_roles.is_none());
@@ -0,0 +1,29 @@
+use poem_openapi::{payload::Json, OpenApi, Tags};
+
+use crate::{
+    core::build_info::{build_timestamp, enabled_features, GIT_COMMIT, VERSION},
+    schema::version::{VersionResponse, VersionResponses},
+};
+
+#[derive(Tags)]
+enum ApiVersionInfoTags {
+    Version,
+}
+
+pub struct ApiVersionInfo;
+
+#[OpenApi]
+impl ApiVersionInfo {
+    /// Build info of the running service
+    ///
+    /// Crate version, git commit, build time and enabled cargo features, no token required.
+    #[oai(path = "/version", method = "get", tag = "ApiVersionInfoTags::Version")]
+    async fn version_api(&self) -> VersionResponses {
+        VersionResponses::Ok(Json(VersionResponse {
+            version: VERSION.to_string(),
+            git_commit: GIT_COMMIT.to_string(),
+            build_timestamp: build_timestamp(),
+            features: enabled_features(),
+        }))
+    }
+}
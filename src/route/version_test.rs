@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use poem::test::TestClient;
+use sqlx::PgPool;
+
+use crate::{
+    core::{build_info::VERSION, session_store::create_redis_pool},
+    init_openapi_route,
+    settings::get_config,
+    AppState,
+};
+
+#[sqlx::test]
+async fn test_version_api(pool: PgPool) {
+    // Given
+    let config = get_config();
+    let redis_pool = create_redis_pool(&config).unwrap();
+    let app_state = Arc::new(AppState {
+        db: pool,
+        redis_conn: redis_pool.into(),
+    });
+    let cli = TestClient::new(init_openapi_route(app_state, &config));
+
+    // When
+    let resp = cli.get("/version").send().await;
+
+    // Expect
+    resp.assert_status_is_ok();
+    let json = resp.json().await;
+    let value = json.value().object();
+    value.get("version").assert_string(VERSION);
+    assert!(!value.get("git_commit").string().is_empty());
+    assert!(!value.get("build_timestamp").string().is_empty());
+    value.get("features").array();
+}
@@ -1,26 +1,72 @@
-use poem_openapi::{payload::Json, ApiResponse, Object};
-use serde::Deserialize;
+use poem_openapi::{
+    payload::{Form, Json},
+    types::Example,
+    ApiRequest, ApiResponse, Object,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::schema::common::{BadRequestResponse, InternalServerErrorResponse};
+use crate::{
+    core::validation::{Validate, Validator},
+    impl_from_app_error,
+    schema::common::{BadRequestResponse, ForbiddenResponse, InternalServerErrorResponse},
+};
 
-use super::common::UnauthorizedResponse;
+use super::common::{UnauthorizedResponse, UnprocessableEntityResponse};
 
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct LoginRequest {
     pub user_name: String,
     pub password: String,
+    /// Ids of the latest terms versions accepted with this login
+    pub accept_terms: Option<Vec<String>>,
+    /// One time recovery code of users with 2FA enabled, when the authenticator is
+    /// unavailable. Optional, checked when sent and used up only by a login that returns
+    /// tokens, ignored for users without 2FA
+    pub recovery_code: Option<String>,
 }
 
+impl Example for LoginRequest {
+    fn example() -> Self {
+        Self {
+            user_name: "admin".to_string(),
+            password: "secret".to_string(),
+            accept_terms: None,
+            recovery_code: None,
+        }
+    }
+}
+
+/// Access token pair, send `token` as bearer and renew it with `refresh_token`
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct LoginResponse {
+    /// Access token expiry, RFC 3339
     pub exp: String,
+    /// Seconds until the access token expires
     pub exp_in: i32,
+    /// Refresh token expiry, RFC 3339
     pub exp_refresh_token: String,
     pub refresh_token: String,
+    /// JWT access token
     pub token: String,
+    /// Always Bearer
     pub token_type: String,
 }
 
+impl Example for LoginResponse {
+    fn example() -> Self {
+        Self {
+            exp: "2025-04-01T12:00:00+07:00".to_string(),
+            exp_in: 14400,
+            exp_refresh_token: "2025-04-01T18:00:00+07:00".to_string(),
+            refresh_token: "eyJhbGciOiJIUzI1NiJ9.refresh.signature".to_string(),
+            token: "eyJhbGciOiJIUzI1NiJ9.access.signature".to_string(),
+            token_type: "Bearer".to_string(),
+        }
+    }
+}
+
 #[derive(ApiResponse)]
 pub enum LoginResponses {
     #[oai(status = 200)]
@@ -29,25 +75,68 @@ pub enum LoginResponses {
     #[oai(status = 400)]
     BadRequet(Json<BadRequestResponse>),
 
+    /// Latest terms not accepted while TERMS_ACCEPTANCE_REQUIRED is enabled,
+    /// or the user is locked after too many failed logins
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(LoginResponses { Forbidden });
+
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct RefreshTokenRequest {
+    /// refresh_token from the login or previous refresh response
     pub refresh_token: String,
 }
 
+impl Example for RefreshTokenRequest {
+    fn example() -> Self {
+        Self {
+            refresh_token: "eyJhbGciOiJIUzI1NiJ9.refresh.signature".to_string(),
+        }
+    }
+}
+
+/// New token pair, the previous pair is revoked
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct RefreshTokenResponse {
+    /// Access token expiry, RFC 3339
     pub exp: String,
+    /// Seconds until the access token expires
     pub exp_in: i32,
+    /// Refresh token expiry, RFC 3339
     pub exp_refresh_token: String,
     pub refresh_token: String,
+    /// JWT access token
     pub token: String,
+    /// Always Bearer
     pub token_type: String,
 }
 
+impl From<LoginResponse> for RefreshTokenResponse {
+    fn from(value: LoginResponse) -> Self {
+        Self {
+            exp: value.exp,
+            exp_in: value.exp_in,
+            exp_refresh_token: value.exp_refresh_token,
+            refresh_token: value.refresh_token,
+            token: value.token,
+            token_type: value.token_type,
+        }
+    }
+}
+
+impl Example for RefreshTokenResponse {
+    fn example() -> Self {
+        LoginResponse::example().into()
+    }
+}
+
 #[derive(ApiResponse)]
 pub enum RefreshTokenResponses {
     #[oai(status = 200)]
@@ -59,10 +148,19 @@ pub enum RefreshTokenResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    /// Latest terms not accepted while TERMS_ACCEPTANCE_REQUIRED is enabled
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(RefreshTokenResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(ApiResponse)]
 pub enum LogoutResponses {
     #[oai(status = 204)]
@@ -74,3 +172,302 @@ pub enum LogoutResponses {
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(LogoutResponses { Unauthorized });
+
+#[derive(Object, Deserialize)]
+#[oai(example)]
+pub struct IntrospectRequest {
+    /// Access token to check
+    pub token: String,
+}
+
+impl Example for IntrospectRequest {
+    fn example() -> Self {
+        Self {
+            token: "eyJhbGciOiJIUzI1NiJ9.access.signature".to_string(),
+        }
+    }
+}
+
+/// RFC 7662 style token state, only `active` is set for revoked, expired or unknown tokens
+#[derive(Object, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[oai(example)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    /// User id
+    pub sub: Option<String>,
+    pub user_name: Option<String>,
+    /// Access token expiry, unix timestamp
+    pub exp: Option<i64>,
+    /// Only set on tokens minted by `cli issue-token` and the client credentials grant
+    pub scopes: Option<Vec<String>>,
+}
+
+impl IntrospectResponse {
+    pub fn inactive() -> Self {
+        Self {
+            active: false,
+            sub: None,
+            user_name: None,
+            exp: None,
+            scopes: None,
+        }
+    }
+}
+
+impl Example for IntrospectResponse {
+    fn example() -> Self {
+        Self {
+            active: true,
+            sub: Some("0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b".to_string()),
+            user_name: Some("admin".to_string()),
+            exp: Some(1743483600),
+            scopes: None,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum IntrospectResponses {
+    #[oai(status = 200)]
+    Ok(Json<IntrospectResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(IntrospectResponses {});
+
+/// RFC 6749 token request, only the client_credentials grant is supported. client_id is
+/// the id of a service account and client_secret its api key
+#[derive(Object, Deserialize)]
+#[oai(example)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space separated subset of the scopes of the service account, all of them when empty
+    pub scope: Option<String>,
+}
+
+impl Example for TokenRequest {
+    fn example() -> Self {
+        Self {
+            grant_type: "client_credentials".to_string(),
+            client_id: "0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b".to_string(),
+            client_secret: "sa_6bQ0m3b9mX5t1kR8yP2wV7cN4hJ0sD1fG3aL5eU9iZc".to_string(),
+            scope: Some("user.read".to_string()),
+        }
+    }
+}
+
+/// Form encoded as OAuth2 clients send it, or json
+#[derive(ApiRequest)]
+pub enum TokenRequestBody {
+    Form(Form<TokenRequest>),
+    Json(Json<TokenRequest>),
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    /// Always Bearer
+    pub token_type: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    /// Space separated scopes carried by the access token
+    pub scope: String,
+}
+
+/// RFC 6749 error, `error` is one of invalid_request, invalid_client,
+/// unsupported_grant_type or invalid_scope
+#[derive(Object, Deserialize, Serialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    pub error_description: Option<String>,
+}
+
+impl OAuthErrorResponse {
+    pub fn new(error: &str, error_description: impl Into<String>) -> Self {
+        Self {
+            error: error.to_string(),
+            error_description: Some(error_description.into()),
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum TokenResponses {
+    #[oai(status = 200)]
+    Ok(Json<TokenResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<OAuthErrorResponse>),
+
+    /// Unknown client, wrong secret, expired or deleted service account
+    #[oai(status = 401)]
+    Unauthorized(Json<OAuthErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(TokenResponses {});
+
+/// Upper bound of pairs checked in one request
+pub const PERMISSION_CHECK_MAX_ITEMS: usize = 100;
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionCheckItem {
+    pub permission_name: String,
+    /// Permission attribute name e.g. read
+    pub attribute: String,
+}
+
+#[derive(Object, Deserialize)]
+#[oai(example)]
+pub struct PermissionCheckRequest {
+    pub permissions: Vec<PermissionCheckItem>,
+}
+
+impl Example for PermissionCheckRequest {
+    fn example() -> Self {
+        Self {
+            permissions: vec![
+                PermissionCheckItem {
+                    permission_name: "user".to_string(),
+                    attribute: "read".to_string(),
+                },
+                PermissionCheckItem {
+                    permission_name: "role".to_string(),
+                    attribute: "create".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+impl Validate for PermissionCheckRequest {
+    fn validate(&self, v: &mut Validator) {
+        if self.permissions.is_empty() {
+            v.add_error("permissions", "permissions is required".to_string());
+        }
+        if self.permissions.len() > PERMISSION_CHECK_MAX_ITEMS {
+            v.add_error(
+                "permissions",
+                format!(
+                    "permissions must have at most {} items",
+                    PERMISSION_CHECK_MAX_ITEMS
+                ),
+            );
+        }
+        v.each("permissions", &self.permissions, |v, x| {
+            v.required("permission_name", &x.permission_name)
+                .required("attribute", &x.attribute);
+        });
+    }
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionCheckResult {
+    pub permission_name: String,
+    pub attribute: String,
+    pub allowed: bool,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionCheckResponse {
+    /// Same order as the request
+    pub results: Vec<PermissionCheckResult>,
+}
+
+#[derive(ApiResponse)]
+pub enum PermissionCheckResponses {
+    #[oai(status = 200)]
+    Ok(Json<PermissionCheckResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PermissionCheckResponses { Unauthorized });
+
+#[derive(Object, Deserialize)]
+#[oai(example)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+    pub confirm_new_password: String,
+}
+
+impl Example for ChangePasswordRequest {
+    fn example() -> Self {
+        Self {
+            current_password: "secret".to_string(),
+            new_password: "n3w-secret".to_string(),
+            confirm_new_password: "n3w-secret".to_string(),
+        }
+    }
+}
+
+impl Validate for ChangePasswordRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("current_password", &self.current_password)
+            .password("new_password", &self.new_password);
+        if self.new_password == self.current_password {
+            v.add_error(
+                "new_password",
+                "new_password must differ from current_password".to_string(),
+            );
+        }
+        if self.confirm_new_password != self.new_password {
+            v.add_error(
+                "confirm_new_password",
+                "new_password and confirm_new_password must be same".to_string(),
+            );
+        }
+    }
+}
+
+#[derive(Object, Deserialize)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
+
+#[derive(ApiResponse)]
+pub enum ChangePasswordResponses {
+    /// Password changed, every other session of the user is signed out
+    #[oai(status = 200)]
+    Ok(Json<ChangePasswordResponse>),
+
+    /// Wrong current password
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    /// Locked after too many wrong passwords, counted together with failed logins
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    /// New password does not meet the password policy
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ChangePasswordResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
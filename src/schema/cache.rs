@@ -0,0 +1,95 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    core::validation::{Validate, Validator},
+    impl_from_app_error,
+};
+
+use super::common::{
+    BadRequestResponse, ForbiddenResponse, InternalServerErrorResponse, NotFoundResponse,
+    UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+/// Leave user_ids empty to rebuild the cache of every active user
+#[derive(Object, Deserialize, Serialize)]
+pub struct CacheRebuildRequest {
+    pub user_ids: Option<Vec<String>>,
+}
+
+impl Validate for CacheRebuildRequest {
+    fn validate(&self, v: &mut Validator) {
+        for user_id in self.user_ids.iter().flatten() {
+            if Uuid::parse_str(user_id.trim()).is_err() {
+                v.add_error("user_ids", format!("{} is not a valid uuid", user_id));
+            }
+        }
+    }
+}
+
+/// Rebuild progress, poll it until status is done or failed
+#[derive(Object, Deserialize, Serialize)]
+pub struct CacheRebuildResponse {
+    pub id: String,
+    /// running, done or failed
+    pub status: String,
+    pub total: u64,
+    pub done: u64,
+    /// users whose permissions could not be cached, they are loaded lazily instead
+    pub failed: u64,
+    pub error: Option<String>,
+    pub created_date: String,
+    pub finished_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum CacheRebuildResponses {
+    #[oai(status = 202)]
+    Accepted(Json<CacheRebuildResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(CacheRebuildResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(ApiResponse)]
+pub enum CacheRebuildDetailResponses {
+    #[oai(status = 200)]
+    Ok(Json<CacheRebuildResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(CacheRebuildDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
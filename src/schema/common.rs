@@ -1,12 +1,22 @@
 use poem_openapi::{
-    types::{ParseFromJSON, ToJSON},
-    Object,
+    types::{Example, ParseFromJSON, ToJSON},
+    Enum, Object,
 };
 
+use crate::core::{
+    error_code::error_code_of,
+    request_id::{current_request_id, debug_errors},
+    sanitize::mask_sensitive,
+};
+
+/// One page of results
 #[derive(Object, Debug)]
 pub struct PaginateResponse<T: ToJSON + ParseFromJSON> {
+    /// Total number of rows matching the query
     pub counts: u32,
+    /// Current page, starts from 1
     pub page: u32,
+    /// Number of pages
     pub page_count: u32,
     pub page_size: u32,
     pub results: Vec<T>,
@@ -17,45 +27,231 @@ pub struct OkResponse {
     pub message: String,
 }
 
+/// Machine readable reason of an error response, stable across message rewording
+/// and translation. Messages are mapped to codes by core::error_code.
+#[derive(Enum, Clone, Copy, Debug, PartialEq, Eq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    ValidationFailed,
+    InternalError,
+    InvalidCredentials,
+    TokenExpired,
+    AccountInactive,
+    AccountNotProvisioned,
+    AccountExpired,
+    AccountLocked,
+    PermissionRequired,
+    TermsNotAccepted,
+    PasswordMismatch,
+    EmailUnchanged,
+    InvalidEmail,
+    UnsupportedLocale,
+    UserNotFound,
+    UserAlreadyAnonymized,
+    UserNameConflict,
+    RoleNotFound,
+    RoleConflict,
+    GroupNotFound,
+    GroupConflict,
+    PermissionNotFound,
+    PermissionConflict,
+    AttributeNotFound,
+    AttributeInUse,
+    UserGroupRoleNotFound,
+    UserGroupRoleConflict,
+    RolePermissionConflict,
+    GroupPermissionConflict,
+    UserPermissionConflict,
+    ConsentTypeNotFound,
+    ConsentTypeConflict,
+    TermsVersionNotFound,
+    TermsVersionConflict,
+    DataExportNotFound,
+    DirectorySourceNotFound,
+    SsoProviderNotFound,
+    JitRuleNotFound,
+    RoleMappingNotFound,
+    ScimTargetNotFound,
+    ScimEventNotFound,
+    NotificationTemplateNotFound,
+    NotificationTemplateConflict,
+    UserContactNotFound,
+    UserContactConflict,
+    UserDeviceNotFound,
+    PreferenceNotFound,
+    DuplicateValue,
+    ReferenceNotFound,
+}
+
+/// Request rejected, message tells why
 #[derive(Object, Debug)]
+#[oai(example)]
 pub struct BadRequestResponse {
+    pub code: ErrorCode,
     pub message: String,
+    /// Request field the error is about, when known
+    #[oai(skip_serializing_if_is_none)]
+    pub field: Option<String>,
+}
+
+impl Example for BadRequestResponse {
+    fn example() -> Self {
+        Self::new("Invalid credentials".to_string())
+    }
+}
+
+impl BadRequestResponse {
+    pub fn new(message: String) -> Self {
+        Self {
+            code: error_code_of(&message, ErrorCode::BadRequest),
+            message,
+            field: None,
+        }
+    }
+
+    pub fn with_field(mut self, field: String) -> Self {
+        self.field = Some(field);
+        self
+    }
 }
 
+/// Missing, invalid or expired bearer token
 #[derive(Object, Debug)]
+#[oai(example)]
 pub struct UnauthorizedResponse {
+    pub code: ErrorCode,
     pub message: String,
 }
 
+impl Example for UnauthorizedResponse {
+    fn example() -> Self {
+        Self::default()
+    }
+}
+
 impl Default for UnauthorizedResponse {
     fn default() -> Self {
+        Self::new("unauthorized".to_string())
+    }
+}
+
+impl UnauthorizedResponse {
+    pub fn new(message: String) -> Self {
         Self {
-            message: "unauthorized".to_string(),
+            code: error_code_of(&message, ErrorCode::Unauthorized),
+            message,
         }
     }
 }
 
+/// Authenticated user lacks the required permission
 #[derive(Object, Debug)]
+#[oai(example)]
 pub struct ForbiddenResponse {
+    pub code: ErrorCode,
     pub message: String,
 }
 
+impl Example for ForbiddenResponse {
+    fn example() -> Self {
+        Self::new("forbidden".to_string())
+    }
+}
+
+impl ForbiddenResponse {
+    pub fn new(message: String) -> Self {
+        Self {
+            code: error_code_of(&message, ErrorCode::Forbidden),
+            message,
+        }
+    }
+}
+
 #[derive(Object, Debug)]
+#[oai(example)]
 pub struct NotFoundResponse {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl Example for NotFoundResponse {
+    fn example() -> Self {
+        Self::new("user not found".to_string())
+    }
+}
+
+impl NotFoundResponse {
+    pub fn new(message: String) -> Self {
+        Self {
+            code: error_code_of(&message, ErrorCode::NotFound),
+            message,
+        }
+    }
+}
+
+/// Request clashes with an existing record, e.g. a duplicate name
+#[derive(Object, Debug)]
+#[oai(example)]
+pub struct ConflictResponse {
+    pub code: ErrorCode,
     pub message: String,
+    /// Request field the error is about, when known
+    #[oai(skip_serializing_if_is_none)]
+    pub field: Option<String>,
+}
+
+impl Example for ConflictResponse {
+    fn example() -> Self {
+        Self::new("user name jane.doe is taken".to_string())
+    }
+}
+
+impl ConflictResponse {
+    pub fn new(message: String) -> Self {
+        Self {
+            code: error_code_of(&message, ErrorCode::Conflict),
+            message,
+            field: None,
+        }
+    }
+
+    pub fn with_field(mut self, field: String) -> Self {
+        self.field = Some(field);
+        self
+    }
 }
 
 #[derive(Object, Debug, Clone)]
 pub struct ValidateItem {
+    /// Path of the invalid field, e.g. ["body", "user_name"]
     loc: Vec<String>,
     msg: String,
 }
 
+/// Field level validation errors
 #[derive(Object, Debug, Clone)]
+#[oai(example)]
 pub struct UnprocessableEntityResponse {
+    pub code: ErrorCode,
     pub detail: Vec<ValidateItem>,
 }
 
+impl Example for UnprocessableEntityResponse {
+    fn example() -> Self {
+        let mut res = Self::new();
+        res.add_error(
+            vec!["body".to_string(), "user_name".to_string()],
+            "user_name already used".to_string(),
+        );
+        res
+    }
+}
+
 impl Default for UnprocessableEntityResponse {
     fn default() -> Self {
         Self::new()
@@ -64,7 +260,20 @@ impl Default for UnprocessableEntityResponse {
 
 impl UnprocessableEntityResponse {
     pub fn new() -> Self {
-        Self { detail: vec![] }
+        Self {
+            code: ErrorCode::ValidationFailed,
+            detail: vec![],
+        }
+    }
+
+    /// Single error located at body.loc, at body itself when loc is empty
+    pub fn body_error(loc: &[&str], msg: String) -> Self {
+        let mut res = Self::new();
+        res.add_error(
+            ["body"].iter().chain(loc).map(|x| x.to_string()).collect(),
+            msg,
+        );
+        res
     }
 
     pub fn is_has_error(&self) -> bool {
@@ -76,20 +285,49 @@ impl UnprocessableEntityResponse {
     }
 }
 
+/// Unexpected failure. The error is logged with the request id, clients only get it as
+/// detail when debug_errors is set
 #[derive(Object, Debug)]
 pub struct InternalServerErrorResponse {
-    pub detail: String,
+    pub code: ErrorCode,
+    pub message: String,
+    /// Failing step and error, only returned with debug_errors
+    #[oai(skip_serializing_if_is_none)]
+    pub detail: Option<String>,
+    /// Id of the request, quote it when reporting the error, see core::request_id
+    #[oai(skip_serializing_if_is_none)]
+    pub request_id: Option<String>,
+    /// Logged error, never serialized
+    #[oai(skip)]
+    pub error: String,
 }
 
 impl InternalServerErrorResponse {
     pub fn new(filepath: &str, function: &str, identifier: &str, err: &str) -> Self {
         let msg = format!(
             "error: on {}::{} iden: {} error: {}",
-            filepath, function, identifier, err
+            filepath,
+            function,
+            identifier,
+            mask_sensitive(err)
         );
-        tracing::error!("{}", msg);
+        Self::logged(msg)
+    }
+
+    /// Same as new for an error whose context chain locates the failing step,
+    /// see core::error::AppError
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        Self::logged(format!("error: {}", mask_sensitive(&format!("{:#}", err))))
+    }
+
+    fn logged(error: String) -> Self {
+        tracing::error!("{}", error);
         Self {
-            detail: msg.to_string(),
+            code: ErrorCode::InternalError,
+            message: "internal server error".to_string(),
+            detail: debug_errors().then(|| error.clone()),
+            request_id: current_request_id(),
+            error,
         }
     }
 }
@@ -0,0 +1,196 @@
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ConsentTypeResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Current version of the terms, users accepted an older one must accept again
+    pub version: i32,
+    pub is_active: bool,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum ConsentTypeListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ConsentTypeResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ConsentTypeListResponses { Unauthorized });
+
+#[derive(Object, Deserialize)]
+pub struct ConsentTypeCreateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(ApiResponse)]
+pub enum ConsentTypeCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<ConsentTypeResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ConsentTypeCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+/// Set `new_version` when the terms changed, previous acceptances become outdated
+#[derive(Object, Deserialize)]
+pub struct ConsentTypeUpdateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+    pub new_version: Option<bool>,
+}
+
+#[derive(ApiResponse)]
+pub enum ConsentTypeUpdateResponses {
+    #[oai(status = 200)]
+    Ok(Json<ConsentTypeResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ConsentTypeUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+/// Current decision of the request user on one consent type
+#[derive(Object, Deserialize, Serialize)]
+pub struct UserConsentStatusResponse {
+    pub consent_type: ConsentTypeResponse,
+    /// None when the user never decided
+    pub accepted: Option<bool>,
+    pub version: Option<i32>,
+    /// Accepted an older version than the current one
+    pub outdated: bool,
+    pub decided_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum UserConsentListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<UserConsentStatusResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserConsentListResponses { Unauthorized });
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct UserConsentResponse {
+    pub id: String,
+    pub consent_type_id: String,
+    pub version: i32,
+    pub accepted: bool,
+    pub created_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum UserConsentHistoryResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<UserConsentResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserConsentHistoryResponses { Unauthorized });
+
+/// Accept (true) or withdraw (false) the current version of a consent type
+#[derive(Object, Deserialize)]
+pub struct UserConsentCreateRequest {
+    pub consent_type_id: String,
+    pub accepted: bool,
+}
+
+#[derive(ApiResponse)]
+pub enum UserConsentCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<UserConsentResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserConsentCreateResponses {
+    BadRequest,
+    Unauthorized,
+    NotFound
+});
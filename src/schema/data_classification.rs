@@ -0,0 +1,49 @@
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    BadRequestResponse, ForbiddenResponse, InternalServerErrorResponse, UnauthorizedResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ClassifiedAttributeResponse {
+    /// postgres or redis
+    pub store: String,
+    /// Table, or key pattern for redis
+    pub location: String,
+    pub attribute: String,
+    /// plaintext, hashed, encrypted or expiring
+    pub protection: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct DataClassReportResponse {
+    /// pii, sensitive or public
+    pub class: String,
+    pub attributes: Vec<ClassifiedAttributeResponse>,
+}
+
+#[derive(ApiResponse)]
+pub enum DataClassificationReportResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<DataClassReportResponse>>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DataClassificationReportResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
@@ -0,0 +1,289 @@
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct DirectorySourceDetailUser {
+    pub id: String,
+    pub user_name: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct DirectorySyncRunResponse {
+    pub id: String,
+    pub source_id: String,
+    pub status: String,
+    pub users_created: i32,
+    pub users_updated: i32,
+    pub users_deactivated: i32,
+    pub groups_created: i32,
+    pub memberships_added: i32,
+    pub memberships_removed: i32,
+    pub error: Option<String>,
+    pub started_date: Option<String>,
+    pub finished_date: Option<String>,
+}
+
+/// Directory source as returned by the api, secrets are only reported as present or not
+#[derive(Object, Deserialize, Serialize)]
+pub struct DirectorySourceResponse {
+    pub id: String,
+    pub name: String,
+    pub provider: String,
+    pub is_active: Option<bool>,
+    pub sync_interval: i32,
+    pub ldap_url: Option<String>,
+    pub ldap_bind_dn: Option<String>,
+    pub has_ldap_bind_password: bool,
+    pub ldap_base_dn: Option<String>,
+    pub ldap_user_filter: Option<String>,
+    pub ldap_group_filter: Option<String>,
+    pub azure_tenant_id: Option<String>,
+    pub azure_client_id: Option<String>,
+    pub has_azure_client_secret: bool,
+    pub azure_authority_url: Option<String>,
+    pub azure_graph_url: Option<String>,
+    pub last_sync_date: Option<String>,
+    pub created_by: Option<DirectorySourceDetailUser>,
+    pub updated_by: Option<DirectorySourceDetailUser>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateDirectorySourceResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<DirectorySourceResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateDirectorySourceResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct DirectorySourceDetailSuccessResponse {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub source: DirectorySourceResponse,
+    pub last_run: Option<DirectorySyncRunResponse>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum DirectorySourceDetailResponses {
+    #[oai(status = 200)]
+    Ok(Json<DirectorySourceDetailSuccessResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DirectorySourceDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize)]
+pub struct DirectorySourceCreateRequest {
+    pub name: String,
+    pub provider: String,
+    pub is_active: Option<bool>,
+    pub sync_interval: Option<i32>,
+    pub ldap_url: Option<String>,
+    pub ldap_bind_dn: Option<String>,
+    pub ldap_bind_password: Option<String>,
+    pub ldap_base_dn: Option<String>,
+    pub ldap_user_filter: Option<String>,
+    pub ldap_group_filter: Option<String>,
+    pub azure_tenant_id: Option<String>,
+    pub azure_client_id: Option<String>,
+    pub azure_client_secret: Option<String>,
+    pub azure_authority_url: Option<String>,
+    pub azure_graph_url: Option<String>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum DirectorySourceCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<DirectorySourceResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DirectorySourceCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+/// Omitted secrets keep their stored value
+#[derive(Object, Deserialize)]
+pub struct DirectorySourceUpdateRequest {
+    pub name: String,
+    pub provider: String,
+    pub is_active: Option<bool>,
+    pub sync_interval: Option<i32>,
+    pub ldap_url: Option<String>,
+    pub ldap_bind_dn: Option<String>,
+    pub ldap_bind_password: Option<String>,
+    pub ldap_base_dn: Option<String>,
+    pub ldap_user_filter: Option<String>,
+    pub ldap_group_filter: Option<String>,
+    pub azure_tenant_id: Option<String>,
+    pub azure_client_id: Option<String>,
+    pub azure_client_secret: Option<String>,
+    pub azure_authority_url: Option<String>,
+    pub azure_graph_url: Option<String>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum DirectorySourceUpdateResponses {
+    #[oai(status = 200)]
+    Ok(Json<DirectorySourceResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DirectorySourceUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum DirectorySourceDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DirectorySourceDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum DirectorySyncResponses {
+    #[oai(status = 200)]
+    Ok(Json<DirectorySyncRunResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DirectorySyncResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum PaginateDirectorySyncRunResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<DirectorySyncRunResponse>>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateDirectorySyncRunResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
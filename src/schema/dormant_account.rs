@@ -0,0 +1,71 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::Deserialize;
+
+use crate::impl_from_app_error;
+
+use super::common::{
+    ForbiddenResponse, InternalServerErrorResponse, NotFoundResponse, UnauthorizedResponse,
+};
+
+#[derive(Object, Deserialize)]
+pub struct DormantAccountResponse {
+    pub user_id: String,
+    pub last_login_date: Option<String>,
+    /// Date the dormant warning was sent, cleared on login
+    pub dormant_warning_date: Option<String>,
+    /// Date the user was flagged dormant, only with DORMANT_ACCOUNT_ACTION=flag
+    pub dormant_flagged_date: Option<String>,
+    pub dormant_exempt: bool,
+    pub dormant_exempt_reason: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum DormantAccountListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<DormantAccountResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DormantAccountListResponses {
+    Unauthorized,
+    Forbidden
+});
+
+/// Exempt users, typically service accounts, are never warned, flagged or suspended
+#[derive(Object, Deserialize)]
+pub struct DormantAccountExemptionRequest {
+    pub exempt: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum DormantAccountExemptionResponses {
+    #[oai(status = 200)]
+    Ok(Json<DormantAccountResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DormantAccountExemptionResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
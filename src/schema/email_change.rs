@@ -0,0 +1,87 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::validation::{Validate, Validator},
+    impl_from_app_error,
+};
+
+use super::common::{
+    BadRequestResponse, InternalServerErrorResponse, UnauthorizedResponse,
+    UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize)]
+pub struct EmailChangeRequest {
+    pub email: String,
+    /// Current password of the request user
+    pub password: String,
+}
+
+impl Validate for EmailChangeRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("email", &self.email)
+            .email("email", Some(&self.email))
+            .required("password", &self.password);
+    }
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct EmailChangeResponse {
+    /// Address on the profile, unchanged until confirmed
+    pub email: Option<String>,
+    pub pending_email: String,
+    /// Seconds the confirmation link stays valid
+    pub expires_in: u64,
+}
+
+#[derive(ApiResponse)]
+pub enum EmailChangeResponses {
+    /// Confirmation link sent to the new address
+    #[oai(status = 202)]
+    Accepted(Json<EmailChangeResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(EmailChangeResponses {
+    BadRequest,
+    Unauthorized
+});
+
+#[derive(Object, Deserialize)]
+pub struct EmailChangeConfirmRequest {
+    /// Token from the confirmation link
+    pub token: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct EmailChangeConfirmResponse {
+    pub user_id: String,
+    pub email: String,
+}
+
+#[derive(ApiResponse)]
+pub enum EmailChangeConfirmResponses {
+    #[oai(status = 200)]
+    Ok(Json<EmailChangeConfirmResponse>),
+
+    /// Unknown or expired token
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(EmailChangeConfirmResponses { BadRequest });
@@ -0,0 +1,34 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::impl_from_app_error;
+
+use super::common::{BadRequestResponse, InternalServerErrorResponse};
+
+#[derive(Object, Deserialize)]
+pub struct VerifyEmailRequest {
+    /// Token from the verification link
+    pub token: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct VerifyEmailResponse {
+    pub user_id: String,
+    pub email: String,
+    pub email_verified_at: String,
+}
+
+#[derive(ApiResponse)]
+pub enum VerifyEmailResponses {
+    #[oai(status = 200)]
+    Ok(Json<VerifyEmailResponse>),
+
+    /// Unknown or expired token, or the email changed since the link was sent
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(VerifyEmailResponses { BadRequest });
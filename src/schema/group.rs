@@ -1,9 +1,14 @@
-use poem_openapi::{payload::Json, ApiResponse, Object};
+use poem_openapi::{payload::Json, types::Example, ApiResponse, Object};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    core::validation::{Validate, Validator, DESCRIPTION_MAX_LENGTH, NAME_MAX_LENGTH},
+    impl_from_app_error,
+};
+
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize, Serialize)]
@@ -29,13 +34,26 @@ pub enum PaginateGroupResponses {
     #[oai(status = 200)]
     Ok(Json<PaginateResponse<DetailGroupPagination>>),
 
+    /// Unknown sort_by or sort_dir
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginateGroupResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct GroupAllResponse {
     pub id: String,
@@ -56,10 +74,18 @@ pub enum GroupAllResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GroupAllResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct GroupDropdownResponse {
     pub id: String,
@@ -74,10 +100,18 @@ pub enum GroupDropdownResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GroupDropdownResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct GroupDetailSuccessResponse {
     pub id: String,
@@ -101,6 +135,9 @@ pub enum GroupDetailResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
@@ -108,13 +145,44 @@ pub enum GroupDetailResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GroupDetailResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct GroupCreateRequest {
+    /// Unique group name
     pub group_name: String,
     pub description: Option<String>,
     pub is_active: Option<bool>,
 }
 
+impl Example for GroupCreateRequest {
+    fn example() -> Self {
+        Self {
+            group_name: "finance".to_string(),
+            description: Some("Finance department".to_string()),
+            is_active: Some(true),
+        }
+    }
+}
+
+impl Validate for GroupCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("group_name", &self.group_name)
+            .max_length("group_name", Some(&self.group_name), NAME_MAX_LENGTH)
+            .max_length(
+                "description",
+                self.description.as_deref(),
+                DESCRIPTION_MAX_LENGTH,
+            );
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct GroupCreateResponse {
     pub id: String,
@@ -128,20 +196,63 @@ pub enum GroupCreateResponses {
     #[oai(status = 201)]
     Ok(Json<GroupCreateResponse>),
 
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GroupCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct GroupUpdateRequest {
+    /// Unique group name
     pub group_name: String,
     pub description: Option<String>,
     pub is_active: Option<bool>,
 }
 
+impl Example for GroupUpdateRequest {
+    fn example() -> Self {
+        Self {
+            group_name: "finance".to_string(),
+            description: Some("Finance department".to_string()),
+            is_active: Some(true),
+        }
+    }
+}
+
+impl Validate for GroupUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("group_name", &self.group_name)
+            .max_length("group_name", Some(&self.group_name), NAME_MAX_LENGTH)
+            .max_length(
+                "description",
+                self.description.as_deref(),
+                DESCRIPTION_MAX_LENGTH,
+            );
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct GroupUpdateResponse {
     pub id: String,
@@ -155,16 +266,36 @@ pub enum GroupUpdateResponses {
     #[oai(status = 200)]
     Ok(Json<GroupUpdateResponse>),
 
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GroupUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum GroupDeleteResponses {
     #[oai(status = 204)]
@@ -173,9 +304,73 @@ pub enum GroupDeleteResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(GroupDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Group in the recycle bin, restore it to apply its grants again
+#[derive(Object, Deserialize, Serialize)]
+pub struct DeletedGroupResponse {
+    pub id: String,
+    pub group_name: String,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+    pub deleted_by: Option<GroupDetailUser>,
+    pub deleted_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateDeletedGroupResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<DeletedGroupResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateDeletedGroupResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(ApiResponse)]
+pub enum GroupRestoreResponses {
+    #[oai(status = 200)]
+    Ok(Json<GroupUpdateResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(GroupRestoreResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
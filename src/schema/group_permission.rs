@@ -1,9 +1,14 @@
 use poem_openapi::{payload::Json, ApiResponse, Object};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    core::validation::{Validate, Validator},
+    impl_from_app_error,
+};
+
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize, Serialize)]
@@ -42,10 +47,19 @@ pub enum PaginateGroupPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginateGroupPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct GroupPermissionCreateRequest {
     pub group_id: String,
@@ -53,6 +67,14 @@ pub struct GroupPermissionCreateRequest {
     pub attribute_id: String,
 }
 
+impl Validate for GroupPermissionCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.uuid("group_id", &self.group_id)
+            .uuid("permission_id", &self.permission_id)
+            .uuid("attribute_id", &self.attribute_id);
+    }
+}
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct GroupPermissionCreateResponse {
     pub group_id: String,
@@ -71,10 +93,26 @@ pub enum CreateGroupPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(CreateGroupPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum DeleteGroupPermissionResponses {
     #[oai(status = 204)]
@@ -86,9 +124,87 @@ pub enum DeleteGroupPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(DeleteGroupPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize)]
+pub struct GroupPermissionBulkItem {
+    pub permission_id: String,
+    pub attribute_id: String,
+}
+
+#[derive(Object, Deserialize)]
+pub struct GroupPermissionBulkCreateRequest {
+    pub group_id: String,
+    pub permissions: Vec<GroupPermissionBulkItem>,
+}
+
+impl Validate for GroupPermissionBulkCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.uuid("group_id", &self.group_id);
+        if self.permissions.is_empty() {
+            v.add_error("permissions", "permissions is required".to_string());
+        }
+        v.each("permissions", &self.permissions, |v, x| {
+            v.uuid("permission_id", &x.permission_id)
+                .uuid("attribute_id", &x.attribute_id);
+        });
+    }
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct GroupPermissionBulkItemResult {
+    pub permission_id: String,
+    pub attribute_id: String,
+    /// false when the group already had the pair, it is left as is
+    pub created: bool,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct GroupPermissionBulkCreateResponse {
+    pub group_id: String,
+    /// Same order as the request
+    pub results: Vec<GroupPermissionBulkItemResult>,
+}
+
+#[derive(ApiResponse)]
+pub enum BulkCreateGroupPermissionResponses {
+    #[oai(status = 200)]
+    Ok(Json<GroupPermissionBulkCreateResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    /// Invalid pairs are located at permissions.index, nothing is inserted
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(BulkCreateGroupPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
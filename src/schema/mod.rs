@@ -1,10 +1,28 @@
 pub mod auth;
+pub mod cache;
 pub mod common;
+pub mod consent;
+pub mod data_classification;
+pub mod directory_source;
+pub mod dormant_account;
+pub mod email_change;
+pub mod email_verification;
 pub mod group;
 pub mod group_permission;
+pub mod notification_template;
 pub mod permission;
 pub mod permission_attribute;
 pub mod role;
 pub mod role_permission;
+pub mod scim_target;
+pub mod service_account;
+pub mod sso_provider;
+pub mod terms;
 pub mod user;
+pub mod user_contact;
+pub mod user_data_export;
+pub mod user_device;
 pub mod user_permission;
+pub mod user_preference;
+pub mod user_recovery_code;
+pub mod version;
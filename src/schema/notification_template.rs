@@ -0,0 +1,185 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::impl_from_app_error;
+
+use super::common::{
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct NotificationTemplateResponse {
+    pub id: String,
+    pub event: String,
+    pub channel: String,
+    pub locale: String,
+    pub subject: Option<String>,
+    pub body: String,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct NotificationTemplateRequest {
+    /// invite, password_reset, email_change_confirm, email_change_notice, new_login,
+    /// password_changed or email_verification
+    pub event: String,
+    /// email, sms or push
+    pub channel: String,
+    /// e.g. id or id-ID, users fall back from their locale to its language then en
+    pub locale: String,
+    /// Handlebars, required for email and as the push title
+    pub subject: Option<String>,
+    /// Handlebars, e.g. Hi {{user_name}}
+    pub body: String,
+}
+
+#[derive(Object, Deserialize)]
+pub struct NotificationTemplatePreviewRequest {
+    pub event: String,
+    pub channel: String,
+    /// en when empty
+    pub locale: Option<String>,
+    /// Template variables, e.g. {"user_name": "jane.doe"}
+    pub data: serde_json::Value,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct NotificationTemplatePreviewResponse {
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(ApiResponse)]
+pub enum NotificationTemplateListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<NotificationTemplateResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(NotificationTemplateListResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(ApiResponse)]
+pub enum NotificationTemplateCreateResponses {
+    #[oai(status = 201)]
+    Created(Json<NotificationTemplateResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(NotificationTemplateCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum NotificationTemplateUpdateResponses {
+    #[oai(status = 200)]
+    Ok(Json<NotificationTemplateResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(NotificationTemplateUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum NotificationTemplateDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(NotificationTemplateDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum NotificationTemplatePreviewResponses {
+    #[oai(status = 200)]
+    Ok(Json<NotificationTemplatePreviewResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(NotificationTemplatePreviewResponses {
+    Unauthorized,
+    Forbidden
+});
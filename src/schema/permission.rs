@@ -1,9 +1,15 @@
 use poem_openapi::{payload::Json, ApiResponse, Object};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    core::validation::{Validate, Validator, DESCRIPTION_MAX_LENGTH, NAME_MAX_LENGTH},
+    impl_from_app_error,
+    model::user::UserStatus,
+};
+
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ForbiddenResponse, InternalServerErrorResponse, NotFoundResponse,
+    PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize, Serialize)]
@@ -31,13 +37,26 @@ pub enum PaginatePermissionResponses {
     #[oai(status = 200)]
     Ok(Json<PaginateResponse<DetailPermission>>),
 
+    /// Unknown sort_by or sort_dir
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginatePermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct PermissionAllResponse {
     pub id: String,
@@ -58,10 +77,18 @@ pub enum AllPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(AllPermissionResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct PermissionDropdownResponse {
     pub id: String,
@@ -76,10 +103,18 @@ pub enum DropdownPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(DropdownPermissionResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct PermissionAttributeListPermissionDetail {
     pub id: String,
@@ -111,6 +146,9 @@ pub enum PermissionDetailResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
@@ -118,6 +156,12 @@ pub enum PermissionDetailResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PermissionDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(Object, Deserialize)]
 pub struct PermissionCreateRequest {
     pub permission_name: String,
@@ -128,6 +172,29 @@ pub struct PermissionCreateRequest {
     pub permission_attribute_ids: Vec<String>,
 }
 
+impl Validate for PermissionCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("permission_name", &self.permission_name)
+            .max_length(
+                "permission_name",
+                Some(&self.permission_name),
+                NAME_MAX_LENGTH,
+            )
+            .max_length(
+                "description",
+                self.description.as_deref(),
+                DESCRIPTION_MAX_LENGTH,
+            )
+            .each(
+                "permission_attribute_ids",
+                &self.permission_attribute_ids,
+                |v, x| {
+                    v.uuid("id", x);
+                },
+            );
+    }
+}
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct PermissionCreateResponse {
     pub id: String,
@@ -149,10 +216,22 @@ pub enum PermissionCreateResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PermissionCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct PermissionUpdateRequest {
     pub permission_name: String,
@@ -163,6 +242,29 @@ pub struct PermissionUpdateRequest {
     pub permission_attribute_ids: Vec<String>,
 }
 
+impl Validate for PermissionUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("permission_name", &self.permission_name)
+            .max_length(
+                "permission_name",
+                Some(&self.permission_name),
+                NAME_MAX_LENGTH,
+            )
+            .max_length(
+                "description",
+                self.description.as_deref(),
+                DESCRIPTION_MAX_LENGTH,
+            )
+            .each(
+                "permission_attribute_ids",
+                &self.permission_attribute_ids,
+                |v, x| {
+                    v.uuid("id", x);
+                },
+            );
+    }
+}
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct PermissionUpdateResponse {
     pub id: String,
@@ -184,13 +286,26 @@ pub enum PermissionUpdateResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PermissionUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(ApiResponse)]
 pub enum PermissionDeleteResponses {
     #[oai(status = 204)]
@@ -202,9 +317,73 @@ pub enum PermissionDeleteResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PermissionDeleteResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionHolderItem {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionHolderGrant {
+    pub attribute: PermissionHolderItem,
+    /// direct, role or group
+    pub via: String,
+    /// Role or group the grant comes from, null for direct grants
+    pub source: Option<PermissionHolderItem>,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionHolderResponse {
+    pub user: DetailUserPermission,
+    pub status: UserStatus,
+    pub grants: Vec<PermissionHolderGrant>,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionHoldersResponse {
+    pub id: String,
+    pub permission_name: String,
+    pub holders: Vec<PermissionHolderResponse>,
+}
+
+#[derive(ApiResponse)]
+pub enum PermissionHoldersResponses {
+    #[oai(status = 200)]
+    Ok(Json<PermissionHoldersResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(PermissionHoldersResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
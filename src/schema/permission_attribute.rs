@@ -2,15 +2,18 @@ use poem_openapi::{payload::Json, ApiResponse, Object};
 use serde::{Deserialize, Serialize};
 
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ErrorCode, ForbiddenResponse,
+    InternalServerErrorResponse, NotFoundResponse, PaginateResponse, UnauthorizedResponse,
 };
+use crate::{core::error_code::error_code_of, impl_from_app_error};
 
 #[derive(Object, Deserialize, Serialize)]
 pub struct DetailPermissionAttribute {
     pub id: String,
     pub name: String,
     pub description: Option<String>,
+    /// e.g. billing or reporting, none when uncategorized
+    pub category: Option<String>,
 }
 
 #[derive(ApiResponse)]
@@ -21,10 +24,18 @@ pub enum PaginatePermissionAttributeResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginatePermissionAttributeResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(ApiResponse)]
 pub enum DropdownPermissionAttributeResponses {
     #[oai(status = 200)]
@@ -33,10 +44,73 @@ pub enum DropdownPermissionAttributeResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(DropdownPermissionAttributeResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct PermissionAttributeCategory {
+    /// None for attributes without a category
+    pub category: Option<String>,
+    pub counts: u32,
+}
+
+#[derive(ApiResponse)]
+pub enum PermissionAttributeCategoryResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<PermissionAttributeCategory>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PermissionAttributeCategoryResponses {
+    Unauthorized,
+    Forbidden
+});
+
+/// Attributes of one category, sorted by name
+#[derive(Object, Deserialize, Serialize)]
+pub struct GroupedPermissionAttribute {
+    /// None for attributes without a category
+    pub category: Option<String>,
+    pub attributes: Vec<DetailPermissionAttribute>,
+}
+
+#[derive(ApiResponse)]
+pub enum GroupedPermissionAttributeResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<GroupedPermissionAttribute>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GroupedPermissionAttributeResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(ApiResponse)]
 pub enum DetailPermissionAttributeResponses {
     #[oai(status = 200)]
@@ -45,6 +119,9 @@ pub enum DetailPermissionAttributeResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
@@ -52,10 +129,17 @@ pub enum DetailPermissionAttributeResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(DetailPermissionAttributeResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(Object, Deserialize)]
 pub struct CreatePermissionAttributeRequest {
     pub name: String,
     pub description: Option<String>,
+    pub category: Option<String>,
 }
 
 #[derive(ApiResponse)]
@@ -69,14 +153,24 @@ pub enum CreatePermissionAttributeResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(CreatePermissionAttributeResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct UpdatePermissionAttributeRequest {
     pub name: String,
     pub description: Option<String>,
+    pub category: Option<String>,
 }
 
 #[derive(ApiResponse)]
@@ -90,6 +184,9 @@ pub enum UpdatePermissionAttributeResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
@@ -97,6 +194,13 @@ pub enum UpdatePermissionAttributeResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(UpdatePermissionAttributeResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(ApiResponse)]
 pub enum DeletePermissionAttributeResponses {
     #[oai(status = 204)]
@@ -105,9 +209,74 @@ pub enum DeletePermissionAttributeResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<PermissionAttributeInUseResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(DeletePermissionAttributeResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Debug, Deserialize, Serialize)]
+pub struct PermissionAttributeUsage {
+    /// permission, user_permission, role_permission or group_permission
+    pub kind: String,
+    pub counts: u32,
+}
+
+/// Attribute is still granted, delete with cascade=true to remove the grants as well
+#[derive(Object, Debug)]
+pub struct PermissionAttributeInUseResponse {
+    pub code: ErrorCode,
+    pub message: String,
+    pub usages: Vec<PermissionAttributeUsage>,
+}
+
+impl PermissionAttributeInUseResponse {
+    pub fn new(message: String, usages: Vec<PermissionAttributeUsage>) -> Self {
+        Self {
+            code: error_code_of(&message, ErrorCode::Conflict),
+            message,
+            usages,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum RestorePermissionAttributeResponses {
+    #[oai(status = 200)]
+    Ok(Json<DetailPermissionAttribute>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(RestorePermissionAttributeResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
@@ -1,9 +1,14 @@
-use poem_openapi::{payload::Json, ApiResponse, Object};
+use poem_openapi::{payload::Json, types::Example, ApiResponse, Object};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    core::validation::{Validate, Validator, DESCRIPTION_MAX_LENGTH, NAME_MAX_LENGTH},
+    impl_from_app_error,
+};
+
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize, Serialize)]
@@ -29,13 +34,26 @@ pub enum PaginateRoleResponses {
     #[oai(status = 200)]
     Ok(Json<PaginateResponse<DetailRolePagination>>),
 
+    /// Unknown sort_by or sort_dir
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginateRoleResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct RoleAllResponse {
     pub id: String,
@@ -56,10 +74,18 @@ pub enum RoleAllResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(RoleAllResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct RoleDropdownResponse {
     pub id: String,
@@ -74,10 +100,18 @@ pub enum RoleDropdownResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(RoleDropdownResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct RoleDetailSuccessResponse {
     pub id: String,
@@ -101,6 +135,9 @@ pub enum RoleDetailResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
@@ -108,13 +145,44 @@ pub enum RoleDetailResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(RoleDetailResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct RoleCreateRequest {
+    /// Unique role name
     pub role_name: String,
     pub description: Option<String>,
     pub is_active: Option<bool>,
 }
 
+impl Example for RoleCreateRequest {
+    fn example() -> Self {
+        Self {
+            role_name: "operator".to_string(),
+            description: Some("Day to day user administration".to_string()),
+            is_active: Some(true),
+        }
+    }
+}
+
+impl Validate for RoleCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("role_name", &self.role_name)
+            .max_length("role_name", Some(&self.role_name), NAME_MAX_LENGTH)
+            .max_length(
+                "description",
+                self.description.as_deref(),
+                DESCRIPTION_MAX_LENGTH,
+            );
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct RoleCreateResponse {
     pub id: String,
@@ -128,20 +196,63 @@ pub enum RoleCreateResponses {
     #[oai(status = 201)]
     Ok(Json<RoleCreateResponse>),
 
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(RoleCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct RoleUpdateRequest {
+    /// Unique role name
     pub role_name: String,
     pub description: Option<String>,
     pub is_active: Option<bool>,
 }
 
+impl Example for RoleUpdateRequest {
+    fn example() -> Self {
+        Self {
+            role_name: "operator".to_string(),
+            description: Some("Day to day user administration".to_string()),
+            is_active: Some(true),
+        }
+    }
+}
+
+impl Validate for RoleUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("role_name", &self.role_name)
+            .max_length("role_name", Some(&self.role_name), NAME_MAX_LENGTH)
+            .max_length(
+                "description",
+                self.description.as_deref(),
+                DESCRIPTION_MAX_LENGTH,
+            );
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct RoleUpdateResponse {
     pub id: String,
@@ -155,16 +266,36 @@ pub enum RoleUpdateResponses {
     #[oai(status = 200)]
     Ok(Json<RoleUpdateResponse>),
 
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(RoleUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum RoleDeleteResponses {
     #[oai(status = 204)]
@@ -173,9 +304,73 @@ pub enum RoleDeleteResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(RoleDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Role in the recycle bin, restore it to apply its grants again
+#[derive(Object, Deserialize, Serialize)]
+pub struct DeletedRoleResponse {
+    pub id: String,
+    pub role_name: String,
+    pub description: Option<String>,
+    pub is_active: Option<bool>,
+    pub deleted_by: Option<RoleDetailUser>,
+    pub deleted_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateDeletedRoleResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<DeletedRoleResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateDeletedRoleResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(ApiResponse)]
+pub enum RoleRestoreResponses {
+    #[oai(status = 200)]
+    Ok(Json<RoleUpdateResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(RoleRestoreResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
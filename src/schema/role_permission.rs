@@ -1,9 +1,15 @@
-use poem_openapi::{payload::Json, ApiResponse, Object};
+use poem_openapi::{payload::Json, ApiResponse, Enum, Object};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    core::validation::{Validate, Validator},
+    impl_from_app_error,
+};
 
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize, Serialize)]
@@ -42,10 +48,19 @@ pub enum PaginateRolePermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginateRolePermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct RolePermissionCreateRequest {
     pub role_id: String,
@@ -53,6 +68,14 @@ pub struct RolePermissionCreateRequest {
     pub attribute_id: String,
 }
 
+impl Validate for RolePermissionCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.uuid("role_id", &self.role_id)
+            .uuid("permission_id", &self.permission_id)
+            .uuid("attribute_id", &self.attribute_id);
+    }
+}
+
 #[derive(Object, Deserialize, Serialize)]
 pub struct RolePermissionCreateResponse {
     pub role_id: String,
@@ -71,10 +94,26 @@ pub enum CreateRolePermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(CreateRolePermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum DeleteRolePermissionResponses {
     #[oai(status = 204)]
@@ -86,9 +125,92 @@ pub enum DeleteRolePermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(DeleteRolePermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Enum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[oai(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RolePermissionCopyMode {
+    /// Keep the grants of the target role, add the missing ones
+    #[default]
+    Merge,
+    /// Target role ends up with exactly the grants of the source role
+    Replace,
+}
+
+#[derive(Object, Deserialize)]
+pub struct RolePermissionCopyRequest {
+    pub source_role_id: String,
+    pub target_role_id: String,
+    /// merge by default
+    pub mode: Option<RolePermissionCopyMode>,
+}
+
+impl Validate for RolePermissionCopyRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.uuid("source_role_id", &self.source_role_id)
+            .uuid("target_role_id", &self.target_role_id);
+        // compared as uuids, the same id may be written in another case or without hyphens
+        let source_role_id = Uuid::parse_str(&self.source_role_id);
+        let target_role_id = Uuid::parse_str(&self.target_role_id);
+        if matches!((source_role_id, target_role_id), (Ok(x), Ok(y)) if x == y) {
+            v.add_error(
+                "target_role_id",
+                "target_role_id must differ from source_role_id".to_string(),
+            );
+        }
+    }
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct RolePermissionCopyResponse {
+    pub source_role_id: String,
+    pub target_role_id: String,
+    pub mode: RolePermissionCopyMode,
+    /// Grants added to the target role
+    pub copied: u32,
+    /// Grants of the target role removed by replace
+    pub removed: u32,
+}
+
+#[derive(ApiResponse)]
+pub enum CopyRolePermissionResponses {
+    #[oai(status = 200)]
+    Ok(Json<RolePermissionCopyResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(CopyRolePermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
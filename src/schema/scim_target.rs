@@ -0,0 +1,289 @@
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ScimTargetDetailUser {
+    pub id: String,
+    pub user_name: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct DetailScimTargetPagination {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    pub has_bearer_token: bool,
+    pub is_active: Option<bool>,
+    pub max_attempts: i32,
+    pub created_by: Option<ScimTargetDetailUser>,
+    pub updated_by: Option<ScimTargetDetailUser>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateScimTargetResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<DetailScimTargetPagination>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateScimTargetResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(Object, Deserialize, Serialize, Default)]
+pub struct ScimTargetEventSummary {
+    pub pending: u32,
+    pub success: u32,
+    pub failed: u32,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ScimTargetDetailSuccessResponse {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    pub has_bearer_token: bool,
+    pub is_active: Option<bool>,
+    pub max_attempts: i32,
+    pub event_summary: ScimTargetEventSummary,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+    pub created_by: Option<ScimTargetDetailUser>,
+    pub updated_by: Option<ScimTargetDetailUser>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum ScimTargetDetailResponses {
+    #[oai(status = 200)]
+    Ok(Json<ScimTargetDetailSuccessResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ScimTargetDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize)]
+pub struct ScimTargetCreateRequest {
+    pub name: String,
+    pub base_url: String,
+    pub bearer_token: Option<String>,
+    pub is_active: Option<bool>,
+    pub max_attempts: Option<i32>,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ScimTargetCreateResponse {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    pub has_bearer_token: bool,
+    pub is_active: Option<bool>,
+    pub max_attempts: i32,
+}
+
+#[derive(ApiResponse)]
+pub enum ScimTargetCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<ScimTargetCreateResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ScimTargetCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+#[derive(Object, Deserialize)]
+pub struct ScimTargetUpdateRequest {
+    pub name: String,
+    pub base_url: String,
+    pub bearer_token: Option<String>,
+    pub is_active: Option<bool>,
+    pub max_attempts: Option<i32>,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ScimTargetUpdateResponse {
+    pub id: String,
+    pub name: String,
+    pub base_url: String,
+    pub has_bearer_token: bool,
+    pub is_active: Option<bool>,
+    pub max_attempts: i32,
+}
+
+#[derive(ApiResponse)]
+pub enum ScimTargetUpdateResponses {
+    #[oai(status = 200)]
+    Ok(Json<ScimTargetUpdateResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ScimTargetUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum ScimTargetDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ScimTargetDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ScimProvisioningEventResponse {
+    pub id: String,
+    pub target_id: String,
+    pub user_id: String,
+    pub event_type: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_date: Option<String>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateScimProvisioningEventResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<ScimProvisioningEventResponse>>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateScimProvisioningEventResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(ApiResponse)]
+pub enum ScimProvisioningEventRetryResponses {
+    #[oai(status = 200)]
+    Ok(Json<ScimProvisioningEventResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ScimProvisioningEventRetryResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
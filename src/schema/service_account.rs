@@ -0,0 +1,208 @@
+use poem_openapi::{payload::Json, types::Example, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{
+        service_account::parse_scope,
+        validation::{Validate, Validator, DESCRIPTION_MAX_LENGTH},
+    },
+    impl_from_app_error,
+};
+
+use super::common::{
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct ServiceAccountResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Backing user the requests of the account run as
+    pub user_id: String,
+    /// First characters of the current key
+    pub key_prefix: String,
+    /// Permissions of the backing user, direct or through a role or group, written as
+    /// `name.attribute`
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateServiceAccountResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<ServiceAccountResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateServiceAccountResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(ApiResponse)]
+pub enum ServiceAccountDetailResponses {
+    #[oai(status = 200)]
+    Ok(Json<ServiceAccountResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ServiceAccountDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize)]
+#[oai(example)]
+pub struct ServiceAccountCreateRequest {
+    /// Unique name, same characters as a user name
+    pub name: String,
+    pub description: Option<String>,
+    /// Permissions granted to the account written as `name.attribute`, the request user
+    /// must hold each of them
+    pub scopes: Vec<String>,
+    /// The key stops working from this date, never when empty
+    pub expires_at: Option<String>,
+}
+
+impl Example for ServiceAccountCreateRequest {
+    fn example() -> Self {
+        Self {
+            name: "billing-sync".to_string(),
+            description: Some("Nightly user sync of the billing service".to_string()),
+            scopes: vec!["user.read".to_string()],
+            expires_at: Some("2026-01-01T00:00:00Z".to_string()),
+        }
+    }
+}
+
+impl Validate for ServiceAccountCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.user_name("name", &self.name).max_length(
+            "description",
+            self.description.as_deref(),
+            DESCRIPTION_MAX_LENGTH,
+        );
+        if self.scopes.is_empty() {
+            v.add_error("scopes", "scopes is required".to_string());
+        }
+        for scope in self.scopes.iter().filter(|x| parse_scope(x).is_none()) {
+            v.add_error(
+                "scopes",
+                format!("{} is not written as name.attribute", scope),
+            );
+        }
+    }
+}
+
+/// The key is shown once, only its hash is kept. Send it as `Authorization: Bearer <api_key>`
+#[derive(Object, Deserialize, Serialize)]
+pub struct ServiceAccountKeyResponse {
+    pub api_key: String,
+    pub service_account: ServiceAccountResponse,
+}
+
+#[derive(ApiResponse)]
+pub enum ServiceAccountCreateResponses {
+    #[oai(status = 201)]
+    Created(Json<ServiceAccountKeyResponse>),
+
+    /// Unknown scope or expires_at in the past
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    /// Missing service_account.create or one of the scopes
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ServiceAccountCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum ServiceAccountRotateKeyResponses {
+    #[oai(status = 200)]
+    Ok(Json<ServiceAccountKeyResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ServiceAccountRotateKeyResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum ServiceAccountDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(ServiceAccountDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
@@ -0,0 +1,571 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::impl_from_app_error;
+
+use super::{
+    auth::LoginResponse,
+    common::{
+        BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+        NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+    },
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct SsoProviderDetailUser {
+    pub id: String,
+    pub user_name: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct SsoProviderResponse {
+    pub id: String,
+    pub name: String,
+    pub protocol: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub jwks_url: Option<String>,
+    pub has_client_secret: bool,
+    pub authorization_url: Option<String>,
+    pub token_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    pub scopes: Option<String>,
+    pub username_claim: String,
+    pub email_claim: String,
+    pub is_active: Option<bool>,
+    pub jit_enabled: bool,
+    pub created_by: Option<SsoProviderDetailUser>,
+    pub updated_by: Option<SsoProviderDetailUser>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct SsoJitRuleResponse {
+    pub id: String,
+    pub provider_id: String,
+    pub claim: Option<String>,
+    pub value: Option<String>,
+    pub group_id: Option<String>,
+    pub role_id: Option<String>,
+    pub created_date: Option<String>,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct SsoRoleMappingResponse {
+    pub id: String,
+    pub provider_id: String,
+    pub claim: String,
+    pub value: String,
+    pub group_id: Option<String>,
+    pub role_id: Option<String>,
+    pub created_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaginateSsoProviderResponses {
+    #[oai(status = 200)]
+    Ok(Json<PaginateResponse<SsoProviderResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(PaginateSsoProviderResponses {
+    Unauthorized,
+    Forbidden
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct SsoProviderDetailSuccessResponse {
+    #[oai(flatten)]
+    #[serde(flatten)]
+    pub provider: SsoProviderResponse,
+    pub jit_rules: Vec<SsoJitRuleResponse>,
+    pub role_mappings: Vec<SsoRoleMappingResponse>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum SsoProviderDetailResponses {
+    #[oai(status = 200)]
+    Ok(Json<SsoProviderDetailSuccessResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoProviderDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Protocol oidc (default), google or github. Issuer, jwks_url and the endpoints default
+/// to the ones of Google and GitHub, only oidc providers require issuer and jwks_url.
+/// authorization_url and token_url enable the authorization code flow of /auth/sso/authorize/
+#[derive(Object, Deserialize)]
+pub struct SsoProviderCreateRequest {
+    pub name: String,
+    pub protocol: Option<String>,
+    pub issuer: Option<String>,
+    pub client_id: String,
+    pub jwks_url: Option<String>,
+    /// Kept on the provider, never returned
+    pub client_secret: Option<String>,
+    pub authorization_url: Option<String>,
+    pub token_url: Option<String>,
+    /// Read instead of an id token by github providers
+    pub userinfo_url: Option<String>,
+    /// Space separated, default openid profile email
+    pub scopes: Option<String>,
+    pub username_claim: Option<String>,
+    pub email_claim: Option<String>,
+    pub is_active: Option<bool>,
+    pub jit_enabled: Option<bool>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum SsoProviderCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<SsoProviderResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoProviderCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+/// Same fields as SsoProviderCreateRequest
+#[derive(Object, Deserialize)]
+pub struct SsoProviderUpdateRequest {
+    pub name: String,
+    pub protocol: Option<String>,
+    pub issuer: Option<String>,
+    pub client_id: String,
+    pub jwks_url: Option<String>,
+    /// Kept on the provider, never returned, the current one is kept when empty
+    pub client_secret: Option<String>,
+    pub authorization_url: Option<String>,
+    pub token_url: Option<String>,
+    /// Read instead of an id token by github providers
+    pub userinfo_url: Option<String>,
+    /// Space separated, default openid profile email
+    pub scopes: Option<String>,
+    pub username_claim: Option<String>,
+    pub email_claim: Option<String>,
+    pub is_active: Option<bool>,
+    pub jit_enabled: Option<bool>,
+}
+
+#[derive(ApiResponse)]
+#[allow(clippy::large_enum_variant)]
+pub enum SsoProviderUpdateResponses {
+    #[oai(status = 200)]
+    Ok(Json<SsoProviderResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoProviderUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum SsoProviderDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoProviderDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Rule without claim applies to every provisioned user,
+/// rule without value only requires the claim to be present
+#[derive(Object, Deserialize)]
+pub struct SsoJitRuleCreateRequest {
+    pub provider_id: String,
+    pub claim: Option<String>,
+    pub value: Option<String>,
+    pub group_id: Option<String>,
+    pub role_id: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum SsoJitRuleCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<SsoJitRuleResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoJitRuleCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum SsoJitRuleDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoJitRuleDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum SsoRoleMappingListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<SsoRoleMappingResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoRoleMappingListResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Users whose claim carries the value hold the group and/or role,
+/// it is revoked on the next sso login once the value is gone
+#[derive(Object, Deserialize)]
+pub struct SsoRoleMappingCreateRequest {
+    pub provider_id: String,
+    pub claim: String,
+    pub value: String,
+    pub group_id: Option<String>,
+    pub role_id: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum SsoRoleMappingCreateResponses {
+    #[oai(status = 201)]
+    Ok(Json<SsoRoleMappingResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoRoleMappingCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum SsoRoleMappingDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoRoleMappingDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(Object, Deserialize)]
+pub struct SsoLoginRequest {
+    /// Name of the sso provider
+    pub provider: String,
+    pub id_token: String,
+    /// Ids of the latest terms versions accepted with this login
+    pub accept_terms: Option<Vec<String>>,
+}
+
+#[derive(ApiResponse)]
+pub enum SsoLoginResponses {
+    #[oai(status = 200)]
+    Ok(Json<LoginResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    /// Latest terms not accepted while TERMS_ACCEPTANCE_REQUIRED is enabled
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoLoginResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct SsoAuthorizeResponse {
+    /// Send the user there, the provider redirects back to redirect_uri with code and state
+    pub authorization_url: String,
+    pub state: String,
+}
+
+#[derive(ApiResponse)]
+pub enum SsoAuthorizeResponses {
+    #[oai(status = 200)]
+    Ok(Json<SsoAuthorizeResponse>),
+
+    /// Unknown provider, provider without authorization code flow or invalid redirect_uri
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(SsoAuthorizeResponses {
+    BadRequest,
+    Unauthorized
+});
+
+#[derive(Object, Deserialize)]
+pub struct SsoCallbackRequest {
+    /// State and code the provider redirected back with
+    pub state: String,
+    pub code: String,
+    /// Ids of the latest terms versions accepted with this login
+    pub accept_terms: Option<Vec<String>>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct SsoIdentityLinkRequest {
+    /// State and code the provider redirected back with
+    pub state: String,
+    pub code: String,
+}
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct UserIdentityResponse {
+    pub id: String,
+    /// `sso:` followed by the sso provider name
+    pub provider: String,
+    /// Id of the user on the provider
+    pub subject: String,
+    pub created_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum UserIdentityListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<UserIdentityResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserIdentityListResponses { Unauthorized });
+
+#[derive(ApiResponse)]
+pub enum UserIdentityLinkResponses {
+    #[oai(status = 201)]
+    Created(Json<UserIdentityResponse>),
+
+    /// Unknown or expired state, or the provider rejected the code
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    /// The provider account is linked to another user
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserIdentityLinkResponses {
+    BadRequest,
+    Unauthorized,
+    Conflict
+});
+
+#[derive(ApiResponse)]
+pub enum UserIdentityDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserIdentityDeleteResponses {
+    Unauthorized,
+    NotFound
+});
@@ -0,0 +1,104 @@
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use crate::impl_from_app_error;
+
+use super::common::{
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, UnauthorizedResponse, UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct TermsVersionResponse {
+    pub id: String,
+    /// terms_of_service or privacy_policy
+    pub kind: String,
+    pub version: String,
+    pub url: Option<String>,
+    pub content: Option<String>,
+    pub published_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum TermsVersionListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<TermsVersionResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(TermsVersionListResponses { Unauthorized });
+
+#[derive(Object, Deserialize)]
+pub struct TermsVersionPublishRequest {
+    /// terms_of_service or privacy_policy
+    pub kind: String,
+    pub version: String,
+    pub url: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum TermsVersionPublishResponses {
+    #[oai(status = 201)]
+    Ok(Json<TermsVersionResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(TermsVersionPublishResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+#[derive(Object, Deserialize)]
+pub struct TermsAcceptRequest {
+    pub terms_version_id: String,
+}
+
+#[derive(ApiResponse)]
+pub enum TermsAcceptResponses {
+    /// Versions still pending for the request user
+    #[oai(status = 200)]
+    Ok(Json<Vec<TermsVersionResponse>>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(TermsAcceptResponses {
+    BadRequest,
+    Unauthorized,
+    NotFound
+});
@@ -1,9 +1,15 @@
-use poem_openapi::{payload::Json, ApiResponse, Object};
+use poem_openapi::{payload::Json, types::Example, ApiResponse, Object};
 use serde::Deserialize;
 
+use crate::{
+    core::validation::{Validate, Validator, PASSWORD_MAX_LENGTH, PROFILE_FIELD_MAX_LENGTH},
+    impl_from_app_error,
+    model::user::UserStatus,
+};
+
 use super::common::{
-    BadRequestResponse, ForbiddenResponse, InternalServerErrorResponse, NotFoundResponse,
-    PaginateResponse, UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize)]
@@ -17,6 +23,8 @@ pub struct DetailUser {
     pub id: String,
     pub user_name: String,
     pub is_active: Option<bool>,
+    pub status: UserStatus,
+    pub expires_at: Option<String>,
     pub is_2faenabled: Option<bool>,
     pub created_date: Option<String>,
     pub updated_date: Option<String>,
@@ -28,6 +36,10 @@ pub enum GetPaginateUserResponses {
     #[oai(status = 200)]
     Ok(Json<PaginateResponse<DetailUser>>),
 
+    /// Unknown sort_by or sort_dir, or an invalid filter
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
@@ -38,6 +50,12 @@ pub enum GetPaginateUserResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GetPaginateUserResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(ApiResponse)]
 pub enum GetAllUserResponses {
     #[oai(status = 200)]
@@ -46,16 +64,28 @@ pub enum GetAllUserResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(GetAllUserResponses {
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct DetailUserProfile {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub email: Option<String>,
     pub address: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    /// None while the email is not verified, see /auth/verify-email/
+    pub email_verified_at: Option<String>,
 }
 
 #[derive(Object, Deserialize)]
@@ -81,6 +111,8 @@ pub struct UserDetailResponse {
     pub id: String,
     pub user_name: String,
     pub is_active: Option<bool>,
+    pub status: UserStatus,
+    pub expires_at: Option<String>,
     pub is_2faenabled: Option<bool>,
     pub created_date: Option<String>,
     pub updated_date: Option<String>,
@@ -88,6 +120,8 @@ pub struct UserDetailResponse {
     pub created_by: Option<DetailCreatedOrUpdatedUser>,
     pub updated_by: Option<DetailCreatedOrUpdatedUser>,
     pub group_roles: Vec<DetailGroupRole>,
+    /// Newest first, kept for audit
+    pub previous_user_names: Vec<String>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -109,6 +143,13 @@ pub enum UserDetailResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(UserDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Role granted to the user inside a group, ids are uuid
 #[derive(Object, Deserialize)]
 pub struct GroupRole {
     pub group_id: String,
@@ -116,22 +157,79 @@ pub struct GroupRole {
 }
 
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct UserCreateRequest {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub email: Option<String>,
+    /// e.g. id-ID, notification emails are written in en or id
+    pub locale: Option<String>,
+    /// IANA name e.g. Asia/Jakarta, dates in notification emails
+    pub timezone: Option<String>,
     pub is_active: bool,
+    /// "%Y-%m-%d %H:%M:%S" in the timezone of the request user or RFC 3339, logins are
+    /// rejected from then
+    pub expires_at: Option<String>,
     pub password: String,
+    /// Unique login name
     pub user_name: String,
     pub address: Option<String>,
     pub group_roles: Option<Vec<GroupRole>>,
 }
 
+impl Example for UserCreateRequest {
+    fn example() -> Self {
+        Self {
+            first_name: Some("Jane".to_string()),
+            last_name: Some("Doe".to_string()),
+            email: Some("jane.doe@example.com".to_string()),
+            locale: Some("id-ID".to_string()),
+            timezone: Some("Asia/Jakarta".to_string()),
+            is_active: true,
+            expires_at: Some("2025-12-31 23:59:59".to_string()),
+            password: "S3cure-password".to_string(),
+            user_name: "jane.doe".to_string(),
+            address: None,
+            group_roles: Some(vec![GroupRole {
+                group_id: "0195f3c2-8a4b-7c1e-9d2f-3a4b5c6d7e8f".to_string(),
+                role_id: "0195f3c2-8a4b-7c1e-9d2f-3a4b5c6d7e90".to_string(),
+            }]),
+        }
+    }
+}
+
+impl Validate for UserCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.user_name("user_name", &self.user_name)
+            .required("password", &self.password)
+            .max_length("password", Some(&self.password), PASSWORD_MAX_LENGTH)
+            .max_length(
+                "first_name",
+                self.first_name.as_deref(),
+                PROFILE_FIELD_MAX_LENGTH,
+            )
+            .max_length(
+                "last_name",
+                self.last_name.as_deref(),
+                PROFILE_FIELD_MAX_LENGTH,
+            )
+            .max_length("address", self.address.as_deref(), PROFILE_FIELD_MAX_LENGTH)
+            .email("email", self.email.as_deref());
+        if let Some(group_roles) = &self.group_roles {
+            v.each("group_roles", group_roles, |v, x| {
+                v.uuid("group_id", &x.group_id).uuid("role_id", &x.role_id);
+            });
+        }
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct UserCreateResponse {
     pub id: String,
     pub user_name: String,
     pub is_active: Option<bool>,
+    pub status: UserStatus,
+    pub expires_at: Option<String>,
     pub group_roles: Vec<DetailGroupRole>,
     pub user_profile: Option<DetailUserProfile>,
 }
@@ -150,31 +248,103 @@ pub enum UserCreateResponses {
     #[oai(status = 403)]
     Forbidden(Json<ForbiddenResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(UserCreateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
+/// Replace the user, group_roles replaces every group role of the user
 #[derive(Object, Deserialize)]
+#[oai(example)]
 pub struct UserUpdateRequest {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub email: Option<String>,
+    /// e.g. id-ID, notification emails are written in en or id
+    pub locale: Option<String>,
+    /// IANA name e.g. Asia/Jakarta, dates in notification emails
+    pub timezone: Option<String>,
     pub is_active: bool,
+    /// "%Y-%m-%d %H:%M:%S" in the timezone of the request user or RFC 3339, logins are
+    /// rejected from then
+    pub expires_at: Option<String>,
     pub password: String,
+    /// Unique login name
     pub user_name: String,
     pub address: Option<String>,
     pub group_roles: Option<Vec<GroupRole>>,
 }
 
+impl Example for UserUpdateRequest {
+    fn example() -> Self {
+        let res = UserCreateRequest::example();
+        Self {
+            first_name: res.first_name,
+            last_name: res.last_name,
+            email: res.email,
+            locale: res.locale,
+            timezone: res.timezone,
+            is_active: res.is_active,
+            expires_at: res.expires_at,
+            password: res.password,
+            user_name: res.user_name,
+            address: Some("Jl. Sudirman 1, Jakarta".to_string()),
+            group_roles: res.group_roles,
+        }
+    }
+}
+
+impl Validate for UserUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.user_name("user_name", &self.user_name)
+            .required("password", &self.password)
+            .max_length("password", Some(&self.password), PASSWORD_MAX_LENGTH)
+            .max_length(
+                "first_name",
+                self.first_name.as_deref(),
+                PROFILE_FIELD_MAX_LENGTH,
+            )
+            .max_length(
+                "last_name",
+                self.last_name.as_deref(),
+                PROFILE_FIELD_MAX_LENGTH,
+            )
+            .max_length("address", self.address.as_deref(), PROFILE_FIELD_MAX_LENGTH)
+            .email("email", self.email.as_deref());
+        if let Some(group_roles) = &self.group_roles {
+            v.each("group_roles", group_roles, |v, x| {
+                v.uuid("group_id", &x.group_id).uuid("role_id", &x.role_id);
+            });
+        }
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct UserUpdateResponse {
     pub id: String,
     pub user_name: String,
     pub is_active: Option<bool>,
+    pub status: UserStatus,
+    pub expires_at: Option<String>,
+    /// New address waiting for confirmation, the profile keeps the current one until then
+    pub pending_email: Option<String>,
     pub group_roles: Vec<DetailGroupRole>,
     pub user_profile: Option<DetailUserProfile>,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(ApiResponse)]
 pub enum UserUpdateResponses {
     #[oai(status = 200)]
@@ -192,10 +362,24 @@ pub enum UserUpdateResponses {
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(UserUpdateResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum UserDeleteResponses {
     #[oai(status = 204)]
@@ -214,6 +398,64 @@ pub enum UserDeleteResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(UserDeleteResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum UserAnonymizeResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserAnonymizeResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum UserUnlockResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserUnlockResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(Object, Deserialize)]
 pub struct ResetPasswordRequest {
     pub new_password: String,
@@ -239,10 +481,19 @@ pub enum ResetPasswordResponses {
     #[oai(status = 403)]
     Forbidden(Json<ForbiddenResponse>),
 
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(ResetPasswordResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct ChangeStatusRequest {
     pub status: bool,
@@ -253,6 +504,9 @@ pub enum ChangeStatusResponses {
     #[oai(status = 204)]
     NoContent,
 
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
@@ -266,6 +520,83 @@ pub enum ChangeStatusResponses {
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(ChangeStatusResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+/// Move the user to another lifecycle status, deprovisioned is terminal
+#[derive(Object, Deserialize)]
+pub struct UserLifecycleStatusRequest {
+    pub status: UserStatus,
+    pub reason: Option<String>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct UserStatusHistoryResponse {
+    pub id: String,
+    pub from_status: UserStatus,
+    pub to_status: UserStatus,
+    pub reason: Option<String>,
+    /// Empty for automatic changes
+    pub changed_by: Option<String>,
+    pub created_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum UserLifecycleStatusResponses {
+    #[oai(status = 200)]
+    Ok(Json<UserStatusHistoryResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserLifecycleStatusResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum UserStatusHistoryResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<UserStatusHistoryResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserStatusHistoryResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
 #[derive(Object, Deserialize)]
 pub struct AddUserGroupRoleRequest {
     pub user_id: String,
@@ -273,6 +604,14 @@ pub struct AddUserGroupRoleRequest {
     pub group_id: String,
 }
 
+impl Validate for AddUserGroupRoleRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id)
+            .uuid("role_id", &self.role_id)
+            .uuid("group_id", &self.group_id);
+    }
+}
+
 #[derive(Object, Deserialize)]
 pub struct AddUserGroupRoleResponse {
     pub id: String,
@@ -295,10 +634,23 @@ pub enum AddUserGroupRoleResponses {
     #[oai(status = 403)]
     Forbidden(Json<ForbiddenResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(AddUserGroupRoleResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum DeleteUserGroupRoleResponses {
     #[oai(status = 204)]
@@ -316,3 +668,9 @@ pub enum DeleteUserGroupRoleResponses {
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(DeleteUserGroupRoleResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
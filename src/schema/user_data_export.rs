@@ -0,0 +1,101 @@
+use crate::impl_from_app_error;
+use poem_openapi::{
+    payload::{Attachment, Json},
+    ApiResponse, Object,
+};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    BadRequestResponse, ForbiddenResponse, InternalServerErrorResponse, NotFoundResponse,
+    UnauthorizedResponse,
+};
+
+/// Export job, download the document once status is completed
+#[derive(Object, Deserialize, Serialize)]
+pub struct UserDataExportResponse {
+    pub id: String,
+    pub user_id: String,
+    /// pending, completed or failed
+    pub status: String,
+    pub last_error: Option<String>,
+    pub requested_by: Option<String>,
+    pub created_date: Option<String>,
+    pub completed_date: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum CreateUserDataExportResponses {
+    #[oai(status = 202)]
+    Accepted(Json<UserDataExportResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(CreateUserDataExportResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum UserDataExportDetailResponses {
+    #[oai(status = 200)]
+    Ok(Json<UserDataExportResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserDataExportDetailResponses {
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
+
+#[derive(ApiResponse)]
+pub enum UserDataExportDownloadResponses {
+    /// Json document as attachment
+    #[oai(status = 200)]
+    Ok(Attachment<Vec<u8>>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserDataExportDownloadResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
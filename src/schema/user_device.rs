@@ -0,0 +1,78 @@
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    InternalServerErrorResponse, NotFoundResponse, UnauthorizedResponse,
+    UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct UserDeviceResponse {
+    pub id: String,
+    pub platform: String,
+    pub name: Option<String>,
+    pub created_date: Option<String>,
+    pub updated_date: Option<String>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct UserDeviceRequest {
+    /// fcm or apns
+    pub platform: String,
+    /// Push token issued to the app, registering it again moves it to the request user
+    pub token: String,
+    /// e.g. Pixel 8
+    pub name: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum UserDeviceListResponses {
+    #[oai(status = 200)]
+    Ok(Json<Vec<UserDeviceResponse>>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserDeviceListResponses { Unauthorized });
+
+#[derive(ApiResponse)]
+pub enum UserDeviceRegisterResponses {
+    #[oai(status = 201)]
+    Created(Json<UserDeviceResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserDeviceRegisterResponses { Unauthorized });
+
+#[derive(ApiResponse)]
+pub enum UserDeviceDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserDeviceDeleteResponses {
+    Unauthorized,
+    NotFound
+});
@@ -1,9 +1,14 @@
 use poem_openapi::{payload::Json, ApiResponse, Object};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    core::validation::{Validate, Validator},
+    impl_from_app_error,
+};
+
 use super::common::{
-    BadRequestResponse, InternalServerErrorResponse, NotFoundResponse, PaginateResponse,
-    UnauthorizedResponse,
+    BadRequestResponse, ConflictResponse, ForbiddenResponse, InternalServerErrorResponse,
+    NotFoundResponse, PaginateResponse, UnauthorizedResponse, UnprocessableEntityResponse,
 };
 
 #[derive(Object, Deserialize, Serialize)]
@@ -29,6 +34,8 @@ pub struct DetailUserPermissionResponse {
     pub user: DetailUserUserPermission,
     pub permission: DetailPermissionUserPermission,
     pub permission_attribute: DetailPermissionAttributeUserPermission,
+    /// None for permanent grants
+    pub expires_at: Option<String>,
 }
 
 #[derive(ApiResponse)]
@@ -42,15 +49,34 @@ pub enum PaginateUserPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(PaginateUserPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden
+});
+
 #[derive(Object, Deserialize)]
 pub struct UserPermissionCreateRequest {
     pub user_id: String,
     pub permission_id: String,
     pub attribute_id: String,
+    /// Temporary grant, e.g. 2025-12-31 23:59:59, permanent when empty
+    pub expires_at: Option<String>,
+}
+
+impl Validate for UserPermissionCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id)
+            .uuid("permission_id", &self.permission_id)
+            .uuid("attribute_id", &self.attribute_id);
+    }
 }
 
 #[derive(Object, Deserialize, Serialize)]
@@ -58,6 +84,7 @@ pub struct UserPermissionCreateResponse {
     pub user_id: String,
     pub permission_id: String,
     pub attribute_id: String,
+    pub expires_at: Option<String>,
 }
 
 #[derive(ApiResponse)]
@@ -71,10 +98,26 @@ pub enum CreateUserPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ConflictResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
 
+impl_from_app_error!(CreateUserPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    Conflict
+});
+
 #[derive(ApiResponse)]
 pub enum DeleteUserPermissionResponses {
     #[oai(status = 204)]
@@ -86,9 +129,19 @@ pub enum DeleteUserPermissionResponses {
     #[oai(status = 401)]
     Unauthorized(Json<UnauthorizedResponse>),
 
+    #[oai(status = 403)]
+    Forbidden(Json<ForbiddenResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<NotFoundResponse>),
 
     #[oai(status = 500)]
     InternalServerError(Json<InternalServerErrorResponse>),
 }
+
+impl_from_app_error!(DeleteUserPermissionResponses {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound
+});
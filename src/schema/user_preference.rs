@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{
+    InternalServerErrorResponse, NotFoundResponse, UnauthorizedResponse,
+    UnprocessableEntityResponse,
+};
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct UserPreferencesResponse {
+    pub preferences: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Object, Deserialize)]
+pub struct UserPreferencesRequest {
+    /// Merged into the stored preferences, a null value removes the key. Known keys
+    /// (theme, date_format, page_size, email_notifications) are validated, app specific
+    /// keys must be namespaced e.g. myapp.sidebar and take any json.
+    pub preferences: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(ApiResponse)]
+pub enum UserPreferencesResponses {
+    #[oai(status = 200)]
+    Ok(Json<UserPreferencesResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserPreferencesResponses { Unauthorized });
+
+#[derive(ApiResponse)]
+pub enum UserPreferencesUpdateResponses {
+    #[oai(status = 200)]
+    Ok(Json<UserPreferencesResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<UnprocessableEntityResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserPreferencesUpdateResponses { Unauthorized });
+
+#[derive(ApiResponse)]
+pub enum UserPreferenceDeleteResponses {
+    #[oai(status = 204)]
+    NoContent,
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<NotFoundResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(UserPreferenceDeleteResponses {
+    Unauthorized,
+    NotFound
+});
@@ -0,0 +1,57 @@
+use crate::impl_from_app_error;
+use poem_openapi::{payload::Json, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+use super::common::{BadRequestResponse, InternalServerErrorResponse, UnauthorizedResponse};
+
+/// Current password, required to issue new recovery codes
+#[derive(Object, Deserialize, Serialize)]
+pub struct RecoveryCodesCreateRequest {
+    pub password: String,
+}
+
+/// Shown once, store them somewhere safe. Each code works a single time
+#[derive(Object, Deserialize, Serialize)]
+pub struct RecoveryCodesCreateResponse {
+    pub codes: Vec<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum RecoveryCodesCreateResponses {
+    #[oai(status = 201)]
+    Created(Json<RecoveryCodesCreateResponse>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<BadRequestResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(RecoveryCodesCreateResponses {
+    BadRequest,
+    Unauthorized
+});
+
+#[derive(Object, Deserialize, Serialize)]
+pub struct RecoveryCodesStatusResponse {
+    /// Unused codes left, generate a new set before running out
+    pub remaining: i64,
+}
+
+#[derive(ApiResponse)]
+pub enum RecoveryCodesStatusResponses {
+    #[oai(status = 200)]
+    Ok(Json<RecoveryCodesStatusResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<UnauthorizedResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<InternalServerErrorResponse>),
+}
+
+impl_from_app_error!(RecoveryCodesStatusResponses { Unauthorized });
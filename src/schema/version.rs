@@ -0,0 +1,33 @@
+use poem_openapi::{payload::Json, types::Example, ApiResponse, Object};
+use serde::{Deserialize, Serialize};
+
+/// What is running, embedded at compile time
+#[derive(Object, Deserialize, Serialize)]
+#[oai(example)]
+pub struct VersionResponse {
+    /// Crate version
+    pub version: String,
+    /// Git commit hash, unknown when built outside a git checkout
+    pub git_commit: String,
+    /// RFC 3339
+    pub build_timestamp: String,
+    /// Enabled cargo features
+    pub features: Vec<String>,
+}
+
+impl Example for VersionResponse {
+    fn example() -> Self {
+        Self {
+            version: "0.1.0".to_string(),
+            git_commit: "5cfafd3f0c1e2d4b6a8e9f7c3b2a1d0e9f8c7b6a".to_string(),
+            build_timestamp: "2025-04-01T05:00:00Z".to_string(),
+            features: vec!["tui".to_string()],
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+pub enum VersionResponses {
+    #[oai(status = 200)]
+    Ok(Json<VersionResponse>),
+}
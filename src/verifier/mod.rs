@@ -0,0 +1,440 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet},
+    Algorithm, DecodingKey, TokenData, Validation,
+};
+use poem::{
+    http::StatusCode, Endpoint, Error, FromRequest, IntoResponse, Middleware, Request, RequestBody,
+    Response, Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{core::security::Claims, schema::auth::IntrospectResponse};
+
+/// Introspection results and JWKS are reused for this long unless set otherwise
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Where tokens issued by core are checked, none of them need database access
+#[derive(Clone)]
+pub enum TokenSource {
+    /// POST {url} with `{"token": ...}`, e.g. https://core.example.com/api/auth/introspect,
+    /// authenticated with the api key of a service account. Sees logout and deactivation,
+    /// costs a request per token and cache ttl
+    Introspection { url: String, api_key: String },
+    /// Signature and expiry checked locally against the key set published on {url}, e.g.
+    /// https://core.example.com/.well-known/jwks.json of a core signing with RS256 or ES256
+    Jwks { url: String },
+    /// Signature and expiry checked locally with the JWT_SECRET of core, tokens signed
+    /// with a rotated key of JWT_KEYS_FILE fail
+    Secret(String),
+}
+
+/// Caller of the request, set by RequireToken
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct VerifiedToken {
+    /// User id
+    pub sub: String,
+    pub user_name: String,
+    /// unix timestamp
+    pub exp: i64,
+    pub scopes: Option<Vec<String>>,
+}
+
+impl VerifiedToken {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_some_and(|x| x.iter().any(|x| x == scope))
+    }
+}
+
+impl From<Claims> for VerifiedToken {
+    fn from(val: Claims) -> Self {
+        Self {
+            sub: val.id,
+            user_name: val.user_name,
+            exp: val.exp,
+            scopes: val.scopes,
+        }
+    }
+}
+
+/// Claims of a token signed by core. Refresh tokens are signed with the same key and carry
+/// the same fields, their type_key tells them apart, see ClaimsRefresh
+#[derive(Deserialize)]
+struct SignedClaims {
+    #[serde(flatten)]
+    claims: Claims,
+    type_key: Option<String>,
+}
+
+/// VerifiedToken of a valid access token, None for refresh tokens and invalid ones
+fn access_token(
+    res: jsonwebtoken::errors::Result<TokenData<SignedClaims>>,
+) -> Option<VerifiedToken> {
+    res.ok()
+        .map(|x| x.claims)
+        .filter(|x| x.type_key.is_none())
+        .map(|x| x.claims.into())
+}
+
+/// Algorithm a published key signs with, its alg or else the one its key type implies.
+/// Never taken from the token header, which whoever made the token controls
+fn jwk_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    if let Some(alg) = &jwk.common.key_algorithm {
+        return Algorithm::from_str(&alg.to_string()).ok();
+    }
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Some(Algorithm::RS256),
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => Some(Algorithm::ES256),
+            EllipticCurve::P384 => Some(Algorithm::ES384),
+            _ => None,
+        },
+        AlgorithmParameters::OctetKeyPair(_) => Some(Algorithm::EdDSA),
+        // secrets are never published, a symmetric key in a key set is a misconfiguration
+        AlgorithmParameters::OctetKey(_) => None,
+    }
+}
+
+impl<'a> FromRequest<'a> for VerifiedToken {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        req.extensions()
+            .get::<VerifiedToken>()
+            .cloned()
+            .ok_or_else(|| Error::from_status(StatusCode::UNAUTHORIZED))
+    }
+}
+
+/// Validates bearer tokens issued by core from another service
+pub struct TokenVerifier {
+    source: TokenSource,
+    cache_ttl: Duration,
+    http: reqwest::Client,
+    introspected: Mutex<HashMap<String, (Instant, Option<VerifiedToken>)>>,
+    jwks: Mutex<Option<(Instant, JwkSet)>>,
+}
+
+impl TokenVerifier {
+    pub fn new(source: TokenSource) -> Self {
+        Self {
+            source,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            http: reqwest::Client::new(),
+            introspected: Mutex::new(HashMap::new()),
+            jwks: Mutex::new(None),
+        }
+    }
+
+    /// Zero disables caching, revocation is then seen on the next request
+    pub fn cache_ttl(mut self, val: Duration) -> Self {
+        self.cache_ttl = val;
+        self
+    }
+
+    /// Ok(None) for invalid, expired or revoked tokens, Err when the source is unreachable
+    pub async fn verify(&self, token: &str) -> anyhow::Result<Option<VerifiedToken>> {
+        match &self.source {
+            TokenSource::Secret(secret) => {
+                let key = DecodingKey::from_secret(secret.as_bytes());
+                Ok(access_token(decode(token, &key, &Validation::default())))
+            }
+            TokenSource::Jwks { url } => self.verify_jwks(url, token).await,
+            TokenSource::Introspection { url, api_key } => {
+                self.introspect(url, api_key, token).await
+            }
+        }
+    }
+
+    async fn introspect(
+        &self,
+        url: &str,
+        api_key: &str,
+        token: &str,
+    ) -> anyhow::Result<Option<VerifiedToken>> {
+        let now = Instant::now();
+        if let Some((fetched_at, res)) = self.introspected.lock().unwrap().get(token) {
+            if now.duration_since(*fetched_at) < self.cache_ttl {
+                return Ok(res.clone());
+            }
+        }
+        let res: IntrospectResponse = self
+            .http
+            .post(url)
+            .bearer_auth(api_key)
+            .json(&serde_json::json!({ "token": token }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let res = match res {
+            IntrospectResponse {
+                active: true,
+                sub: Some(sub),
+                user_name: Some(user_name),
+                exp: Some(exp),
+                scopes,
+            } => Some(VerifiedToken {
+                sub,
+                user_name,
+                exp,
+                scopes,
+            }),
+            _ => None,
+        };
+        if !self.cache_ttl.is_zero() {
+            let mut introspected = self.introspected.lock().unwrap();
+            // Drop stale entries so one-off tokens don't pile up
+            if introspected.len() > 10_000 {
+                introspected.retain(|_, (x, _)| now.duration_since(*x) < self.cache_ttl);
+            }
+            introspected.insert(token.to_string(), (now, res.clone()));
+        }
+        Ok(res)
+    }
+
+    async fn verify_jwks(&self, url: &str, token: &str) -> anyhow::Result<Option<VerifiedToken>> {
+        let header = match decode_header(token) {
+            Ok(val) => val,
+            Err(_) => return Ok(None),
+        };
+        let cached = self
+            .jwks
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(x, _)| x.elapsed() < self.cache_ttl)
+            .map(|(_, x)| x.clone());
+        let jwks = match cached {
+            Some(val) => val,
+            None => {
+                let val: JwkSet = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                *self.jwks.lock().unwrap() = Some((Instant::now(), val.clone()));
+                val
+            }
+        };
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        };
+        let jwk = match jwk {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        // a token naming another algorithm than its key is forged or misrouted
+        let algorithm = match jwk_algorithm(jwk) {
+            Some(val) if val == header.alg => val,
+            _ => return Ok(None),
+        };
+        let key = match DecodingKey::from_jwk(jwk) {
+            Ok(val) => val,
+            Err(_) => return Ok(None),
+        };
+        Ok(access_token(decode(
+            token,
+            &key,
+            &Validation::new(algorithm),
+        )))
+    }
+}
+
+/// Rejects requests without a valid bearer token with 401 and hands the VerifiedToken
+/// to handlers, 503 when the token source is unreachable
+pub struct RequireToken {
+    verifier: Arc<TokenVerifier>,
+}
+
+impl RequireToken {
+    pub fn new(verifier: Arc<TokenVerifier>) -> Self {
+        RequireToken { verifier }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequireToken {
+    type Output = RequireTokenEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequireTokenEndpoint {
+            inner: ep,
+            verifier: self.verifier.clone(),
+        }
+    }
+}
+
+pub struct RequireTokenEndpoint<E> {
+    inner: E,
+    verifier: Arc<TokenVerifier>,
+}
+
+impl<E: Endpoint> Endpoint for RequireTokenEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        let token = req
+            .headers()
+            .get("authorization")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .map(|x| x.to_string());
+        let verified = match token {
+            Some(token) => match self.verifier.verify(&token).await {
+                Ok(val) => val,
+                Err(err) => {
+                    tracing::error!("token verification failed: {}", err);
+                    return Ok(Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .body("token verification unavailable"));
+                }
+            },
+            None => None,
+        };
+        match verified {
+            Some(val) => {
+                req.extensions_mut().insert(val);
+                match self.inner.call(req).await {
+                    Ok(val) => Ok(val.into_response()),
+                    Err(err) => Ok(err.into_response()),
+                }
+            }
+            None => Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body("unauthorized")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use jsonwebtoken::{jwk::Jwk, Algorithm};
+    use poem::{handler, http::StatusCode, test::TestClient, EndpointExt, Route};
+    use serde_json::json;
+
+    use crate::{
+        core::{
+            jwk::public_jwk,
+            security::{encode_refresh_token, encode_token, Claims, ClaimsRefresh, JwtKeys},
+            test_utils::{TEST_EC_PRIVATE_KEY, TEST_RSA_PRIVATE_KEY},
+        },
+        settings::get_config,
+        verifier::{jwk_algorithm, RequireToken, TokenSource, TokenVerifier, VerifiedToken},
+    };
+
+    #[handler]
+    fn index(token: VerifiedToken) -> String {
+        token.user_name
+    }
+
+    #[tokio::test]
+    async fn test_require_token() -> anyhow::Result<()> {
+        // Given
+        let config = get_config();
+        let token = encode_token(
+            &Claims::new(
+                "0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b",
+                "test_user",
+                config.clone(),
+            ),
+            &JwtKeys::from_secret(&config.jwt_secret),
+        )?;
+        let other = encode_token(
+            &Claims::new(
+                "0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b",
+                "test_user",
+                config.clone(),
+            ),
+            &JwtKeys::from_secret("another secret"),
+        )?;
+        let refresh = encode_refresh_token(
+            &ClaimsRefresh::new(
+                "0195f0a4-8a8e-7b4c-9b8e-2f6c1d3e4a5b",
+                "test_user",
+                config.clone(),
+            ),
+            &JwtKeys::from_secret(&config.jwt_secret),
+        )?;
+        let verifier = Arc::new(TokenVerifier::new(TokenSource::Secret(config.jwt_secret)));
+        let cli = TestClient::new(
+            Route::new()
+                .at("/", index)
+                .with(RequireToken::new(verifier)),
+        );
+
+        // When
+        let ok = cli
+            .get("/")
+            .header("authorization", format!("Bearer {}", token))
+            .send()
+            .await;
+        let wrong_key = cli
+            .get("/")
+            .header("authorization", format!("Bearer {}", other))
+            .send()
+            .await;
+        let refresh = cli
+            .get("/")
+            .header("authorization", format!("Bearer {}", refresh))
+            .send()
+            .await;
+        let missing = cli.get("/").send().await;
+
+        // Expect
+        ok.assert_status_is_ok();
+        ok.assert_text("test_user").await;
+        wrong_key.assert_status(StatusCode::UNAUTHORIZED);
+        refresh.assert_status(StatusCode::UNAUTHORIZED);
+        missing.assert_status(StatusCode::UNAUTHORIZED);
+
+        // When introspection endpoint is unreachable
+        let verifier = Arc::new(TokenVerifier::new(TokenSource::Introspection {
+            url: "http://127.0.0.1:9/api/auth/introspect".to_string(),
+            api_key: "sa_key".to_string(),
+        }));
+        let cli = TestClient::new(
+            Route::new()
+                .at("/", index)
+                .with(RequireToken::new(verifier)),
+        );
+        let resp = cli
+            .get("/")
+            .header("authorization", format!("Bearer {}", token))
+            .send()
+            .await;
+
+        // Expect
+        resp.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwk_algorithm() -> anyhow::Result<()> {
+        // Given keys published by core
+        let rsa = public_jwk(Algorithm::RS256, TEST_RSA_PRIVATE_KEY)?;
+        let mut ec = serde_json::to_value(public_jwk(Algorithm::ES256, TEST_EC_PRIVATE_KEY)?)?;
+        ec.as_object_mut().unwrap().remove("alg");
+        let ec: Jwk = serde_json::from_value(ec)?;
+        let secret: Jwk = serde_json::from_value(json!({"kty": "oct", "k": "c2VjcmV0"}))?;
+
+        // Expect the algorithm of the key, none for a symmetric one
+        assert_eq!(jwk_algorithm(&rsa), Some(Algorithm::RS256));
+        assert_eq!(jwk_algorithm(&ec), Some(Algorithm::ES256));
+        assert_eq!(jwk_algorithm(&secret), None);
+        Ok(())
+    }
+}